@@ -0,0 +1,8 @@
+-- cached aggregates for block groups, refreshed whenever an operation touches a block group.
+CREATE TABLE block_group_stats (
+  block_group_id INTEGER PRIMARY KEY NOT NULL,
+  node_count INTEGER NOT NULL,
+  edge_count INTEGER NOT NULL,
+  total_length INTEGER NOT NULL,
+  FOREIGN KEY(block_group_id) REFERENCES block_groups(id)
+) STRICT;
@@ -0,0 +1,15 @@
+-- annotations are stored against the path they were imported onto, in 0-based, end-exclusive path
+-- coordinates, so they can be projected onto the paths of other samples later.
+CREATE TABLE annotations (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  path_id INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  feature_type TEXT NOT NULL,
+  source TEXT NOT NULL,
+  start INTEGER NOT NULL,
+  end INTEGER NOT NULL,
+  strand TEXT NOT NULL,
+  attributes TEXT NOT NULL,
+  FOREIGN KEY(path_id) REFERENCES paths(id)
+) STRICT;
+CREATE UNIQUE INDEX annotation_uidx ON annotations(path_id, name, feature_type, source, start, end, strand, attributes);
@@ -0,0 +1 @@
+ALTER TABLE block_groups DROP COLUMN is_circular;
@@ -0,0 +1,3 @@
+-- circular block groups, such as plasmids, have a path that continues from its end back into its
+-- start, so coordinates on them may wrap around the origin.
+ALTER TABLE block_groups ADD COLUMN is_circular INTEGER NOT NULL DEFAULT 0;
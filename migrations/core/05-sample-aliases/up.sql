@@ -0,0 +1,8 @@
+-- samples found to duplicate another sample are kept, as operations can't remove them, but are
+-- recorded as an alias of the sample they duplicate.
+CREATE TABLE sample_aliases (
+  alias TEXT PRIMARY KEY NOT NULL,
+  sample_name TEXT NOT NULL,
+  FOREIGN KEY(alias) REFERENCES samples(name),
+  FOREIGN KEY(sample_name) REFERENCES samples(name)
+) STRICT;
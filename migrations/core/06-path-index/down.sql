@@ -0,0 +1 @@
+DROP TABLE path_index;
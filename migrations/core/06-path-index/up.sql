@@ -0,0 +1,18 @@
+-- the blocks of each path with their path and node coordinates, so coordinate lookups don't walk
+-- every edge of a path. Paths don't change once created, so rows are written with the path (or on
+-- first use for older paths) and removed with it.
+CREATE TABLE path_index (
+  path_id INTEGER NOT NULL,
+  block_id INTEGER NOT NULL,
+  node_id INTEGER NOT NULL,
+  path_start INTEGER NOT NULL,
+  path_end INTEGER NOT NULL,
+  sequence_start INTEGER NOT NULL,
+  sequence_end INTEGER NOT NULL,
+  strand TEXT NOT NULL,
+  PRIMARY KEY (path_id, block_id),
+  FOREIGN KEY(path_id) REFERENCES paths(id),
+  FOREIGN KEY(node_id) REFERENCES nodes(id)
+) STRICT;
+CREATE INDEX path_index_path_start_idx ON path_index(path_id, path_start);
+CREATE INDEX path_index_path_end_idx ON path_index(path_id, path_end);
@@ -0,0 +1,4 @@
+-- the remaining stats are recalculated on first access like the new columns were
+DELETE FROM block_group_stats;
+ALTER TABLE block_group_stats DROP COLUMN variant_site_count;
+ALTER TABLE block_group_stats DROP COLUMN path_count;
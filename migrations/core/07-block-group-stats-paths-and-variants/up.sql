@@ -0,0 +1,5 @@
+-- the cached stats are recalculated on first access, so clearing them fills in the new columns for
+-- existing block groups.
+DELETE FROM block_group_stats;
+ALTER TABLE block_group_stats ADD COLUMN path_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE block_group_stats ADD COLUMN variant_site_count INTEGER NOT NULL DEFAULT 0;
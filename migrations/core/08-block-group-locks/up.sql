@@ -0,0 +1,7 @@
+-- advisory write locks held by applications updating a block group.
+CREATE TABLE block_group_locks (
+  block_group_id INTEGER PRIMARY KEY NOT NULL,
+  owner TEXT NOT NULL,
+  acquired_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY(block_group_id) REFERENCES block_groups(id)
+) STRICT;
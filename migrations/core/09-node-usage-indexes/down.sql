@@ -0,0 +1,3 @@
+DROP INDEX path_index_node_idx;
+DROP INDEX block_group_edges_edge_idx;
+DROP INDEX edges_target_node_idx;
@@ -0,0 +1,5 @@
+-- lookups from a node to the graphs and paths it is part of. Edges are already indexed by their
+-- source node through edge_uidx.
+CREATE INDEX edges_target_node_idx ON edges(target_node_id);
+CREATE INDEX block_group_edges_edge_idx ON block_group_edges(edge_id);
+CREATE INDEX path_index_node_idx ON path_index(node_id);
@@ -0,0 +1,2 @@
+-- sequences already kept in the object store stay there, as their rows still name their store
+ALTER TABLE collections DROP COLUMN sequence_store;
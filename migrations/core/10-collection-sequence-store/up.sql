@@ -0,0 +1,3 @@
+-- where new sequences of a collection keep their bases: in the sequences table (database) or
+-- compressed in the object store of the .gen directory (objects)
+ALTER TABLE collections ADD COLUMN sequence_store TEXT NOT NULL DEFAULT 'database';
@@ -0,0 +1,2 @@
+ALTER TABLE sequences DROP COLUMN packed_exceptions;
+ALTER TABLE sequences DROP COLUMN packed_bases;
@@ -0,0 +1,4 @@
+-- DNA packed 2 bits a base, along with the characters and soft masking the packing can't hold, for
+-- sequences of collections using the packed sequence store
+ALTER TABLE sequences ADD COLUMN packed_bases BLOB;
+ALTER TABLE sequences ADD COLUMN packed_exceptions BLOB;
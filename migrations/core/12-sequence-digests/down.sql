@@ -0,0 +1 @@
+DROP TABLE sequence_digests;
@@ -0,0 +1,9 @@
+-- checksums of imported sequences in the forms the GA4GH refget protocol looks sequences up by.
+CREATE TABLE sequence_digests (
+  sequence_hash TEXT PRIMARY KEY NOT NULL,
+  md5 TEXT NOT NULL,
+  trunc512 TEXT NOT NULL,
+  FOREIGN KEY(sequence_hash) REFERENCES sequences(hash)
+) STRICT;
+CREATE INDEX sequence_digests_md5 ON sequence_digests(md5);
+CREATE INDEX sequence_digests_trunc512 ON sequence_digests(trunc512);
@@ -0,0 +1,2 @@
+DROP TABLE edge_attributes;
+DROP TABLE node_attributes;
@@ -0,0 +1,16 @@
+-- typed key/value metadata on nodes and edges, such as the file and record they were imported
+-- from. Values keep the type they were stored with: integer, real or text.
+CREATE TABLE node_attributes (
+  node_id INTEGER NOT NULL,
+  key TEXT NOT NULL,
+  value ANY NOT NULL,
+  PRIMARY KEY (node_id, key),
+  FOREIGN KEY(node_id) REFERENCES nodes(id)
+) STRICT;
+CREATE TABLE edge_attributes (
+  edge_id INTEGER NOT NULL,
+  key TEXT NOT NULL,
+  value ANY NOT NULL,
+  PRIMARY KEY (edge_id, key),
+  FOREIGN KEY(edge_id) REFERENCES edges(id)
+) STRICT;
@@ -0,0 +1,13 @@
+-- paths chosen as the current path of their block group. Without a choice the newest path is
+-- current, and a choice only holds while newest_path_id is still the newest path, so a path added
+-- by a later update becomes current as before. Choices are only ever added, never changed.
+CREATE TABLE current_path_choices (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  block_group_id INTEGER NOT NULL,
+  path_id INTEGER NOT NULL,
+  newest_path_id INTEGER NOT NULL,
+  FOREIGN KEY(block_group_id) REFERENCES block_groups(id),
+  FOREIGN KEY(path_id) REFERENCES paths(id),
+  FOREIGN KEY(newest_path_id) REFERENCES paths(id)
+) STRICT;
+CREATE INDEX current_path_choices_block_group_idx ON current_path_choices(block_group_id);
@@ -0,0 +1,13 @@
+-- read depth imported from bedGraph and BED files, as runs of bases of a node with the same depth
+-- in 0-based, end-exclusive coordinates of the node's sequence. Coverage is kept on nodes rather
+-- than paths so samples derived from the one it was imported onto have it wherever they share
+-- sequence with it.
+CREATE TABLE node_coverage (
+  track TEXT NOT NULL,
+  node_id INTEGER NOT NULL,
+  start INTEGER NOT NULL,
+  end INTEGER NOT NULL,
+  depth REAL NOT NULL,
+  PRIMARY KEY (track, node_id, start),
+  FOREIGN KEY(node_id) REFERENCES nodes(id)
+) STRICT;
@@ -0,0 +1,6 @@
+ALTER TABLE defaults DROP COLUMN email;
+ALTER TABLE defaults DROP COLUMN author;
+ALTER TABLE operation DROP COLUMN message;
+ALTER TABLE operation DROP COLUMN timestamp;
+ALTER TABLE operation DROP COLUMN email;
+ALTER TABLE operation DROP COLUMN author;
@@ -0,0 +1,7 @@
+ALTER TABLE operation ADD COLUMN author TEXT;
+ALTER TABLE operation ADD COLUMN email TEXT;
+ALTER TABLE operation ADD COLUMN timestamp TEXT;
+ALTER TABLE operation ADD COLUMN message TEXT;
+
+ALTER TABLE defaults ADD COLUMN author TEXT;
+ALTER TABLE defaults ADD COLUMN email TEXT;
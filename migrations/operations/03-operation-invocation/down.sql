@@ -0,0 +1,3 @@
+ALTER TABLE operation DROP COLUMN working_directory;
+ALTER TABLE operation DROP COLUMN gen_version;
+ALTER TABLE operation DROP COLUMN command;
@@ -0,0 +1,4 @@
+-- command is the json encoded argv of the process that created the operation
+ALTER TABLE operation ADD COLUMN command TEXT;
+ALTER TABLE operation ADD COLUMN gen_version TEXT;
+ALTER TABLE operation ADD COLUMN working_directory TEXT;
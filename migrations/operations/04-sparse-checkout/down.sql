@@ -0,0 +1,2 @@
+DROP TABLE sparse_pending_operations;
+DROP TABLE sparse_collections;
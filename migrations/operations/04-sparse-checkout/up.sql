@@ -0,0 +1,17 @@
+-- the collections materialized by a sparse checkout, no rows means every collection is
+CREATE TABLE sparse_collections (
+  id INTEGER PRIMARY KEY NOT NULL,
+  db_uuid TEXT NOT NULL,
+  collection_name TEXT NOT NULL
+) STRICT;
+CREATE UNIQUE INDEX sparse_collections_uidx ON sparse_collections(db_uuid, collection_name);
+
+-- operations applied during a sparse checkout whose rows for a collection were not materialized
+CREATE TABLE sparse_pending_operations (
+  id INTEGER PRIMARY KEY NOT NULL,
+  db_uuid TEXT NOT NULL,
+  collection_name TEXT NOT NULL,
+  operation_hash TEXT NOT NULL,
+  FOREIGN KEY(operation_hash) REFERENCES operation(hash)
+) STRICT;
+CREATE UNIQUE INDEX sparse_pending_operations_uidx ON sparse_pending_operations(db_uuid, collection_name, operation_hash);
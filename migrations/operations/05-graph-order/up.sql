@@ -0,0 +1,3 @@
+-- the graph names of a custom order for listing and exporting graphs, one per line. Graphs are
+-- naturally sorted when it's not set.
+ALTER TABLE defaults ADD COLUMN graph_order TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE gfa_export_segment;
+DROP TABLE gfa_export;
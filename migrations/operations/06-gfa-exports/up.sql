@@ -0,0 +1,21 @@
+-- GFA exports and the node coordinates of their segments, so segment IDs in alignments against an
+-- exported GFA can be resolved back to nodes when the alignments are imported.
+CREATE TABLE gfa_export (
+  id INTEGER PRIMARY KEY NOT NULL,
+  db_uuid TEXT NOT NULL,
+  operation_hash TEXT,
+  collection_name TEXT NOT NULL,
+  file_path TEXT NOT NULL,
+  FOREIGN KEY(operation_hash) REFERENCES operation(hash)
+) STRICT;
+
+CREATE TABLE gfa_export_segment (
+  gfa_export_id INTEGER NOT NULL,
+  segment_id TEXT NOT NULL,
+  node_id INTEGER NOT NULL,
+  sequence_start INTEGER NOT NULL,
+  sequence_end INTEGER NOT NULL,
+  PRIMARY KEY (gfa_export_id, segment_id),
+  FOREIGN KEY(gfa_export_id) REFERENCES gfa_export(id)
+) STRICT;
+CREATE INDEX gfa_export_segment_id_idx ON gfa_export_segment(segment_id);
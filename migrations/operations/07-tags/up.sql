@@ -0,0 +1,11 @@
+-- names given to an operation or a sample of a database. Tags of operations can be used in place of
+-- the operation's hash.
+CREATE TABLE tag (
+  db_uuid TEXT NOT NULL,
+  name TEXT NOT NULL,
+  operation_hash TEXT,
+  sample_name TEXT,
+  PRIMARY KEY (db_uuid, name),
+  FOREIGN KEY(operation_hash) REFERENCES operation(hash),
+  CHECK ((operation_hash IS NULL) != (sample_name IS NULL))
+) STRICT;
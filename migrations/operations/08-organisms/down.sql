@@ -0,0 +1,2 @@
+ALTER TABLE defaults DROP COLUMN organism;
+ALTER TABLE defaults DROP COLUMN organisms;
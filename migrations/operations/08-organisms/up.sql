@@ -0,0 +1,4 @@
+-- user defined organism profiles, as the contents of a TOML file, and the organism commands use
+-- when none is given. The built in profiles are used when these aren't set.
+ALTER TABLE defaults ADD COLUMN organisms TEXT;
+ALTER TABLE defaults ADD COLUMN organism TEXT;
@@ -0,0 +1 @@
+ALTER TABLE defaults DROP COLUMN tuning_profile;
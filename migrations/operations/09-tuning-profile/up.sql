@@ -0,0 +1,2 @@
+-- the connection settings databases are opened with, bulk or safe. Unset means safe.
+ALTER TABLE defaults ADD COLUMN tuning_profile TEXT;
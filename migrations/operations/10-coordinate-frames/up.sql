@@ -0,0 +1,11 @@
+-- named coordinate systems of a database, a sample and optionally one of its paths, whose
+-- coordinates are offset from those of the path. Commands accept a frame in place of the sample
+-- and path options.
+CREATE TABLE coordinate_frame (
+  db_uuid TEXT NOT NULL,
+  name TEXT NOT NULL,
+  sample_name TEXT,
+  path_name TEXT,
+  offset INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (db_uuid, name)
+) STRICT;
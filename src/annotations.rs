@@ -1 +1,3 @@
+pub mod cds;
+pub mod coverage;
 pub mod gff;
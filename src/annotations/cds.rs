@@ -0,0 +1,481 @@
+use crate::annotations::gff::AnnotationError;
+use crate::models::block_group::BlockGroup;
+use crate::models::path::{revcomp, Annotation, Path};
+use crate::models::sample::Sample;
+use crate::models::strand::Strand;
+use crate::organism::OrganismProfile;
+use crate::range::{Range, RangeMapping};
+use intervaltree::IntervalTree;
+use noodles::gff;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+
+// A coding sequence of a GFF file: the CDS records that share an ID, or a parent if they have no
+// ID, which are the exons of one protein. The phase is that of the record at the 5' end.
+#[derive(Clone, Debug, PartialEq)]
+struct CodingSequence {
+    name: String,
+    path_name: String,
+    phase: usize,
+    segments: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AminoAcidChange {
+    // 1-based, like protein positions are usually given
+    pub position: usize,
+    // None past the end of the shorter protein
+    pub reference: Option<char>,
+    pub alternate: Option<char>,
+}
+
+impl fmt::Display for AminoAcidChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.reference.unwrap_or('-'),
+            self.position,
+            self.alternate.unwrap_or('-')
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CdsTranslation {
+    pub name: String,
+    pub reference_protein: String,
+    // None if the coding sequence couldn't be placed on the sample's path
+    pub protein: Option<String>,
+    pub coding_sequence_changed: bool,
+    // the sample's coding sequence is not in the reference's frame any more
+    pub frameshift: bool,
+    pub changes: Vec<AminoAcidChange>,
+}
+
+impl CdsTranslation {
+    pub fn is_silent(&self) -> bool {
+        self.protein.is_some() && self.changes.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.protein.is_none() {
+            return format!("{}: not found on the sample", self.name);
+        }
+        if self.changes.is_empty() {
+            return if self.coding_sequence_changed {
+                format!(
+                    "{}: silent, the coding sequence changed but not the protein",
+                    self.name
+                )
+            } else {
+                format!("{}: unchanged", self.name)
+            };
+        }
+        format!(
+            "{name}: {count} amino acid change{plural}{frameshift}: {changes}",
+            name = self.name,
+            count = self.changes.len(),
+            plural = if self.changes.len() == 1 { "" } else { "s" },
+            frameshift = if self.frameshift { " (frameshift)" } else { "" },
+            changes = self
+                .changes
+                .iter()
+                .map(|change| change.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+fn read_coding_sequences(gff_path: &str) -> Result<Vec<CodingSequence>, AnnotationError> {
+    let mut reader = File::open(gff_path)
+        .map(BufReader::new)
+        .map(gff::io::Reader::new)?;
+    let mut coding_sequences: Vec<CodingSequence> = vec![];
+    for result in reader.records() {
+        let record = result?;
+        if record.ty() != "CDS" {
+            continue;
+        }
+        let attributes = record.attributes();
+        let name = ["ID", "Parent", "Name"]
+            .iter()
+            .find_map(|tag| attributes.get(*tag).and_then(|value| value.as_string()))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("CDS {}-{}", record.start(), record.end()));
+        let strand = record
+            .strand()
+            .to_string()
+            .parse()
+            .unwrap_or(Strand::Unknown);
+        let phase = match record.phase() {
+            Some(gff::record::Phase::One) => 1,
+            Some(gff::record::Phase::Two) => 2,
+            _ => 0,
+        };
+        // GFF positions are 1-based and inclusive, paths are 0-based and end-exclusive
+        let segment = Annotation {
+            name: name.clone(),
+            start: record.start().get() as i64 - 1,
+            end: record.end().get() as i64,
+            strand,
+        };
+        let path_name = record.reference_sequence_name().to_string();
+        match coding_sequences
+            .iter_mut()
+            .find(|coding_sequence| coding_sequence.name == name)
+        {
+            Some(coding_sequence) => {
+                // the 5' record is the last on the reverse strand
+                let five_prime = if strand == Strand::Reverse {
+                    segment.start > coding_sequence.segments[0].start
+                } else {
+                    segment.start < coding_sequence.segments[0].start
+                };
+                if five_prime {
+                    coding_sequence.phase = phase;
+                    coding_sequence.segments.insert(0, segment);
+                } else {
+                    coding_sequence.segments.push(segment);
+                }
+            }
+            None => coding_sequences.push(CodingSequence {
+                name,
+                path_name,
+                phase,
+                segments: vec![segment],
+            }),
+        }
+    }
+    for coding_sequence in coding_sequences.iter_mut() {
+        let reverse = coding_sequence.segments[0].strand == Strand::Reverse;
+        coding_sequence
+            .segments
+            .sort_by_key(|segment| segment.start);
+        if reverse {
+            coding_sequence.segments.reverse();
+        }
+    }
+    Ok(coding_sequences)
+}
+
+// Joins the exons of a coding sequence in the order they are transcribed, reading those on the
+// reverse strand as their reverse complement, and drops the bases before the first codon.
+fn splice(conn: &Connection, path: &Path, segments: &[Annotation], phase: usize) -> String {
+    let mut sequence = String::new();
+    for segment in segments {
+        let bases = path.subsequence(
+            conn,
+            &Range {
+                start: segment.start,
+                end: segment.end,
+            },
+        );
+        if segment.strand == Strand::Reverse {
+            sequence.push_str(&revcomp(&bases));
+        } else {
+            sequence.push_str(&bases);
+        }
+    }
+    sequence.split_off(phase.min(sequence.len()))
+}
+
+// Places a segment of a coding sequence on the sample's path. Propagating an annotation leaves out
+// an edit at either of its ends, as it only keeps the bases both paths share, so the ends are placed
+// next to the bases on either side of the segment instead when those are shared.
+fn place_segment(
+    segment: &Annotation,
+    mapping_tree: &IntervalTree<i64, RangeMapping>,
+    reference_length: i64,
+    sequence_length: i64,
+    is_circular: bool,
+) -> Option<Annotation> {
+    let propagate = |start: i64, end: i64| {
+        Path::propagate_annotation(
+            Annotation {
+                start,
+                end,
+                ..segment.clone()
+            },
+            mapping_tree,
+            sequence_length,
+            is_circular,
+        )
+    };
+    let placed = propagate(segment.start, segment.end)?;
+    if placed.strand != segment.strand {
+        return Some(placed);
+    }
+    let shared_base = |position: i64| {
+        (0..reference_length)
+            .contains(&position)
+            .then(|| propagate(position, position + 1))
+            .flatten()
+            .filter(|base| base.end - base.start == 1 && base.strand == segment.strand)
+    };
+    let start = shared_base(segment.start - 1).map_or(placed.start, |base| base.end);
+    let end = shared_base(segment.end).map_or(placed.end, |base| base.start);
+    Some(if start <= end {
+        Annotation {
+            start,
+            end,
+            ..placed
+        }
+    } else {
+        placed
+    })
+}
+
+fn amino_acid_changes(reference: &str, protein: &str) -> Vec<AminoAcidChange> {
+    let reference = reference.chars().collect::<Vec<_>>();
+    let protein = protein.chars().collect::<Vec<_>>();
+    (0..reference.len().max(protein.len()))
+        .filter_map(|index| {
+            let reference = reference.get(index).copied();
+            let alternate = protein.get(index).copied();
+            (reference != alternate).then_some(AminoAcidChange {
+                position: index + 1,
+                reference,
+                alternate,
+            })
+        })
+        .collect()
+}
+
+/*
+   Translates the CDS features of a GFF file, given on the paths of the reference sample, on both the
+   reference and another sample. Features are placed on the sample's paths the way annotations are
+   propagated, so an edit within a feature is part of the sample's coding sequence. The proteins are
+   compared position by position, which is enough to tell silent edits from ones that change the
+   protein, but shows a frameshift as a change of every amino acid after it.
+*/
+pub fn translate_cds(
+    conn: &Connection,
+    collection_name: &str,
+    reference_sample_name: Option<&str>,
+    sample_name: &str,
+    gff_path: &str,
+    profile: &OrganismProfile,
+) -> Result<Vec<CdsTranslation>, AnnotationError> {
+    let current_paths = |sample_name: Option<&str>| {
+        Sample::get_block_groups(conn, collection_name, sample_name)
+            .iter()
+            .map(|bg| (bg.name.clone(), BlockGroup::get_current_path(conn, bg.id)))
+            .collect::<HashMap<String, Path>>()
+    };
+    let reference_paths = current_paths(reference_sample_name);
+    let sample_paths = current_paths(Some(sample_name));
+    let mut mapping_trees: HashMap<String, IntervalTree<i64, RangeMapping>> = HashMap::new();
+
+    let mut translations = vec![];
+    for coding_sequence in read_coding_sequences(gff_path)? {
+        let path_name = &coding_sequence.path_name;
+        let (Some(reference_path), Some(sample_path)) =
+            (reference_paths.get(path_name), sample_paths.get(path_name))
+        else {
+            return Err(AnnotationError::UnknownPath(path_name.clone()));
+        };
+        let reference_sequence = splice(
+            conn,
+            reference_path,
+            &coding_sequence.segments,
+            coding_sequence.phase,
+        );
+
+        let mapping_tree = mapping_trees
+            .entry(path_name.clone())
+            .or_insert_with(|| reference_path.get_mapping_tree(conn, sample_path));
+        let sequence_length = sample_path.length(conn);
+        let is_circular = sample_path.is_circular(conn);
+        let reference_length = reference_path.length(conn);
+        let sample_segments = coding_sequence
+            .segments
+            .iter()
+            .map(|segment| {
+                place_segment(
+                    segment,
+                    mapping_tree,
+                    reference_length,
+                    sequence_length,
+                    is_circular,
+                )
+            })
+            .collect::<Option<Vec<_>>>();
+        let sample_sequence = sample_segments
+            .map(|segments| splice(conn, sample_path, &segments, coding_sequence.phase));
+
+        let reference_protein = profile.translate(&reference_sequence);
+        let protein = sample_sequence
+            .as_ref()
+            .map(|sequence| profile.translate(sequence));
+        translations.push(CdsTranslation {
+            name: coding_sequence.name,
+            changes: protein
+                .as_ref()
+                .map(|protein| amino_acid_changes(&reference_protein, protein))
+                .unwrap_or_default(),
+            coding_sequence_changed: sample_sequence.as_ref().is_some_and(|sequence| {
+                sequence.to_uppercase() != reference_sequence.to_uppercase()
+            }),
+            frameshift: sample_sequence
+                .as_ref()
+                .is_some_and(|sequence| sequence.len() % 3 != reference_sequence.len() % 3),
+            reference_protein,
+            protein,
+        });
+    }
+    Ok(translations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_translate_cds() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        // ATCGATCGATCGATCGATCGGGAACACACAGAGA, with a forward CDS split over 1-6 and 9-14
+        // (ATCGAT + ATCGAT: IDID) and a reverse one on 2-10 read from its second base (the
+        // reverse complement of TCGATCGAT is ATCGATCGA, so TCG ATC GA: SI)
+        let dir = tempdir().unwrap();
+        let gff_path = dir.path().join("cds.gff");
+        fs::write(
+            &gff_path,
+            "##gff-version 3\n\
+             m123\ttest\tCDS\t1\t6\t.\t+\t0\tID=forward\n\
+             m123\ttest\tCDS\t9\t14\t.\t+\t0\tID=forward\n\
+             m123\ttest\tCDS\t2\t10\t.\t-\t1\tID=reverse\n\
+             m123\ttest\tgene\t1\t15\t.\t+\t.\tID=gene\n",
+        )
+        .unwrap();
+        let profile = OrganismProfile::standard();
+
+        // a silent edit of the first codon's last base, ATC to ATT, and a missense one of the
+        // second exon's first codon, ATC to GTC. Both are in the reverse CDS too, the first as a
+        // silent GA to AA in its last, partial codon and the second as TCG to CCG.
+        for (sample, start, end, bases) in [("silent", 2, 3, "T"), ("missense", 8, 9, "G")] {
+            let insert_path = dir.path().join(format!("{sample}.fa"));
+            fs::write(&insert_path, format!(">{sample}\n{bases}\n")).unwrap();
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                sample,
+                "m123",
+                start,
+                end,
+                insert_path.to_str().unwrap(),
+            )
+            .unwrap();
+        }
+
+        let translations = translate_cds(
+            conn,
+            "test",
+            None,
+            "silent",
+            gff_path.to_str().unwrap(),
+            &profile,
+        )
+        .unwrap();
+        assert_eq!(translations.len(), 2);
+        assert_eq!(translations[0].name, "forward");
+        assert_eq!(translations[0].reference_protein, "IDID");
+        assert_eq!(translations[0].protein.as_deref(), Some("IDID"));
+        assert!(translations[0].coding_sequence_changed);
+        assert!(translations[0].is_silent());
+        assert_eq!(
+            translations[0].describe(),
+            "forward: silent, the coding sequence changed but not the protein"
+        );
+        assert_eq!(translations[1].name, "reverse");
+        assert_eq!(translations[1].reference_protein, "SI");
+        assert!(translations[1].is_silent());
+
+        let translations = translate_cds(
+            conn,
+            "test",
+            None,
+            "missense",
+            gff_path.to_str().unwrap(),
+            &profile,
+        )
+        .unwrap();
+        assert_eq!(translations[0].protein.as_deref(), Some("IDVD"));
+        assert_eq!(
+            translations[0].changes,
+            vec![AminoAcidChange {
+                position: 3,
+                reference: Some('I'),
+                alternate: Some('V'),
+            }]
+        );
+        assert_eq!(
+            translations[0].describe(),
+            "forward: 1 amino acid change: I3V"
+        );
+        assert_eq!(translations[1].protein.as_deref(), Some("PI"));
+        assert_eq!(
+            translations[1].describe(),
+            "reverse: 1 amino acid change: S1P"
+        );
+
+        // replacing 2 bases with 1 shifts the frame of the forward CDS
+        let insert_path = dir.path().join("shifted.fa");
+        fs::write(&insert_path, ">shifted\nA\n").unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "shifted",
+            "m123",
+            10,
+            12,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let translations = translate_cds(
+            conn,
+            "test",
+            None,
+            "shifted",
+            gff_path.to_str().unwrap(),
+            &profile,
+        )
+        .unwrap();
+        assert!(translations[0].frameshift);
+        assert_eq!(translations[0].protein.as_deref(), Some("IDI"));
+        assert_eq!(
+            translations[0].describe(),
+            "forward: 1 amino acid change (frameshift): D4-"
+        );
+    }
+}
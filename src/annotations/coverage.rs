@@ -0,0 +1,466 @@
+use crate::models::block_group::{BlockGroup, NodeIntervalBlock};
+use crate::models::file_types::FileTypes;
+use crate::models::node_coverage::NodeCoverage;
+use crate::models::operations::{Operation, OperationInfo};
+use crate::models::path_index::PathIndex;
+use crate::models::sample::Sample;
+use crate::models::strand::Strand;
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use crate::range::{Range, Region, RegionError};
+use intervaltree::IntervalTree;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CoverageError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("{0}")]
+    Region(#[from] RegionError),
+    #[error("No graph named {0} exists for this sample")]
+    UnknownGraph(String),
+    #[error("A coverage track named {0} already exists")]
+    TrackExists(String),
+    #[error("No coverage track named {0}")]
+    UnknownTrack(String),
+    #[error("No coverage has been imported")]
+    NoTracks,
+    #[error("There are several coverage tracks ({}), pick one with --track", .0.join(", "))]
+    AmbiguousTrack(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CoverageFormat {
+    BedGraph,
+    Bed,
+}
+
+impl CoverageFormat {
+    // The column with the depth, the value of a bedGraph line or the score of a BED line.
+    fn depth_column(&self) -> usize {
+        match self {
+            CoverageFormat::BedGraph => 3,
+            CoverageFormat::Bed => 4,
+        }
+    }
+}
+
+// A run of bases of a path with the same depth, in 0-based, end-exclusive path coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageRun {
+    pub start: i64,
+    pub end: i64,
+    pub depth: f64,
+}
+
+// The range of a block's node sequence under a range of the path within the block. Blocks on the
+// reverse strand are read from the end of their sequence, so the range is mirrored within the block.
+fn sequence_range(block: &NodeIntervalBlock, path_range: &Range) -> Range {
+    if block.strand == Strand::Reverse {
+        Range {
+            start: block.sequence_end - (path_range.end - block.start),
+            end: block.sequence_end - (path_range.start - block.start),
+        }
+    } else {
+        Range {
+            start: block.sequence_start + (path_range.start - block.start),
+            end: block.sequence_start + (path_range.end - block.start),
+        }
+    }
+}
+
+// The inverse of sequence_range, the range of the path a range of the block's node sequence is at.
+fn path_range(block: &NodeIntervalBlock, sequence_range: &Range) -> Range {
+    if block.strand == Strand::Reverse {
+        Range {
+            start: block.start + (block.sequence_end - sequence_range.end),
+            end: block.start + (block.sequence_end - sequence_range.start),
+        }
+    } else {
+        Range {
+            start: block.start + (sequence_range.start - block.sequence_start),
+            end: block.start + (sequence_range.end - block.sequence_start),
+        }
+    }
+}
+
+// Orders the runs of each node, joining adjacent runs with the same depth. Where runs overlap, such
+// as on a node the path passes more than once, the run starting first keeps its depth.
+fn merge_runs(mut runs: Vec<NodeCoverage>) -> Vec<NodeCoverage> {
+    runs.sort_by_key(|run| (run.node_id, run.start));
+    let mut merged: Vec<NodeCoverage> = vec![];
+    for mut run in runs {
+        if let Some(last) = merged.last_mut().filter(|last| last.node_id == run.node_id) {
+            run.start = run.start.max(last.end);
+            if run.start >= run.end {
+                continue;
+            }
+            if run.start == last.end && run.depth == last.depth {
+                last.end = run.end;
+                continue;
+            }
+        }
+        merged.push(run);
+    }
+    merged
+}
+
+// Imports the depth of a bedGraph or BED file as a coverage track of the current paths of a
+// sample's graphs, which the file's chromosomes name. The depth is stored against the nodes of the
+// paths rather than the paths, so samples derived from this one have it wherever they keep its
+// sequence.
+pub fn import_coverage(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    track: &str,
+    file_path: &str,
+    format: CoverageFormat,
+) -> Result<Operation, CoverageError> {
+    if NodeCoverage::tracks(conn).iter().any(|name| name == track) {
+        return Err(CoverageError::TrackExists(track.to_string()));
+    }
+    let mut session = start_operation(conn);
+    let reader = BufReader::new(File::open(file_path)?);
+
+    let block_groups_by_name = Sample::get_block_groups(conn, collection_name, sample_name)
+        .into_iter()
+        .map(|block_group| (block_group.name.clone(), block_group))
+        .collect::<HashMap<_, _>>();
+    let mut block_trees_by_name: HashMap<String, IntervalTree<i64, NodeIntervalBlock>> =
+        HashMap::new();
+    let depth_column = format.depth_column();
+
+    let mut runs = vec![];
+    let mut summary: HashMap<String, i64> = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() <= depth_column {
+            return Err(invalid(format!(
+                "Coverage line has fewer than {columns} columns: {line}",
+                columns = depth_column + 1
+            ))
+            .into());
+        }
+        let graph_name = fields[0];
+        let parse_coordinate = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| invalid(format!("Invalid coordinate {value} in line: {line}")))
+        };
+        let start = parse_coordinate(fields[1])?;
+        let end = parse_coordinate(fields[2])?;
+        if start < 0 || end <= start {
+            return Err(invalid(format!("Invalid interval in line: {line}")).into());
+        }
+        let depth = fields[depth_column]
+            .parse::<f64>()
+            .map_err(|_| invalid(format!("Invalid depth in line: {line}")))?;
+
+        let block_group = block_groups_by_name
+            .get(graph_name)
+            .ok_or_else(|| CoverageError::UnknownGraph(graph_name.to_string()))?;
+        let block_tree = block_trees_by_name
+            .entry(graph_name.to_string())
+            .or_insert_with(|| {
+                BlockGroup::try_get_current_path(conn, block_group.id)
+                    .map(|path| PathIndex::blocks(conn, path.id))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|block| (block.start..block.end, block))
+                    .collect()
+            });
+        for element in block_tree.query(start..end) {
+            let block = &element.value;
+            let range = sequence_range(
+                block,
+                &Range {
+                    start: start.max(block.start),
+                    end: end.min(block.end),
+                },
+            );
+            runs.push(NodeCoverage {
+                track: track.to_string(),
+                node_id: block.node_id,
+                start: range.start,
+                end: range.end,
+                depth,
+            });
+        }
+        *summary.entry(graph_name.to_string()).or_default() += 1;
+    }
+    NodeCoverage::bulk_create(conn, &merge_runs(runs));
+
+    let mut summary_str = "".to_string();
+    for (graph_name, count) in summary.iter().sorted() {
+        summary_str.push_str(&format!(" {graph_name}: {count} intervals.\n"));
+    }
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: file_path.to_string(),
+            file_type: FileTypes::BedGraph,
+            description: "coverage_import".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+// The track coverage is read from, the one given or the only one there is.
+pub fn resolve_track(conn: &Connection, track: Option<&str>) -> Result<String, CoverageError> {
+    let tracks = NodeCoverage::tracks(conn);
+    match track {
+        Some(track) if tracks.iter().any(|name| name == track) => Ok(track.to_string()),
+        Some(track) => Err(CoverageError::UnknownTrack(track.to_string())),
+        None => match tracks.len() {
+            0 => Err(CoverageError::NoTracks),
+            1 => Ok(tracks[0].clone()),
+            _ => Err(CoverageError::AmbiguousTrack(tracks)),
+        },
+    }
+}
+
+fn path_coverage(conn: &Connection, path_id: i64, track: &str, range: &Range) -> Vec<CoverageRun> {
+    let blocks = PathIndex::blocks_in_range(conn, path_id, range.start, range.end);
+    let node_ids = blocks
+        .iter()
+        .map(|block| block.node_id)
+        .unique()
+        .collect::<Vec<i64>>();
+    let mut coverage_by_node_id: HashMap<i64, Vec<NodeCoverage>> = HashMap::new();
+    for run in NodeCoverage::query_for_nodes(conn, track, &node_ids) {
+        coverage_by_node_id
+            .entry(run.node_id)
+            .or_default()
+            .push(run);
+    }
+
+    let mut runs = vec![];
+    for block in blocks.iter() {
+        let block_range = Range {
+            start: range.start.max(block.start),
+            end: range.end.min(block.end),
+        };
+        let block_sequence_range = sequence_range(block, &block_range);
+        let block_runs = coverage_by_node_id
+            .get(&block.node_id)
+            .into_iter()
+            .flatten()
+            .filter(|run| {
+                run.start < block_sequence_range.end && run.end > block_sequence_range.start
+            })
+            .map(|run| {
+                let run_range = path_range(
+                    block,
+                    &Range {
+                        start: run.start.max(block_sequence_range.start),
+                        end: run.end.min(block_sequence_range.end),
+                    },
+                );
+                CoverageRun {
+                    start: run_range.start,
+                    end: run_range.end,
+                    depth: run.depth,
+                }
+            })
+            .sorted_by_key(|run| run.start);
+        // bases of the block without coverage have a depth of 0
+        let mut position = block_range.start;
+        for run in block_runs {
+            if run.start > position {
+                runs.push(CoverageRun {
+                    start: position,
+                    end: run.start,
+                    depth: 0.0,
+                });
+            }
+            position = run.end;
+            runs.push(run);
+        }
+        if position < block_range.end {
+            runs.push(CoverageRun {
+                start: position,
+                end: block_range.end,
+                depth: 0.0,
+            });
+        }
+    }
+    runs
+}
+
+// The coverage of a track over a region of the current path of one of a sample's graphs. Runs don't
+// span nodes, so each graph segment has its own. Bases without coverage, such as sequence added
+// after the track was imported, have a depth of 0.
+pub fn region_coverage(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    track: &str,
+    region: &Region,
+) -> Result<Vec<CoverageRun>, CoverageError> {
+    let block_group = Sample::get_block_groups(conn, collection_name, sample_name)
+        .into_iter()
+        .find(|block_group| block_group.name == region.name)
+        .ok_or_else(|| CoverageError::UnknownGraph(region.name.clone()))?;
+    let (path, range) = BlockGroup::region_range(conn, &block_group, region)?;
+    // a region across the origin of a circular graph is read as its two sides
+    let ranges = if range.is_wraparound() {
+        vec![
+            Range {
+                start: range.start,
+                end: path.length(conn),
+            },
+            Range {
+                start: 0,
+                end: range.end,
+            },
+        ]
+    } else {
+        vec![range]
+    };
+    Ok(ranges
+        .iter()
+        .flat_map(|range| path_coverage(conn, path.id, track, range))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_imports_and_propagates_coverage() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let bedgraph_path = temp_dir.path().join("coverage.bedgraph");
+        fs::write(
+            &bedgraph_path,
+            "track type=bedGraph\nm123\t0\t10\t5\nm123\t10\t20\t5\nm123\t20\t34\t12.5\n",
+        )
+        .unwrap();
+        import_coverage(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "run-1",
+            bedgraph_path.to_str().unwrap(),
+            CoverageFormat::BedGraph,
+        )
+        .unwrap();
+        assert!(matches!(
+            import_coverage(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "run-1",
+                bedgraph_path.to_str().unwrap(),
+                CoverageFormat::BedGraph,
+            ),
+            Err(CoverageError::TrackExists(_))
+        ));
+        assert_eq!(resolve_track(conn, None).unwrap(), "run-1");
+
+        let region = |start, end| Region {
+            name: "m123".to_string(),
+            start: Some(start),
+            end: Some(end),
+        };
+        // adjacent intervals with the same depth are stored as one run
+        assert_eq!(
+            region_coverage(conn, "test", None, "run-1", &region(5, 25)).unwrap(),
+            vec![
+                CoverageRun {
+                    start: 5,
+                    end: 20,
+                    depth: 5.0
+                },
+                CoverageRun {
+                    start: 20,
+                    end: 25,
+                    depth: 12.5
+                },
+            ]
+        );
+
+        // the child shares the bases around the replaced ones, but the 2 inserted bases have none
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            region_coverage(conn, "test", Some("child"), "run-1", &region(10, 20)).unwrap(),
+            vec![
+                CoverageRun {
+                    start: 10,
+                    end: 15,
+                    depth: 5.0
+                },
+                CoverageRun {
+                    start: 15,
+                    end: 17,
+                    depth: 0.0
+                },
+                CoverageRun {
+                    start: 17,
+                    end: 20,
+                    depth: 12.5
+                },
+            ]
+        );
+    }
+}
@@ -1,6 +1,11 @@
+use crate::models::annotation::{Annotation as StoredAnnotation, AnnotationData};
 use crate::models::block_group::BlockGroup;
+use crate::models::file_types::FileTypes;
+use crate::models::operations::{Operation, OperationInfo};
 use crate::models::path::{Annotation, Path};
 use crate::models::sample::Sample;
+use crate::models::strand::Strand;
+use crate::operation_management::{end_operation, start_operation, OperationError};
 use noodles::core::Position;
 use noodles::gff;
 use rusqlite::Connection;
@@ -8,6 +13,19 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AnnotationError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("No path named {0} exists for this sample")]
+    UnknownPath(String),
+    #[error("{changed} of {total} proteins changed")]
+    ProteinsChanged { changed: usize, total: usize },
+}
 
 pub fn propagate_gff(
     conn: &Connection,
@@ -50,16 +68,32 @@ pub fn propagate_gff(
     for result in reader.records() {
         let record = result?;
         let path_name = record.reference_sequence_name().to_string();
+        // GFF positions are 1-based and inclusive, paths are 0-based and end-exclusive
         let annotation = Annotation {
             name: "".to_string(),
-            start: record.start().get() as i64,
+            start: record.start().get() as i64 - 1,
             end: record.end().get() as i64,
+            strand: record
+                .strand()
+                .to_string()
+                .parse()
+                .unwrap_or(Strand::Unknown),
         };
         let mapping_tree = path_mappings_by_bg_name.get(&path_name).unwrap();
         let sequence_length = sequence_lengths_by_path_name.get(&path_name).unwrap();
-        let propagated_annotation =
-            Path::propagate_annotation(annotation, mapping_tree, *sequence_length).unwrap();
+        let propagated_annotation = Path::propagate_annotation(
+            annotation,
+            mapping_tree,
+            *sequence_length,
+            target_paths_by_bg_name[&path_name].is_circular(conn),
+        )
+        .unwrap();
 
+        let strand = propagated_annotation
+            .strand
+            .to_string()
+            .parse::<gff::record::Strand>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         let score = record.score();
         let phase = record.phase();
         let mut updated_record_builder = gff::Record::builder()
@@ -67,14 +101,14 @@ pub fn propagate_gff(
             .set_source(record.source().to_string())
             .set_type(record.ty().to_string())
             .set_start(
-                Position::new(propagated_annotation.start.try_into().unwrap())
+                Position::new((propagated_annotation.start + 1).try_into().unwrap())
                     .expect("Could not convert start ({start}) to usize for propagation"),
             )
             .set_end(
                 Position::new(propagated_annotation.end.try_into().unwrap())
                     .expect("Could not convert end ({end}) to usize for propagation"),
             )
-            .set_strand(record.strand())
+            .set_strand(strand)
             .set_attributes(record.attributes().clone());
 
         if let Some(score) = score {
@@ -90,6 +124,134 @@ pub fn propagate_gff(
     Ok(())
 }
 
+// Stores the records of a GFF file against the current paths of a sample, so they can be projected
+// onto derived samples later without the original file.
+pub fn import_gff_annotations(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    gff_input_filename: &str,
+) -> Result<Operation, AnnotationError> {
+    let mut session = start_operation(conn);
+
+    let mut reader = File::open(gff_input_filename)
+        .map(BufReader::new)
+        .map(gff::io::Reader::new)?;
+
+    let path_ids_by_bg_name = Sample::get_block_groups(conn, collection_name, sample_name)
+        .iter()
+        .map(|bg| {
+            (
+                bg.name.clone(),
+                BlockGroup::get_current_path(conn, bg.id).id,
+            )
+        })
+        .collect::<HashMap<String, i64>>();
+
+    let mut annotations = vec![];
+    let mut summary: HashMap<String, i64> = HashMap::new();
+    for result in reader.records() {
+        let record = result?;
+        let path_name = record.reference_sequence_name().to_string();
+        let path_id = *path_ids_by_bg_name
+            .get(&path_name)
+            .ok_or_else(|| AnnotationError::UnknownPath(path_name.clone()))?;
+        let attributes = record.attributes();
+        let name = ["Name", "ID"]
+            .iter()
+            .find_map(|tag| attributes.get(*tag).and_then(|value| value.as_string()))
+            .unwrap_or(record.ty())
+            .to_string();
+        annotations.push(AnnotationData {
+            path_id,
+            name,
+            feature_type: record.ty().to_string(),
+            source: record.source().to_string(),
+            // GFF positions are 1-based and inclusive, paths are 0-based and end-exclusive
+            start: record.start().get() as i64 - 1,
+            end: record.end().get() as i64,
+            strand: record.strand().to_string(),
+            attributes: attributes.to_string(),
+        });
+        *summary.entry(path_name).or_default() += 1;
+    }
+    StoredAnnotation::bulk_create(conn, &annotations);
+
+    let mut summary_str = "".to_string();
+    for (path_name, count) in summary.iter() {
+        summary_str.push_str(&format!(" {path_name}: {count} annotations.\n"));
+    }
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: gff_input_filename.to_string(),
+            file_type: FileTypes::GFF,
+            description: "gff_annotation".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+// Writes the annotations stored for a collection as a GFF file, projected onto the current paths of
+// the given sample.
+pub fn export_stored_gff(
+    conn: &Connection,
+    collection_name: &str,
+    to_sample_name: &str,
+    gff_output_filename: &str,
+) -> io::Result<()> {
+    let output_file = File::create(gff_output_filename)?;
+    let mut writer = gff::io::Writer::new(output_file);
+
+    let target_block_groups = Sample::get_block_groups(conn, collection_name, Some(to_sample_name));
+    let target_paths_by_id = target_block_groups
+        .iter()
+        .map(|bg| {
+            let path = BlockGroup::get_current_path(conn, bg.id);
+            (path.id, bg.name.clone())
+        })
+        .collect::<HashMap<i64, String>>();
+    let annotations = StoredAnnotation::project_onto(
+        conn,
+        &StoredAnnotation::query_for_collection(conn, collection_name),
+        &target_block_groups,
+    );
+
+    for annotation in annotations {
+        let attributes = annotation
+            .attributes
+            .parse::<gff::record::Attributes>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let strand = annotation
+            .strand
+            .parse::<gff::record::Strand>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let record = gff::Record::builder()
+            .set_reference_sequence_name(target_paths_by_id[&annotation.path_id].clone())
+            .set_source(annotation.source)
+            .set_type(annotation.feature_type)
+            .set_start(
+                Position::new((annotation.start + 1).try_into().unwrap())
+                    .expect("Could not convert start to a GFF position"),
+            )
+            .set_end(
+                Position::new(annotation.end.try_into().unwrap())
+                    .expect("Could not convert end to a GFF position"),
+            )
+            .set_strand(strand)
+            .set_attributes(attributes)
+            .build();
+        writer.write_record(&record)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -97,6 +259,7 @@ mod tests {
     use crate::imports::fasta::import_fasta;
     use crate::models::metadata;
     use crate::models::operations::setup_db;
+    use crate::patch::{apply_patches, create_patch, load_patches};
     use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
     use crate::updates::fasta::update_with_fasta;
     use std::path::PathBuf;
@@ -183,4 +346,130 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_stored_annotations_are_projected() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let gff_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.gff");
+        let conn = get_connection(None);
+        let db_uuid = metadata::get_db_uuid(&conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            &conn,
+            op_conn,
+        )
+        .unwrap();
+        import_gff_annotations(&conn, op_conn, "test", None, gff_path.to_str().unwrap()).unwrap();
+
+        let annotations = StoredAnnotation::query_for_collection(&conn, "test");
+        assert_eq!(
+            annotations
+                .iter()
+                .map(|a| (a.name.as_str(), a.start, a.end))
+                .collect::<Vec<_>>(),
+            vec![("m123_region", 0, 34), ("gene-a0001", 4, 20)]
+        );
+        // importing the same file again stores nothing new
+        assert!(matches!(
+            import_gff_annotations(&conn, op_conn, "test", None, gff_path.to_str().unwrap()),
+            Err(AnnotationError::OperationError(OperationError::NoChanges))
+        ));
+
+        update_with_fasta(
+            &conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let output_path = temp_dir.path().join("output.gff");
+        export_stored_gff(&conn, "test", "child sample", output_path.to_str().unwrap()).unwrap();
+
+        let mut reader = File::open(&output_path)
+            .map(BufReader::new)
+            .map(gff::io::Reader::new)
+            .unwrap();
+        let records = reader
+            .records()
+            .map(|record| record.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].ty(), "Region");
+        assert_eq!(records[0].start().get(), 1);
+        assert_eq!(records[0].end().get(), 26);
+        assert_eq!(records[1].ty(), "Gene");
+        assert_eq!(records[1].start().get(), 5);
+        assert_eq!(records[1].end().get(), 15);
+        assert_eq!(
+            records[1].attributes().get("ID").unwrap().as_string(),
+            Some("gene-a0001")
+        );
+    }
+
+    #[test]
+    fn test_annotations_apply_across_dbs() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let gff_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.gff");
+        let conn = &get_connection(None);
+        let conn2 = &get_connection(None);
+        let operation_conn = &get_operation_connection(None);
+        let operation_conn2 = &get_operation_connection(None);
+        setup_db(operation_conn, &metadata::get_db_uuid(conn));
+        setup_db(operation_conn2, &metadata::get_db_uuid(conn2));
+
+        let op_1 = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let op_2 = import_gff_annotations(
+            conn,
+            operation_conn,
+            "test",
+            None,
+            gff_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let mut write_stream: Vec<u8> = Vec::new();
+        create_patch(
+            operation_conn,
+            &[op_1.hash, op_2.hash],
+            false,
+            &mut write_stream,
+        );
+        apply_patches(
+            conn2,
+            operation_conn2,
+            &load_patches(&write_stream[..]).unwrap(),
+        )
+        .unwrap();
+
+        let annotations = StoredAnnotation::query_for_collection(conn2, "test");
+        assert_eq!(annotations.len(), 2);
+        let path = Path::get(conn2, annotations[0].path_id);
+        assert_eq!(
+            BlockGroup::get_by_id(conn2, path.block_group_id).name,
+            "m123"
+        );
+    }
 }
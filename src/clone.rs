@@ -0,0 +1,336 @@
+use crate::config::{get_operation_connection, RepositoryLock, RepositoryLockError, BASE_DIR};
+use crate::get_connection;
+use crate::models::metadata::get_db_uuid;
+use crate::models::operations::{setup_db, Branch, OperationState};
+use crate::operation_management::replay_operation;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CloneError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{0}")]
+    RepositoryLock(#[from] RepositoryLockError),
+    #[error("{0} isn't supported, only repositories on this filesystem can be cloned")]
+    UnsupportedRemote(String),
+    #[error("{0} isn't a gen repository")]
+    NotARepository(PathBuf),
+    #[error("The repository has no database at {0} to clone")]
+    MissingDatabase(PathBuf),
+    #[error("{0} already exists and isn't empty")]
+    DestinationExists(PathBuf),
+    #[error("{0} isn't valid UTF-8, which database paths must be")]
+    NonUnicodePath(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CloneSummary {
+    pub destination: PathBuf,
+    pub copied_files: u64,
+    pub copied_bytes: u64,
+    pub branch: String,
+    // the operation checked out, none when the branch has no operations yet
+    pub operation_hash: Option<String>,
+}
+
+// The repository directory a remote names, either as a path or a file:// URL.
+pub fn remote_path(remote: &str) -> Result<PathBuf, CloneError> {
+    let path = match remote.split_once("://") {
+        Some(("file", path)) => path,
+        Some(_) => return Err(CloneError::UnsupportedRemote(remote.to_string())),
+        None => remote,
+    };
+    Ok(PathBuf::from(path))
+}
+
+fn path_str(path: &Path) -> Result<&str, CloneError> {
+    path.to_str()
+        .ok_or_else(|| CloneError::NonUnicodePath(path.to_path_buf()))
+}
+
+// Points BASE_DIR at another repository until dropped, so the repository the command started in is
+// restored however the work in between ends, including a panic.
+struct BaseDirGuard {
+    previous: PathBuf,
+}
+
+impl BaseDirGuard {
+    fn set(base_dir: PathBuf) -> BaseDirGuard {
+        let previous = BASE_DIR.with(|v| v.read().unwrap().clone());
+        BASE_DIR.with(|v| *v.write().unwrap() = base_dir);
+        BaseDirGuard { previous }
+    }
+}
+
+impl Drop for BaseDirGuard {
+    fn drop(&mut self) {
+        BASE_DIR.with(|v| *v.write().unwrap() = self.previous.clone());
+    }
+}
+
+// The directory a clone is made in when none is given, named after the remote like git does.
+pub fn default_destination(remote: &Path) -> PathBuf {
+    remote
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("gen-clone"))
+}
+
+// Databases are rebuilt from the operations rather than copied, and the lock belongs to the remote.
+fn is_fetched(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let is_database = matches!(
+        extension,
+        Some("db") | Some("db-wal") | Some("db-shm") | Some("db-journal")
+    );
+    !is_database && path.file_name().is_some_and(|name| name != "lock")
+}
+
+fn fetch_dir(source: &Path, destination: &Path, summary: &mut CloneSummary) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let source_path = entry.path();
+        let destination_path = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            fetch_dir(&source_path, &destination_path, summary)?;
+        } else if is_fetched(&source_path) {
+            summary.copied_bytes += fs::copy(&source_path, &destination_path)?;
+            summary.copied_files += 1;
+        }
+    }
+    Ok(())
+}
+
+// The path of the database commands use by default, and what it becomes in the clone. A default
+// database outside of the remote isn't the remote's to give, so the clone uses .gen/default.db.
+fn default_databases(
+    operation_conn: &Connection,
+    remote: &Path,
+    destination: &Path,
+) -> Result<(PathBuf, Option<PathBuf>), CloneError> {
+    let db_name: Option<String> =
+        operation_conn.query_row("select db_name from defaults where id = 1", [], |row| {
+            row.get(0)
+        })?;
+    match db_name {
+        Some(db_name) => {
+            let remote_db = remote.join(db_name);
+            let cloned_db = remote_db
+                .strip_prefix(remote)
+                .ok()
+                .map(|relative_path| destination.join(relative_path));
+            Ok((remote_db, cloned_db))
+        }
+        None => Ok((remote.join(".gen").join("default.db"), None)),
+    }
+}
+
+// Clones the repository rooted at remote, the directory holding its .gen directory, into
+// destination. The operations, their changesets and the sequence objects are copied, and the
+// remote's default database is rebuilt by applying the operations of its main branch up to the
+// branch's head, leaving main checked out. The remote is locked while it's read, waiting for a
+// command changing it to finish if `wait` is given.
+pub fn clone_repository(
+    remote: &str,
+    destination: &Path,
+    wait: bool,
+) -> Result<CloneSummary, CloneError> {
+    let remote = remote_path(remote)?;
+    let remote_gen_db = remote.join(".gen").join("gen.db");
+    if !remote_gen_db.is_file() {
+        return Err(CloneError::NotARepository(remote));
+    }
+    let remote = remote.canonicalize()?;
+    if destination.exists() && fs::read_dir(destination)?.next().is_some() {
+        return Err(CloneError::DestinationExists(destination.to_path_buf()));
+    }
+    fs::create_dir_all(destination)?;
+    let destination = destination.canonicalize()?;
+
+    let remote_lock = RepositoryLock::acquire_in(&remote.join(".gen"), wait)?;
+    let remote_operation_conn =
+        Connection::open_with_flags(&remote_gen_db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let (remote_db, cloned_db) = default_databases(&remote_operation_conn, &remote, &destination)?;
+    if !remote_db.is_file() {
+        return Err(CloneError::MissingDatabase(remote_db));
+    }
+    let db_uuid = get_db_uuid(&Connection::open_with_flags(
+        &remote_db,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?);
+
+    let mut summary = CloneSummary {
+        destination: destination.clone(),
+        copied_files: 0,
+        copied_bytes: 0,
+        branch: "main".to_string(),
+        operation_hash: None,
+    };
+    let gen_dir = destination.join(".gen");
+    fetch_dir(&remote.join(".gen"), &gen_dir, &mut summary)?;
+    // the operation database is backed up rather than copied in case a command that doesn't take
+    // the lock has it open
+    let cloned_gen_db = gen_dir.join("gen.db");
+    remote_operation_conn.backup(DatabaseName::Main, &cloned_gen_db, None)?;
+    summary.copied_files += 1;
+    summary.copied_bytes += fs::metadata(&cloned_gen_db)?.len();
+    drop(remote_lock);
+
+    let operation_conn = get_operation_connection(cloned_gen_db);
+    operation_conn.execute(
+        "update defaults set db_name = ?1 where id = 1",
+        (cloned_db.as_deref().map(path_str).transpose()?,),
+    )?;
+    let db_path = cloned_db.unwrap_or_else(|| gen_dir.join("default.db"));
+    if let Some(parent) = db_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let conn = get_connection(path_str(&db_path)?);
+    conn.execute("update gen_metadata set db_uuid = ?1", (&db_uuid,))?;
+    // the clone has every collection, whatever the remote had checked out
+    operation_conn.execute(
+        "delete from sparse_collections where db_uuid = ?1",
+        (&db_uuid,),
+    )?;
+    operation_conn.execute(
+        "delete from sparse_pending_operations where db_uuid = ?1",
+        (&db_uuid,),
+    )?;
+    operation_conn.execute(
+        "update operation_state set operation_hash = null where db_uuid = ?1",
+        (&db_uuid,),
+    )?;
+    setup_db(&operation_conn, &db_uuid);
+    OperationState::set_branch(&operation_conn, &db_uuid, &summary.branch);
+
+    let branch = Branch::get_by_name(&operation_conn, &db_uuid, &summary.branch).unwrap();
+    if let Some(head) = branch.current_operation_hash {
+        let operations = Branch::get_operations(&operation_conn, branch.id);
+        let head_position = operations
+            .iter()
+            .position(|operation| operation.hash == head)
+            .unwrap();
+        // changesets and sequence objects are read from the clone's .gen directory
+        let base_dir = BaseDirGuard::set(destination.clone());
+        let tx = conn.unchecked_transaction()?;
+        for operation in operations[..=head_position].iter() {
+            replay_operation(&conn, operation);
+        }
+        tx.commit()?;
+        drop(base_dir);
+        OperationState::set_operation(&operation_conn, &db_uuid, &head);
+        summary.operation_hash = Some(head);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::traits::Query;
+    use crate::updates::fasta::update_with_fasta;
+    use tempfile::tempdir;
+
+    fn sequences(conn: &Connection) -> Vec<String> {
+        let mut sequences =
+            BlockGroup::query(conn, "select * from block_groups", rusqlite::params![])
+                .iter()
+                .map(|block_group| {
+                    BlockGroup::get_current_path(conn, block_group.id).sequence(conn)
+                })
+                .collect::<Vec<String>>();
+        sequences.sort();
+        sequences
+    }
+
+    #[test]
+    fn test_clones_repository() {
+        let remote = tempdir().unwrap().into_path();
+        BASE_DIR.with(|v| *v.write().unwrap() = remote.clone());
+        fs::create_dir_all(remote.join(".gen")).unwrap();
+        let remote_operation_conn = get_operation_connection(remote.join(".gen").join("gen.db"));
+        let remote_conn = get_connection(remote.join(".gen").join("default.db").to_str().unwrap());
+        let db_uuid = get_db_uuid(&remote_conn);
+        setup_db(&remote_operation_conn, &db_uuid);
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            &remote_conn,
+            &remote_operation_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            &remote_conn,
+            &remote_operation_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            2,
+            5,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let head = OperationState::get_operation(&remote_operation_conn, &db_uuid).unwrap();
+
+        let destination = tempdir().unwrap().into_path().join("clone");
+        let summary = clone_repository(
+            &format!("file://{remote}", remote = remote.display()),
+            &destination,
+            false,
+        )
+        .unwrap();
+        // the clone is rebuilt from its own .gen directory, then the original one is restored
+        assert_eq!(BASE_DIR.with(|v| v.read().unwrap().clone()), remote);
+        assert_eq!(summary.branch, "main");
+        assert_eq!(summary.operation_hash, Some(head.clone()));
+        assert!(summary.copied_files > 0);
+
+        let cloned_conn = get_connection(
+            destination
+                .join(".gen")
+                .join("default.db")
+                .to_str()
+                .unwrap(),
+        );
+        let cloned_operation_conn =
+            get_operation_connection(destination.join(".gen").join("gen.db"));
+        assert_eq!(get_db_uuid(&cloned_conn), db_uuid);
+        assert_eq!(
+            OperationState::get_operation(&cloned_operation_conn, &db_uuid),
+            Some(head)
+        );
+        assert_eq!(sequences(&cloned_conn), sequences(&remote_conn));
+        assert!(!destination.join(".gen").join("lock").exists());
+
+        assert!(matches!(
+            clone_repository(remote.to_str().unwrap(), &destination, false),
+            Err(CloneError::DestinationExists(_))
+        ));
+        assert!(matches!(
+            clone_repository("https://example.com/repo", &remote.join("other"), false),
+            Err(CloneError::UnsupportedRemote(_))
+        ));
+        assert!(matches!(
+            clone_repository(
+                destination.join("missing").to_str().unwrap(),
+                &remote,
+                false
+            ),
+            Err(CloneError::NotARepository(_))
+        ));
+    }
+}
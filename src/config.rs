@@ -1,19 +1,41 @@
 use crate::migrations::run_operation_migrations;
 use crate::models::operations::Operation;
 use rusqlite::Connection;
+use std::io::{self, Read, Write};
 use std::string::ToString;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::RwLock;
+use std::time::Duration;
 use std::{
     env, fs,
     path::{Path, PathBuf},
+    process,
     sync::LazyLock,
 };
+use thiserror::Error;
 
 thread_local! {
 pub static BASE_DIR: LazyLock<RwLock<PathBuf>> =
     LazyLock::new(|| RwLock::new(env::current_dir().unwrap()));
 }
 
+// Caches kept in the databases, such as path layouts and graph stats, are filled in when first
+// read. Commands running without the repository lock turn this off so they never write to a
+// database another command may be changing, and compute what's missing from the cache instead.
+static CACHE_WRITES: AtomicBool = AtomicBool::new(true);
+
+pub fn disable_cache_writes() {
+    CACHE_WRITES.store(false, Ordering::Relaxed);
+}
+
+pub fn cache_writes_enabled() -> bool {
+    CACHE_WRITES.load(Ordering::Relaxed)
+}
+
+// How long a connection waits for another process to finish writing before giving up, such as a
+// command reading a database while an update commits.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub fn get_operation_connection(db_path: impl Into<Option<PathBuf>>) -> Connection {
     let db_path = db_path.into();
     let path = if let Some(s) = db_path {
@@ -23,10 +45,75 @@ pub fn get_operation_connection(db_path: impl Into<Option<PathBuf>>) -> Connecti
     };
     let mut conn =
         Connection::open(&path).unwrap_or_else(|_| panic!("Error connecting to {:?}", &path));
+    conn.busy_timeout(BUSY_TIMEOUT).unwrap();
     run_operation_migrations(&mut conn);
     conn
 }
 
+#[derive(Debug, Error)]
+pub enum RepositoryLockError {
+    #[error("The repository is locked by PID {0}, run with --wait to wait for it")]
+    Locked(String),
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/* An advisory lock on a repository, taken by commands that change it so two of them can't interleave
+   their changes to the databases and the operation state. The lock is held on a file in the .gen
+   directory, which names the PID of the holder, until it's dropped. The operating system releases
+   it when the process exits, so a command that crashes doesn't leave the repository locked. The
+   file itself is never removed, as a process waiting on it would then hold a lock nobody else sees.
+*/
+#[derive(Debug)]
+pub struct RepositoryLock {
+    file: fs::File,
+}
+
+impl RepositoryLock {
+    pub fn acquire(wait: bool) -> Result<RepositoryLock, RepositoryLockError> {
+        RepositoryLock::acquire_in(Path::new(&get_gen_dir()), wait)
+    }
+
+    // Locks the repository of the given .gen directory, such as one being forked or cloned.
+    pub fn acquire_in(gen_dir: &Path, wait: bool) -> Result<RepositoryLock, RepositoryLockError> {
+        let path = gen_dir.join("lock");
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                // some platforms don't allow reading a file another process has locked
+                let _ = file.read_to_string(&mut holder);
+                let holder = match holder.trim() {
+                    "" => "unknown".to_string(),
+                    pid => pid.to_string(),
+                };
+                if !wait {
+                    return Err(RepositoryLockError::Locked(holder));
+                }
+                eprintln!("Waiting for PID {holder} to release the repository lock.");
+                file.lock()?;
+            }
+            Err(fs::TryLockError::Error(err)) => return Err(err.into()),
+        }
+        file.set_len(0)?;
+        file.write_all(process::id().to_string().as_bytes())?;
+        Ok(RepositoryLock { file })
+    }
+}
+
+impl Drop for RepositoryLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
 fn ensure_dir(path: &PathBuf) {
     if !path.is_dir() {
         fs::create_dir_all(path).unwrap();
@@ -83,4 +170,18 @@ mod tests {
         setup_gen_dir();
         assert!(!get_gen_dir().is_empty());
     }
+
+    #[test]
+    fn test_locks_repository() {
+        setup_gen_dir();
+        let lock = RepositoryLock::acquire(false).unwrap();
+        match RepositoryLock::acquire(false) {
+            Err(RepositoryLockError::Locked(holder)) => {
+                assert_eq!(holder, process::id().to_string())
+            }
+            result => panic!("expected the repository to be locked, got {result:?}"),
+        }
+        drop(lock);
+        assert!(RepositoryLock::acquire(false).is_ok());
+    }
 }
@@ -0,0 +1,84 @@
+use crate::migrations::run_migrations;
+use crate::tuning::TuningProfile;
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub type ConnectionPool = Pool<SqliteConnectionManager>;
+pub type PooledSqliteConnection = PooledConnection<SqliteConnectionManager>;
+
+static MEMORY_DATABASE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// A pool of connections to a database, so it can be read from several threads at once. A
+// connection is not shared between threads, but each thread can check one out of the pool and pass
+// it to the model functions, which take a `&Connection` and work the same on a pooled one.
+pub fn get_connection_pool(db_path: &str, max_size: u32) -> ConnectionPool {
+    build_pool(SqliteConnectionManager::file(db_path), max_size)
+}
+
+// A pool over a new in-memory database. The database is shared between the pool's connections and
+// lives as long as the pool keeps any of them open.
+pub fn get_memory_connection_pool(max_size: u32) -> ConnectionPool {
+    let database_index = MEMORY_DATABASE_COUNT.fetch_add(1, Ordering::Relaxed);
+    let uri = format!(
+        "file:gen-memory-{pid}-{database_index}?mode=memory&cache=shared",
+        pid = std::process::id()
+    );
+    build_pool(SqliteConnectionManager::file(uri), max_size)
+}
+
+fn build_pool(manager: SqliteConnectionManager, max_size: u32) -> ConnectionPool {
+    let manager = manager.with_init(|conn| {
+        rusqlite::vtab::array::load_module(conn)?;
+        TuningProfile::Safe.apply(conn)
+    });
+    let pool = Pool::builder()
+        .max_size(max_size)
+        .min_idle(Some(1))
+        .build(manager)
+        .unwrap_or_else(|err| panic!("Error creating connection pool: {err}"));
+    run_migrations(&mut pool.get().unwrap());
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{collection::Collection, sequence::Sequence};
+    use std::thread;
+
+    #[test]
+    fn test_reads_from_several_threads() {
+        let pool = get_memory_connection_pool(4);
+        let hash = {
+            let conn = pool.get().unwrap();
+            Collection::create(&conn, "test");
+            Sequence::new()
+                .sequence_type("DNA")
+                .sequence("ATCGATCG")
+                .save(&conn)
+                .hash
+        };
+
+        let handles = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                let hash = hash.clone();
+                thread::spawn(move || {
+                    let conn = pool.get().unwrap();
+                    Sequence::sequences_by_hash(&conn, vec![hash.as_str()])[&hash]
+                        .get_sequence(2, 6)
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), "CGAT");
+        }
+
+        // every pool gets its own database
+        let other_pool = get_memory_connection_pool(1);
+        assert!(
+            Sequence::sequences_by_hash(&other_pool.get().unwrap(), vec![hash.as_str()]).is_empty()
+        );
+    }
+}
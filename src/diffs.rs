@@ -1 +1,7 @@
+pub mod genbank;
 pub mod gfa;
+pub mod recipe;
+pub mod sets;
+pub mod three_way;
+pub mod tsv;
+pub mod vcf;
@@ -0,0 +1,227 @@
+use crate::genbank::GenBankError;
+use crate::range::Range;
+use dissimilar::Chunk;
+use gb_io::seq::{Feature, Seq};
+use itertools::Itertools;
+use std::io::{self, Write};
+use std::str;
+
+// A region that differs between two revisions of a record, with the features of either revision
+// that it touches. Coordinates are 0-based and end-exclusive, and one of the two ranges is empty for
+// insertions and deletions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GenBankChange {
+    pub record: String,
+    pub old_range: Range,
+    pub new_range: Range,
+    pub old_sequence: String,
+    pub new_sequence: String,
+    pub features: Vec<String>,
+}
+
+// Compares two revisions of the same GenBank records. Records are paired up by name, or taken as
+// revisions of each other when both files hold a single record. The sequences are aligned and every
+// differing region is reported with the features that overlap it in the old or the new revision.
+pub fn genbank_diff(
+    old_records: &[Seq],
+    new_records: &[Seq],
+) -> Result<Vec<GenBankChange>, GenBankError> {
+    let pairs = if old_records.len() == 1 && new_records.len() == 1 {
+        vec![(&old_records[0], &new_records[0])]
+    } else {
+        old_records
+            .iter()
+            .filter_map(|old| {
+                new_records
+                    .iter()
+                    .find(|new| new.name.is_some() && new.name == old.name)
+                    .map(|new| (old, new))
+            })
+            .collect()
+    };
+
+    let mut changes = vec![];
+    for (old, new) in pairs {
+        let old_sequence = record_sequence(old)?;
+        let new_sequence = record_sequence(new)?;
+        for (old_range, new_range) in differing_ranges(&old_sequence, &new_sequence) {
+            let features = overlapping_features(old, &old_range)
+                .chain(overlapping_features(new, &new_range))
+                .unique()
+                .collect();
+            changes.push(GenBankChange {
+                record: new.name.clone().unwrap_or_default(),
+                old_sequence: old_sequence[old_range.start as usize..old_range.end as usize]
+                    .to_string(),
+                new_sequence: new_sequence[new_range.start as usize..new_range.end as usize]
+                    .to_string(),
+                old_range,
+                new_range,
+                features,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+pub fn write_genbank_diff<W: Write>(writer: &mut W, changes: &[GenBankChange]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "record\told_start\told_end\tnew_start\tnew_end\told_sequence\tnew_sequence\tfeatures"
+    )?;
+    for change in changes {
+        writeln!(
+            writer,
+            "{record}\t{old_start}\t{old_end}\t{new_start}\t{new_end}\t{old_sequence}\t{new_sequence}\t{features}",
+            record = change.record,
+            old_start = change.old_range.start,
+            old_end = change.old_range.end,
+            new_start = change.new_range.start,
+            new_end = change.new_range.end,
+            old_sequence = change.old_sequence,
+            new_sequence = change.new_sequence,
+            features = change.features.join(","),
+        )?;
+    }
+    Ok(())
+}
+
+fn record_sequence(record: &Seq) -> Result<String, GenBankError> {
+    str::from_utf8(&record.seq)
+        .map(|sequence| sequence.to_ascii_uppercase())
+        .map_err(|_| GenBankError::ParseError("No sequence present".to_string()))
+}
+
+// The pairs of ranges that differ between two sequences, in order. Adjacent deletions and
+// insertions are reported together as one replaced range.
+fn differing_ranges(old_sequence: &str, new_sequence: &str) -> Vec<(Range, Range)> {
+    let mut ranges = vec![];
+    let mut old_position = 0;
+    let mut new_position = 0;
+    let mut current: Option<(Range, Range)> = None;
+    for chunk in dissimilar::diff(old_sequence, new_sequence) {
+        let (old_length, new_length) = match chunk {
+            Chunk::Equal(text) => {
+                ranges.extend(current.take());
+                old_position += text.len() as i64;
+                new_position += text.len() as i64;
+                continue;
+            }
+            Chunk::Delete(text) => (text.len() as i64, 0),
+            Chunk::Insert(text) => (0, text.len() as i64),
+        };
+        let (old_range, new_range) = current.get_or_insert((
+            Range {
+                start: old_position,
+                end: old_position,
+            },
+            Range {
+                start: new_position,
+                end: new_position,
+            },
+        ));
+        old_position += old_length;
+        new_position += new_length;
+        old_range.end = old_position;
+        new_range.end = new_position;
+    }
+    ranges.extend(current);
+    ranges
+}
+
+// Features that overlap a range, as kind:name, or just their kind when they have no name. An empty range, where sequence was inserted, touches
+// the features it falls within.
+fn overlapping_features<'a>(
+    record: &'a Seq,
+    range: &'a Range,
+) -> impl Iterator<Item = String> + 'a {
+    record
+        .features
+        .iter()
+        .filter(|feature| feature.kind.to_string() != "source")
+        .filter(move |feature| {
+            let Ok((start, end)) = feature.location.find_bounds() else {
+                return false;
+            };
+            if range.start == range.end {
+                start < range.start && range.start < end
+            } else {
+                start < range.end && range.start < end
+            }
+        })
+        .map(|feature| match feature_name(feature) {
+            Some(name) => format!("{}:{name}", feature.kind),
+            None => feature.kind.to_string(),
+        })
+}
+
+fn feature_name(feature: &Feature) -> Option<String> {
+    for key in ["label", "gene", "locus_tag", "product"] {
+        if let Some((_, Some(value))) = feature.qualifiers.iter().find(|(k, _)| k == key) {
+            // qualifiers can wrap over several lines
+            return Some(value.split_whitespace().join(" "));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gb_io::reader;
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    fn read_records(filename: &str) -> Vec<Seq> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/geneious_genbank")
+            .join(filename);
+        reader::SeqReader::new(File::open(path).unwrap())
+            .map(|record| record.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_differing_ranges() {
+        assert_eq!(
+            differing_ranges("AAAACCCCGGGGTTTT", "AAAAGGGGTTTTAA"),
+            vec![
+                (Range { start: 4, end: 8 }, Range { start: 4, end: 4 }),
+                (Range { start: 16, end: 16 }, Range { start: 12, end: 14 }),
+            ]
+        );
+        assert!(differing_ranges("ATCG", "ATCG").is_empty());
+    }
+
+    #[test]
+    fn test_genbank_diff() {
+        let old_records = read_records("deletion.gb");
+        let new_records = read_records("insertion.gb");
+        let changes = genbank_diff(&old_records, &new_records).unwrap();
+        // the later revision inserts the chloramphenicol resistance gene
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.record, "insertion");
+        assert_eq!(
+            change.old_range,
+            Range {
+                start: 767,
+                end: 767
+            }
+        );
+        assert_eq!(
+            change.new_range,
+            Range {
+                start: 767,
+                end: 2222
+            }
+        );
+        assert_eq!(change.old_sequence, "");
+        assert_eq!(change.new_sequence.len(), 1455);
+        assert!(change.features.contains(&"CDS:cat".to_string()));
+        assert!(change.features.contains(&"gene:cat".to_string()));
+
+        let changes = genbank_diff(&old_records, &old_records).unwrap();
+        assert!(changes.is_empty());
+    }
+}
@@ -163,8 +163,8 @@ pub fn gfa_sample_diff(
 
     let file = File::create(filename).unwrap();
     let mut writer = BufWriter::new(file);
-    write_segments(&mut writer, &segments.iter().cloned().collect());
-    write_links(&mut writer, &links.iter().cloned().collect());
+    write_segments(&mut writer, &segments.iter().cloned().collect::<Vec<_>>());
+    write_links(&mut writer, &links.iter().cloned().collect::<Vec<_>>());
 
     for path in paths {
         writer
@@ -223,7 +223,7 @@ fn links_from_blocks(node_blocks: &[NodeIntervalBlock]) -> Vec<Link> {
 }
 
 fn path_from_segments(sample_name: Option<&str>, path: &Path, segments: &[Segment]) -> GFAPath {
-    let path_name = if sample_name.unwrap_or("") != "" {
+    let path_name = if !sample_name.unwrap_or("").is_empty() {
         format!("{}.{}", sample_name.unwrap(), path.name)
     } else {
         path.name.clone()
@@ -0,0 +1,192 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use crate::range::Range;
+use itertools::Itertools;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+// A sequence edit of a recipe. The edit replaces the reference bases with the replacement ones, and
+// is found on a genome by the bases flanking it. The coordinates are those of the sample the
+// recipe was made from, 0-based and end-exclusive, and only break ties between sites with the
+// same flanks.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RecipeEdit {
+    pub graph: String,
+    pub start: i64,
+    pub end: i64,
+    pub upstream: String,
+    pub reference: String,
+    pub replacement: String,
+    pub downstream: String,
+}
+
+// The edits that turn one sample into another, in the order of the graphs and of the edits on
+// them.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Recipe {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub edits: Vec<RecipeEdit>,
+}
+
+// Turns the differences between two paths into edits. Insertions and deletions take in the base
+// before them, or after them at the start of a path, so every edit replaces at least one base.
+fn path_edits(
+    conn: &Connection,
+    graph: &str,
+    source_path: &Path,
+    target_path: &Path,
+    flank: i64,
+) -> Vec<RecipeEdit> {
+    let source_sequence = source_path.sequence(conn);
+    let target_sequence = target_path.sequence(conn);
+    let source_length = source_sequence.len() as i64;
+    let slice = |sequence: &str, range: &Range| {
+        sequence[range.start as usize..range.end as usize].to_string()
+    };
+    source_path
+        .find_differences(conn, target_path)
+        .into_iter()
+        .map(|difference| {
+            let mut source_range = difference.source_range;
+            let mut target_range = difference.target_range;
+            if source_range.start == source_range.end || target_range.start == target_range.end {
+                if source_range.start > 0 {
+                    source_range.start -= 1;
+                    target_range.start -= 1;
+                } else if source_range.end < source_length {
+                    source_range.end += 1;
+                    target_range.end += 1;
+                }
+            }
+            RecipeEdit {
+                graph: graph.to_string(),
+                start: source_range.start,
+                end: source_range.end,
+                upstream: slice(
+                    &source_sequence,
+                    &Range {
+                        start: (source_range.start - flank).max(0),
+                        end: source_range.start,
+                    },
+                ),
+                reference: slice(&source_sequence, &source_range),
+                replacement: slice(&target_sequence, &target_range),
+                downstream: slice(
+                    &source_sequence,
+                    &Range {
+                        start: source_range.end,
+                        end: (source_range.end + flank).min(source_length),
+                    },
+                ),
+            }
+        })
+        .collect()
+}
+
+// Collects the edits that distinguish a sample from the sample it was derived from, by comparing
+// the current paths of the graphs they share. Each edit keeps flank bases on either side so the
+// recipe can be replayed on another genome with apply-plan.
+pub fn create_recipe(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+    flank: i64,
+) -> Recipe {
+    let source_paths_by_bg_name = Sample::get_block_groups(conn, collection_name, from_sample_name)
+        .iter()
+        .map(|bg| (bg.name.clone(), BlockGroup::get_current_path(conn, bg.id)))
+        .collect::<HashMap<String, Path>>();
+    let edits = Sample::get_block_groups(conn, collection_name, to_sample_name)
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .flat_map(|bg| {
+            source_paths_by_bg_name
+                .get(&bg.name)
+                .map(|source_path| {
+                    let target_path = BlockGroup::get_current_path(conn, bg.id);
+                    path_edits(conn, &bg.name, source_path, &target_path, flank)
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+    Recipe {
+        from: from_sample_name.map(|name| name.to_string()),
+        to: to_sample_name.map(|name| name.to_string()),
+        edits,
+    }
+}
+
+pub fn write_recipe(recipe: &Recipe, filename: &str) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(filename)?);
+    serde_yaml::to_writer(writer, recipe).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_creates_recipe() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // ATCGATCGATCGATCGATCGGGAACACACAGAGA becomes ATCGATCGATCGATCAAAAAAAAACACAGAGA
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let recipe = create_recipe(conn, "test", None, Some("child"), 5);
+        assert_eq!(
+            recipe,
+            Recipe {
+                from: None,
+                to: Some("child".to_string()),
+                edits: vec![RecipeEdit {
+                    graph: "m123".to_string(),
+                    start: 15,
+                    end: 25,
+                    upstream: "CGATC".to_string(),
+                    reference: "GATCGGGAAC".to_string(),
+                    replacement: "AAAAAAAA".to_string(),
+                    downstream: "ACACA".to_string(),
+                }],
+            }
+        );
+        assert!(create_recipe(conn, "test", Some("child"), Some("child"), 5)
+            .edits
+            .is_empty());
+    }
+}
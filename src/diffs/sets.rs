@@ -0,0 +1,243 @@
+use crate::gfa::{write_links, write_segments, Link, Segment};
+use crate::models::{
+    block_group_edge::{AugmentedEdge, BlockGroupEdge},
+    edge::{Edge, GroupBlock},
+    node::Node,
+    sample::Sample,
+    strand::Strand,
+};
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+// The nodes and edges used by the graphs of a sample. Nodes and edges are shared between samples
+// when a sample is derived from another, so set operations on them compare samples without going
+// through a reference path: the edges only in the newer sample are its new junctions and the nodes
+// only in it are its new sequence.
+#[derive(Clone, Debug, Default)]
+pub struct GraphSet {
+    pub node_ids: HashSet<i64>,
+    pub edges: HashMap<i64, AugmentedEdge>,
+}
+
+impl GraphSet {
+    pub fn for_sample(conn: &Connection, collection_name: &str, sample_name: Option<&str>) -> Self {
+        let mut set = GraphSet::default();
+        for block_group in Sample::get_block_groups(conn, collection_name, sample_name) {
+            for augmented_edge in BlockGroupEdge::edges_for_block_group(conn, block_group.id) {
+                for node_id in [
+                    augmented_edge.edge.source_node_id,
+                    augmented_edge.edge.target_node_id,
+                ] {
+                    if !Node::is_terminal(node_id) {
+                        set.node_ids.insert(node_id);
+                    }
+                }
+                set.edges
+                    .entry(augmented_edge.edge.id)
+                    .or_insert(augmented_edge);
+            }
+        }
+        set
+    }
+
+    pub fn intersection(&self, other: &GraphSet) -> GraphSet {
+        GraphSet {
+            node_ids: self
+                .node_ids
+                .intersection(&other.node_ids)
+                .copied()
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|(edge_id, _)| other.edges.contains_key(edge_id))
+                .map(|(edge_id, edge)| (*edge_id, edge.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn union(&self, other: &GraphSet) -> GraphSet {
+        let mut edges = other.edges.clone();
+        edges.extend(self.edges.clone());
+        GraphSet {
+            node_ids: self.node_ids.union(&other.node_ids).copied().collect(),
+            edges,
+        }
+    }
+
+    pub fn difference(&self, other: &GraphSet) -> GraphSet {
+        GraphSet {
+            node_ids: self.node_ids.difference(&other.node_ids).copied().collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|(edge_id, _)| !other.edges.contains_key(edge_id))
+                .map(|(edge_id, edge)| (*edge_id, edge.clone()))
+                .collect(),
+        }
+    }
+
+    // The total length of the sequences of the nodes in the set.
+    pub fn sequence_length(&self, conn: &Connection) -> i64 {
+        Node::get_sequences_by_node_ids(conn, &self.node_ids.iter().copied().collect::<Vec<i64>>())
+            .values()
+            .map(|sequence| sequence.length)
+            .sum()
+    }
+
+    // Writes the subgraph formed by the set as GFA. Nodes in the set are written in full, while a
+    // node outside of it only contributes the blocks that an edge of the set joins to, so every
+    // link has both of its segments. No paths are written since they generally leave the set.
+    pub fn write_gfa(&self, conn: &Connection, filename: &PathBuf) {
+        let mut edges = self.edges.values().cloned().collect::<Vec<AugmentedEdge>>();
+        let blocks = Edge::blocks_from_edges(conn, &edges);
+        edges.extend(Edge::boundary_edges_from_sequences(&blocks));
+        let (graph, _edges_by_node_pair) = Edge::build_graph(&edges, &blocks);
+
+        let mut kept_nodes = graph
+            .nodes()
+            .filter(|node| self.node_ids.contains(&node.node_id))
+            .collect::<HashSet<_>>();
+        let mut links = vec![];
+        for (source, target, edge_info) in graph.all_edges() {
+            if Node::is_terminal(source.node_id) || Node::is_terminal(target.node_id) {
+                continue;
+            }
+            // Boundary edges have an ID of -1 and only join two blocks of the same node.
+            let in_set = if edge_info.edge_id == -1 {
+                self.node_ids.contains(&source.node_id)
+            } else {
+                self.edges.contains_key(&edge_info.edge_id)
+            };
+            if in_set {
+                kept_nodes.insert(source);
+                kept_nodes.insert(target);
+                links.push(Link {
+                    source_segment_id: format!("{}.{}", source.node_id, source.sequence_start),
+                    source_strand: edge_info.source_strand,
+                    target_segment_id: format!("{}.{}", target.node_id, target.sequence_start),
+                    target_strand: edge_info.target_strand,
+                });
+            }
+        }
+
+        let blocks_by_id = blocks
+            .iter()
+            .map(|block| (block.id, block))
+            .collect::<HashMap<i64, &GroupBlock>>();
+        let segments = kept_nodes
+            .into_iter()
+            .map(|node| Segment {
+                sequence: blocks_by_id[&node.block_id].sequence(),
+                node_id: node.node_id,
+                sequence_start: node.sequence_start,
+                strand: Strand::Forward,
+            })
+            .sorted_by_key(|segment| (segment.node_id, segment.sequence_start))
+            .collect::<Vec<Segment>>();
+        let links = links
+            .into_iter()
+            .sorted_by(|link1, link2| {
+                (&link1.source_segment_id, &link1.target_segment_id)
+                    .cmp(&(&link2.source_segment_id, &link2.target_segment_id))
+            })
+            .collect::<Vec<Link>>();
+
+        let file = File::create(filename).unwrap();
+        let mut writer = BufWriter::new(file);
+        write_segments(&mut writer, &segments);
+        write_links(&mut writer, &links);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sample_set_operations() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let reference = GraphSet::for_sample(conn, "test", None);
+        let child = GraphSet::for_sample(conn, "test", Some("child sample"));
+
+        let shared = reference.intersection(&child);
+        assert_eq!(shared.node_ids.len(), 1);
+        assert_eq!(shared.edges.len(), 2);
+        assert_eq!(shared.sequence_length(conn), 34);
+
+        let new_in_child = child.difference(&reference);
+        assert_eq!(new_in_child.node_ids.len(), 1);
+        assert_eq!(new_in_child.edges.len(), 2);
+        assert_eq!(new_in_child.sequence_length(conn), 2);
+
+        let removed_in_child = reference.difference(&child);
+        assert!(removed_in_child.node_ids.is_empty());
+        assert!(removed_in_child.edges.is_empty());
+
+        let union = reference.union(&child);
+        assert_eq!(union.node_ids.len(), 2);
+        assert_eq!(union.edges.len(), 4);
+        assert_eq!(union.sequence_length(conn), 36);
+
+        // the inserted sequence with the reference blocks on either side of it
+        let temp_dir = tempdir().unwrap();
+        let gfa_path = temp_dir.path().join("new.gfa");
+        new_in_child.write_gfa(conn, &gfa_path);
+        let reference_node_id = *shared.node_ids.iter().next().unwrap();
+        let new_node_id = *new_in_child.node_ids.iter().next().unwrap();
+        let content = fs::read_to_string(&gfa_path).unwrap();
+        let lines = content
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<HashSet<String>>();
+        assert_eq!(
+            lines,
+            HashSet::from_iter(vec![
+                format!("S\t{reference_node_id}.0\tATCGATCGATCGATC\t*"),
+                format!("S\t{reference_node_id}.25\tACACAGAGA\t*"),
+                format!("S\t{new_node_id}.0\tAA\t*"),
+                format!("L\t{reference_node_id}.0\t+\t{new_node_id}.0\t+\t0M"),
+                format!("L\t{new_node_id}.0\t+\t{reference_node_id}.25\t+\t0M"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use crate::range::{Range, RangeMapping};
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ThreeWayStatus {
+    OnlyFirst,
+    OnlySecond,
+    Same,
+    Conflicting,
+}
+
+impl fmt::Display for ThreeWayStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ThreeWayStatus::OnlyFirst => "only_first",
+            ThreeWayStatus::OnlySecond => "only_second",
+            ThreeWayStatus::Same => "same",
+            ThreeWayStatus::Conflicting => "conflicting",
+        };
+        write!(f, "{status}")
+    }
+}
+
+// A region of the ancestor that was changed in at least one of the two derived samples, with the
+// sequence each of the three samples has over it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ThreeWayRegion {
+    pub graph: String,
+    pub ancestor_range: Range,
+    pub status: ThreeWayStatus,
+    pub ancestor_sequence: String,
+    pub first_sequence: String,
+    pub second_sequence: String,
+}
+
+// Compares two samples derived from a common ancestor. The changes each sample made to the current
+// path of the ancestor are grouped by the region of the ancestor they touch, and changes of the two
+// samples that overlap or are adjacent end up in the same region. A region changed by both samples
+// is the same if both ended up with the same sequence over it and conflicting otherwise. Graphs that
+// are missing from any of the three samples are skipped.
+pub fn three_way_diff(
+    conn: &Connection,
+    collection_name: &str,
+    ancestor_sample_name: Option<&str>,
+    first_sample_name: Option<&str>,
+    second_sample_name: Option<&str>,
+) -> Vec<ThreeWayRegion> {
+    let paths_by_bg_name = |sample_name: Option<&str>| {
+        Sample::get_block_groups(conn, collection_name, sample_name)
+            .iter()
+            .map(|bg| (bg.name.clone(), BlockGroup::get_current_path(conn, bg.id)))
+            .collect::<HashMap<String, Path>>()
+    };
+    let ancestor_paths = paths_by_bg_name(ancestor_sample_name);
+    let first_paths = paths_by_bg_name(first_sample_name);
+    let second_paths = paths_by_bg_name(second_sample_name);
+
+    let mut regions = vec![];
+    for (graph, ancestor_path) in ancestor_paths
+        .iter()
+        .sorted_by(|(name1, _), (name2, _)| name1.cmp(name2))
+    {
+        let (Some(first_path), Some(second_path)) =
+            (first_paths.get(graph), second_paths.get(graph))
+        else {
+            continue;
+        };
+        let ancestor_sequence = ancestor_path.sequence(conn);
+        let first_sequence = first_path.sequence(conn);
+        let second_sequence = second_path.sequence(conn);
+
+        let mut changes = ancestor_path
+            .find_differences(conn, first_path)
+            .into_iter()
+            .map(|difference| (true, difference))
+            .chain(
+                ancestor_path
+                    .find_differences(conn, second_path)
+                    .into_iter()
+                    .map(|difference| (false, difference)),
+            )
+            .sorted_by_key(|(_, difference)| {
+                (difference.source_range.start, difference.source_range.end)
+            })
+            .peekable();
+
+        while let Some(change) = changes.next() {
+            let mut cluster = vec![change];
+            let mut cluster_end = cluster[0].1.source_range.end;
+            while let Some(next) =
+                changes.next_if(|(_, difference)| difference.source_range.start <= cluster_end)
+            {
+                cluster_end = cluster_end.max(next.1.source_range.end);
+                cluster.push(next);
+            }
+            let ancestor_range = Range {
+                start: cluster[0].1.source_range.start,
+                end: cluster_end,
+            };
+            let first_changes = cluster
+                .iter()
+                .filter(|(is_first, _)| *is_first)
+                .map(|(_, difference)| difference)
+                .collect::<Vec<&RangeMapping>>();
+            let second_changes = cluster
+                .iter()
+                .filter(|(is_first, _)| !*is_first)
+                .map(|(_, difference)| difference)
+                .collect::<Vec<&RangeMapping>>();
+            let first_region_sequence = apply_differences(
+                &ancestor_sequence,
+                &ancestor_range,
+                &first_changes,
+                &first_sequence,
+            );
+            let second_region_sequence = apply_differences(
+                &ancestor_sequence,
+                &ancestor_range,
+                &second_changes,
+                &second_sequence,
+            );
+            let status = if second_changes.is_empty() {
+                ThreeWayStatus::OnlyFirst
+            } else if first_changes.is_empty() {
+                ThreeWayStatus::OnlySecond
+            } else if first_region_sequence == second_region_sequence {
+                ThreeWayStatus::Same
+            } else {
+                ThreeWayStatus::Conflicting
+            };
+            regions.push(ThreeWayRegion {
+                graph: graph.clone(),
+                ancestor_sequence: ancestor_sequence
+                    [ancestor_range.start as usize..ancestor_range.end as usize]
+                    .to_string(),
+                ancestor_range,
+                status,
+                first_sequence: first_region_sequence,
+                second_sequence: second_region_sequence,
+            });
+        }
+    }
+    regions
+}
+
+// The sequence a derived sample has over a range of the ancestor, given its differences within it.
+fn apply_differences(
+    ancestor_sequence: &str,
+    range: &Range,
+    differences: &[&RangeMapping],
+    derived_sequence: &str,
+) -> String {
+    let mut sequence = String::new();
+    let mut position = range.start as usize;
+    for difference in differences {
+        sequence.push_str(&ancestor_sequence[position..difference.source_range.start as usize]);
+        sequence.push_str(
+            &derived_sequence
+                [difference.target_range.start as usize..difference.target_range.end as usize],
+        );
+        position = difference.source_range.end as usize;
+    }
+    sequence.push_str(&ancestor_sequence[position..range.end as usize]);
+    sequence
+}
+
+pub fn write_three_way_tsv(regions: &[ThreeWayRegion], filename: &PathBuf) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(
+        writer,
+        "graph\tancestor_start\tancestor_end\tstatus\tancestor_sequence\tfirst_sequence\tsecond_sequence"
+    )?;
+    for region in regions {
+        writeln!(
+            writer,
+            "{graph}\t{start}\t{end}\t{status}\t{ancestor}\t{first}\t{second}",
+            graph = region.graph,
+            start = region.ancestor_range.start,
+            end = region.ancestor_range.end,
+            status = region.status,
+            ancestor = region.ancestor_sequence,
+            first = region.first_sequence,
+            second = region.second_sequence,
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_classifies_changes() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let aa_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let tt_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/tttttttt.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // both samples replace [15, 25) with AA, only the first replaces [2, 4) and the two samples
+        // replace [28, 30) differently. Changes are made from right to left so the coordinates of
+        // the ancestor still apply.
+        let mut derived_samples = HashSet::new();
+        for (sample, start, end, fasta) in [
+            ("first", 28, 30, &aa_path),
+            ("second", 28, 30, &tt_path),
+            ("first", 15, 25, &aa_path),
+            ("second", 15, 25, &aa_path),
+            ("first", 2, 4, &tt_path),
+        ] {
+            let parent = derived_samples.contains(sample).then_some(sample);
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                parent,
+                sample,
+                "m123",
+                start,
+                end,
+                fasta.to_str().unwrap(),
+            )
+            .unwrap();
+            derived_samples.insert(sample);
+        }
+
+        let regions = three_way_diff(conn, "test", None, Some("first"), Some("second"));
+        assert_eq!(
+            regions
+                .iter()
+                .map(|region| (
+                    region.ancestor_range.start,
+                    region.ancestor_range.end,
+                    region.status,
+                    region.first_sequence.clone(),
+                    region.second_sequence.clone(),
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    2,
+                    4,
+                    ThreeWayStatus::OnlyFirst,
+                    "TTTTTTTT".to_string(),
+                    "CG".to_string()
+                ),
+                (
+                    15,
+                    25,
+                    ThreeWayStatus::Same,
+                    "AA".to_string(),
+                    "AA".to_string()
+                ),
+                (
+                    28,
+                    30,
+                    ThreeWayStatus::Conflicting,
+                    "AA".to_string(),
+                    "TTTTTTTT".to_string()
+                ),
+            ]
+        );
+    }
+}
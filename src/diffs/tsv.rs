@@ -0,0 +1,119 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+// Writes the differences between the current paths of two samples as a tsv, one row per region of
+// the first sample that was replaced in the second. Coordinates are 0-based and end-exclusive, and
+// one of the two sides is empty for insertions and deletions. Graphs that only exist in one of the
+// samples are skipped.
+pub fn tsv_sample_diff(
+    conn: &Connection,
+    collection_name: &str,
+    filename: &PathBuf,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+
+    let source_paths_by_bg_name = Sample::get_block_groups(conn, collection_name, from_sample_name)
+        .iter()
+        .map(|bg| (bg.name.clone(), BlockGroup::get_current_path(conn, bg.id)))
+        .collect::<HashMap<String, Path>>();
+    let target_block_groups = Sample::get_block_groups(conn, collection_name, to_sample_name);
+
+    writeln!(
+        writer,
+        "graph\tsource_start\tsource_end\tsource_sequence\ttarget_start\ttarget_end\ttarget_sequence"
+    )?;
+    for bg in target_block_groups
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+    {
+        let Some(source_path) = source_paths_by_bg_name.get(&bg.name) else {
+            continue;
+        };
+        let target_path = BlockGroup::get_current_path(conn, bg.id);
+        let source_sequence = source_path.sequence(conn);
+        let target_sequence = target_path.sequence(conn);
+        for difference in source_path.find_differences(conn, &target_path) {
+            let source_range = difference.source_range;
+            let target_range = difference.target_range;
+            writeln!(
+                writer,
+                "{name}\t{source_start}\t{source_end}\t{source_allele}\t{target_start}\t{target_end}\t{target_allele}",
+                name = bg.name,
+                source_start = source_range.start,
+                source_end = source_range.end,
+                source_allele = &source_sequence[source_range.start as usize..source_range.end as usize],
+                target_start = target_range.start,
+                target_end = target_range.end,
+                target_allele = &target_sequence[target_range.start as usize..target_range.end as usize],
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tsv_diff() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("diff.tsv");
+        tsv_sample_diff(conn, "test", &output_path, None, Some("child sample")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "graph\tsource_start\tsource_end\tsource_sequence\ttarget_start\ttarget_end\ttarget_sequence\nm123\t15\t25\tGATCGGGAAC\t15\t17\tAA\n"
+        );
+
+        tsv_sample_diff(conn, "test", &output_path, Some("child sample"), None).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "graph\tsource_start\tsource_end\tsource_sequence\ttarget_start\ttarget_end\ttarget_sequence\nm123\t15\t17\tAA\t15\t25\tGATCGGGAAC\n"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use crate::range::Range;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+// Writes the differences between the current paths of two samples as a VCF, using the first sample
+// as the reference and the second as the only genotyped sample. Each graph is a contig, and graphs
+// that only exist in one of the samples are skipped.
+pub fn vcf_sample_diff(
+    conn: &Connection,
+    collection_name: &str,
+    filename: &PathBuf,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+
+    let target_paths_by_bg_name = Sample::get_block_groups(conn, collection_name, to_sample_name)
+        .iter()
+        .map(|bg| (bg.name.clone(), BlockGroup::get_current_path(conn, bg.id)))
+        .collect::<HashMap<String, Path>>();
+    let shared_paths = Sample::get_block_groups(conn, collection_name, from_sample_name)
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .filter_map(|bg| {
+            target_paths_by_bg_name.get(&bg.name).map(|target_path| {
+                (
+                    bg.name.clone(),
+                    BlockGroup::get_current_path(conn, bg.id),
+                    target_path,
+                )
+            })
+        })
+        .collect::<Vec<(String, Path, &Path)>>();
+
+    writeln!(writer, "##fileformat=VCFv4.2")?;
+    writeln!(writer, "##source=gen")?;
+    let mut sequences = vec![];
+    for (name, source_path, target_path) in shared_paths.iter() {
+        let source_sequence = source_path.sequence(conn);
+        writeln!(
+            writer,
+            "##contig=<ID={name},length={length}>",
+            length = source_sequence.len()
+        )?;
+        sequences.push((source_sequence, target_path.sequence(conn)));
+    }
+    writeln!(
+        writer,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    writeln!(
+        writer,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{sample}",
+        sample = Sample::display_name(to_sample_name)
+    )?;
+
+    for ((name, source_path, target_path), (source_sequence, target_sequence)) in
+        shared_paths.iter().zip(sequences.iter())
+    {
+        for difference in source_path.find_differences(conn, target_path) {
+            if let Some((position, ref_allele, alt_allele)) = vcf_alleles(
+                source_sequence,
+                target_sequence,
+                &difference.source_range,
+                &difference.target_range,
+            ) {
+                writeln!(
+                    writer,
+                    "{name}\t{position}\t.\t{ref_allele}\t{alt_allele}\t.\tPASS\t.\tGT\t1"
+                )?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+// Returns the 1-based position and the alleles for a replaced range. VCF alleles can't be empty, so
+// insertions and deletions are anchored on the base before them, or on the base after them at the
+// start of a contig.
+fn vcf_alleles(
+    source_sequence: &str,
+    target_sequence: &str,
+    source_range: &Range,
+    target_range: &Range,
+) -> Option<(i64, String, String)> {
+    let source_start = source_range.start as usize;
+    let source_end = source_range.end as usize;
+    let source_allele = &source_sequence[source_start..source_end];
+    let target_allele = &target_sequence[target_range.start as usize..target_range.end as usize];
+    if !source_allele.is_empty() && !target_allele.is_empty() {
+        Some((
+            source_range.start + 1,
+            source_allele.to_string(),
+            target_allele.to_string(),
+        ))
+    } else if source_start > 0 {
+        let anchor = &source_sequence[source_start - 1..source_start];
+        Some((
+            source_range.start,
+            format!("{anchor}{source_allele}"),
+            format!("{anchor}{target_allele}"),
+        ))
+    } else if source_end < source_sequence.len() {
+        let anchor = &source_sequence[source_end..source_end + 1];
+        Some((
+            1,
+            format!("{source_allele}{anchor}"),
+            format!("{target_allele}{anchor}"),
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_vcf_diff() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("diff.vcf");
+        vcf_sample_diff(conn, "test", &output_path, None, Some("child sample")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            [
+                "##fileformat=VCFv4.2",
+                "##source=gen",
+                "##contig=<ID=m123,length=34>",
+                "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">",
+                "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tchild sample",
+                "m123\t16\t.\tGATCGGGAAC\tAA\t.\tPASS\t.\tGT\t1",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_anchors_indels() {
+        let source = "ATCGATCG";
+        // deletion of [2, 4)
+        assert_eq!(
+            vcf_alleles(
+                source,
+                "ATATCG",
+                &Range { start: 2, end: 4 },
+                &Range { start: 2, end: 2 }
+            ),
+            Some((2, "TCG".to_string(), "T".to_string()))
+        );
+        // insertion at the start of the contig
+        assert_eq!(
+            vcf_alleles(
+                source,
+                "GGATCGATCG",
+                &Range { start: 0, end: 0 },
+                &Range { start: 0, end: 2 }
+            ),
+            Some((1, "A".to_string(), "GGA".to_string()))
+        );
+        // the whole contig was deleted
+        assert_eq!(
+            vcf_alleles(
+                source,
+                "",
+                &Range { start: 0, end: 8 },
+                &Range { start: 0, end: 0 }
+            ),
+            None
+        );
+    }
+}
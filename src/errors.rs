@@ -0,0 +1,116 @@
+use crate::annotations::coverage::CoverageError;
+use crate::annotations::gff::AnnotationError;
+use crate::clone::CloneError;
+use crate::config::RepositoryLockError;
+use crate::exports::msa::MsaError;
+use crate::fork::ForkError;
+use crate::fsck::FsckError;
+use crate::gc::GcError;
+use crate::genbank::GenBankError;
+use crate::graph_operators::{MergeError, SimplifyError};
+use crate::hydration::HydrationError;
+use crate::imports::fasta::FastaError;
+use crate::imports::library::LibraryError;
+use crate::imports::maf::MafError;
+use crate::imports::paf::PafError;
+use crate::models::block_group_lock::BlockGroupLockError;
+use crate::operation_management::OperationError;
+use crate::organism::OrganismError;
+use crate::patch::PatchError;
+use crate::range::RegionError;
+use crate::updates::edges::EdgeError;
+use crate::updates::fasta::FastaUpdateError;
+use crate::updates::manifest::ManifestError;
+use crate::updates::paths::PathError;
+use crate::updates::recipe::RecipeError;
+use crate::updates::vcf::VcfError;
+use crate::validate::ValidationError;
+use std::io;
+use thiserror::Error;
+
+// Top level error for callers driving gen as a library or through the CLI. Module specific
+// errors convert into this so handlers can use `?` regardless of which step failed.
+#[derive(Debug, Error)]
+pub enum GenError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{0}")]
+    Operation(#[from] OperationError),
+    #[error("{0}")]
+    Fasta(#[from] FastaError),
+    #[error("{0}")]
+    FastaUpdate(#[from] FastaUpdateError),
+    #[error("{0}")]
+    Vcf(#[from] VcfError),
+    #[error("{0}")]
+    GenBank(#[from] GenBankError),
+    #[error("{0}")]
+    Region(#[from] RegionError),
+    #[error("{0}")]
+    Annotation(#[from] AnnotationError),
+    #[error("{0}")]
+    Coverage(#[from] CoverageError),
+    #[error("{0}")]
+    Library(#[from] LibraryError),
+    #[error("{0}")]
+    Maf(#[from] MafError),
+    #[error("{0}")]
+    Paf(#[from] PafError),
+    #[error("{0}")]
+    Recipe(#[from] RecipeError),
+    #[error("{0}")]
+    Manifest(#[from] ManifestError),
+    #[error("{0}")]
+    Patch(#[from] PatchError),
+    #[error("{0}")]
+    Fork(#[from] ForkError),
+    #[error("{0}")]
+    Clone(#[from] CloneError),
+    #[error("{0}")]
+    Fsck(#[from] FsckError),
+    #[error("{0}")]
+    Gc(#[from] GcError),
+    #[error("{0}")]
+    Hydration(#[from] HydrationError),
+    #[error("{0}")]
+    BlockGroupLock(#[from] BlockGroupLockError),
+    #[error("{0}")]
+    Merge(#[from] MergeError),
+    #[error("{0}")]
+    Simplify(#[from] SimplifyError),
+    #[error("{0}")]
+    Edge(#[from] EdgeError),
+    #[error("{0}")]
+    Path(#[from] PathError),
+    #[error("{0}")]
+    Msa(#[from] MsaError),
+    #[error("{0}")]
+    Validation(#[from] ValidationError),
+    #[error("{0}")]
+    Organism(#[from] OrganismError),
+    #[error("{0}")]
+    RepositoryLock(#[from] RepositoryLockError),
+    #[error("Invalid Argument: {0}")]
+    InvalidArgument(String),
+    #[error("Not Found: {0}")]
+    NotFound(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wraps_module_errors() {
+        let err: GenError = FastaError::OperationError(OperationError::NoChanges).into();
+        assert!(matches!(
+            err,
+            GenError::Fasta(FastaError::OperationError(OperationError::NoChanges))
+        ));
+        assert_eq!(err.to_string(), "Operation Error: No Changes");
+        let err: GenError = io::Error::new(io::ErrorKind::NotFound, "missing.fa").into();
+        assert_eq!(err.to_string(), "IO Error: missing.fa");
+    }
+}
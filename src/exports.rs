@@ -1,3 +1,9 @@
+pub mod bed;
 pub mod fasta;
 pub mod genbank;
 pub mod gfa;
+pub mod liftover;
+pub mod mapping;
+pub mod msa;
+pub mod readme;
+pub mod sql;
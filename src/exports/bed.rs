@@ -0,0 +1,315 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::{Annotation, Path};
+use crate::models::sample::Sample;
+use crate::models::strand::Strand;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+// BED intervals are 0-based and half-open, which matches how coordinates are stored on paths, so
+// no conversion is needed when mapping them between samples.
+pub fn propagate_bed(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: &str,
+    bed_input_filename: &str,
+    bed_output_filename: &str,
+) -> io::Result<()> {
+    let reader = BufReader::new(File::open(bed_input_filename)?);
+    let mut writer = BufWriter::new(File::create(bed_output_filename)?);
+
+    let source_block_groups = Sample::get_block_groups(conn, collection_name, from_sample_name);
+    let target_block_groups = Sample::get_block_groups(conn, collection_name, Some(to_sample_name));
+    let source_paths_by_bg_name = source_block_groups
+        .iter()
+        .filter_map(|bg| {
+            Some((
+                bg.name.clone(),
+                BlockGroup::try_get_current_path(conn, bg.id)?,
+            ))
+        })
+        .collect::<HashMap<String, Path>>();
+
+    let mut path_mappings_by_bg_name = HashMap::new();
+    let mut sequence_lengths_by_bg_name = HashMap::new();
+    let mut circular_bg_names = HashSet::new();
+    for bg in target_block_groups.iter() {
+        let Some(target_path) = BlockGroup::try_get_current_path(conn, bg.id) else {
+            continue;
+        };
+        if let Some(source_path) = source_paths_by_bg_name.get(&bg.name) {
+            path_mappings_by_bg_name.insert(
+                bg.name.clone(),
+                source_path.get_mapping_tree(conn, &target_path),
+            );
+            sequence_lengths_by_bg_name
+                .insert(bg.name.clone(), target_path.sequence(conn).len() as i64);
+            if bg.is_circular {
+                circular_bg_names.insert(bg.name.clone());
+            }
+        }
+    }
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            writeln!(writer, "{line}")?;
+            continue;
+        }
+
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("BED line has fewer than 3 columns: {line}"),
+            ));
+        }
+        let path_name = fields[0];
+        let parse_coordinate = |value: &str| {
+            value.parse::<i64>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid BED coordinate {value} in line: {line}"),
+                )
+            })
+        };
+        let annotation = Annotation {
+            name: fields.get(3).unwrap_or(&"").to_string(),
+            start: parse_coordinate(fields[1])?,
+            end: parse_coordinate(fields[2])?,
+            strand: fields
+                .get(5)
+                .and_then(|strand| strand.parse().ok())
+                .unwrap_or(Strand::Unknown),
+        };
+
+        let (Some(mapping_tree), Some(sequence_length)) = (
+            path_mappings_by_bg_name.get(path_name),
+            sequence_lengths_by_bg_name.get(path_name),
+        ) else {
+            println!("No graph named {path_name} found for sample {to_sample_name}, skipping.");
+            continue;
+        };
+
+        // Annotations that don't overlap any shared sequence have no position in the target
+        // sample and are dropped.
+        if let Some(propagated_annotation) = Path::propagate_annotation(
+            annotation,
+            mapping_tree,
+            *sequence_length,
+            circular_bg_names.contains(path_name),
+        ) {
+            let mut output_fields = vec![
+                path_name.to_string(),
+                propagated_annotation.start.to_string(),
+                propagated_annotation.end.to_string(),
+            ];
+            output_fields.extend(fields[3..].iter().map(|field| field.to_string()));
+            // the strand column is flipped when the interval lands on inverted sequence
+            if output_fields.len() > 5 && propagated_annotation.strand != Strand::Unknown {
+                output_fields[5] = propagated_annotation.strand.to_string();
+            }
+            writeln!(writer, "{}", output_fields.join("\t"))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Writes the intervals of the reference's current paths where a sample departs from it, as BED.
+// Replaced intervals are named replacement:N>M for N reference bases replaced by M sample bases,
+// insertions are zero-length intervals at the insertion point named insertion:M, and deletions
+// cover the removed reference bases and are named deletion:N. Graphs only one of the samples has
+// are skipped.
+pub fn export_divergence_bed(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    reference_sample_name: Option<&str>,
+    bed_output_filename: &str,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(bed_output_filename)?);
+    let sample_paths_by_bg_name = Sample::get_block_groups(conn, collection_name, sample_name)
+        .iter()
+        .filter_map(|bg| {
+            Some((
+                bg.name.clone(),
+                BlockGroup::try_get_current_path(conn, bg.id)?,
+            ))
+        })
+        .collect::<HashMap<String, Path>>();
+
+    for bg in Sample::get_block_groups(conn, collection_name, reference_sample_name)
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+    {
+        let Some(sample_path) = sample_paths_by_bg_name.get(&bg.name) else {
+            continue;
+        };
+        let Some(reference_path) = BlockGroup::try_get_current_path(conn, bg.id) else {
+            continue;
+        };
+        for difference in reference_path.find_differences(conn, sample_path) {
+            let reference_range = difference.source_range;
+            let reference_length = reference_range.end - reference_range.start;
+            let sample_length = difference.target_range.end - difference.target_range.start;
+            let name = if reference_length == 0 {
+                format!("insertion:{sample_length}")
+            } else if sample_length == 0 {
+                format!("deletion:{reference_length}")
+            } else {
+                format!("replacement:{reference_length}>{sample_length}")
+            };
+            writeln!(
+                writer,
+                "{graph}\t{start}\t{end}\t{name}",
+                graph = bg.name,
+                start = reference_range.start,
+                end = reference_range.end,
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_simple_bed_propagate() {
+        setup_gen_dir();
+        let mut fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_path.push("fixtures/simple.fa");
+        let mut fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_update_path.push("fixtures/aa.fa");
+        let mut bed_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        bed_path.push("fixtures/simple.bed");
+        let conn = get_connection(None);
+        let db_uuid = metadata::get_db_uuid(&conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            &conn,
+            op_conn,
+        )
+        .unwrap();
+
+        let _ = update_with_fasta(
+            &conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        );
+
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let mut output_path = PathBuf::from(temp_dir.path());
+        output_path.push("output.bed");
+        propagate_bed(
+            &conn,
+            "test",
+            None,
+            "child sample",
+            bed_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        // The edit replaces [15, 25) with a 2 bp sequence, so the region shrinks to 26 bp, the
+        // gene on [5, 20) is truncated to [5, 15), and the feature entirely within the replaced
+        // region is dropped.
+        assert_eq!(
+            fs::read_to_string(output_path).unwrap(),
+            "track name=simple\n\
+             m123\t0\t26\tm123_region\t0\t+\n\
+             m123\t5\t15\tgene-a0001\t0\t+\n"
+        );
+    }
+
+    #[test]
+    fn test_divergence_bed() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let replacement_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let insertion_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            2,
+            2,
+            insertion_path.to_str().unwrap(),
+        )
+        .unwrap();
+        // the child's coordinates are shifted by the 8 bp insertion
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            Some("child"),
+            "child",
+            "m123",
+            23,
+            33,
+            replacement_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("child_divergence.bed");
+        let output_filename = output_path.to_str().unwrap();
+        export_divergence_bed(conn, "test", Some("child"), None, output_filename).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "m123\t2\t2\tinsertion:8\nm123\t15\t25\treplacement:10>2\n"
+        );
+
+        // with the child as the reference, the insertion becomes a deletion
+        export_divergence_bed(conn, "test", None, Some("child"), output_filename).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "m123\t2\t10\tdeletion:8\nm123\t23\t25\treplacement:2>10\n"
+        );
+    }
+}
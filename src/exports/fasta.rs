@@ -1,25 +1,40 @@
+use intervaltree::IntervalTree;
+use itertools::Itertools;
 use noodles::fasta;
 use rusqlite;
 use rusqlite::Connection;
+use std::collections::HashMap;
 use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::PathBuf;
 
+use crate::graph_order::GraphOrder;
+use crate::models::accession::Accession;
 use crate::models::block_group::BlockGroup;
+use crate::models::node::Node;
+use crate::models::path::{Path, PathBlock};
 use crate::models::sample::Sample;
+use crate::models::sequence::Molecule;
+use crate::range::Range;
 
 pub fn export_fasta(
     conn: &Connection,
     collection_name: &str,
     sample_name: Option<&str>,
     filename: &PathBuf,
+    graph_order: &GraphOrder,
 ) {
-    let block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    let mut block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    graph_order.sort_by_name(&mut block_groups, |block_group| &block_group.name);
 
     let file = File::create(filename).unwrap();
     let mut writer = fasta::io::Writer::new(file);
 
     for block_group in block_groups {
-        let path = BlockGroup::get_current_path(conn, block_group.id);
+        // graphs without a path have no sequence to write
+        let Some(path) = BlockGroup::try_get_current_path(conn, block_group.id) else {
+            continue;
+        };
 
         let definition = fasta::record::Definition::new(block_group.name, None);
         let sequence = fasta::record::Sequence::from(path.sequence(conn).into_bytes());
@@ -31,19 +46,274 @@ pub fn export_fasta(
     println!("Exported to file {}", filename.display());
 }
 
+// Writes each phased haplotype of every graph as its own record, named after the graph and the
+// haplotype's number counting from 1, e.g. `>chr1_hap1` and `>chr1_hap2`.
+pub fn export_haplotype_fasta(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    filename: &PathBuf,
+    graph_order: &GraphOrder,
+) -> io::Result<()> {
+    let mut block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    graph_order.sort_by_name(&mut block_groups, |block_group| &block_group.name);
+
+    let mut writer = fasta::io::Writer::new(File::create(filename)?);
+    for block_group in block_groups {
+        let haplotypes = BlockGroup::haplotype_sequences(conn, block_group.id);
+        for (index, sequence) in haplotypes.into_iter().enumerate() {
+            let definition = fasta::record::Definition::new(
+                format!("{}_hap{}", block_group.name, index + 1),
+                None,
+            );
+            let sequence = fasta::record::Sequence::from(sequence.into_bytes());
+            writer.write_record(&fasta::Record::new(definition, sequence))?;
+        }
+    }
+
+    println!("Exported to file {}", filename.display());
+    Ok(())
+}
+
+// The number of bases on each line of streamed FASTA records, as noodles writes records.
+const LINE_BASES: usize = 80;
+
+// Writes one FASTA record whose sequence comes in chunks, such as from Path::sequence_chunks,
+// wrapping lines as it goes so only one chunk is held at a time.
+pub fn write_fasta_chunks<W: Write>(
+    writer: &mut W,
+    name: &str,
+    chunks: impl Iterator<Item = String>,
+) -> io::Result<()> {
+    writeln!(writer, ">{name}")?;
+    let mut line_length = 0;
+    for chunk in chunks {
+        let mut bases = chunk.as_bytes();
+        while !bases.is_empty() {
+            let line_end = bases.len().min(LINE_BASES - line_length);
+            writer.write_all(&bases[..line_end])?;
+            line_length += line_end;
+            bases = &bases[line_end..];
+            if line_length == LINE_BASES {
+                writer.write_all(b"\n")?;
+                line_length = 0;
+            }
+        }
+    }
+    if line_length > 0 {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// The number of bases read from the database at a time when streaming a region.
+const REGION_CHUNK_BASES: i64 = 1 << 20;
+
+// Streams a range of a path as a FASTA record named after the region in 1-based coordinates, e.g.
+// `>chr1:101-200`.
+pub fn write_region_fasta<W: Write>(
+    conn: &Connection,
+    graph_name: &str,
+    path: &Path,
+    range: &Range,
+    writer: &mut W,
+) -> io::Result<()> {
+    let name = format!("{graph_name}:{}-{}", range.start + 1, range.end);
+    write_fasta_chunks(
+        writer,
+        &name,
+        path.sequence_chunks(conn, range, REGION_CHUNK_BASES),
+    )?;
+    writer.flush()
+}
+
+// Writes every accession in the collection as its own record. The description records where the
+// accession was defined so the file can be traced back to the graph it came from, e.g.
+// `>lp1 graph=m123 path=m123 sample=child`.
+pub fn export_accessions(conn: &Connection, collection_name: &str, filename: &PathBuf) {
+    let accessions = Accession::query_for_collection(conn, collection_name);
+
+    let file = File::create(filename).unwrap();
+    let mut writer = fasta::io::Writer::new(file);
+
+    for accession in accessions {
+        let path = Path::get(conn, accession.path_id);
+        let block_group = BlockGroup::get_by_id(conn, path.block_group_id);
+        let mut description = format!("graph={} path={}", block_group.name, path.name);
+        if let Some(sample_name) = block_group.sample_name {
+            description.push_str(&format!(" sample={sample_name}"));
+        }
+
+        let definition =
+            fasta::record::Definition::new(accession.name.clone(), Some(description.into_bytes()));
+        let sequence = fasta::record::Sequence::from(accession.sequence(conn).into_bytes());
+        let record = fasta::Record::new(definition, sequence);
+
+        let _ = writer.write_record(&record);
+    }
+
+    println!("Exported accessions to file {}", filename.display());
+}
+
+// Writes the sequence of every interval in a BED file as its own record, named after the BED name
+// column or `graph:start-end` when there isn't one. Intervals with a - in the strand column are
+// reverse complemented. The blocks of each graph's current path are only loaded once, however many
+// intervals fall on it.
+pub fn export_bed_sequences(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    bed_filename: &PathBuf,
+    filename: &PathBuf,
+) -> io::Result<()> {
+    let reader = BufReader::new(File::open(bed_filename)?);
+    let mut writer = fasta::io::Writer::new(File::create(filename)?);
+
+    let block_groups_by_name = Sample::get_block_groups(conn, collection_name, sample_name)
+        .into_iter()
+        .map(|block_group| (block_group.name.clone(), block_group))
+        .collect::<HashMap<_, _>>();
+    let mut block_trees_by_name: HashMap<String, (IntervalTree<i64, PathBlock>, i64)> =
+        HashMap::new();
+    // the molecule of each node, so the reverse strand of a block is that of its molecule
+    let mut molecules_by_node_id: HashMap<i64, Molecule> = HashMap::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 3 {
+            return Err(invalid(format!(
+                "BED line has fewer than 3 columns: {line}"
+            )));
+        }
+        let graph_name = fields[0];
+        let parse_coordinate = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| invalid(format!("Invalid BED coordinate {value} in line: {line}")))
+        };
+        let start = parse_coordinate(fields[1])?;
+        let end = parse_coordinate(fields[2])?;
+
+        if !block_trees_by_name.contains_key(graph_name) {
+            let block_group = block_groups_by_name
+                .get(graph_name)
+                .ok_or_else(|| invalid(format!("No graph named {graph_name} found")))?;
+            let blocks = BlockGroup::try_get_current_path(conn, block_group.id)
+                .ok_or_else(|| invalid(format!("Graph {graph_name} has no path")))?
+                .blocks(conn);
+            let length = blocks
+                .iter()
+                .filter(|block| block.id >= 0)
+                .map(|block| block.path_end)
+                .max()
+                .unwrap_or(0);
+            let node_ids = blocks
+                .iter()
+                .filter(|block| block.id >= 0)
+                .map(|block| block.node_id)
+                .unique()
+                .collect::<Vec<i64>>();
+            molecules_by_node_id.extend(
+                Node::get_sequences_by_node_ids(conn, &node_ids)
+                    .into_iter()
+                    .map(|(node_id, sequence)| (node_id, sequence.molecule())),
+            );
+            // The start and end blocks only hold the ends of the path and have no sequence.
+            let tree = blocks
+                .into_iter()
+                .filter(|block| block.id >= 0)
+                .map(|block| (block.path_start..block.path_end, block))
+                .collect::<IntervalTree<i64, PathBlock>>();
+            block_trees_by_name.insert(graph_name.to_string(), (tree, length));
+        }
+        let (tree, length) = &block_trees_by_name[graph_name];
+        if start < 0 || start > end || end > *length {
+            return Err(invalid(format!(
+                "Interval {start}-{end} is outside of {graph_name} (length {length})"
+            )));
+        }
+
+        let slices = tree
+            .query(start..end)
+            .map(|element| &element.value)
+            .sorted_by_key(|block| block.path_start)
+            .map(|block| {
+                let block_start = (start.max(block.path_start) - block.path_start) as usize;
+                let block_end = (end.min(block.path_end) - block.path_start) as usize;
+                (block.node_id, &block.block_sequence[block_start..block_end])
+            })
+            .collect::<Vec<(i64, &str)>>();
+        let sequence = if fields.get(5) == Some(&"-") {
+            slices
+                .iter()
+                .rev()
+                .map(|(node_id, slice)| molecules_by_node_id[node_id].reverse_complement(slice))
+                .collect::<String>()
+        } else {
+            slices.iter().map(|(_, slice)| *slice).collect::<String>()
+        };
+
+        let name = match fields.get(3) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("{graph_name}:{start}-{end}"),
+        };
+        let definition = fasta::record::Definition::new(name, None);
+        let record = fasta::Record::new(
+            definition,
+            fasta::record::Sequence::from(sequence.into_bytes()),
+        );
+        writer.write_record(&record)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
-    use crate::imports::fasta::import_fasta;
+    use crate::imports::fasta::{import_fasta, import_fasta_with_type};
     use crate::models::{metadata, operations::setup_db};
     use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
     use crate::updates::fasta::update_with_fasta;
+    use crate::updates::vcf::update_with_vcf;
     use noodles::fasta;
+    use std::fs;
     use std::path::PathBuf;
     use std::{io, str};
     use tempfile;
 
+    #[test]
+    fn test_write_fasta_chunks() {
+        let mut output = vec![];
+        let chunks = vec!["A".repeat(50), "C".repeat(50), "G".repeat(60)];
+        write_fasta_chunks(&mut output, "chr1:1-160", chunks.into_iter()).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                ">chr1:1-160\n{}{}\n{}{}\n",
+                "A".repeat(50),
+                "C".repeat(30),
+                "C".repeat(20),
+                "G".repeat(60)
+            )
+        );
+
+        let mut output = vec![];
+        write_fasta_chunks(&mut output, "empty", std::iter::empty()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), ">empty\n");
+    }
+
     #[test]
     fn test_import_then_export() {
         setup_gen_dir();
@@ -65,23 +335,22 @@ mod tests {
             op_conn,
         )
         .unwrap();
+        // a graph without a path is left out of the export
+        BlockGroup::create(conn, &collection, None, "empty");
         let tmp_dir = tempfile::tempdir().unwrap().into_path();
         let filename = tmp_dir.join("out.fa");
-        export_fasta(conn, &collection, None, &filename);
+        export_fasta(conn, &collection, None, &filename, &GraphOrder::Natural);
 
         let mut fasta_reader = fasta::io::reader::Builder
             .build_from_path(filename)
             .unwrap();
-        let record = fasta_reader
+        let records = fasta_reader
             .records()
-            .next()
-            .ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidData, "No records found in fasta file")
-            })
-            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
             .unwrap();
+        assert_eq!(records.len(), 1);
 
-        let sequence = str::from_utf8(record.sequence().as_ref())
+        let sequence = str::from_utf8(records[0].sequence().as_ref())
             .unwrap()
             .to_string();
         assert_eq!(sequence, "ATCGATCGATCGATCGATCGGGAACACACAGAGA");
@@ -129,7 +398,13 @@ mod tests {
 
         let tmp_dir = tempfile::tempdir().unwrap().into_path();
         let filename = tmp_dir.join("out.fa");
-        export_fasta(conn, &collection, Some("child sample"), &filename);
+        export_fasta(
+            conn,
+            &collection,
+            Some("child sample"),
+            &filename,
+            &GraphOrder::Natural,
+        );
 
         let mut fasta_reader = fasta::io::reader::Builder
             .build_from_path(filename)
@@ -148,4 +423,163 @@ mod tests {
             .to_string();
         assert_eq!(sequence, "ATAAAAAAAATCGATCGATCGATCGGGAACACACAGAGA");
     }
+
+    #[test]
+    fn test_export_haplotypes() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/phased.vcf");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        let collection = "test".to_string();
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            &collection,
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // foo is 1|0 for a deletion, 0|1 for an insertion and 0/1 for a SNP, which being unphased
+        // is on neither haplotype
+        update_with_vcf(
+            &vcf_path.to_str().unwrap().to_string(),
+            &collection,
+            "".to_string(),
+            "".to_string(),
+            conn,
+            op_conn,
+            None,
+        )
+        .unwrap();
+
+        let tmp_dir = tempfile::tempdir().unwrap().into_path();
+        let filename = tmp_dir.join("out.fa");
+        export_haplotype_fasta(
+            conn,
+            &collection,
+            Some("foo"),
+            &filename,
+            &GraphOrder::Natural,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&filename).unwrap(),
+            ">m123_hap1\nATCATCGATCGATCGATCGGGAACACACAGAGA\n>m123_hap2\nATCGATCGATAGAGATCGATCGGGAACACACAGAGA\n"
+        );
+
+        // without phased edges, the one haplotype is the current path
+        update_with_fasta(
+            conn,
+            op_conn,
+            &collection,
+            None,
+            "child",
+            "m123",
+            2,
+            5,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let block_group_id = BlockGroup::get_id(conn, &collection, Some("child"), "m123");
+        assert_eq!(
+            BlockGroup::haplotype_sequences(conn, block_group_id),
+            vec![BlockGroup::get_current_path(conn, block_group_id).sequence(conn)]
+        );
+    }
+
+    #[test]
+    fn test_export_bed_sequences() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let bed_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.bed");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let filename = tmp_dir.path().join("out.fa");
+        export_bed_sequences(conn, "test", None, &bed_path, &filename).unwrap();
+        assert_eq!(
+            fs::read_to_string(&filename).unwrap(),
+            ">m123_region\nATCGATCGATCGATCGATCGGGAACACACAGAGA\n>gene-a0001\nTCGATCGATCGATCG\n>insert-site\nTCGGG\n"
+        );
+
+        // intervals spanning the inserted sequence, one of them unnamed and on the reverse strand
+        let child_bed_path = tmp_dir.path().join("child.bed");
+        fs::write(&child_bed_path, "m123\t5\t20\tspan\nm123\t14\t17\t\t0\t-\n").unwrap();
+        export_bed_sequences(
+            conn,
+            "test",
+            Some("child sample"),
+            &child_bed_path,
+            &filename,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&filename).unwrap(),
+            ">span\nTCGATCGATCAAACA\n>m123:14-17\nTTG\n"
+        );
+
+        // the child's sequence is shorter than the first interval
+        assert!(
+            export_bed_sequences(conn, "test", Some("child sample"), &bed_path, &filename).is_err()
+        );
+    }
+
+    #[test]
+    fn test_export_bed_sequences_of_rna() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let fasta_path = tmp_dir.path().join("rna.fa");
+        fs::write(&fasta_path, ">transcript\nACGUUAGC\n").unwrap();
+        import_fasta_with_type(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            "RNA",
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        // the reverse strand of RNA is read with U, not T
+        let bed_path = tmp_dir.path().join("rna.bed");
+        fs::write(&bed_path, "transcript\t0\t5\tminus\t0\t-\n").unwrap();
+        let filename = tmp_dir.path().join("out.fa");
+        export_bed_sequences(conn, "test", None, &bed_path, &filename).unwrap();
+        assert_eq!(fs::read_to_string(&filename).unwrap(), ">minus\nAACGU\n");
+    }
 }
@@ -4,8 +4,9 @@ use crate::models::block_group::BlockGroup;
 use crate::models::node::Node;
 use crate::models::path::PathBlock;
 use crate::models::sample::Sample;
+use crate::models::sequence::Molecule;
 use gb_io;
-use gb_io::seq::Location;
+use gb_io::seq::{Location, Topology};
 use gb_io::QualifierKey;
 use itertools::Itertools;
 use petgraph::prelude::DiGraphMap;
@@ -150,7 +151,9 @@ pub fn export_genbank(
     let mut writer = gb_io::writer::SeqWriter::new(file);
 
     for block_group in block_groups.iter() {
-        let path = BlockGroup::get_current_path(conn, block_group.id);
+        let Some(path) = BlockGroup::try_get_current_path(conn, block_group.id) else {
+            continue;
+        };
         let path_blocks = path
             .blocks(conn)
             .into_iter()
@@ -159,6 +162,17 @@ pub fn export_genbank(
         let mut seq = gb_io::seq::Seq::empty();
         seq.name = Some(block_group.name.clone());
         seq.seq = path.sequence(conn).into_bytes();
+        // The LOCUS line is parsed by column, so a blank molecule type would be read back as the
+        // topology. Types GenBank does not know, like aa, are written as the molecule they are.
+        seq.molecule_type = path_blocks.first().map(|block| {
+            Molecule::genbank_molecule_type(
+                &Node::get_sequences_by_node_ids(conn, &[block.node_id])[&block.node_id]
+                    .sequence_type,
+            )
+        });
+        if block_group.is_circular {
+            seq.topology = Topology::Circular;
+        }
 
         // Identify the node traversal corresponding to our path.
         let graph = BlockGroup::get_graph(conn, block_group.id);
@@ -330,6 +344,7 @@ mod tests {
         let b = reader::parse_file(b).unwrap();
         let b_seq = str::from_utf8(&b[0].seq).unwrap().to_string();
         assert_eq!(a_seq, b_seq);
+        assert_eq!(a[0].topology, b[0].topology);
 
         let mut a_features = vec![];
         for feature in a[0].features.iter() {
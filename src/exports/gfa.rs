@@ -1,31 +1,59 @@
-use crate::gfa::{path_line, write_links, write_segments, Link, Path as GFAPath, Segment};
+use crate::errors::GenError;
+use crate::gfa::{
+    path_line, walk_line, write_link_with_tags, write_links, write_segment_with_tags,
+    write_segments, Link, Path as GFAPath, Segment, Walk,
+};
+use crate::graph::{GraphEdge, GraphNode};
+use crate::graph_order::GraphOrder;
+use crate::models::traits::Query;
 use crate::models::{
+    attribute::AttributeValue,
     block_group::BlockGroup,
     block_group_edge::BlockGroupEdge,
     collection::Collection,
     edge::{Edge, GroupBlock},
-    node::Node,
+    node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID},
+    operations::ExportedSegment,
     path::Path,
     path_edge::PathEdge,
+    path_index::PathIndex,
     sample::Sample,
     strand::Strand,
 };
+use crate::progress_bar::{get_eta_progress_bar, get_handler, get_time_elapsed_bar};
+use crate::range::Range;
+use indicatif::MultiProgress;
 use itertools::Itertools;
-use rusqlite::Connection;
-use std::collections::{HashMap, HashSet};
+use petgraph::graphmap::DiGraphMap;
+use petgraph::visit::{Dfs, Reversed};
+use petgraph::Direction;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use std::rc::Rc;
+
+// A conservative rate for writing a GFA export, for estimating how long one takes.
+const ESTIMATED_GFA_BYTES_PER_SECOND: f64 = 20_000_000.0;
+
+// Links are written in chunks, as there are usually many and each is short.
+const LINK_CHUNK_SIZE: usize = 10_000;
 
 pub fn export_gfa(
     conn: &Connection,
     collection_name: &str,
     filename: &PathBuf,
     sample_name: Option<String>,
-) {
+    graph_order: &GraphOrder,
+) -> Vec<ExportedSegment> {
     // General note about how we encode segment IDs.  The node ID and the start coordinate in the
     // sequence are all that's needed, because the end coordinate can be inferred from the length of
     // the segment's sequence.  So the segment ID is of the form <node ID>.<start coordinate>
+    let progress_bar = get_handler();
+    let bar = progress_bar.add(get_time_elapsed_bar());
+    bar.set_message("Collecting graph");
     let block_groups = Collection::get_block_groups(conn, collection_name);
 
     let mut edge_set = HashSet::new();
@@ -50,19 +78,60 @@ pub fn export_gfa(
     let mut edges = edge_set.into_iter().collect::<Vec<_>>();
 
     let mut blocks = Edge::blocks_from_edges(conn, &edges);
-    blocks.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+    blocks.sort_by_key(|block| block.node_id);
     let boundary_edges = Edge::boundary_edges_from_sequences(&blocks);
     edges.extend(boundary_edges.clone());
 
     let (mut graph, _edges_by_node_pair) = Edge::build_graph(&edges, &blocks);
 
     BlockGroup::prune_graph(&mut graph);
+    bar.finish();
 
-    let file = File::create(filename).unwrap();
-    let mut writer = BufWriter::new(file);
+    let mut writer = BufWriter::new(File::create(filename).unwrap());
+    let segments = write_graph(&mut writer, conn, &graph, &blocks, &progress_bar);
+    write_paths(
+        &mut writer,
+        conn,
+        collection_name,
+        &blocks,
+        graph_order,
+        &progress_bar,
+    );
+
+    segments.iter().map(Segment::exported).collect()
+}
+
+// The attributes of nodes or edges as GFA optional fields, leaving out those whose keys can't be
+// GFA tags.
+fn gfa_tags(
+    attributes: HashMap<i64, BTreeMap<String, AttributeValue>>,
+) -> HashMap<i64, Vec<String>> {
+    attributes
+        .into_iter()
+        .map(|(id, attributes)| {
+            (
+                id,
+                attributes
+                    .iter()
+                    .filter_map(|(key, value)| value.gfa_tag(key))
+                    .collect(),
+            )
+        })
+        .collect()
+}
 
+// Writes the segments and links of a pruned graph, returning the segments written. Node attributes
+// are written as optional fields of every segment of the node, and edge attributes as those of the
+// edge's link.
+fn write_graph(
+    writer: &mut BufWriter<File>,
+    conn: &Connection,
+    graph: &DiGraphMap<GraphNode, GraphEdge>,
+    blocks: &[GroupBlock],
+    progress_bar: &MultiProgress,
+) -> Vec<Segment> {
     let mut segments = vec![];
-    for block in &blocks {
+    for block in blocks {
         if !Node::is_terminal(block.node_id) {
             segments.push(Segment {
                 sequence: block.sequence(),
@@ -74,7 +143,31 @@ pub fn export_gfa(
             });
         }
     }
-    write_segments(&mut writer, &segments);
+    let node_tags = gfa_tags(Node::attributes_for_nodes(
+        conn,
+        &segments
+            .iter()
+            .map(|segment| segment.node_id)
+            .unique()
+            .collect::<Vec<i64>>(),
+    ));
+    // progress is counted in bases, as a few long segments can take most of the time
+    let bar = progress_bar.add(get_eta_progress_bar(
+        segments
+            .iter()
+            .map(|segment| segment.sequence.len() as u64)
+            .sum(),
+    ));
+    bar.set_message("Segment bases written");
+    for segment in segments.iter() {
+        write_segment_with_tags(
+            writer,
+            segment,
+            node_tags.get(&segment.node_id).map_or(&[], Vec::as_slice),
+        );
+        bar.inc(segment.sequence.len() as u64);
+    }
+    bar.finish();
 
     let mut links = vec![];
     for (source, target, edge_info) in graph.all_edges() {
@@ -92,16 +185,687 @@ pub fn export_gfa(
                 strand: edge_info.target_strand,
             };
 
-            links.push(Link {
-                source_segment_id: source_segment.segment_id(),
-                source_strand: edge_info.source_strand,
-                target_segment_id: target_segment.segment_id(),
-                target_strand: edge_info.target_strand,
+            links.push((
+                Link {
+                    source_segment_id: source_segment.segment_id(),
+                    source_strand: edge_info.source_strand,
+                    target_segment_id: target_segment.segment_id(),
+                    target_strand: edge_info.target_strand,
+                },
+                edge_info.edge_id,
+            ));
+        }
+    }
+    let edge_tags = gfa_tags(Edge::attributes_for_edges(
+        conn,
+        &links
+            .iter()
+            .map(|(_, edge_id)| *edge_id)
+            .unique()
+            .collect::<Vec<i64>>(),
+    ));
+    let bar = progress_bar.add(get_eta_progress_bar(links.len() as u64));
+    bar.set_message("Links written");
+    for chunk in links.chunks(LINK_CHUNK_SIZE) {
+        for (link, edge_id) in chunk {
+            write_link_with_tags(
+                writer,
+                link,
+                edge_tags.get(edge_id).map_or(&[], Vec::as_slice),
+            );
+        }
+        bar.inc(chunk.len() as u64);
+    }
+    bar.finish();
+    segments
+}
+
+// Exports a single graph with its paths, such as one graph of a sample.
+pub fn export_block_group_gfa(
+    conn: &Connection,
+    block_group_id: i64,
+    filename: &PathBuf,
+) -> Vec<ExportedSegment> {
+    let progress_bar = get_handler();
+    let mut edges = BlockGroupEdge::edges_for_block_group(conn, block_group_id);
+    let mut blocks = Edge::blocks_from_edges(conn, &edges);
+    blocks.sort_by_key(|block| block.node_id);
+    edges.extend(Edge::boundary_edges_from_sequences(&blocks));
+    let (mut graph, _edges_by_node_pair) = Edge::build_graph(&edges, &blocks);
+    BlockGroup::prune_graph(&mut graph);
+
+    let mut writer = BufWriter::new(File::create(filename).unwrap());
+    let segments = write_graph(&mut writer, conn, &graph, &blocks, &progress_bar);
+    let block_group = BlockGroup::get_by_id(conn, block_group_id);
+    let paths = Path::query(
+        conn,
+        "select * from paths where block_group_id = ?1 order by id;",
+        params!(block_group_id),
+    );
+    write_path_lines(
+        &mut writer,
+        conn,
+        paths,
+        &HashMap::from([(block_group_id, block_group)]),
+        &blocks,
+        &progress_bar,
+    );
+    segments.iter().map(Segment::exported).collect()
+}
+
+// Exports the part of a graph a range of one of its paths, the backbone, spans: every block reachable
+// from the start of the range that also leads to its end, with the blocks at either end trimmed to
+// the range. The backbone is written as the only path, named for the range it covers, since the
+// other paths may leave the exported blocks.
+pub fn export_region_gfa(
+    conn: &Connection,
+    block_group_id: i64,
+    backbone: &Path,
+    range: &Range,
+    filename: &PathBuf,
+) -> Vec<ExportedSegment> {
+    let mut edges = BlockGroupEdge::edges_for_block_group(conn, block_group_id);
+    let mut blocks = Edge::blocks_from_edges(conn, &edges);
+    blocks.sort_by_key(|block| block.node_id);
+    edges.extend(Edge::boundary_edges_from_sequences(&blocks));
+    let (mut graph, _edges_by_node_pair) = Edge::build_graph(&edges, &blocks);
+    BlockGroup::prune_graph(&mut graph);
+
+    let mut graph_nodes_by_node_id: HashMap<i64, Vec<GraphNode>> = HashMap::new();
+    for node in graph.nodes() {
+        graph_nodes_by_node_id
+            .entry(node.node_id)
+            .or_default()
+            .push(node);
+    }
+    for nodes in graph_nodes_by_node_id.values_mut() {
+        nodes.sort_by_key(|node| node.sequence_start);
+    }
+
+    // The blocks of the graph the backbone goes through within the range, in path order, with the
+    // part of each block's sequence the range covers.
+    let mut steps = vec![];
+    for block in PathIndex::blocks_in_range(conn, backbone.id, range.start, range.end) {
+        let start = block.start.max(range.start) - block.start;
+        let end = block.end.min(range.end) - block.start;
+        let (sequence_start, sequence_end) = if block.strand == Strand::Reverse {
+            (block.sequence_end - end, block.sequence_end - start)
+        } else {
+            (block.sequence_start + start, block.sequence_start + end)
+        };
+        let nodes = graph_nodes_by_node_id[&block.node_id]
+            .iter()
+            .filter(|node| node.sequence_start < sequence_end && node.sequence_end > sequence_start)
+            .map(|node| {
+                (
+                    *node,
+                    node.sequence_start.max(sequence_start),
+                    node.sequence_end.min(sequence_end),
+                    block.strand,
+                )
+            })
+            .collect::<Vec<_>>();
+        if block.strand == Strand::Reverse {
+            steps.extend(nodes.into_iter().rev());
+        } else {
+            steps.extend(nodes);
+        }
+    }
+    let (Some(first_step), Some(last_step)) = (steps.first().copied(), steps.last().copied())
+    else {
+        File::create(filename).unwrap();
+        return vec![];
+    };
+
+    let mut reachable = HashSet::new();
+    let mut dfs = Dfs::new(&graph, first_step.0);
+    while let Some(node) = dfs.next(&graph) {
+        reachable.insert(node);
+    }
+    let reversed_graph = Reversed(&graph);
+    let mut dfs = Dfs::new(reversed_graph, last_step.0);
+    let mut retained: HashMap<GraphNode, (i64, i64)> = HashMap::new();
+    while let Some(node) = dfs.next(reversed_graph) {
+        if reachable.contains(&node) && !Node::is_terminal(node.node_id) {
+            retained.insert(node, (node.sequence_start, node.sequence_end));
+        }
+    }
+    // only the blocks at the ends of the range are trimmed, keeping the sequence of both ends if
+    // the backbone enters the same block twice
+    if first_step.0 == last_step.0 {
+        retained.insert(
+            first_step.0,
+            (first_step.1.min(last_step.1), first_step.2.max(last_step.2)),
+        );
+    } else {
+        retained.insert(first_step.0, (first_step.1, first_step.2));
+        retained.insert(last_step.0, (last_step.1, last_step.2));
+    }
+    let segment_id = |node: &GraphNode| format!("{}.{}", node.node_id, retained[node].0);
+
+    let blocks_by_id = blocks
+        .iter()
+        .map(|block| (block.id, block))
+        .collect::<HashMap<i64, &GroupBlock>>();
+    let segments = retained
+        .iter()
+        .sorted_by_key(|(node, _)| (node.node_id, node.sequence_start))
+        .map(|(node, (start, end))| Segment {
+            sequence: blocks_by_id[&node.block_id].sequence()
+                [(start - node.sequence_start) as usize..(end - node.sequence_start) as usize]
+                .to_string(),
+            node_id: node.node_id,
+            sequence_start: *start,
+            strand: Strand::Forward,
+        })
+        .collect::<Vec<Segment>>();
+    let links = graph
+        .all_edges()
+        .filter(|(source, target, _)| {
+            retained.contains_key(source) && retained.contains_key(target)
+        })
+        .map(|(source, target, edge_info)| {
+            (
+                Link {
+                    source_segment_id: segment_id(&source),
+                    source_strand: edge_info.source_strand,
+                    target_segment_id: segment_id(&target),
+                    target_strand: edge_info.target_strand,
+                },
+                edge_info.edge_id,
+            )
+        })
+        .sorted_by(|(link1, _), (link2, _)| {
+            (&link1.source_segment_id, &link1.target_segment_id)
+                .cmp(&(&link2.source_segment_id, &link2.target_segment_id))
+        })
+        .collect::<Vec<(Link, i64)>>();
+
+    let node_tags = gfa_tags(Node::attributes_for_nodes(
+        conn,
+        &segments
+            .iter()
+            .map(|segment| segment.node_id)
+            .unique()
+            .collect::<Vec<i64>>(),
+    ));
+    let edge_tags = gfa_tags(Edge::attributes_for_edges(
+        conn,
+        &links
+            .iter()
+            .map(|(_, edge_id)| *edge_id)
+            .unique()
+            .collect::<Vec<i64>>(),
+    ));
+    let mut writer = BufWriter::new(File::create(filename).unwrap());
+    for segment in segments.iter() {
+        write_segment_with_tags(
+            &mut writer,
+            segment,
+            node_tags.get(&segment.node_id).map_or(&[], Vec::as_slice),
+        );
+    }
+    for (link, edge_id) in links.iter() {
+        write_link_with_tags(
+            &mut writer,
+            link,
+            edge_tags.get(edge_id).map_or(&[], Vec::as_slice),
+        );
+    }
+
+    // the range is named like a 1-based region string, so it can be passed back to gen
+    let block_group = BlockGroup::get_by_id(conn, block_group_id);
+    let path_name = match block_group.sample_name {
+        Some(sample_name) if !sample_name.is_empty() => format!("{}.{sample_name}", backbone.name),
+        _ => backbone.name.clone(),
+    };
+    let path = GFAPath {
+        name: format!("{path_name}:{}-{}", range.start + 1, range.end),
+        segment_ids: steps.iter().map(|(node, ..)| segment_id(node)).collect(),
+        node_strands: steps.iter().map(|(.., strand)| *strand).collect(),
+    };
+    writer.write_all(&path_line(&path).into_bytes()).unwrap();
+
+    segments.iter().map(Segment::exported).collect()
+}
+
+// Exports the graphs of a sample with a walk for each phased haplotype of every graph instead of
+// its paths, the input vg builds a GBWT or GBZ from (vg gbwt -G graph.gfa --gbz-format -g
+// graph.gbz). Haplotypes are walked as BlockGroup::haplotype_sequences does and numbered from 1,
+// like the records of export_haplotype_fasta.
+pub fn export_haplotype_gfa(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    filename: &PathBuf,
+    graph_order: &GraphOrder,
+) -> Vec<ExportedSegment> {
+    let progress_bar = get_handler();
+    let mut block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    graph_order.sort_by_name(&mut block_groups, |block_group| &block_group.name);
+
+    let mut edges = block_groups
+        .iter()
+        .flat_map(|block_group| BlockGroupEdge::edges_for_block_group(conn, block_group.id))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut blocks = Edge::blocks_from_edges(conn, &edges);
+    blocks.sort_by_key(|block| block.node_id);
+    edges.extend(Edge::boundary_edges_from_sequences(&blocks));
+    let (mut graph, _edges_by_node_pair) = Edge::build_graph(&edges, &blocks);
+    BlockGroup::prune_graph(&mut graph);
+
+    let mut writer = BufWriter::new(File::create(filename).unwrap());
+    let segments = write_graph(&mut writer, conn, &graph, &blocks, &progress_bar);
+
+    // the blocks of each graph may be split further where the graphs of the sample differ, so a
+    // block of a walk is written as every segment within it
+    let mut blocks_by_node_id: HashMap<i64, Vec<&GroupBlock>> = HashMap::new();
+    for block in blocks.iter() {
+        blocks_by_node_id
+            .entry(block.node_id)
+            .or_default()
+            .push(block);
+    }
+    for node_blocks in blocks_by_node_id.values_mut() {
+        node_blocks.sort_by_key(|block| block.start);
+    }
+    let bar = progress_bar.add(get_eta_progress_bar(block_groups.len() as u64));
+    bar.set_message("Graphs written");
+    for block_group in block_groups.iter() {
+        for (index, walk) in BlockGroup::haplotype_walks(conn, block_group.id)
+            .into_iter()
+            .enumerate()
+        {
+            let mut segment_ids = vec![];
+            let mut node_strands = vec![];
+            let mut length = 0;
+            for (node, strand) in walk {
+                if Node::is_terminal(node.node_id) {
+                    continue;
+                }
+                let node_blocks = blocks_by_node_id[&node.node_id]
+                    .iter()
+                    .filter(|block| {
+                        block.start >= node.sequence_start && block.end <= node.sequence_end
+                    })
+                    .map(|block| format!("{}.{}", block.node_id, block.start))
+                    .collect::<Vec<String>>();
+                node_strands.extend(vec![strand; node_blocks.len()]);
+                if strand == Strand::Reverse {
+                    segment_ids.extend(node_blocks.into_iter().rev());
+                } else {
+                    segment_ids.extend(node_blocks);
+                }
+                length += node.sequence_end - node.sequence_start;
+            }
+            let walk = Walk {
+                sample: Sample::display_name(sample_name).to_string(),
+                haplotype_index: index as i64 + 1,
+                sequence_name: block_group.name.clone(),
+                length,
+                segment_ids,
+                node_strands,
+            };
+            writer
+                .write_all(&walk_line(&walk).into_bytes())
+                .unwrap_or_else(|_| {
+                    panic!("Error writing walk of {} to GFA stream", block_group.name)
+                });
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    segments.iter().map(Segment::exported).collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GfaExportEstimate {
+    pub segment_count: i64,
+    pub link_count: i64,
+    pub path_count: i64,
+    pub path_step_count: i64,
+    pub size: i64,
+}
+
+impl GfaExportEstimate {
+    pub fn seconds(&self) -> f64 {
+        self.size as f64 / ESTIMATED_GFA_BYTES_PER_SECOND
+    }
+}
+
+// Predicts what export_gfa would write, from the stored nodes, edges and path indexes instead of
+// the graph itself. Nodes become a segment for every stretch between the positions edges leave or
+// enter them at, and each path has a step for each of those segments it passes. Segments and links
+// that export_gfa prunes are still counted, so the estimate errs on the large side.
+pub fn estimate_gfa_export(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+) -> GfaExportEstimate {
+    let block_groups = match sample_name {
+        // like export_gfa, a sample's export only covers its first graph
+        Some(sample_name) => Sample::get_block_groups(conn, collection_name, Some(sample_name))
+            .into_iter()
+            .take(1)
+            .collect(),
+        None => Collection::get_block_groups(conn, collection_name),
+    };
+    let block_group_ids = block_groups
+        .iter()
+        .map(|block_group| Value::from(block_group.id))
+        .collect::<Vec<Value>>();
+    let sample_names = Collection::get_block_groups(conn, collection_name)
+        .into_iter()
+        .map(|block_group| (block_group.id, block_group.sample_name))
+        .collect::<HashMap<i64, Option<String>>>();
+    let paths = Path::query_for_collection(conn, collection_name);
+    let path_block_count = paths
+        .iter()
+        .map(|path| PathIndex::block_count(conn, path.id))
+        .sum::<i64>();
+    let path_ids = paths
+        .iter()
+        .map(|path| Value::from(path.id))
+        .collect::<Vec<Value>>();
+
+    // points are the positions on nodes that edges leave or enter, which end segments
+    let (node_count, total_length, split_count, edge_count, max_node_id, path_split_count): (
+        i64,
+        i64,
+        i64,
+        i64,
+        i64,
+        i64,
+    ) = conn
+        .query_row(
+            "with bg_edges as (select distinct edges.* from edges join block_group_edges bge on bge.edge_id = edges.id where bge.block_group_id in rarray(?1)),
+            node_lengths as (select nodes.id, sequences.length from nodes join sequences on sequences.hash = nodes.sequence_hash where nodes.id in (select source_node_id from bg_edges union select target_node_id from bg_edges) and nodes.id not in (?2, ?3)),
+            points as (select source_node_id as node_id, source_coordinate as coordinate from bg_edges union select target_node_id, target_coordinate from bg_edges)
+            select
+                (select count(*) from node_lengths),
+                (select coalesce(sum(length), 0) from node_lengths),
+                (select count(*) from points join node_lengths on node_lengths.id = points.node_id where points.coordinate > 0 and points.coordinate < node_lengths.length),
+                (select count(*) from bg_edges where source_node_id not in (?2, ?3) and target_node_id not in (?2, ?3)),
+                (select coalesce(max(id), 0) from node_lengths),
+                (select count(*) from path_index join points on points.node_id = path_index.node_id and points.coordinate > path_index.sequence_start and points.coordinate < path_index.sequence_end where path_index.path_id in rarray(?4))",
+            params![
+                Rc::new(block_group_ids),
+                PATH_START_NODE_ID,
+                PATH_END_NODE_ID,
+                Rc::new(path_ids)
+            ],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
+        )
+        .unwrap();
+    let segment_count = node_count + split_count;
+    // segments split from the same node are joined by a link
+    let link_count = edge_count + split_count;
+    // a path block becomes a step for every segment it is split into
+    let path_step_count = path_block_count + path_split_count;
+    // segment ids are <node id>.<start>, and most segments start near the beginning of their node
+    let id_length = max_node_id.to_string().len() as i64 + 2;
+    // path lines are named <path name>.<sample name>
+    let path_name_length = paths
+        .iter()
+        .map(|path| {
+            path.name.len() as i64
+                + sample_names[&path.block_group_id]
+                    .as_ref()
+                    .map_or(0, |sample_name| sample_name.len() as i64 + 1)
+        })
+        .sum::<i64>();
+
+    let size = total_length
+        + segment_count * (id_length + 5)
+        + link_count * (2 * id_length + 11)
+        + path_name_length
+        + paths.len() as i64 * 5
+        + path_step_count * (id_length + 2);
+    GfaExportEstimate {
+        segment_count,
+        link_count,
+        path_count: paths.len() as i64,
+        path_step_count,
+        size,
+    }
+}
+
+// Exports only the parts of a sample's graphs that diverge from its parent's, i.e. the graphs of
+// the same name it was inferred to be made from (see BlockGroup::infer_parent), or the graphs with
+// no sample when none is found. Sequence that is new to the sample is exported in full, and up
+// to `context` bp of the surrounding sequence is kept on either side of every divergent edge.
+// Flanking blocks are trimmed to the retained sequence, and the segment ID still encodes the start
+// coordinate of what was kept. Paths are not written because they would run through segments that
+// are left out of the export.
+pub fn export_divergent_gfa(
+    conn: &Connection,
+    collection_name: &str,
+    filename: &PathBuf,
+    sample_name: &str,
+    context: i64,
+) -> Result<Vec<ExportedSegment>, GenError> {
+    if context < 0 {
+        return Err(GenError::InvalidArgument(format!(
+            "--context must not be negative, got {context}."
+        )));
+    }
+    let sample_block_groups = Sample::get_block_groups(conn, collection_name, Some(sample_name));
+    if sample_block_groups.is_empty() {
+        return Err(GenError::NotFound(format!(
+            "No graphs found for collection {collection_name} and sample {sample_name}."
+        )));
+    }
+    let collection_block_groups = BlockGroup::query(
+        conn,
+        "select * from block_groups where collection_name = ?1 and name in (select name from block_groups where collection_name = ?1 and sample_name = ?2);",
+        params![collection_name, sample_name],
+    );
+    let signatures = collection_block_groups
+        .iter()
+        .map(|block_group| {
+            (
+                block_group.id,
+                BlockGroup::path_signatures(conn, block_group.id),
+            )
+        })
+        .collect::<HashMap<i64, HashSet<(String, Vec<i64>)>>>();
+
+    let mut segments = HashSet::new();
+    let mut links = HashSet::new();
+    for block_group in sample_block_groups {
+        let parent_block_group = block_group
+            .infer_parent(&collection_block_groups, &signatures)
+            .or_else(|| {
+                collection_block_groups.iter().find(|candidate| {
+                    candidate.name == block_group.name && candidate.sample_name.is_none()
+                })
             });
+        let reference_edges = parent_block_group
+            .map(|parent| BlockGroupEdge::edges_for_block_group(conn, parent.id))
+            .unwrap_or_default();
+        let reference_edge_ids = reference_edges
+            .iter()
+            .map(|augmented_edge| augmented_edge.edge.id)
+            .collect::<HashSet<i64>>();
+        let reference_node_ids = reference_edges
+            .iter()
+            .flat_map(|augmented_edge| {
+                [
+                    augmented_edge.edge.source_node_id,
+                    augmented_edge.edge.target_node_id,
+                ]
+            })
+            .collect::<HashSet<i64>>();
+
+        let mut edges = BlockGroupEdge::edges_for_block_group(conn, block_group.id);
+        let blocks = Edge::blocks_from_edges(conn, &edges);
+        let boundary_edges = Edge::boundary_edges_from_sequences(&blocks);
+        edges.extend(boundary_edges);
+        let (mut graph, _edges_by_node_pair) = Edge::build_graph(&edges, &blocks);
+        BlockGroup::prune_graph(&mut graph);
+
+        // Boundary edges have an ID of -1 and only join two blocks of the same node.
+        let divergent_edges = graph
+            .all_edges()
+            .filter(|(_, _, edge_info)| {
+                edge_info.edge_id != -1 && !reference_edge_ids.contains(&edge_info.edge_id)
+            })
+            .map(|(source, target, _)| (source, target))
+            .collect::<Vec<(GraphNode, GraphNode)>>();
+
+        let mut retained = RetainedSequence::default();
+        for (source, target) in divergent_edges {
+            for (node, direction) in [(source, Direction::Incoming), (target, Direction::Outgoing)]
+            {
+                if Node::is_terminal(node.node_id) {
+                    continue;
+                }
+                if reference_node_ids.contains(&node.node_id) {
+                    // Keep at least a base on each side of the edge so the junction is exported
+                    // even without any context.
+                    retained.extend(&graph, node, direction, context.max(1));
+                } else {
+                    retained.whole.insert(node);
+                    for neighbor in graph.neighbors_directed(node, direction) {
+                        retained.extend(&graph, neighbor, direction, context);
+                    }
+                }
+            }
+        }
+
+        let blocks_by_id = blocks
+            .iter()
+            .map(|block| (block.id, block))
+            .collect::<HashMap<i64, &GroupBlock>>();
+        for node in graph.nodes() {
+            if Node::is_terminal(node.node_id) {
+                continue;
+            }
+            let sequence = blocks_by_id[&node.block_id].sequence();
+            for (start, end) in retained.ranges(node) {
+                segments.insert(Segment {
+                    sequence: sequence[(start - node.sequence_start) as usize
+                        ..(end - node.sequence_start) as usize]
+                        .to_string(),
+                    node_id: node.node_id,
+                    sequence_start: start,
+                    strand: Strand::Forward,
+                });
+            }
+        }
+
+        for (source, target, edge_info) in graph.all_edges() {
+            if Node::is_terminal(source.node_id) || Node::is_terminal(target.node_id) {
+                continue;
+            }
+            let source_range = retained.ranges(source).last().copied();
+            let target_range = retained.ranges(target).first().copied();
+            if let (Some((source_start, source_end)), Some((target_start, _))) =
+                (source_range, target_range)
+            {
+                if source_end == source.sequence_end && target_start == target.sequence_start {
+                    links.insert(Link {
+                        source_segment_id: format!("{}.{}", source.node_id, source_start),
+                        source_strand: edge_info.source_strand,
+                        target_segment_id: format!("{}.{}", target.node_id, target_start),
+                        target_strand: edge_info.target_strand,
+                    });
+                }
+            }
         }
     }
+
+    let segments = segments
+        .into_iter()
+        .sorted_by_key(|segment| (segment.node_id, segment.sequence_start))
+        .collect::<Vec<Segment>>();
+    let links = links
+        .into_iter()
+        .sorted_by(|link1, link2| {
+            (&link1.source_segment_id, &link1.target_segment_id)
+                .cmp(&(&link2.source_segment_id, &link2.target_segment_id))
+        })
+        .collect::<Vec<Link>>();
+
+    let file = File::create(filename)?;
+    let mut writer = BufWriter::new(file);
+    write_segments(&mut writer, &segments);
     write_links(&mut writer, &links);
-    write_paths(&mut writer, conn, collection_name, &blocks);
+    writer.flush()?;
+
+    Ok(segments.iter().map(Segment::exported).collect())
+}
+
+// Tracks how much of each block is kept when exporting divergent regions. Blocks in `whole` are
+// kept in full, otherwise a prefix of `prefixes[node]` bp (reached walking forward from a divergent
+// edge) and a suffix of `suffixes[node]` bp (reached walking backward) are kept.
+#[derive(Default)]
+struct RetainedSequence {
+    whole: HashSet<GraphNode>,
+    prefixes: HashMap<GraphNode, i64>,
+    suffixes: HashMap<GraphNode, i64>,
+}
+
+impl RetainedSequence {
+    // Keeps `length` bp of sequence starting at `node` and walking in `direction`, continuing into
+    // the neighboring blocks while any of the length remains.
+    fn extend(
+        &mut self,
+        graph: &DiGraphMap<GraphNode, GraphEdge>,
+        node: GraphNode,
+        direction: Direction,
+        length: i64,
+    ) {
+        let mut stack = vec![(node, length)];
+        while let Some((node, length)) = stack.pop() {
+            if length <= 0 || Node::is_terminal(node.node_id) {
+                continue;
+            }
+            let retained_lengths = match direction {
+                Direction::Outgoing => &mut self.prefixes,
+                Direction::Incoming => &mut self.suffixes,
+            };
+            let retained_length = retained_lengths.entry(node).or_insert(0);
+            if *retained_length >= length {
+                continue;
+            }
+            *retained_length = length;
+            let remaining = length - (node.sequence_end - node.sequence_start);
+            for neighbor in graph.neighbors_directed(node, direction) {
+                stack.push((neighbor, remaining));
+            }
+        }
+    }
+
+    // The (start, end) coordinate ranges of the node's sequence that are kept, in order.
+    fn ranges(&self, node: GraphNode) -> Vec<(i64, i64)> {
+        let length = node.sequence_end - node.sequence_start;
+        let prefix = self.prefixes.get(&node).copied().unwrap_or(0).min(length);
+        let suffix = self.suffixes.get(&node).copied().unwrap_or(0).min(length);
+        if self.whole.contains(&node) || (prefix + suffix >= length && prefix + suffix > 0) {
+            return vec![(node.sequence_start, node.sequence_end)];
+        }
+        let mut ranges = vec![];
+        if prefix > 0 {
+            ranges.push((node.sequence_start, node.sequence_start + prefix));
+        }
+        if suffix > 0 {
+            ranges.push((node.sequence_end - suffix, node.sequence_end));
+        }
+        ranges
+    }
 }
 
 // NOTE: A path is an immutable list of edges, but the sequence between the target of one edge and
@@ -139,8 +903,35 @@ fn write_paths(
     conn: &Connection,
     collection_name: &str,
     blocks: &[GroupBlock],
+    graph_order: &GraphOrder,
+    progress_bar: &MultiProgress,
+) {
+    let block_groups_by_id = Collection::get_block_groups(conn, collection_name)
+        .into_iter()
+        .map(|block_group| (block_group.id, block_group))
+        .collect::<HashMap<i64, BlockGroup>>();
+    let mut paths = Path::query_for_collection(conn, collection_name);
+    let graph_name = |path: &Path| &block_groups_by_id[&path.block_group_id].name;
+    paths.sort_by(|a, b| graph_order.compare(graph_name(a), graph_name(b)));
+    write_path_lines(
+        writer,
+        conn,
+        paths,
+        &block_groups_by_id,
+        blocks,
+        progress_bar,
+    );
+}
+
+// Writes a path line for each path, naming the paths of a sample's graphs as path.sample.
+fn write_path_lines(
+    writer: &mut BufWriter<File>,
+    conn: &Connection,
+    paths: Vec<Path>,
+    block_groups_by_id: &HashMap<i64, BlockGroup>,
+    blocks: &[GroupBlock],
+    progress_bar: &MultiProgress,
 ) {
-    let paths = Path::query_for_collection(conn, collection_name);
     let edges_by_path_id =
         PathEdge::edges_for_paths(conn, paths.iter().map(|path| path.id).collect());
 
@@ -153,9 +944,10 @@ fn write_paths(
         .map(|block| ((block.node_id, block.end), block.clone()))
         .collect::<HashMap<(i64, i64), GroupBlock>>();
 
+    let bar = progress_bar.add(get_eta_progress_bar(paths.len() as u64));
+    bar.set_message("Paths written");
     for path in paths {
-        let block_group = BlockGroup::get_by_id(conn, path.block_group_id);
-        let sample_name = block_group.sample_name;
+        let sample_name = &block_groups_by_id[&path.block_group_id].sample_name;
 
         let edges_for_path = edges_by_path_id.get(&path.id).unwrap();
         let mut graph_segment_ids = vec![];
@@ -173,10 +965,11 @@ fn write_paths(
             }
         }
 
-        let full_path_name = if sample_name.is_some() && sample_name.clone().unwrap() != "" {
-            format!("{}.{}", path.name, sample_name.unwrap()).to_string()
-        } else {
-            path.name
+        let full_path_name = match sample_name {
+            Some(sample_name) if !sample_name.is_empty() => {
+                format!("{}.{}", path.name, sample_name)
+            }
+            _ => path.name,
         };
         let path = GFAPath {
             name: full_path_name.clone(),
@@ -186,7 +979,9 @@ fn write_paths(
         writer
             .write_all(&path_line(&path).into_bytes())
             .unwrap_or_else(|_| panic!("Error writing path {} to GFA stream", full_path_name));
+        bar.inc(1);
     }
+    bar.finish();
 }
 
 #[cfg(test)]
@@ -205,7 +1000,14 @@ mod tests {
         strand::Strand,
     };
 
-    use crate::test_helpers::{get_connection, setup_block_group, setup_gen_dir};
+    use crate::imports::fasta::import_fasta;
+    use crate::models::{metadata, operations::setup_db};
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, setup_block_group, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use crate::updates::vcf::update_with_vcf;
+    use std::fs;
     use tempfile::tempdir;
 
     #[test]
@@ -330,7 +1132,13 @@ mod tests {
         let mut gfa_path = PathBuf::from(temp_dir.path());
         gfa_path.push("intermediate.gfa");
 
-        export_gfa(&conn, collection_name, &gfa_path, None);
+        export_gfa(
+            &conn,
+            collection_name,
+            &gfa_path,
+            None,
+            &GraphOrder::Natural,
+        );
         // NOTE: Not directly checking file contents because segments are written in random order
         import_gfa(&gfa_path, "test collection 2", None, &conn);
 
@@ -362,7 +1170,13 @@ mod tests {
         let mut gfa_path = PathBuf::from(temp_dir.path());
         gfa_path.push("intermediate.gfa");
 
-        export_gfa(conn, &collection_name, &gfa_path, None);
+        export_gfa(
+            conn,
+            &collection_name,
+            &gfa_path,
+            None,
+            &GraphOrder::Natural,
+        );
         import_gfa(&gfa_path, "test collection 2", None, conn);
 
         let block_group2 = Collection::get_block_groups(conn, "test collection 2")
@@ -373,6 +1187,73 @@ mod tests {
         assert_eq!(all_sequences, all_sequences2);
     }
 
+    #[test]
+    fn test_attributes_round_trip() {
+        setup_gen_dir();
+        let gfa_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/tagged.gfa");
+        let conn = &get_connection(None);
+        import_gfa(&gfa_path, "test", None, conn);
+
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let gfa_path = temp_dir.path().join("intermediate.gfa");
+        export_gfa(conn, "test", &gfa_path, None, &GraphOrder::Natural);
+
+        let gfa = fs::read_to_string(&gfa_path).unwrap();
+        let segments = gfa
+            .lines()
+            .filter(|line| line.starts_with("S\t"))
+            .map(|line| line.split('\t').skip(2).collect::<Vec<&str>>().join("\t"))
+            .sorted()
+            .collect::<Vec<String>>();
+        assert_eq!(
+            segments,
+            vec!["ATCG\tLB:Z:left\tRC:i:12", "GGCC\tdp:f:0.5", "TTAA\t*"]
+        );
+        let links = gfa
+            .lines()
+            .filter(|line| line.starts_with("L\t"))
+            .map(|line| line.split('\t').skip(5).collect::<Vec<&str>>().join("\t"))
+            .sorted()
+            .collect::<Vec<String>>();
+        assert_eq!(links, vec!["0M", "0M\tID:Z:junction\tRC:i:3"]);
+
+        import_gfa(&gfa_path, "test 2", None, conn);
+        let block_group = Collection::get_block_groups(conn, "test 2").pop().unwrap();
+        let graph = BlockGroup::get_graph(conn, block_group.id);
+        let node_ids = graph
+            .nodes()
+            .map(|node| node.node_id)
+            .unique()
+            .collect::<Vec<i64>>();
+        let node_attributes = Node::attributes_for_nodes(conn, &node_ids)
+            .into_values()
+            .sorted_by_key(|attributes| attributes.len())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            node_attributes,
+            vec![
+                BTreeMap::from([("dp".to_string(), AttributeValue::Float(0.5))]),
+                BTreeMap::from([
+                    ("LB".to_string(), AttributeValue::from("left")),
+                    ("RC".to_string(), AttributeValue::Integer(12)),
+                ]),
+            ]
+        );
+        let edge_ids = graph
+            .all_edges()
+            .map(|(_, _, edge)| edge.edge_id)
+            .collect::<Vec<i64>>();
+        assert_eq!(
+            Edge::attributes_for_edges(conn, &edge_ids)
+                .into_values()
+                .collect::<Vec<_>>(),
+            vec![BTreeMap::from([
+                ("ID".to_string(), AttributeValue::from("junction")),
+                ("RC".to_string(), AttributeValue::Integer(3)),
+            ])]
+        );
+    }
+
     #[test]
     fn test_anderson_round_trip() {
         setup_gen_dir();
@@ -389,7 +1270,13 @@ mod tests {
         let mut gfa_path = PathBuf::from(temp_dir.path());
         gfa_path.push("intermediate.gfa");
 
-        export_gfa(conn, &collection_name, &gfa_path, None);
+        export_gfa(
+            conn,
+            &collection_name,
+            &gfa_path,
+            None,
+            &GraphOrder::Natural,
+        );
         import_gfa(&gfa_path, "anderson promoters 2", None, conn);
 
         let block_group2 = Collection::get_block_groups(conn, "anderson promoters 2")
@@ -416,7 +1303,13 @@ mod tests {
         let mut gfa_path = PathBuf::from(temp_dir.path());
         gfa_path.push("intermediate.gfa");
 
-        export_gfa(conn, &collection_name, &gfa_path, None);
+        export_gfa(
+            conn,
+            &collection_name,
+            &gfa_path,
+            None,
+            &GraphOrder::Natural,
+        );
         import_gfa(&gfa_path, "test collection 2", None, conn);
 
         let block_group2 = Collection::get_block_groups(conn, "test collection 2")
@@ -499,7 +1392,7 @@ mod tests {
         let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
         let mut gfa_path = PathBuf::from(temp_dir.path());
         gfa_path.push("intermediate.gfa");
-        export_gfa(&conn, "test", &gfa_path, None);
+        export_gfa(&conn, "test", &gfa_path, None, &GraphOrder::Natural);
         import_gfa(&gfa_path, "test collection 2", None, &conn);
 
         let block_group2 = Collection::get_block_groups(&conn, "test collection 2")
@@ -543,4 +1436,294 @@ mod tests {
         // split in half, there's just one new TTTTT sequence shared by 2 nodes
         assert_eq!(node_hashes2.len(), 6);
     }
+
+    #[test]
+    fn test_divergent_export() {
+        /*
+        Graph after fasta update:
+        AT ----> CGA ------> TCGATCGATCGATCGGGAACACACAGAGA
+           \-> AAAAAAAA --/
+        */
+        setup_gen_dir();
+        let conn = get_connection(None);
+        let db_uuid = metadata::get_db_uuid(&conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let mut fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_path.push("fixtures/simple.fa");
+        let mut fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_update_path.push("fixtures/aaaaaaaa.fa");
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            &conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            &conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            2,
+            5,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let mut gfa_path = PathBuf::from(temp_dir.path());
+        gfa_path.push("divergent.gfa");
+        export_divergent_gfa(&conn, "test", &gfa_path, "child sample", 3).unwrap();
+
+        let contents = fs::read_to_string(&gfa_path).unwrap();
+        let segment_sequences = contents
+            .lines()
+            .filter(|line| line.starts_with('S'))
+            .map(|line| line.split('\t').nth(2).unwrap())
+            .collect::<HashSet<&str>>();
+        // The inserted sequence, the 2 bp before it (all that precedes it) and 3 bp after it
+        assert_eq!(
+            segment_sequences,
+            HashSet::from_iter(vec!["AT", "AAAAAAAA", "TCG"])
+        );
+        // Only the links into and out of the insertion are kept, not the reference CGA block
+        assert_eq!(
+            contents
+                .lines()
+                .filter(|line| line.starts_with('L'))
+                .count(),
+            2
+        );
+        assert_eq!(
+            contents
+                .lines()
+                .filter(|line| line.starts_with('P'))
+                .count(),
+            0
+        );
+
+        // A grandchild is diffed against the child it was made from, so the child's insertion is
+        // only kept as context
+        let fasta_update2_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/tttttttt.fa");
+        update_with_fasta(
+            &conn,
+            op_conn,
+            "test",
+            Some("child sample"),
+            "grandchild sample",
+            "m123",
+            30,
+            32,
+            fasta_update2_path.to_str().unwrap(),
+        )
+        .unwrap();
+        export_divergent_gfa(&conn, "test", &gfa_path, "grandchild sample", 1).unwrap();
+        let contents = fs::read_to_string(&gfa_path).unwrap();
+        let segment_sequences = contents
+            .lines()
+            .filter(|line| line.starts_with('S'))
+            .map(|line| line.split('\t').nth(2).unwrap())
+            .collect::<HashSet<&str>>();
+        assert!(segment_sequences.contains("TTTTTTTT"));
+        assert!(!segment_sequences.contains("AAAAAAAA"));
+
+        assert!(matches!(
+            export_divergent_gfa(&conn, "test", &gfa_path, "child sample", -1),
+            Err(GenError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_estimates_export() {
+        setup_gen_dir();
+        let conn = get_connection(None);
+        let db_uuid = metadata::get_db_uuid(&conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            &conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            &conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            2,
+            5,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let estimate = estimate_gfa_export(&conn, "test", None);
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let gfa_path = temp_dir.path().join("estimated.gfa");
+        export_gfa(&conn, "test", &gfa_path, None, &GraphOrder::Natural);
+        let contents = fs::read_to_string(&gfa_path).unwrap();
+        let count_lines = |prefix: char| {
+            contents
+                .lines()
+                .filter(|line| line.starts_with(prefix))
+                .count() as i64
+        };
+
+        assert_eq!(estimate.segment_count, count_lines('S'));
+        // links between the segments of a node can be pruned from the export
+        assert!(estimate.link_count >= count_lines('L'));
+        assert_eq!(estimate.path_count, count_lines('P'));
+        assert_eq!(
+            estimate.path_step_count,
+            contents
+                .lines()
+                .filter(|line| line.starts_with('P'))
+                .map(|line| line.split('\t').nth(2).unwrap().split(',').count() as i64)
+                .sum::<i64>()
+        );
+        let size = contents.len() as i64;
+        // within a fifth of the real size, most of the difference being the pruned links
+        assert!((estimate.size - size).abs() * 5 < size);
+    }
+
+    #[test]
+    fn test_exports_region() {
+        let conn = get_connection(None);
+        let (block_group_id, path) = setup_block_group(&conn);
+        let insert_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("NNNN")
+            .save(&conn);
+        let insert_node_id = Node::create(&conn, insert_sequence.hash.as_str(), None);
+        let change = PathChange {
+            block_group_id,
+            path: path.clone(),
+            path_accession: None,
+            start: 7,
+            end: 15,
+            block: PathBlock {
+                id: 0,
+                node_id: insert_node_id,
+                block_sequence: "NNNN".to_string(),
+                sequence_start: 0,
+                sequence_end: 4,
+                path_start: 7,
+                path_end: 15,
+                strand: Strand::Forward,
+            },
+            chromosome_index: 1,
+            phased: 0,
+        };
+        BlockGroup::insert_change(&conn, &change, &path.intervaltree(&conn));
+
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let gfa_path = temp_dir.path().join("region.gfa");
+        let segments = export_region_gfa(
+            &conn,
+            block_group_id,
+            &path,
+            &Range { start: 5, end: 25 },
+            &gfa_path,
+        );
+        // the blocks at the ends are trimmed to the range, and the G node is left out
+        assert_eq!(segments.len(), 6);
+
+        import_gfa(&gfa_path, "region", None, &conn);
+        let block_group = Collection::get_block_groups(&conn, "region").pop().unwrap();
+        assert_eq!(
+            BlockGroup::get_all_sequences(&conn, block_group.id, false),
+            HashSet::from([
+                "AAAAATTTTTTTTTTCCCCC".to_string(),
+                "AANNNNTTTTTCCCCC".to_string()
+            ])
+        );
+        let paths = Path::query_for_collection(&conn, "region");
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].sequence(&conn), "AAAAATTTTTTTTTTCCCCC");
+    }
+
+    #[test]
+    fn test_exports_haplotype_walks() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/phased.vcf");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_vcf(
+            &vcf_path.to_str().unwrap().to_string(),
+            "test",
+            "".to_string(),
+            "".to_string(),
+            conn,
+            op_conn,
+            None,
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().expect("Couldn't get handle to temp directory");
+        let gfa_path = temp_dir.path().join("haplotypes.gfa");
+        export_haplotype_gfa(conn, "test", Some("foo"), &gfa_path, &GraphOrder::Natural);
+
+        // spell out each walk from the segments it goes through
+        let contents = fs::read_to_string(&gfa_path).unwrap();
+        let sequences_by_segment_id = contents
+            .lines()
+            .filter(|line| line.starts_with("S\t"))
+            .map(|line| {
+                let fields = line.split('\t').collect::<Vec<&str>>();
+                (fields[1].to_string(), fields[2].to_string())
+            })
+            .collect::<HashMap<String, String>>();
+        let walks = contents
+            .lines()
+            .filter(|line| line.starts_with("W\t"))
+            .map(|line| {
+                let fields = line.split('\t').collect::<Vec<&str>>();
+                let sequence = fields[6]
+                    .split('>')
+                    .filter(|segment_id| !segment_id.is_empty())
+                    .map(|segment_id| sequences_by_segment_id[segment_id].clone())
+                    .collect::<String>();
+                assert_eq!(fields[5], sequence.len().to_string());
+                (fields[1..4].join(" "), sequence)
+            })
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(
+            walks,
+            vec![
+                (
+                    "foo 1 m123".to_string(),
+                    "ATCATCGATCGATCGATCGGGAACACACAGAGA".to_string()
+                ),
+                (
+                    "foo 2 m123".to_string(),
+                    "ATCGATCGATAGAGATCGATCGGGAACACACAGAGA".to_string()
+                ),
+            ]
+        );
+    }
 }
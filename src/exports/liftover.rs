@@ -0,0 +1,282 @@
+use crate::exports::mapping::sample_path_pairs;
+use crate::models::strand::Strand;
+use crate::range::RangeMapping;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+// The shared blocks of one graph in two samples, for writing as liftover files. The from sample is
+// the target of chain and PAF files, like the old assembly of a UCSC chain, and the to sample is
+// the query.
+struct GraphMappings {
+    name: String,
+    source_length: i64,
+    target_length: i64,
+    mappings: Vec<RangeMapping>,
+}
+
+fn graph_mappings(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+) -> Vec<GraphMappings> {
+    sample_path_pairs(conn, collection_name, from_sample_name, to_sample_name)
+        .into_iter()
+        .map(|(name, source_path, target_path)| {
+            let mappings = source_path
+                .find_block_mappings(conn, &target_path)
+                .into_iter()
+                .sorted_by_key(|mapping| mapping.source_range.start)
+                .collect();
+            GraphMappings {
+                name,
+                source_length: source_path.length(conn),
+                target_length: target_path.length(conn),
+                mappings: RangeMapping::merge_contiguous_mappings(mappings),
+            }
+        })
+        .collect()
+}
+
+// A chain's blocks are (source start, query start, size), with query coordinates on the strand of
+// the chain, so they increase along the chain for both strands.
+#[derive(Clone, Debug, PartialEq)]
+struct Chain {
+    strand: Strand,
+    blocks: Vec<(i64, i64, i64)>,
+}
+
+/*
+   Groups the mappings of a graph, sorted by source start, into chains. A chain goes on while
+   mappings stay on the same strand and move forward on both paths; anything else, like the start
+   or end of an inversion or a block that moved, starts a new chain. Reverse mappings are given in
+   coordinates of the reverse complement of the query, as the chain format expects.
+*/
+fn chains(mappings: &[RangeMapping], target_length: i64) -> Vec<Chain> {
+    let mut chains: Vec<Chain> = vec![];
+    for mapping in mappings.iter() {
+        let strand = if mapping.strand == Strand::Reverse {
+            Strand::Reverse
+        } else {
+            Strand::Forward
+        };
+        let query_start = if strand == Strand::Reverse {
+            target_length - mapping.target_range.end
+        } else {
+            mapping.target_range.start
+        };
+        let size = mapping.source_range.end - mapping.source_range.start;
+        let block = (mapping.source_range.start, query_start, size);
+        match chains.last_mut() {
+            Some(chain)
+                if chain.strand == strand
+                    && chain.blocks.last().is_some_and(|(source, query, size)| {
+                        block.0 >= source + size && block.1 >= query + size
+                    }) =>
+            {
+                chain.blocks.push(block)
+            }
+            _ => chains.push(Chain {
+                strand,
+                blocks: vec![block],
+            }),
+        }
+    }
+    chains
+}
+
+fn strand_symbol(strand: Strand) -> char {
+    if strand == Strand::Reverse {
+        '-'
+    } else {
+        '+'
+    }
+}
+
+/*
+   Writes the shared blocks of two samples as a UCSC chain file, which liftOver and CrossMap use to
+   convert coordinates of the from sample to the to sample. Each chain is scored by its number of
+   aligned bases, as the blocks are identical sequence.
+*/
+pub fn export_chain(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+    filename: &PathBuf,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    let mut chain_id = 0;
+    for graph in graph_mappings(conn, collection_name, from_sample_name, to_sample_name) {
+        for chain in chains(&graph.mappings, graph.target_length) {
+            chain_id += 1;
+            let (source_start, query_start, _) = chain.blocks[0];
+            let (last_source, last_query, last_size) = *chain.blocks.last().unwrap();
+            writeln!(
+                writer,
+                "chain {score} {name} {source_length} + {source_start} {source_end} {name} {target_length} {strand} {query_start} {query_end} {chain_id}",
+                score = chain.blocks.iter().map(|(_, _, size)| size).sum::<i64>(),
+                name = graph.name,
+                source_length = graph.source_length,
+                source_end = last_source + last_size,
+                target_length = graph.target_length,
+                strand = strand_symbol(chain.strand),
+                query_end = last_query + last_size,
+            )?;
+            for (block, next_block) in chain.blocks.iter().tuple_windows() {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}",
+                    block.2,
+                    next_block.0 - block.0 - block.2,
+                    next_block.1 - block.1 - block.2
+                )?;
+            }
+            writeln!(writer, "{last_size}\n")?;
+        }
+    }
+    writer.flush()
+}
+
+// Writes the shared blocks of two samples as PAF, one gapless alignment per block with the to
+// sample as the query, for paftools liftover and other minimap2-based tools.
+pub fn export_paf(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+    filename: &PathBuf,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    for graph in graph_mappings(conn, collection_name, from_sample_name, to_sample_name) {
+        for mapping in graph.mappings.iter() {
+            let size = mapping.source_range.end - mapping.source_range.start;
+            writeln!(
+                writer,
+                "{name}\t{target_length}\t{query_start}\t{query_end}\t{strand}\t{name}\t{source_length}\t{source_start}\t{source_end}\t{size}\t{size}\t255\tcg:Z:{size}M",
+                name = graph.name,
+                target_length = graph.target_length,
+                query_start = mapping.target_range.start,
+                query_end = mapping.target_range.end,
+                strand = strand_symbol(mapping.strand),
+                source_length = graph.source_length,
+                source_start = mapping.source_range.start,
+                source_end = mapping.source_range.end,
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::range::Range;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn mapping(source: (i64, i64), target: (i64, i64), strand: Strand) -> RangeMapping {
+        RangeMapping {
+            source_range: Range {
+                start: source.0,
+                end: source.1,
+            },
+            target_range: Range {
+                start: target.0,
+                end: target.1,
+            },
+            strand,
+        }
+    }
+
+    #[test]
+    fn test_chains() {
+        // an inversion of [10, 20) between two forward blocks
+        let mappings = vec![
+            mapping((0, 10), (0, 10), Strand::Forward),
+            mapping((10, 20), (10, 20), Strand::Reverse),
+            mapping((20, 30), (20, 30), Strand::Forward),
+        ];
+        assert_eq!(
+            chains(&mappings, 30),
+            vec![
+                Chain {
+                    strand: Strand::Forward,
+                    blocks: vec![(0, 0, 10)],
+                },
+                Chain {
+                    strand: Strand::Reverse,
+                    blocks: vec![(10, 10, 10)],
+                },
+                Chain {
+                    strand: Strand::Forward,
+                    blocks: vec![(20, 20, 10)],
+                },
+            ]
+        );
+
+        // a block that moved before the one it followed starts a new chain
+        let mappings = vec![
+            mapping((0, 10), (10, 20), Strand::Forward),
+            mapping((15, 25), (0, 10), Strand::Forward),
+        ];
+        assert_eq!(chains(&mappings, 20).len(), 2);
+    }
+
+    #[test]
+    fn test_exports_liftover_files() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let chain_path = temp_dir.path().join("out.chain");
+        export_chain(conn, "test", None, Some("child sample"), &chain_path).unwrap();
+        // [15, 25) of the reference was replaced by 2 bp, a gap of 10 bp in the reference and 2 bp
+        // in the child
+        assert_eq!(
+            fs::read_to_string(&chain_path).unwrap(),
+            "chain 24 m123 34 + 0 34 m123 26 + 0 26 1\n15\t10\t2\n9\n\n"
+        );
+
+        let paf_path = temp_dir.path().join("out.paf");
+        export_paf(conn, "test", None, Some("child sample"), &paf_path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&paf_path).unwrap(),
+            "m123\t26\t0\t15\t+\tm123\t34\t0\t15\t15\t15\t255\tcg:Z:15M\nm123\t26\t17\t26\t+\tm123\t34\t25\t34\t9\t9\t255\tcg:Z:9M\n"
+        );
+    }
+}
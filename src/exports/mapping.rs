@@ -0,0 +1,129 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+// The current paths of each graph of two samples, by graph name. Graphs that only exist in one of
+// the samples, or have no path in either, are skipped.
+pub(crate) fn sample_path_pairs(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+) -> Vec<(String, Path, Path)> {
+    let mut source_paths_by_bg_name =
+        Sample::get_block_groups(conn, collection_name, from_sample_name)
+            .iter()
+            .filter_map(|bg| {
+                Some((
+                    bg.name.clone(),
+                    BlockGroup::try_get_current_path(conn, bg.id)?,
+                ))
+            })
+            .collect::<HashMap<String, Path>>();
+    Sample::get_block_groups(conn, collection_name, to_sample_name)
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .filter_map(|bg| {
+            let source_path = source_paths_by_bg_name.remove(&bg.name)?;
+            let target_path = BlockGroup::try_get_current_path(conn, bg.id)?;
+            Some((bg.name.clone(), source_path, target_path))
+        })
+        .collect()
+}
+
+// Writes the block mappings between the current paths of two samples as a tsv, one row per shared
+// range. Coordinates are 0-based and end-exclusive, the same as everywhere else on paths. Graphs
+// that only exist in one of the samples are skipped.
+pub fn export_mapping_tsv(
+    conn: &Connection,
+    collection_name: &str,
+    from_sample_name: Option<&str>,
+    to_sample_name: Option<&str>,
+    filename: &PathBuf,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+
+    writeln!(
+        writer,
+        "graph\tsource_start\tsource_end\ttarget_start\ttarget_end"
+    )?;
+    for (name, source_path, target_path) in
+        sample_path_pairs(conn, collection_name, from_sample_name, to_sample_name)
+    {
+        for mapping in source_path
+            .find_block_mappings(conn, &target_path)
+            .iter()
+            .sorted_by_key(|mapping| mapping.source_range.start)
+        {
+            writeln!(
+                writer,
+                "{name}\t{source_start}\t{source_end}\t{target_start}\t{target_end}",
+                source_start = mapping.source_range.start,
+                source_end = mapping.source_range.end,
+                target_start = mapping.target_range.start,
+                target_end = mapping.target_range.end,
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_exports_mapping() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            15,
+            25,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("mapping.tsv");
+        export_mapping_tsv(conn, "test", None, Some("child sample"), &output_path).unwrap();
+
+        // [15, 25) of the reference was replaced by 2 bp, shifting the rest of the path back by 8
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "graph\tsource_start\tsource_end\ttarget_start\ttarget_end\nm123\t0\t15\t0\t15\nm123\t25\t34\t17\t26\n"
+        );
+    }
+}
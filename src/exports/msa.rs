@@ -0,0 +1,410 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use crate::models::strand::Strand;
+use crate::range::{parse_region, Range, RegionError};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+
+const CLUSTAL_LINE_WIDTH: usize = 60;
+
+#[derive(Debug, Error)]
+pub enum MsaError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Region(#[from] RegionError),
+    #[error("Sample {sample} has no graph named {graph}")]
+    MissingGraph { sample: String, graph: String },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MsaFormat {
+    Fasta,
+    Clustal,
+}
+
+impl MsaFormat {
+    // .aln and .clustal files are written as Clustal, anything else as aligned FASTA.
+    pub fn from_path(path: &std::path::Path) -> MsaFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("aln") | Some("clustal") => MsaFormat::Clustal,
+            _ => MsaFormat::Fasta,
+        }
+    }
+}
+
+// A sample's sequence through a region of the reference, as one base (or gap) per reference
+// position and the bases the sample has before each reference position that the reference doesn't.
+struct AlignedRow {
+    name: String,
+    columns: Vec<Option<char>>,
+    insertions: HashMap<i64, String>,
+}
+
+// Places a stretch of sample sequence that doesn't share nodes with the reference range it takes
+// the place of. The sample bases fill the reference columns first, so substitutions line up, and
+// any bases left over are an insertion before the end of the reference range.
+fn place_unaligned(row: &mut AlignedRow, region: &Range, reference_range: &Range, bases: &str) {
+    let mut bases = bases.chars();
+    for position in reference_range.start..reference_range.end {
+        row.columns[(position - region.start) as usize] = bases.next();
+    }
+    let inserted = bases.collect::<String>();
+    if !inserted.is_empty() {
+        row.insertions.insert(reference_range.end, inserted);
+    }
+}
+
+fn align_to_reference(
+    conn: &Connection,
+    name: &str,
+    reference_path: &Path,
+    path: &Path,
+    region: &Range,
+) -> AlignedRow {
+    // The ranges of the path that share nodes with the reference, in reference order. Mappings
+    // that are reversed or out of order with the ones before them, such as inversions and
+    // rearrangements, are left unaligned.
+    let mut anchors: Vec<(Range, i64)> = vec![];
+    let mut mappings = reference_path.find_block_mappings(conn, path);
+    mappings.sort_by_key(|mapping| (mapping.source_range.start, mapping.target_range.start));
+    for mapping in mappings {
+        let reference_range = &mapping.source_range;
+        if mapping.strand == Strand::Reverse || reference_range.start >= reference_range.end {
+            continue;
+        }
+        let follows = anchors.last().is_none_or(|(last_range, last_start)| {
+            reference_range.start >= last_range.end
+                && mapping.target_range.start >= last_start + (last_range.end - last_range.start)
+        });
+        if follows {
+            anchors.push((reference_range.clone(), mapping.target_range.start));
+        }
+    }
+
+    // Where the region starts and ends on the path. A region that starts or ends inside an unaligned
+    // stretch takes all of that stretch.
+    let path_start = anchors
+        .iter()
+        .rev()
+        .find(|(range, _)| range.start <= region.start)
+        .map_or(0, |(range, start)| {
+            start + (region.start.min(range.end) - range.start)
+        });
+    let path_end = anchors
+        .iter()
+        .find(|(range, _)| range.end >= region.end)
+        .map_or_else(
+            || path.length(conn),
+            |(range, start)| start + (region.end.max(range.start) - range.start),
+        );
+    let sequence = path.sequence_range(conn, path_start, path_end);
+    let bases = |start: i64, end: i64| {
+        &sequence[(start - path_start) as usize..(end - path_start) as usize]
+    };
+
+    let mut row = AlignedRow {
+        name: name.to_string(),
+        columns: vec![None; (region.end - region.start) as usize],
+        insertions: HashMap::new(),
+    };
+    let mut reference_position = region.start;
+    let mut path_position = path_start;
+    for (range, start) in anchors {
+        let clipped_start = range.start.max(region.start);
+        let clipped_end = range.end.min(region.end);
+        if clipped_start >= clipped_end {
+            continue;
+        }
+        let anchor_start = start + (clipped_start - range.start);
+        place_unaligned(
+            &mut row,
+            region,
+            &Range {
+                start: reference_position,
+                end: clipped_start,
+            },
+            bases(path_position, anchor_start),
+        );
+        let anchor_end = anchor_start + (clipped_end - clipped_start);
+        for (offset, base) in bases(anchor_start, anchor_end).chars().enumerate() {
+            row.columns[(clipped_start - region.start) as usize + offset] = Some(base);
+        }
+        reference_position = clipped_end;
+        path_position = anchor_end;
+    }
+    place_unaligned(
+        &mut row,
+        region,
+        &Range {
+            start: reference_position,
+            end: region.end,
+        },
+        bases(path_position, path_end),
+    );
+    row
+}
+
+// Lays the rows out as aligned sequences of equal length. Insertions before the same reference
+// position are left aligned and padded with gaps, and columns that are gaps in every row are
+// dropped.
+fn aligned_sequences(rows: &[AlignedRow], region: &Range) -> Vec<String> {
+    let mut sequences = vec![String::new(); rows.len()];
+    for position in region.start..=region.end {
+        let insertion_length = rows
+            .iter()
+            .filter_map(|row| row.insertions.get(&position))
+            .map(|inserted| inserted.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (row, sequence) in rows.iter().zip(sequences.iter_mut()) {
+            let inserted = row
+                .insertions
+                .get(&position)
+                .map_or("", |inserted| inserted);
+            sequence.push_str(&format!("{inserted:-<insertion_length$}"));
+        }
+        if position == region.end {
+            break;
+        }
+        let column = rows
+            .iter()
+            .map(|row| row.columns[(position - region.start) as usize])
+            .collect::<Vec<Option<char>>>();
+        if column.iter().any(Option::is_some) {
+            for (base, sequence) in column.iter().zip(sequences.iter_mut()) {
+                sequence.push(base.unwrap_or('-'));
+            }
+        }
+    }
+    sequences
+}
+
+fn write_clustal(
+    writer: &mut impl Write,
+    names: &[String],
+    sequences: &[String],
+) -> io::Result<()> {
+    // Clustal names can't contain whitespace
+    let names = names
+        .iter()
+        .map(|name| name.split_whitespace().collect::<Vec<_>>().join("_"))
+        .collect::<Vec<String>>();
+    let name_width = names.iter().map(String::len).max().unwrap_or(0) + 4;
+    let sequences = sequences
+        .iter()
+        .map(|sequence| sequence.chars().collect::<Vec<char>>())
+        .collect::<Vec<Vec<char>>>();
+    let length = sequences.first().map_or(0, Vec::len);
+    writeln!(writer, "CLUSTAL W multiple sequence alignment")?;
+    for block_start in (0..length).step_by(CLUSTAL_LINE_WIDTH) {
+        let block_end = (block_start + CLUSTAL_LINE_WIDTH).min(length);
+        writeln!(writer)?;
+        for (name, sequence) in names.iter().zip(&sequences) {
+            writeln!(
+                writer,
+                "{name:<name_width$}{bases}",
+                bases = sequence[block_start..block_end].iter().collect::<String>()
+            )?;
+        }
+        // columns with the same base in every row are marked as conserved
+        let conservation = (block_start..block_end)
+            .map(|column| {
+                let base = sequences[0][column].to_ascii_uppercase();
+                let conserved = base != '-'
+                    && sequences
+                        .iter()
+                        .all(|sequence| sequence[column].to_ascii_uppercase() == base);
+                if conserved {
+                    '*'
+                } else {
+                    ' '
+                }
+            })
+            .collect::<String>();
+        writeln!(writer, "{:name_width$}{conservation}", "")?;
+    }
+    Ok(())
+}
+
+// Writes the sequences of samples through a region of the reference as a multiple sequence
+// alignment. Samples are aligned by the nodes they share with the reference, with gaps for
+// sequence a sample doesn't have and insertion columns for sequence only some samples have.
+// Sample names of None are the reference.
+pub fn export_msa(
+    conn: &Connection,
+    collection_name: &str,
+    region: &str,
+    sample_names: &[Option<String>],
+    format: MsaFormat,
+    filename: &PathBuf,
+) -> Result<(), MsaError> {
+    let reference_block_groups = Sample::get_block_groups(conn, collection_name, None);
+    let graph_names = reference_block_groups
+        .iter()
+        .map(|block_group| block_group.name.clone())
+        .collect::<Vec<String>>();
+    let parsed_region = parse_region(region, &graph_names)?;
+    let reference_block_group = reference_block_groups
+        .iter()
+        .find(|block_group| block_group.name == parsed_region.name)
+        .unwrap();
+    let reference_path = BlockGroup::try_get_current_path(conn, reference_block_group.id)
+        .ok_or_else(|| RegionError::NoPath(reference_block_group.name.clone()))?;
+    let region = parsed_region.range(reference_path.length(conn))?;
+
+    let mut rows = vec![];
+    for sample_name in sample_names {
+        let display_name = Sample::display_name(sample_name.as_deref()).to_string();
+        let block_group = Sample::get_block_groups(conn, collection_name, sample_name.as_deref())
+            .into_iter()
+            .find(|block_group| block_group.name == parsed_region.name)
+            .ok_or_else(|| MsaError::MissingGraph {
+                sample: display_name.clone(),
+                graph: parsed_region.name.clone(),
+            })?;
+        let path = BlockGroup::try_get_current_path(conn, block_group.id)
+            .ok_or_else(|| RegionError::NoPath(block_group.name.clone()))?;
+        rows.push(align_to_reference(
+            conn,
+            &display_name,
+            &reference_path,
+            &path,
+            &region,
+        ));
+    }
+    let sequences = aligned_sequences(&rows, &region);
+    let names = rows
+        .into_iter()
+        .map(|row| row.name)
+        .collect::<Vec<String>>();
+
+    let mut writer = BufWriter::new(File::create(filename)?);
+    match format {
+        MsaFormat::Fasta => {
+            for (name, sequence) in names.iter().zip(&sequences) {
+                writeln!(writer, ">{name}")?;
+                writeln!(writer, "{sequence}")?;
+            }
+        }
+        MsaFormat::Clustal => write_clustal(&mut writer, &names, &sequences)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_exports_msa() {
+        setup_gen_dir();
+        let fixture = |name: &str| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(name)
+        };
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fixture("simple.fa").to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // a replaces [15, 25) with 2 bp, b and c insert 8 bp and 2 bp at 5
+        for (sample_name, fasta, start, end) in [
+            ("a", "aa.fa", 15, 25),
+            ("b", "aaaaaaaa.fa", 5, 5),
+            ("c", "aa.fa", 5, 5),
+        ] {
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                sample_name,
+                "m123",
+                start,
+                end,
+                fixture(fasta).to_str().unwrap(),
+            )
+            .unwrap();
+        }
+        let sample_names = [
+            None,
+            Some("a".to_string()),
+            Some("b".to_string()),
+            Some("c".to_string()),
+        ];
+
+        let temp_dir = tempdir().unwrap();
+        let fasta_path = temp_dir.path().join("region.fa");
+        export_msa(
+            conn,
+            "test",
+            "m123",
+            &sample_names,
+            MsaFormat::from_path(&fasta_path),
+            &fasta_path,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&fasta_path).unwrap(),
+            ">(reference)\nATCGA--------TCGATCGATCGATCGGGAACACACAGAGA\n\
+             >a\nATCGA--------TCGATCGATCAA--------ACACAGAGA\n\
+             >b\nATCGAAAAAAAAATCGATCGATCGATCGGGAACACACAGAGA\n\
+             >c\nATCGAAA------TCGATCGATCGATCGGGAACACACAGAGA\n"
+        );
+
+        // the region ends inside the replaced range of a, so all of its replacement is included
+        let clustal_path = temp_dir.path().join("region.aln");
+        export_msa(
+            conn,
+            "test",
+            "m123:4-20",
+            &sample_names,
+            MsaFormat::from_path(&clustal_path),
+            &clustal_path,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&clustal_path).unwrap(),
+            "CLUSTAL W multiple sequence alignment\n\n\
+             (reference)    GA--------TCGATCGATCGATCG\n\
+             a              GA--------TCGATCGATCAA---\n\
+             b              GAAAAAAAAATCGATCGATCGATCG\n\
+             c              GAAA------TCGATCGATCGATCG\n\
+             \x20              **        ********** *   \n"
+        );
+
+        assert!(matches!(
+            export_msa(
+                conn,
+                "test",
+                "m123",
+                &[Some("missing".to_string())],
+                MsaFormat::Fasta,
+                &fasta_path,
+            ),
+            Err(MsaError::MissingGraph { .. })
+        ));
+    }
+}
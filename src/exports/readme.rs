@@ -0,0 +1,305 @@
+use crate::models::accession::Accession;
+use crate::models::block_group::BlockGroup;
+use crate::models::operations::{Branch, OperationState, OperationSummary};
+use crate::models::path::Path;
+use crate::models::sample::Sample;
+use crate::models::traits::Query;
+use itertools::Itertools;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const RECENT_OPERATION_COUNT: usize = 10;
+
+// Markdown table cells can't hold pipes or line breaks.
+fn cell(value: &str) -> String {
+    value.split_whitespace().join(" ").replace('|', "\\|")
+}
+
+fn file_sha256(filename: &PathBuf) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(filename)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// The sample each sample's graphs were inferred to be made from, see BlockGroup::infer_parent.
+fn infer_parents(
+    conn: &Connection,
+    block_groups: &[BlockGroup],
+) -> BTreeMap<Option<String>, HashSet<Option<String>>> {
+    let signatures = block_groups
+        .iter()
+        .map(|bg| (bg.id, BlockGroup::path_signatures(conn, bg.id)))
+        .collect::<HashMap<i64, HashSet<(String, Vec<i64>)>>>();
+    let mut parents: BTreeMap<Option<String>, HashSet<Option<String>>> = BTreeMap::new();
+    for bg in block_groups.iter() {
+        let parent = bg
+            .infer_parent(block_groups, &signatures)
+            .map(|candidate| candidate.sample_name.clone());
+        let sample_parents = parents.entry(bg.sample_name.clone()).or_default();
+        if let Some(parent) = parent {
+            sample_parents.insert(parent);
+        }
+    }
+    parents
+}
+
+// Writes a Markdown summary of a collection for recipients of its exports: its graphs, its samples
+// and what they were derived from, its accessions, the latest operations of the current branch and
+// the SHA-256 checksums of the exported files.
+pub fn write_readme<W: Write>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+    collection_name: &str,
+    exported_files: &[PathBuf],
+    writer: &mut W,
+) -> io::Result<()> {
+    let block_groups = BlockGroup::query(
+        conn,
+        "select * from block_groups where collection_name = ?1 order by id;",
+        params![collection_name],
+    );
+    let current_operation = OperationState::get_operation(operation_conn, db_uuid);
+
+    writeln!(writer, "# {collection_name}")?;
+    writeln!(writer)?;
+    write!(
+        writer,
+        "The {collection_name} collection of a gen database, with {graph_count} graphs in {sample_count} samples",
+        graph_count = block_groups.iter().map(|bg| &bg.name).unique().count(),
+        sample_count = block_groups.iter().map(|bg| &bg.sample_name).unique().count(),
+    )?;
+    match &current_operation {
+        Some(operation_hash) => writeln!(writer, ", as of operation {operation_hash}.")?,
+        None => writeln!(writer, ".")?,
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "## Graphs")?;
+    writeln!(writer)?;
+    writeln!(writer, "| Graph | Reference length | Topology | Samples |")?;
+    writeln!(writer, "| --- | --- | --- | --- |")?;
+    for (name, graph_block_groups) in &block_groups
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .chunk_by(|bg| bg.name.clone())
+    {
+        let graph_block_groups = graph_block_groups.collect::<Vec<&BlockGroup>>();
+        let reference_length = graph_block_groups
+            .iter()
+            .find(|bg| bg.sample_name.is_none())
+            .and_then(|bg| BlockGroup::try_get_current_path(conn, bg.id))
+            .map(|path| path.length(conn).to_string())
+            .unwrap_or_default();
+        let topology = if graph_block_groups.iter().any(|bg| bg.is_circular) {
+            "circular"
+        } else {
+            "linear"
+        };
+        writeln!(
+            writer,
+            "| {name} | {reference_length} | {topology} | {sample_count} |",
+            name = cell(&name),
+            sample_count = graph_block_groups.len(),
+        )?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "## Samples")?;
+    writeln!(writer)?;
+    writeln!(writer, "| Sample | Derived from | Graphs |")?;
+    writeln!(writer, "| --- | --- | --- |")?;
+    let parents = infer_parents(conn, &block_groups);
+    for (sample_name, sample_parents) in parents.iter() {
+        let derived_from = sample_parents
+            .iter()
+            .map(|parent| cell(Sample::display_name(parent.as_deref())))
+            .sorted()
+            .join(", ");
+        writeln!(
+            writer,
+            "| {sample} | {derived_from} | {graph_count} |",
+            sample = cell(Sample::display_name(sample_name.as_deref())),
+            graph_count = block_groups
+                .iter()
+                .filter(|bg| &bg.sample_name == sample_name)
+                .count(),
+        )?;
+    }
+
+    let accessions = Accession::query_for_collection(conn, collection_name);
+    if !accessions.is_empty() {
+        writeln!(writer)?;
+        writeln!(writer, "## Accessions")?;
+        writeln!(writer)?;
+        writeln!(writer, "| Accession | Sample | Graph | Length |")?;
+        writeln!(writer, "| --- | --- | --- | --- |")?;
+        for accession in accessions.iter() {
+            let path = Path::get(conn, accession.path_id);
+            let block_group = BlockGroup::get_by_id(conn, path.block_group_id);
+            writeln!(
+                writer,
+                "| {name} | {sample} | {graph} | {length} |",
+                name = cell(&accession.name),
+                sample = cell(Sample::display_name(block_group.sample_name.as_deref())),
+                graph = cell(&block_group.name),
+                length = accession.sequence(conn).len(),
+            )?;
+        }
+    }
+
+    if let Some(branch_id) = OperationState::get_current_branch(operation_conn, db_uuid) {
+        let operations = Branch::get_operations(operation_conn, branch_id);
+        if !operations.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "## Recent operations")?;
+            writeln!(writer)?;
+            writeln!(writer, "| Operation | Date | Author | Summary |")?;
+            writeln!(writer, "| --- | --- | --- | --- |")?;
+            for operation in operations.iter().rev().take(RECENT_OPERATION_COUNT) {
+                let summary = OperationSummary::query(
+                    operation_conn,
+                    "select * from operation_summary where operation_hash = ?1",
+                    vec![Value::from(operation.hash.clone())],
+                )
+                .into_iter()
+                .map(|summary| summary.summary)
+                .join(" ");
+                writeln!(
+                    writer,
+                    "| {hash} | {date} | {author} | {summary} |",
+                    hash = operation.hash,
+                    date = cell(operation.timestamp.as_deref().unwrap_or_default()),
+                    author = cell(operation.author.as_deref().unwrap_or_default()),
+                    summary = cell(operation.message.as_deref().unwrap_or(&summary)),
+                )?;
+            }
+        }
+    }
+
+    if !exported_files.is_empty() {
+        writeln!(writer)?;
+        writeln!(writer, "## Files")?;
+        writeln!(writer)?;
+        writeln!(writer, "| File | SHA-256 |")?;
+        writeln!(writer, "| --- | --- |")?;
+        for filename in exported_files.iter() {
+            writeln!(
+                writer,
+                "| {file} | {checksum} |",
+                file = cell(
+                    &filename
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| filename.display().to_string())
+                ),
+                checksum = file_sha256(filename)?,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exports::fasta::export_fasta;
+    use crate::graph_order::GraphOrder;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_readme() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        for (parent, child, start) in [
+            (None, "child", 15),
+            (Some("child"), "grandchild", 2),
+            (None, "sibling", 20),
+        ] {
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                parent,
+                child,
+                "m123",
+                start,
+                start + 5,
+                insert_path.to_str().unwrap(),
+            )
+            .unwrap();
+        }
+
+        let temp_dir = tempdir().unwrap();
+        let export_path = temp_dir.path().join("child.fa");
+        export_fasta(
+            conn,
+            "test",
+            Some("child"),
+            &export_path,
+            &GraphOrder::Natural,
+        );
+        let mut readme = vec![];
+        write_readme(
+            conn,
+            op_conn,
+            &db_uuid,
+            "test",
+            std::slice::from_ref(&export_path),
+            &mut readme,
+        )
+        .unwrap();
+        let readme = String::from_utf8(readme).unwrap();
+        let lines = readme.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "# test");
+        assert!(lines[2].starts_with(
+            "The test collection of a gen database, with 1 graphs in 4 samples, as of operation "
+        ));
+        for line in [
+            "| m123 | 34 | linear | 4 |",
+            "| (reference) |  | 1 |",
+            "| child | (reference) | 1 |",
+            "| grandchild | child | 1 |",
+            "| sibling | (reference) | 1 |",
+        ] {
+            assert!(lines.contains(&line), "{line} not in\n{readme}");
+        }
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line.starts_with("| ") && line.len() > 64 + 4)
+                .filter(|line| line[2..66].chars().all(|c| c.is_ascii_hexdigit()))
+                .count(),
+            4
+        );
+        assert_eq!(
+            *lines.last().unwrap(),
+            format!("| child.fa | {} |", file_sha256(&export_path).unwrap())
+        );
+    }
+}
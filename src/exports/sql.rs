@@ -0,0 +1,276 @@
+use crate::models::operations::Operation;
+use crate::operation_management::load_changeset;
+use fallible_streaming_iterator::FallibleStreamingIterator;
+use rusqlite::hooks::Action;
+use rusqlite::session::ChangesetIter;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, Error as SQLError};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// The table a mirror keeps the operations it has received in. The last hash in it is the one to
+// pass as --since for the next delta.
+pub const OPERATIONS_TABLE_SQL: &str = "CREATE TABLE gen_operations (
+  hash TEXT PRIMARY KEY NOT NULL,
+  parent_hash TEXT,
+  change_type TEXT NOT NULL,
+  timestamp TEXT
+);";
+
+// The operations of a branch after the one with the given hash (or hash prefix), up to and
+// including the branch's current operation. Without a hash every operation up to the current one
+// is returned. None if either operation isn't on the branch.
+pub fn operations_since<'a>(
+    branch_operations: &'a [Operation],
+    current_hash: &str,
+    since_hash: Option<&str>,
+) -> Option<&'a [Operation]> {
+    let end = branch_operations
+        .iter()
+        .position(|operation| operation.hash == current_hash)?;
+    let start = match since_hash {
+        Some(since_hash) => {
+            branch_operations
+                .iter()
+                .position(|operation| operation.hash.starts_with(since_hash))?
+                + 1
+        }
+        None => 0,
+    };
+    if start > end + 1 {
+        return None;
+    }
+    Some(&branch_operations[start..=end])
+}
+
+fn sql_literal(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) => value.to_string(),
+        ValueRef::Text(value) => {
+            format!("'{}'", String::from_utf8_lossy(value).replace('\'', "''"))
+        }
+        ValueRef::Blob(value) => format!(
+            "X'{}'",
+            value
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<String>()
+        ),
+    }
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "select name from pragma_table_info('{table}') order by cid;"
+        ))
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|name| name.unwrap())
+        .collect()
+}
+
+// Renders a changeset as one SQL statement per changed row. Rows are matched on their primary key
+// in updates and deletes. Column names come from the database's schema; changesets recorded before
+// a column was added have fewer columns, and since columns are only ever added at the end of a
+// table the leading names still line up.
+pub fn changeset_sql(conn: &Connection, mut changes: &[u8]) -> Vec<String> {
+    let input: &mut dyn io::Read = &mut changes;
+    let mut iter = ChangesetIter::start_strm(&input).unwrap();
+    let mut columns_by_table: HashMap<String, Vec<String>> = HashMap::new();
+    let mut statements = vec![];
+    while let Some(item) = iter.next().unwrap() {
+        let op = item.op().unwrap();
+        let table = op.table_name();
+        let columns = columns_by_table
+            .entry(table.to_string())
+            .or_insert_with(|| table_columns(conn, table));
+        let column_count = op.number_of_columns() as usize;
+        let pk = item.pk().unwrap();
+        let primary_key_condition = || {
+            (0..column_count)
+                .filter(|column| pk[*column] != 0)
+                .map(|column| {
+                    format!(
+                        "\"{name}\" = {value}",
+                        name = columns[column],
+                        value = sql_literal(item.old_value(column).unwrap())
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" AND ")
+        };
+        let statement = match op.code() {
+            Action::SQLITE_INSERT => format!(
+                "INSERT INTO \"{table}\" ({names}) VALUES ({values});",
+                names = columns[..column_count]
+                    .iter()
+                    .map(|name| format!("\"{name}\""))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                values = (0..column_count)
+                    .map(|column| sql_literal(item.new_value(column).unwrap()))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ),
+            Action::SQLITE_DELETE => format!(
+                "DELETE FROM \"{table}\" WHERE {condition};",
+                condition = primary_key_condition()
+            ),
+            Action::SQLITE_UPDATE => {
+                // only the changed columns have a new value
+                let assignments = (0..column_count)
+                    .filter_map(|column| match item.new_value(column) {
+                        Ok(value) => Some(format!(
+                            "\"{name}\" = {value}",
+                            name = columns[column],
+                            value = sql_literal(value)
+                        )),
+                        Err(SQLError::InvalidColumnIndex(_)) => None,
+                        Err(err) => panic!("Unable to read changeset: {err}"),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!(
+                    "UPDATE \"{table}\" SET {assignments} WHERE {condition};",
+                    condition = primary_key_condition()
+                )
+            }
+            _ => continue,
+        };
+        statements.push(statement);
+    }
+    statements
+}
+
+// Writes the changes of operations as a SQL script for keeping a copy of gen's tables in another
+// database up to date. Each operation is its own transaction, which also records the operation in
+// the gen_operations table. Returns the number of statements written for changed rows.
+pub fn write_sql_delta(
+    conn: &Connection,
+    operations: &[Operation],
+    writer: &mut impl Write,
+) -> io::Result<usize> {
+    let mut statement_count = 0;
+    for operation in operations {
+        let statements = changeset_sql(conn, &load_changeset(operation));
+        writeln!(
+            writer,
+            "-- operation {hash} ({change_type})",
+            hash = operation.hash,
+            change_type = operation.change_type
+        )?;
+        writeln!(writer, "BEGIN TRANSACTION;")?;
+        for statement in statements.iter() {
+            writeln!(writer, "{statement}")?;
+        }
+        writeln!(
+            writer,
+            "INSERT INTO \"gen_operations\" (\"hash\", \"parent_hash\", \"change_type\", \"timestamp\") VALUES ({hash}, {parent_hash}, {change_type}, {timestamp});",
+            hash = sql_literal(ValueRef::from(operation.hash.as_str())),
+            parent_hash = sql_literal(operation.parent_hash.as_deref().map_or(ValueRef::Null, ValueRef::from)),
+            change_type = sql_literal(ValueRef::from(operation.change_type.as_str())),
+            timestamp = sql_literal(operation.timestamp.as_deref().map_or(ValueRef::Null, ValueRef::from)),
+        )?;
+        writeln!(writer, "COMMIT;")?;
+        statement_count += statements.len();
+    }
+    Ok(statement_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::metadata;
+    use crate::models::operations::{setup_db, Branch, OperationState};
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_sql_delta_replays_operations() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        setup_db(op_conn, &db_uuid);
+        let import = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let update = Operation::get_by_hash(
+            op_conn,
+            &OperationState::get_operation(op_conn, &db_uuid).unwrap(),
+        )
+        .unwrap();
+
+        let branch_id = OperationState::get_current_branch(op_conn, &db_uuid).unwrap();
+        let branch_operations = Branch::get_operations(op_conn, branch_id);
+        let all_operations = operations_since(&branch_operations, &update.hash, None).unwrap();
+        assert_eq!(all_operations.len(), 2);
+        let later_operations =
+            operations_since(&branch_operations, &update.hash, Some(&import.hash[..8])).unwrap();
+        assert_eq!(later_operations, std::slice::from_ref(&update));
+        assert!(
+            operations_since(&branch_operations, &update.hash, Some(&update.hash))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(operations_since(&branch_operations, &update.hash, Some("unknown")).is_none());
+
+        // a fresh database brought up to date with the deltas holds the same sequences
+        let mirror = &get_connection(None);
+        mirror.execute_batch(OPERATIONS_TABLE_SQL).unwrap();
+        for operations in [
+            operations_since(&branch_operations, &import.hash, None).unwrap(),
+            later_operations,
+        ] {
+            let mut script = vec![];
+            write_sql_delta(conn, operations, &mut script).unwrap();
+            mirror
+                .execute_batch(&String::from_utf8(script).unwrap())
+                .unwrap();
+        }
+        for sample_name in [None, Some("child")] {
+            let sequence = |conn: &Connection| {
+                BlockGroup::get_current_path(conn, get_sample_bg(conn, "test", sample_name).id)
+                    .sequence(conn)
+            };
+            assert_eq!(sequence(mirror), sequence(conn));
+        }
+        let synced_hashes = mirror
+            .prepare("select hash from gen_operations order by rowid")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|hash| hash.unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(synced_hashes, vec![import.hash, update.hash]);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::config::{get_operation_connection, RepositoryLock, RepositoryLockError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ForkError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{0}")]
+    RepositoryLock(#[from] RepositoryLockError),
+    #[error("{0} already exists and isn't empty")]
+    DestinationExists(PathBuf),
+    #[error("Can't fork a repository into itself ({0})")]
+    DestinationInside(PathBuf),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ForkSummary {
+    pub linked_files: u64,
+    pub copied_files: u64,
+    pub linked_bytes: u64,
+    pub copied_bytes: u64,
+}
+
+// Changesets and their dependency files are named after the operation they record, and sequence
+// objects after their contents, so none of them change once written and a fork can share them
+// with the original.
+fn is_immutable(path: &Path) -> bool {
+    let parent = path.parent();
+    let in_changeset_dir = parent
+        .and_then(|parent| parent.file_name())
+        .is_some_and(|name| name == "changeset");
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let is_changeset = in_changeset_dir && matches!(extension, Some("cs") | Some("dep"));
+    // objects are split into directories on the first two characters of their hash
+    let is_object = parent
+        .and_then(|parent| parent.parent())
+        .is_some_and(|objects_dir| objects_dir.ends_with(".gen/objects"));
+    is_changeset || is_object
+}
+
+// The repository lock belongs to the original, a fork starts unlocked.
+fn is_lock(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "lock")
+        && path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .is_some_and(|name| name == ".gen")
+}
+
+fn fork_dir(source: &Path, destination: &Path, summary: &mut ForkSummary) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let source_path = entry.path();
+        let destination_path = destination.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fork_dir(&source_path, &destination_path, summary)?;
+        } else if is_lock(&source_path) {
+            continue;
+        } else if file_type.is_symlink() {
+            fs::copy(&source_path, &destination_path)?;
+        } else {
+            let length = entry.metadata()?.len();
+            // hard links can't cross filesystems, in which case the file is copied
+            if is_immutable(&source_path) && fs::hard_link(&source_path, &destination_path).is_ok()
+            {
+                summary.linked_files += 1;
+                summary.linked_bytes += length;
+            } else {
+                // fs::copy clones the file on filesystems with copy-on-write support
+                fs::copy(&source_path, &destination_path)?;
+                summary.copied_files += 1;
+                summary.copied_bytes += length;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Makes a copy of the repository rooted at source, the directory holding its .gen directory, at
+// destination. Changesets and sequence objects are hard linked where possible and everything
+// else, such as the databases, is copied. A default database given as an absolute path within the
+// repository is pointed at its copy. The original is locked while it's copied, waiting for a
+// command changing it to finish if `wait` is given.
+pub fn fork_repository(
+    source: &Path,
+    destination: &Path,
+    wait: bool,
+) -> Result<ForkSummary, ForkError> {
+    let source = source.canonicalize()?;
+    if destination.exists() && fs::read_dir(destination)?.next().is_some() {
+        return Err(ForkError::DestinationExists(destination.to_path_buf()));
+    }
+    fs::create_dir_all(destination)?;
+    let destination = destination.canonicalize()?;
+    if destination.starts_with(&source) {
+        return Err(ForkError::DestinationInside(destination));
+    }
+
+    let lock = RepositoryLock::acquire_in(&source.join(".gen"), wait)?;
+    let mut summary = ForkSummary::default();
+    fork_dir(&source, &destination, &mut summary)?;
+    drop(lock);
+
+    let operation_conn = get_operation_connection(destination.join(".gen").join("gen.db"));
+    let db_name: Option<String> =
+        operation_conn.query_row("select db_name from defaults where id = 1", [], |row| {
+            row.get(0)
+        })?;
+    if let Some(relative_path) = db_name
+        .as_ref()
+        .and_then(|db_name| Path::new(db_name).strip_prefix(&source).ok())
+    {
+        operation_conn.execute(
+            "update defaults set db_name = ?1 where id = 1",
+            (destination.join(relative_path).to_str().unwrap(),),
+        )?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_forks_repository() {
+        let source = tempdir().unwrap().into_path();
+        let changeset_dir = source.join(".gen").join("db-uuid").join("changeset");
+        fs::create_dir_all(&changeset_dir).unwrap();
+        fs::write(changeset_dir.join("abc.cs"), "changes").unwrap();
+        fs::write(changeset_dir.join("abc.dep"), "dependencies").unwrap();
+        fs::write(source.join("data.db"), "database").unwrap();
+        let object_dir = source.join(".gen").join("objects").join("a2");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::write(object_dir.join("5fd98a"), "bases").unwrap();
+        fs::write(source.join(".gen").join("lock"), "1234").unwrap();
+        let operation_conn = get_operation_connection(source.join(".gen").join("gen.db"));
+        operation_conn
+            .execute(
+                "update defaults set db_name = ?1 where id = 1",
+                (source.join("data.db").to_str().unwrap(),),
+            )
+            .unwrap();
+        drop(operation_conn);
+
+        let destination = tempdir().unwrap().into_path().join("fork");
+        let summary = fork_repository(&source, &destination, false).unwrap();
+        assert_eq!(summary.linked_files, 3);
+        assert_eq!(summary.copied_files, 2);
+        assert!(!destination.join(".gen").join("lock").exists());
+
+        let forked_changeset_dir = destination.join(".gen").join("db-uuid").join("changeset");
+        assert_eq!(
+            fs::read_to_string(forked_changeset_dir.join("abc.cs")).unwrap(),
+            "changes"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |path: &Path| fs::metadata(path).unwrap().ino();
+            assert_eq!(
+                inode(&forked_changeset_dir.join("abc.cs")),
+                inode(&changeset_dir.join("abc.cs"))
+            );
+            assert_eq!(
+                inode(&destination.join(".gen/objects/a2/5fd98a")),
+                inode(&object_dir.join("5fd98a"))
+            );
+            assert_ne!(
+                inode(&destination.join("data.db")),
+                inode(&source.join("data.db"))
+            );
+        }
+
+        // the copy can change without touching the original
+        fs::write(destination.join("data.db"), "changed").unwrap();
+        assert_eq!(
+            fs::read_to_string(source.join("data.db")).unwrap(),
+            "database"
+        );
+        let forked_db_name: String = get_operation_connection(destination.join(".gen/gen.db"))
+            .query_row("select db_name from defaults where id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(
+            PathBuf::from(forked_db_name),
+            destination.canonicalize().unwrap().join("data.db")
+        );
+
+        assert!(matches!(
+            fork_repository(&source, &destination, false),
+            Err(ForkError::DestinationExists(_))
+        ));
+        assert!(matches!(
+            fork_repository(&source, &source.join("inner"), false),
+            Err(ForkError::DestinationInside(_))
+        ));
+    }
+}
@@ -0,0 +1,459 @@
+use crate::config::get_changeset_path;
+use crate::models::node::Node;
+use crate::models::operations::{Branch, Operation};
+use crate::models::strand::Strand;
+use crate::models::traits::Query;
+use crate::operation_management::{changeset_content_hash, changeset_reader, DependencyModels};
+use itertools::Itertools;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FsckError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Unable to write report: {0}")]
+    Report(#[from] serde_json::Error),
+    #[error("Found {0} integrity problems")]
+    Failed(usize),
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckCheck {
+    // consecutive edges of a path meet at the same node, on the same strand and in order, and are
+    // edges of the path's block group
+    PathEdges,
+    // nodes refer to sequences that exist
+    NodeSequences,
+    // edges refer to nodes that exist
+    Edges,
+    // block group edges refer to block groups and edges that exist
+    BlockGroupEdges,
+    // every operation has changeset files whose contents hash to the operation's hash
+    Changesets,
+}
+
+// A broken invariant. The subject names the row or file at fault, such as path 3 or edge 12.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FsckIssue {
+    pub check: FsckCheck,
+    pub subject: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FsckReport {
+    pub paths: usize,
+    pub nodes: usize,
+    pub edges: usize,
+    pub block_group_edges: usize,
+    pub operations: usize,
+    pub issues: Vec<FsckIssue>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn add(&mut self, check: FsckCheck, subject: String, message: String) {
+        self.issues.push(FsckIssue {
+            check,
+            subject,
+            message,
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PathEdgeRow {
+    path_id: i64,
+    block_group_id: i64,
+    index_in_path: i64,
+    edge_id: i64,
+    // None when the edge doesn't exist
+    edge: Option<(i64, i64, Strand, i64, i64, Strand)>,
+    in_block_group: bool,
+}
+
+fn check_paths(conn: &Connection, report: &mut FsckReport) -> Result<(), FsckError> {
+    report.paths = conn.query_row("select count(*) from paths;", [], |row| row.get(0))?;
+    let mut stmt = conn.prepare(
+        "select pe.path_id, p.block_group_id, pe.index_in_path, pe.edge_id, e.id, e.source_node_id, e.source_coordinate, e.source_strand, e.target_node_id, e.target_coordinate, e.target_strand, \
+        exists(select 1 from block_group_edges bge where bge.block_group_id = p.block_group_id and bge.edge_id = pe.edge_id) \
+        from path_edges pe join paths p on p.id = pe.path_id left join edges e on e.id = pe.edge_id order by pe.path_id, pe.index_in_path;",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            let edge_exists = row.get::<_, Option<i64>>(4)?.is_some();
+            Ok(PathEdgeRow {
+                path_id: row.get(0)?,
+                block_group_id: row.get(1)?,
+                index_in_path: row.get(2)?,
+                edge_id: row.get(3)?,
+                edge: if edge_exists {
+                    Some((
+                        row.get(5)?,
+                        row.get(6)?,
+                        row.get(7)?,
+                        row.get(8)?,
+                        row.get(9)?,
+                        row.get(10)?,
+                    ))
+                } else {
+                    None
+                },
+                in_block_group: row.get(11)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<PathEdgeRow>>>()?;
+
+    for (path_id, path_edges) in &rows.into_iter().chunk_by(|row| row.path_id) {
+        let subject = format!("path {path_id}");
+        let path_edges = path_edges.collect::<Vec<PathEdgeRow>>();
+        for row in path_edges.iter() {
+            if row.edge.is_none() {
+                report.add(
+                    FsckCheck::PathEdges,
+                    subject.clone(),
+                    format!(
+                        "edge {edge_id} at index {index} does not exist",
+                        edge_id = row.edge_id,
+                        index = row.index_in_path
+                    ),
+                );
+            } else if !row.in_block_group {
+                report.add(
+                    FsckCheck::PathEdges,
+                    subject.clone(),
+                    format!(
+                        "edge {edge_id} is not an edge of block group {block_group_id}",
+                        edge_id = row.edge_id,
+                        block_group_id = row.block_group_id
+                    ),
+                );
+            }
+        }
+        for (row1, row2) in path_edges.iter().tuple_windows() {
+            let (Some(edge1), Some(edge2)) = (row1.edge, row2.edge) else {
+                continue;
+            };
+            let (_, _, _, target_node_id, target_coordinate, target_strand) = edge1;
+            let (source_node_id, source_coordinate, source_strand, _, _, _) = edge2;
+            let message = if target_node_id != source_node_id {
+                format!("enters node {target_node_id} but leaves node {source_node_id}")
+            } else if target_strand != source_strand {
+                format!("enters node {target_node_id} on a different strand than it leaves it")
+            } else if target_coordinate >= source_coordinate {
+                format!(
+                    "enters node {target_node_id} at {target_coordinate} but leaves it at {source_coordinate}"
+                )
+            } else {
+                continue;
+            };
+            report.add(
+                FsckCheck::PathEdges,
+                subject.clone(),
+                format!(
+                    "edges {edge1_id} and {edge2_id} don't meet: {message}",
+                    edge1_id = row1.edge_id,
+                    edge2_id = row2.edge_id
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_nodes(conn: &Connection, report: &mut FsckReport) -> Result<(), FsckError> {
+    report.nodes = conn.query_row("select count(*) from nodes;", [], |row| row.get(0))?;
+    let mut stmt = conn.prepare(
+        "select n.id, n.sequence_hash from nodes n left join sequences s on s.hash = n.sequence_hash where s.hash is null order by n.id;",
+    )?;
+    let missing = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+    // the start and end nodes have placeholder hashes
+    for (node_id, sequence_hash) in missing
+        .into_iter()
+        .filter(|(node_id, _)| !Node::is_terminal(*node_id))
+    {
+        report.add(
+            FsckCheck::NodeSequences,
+            format!("node {node_id}"),
+            format!("sequence {sequence_hash} does not exist"),
+        );
+    }
+    Ok(())
+}
+
+fn check_edges(conn: &Connection, report: &mut FsckReport) -> Result<(), FsckError> {
+    report.edges = conn.query_row("select count(*) from edges;", [], |row| row.get(0))?;
+    let mut stmt = conn.prepare(
+        "select e.id from edges e left join nodes s on s.id = e.source_node_id left join nodes t on t.id = e.target_node_id where s.id is null or t.id is null order by e.id;",
+    )?;
+    let broken = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    for edge_id in broken {
+        report.add(
+            FsckCheck::Edges,
+            format!("edge {edge_id}"),
+            "refers to a node that does not exist".to_string(),
+        );
+    }
+    Ok(())
+}
+
+fn check_block_group_edges(conn: &Connection, report: &mut FsckReport) -> Result<(), FsckError> {
+    report.block_group_edges =
+        conn.query_row("select count(*) from block_group_edges;", [], |row| {
+            row.get(0)
+        })?;
+    let mut stmt = conn.prepare(
+        "select bge.id, bge.block_group_id, bge.edge_id, bg.id is not null, e.id is not null from block_group_edges bge \
+        left join block_groups bg on bg.id = bge.block_group_id left join edges e on e.id = bge.edge_id \
+        where bg.id is null or e.id is null order by bge.id;",
+    )?;
+    let broken = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<(i64, i64, i64, bool, bool)>>>()?;
+    for (id, block_group_id, edge_id, block_group_exists, edge_exists) in broken {
+        let subject = format!("block group edge {id}");
+        if !block_group_exists {
+            report.add(
+                FsckCheck::BlockGroupEdges,
+                subject.clone(),
+                format!("block group {block_group_id} does not exist"),
+            );
+        }
+        if !edge_exists {
+            report.add(
+                FsckCheck::BlockGroupEdges,
+                subject,
+                format!("edge {edge_id} does not exist"),
+            );
+        }
+    }
+    Ok(())
+}
+
+// Operation hashes are the hash end_operation takes of the content of the changeset on top of the
+// parent operation and its branch. Operations made before hashes were taken of content have the SHA-256 of the
+// changeset followed by its dependencies.
+fn check_changesets(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+    report: &mut FsckReport,
+) -> Result<(), FsckError> {
+    let operations = Operation::query(
+        operation_conn,
+        "select * from operation where db_uuid = ?1 order by hash;",
+        params![db_uuid],
+    );
+    report.operations = operations.len();
+    for operation in operations.iter() {
+        let subject = format!("operation {hash}", hash = operation.hash);
+        let changeset_path = get_changeset_path(operation);
+        let mut contents = vec![];
+        let mut missing = vec![];
+        for extension in ["cs", "dep"] {
+            let file_path = changeset_path.join(format!("{}.{extension}", operation.hash));
+            let mut file_contents = vec![];
+            match changeset_reader(&file_path)
+                .and_then(|mut reader| reader.read_to_end(&mut file_contents))
+            {
+                Ok(_) => contents.push(file_contents),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    missing.push(file_path.display().to_string())
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !missing.is_empty() {
+            for file_path in missing {
+                report.add(
+                    FsckCheck::Changesets,
+                    subject.clone(),
+                    format!("{file_path} is missing"),
+                );
+            }
+            continue;
+        }
+        let mut hasher = Sha256::new();
+        for file_contents in contents.iter() {
+            hasher.update(file_contents);
+        }
+        let actual_hash = format!("{:x}", hasher.finalize());
+        if actual_hash == operation.hash {
+            continue;
+        }
+        let branch_name = Branch::get_by_id(operation_conn, operation.branch_id)
+            .map(|branch| branch.name)
+            .unwrap_or_default();
+        let content_hash = serde_json::from_slice::<DependencyModels>(&contents[1])
+            .ok()
+            .map(|dependencies| {
+                changeset_content_hash(
+                    conn,
+                    &contents[0],
+                    &dependencies,
+                    operation.parent_hash.as_deref(),
+                    &branch_name,
+                )
+            });
+        if content_hash.as_ref() != Some(&operation.hash) {
+            report.add(
+                FsckCheck::Changesets,
+                subject,
+                format!(
+                    "changeset files hash to {hash}",
+                    hash = content_hash.unwrap_or(actual_hash)
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+// Checks the invariants of a database that its schema can't enforce, along with the changeset
+// files of its operations.
+pub fn fsck(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+) -> Result<FsckReport, FsckError> {
+    let mut report = FsckReport::default();
+    check_paths(conn, &mut report)?;
+    check_nodes(conn, &mut report)?;
+    check_edges(conn, &mut report)?;
+    check_block_group_edges(conn, &mut report)?;
+    check_changesets(conn, operation_conn, db_uuid, &mut report)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::metadata;
+    use crate::models::operations::{setup_db, OperationState};
+    use crate::models::path_index::PathIndex;
+    use crate::models::sequence::Sequence;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_finds_broken_references() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            2,
+            5,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let report = fsck(conn, op_conn, &db_uuid).unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert_eq!(report.operations, 2);
+        assert_eq!(report.paths, 3);
+
+        let child_bg = get_sample_bg(conn, "test", Some("child"));
+        let child_path = BlockGroup::get_current_path(conn, child_bg.id);
+        let insert_node_id = PathIndex::blocks(conn, child_path.id)[1].node_id;
+        let insert_hash = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("AAAAAAAA")
+            .hash();
+        let first_path_edge_id: i64 = conn
+            .query_row(
+                "select edge_id from path_edges where path_id = ?1 order by index_in_path limit 1;",
+                [child_path.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let block_group_edge_id: i64 = conn
+            .query_row(
+                "select id from block_group_edges where edge_id = ?1 and block_group_id = ?2;",
+                [first_path_edge_id, child_bg.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        conn.pragma_update(None, "foreign_keys", "0").unwrap();
+        conn.execute("delete from sequences where hash = ?1;", [&insert_hash])
+            .unwrap();
+        conn.execute(
+            "update block_group_edges set edge_id = 9999 where id = ?1;",
+            [block_group_edge_id],
+        )
+        .unwrap();
+        conn.pragma_update(None, "foreign_keys", "1").unwrap();
+        let operation_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+        let operation = Operation::get_by_hash(op_conn, &operation_hash).unwrap();
+        fs::write(
+            get_changeset_path(&operation).join(format!("{operation_hash}.dep")),
+            b"{}",
+        )
+        .unwrap();
+
+        let report = fsck(conn, op_conn, &db_uuid).unwrap();
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|issue| (issue.check, issue.subject.clone()))
+                .collect::<Vec<_>>(),
+            // the child graph's copy of the reference path starts with the same edge
+            vec![
+                (FsckCheck::PathEdges, format!("path {}", child_path.id - 1)),
+                (FsckCheck::PathEdges, format!("path {}", child_path.id)),
+                (FsckCheck::NodeSequences, format!("node {insert_node_id}")),
+                (
+                    FsckCheck::BlockGroupEdges,
+                    format!("block group edge {block_group_edge_id}")
+                ),
+                (FsckCheck::Changesets, format!("operation {operation_hash}")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use crate::models::attribute::AttributeTable;
+use crate::models::node::Node;
+use crate::models::operations::{Branch, Operation, Tag};
+use crate::models::traits::Query;
+use crate::object_store::{list_objects, remove_object};
+use crate::operation_management::{
+    changeset_sequence_hashes, load_changeset, load_changeset_dependencies,
+};
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::io;
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GcError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/*
+   Resets, branch deletions and checkouts that replay operations can leave sequences, nodes and
+   edges behind that no graph uses anymore. Rows of the current state are in use if a graph or path
+   reaches them: edges through block group or path edges, nodes through those edges, accession
+   edges or path indexes, and sequences through those nodes.
+
+   Other states are kept reachable through operations instead. Checking out an operation recreates
+   the rows it made from its changeset, but it needs the sequences it depends on to already be in
+   the database, so the dependencies of every operation on a branch or tag are kept as well.
+   Operations no branch or tag reaches, such as those hidden by a reset, don't protect anything.
+
+   The object store is shared by the databases of a repository, and replaying an operation recreates
+   the sequences it inserted without their bases. So an object is unused once no sequence left in
+   this database names it and no changeset that may be replayed inserts it, which counts every
+   operation of the other databases.
+*/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Garbage {
+    pub reachable_operations: usize,
+    pub abandoned_operations: usize,
+    pub sequence_hashes: Vec<String>,
+    pub node_ids: Vec<i64>,
+    pub edge_ids: Vec<i64>,
+    // the bases stored for the unused sequences, which is most of the space they take
+    pub sequence_bytes: i64,
+    pub object_hashes: Vec<String>,
+    // the size of the files of the unused objects
+    pub object_bytes: i64,
+}
+
+impl Garbage {
+    pub fn is_empty(&self) -> bool {
+        self.sequence_hashes.is_empty()
+            && self.node_ids.is_empty()
+            && self.edge_ids.is_empty()
+            && self.object_hashes.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{sequences} sequences ({bytes} bytes), {objects} sequence objects ({object_bytes} bytes), {nodes} nodes and {edges} edges are unused. {reachable} operations are reachable from a branch or tag and {abandoned} are abandoned.",
+            sequences = self.sequence_hashes.len(),
+            bytes = self.sequence_bytes,
+            objects = self.object_hashes.len(),
+            object_bytes = self.object_bytes,
+            nodes = self.node_ids.len(),
+            edges = self.edge_ids.len(),
+            reachable = self.reachable_operations,
+            abandoned = self.abandoned_operations,
+        )
+    }
+}
+
+// The hashes of the operations of every branch and tag of a database, along with their ancestors.
+pub fn reachable_operations(operation_conn: &Connection, db_uuid: &str) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    for branch in Branch::query(
+        operation_conn,
+        "select * from branch where db_uuid = ?1",
+        vec![Value::from(db_uuid.to_string())],
+    ) {
+        reachable.extend(
+            Branch::get_operations(operation_conn, branch.id)
+                .into_iter()
+                .map(|operation| operation.hash),
+        );
+    }
+    for tag in Tag::query_for_db(operation_conn, db_uuid) {
+        if let Some(operation_hash) = tag.operation_hash {
+            reachable.extend(Operation::get_upstream(operation_conn, operation_hash));
+        }
+    }
+    reachable
+}
+
+pub fn find_garbage(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+) -> Result<Garbage, GcError> {
+    let reachable = reachable_operations(operation_conn, db_uuid);
+    let operations = Operation::query(
+        operation_conn,
+        "select * from operation where db_uuid = ?1",
+        params![db_uuid],
+    );
+    let mut protected_sequences = HashSet::new();
+    for operation in operations
+        .iter()
+        .filter(|operation| reachable.contains(&operation.hash))
+    {
+        let dependencies = load_changeset_dependencies(operation);
+        protected_sequences.extend(dependencies.sequences.into_iter().map(|seq| seq.hash));
+        protected_sequences.extend(
+            dependencies
+                .nodes
+                .into_iter()
+                .map(|node| node.sequence_hash),
+        );
+    }
+
+    let live_rows = "with live_edges as (select edge_id as id from block_group_edges union select edge_id from path_edges), \
+        live_nodes as (select source_node_id as id from edges where id in live_edges union select target_node_id from edges where id in live_edges \
+        union select source_node_id from accession_edges union select target_node_id from accession_edges union select node_id from path_index)";
+    let mut stmt = conn.prepare(&format!(
+        "{live_rows} select id from edges where id not in live_edges order by id;"
+    ))?;
+    let edge_ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    let mut stmt = conn.prepare(&format!(
+        "{live_rows} select id from nodes where id not in live_nodes order by id;"
+    ))?;
+    let node_ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?
+        .into_iter()
+        .filter(|node_id| !Node::is_terminal(*node_id))
+        .collect::<Vec<i64>>();
+    let mut stmt = conn.prepare(&format!(
+        "{live_rows} select hash, length(sequence) + coalesce(length(packed_bases), 0) from sequences where hash not in (select sequence_hash from nodes where id in live_nodes) order by hash;"
+    ))?;
+    let sequences = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, i64)>>>()?
+        .into_iter()
+        .filter(|(hash, _)| !protected_sequences.contains(hash))
+        .collect::<Vec<(String, i64)>>();
+
+    let unused_sequences = sequences
+        .iter()
+        .map(|(hash, _)| hash.as_str())
+        .collect::<HashSet<&str>>();
+    let mut stmt = conn.prepare("select hash from sequences;")?;
+    let mut used_objects = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<HashSet<String>>>()?;
+    used_objects.retain(|hash| !unused_sequences.contains(hash.as_str()));
+    let other_operations = Operation::query(
+        operation_conn,
+        "select * from operation where db_uuid != ?1",
+        params![db_uuid],
+    );
+    for operation in operations
+        .iter()
+        .filter(|operation| reachable.contains(&operation.hash))
+        .chain(other_operations.iter())
+    {
+        used_objects.extend(changeset_sequence_hashes(&load_changeset(operation)));
+    }
+    let objects = list_objects()?
+        .into_iter()
+        .filter(|(hash, _)| !used_objects.contains(hash))
+        .collect::<Vec<(String, u64)>>();
+
+    Ok(Garbage {
+        reachable_operations: reachable.len(),
+        abandoned_operations: operations
+            .iter()
+            .filter(|operation| !reachable.contains(&operation.hash))
+            .count(),
+        sequence_bytes: sequences.iter().map(|(_, length)| length).sum(),
+        sequence_hashes: sequences.into_iter().map(|(hash, _)| hash).collect(),
+        node_ids,
+        edge_ids,
+        object_bytes: objects.iter().map(|(_, size)| *size as i64).sum(),
+        object_hashes: objects.into_iter().map(|(hash, _)| hash).collect(),
+    })
+}
+
+// Deletes unused rows found by find_garbage. Edges go first, as they refer to nodes, which in turn
+// refer to sequences, along with the attributes of the nodes and edges, the coverage of the nodes
+// and the digests of the sequences.
+pub fn collect_garbage(conn: &Connection, garbage: &Garbage) -> rusqlite::Result<()> {
+    let edge_ids = garbage
+        .edge_ids
+        .iter()
+        .map(|id| Value::from(*id))
+        .collect::<Vec<Value>>();
+    let node_ids = garbage
+        .node_ids
+        .iter()
+        .map(|id| Value::from(*id))
+        .collect::<Vec<Value>>();
+    let sequence_hashes = garbage
+        .sequence_hashes
+        .iter()
+        .map(|hash| Value::from(hash.clone()))
+        .collect::<Vec<Value>>();
+    let edge_ids = Rc::new(edge_ids);
+    AttributeTable::Edge.delete_for_ids(conn, edge_ids.clone())?;
+    conn.execute(
+        "delete from edges where id in rarray(?1);",
+        params![edge_ids],
+    )?;
+    let node_ids = Rc::new(node_ids);
+    AttributeTable::Node.delete_for_ids(conn, node_ids.clone())?;
+    conn.execute(
+        "delete from node_coverage where node_id in rarray(?1);",
+        params![node_ids.clone()],
+    )?;
+    conn.execute(
+        "delete from nodes where id in rarray(?1);",
+        params![node_ids],
+    )?;
+    let sequence_hashes = Rc::new(sequence_hashes);
+    conn.execute(
+        "delete from sequence_digests where sequence_hash in rarray(?1);",
+        params![sequence_hashes.clone()],
+    )?;
+    conn.execute(
+        "delete from sequences where hash in rarray(?1);",
+        params![sequence_hashes],
+    )?;
+    Ok(())
+}
+
+// Deletes the unused objects found by find_garbage. This is done once the rows are deleted and
+// committed, so a failed collection doesn't leave sequences without their bases.
+pub fn collect_garbage_objects(garbage: &Garbage) -> io::Result<()> {
+    for hash in garbage.object_hashes.iter() {
+        remove_object(hash)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::collection::Collection;
+    use crate::models::edge::Edge;
+    use crate::models::metadata;
+    use crate::models::node::PATH_START_NODE_ID;
+    use crate::models::node_coverage::NodeCoverage;
+    use crate::models::operations::{setup_db, OperationState};
+    use crate::models::sample::Sample;
+    use crate::models::sequence::{Sequence, SequenceStore};
+    use crate::models::sequence_digest::SequenceDigest;
+    use crate::models::strand::Strand;
+    use crate::object_store::{has_object, object_path, write_object};
+    use crate::operation_management::{checkout, reset};
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_collects_unused_rows() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        assert!(find_garbage(conn, op_conn, &db_uuid).unwrap().is_empty());
+        let import_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+
+        // an update made on another branch, which checking out main reverts
+        Branch::create(op_conn, &db_uuid, "other");
+        checkout(conn, op_conn, &db_uuid, &Some("other".to_string()), None);
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            2,
+            5,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let update_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+        checkout(conn, op_conn, &db_uuid, &Some("main".to_string()), None);
+
+        // rows left behind that nothing uses
+        let unused_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("GATTACA")
+            .save(conn);
+        let unused_digest = SequenceDigest::create(conn, &unused_sequence.hash, "GATTACA");
+        let unused_node_id = Node::create(conn, &unused_sequence.hash, None);
+        let unused_edge = Edge::create(
+            conn,
+            PATH_START_NODE_ID,
+            0,
+            Strand::Forward,
+            unused_node_id,
+            0,
+            Strand::Forward,
+        );
+        Node::set_attribute(conn, unused_node_id, "source", "test");
+        Edge::set_attribute(conn, unused_edge.id, "source", "test");
+        NodeCoverage::bulk_create(
+            conn,
+            &[NodeCoverage {
+                track: "reads".to_string(),
+                node_id: unused_node_id,
+                start: 0,
+                end: 7,
+                depth: 3.0,
+            }],
+        );
+
+        let garbage = find_garbage(conn, op_conn, &db_uuid).unwrap();
+        assert_eq!(
+            garbage,
+            Garbage {
+                reachable_operations: 2,
+                abandoned_operations: 0,
+                sequence_hashes: vec![unused_sequence.hash.clone()],
+                node_ids: vec![unused_node_id],
+                edge_ids: vec![unused_edge.id],
+                sequence_bytes: 7,
+                object_hashes: vec![],
+                object_bytes: 0,
+            }
+        );
+        collect_garbage(conn, &garbage).unwrap();
+        assert!(find_garbage(conn, op_conn, &db_uuid).unwrap().is_empty());
+        assert!(Sequence::sequence_from_hash(conn, &unused_sequence.hash).is_none());
+        assert!(SequenceDigest::find(conn, &unused_digest.md5).is_none());
+        assert!(NodeCoverage::query_for_nodes(conn, "reads", &[unused_node_id]).is_empty());
+
+        // the other branch can still be checked out
+        checkout(conn, op_conn, &db_uuid, &Some("other".to_string()), None);
+        assert_eq!(
+            OperationState::get_operation(op_conn, &db_uuid).unwrap(),
+            update_hash
+        );
+        let child_bg = get_sample_bg(conn, "test", Some("child"));
+        assert_eq!(
+            BlockGroup::get_current_path(conn, child_bg.id).sequence(conn),
+            "ATAAAAAAAATCGATCGATCGATCGGGAACACACAGAGA"
+        );
+
+        // once reset away, the update no longer protects anything
+        reset(conn, op_conn, &db_uuid, &import_hash);
+        let garbage = find_garbage(conn, op_conn, &db_uuid).unwrap();
+        assert!(garbage.is_empty());
+        assert_eq!(garbage.reachable_operations, 1);
+        assert_eq!(garbage.abandoned_operations, 1);
+    }
+
+    #[test]
+    fn test_collects_unused_objects() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let other_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/multiseq.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        Collection::set_sequence_store(conn, "test", SequenceStore::Objects).unwrap();
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let object_hash = |bases: &str| {
+            Sequence::new()
+                .sequence_type("DNA")
+                .sequence(bases)
+                .build()
+                .hash
+        };
+        let imported_hash = object_hash("ATCGATCGATCGATCGATCGGGAACACACAGAGA");
+        assert!(has_object(&imported_hash));
+
+        // sequences imported on another branch only have their objects once main is checked out
+        Branch::create(op_conn, &db_uuid, "other");
+        checkout(conn, op_conn, &db_uuid, &Some("other".to_string()), None);
+        import_fasta(
+            &other_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        checkout(conn, op_conn, &db_uuid, &Some("main".to_string()), None);
+
+        let unused_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("GATTACA")
+            .store(SequenceStore::Objects)
+            .save(conn);
+        let orphan_hash = object_hash("CCCCGGGG");
+        write_object(&orphan_hash, "CCCCGGGG").unwrap();
+        let orphan_size = fs::metadata(object_path(&orphan_hash)).unwrap().len();
+        let unused_size = fs::metadata(object_path(&unused_sequence.hash))
+            .unwrap()
+            .len();
+
+        let garbage = find_garbage(conn, op_conn, &db_uuid).unwrap();
+        assert_eq!(garbage.sequence_hashes, vec![unused_sequence.hash.clone()]);
+        let mut expected_objects = vec![unused_sequence.hash.clone(), orphan_hash.clone()];
+        expected_objects.sort();
+        assert_eq!(garbage.object_hashes, expected_objects);
+        assert_eq!(garbage.object_bytes, (orphan_size + unused_size) as i64);
+
+        collect_garbage(conn, &garbage).unwrap();
+        collect_garbage_objects(&garbage).unwrap();
+        assert!(!has_object(&unused_sequence.hash));
+        assert!(!has_object(&orphan_hash));
+        assert!(has_object(&imported_hash));
+        assert!(find_garbage(conn, op_conn, &db_uuid).unwrap().is_empty());
+
+        // the other branch's sequences can still be read once it is checked out
+        checkout(conn, op_conn, &db_uuid, &Some("other".to_string()), None);
+        let sequences = Sample::get_block_groups(conn, "test", None)
+            .iter()
+            .map(|block_group| BlockGroup::get_current_path(conn, block_group.id).sequence(conn))
+            .collect::<Vec<String>>();
+        assert_eq!(sequences.len(), 3);
+        assert!(sequences.iter().all(|sequence| !sequence.is_empty()));
+    }
+}
@@ -1,6 +1,8 @@
+use crate::models::strand::Strand;
 use crate::normalize_string;
 use crate::operation_management::OperationError;
-use gb_io::seq::{Location, Seq};
+use crate::range::Range;
+use gb_io::seq::{Feature, Location, Seq, Topology};
 use regex::{Error as RegexError, Regex};
 use std::fmt;
 use std::str::{self, FromStr};
@@ -18,6 +20,15 @@ pub enum GenBankError {
     OperationError(#[from] OperationError),
     #[error("Regex Error: {0}")]
     Regex(#[from] RegexError),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{name} is not a {sequence_type} sequence: it has {character} at position {position}")]
+    InvalidSequence {
+        name: String,
+        sequence_type: String,
+        character: char,
+        position: usize,
+    },
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -61,12 +72,24 @@ pub struct GenBankEdit {
     pub edit_type: EditType,
 }
 
+// A feature whose location joins several ranges of the sequence, such as the exons of a CDS. The
+// ranges are in the order they are read, each with its strand, so complemented ranges are on the
+// reverse strand and read from their end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenBankFeature {
+    pub name: String,
+    pub kind: String,
+    pub ranges: Vec<(Range, Strand)>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct GenBankLocus {
     pub name: String,
     pub molecule_type: Option<String>,
+    pub is_circular: bool,
     pub sequence: String,
     pub changes: Vec<GenBankEdit>,
+    pub joined_features: Vec<GenBankFeature>,
 }
 
 impl GenBankLocus {
@@ -114,6 +137,52 @@ impl GenBankLocus {
     }
 }
 
+// The ranges of a location in the order they are read. Locations that aren't made of ranges of
+// the sequence, such as those between two bases or in other records, have none.
+fn location_ranges(location: &Location) -> Option<Vec<(Range, Strand)>> {
+    match location {
+        Location::Range((start, _), (end, _)) => Some(vec![(
+            Range {
+                start: *start,
+                end: *end,
+            },
+            Strand::Forward,
+        )]),
+        Location::Complement(location) => Some(
+            location_ranges(location)?
+                .into_iter()
+                .rev()
+                .map(|(range, strand)| (range, strand.flipped()))
+                .collect(),
+        ),
+        Location::Join(locations) | Location::Order(locations) => {
+            let mut ranges = vec![];
+            for location in locations {
+                ranges.extend(location_ranges(location)?);
+            }
+            Some(ranges)
+        }
+        _ => None,
+    }
+}
+
+// Features are named for the first of their label, gene, locus tag or product, and their kind.
+fn feature_name(feature: &Feature) -> String {
+    let label = ["label", "gene", "locus_tag", "product"]
+        .iter()
+        .find_map(|key| {
+            feature
+                .qualifiers
+                .iter()
+                .find(|(qualifier, value)| qualifier == *key && value.is_some())
+                .and_then(|(_, value)| value.clone())
+        });
+    match label {
+        Some(label) => format!("{label}_{kind}", kind = feature.kind),
+        None => feature.kind.to_string(),
+    }
+}
+
 pub fn process_sequence(seq: Seq) -> Result<GenBankLocus, GenBankError> {
     let final_sequence = if let Ok(sequence) = str::from_utf8(&seq.seq) {
         sequence.to_string()
@@ -126,10 +195,21 @@ pub fn process_sequence(seq: Seq) -> Result<GenBankLocus, GenBankError> {
         name: seq.name.unwrap_or_default(),
         sequence: final_sequence.clone(),
         molecule_type: seq.molecule_type,
+        is_circular: seq.topology == Topology::Circular,
         changes: vec![],
+        joined_features: vec![],
     };
 
     for feature in seq.features.iter() {
+        if let Some(ranges) = location_ranges(&feature.location) {
+            if ranges.len() > 1 {
+                locus.joined_features.push(GenBankFeature {
+                    name: feature_name(feature),
+                    kind: feature.kind.to_string(),
+                    ranges,
+                });
+            }
+        }
         for (key, value) in feature.qualifiers.iter() {
             if key == "note" {
                 if let Some(v) = value {
@@ -187,7 +267,7 @@ pub fn process_sequence(seq: Seq) -> Result<GenBankLocus, GenBankError> {
         }
     }
 
-    locus.changes.sort_unstable_by(|a, b| a.start.cmp(&b.start));
+    locus.changes.sort_unstable_by_key(|change| change.start);
     Ok(locus)
 }
 
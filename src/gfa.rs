@@ -1,3 +1,4 @@
+use crate::models::operations::ExportedSegment;
 use crate::models::strand::Strand;
 use convert_case::{Case, Casing};
 use std::fs::File;
@@ -26,22 +27,61 @@ pub struct Path {
     pub node_strands: Vec<Strand>,
 }
 
+// A GFA 1.1 walk (W line): the haplotype of a sample through a sequence, such as a chromosome, as
+// the oriented segments it goes through.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Walk {
+    pub sample: String,
+    pub haplotype_index: i64,
+    pub sequence_name: String,
+    pub length: i64,
+    pub segment_ids: Vec<String>,
+    pub node_strands: Vec<Strand>,
+}
+
 impl Segment {
     pub fn segment_id(&self) -> String {
         format!("{}.{}", self.node_id, self.sequence_start)
     }
+
+    pub fn exported(&self) -> ExportedSegment {
+        ExportedSegment {
+            segment_id: self.segment_id(),
+            node_id: self.node_id,
+            sequence_start: self.sequence_start,
+            sequence_end: self.sequence_start + self.sequence.len() as i64,
+        }
+    }
 }
 
-fn segment_line(segment: &Segment) -> String {
+// Segments and links without optional fields are written as gen always has, with a * for the
+// segment's fields.
+fn segment_line(segment: &Segment, tags: &[String]) -> String {
     // NOTE: We encode the node ID and start coordinate in the segment ID
-    format!("S\t{}\t{}\t*\n", segment.segment_id(), segment.sequence)
+    let tags = if tags.is_empty() {
+        "*".to_string()
+    } else {
+        tags.join("\t")
+    };
+    format!(
+        "S\t{}\t{}\t{}\n",
+        segment.segment_id(),
+        segment.sequence,
+        tags
+    )
 }
 
-fn link_line(link: &Link) -> String {
-    format!(
-        "L\t{}\t{}\t{}\t{}\t0M\n",
+fn link_line(link: &Link, tags: &[String]) -> String {
+    let mut line = format!(
+        "L\t{}\t{}\t{}\t{}\t0M",
         link.source_segment_id, link.source_strand, link.target_segment_id, link.target_strand
-    )
+    );
+    for tag in tags {
+        line.push('\t');
+        line.push_str(tag);
+    }
+    line.push('\n');
+    line
 }
 
 pub fn path_line(path: &Path) -> String {
@@ -55,28 +95,56 @@ pub fn path_line(path: &Path) -> String {
     format!("P\t{}\t{}\t*\n", path.name.to_case(Case::Train), segments)
 }
 
-pub fn write_segments(writer: &mut BufWriter<File>, segments: &Vec<Segment>) {
+pub fn walk_line(walk: &Walk) -> String {
+    let segments = walk
+        .segment_ids
+        .iter()
+        .zip(walk.node_strands.iter())
+        .map(|(segment_id, node_strand)| {
+            let orientation = if *node_strand == Strand::Reverse {
+                '<'
+            } else {
+                '>'
+            };
+            format!("{orientation}{segment_id}")
+        })
+        .collect::<String>();
+    format!(
+        "W\t{}\t{}\t{}\t0\t{}\t{}\n",
+        walk.sample, walk.haplotype_index, walk.sequence_name, walk.length, segments
+    )
+}
+
+pub fn write_segments(writer: &mut BufWriter<File>, segments: &[Segment]) {
     for segment in segments {
-        writer
-            .write_all(&segment_line(segment).into_bytes())
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Error writing segment with sequence {} to GFA stream",
-                    segment.sequence,
-                )
-            });
+        write_segment_with_tags(writer, segment, &[]);
     }
 }
 
-pub fn write_links(writer: &mut BufWriter<File>, links: &Vec<Link>) {
+pub fn write_segment_with_tags(writer: &mut BufWriter<File>, segment: &Segment, tags: &[String]) {
+    writer
+        .write_all(&segment_line(segment, tags).into_bytes())
+        .unwrap_or_else(|_| {
+            panic!(
+                "Error writing segment with sequence {} to GFA stream",
+                segment.sequence,
+            )
+        });
+}
+
+pub fn write_links(writer: &mut BufWriter<File>, links: &[Link]) {
     for link in links {
-        writer
-            .write_all(&link_line(link).into_bytes())
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Error writing link from segment {:?} to {:?} to GFA stream",
-                    link.source_segment_id, link.target_segment_id,
-                )
-            });
+        write_link_with_tags(writer, link, &[]);
     }
 }
+
+pub fn write_link_with_tags(writer: &mut BufWriter<File>, link: &Link, tags: &[String]) {
+    writer
+        .write_all(&link_line(link, tags).into_bytes())
+        .unwrap_or_else(|_| {
+            panic!(
+                "Error writing link from segment {:?} to {:?} to GFA stream",
+                link.source_segment_id, link.target_segment_id,
+            )
+        });
+}
@@ -102,16 +102,35 @@ impl Opt for () {
 
 impl Opt for SeqIndex {
     fn parse1(input: Option<&str>, s: &mut String) -> Self {
-        if input.is_none() {
-            SeqIndex([0, 0])
-        } else {
-            let input = input.unwrap();
+        if let Some(input) = input {
             s.push_str(input);
             Self([s.len() - input.len(), s.len()])
+        } else {
+            SeqIndex([0, 0])
         }
     }
 }
 
+/// Optional fields as they are written, such as RC:i:120
+#[derive(Debug, Clone, Default, Ord, Eq, PartialOrd, PartialEq)]
+pub struct Tags(pub Vec<String>);
+
+impl Opt for Tags {
+    fn parse1(input: Option<&str>, _s: &mut String) -> Self {
+        Tags(
+            input
+                .map(|input| input.split('\t').map(str::to_string).collect())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// All optional fields left on a line, joined by tabs, so a line's opt holds every one of them
+fn optional_fields<'a>(fields: impl Iterator<Item = &'a str>) -> Option<String> {
+    let fields = fields.collect::<Vec<&str>>();
+    (!fields.is_empty()).then(|| fields.join("\t"))
+}
+
 ///  Start position and end position of a sequence
 ///
 /// Similar to a slice
@@ -253,23 +272,23 @@ impl<T: SampleType + Ord + Clone, S: Opt + Ord + Clone, U: Opt> Gfa<T, S, U> {
                         if version_number < 2.0 {
                             let sequence = split_line.next().unwrap();
                             let size = sequence.len() as u32;
-                            let opt = split_line.next();
+                            let opt = optional_fields(split_line);
                             z.segments.push(Segment {
                                 id: T::parse1(name, &mut z.sequence),
                                 sequence: SeqIndex::parse1(sequence, &mut z.sequence),
                                 length: size,
-                                opt: S::parse1(opt, &mut z.sequence),
+                                opt: S::parse1(opt.as_deref(), &mut z.sequence),
                             });
                         } else {
                             let sequence = split_line.next().unwrap();
                             let size = split_line.next().unwrap().parse().unwrap();
-                            let opt = split_line.next();
+                            let opt = optional_fields(split_line);
 
                             z.segments.push(Segment {
                                 id: T::parse1(name, &mut z.sequence),
                                 sequence: SeqIndex::parse1(sequence, &mut z.sequence),
                                 length: size,
-                                opt: S::parse1(opt, &mut z.sequence),
+                                opt: S::parse1(opt.as_deref(), &mut z.sequence),
                             });
                         }
                     }
@@ -283,14 +302,14 @@ impl<T: SampleType + Ord + Clone, S: Opt + Ord + Clone, U: Opt> Gfa<T, S, U> {
                         let to = split_line.next().unwrap();
                         let to_dir = split_line.next().unwrap() == "+";
                         let overlap = split_line.next();
-                        let opt = split_line.next();
+                        let opt = optional_fields(split_line);
                         z.links.push(Link {
                             from: T::parse1(from, &mut z.sequence),
                             from_dir,
                             to: T::parse1(to, &mut z.sequence),
                             to_dir,
                             overlap: U::parse1(overlap, &mut z.sequence),
-                            opt: S::parse1(opt, &mut z.sequence),
+                            opt: S::parse1(opt.as_deref(), &mut z.sequence),
                         });
                     }
                     "P" => {
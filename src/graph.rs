@@ -1,3 +1,5 @@
+pub mod bubbles;
+
 use crate::models::block_group::NodeIntervalBlock;
 use crate::models::strand::Strand;
 use interavl::IntervalTree as IT2;
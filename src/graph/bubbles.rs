@@ -0,0 +1,355 @@
+use crate::graph::{all_simple_paths, GraphEdge, GraphNode};
+use crate::models::block_group::BlockGroup;
+use crate::models::node::Node;
+use crate::models::path::Path;
+use crate::models::path_index::PathIndex;
+use crate::models::strand::Strand;
+use crate::models::traits::*;
+use crate::range::Range;
+use itertools::Itertools;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
+use rusqlite::types::Value as SQLValue;
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+// Bubbles with many nested branches have more walks than are worth spelling out, so only this many
+// of them are turned into alleles. The alleles the paths of samples take are always listed.
+const MAX_WALK_ALLELES: usize = 64;
+
+// A part of a graph that can only be entered through its source and only be left through its sink.
+// Every walk from the source reaches the sink, and no walk goes from the sink back to the source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Superbubble {
+    pub source: GraphNode,
+    pub sink: GraphNode,
+    pub interior: Vec<GraphNode>,
+}
+
+// A bubble with the sequences between its source and sink, the first of which is the one the
+// current path of the graph takes. The range is where the bubble is on that path, if it goes
+// through it, and each sample with the same graph is listed with the index of its allele, or none
+// if its current path doesn't go through the bubble.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantSite {
+    pub bubble: Superbubble,
+    pub range: Option<Range>,
+    pub alleles: Vec<String>,
+    pub sample_alleles: Vec<(Option<String>, Option<usize>)>,
+}
+
+pub fn find_superbubbles(conn: &Connection, block_group_id: i64) -> Vec<Superbubble> {
+    superbubbles(&BlockGroup::get_graph(conn, block_group_id))
+}
+
+// Only blocks with more than one edge out of them can start a bubble. Nested bubbles are listed
+// separately, each with the nearest sink of its source.
+pub fn superbubbles(graph: &DiGraphMap<GraphNode, GraphEdge>) -> Vec<Superbubble> {
+    graph
+        .nodes()
+        .filter(|node| graph.neighbors_directed(*node, Direction::Outgoing).count() > 1)
+        .sorted()
+        .filter_map(|source| superbubble_from(graph, source))
+        .collect()
+}
+
+// Walks out of the source, only moving into a block once every edge into it has been walked, until
+// a single block is left that everything walked leads to (Onodera et al., 2013). Reaching a tip or
+// the source again means there is no bubble.
+fn superbubble_from(
+    graph: &DiGraphMap<GraphNode, GraphEdge>,
+    source: GraphNode,
+) -> Option<Superbubble> {
+    let mut seen = HashSet::from([source]);
+    let mut visited = HashSet::new();
+    let mut interior = vec![];
+    let mut stack = vec![source];
+    while let Some(node) = stack.pop() {
+        seen.remove(&node);
+        visited.insert(node);
+        if node != source {
+            interior.push(node);
+        }
+        let children = graph
+            .neighbors_directed(node, Direction::Outgoing)
+            .collect::<Vec<GraphNode>>();
+        if children.is_empty() {
+            return None;
+        }
+        for child in children {
+            if child == source {
+                return None;
+            }
+            seen.insert(child);
+            if graph
+                .neighbors_directed(child, Direction::Incoming)
+                .all(|parent| visited.contains(&parent))
+            {
+                stack.push(child);
+            }
+        }
+        if stack.len() == 1 && seen.len() == 1 && seen.contains(&stack[0]) {
+            let sink = stack.pop().unwrap();
+            if graph.contains_edge(sink, source) {
+                return None;
+            }
+            interior.sort();
+            return Some(Superbubble {
+                source,
+                sink,
+                interior,
+            });
+        }
+    }
+    None
+}
+
+// The sequences of the walks through a bubble, without its source and sink.
+pub fn walk_alleles(
+    conn: &Connection,
+    graph: &DiGraphMap<GraphNode, GraphEdge>,
+    bubble: &Superbubble,
+) -> Vec<String> {
+    let node_ids = bubble
+        .interior
+        .iter()
+        .map(|block| block.node_id)
+        .unique()
+        .collect::<Vec<i64>>();
+    let sequences_by_node_id = Node::get_sequences_by_node_ids(conn, &node_ids);
+    all_simple_paths(graph, bubble.source, bubble.sink)
+        .take(MAX_WALK_ALLELES)
+        .map(|walk| {
+            walk[..walk.len() - 1]
+                .iter()
+                .tuple_windows()
+                .map(|(previous, block)| {
+                    let node_sequence = &sequences_by_node_id[&block.node_id];
+                    let sequence =
+                        node_sequence.get_sequence(block.sequence_start, block.sequence_end);
+                    if graph.edge_weight(*previous, *block).unwrap().target_strand
+                        == Strand::Reverse
+                    {
+                        node_sequence.molecule().reverse_complement(&sequence)
+                    } else {
+                        sequence
+                    }
+                })
+                .collect::<String>()
+        })
+        .unique()
+        .collect()
+}
+
+// Where a path leaves the source of a bubble and enters its sink, when it goes through both on the
+// forward strand.
+fn bubble_range(conn: &Connection, path: &Path, bubble: &Superbubble) -> Option<Range> {
+    let start = if Node::is_start_node(bubble.source.node_id) {
+        0
+    } else {
+        PathIndex::node_blocks(conn, path.id, bubble.source.node_id)
+            .iter()
+            .find(|block| {
+                block.strand == Strand::Forward
+                    && block.sequence_start < bubble.source.sequence_end
+                    && bubble.source.sequence_end <= block.sequence_end
+            })
+            .map(|block| block.start + bubble.source.sequence_end - block.sequence_start)?
+    };
+    let end = if Node::is_end_node(bubble.sink.node_id) {
+        path.length(conn)
+    } else {
+        PathIndex::node_blocks(conn, path.id, bubble.sink.node_id)
+            .iter()
+            .map(|block| {
+                (
+                    block,
+                    block.start + bubble.sink.sequence_start - block.sequence_start,
+                )
+            })
+            .find(|(block, position)| {
+                block.strand == Strand::Forward
+                    && block.sequence_start <= bubble.sink.sequence_start
+                    && bubble.sink.sequence_start < block.sequence_end
+                    && *position >= start
+            })
+            .map(|(_, position)| position)?
+    };
+    Some(Range { start, end })
+}
+
+// The bubbles of a graph with the alleles the current paths of every sample with a graph of the
+// same name take through them. Alleles only found on the path of another sample are added after
+// the ones of the graph itself.
+pub fn variant_sites(conn: &Connection, block_group: &BlockGroup) -> Vec<VariantSite> {
+    let graph = BlockGroup::get_graph(conn, block_group.id);
+    let current_path = BlockGroup::try_get_current_path(conn, block_group.id);
+    let sample_paths = BlockGroup::query(
+        conn,
+        "select * from block_groups where collection_name = ?1 and name = ?2 order by sample_name;",
+        rusqlite::params!(
+            SQLValue::from(block_group.collection_name.clone()),
+            SQLValue::from(block_group.name.clone())
+        ),
+    )
+    .into_iter()
+    .map(|sample_block_group| {
+        (
+            sample_block_group.sample_name,
+            BlockGroup::try_get_current_path(conn, sample_block_group.id),
+        )
+    })
+    .collect::<Vec<(Option<String>, Option<Path>)>>();
+
+    superbubbles(&graph)
+        .into_iter()
+        .map(|bubble| {
+            let range = current_path
+                .as_ref()
+                .and_then(|path| bubble_range(conn, path, &bubble));
+            let mut alleles = vec![];
+            if let (Some(path), Some(range)) = (&current_path, &range) {
+                alleles.push(path.sequence_range(conn, range.start, range.end));
+            }
+            for allele in walk_alleles(conn, &graph, &bubble) {
+                if !alleles.contains(&allele) {
+                    alleles.push(allele);
+                }
+            }
+            let sample_alleles = sample_paths
+                .iter()
+                .map(|(sample_name, path)| {
+                    let allele = path.as_ref().and_then(|path| {
+                        bubble_range(conn, path, &bubble)
+                            .map(|range| path.sequence_range(conn, range.start, range.end))
+                    });
+                    let index = allele.map(|allele| {
+                        alleles
+                            .iter()
+                            .position(|existing| *existing == allele)
+                            .unwrap_or_else(|| {
+                                alleles.push(allele);
+                                alleles.len() - 1
+                            })
+                    });
+                    (sample_name.clone(), index)
+                })
+                .collect();
+            VariantSite {
+                bubble,
+                range,
+                alleles,
+                sample_alleles,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block_group::PathChange;
+    use crate::models::path::PathBlock;
+    use crate::models::sequence::Sequence;
+    use crate::test_helpers::{get_connection, setup_block_group};
+
+    fn block(node_id: i64) -> GraphNode {
+        GraphNode {
+            block_id: node_id,
+            node_id,
+            sequence_start: 0,
+            sequence_end: 1,
+        }
+    }
+
+    fn edge() -> GraphEdge {
+        GraphEdge {
+            edge_id: 0,
+            source_strand: Strand::Forward,
+            target_strand: Strand::Forward,
+            chromosome_index: 0,
+            phased: 0,
+        }
+    }
+
+    #[test]
+    fn test_finds_nested_superbubbles() {
+        let mut graph = DiGraphMap::new();
+        for (source, target) in [
+            (3, 4),
+            (3, 5),
+            (3, 7),
+            (4, 6),
+            (4, 9),
+            (9, 6),
+            (5, 6),
+            (6, 7),
+            (7, 8),
+        ] {
+            graph.add_edge(block(source), block(target), edge());
+        }
+        let inner = Superbubble {
+            source: block(4),
+            sink: block(6),
+            interior: vec![block(9)],
+        };
+        assert_eq!(
+            superbubbles(&graph),
+            vec![
+                Superbubble {
+                    source: block(3),
+                    sink: block(7),
+                    interior: vec![block(4), block(5), block(6), block(9)],
+                },
+                inner.clone(),
+            ]
+        );
+
+        // a walk from the sink back to the source makes a cycle instead of a bubble
+        graph.add_edge(block(7), block(3), edge());
+        assert_eq!(superbubbles(&graph), vec![inner]);
+    }
+
+    #[test]
+    fn test_lists_variant_sites() {
+        let conn = &get_connection(None);
+        let (block_group_id, path) = setup_block_group(conn);
+        let insert_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("NNNN")
+            .save(conn);
+        let insert_node_id = Node::create(conn, insert_sequence.hash.as_str(), None);
+        let change = PathChange {
+            block_group_id,
+            path: path.clone(),
+            path_accession: None,
+            start: 7,
+            end: 15,
+            block: PathBlock {
+                id: 0,
+                node_id: insert_node_id,
+                block_sequence: "NNNN".to_string(),
+                sequence_start: 0,
+                sequence_end: 4,
+                path_start: 7,
+                path_end: 15,
+                strand: Strand::Forward,
+            },
+            chromosome_index: 1,
+            phased: 0,
+        };
+        BlockGroup::insert_change(conn, &change, &path.intervaltree(conn));
+
+        let bubbles = find_superbubbles(conn, block_group_id);
+        assert_eq!(bubbles.len(), 1);
+        assert_eq!(bubbles[0].source.sequence_end, 7);
+        assert_eq!(bubbles[0].sink.sequence_start, 5);
+        assert_eq!(bubbles[0].interior.len(), 3);
+
+        let sites = variant_sites(conn, &BlockGroup::get_by_id(conn, block_group_id));
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].range, Some(Range { start: 7, end: 15 }));
+        assert_eq!(sites[0].alleles, vec!["AAATTTTT", "NNNN"]);
+        assert_eq!(sites[0].sample_alleles, vec![(None, Some(0))]);
+    }
+}
@@ -0,0 +1,701 @@
+use crate::calculate_hash;
+use crate::diffs::three_way::{three_way_diff, ThreeWayStatus};
+use crate::models::file_types::FileTypes;
+use crate::models::{
+    accession::{Accession, AccessionEdge, AccessionEdgeData, AccessionPath},
+    block_group::BlockGroup,
+    block_group_edge::{BlockGroupEdge, BlockGroupEdgeData},
+    collection::Collection,
+    edge::{Edge, EdgeData},
+    node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID},
+    operations::{Operation, OperationInfo},
+    path::Path,
+    path_edge::PathEdge,
+    sample::Sample,
+    sequence::Sequence,
+    strand::Strand,
+    traits::*,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use itertools::Itertools;
+use rusqlite::types::Value as SQLValue;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("Sample {0} already has graphs in this collection")]
+    SampleExists(String),
+    #[error("Both samples change the same regions: {0}")]
+    Conflict(String),
+    #[error("Unable to build a merged path for graph {0}")]
+    NoPath(String),
+}
+
+// Merges two samples derived from a common ancestor into a new sample. The graphs of the new sample
+// have the edges of both samples, and their current paths make the changes of both samples to the
+// ancestor. Changes that the two samples made to the same region of the ancestor are only allowed
+// if they result in the same sequence, in which case the changes of the first sample are used.
+pub fn merge_samples(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    ancestor_sample_name: Option<&str>,
+    first_sample_name: &str,
+    second_sample_name: &str,
+    new_sample_name: &str,
+) -> Result<Operation, MergeError> {
+    if !Sample::get_block_groups(conn, collection_name, Some(new_sample_name)).is_empty() {
+        return Err(MergeError::SampleExists(new_sample_name.to_string()));
+    }
+
+    let regions = three_way_diff(
+        conn,
+        collection_name,
+        ancestor_sample_name,
+        Some(first_sample_name),
+        Some(second_sample_name),
+    );
+    let block_groups_by_name = |sample_name: Option<&str>| {
+        Sample::get_block_groups(conn, collection_name, sample_name)
+            .into_iter()
+            .map(|block_group| (block_group.name.clone(), block_group))
+            .collect::<HashMap<String, BlockGroup>>()
+    };
+    let ancestor_block_groups = block_groups_by_name(ancestor_sample_name);
+    let first_block_groups = block_groups_by_name(Some(first_sample_name));
+    let second_block_groups = block_groups_by_name(Some(second_sample_name));
+
+    // A graph that both samples added is treated as a conflict over all of it.
+    let conflicts = regions
+        .iter()
+        .filter(|region| region.status == ThreeWayStatus::Conflicting)
+        .map(|region| {
+            format!(
+                "{}:{}-{}",
+                region.graph, region.ancestor_range.start, region.ancestor_range.end
+            )
+        })
+        .chain(
+            first_block_groups
+                .keys()
+                .filter(|name| {
+                    second_block_groups.contains_key(*name)
+                        && !ancestor_block_groups.contains_key(*name)
+                })
+                .sorted()
+                .cloned(),
+        )
+        .collect::<Vec<String>>();
+    if !conflicts.is_empty() {
+        return Err(MergeError::Conflict(conflicts.join(", ")));
+    }
+
+    let graph_names = first_block_groups
+        .keys()
+        .chain(second_block_groups.keys())
+        .unique()
+        .sorted()
+        .cloned()
+        .collect::<Vec<String>>();
+    let mut paths_by_graph_name = HashMap::new();
+    for graph_name in graph_names.iter() {
+        let path = match (
+            ancestor_block_groups.get(graph_name),
+            first_block_groups.get(graph_name),
+            second_block_groups.get(graph_name),
+        ) {
+            (Some(ancestor_block_group), Some(first_block_group), Some(second_block_group)) => {
+                let first_path = BlockGroup::get_current_path(conn, first_block_group.id);
+                let merged_edge_ids = merge_path_edges(
+                    &PathEdge::edges_for_path(
+                        conn,
+                        BlockGroup::get_current_path(conn, ancestor_block_group.id).id,
+                    ),
+                    &PathEdge::edges_for_path(conn, first_path.id),
+                    &PathEdge::edges_for_path(
+                        conn,
+                        BlockGroup::get_current_path(conn, second_block_group.id).id,
+                    ),
+                )
+                .ok_or_else(|| MergeError::NoPath(graph_name.clone()))?;
+                (first_path.name, merged_edge_ids)
+            }
+            (_, Some(block_group), _) | (_, _, Some(block_group)) => {
+                let path = BlockGroup::get_current_path(conn, block_group.id);
+                let path_edge_ids = PathEdge::edges_for_path(conn, path.id)
+                    .iter()
+                    .map(|edge| edge.id)
+                    .collect::<Vec<i64>>();
+                (path.name, path_edge_ids)
+            }
+            (_, None, None) => unreachable!(),
+        };
+        paths_by_graph_name.insert(graph_name.clone(), path);
+    }
+
+    let mut session = start_operation(conn);
+    Sample::get_or_create(conn, new_sample_name);
+    for graph_name in graph_names.iter() {
+        let is_circular = first_block_groups
+            .get(graph_name)
+            .or(second_block_groups.get(graph_name))
+            .is_some_and(|block_group| block_group.is_circular);
+        let new_block_group = BlockGroup::create_with_topology(
+            conn,
+            collection_name,
+            Some(new_sample_name),
+            graph_name,
+            is_circular,
+        );
+
+        let mut edge_ids = HashSet::new();
+        let mut new_block_group_edges = vec![];
+        for block_group in first_block_groups
+            .get(graph_name)
+            .iter()
+            .chain(second_block_groups.get(graph_name).iter())
+        {
+            for augmented_edge in BlockGroupEdge::edges_for_block_group(conn, block_group.id) {
+                if edge_ids.insert(augmented_edge.edge.id) {
+                    new_block_group_edges.push(BlockGroupEdgeData {
+                        block_group_id: new_block_group.id,
+                        edge_id: augmented_edge.edge.id,
+                        chromosome_index: augmented_edge.chromosome_index,
+                        phased: augmented_edge.phased,
+                    });
+                }
+            }
+        }
+        BlockGroupEdge::bulk_create(conn, &new_block_group_edges);
+
+        let (path_name, path_edge_ids) = &paths_by_graph_name[graph_name];
+        Path::create(conn, path_name, new_block_group.id, path_edge_ids);
+    }
+
+    let summary_str = format!(
+        "{new_sample_name}: merged {first_sample_name} and {second_sample_name}, {count} changes.\n",
+        count = regions.len()
+    );
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "merge_samples".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+// Builds the edges of a path that makes the changes of both derived paths to the ancestor path. The
+// edges of the ancestor that either derived path dropped are left out and the edges either of them
+// added are kept, and the result is walked from the start of the path: every step takes the next
+// edge leaving the current node at or after the coordinate it was entered at. When the two paths
+// leave a node at the same coordinate, the edge of the first path is taken.
+fn merge_path_edges(
+    ancestor_edges: &[Edge],
+    first_edges: &[Edge],
+    second_edges: &[Edge],
+) -> Option<Vec<i64>> {
+    let first_edge_ids = first_edges
+        .iter()
+        .map(|edge| edge.id)
+        .collect::<HashSet<i64>>();
+    let second_edge_ids = second_edges
+        .iter()
+        .map(|edge| edge.id)
+        .collect::<HashSet<i64>>();
+    let dropped_edge_ids = ancestor_edges
+        .iter()
+        .filter(|edge| !first_edge_ids.contains(&edge.id) || !second_edge_ids.contains(&edge.id))
+        .map(|edge| edge.id)
+        .collect::<HashSet<i64>>();
+    let candidates = first_edges
+        .iter()
+        .chain(second_edges.iter())
+        .filter(|edge| !dropped_edge_ids.contains(&edge.id))
+        .unique_by(|edge| edge.id)
+        .collect::<Vec<&Edge>>();
+
+    let mut edge_ids = vec![];
+    let mut current = candidates
+        .iter()
+        .find(|edge| edge.source_node_id == PATH_START_NODE_ID)?;
+    // Each edge is used at most once, so a longer walk has to be going around a cycle.
+    while edge_ids.len() <= candidates.len() {
+        edge_ids.push(current.id);
+        if current.target_node_id == PATH_END_NODE_ID {
+            return Some(edge_ids);
+        }
+        current = candidates
+            .iter()
+            .filter(|edge| {
+                edge.source_node_id == current.target_node_id
+                    && edge.source_strand == current.target_strand
+                    && edge.source_coordinate >= current.target_coordinate
+            })
+            .min_by_key(|edge| edge.source_coordinate)?;
+    }
+    None
+}
+
+#[derive(Debug, Error)]
+pub enum SimplifyError {
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("Sample {0} already has graphs in this collection")]
+    SampleExists(String),
+    #[error("Sample {0} has no graphs in this collection")]
+    NoGraphs(String),
+}
+
+// Copies the graphs of a sample into a new sample with every non-branching chain of nodes merged
+// into a single node. Graphs only ever gain edges, so the chains are merged in a copy instead of
+// in place. The paths and accessions of the sample are carried over onto the merged nodes and
+// spell the same sequences as before.
+pub fn simplify_sample(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    new_sample_name: &str,
+) -> Result<Operation, SimplifyError> {
+    if !Sample::get_block_groups(conn, collection_name, Some(new_sample_name)).is_empty() {
+        return Err(SimplifyError::SampleExists(new_sample_name.to_string()));
+    }
+    let block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    if block_groups.is_empty() {
+        return Err(SimplifyError::NoGraphs(
+            Sample::display_name(sample_name).to_string(),
+        ));
+    }
+
+    let mut session = start_operation(conn);
+    Sample::get_or_create(conn, new_sample_name);
+    let mut merged_count = 0;
+    for block_group in block_groups.iter() {
+        let new_block_group = BlockGroup::create_with_topology(
+            conn,
+            collection_name,
+            Some(new_sample_name),
+            &block_group.name,
+            block_group.is_circular,
+        );
+        merged_count += simplify_block_group(conn, block_group, new_block_group.id);
+    }
+
+    let summary_str = format!(
+        "{new_sample_name}: simplified {sample}, merged {merged_count} nodes.\n",
+        sample = Sample::display_name(sample_name)
+    );
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "simplify".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+// Fills a new block group with the graph of another one, where each chain of nodes joined by links
+// is replaced by one node. A link is the only edge leaving its source node and the only edge
+// entering its target node, and all edges of both nodes are on the forward strand and stay on their
+// own side of the link. The merged node joins each node's sequence up to the link out of it with
+// the next node's sequence from the link into it. Returns the number of nodes merged away.
+fn simplify_block_group(
+    conn: &Connection,
+    block_group: &BlockGroup,
+    new_block_group_id: i64,
+) -> usize {
+    let augmented_edges = BlockGroupEdge::edges_for_block_group(conn, block_group.id);
+    let edges = augmented_edges
+        .iter()
+        .map(|augmented_edge| &augmented_edge.edge)
+        .unique_by(|edge| edge.id)
+        .collect::<Vec<&Edge>>();
+    let mut edges_out: HashMap<i64, Vec<&Edge>> = HashMap::new();
+    let mut edges_in: HashMap<i64, Vec<&Edge>> = HashMap::new();
+    for edge in edges.iter() {
+        edges_out
+            .entry(edge.source_node_id)
+            .or_default()
+            .push(*edge);
+        edges_out.entry(edge.target_node_id).or_default();
+        edges_in.entry(edge.target_node_id).or_default().push(*edge);
+        edges_in.entry(edge.source_node_id).or_default();
+    }
+    let is_forward = |node_id: i64| {
+        edges_out[&node_id]
+            .iter()
+            .chain(edges_in[&node_id].iter())
+            .all(|edge| {
+                edge.source_strand == Strand::Forward && edge.target_strand == Strand::Forward
+            })
+    };
+    let links = edges
+        .iter()
+        .filter(|edge| {
+            !Node::is_terminal(edge.source_node_id)
+                && !Node::is_terminal(edge.target_node_id)
+                && edge.source_node_id != edge.target_node_id
+                && edges_out[&edge.source_node_id].len() == 1
+                && edges_in[&edge.target_node_id].len() == 1
+                && is_forward(edge.source_node_id)
+                && is_forward(edge.target_node_id)
+                && edges_in[&edge.source_node_id]
+                    .iter()
+                    .all(|in_edge| in_edge.target_coordinate <= edge.source_coordinate)
+                && edges_out[&edge.target_node_id]
+                    .iter()
+                    .all(|out_edge| out_edge.source_coordinate >= edge.target_coordinate)
+        })
+        .map(|edge| (edge.source_node_id, *edge))
+        .collect::<HashMap<i64, &Edge>>();
+    let link_targets = links
+        .values()
+        .map(|edge| edge.target_node_id)
+        .collect::<HashSet<i64>>();
+
+    // Each node has at most one link in and one out, so following the links from a node no link
+    // enters visits a chain once. Cycles made only of links have no such node and are left alone.
+    let chains = links
+        .keys()
+        .filter(|node_id| !link_targets.contains(node_id))
+        .sorted()
+        .map(|head| {
+            let mut chain = vec![*head];
+            while let Some(link) = links.get(chain.last().unwrap()) {
+                chain.push(link.target_node_id);
+            }
+            chain
+        })
+        .collect::<Vec<Vec<i64>>>();
+    let sequences_by_node_id = Node::get_sequences_by_node_ids(
+        conn,
+        &chains.iter().flatten().copied().collect::<Vec<_>>(),
+    );
+
+    // Chained nodes by their hash rather than their id, so merging them gives the same node in
+    // every repository.
+    let node_keys = Node::get_nodes(conn, &chains.iter().flatten().copied().collect::<Vec<_>>())
+        .into_iter()
+        .map(|node| (node.id, node.hash.unwrap_or(node.sequence_hash)))
+        .collect::<HashMap<i64, String>>();
+
+    // Where each chained node ended up, as the merged node and the shift of its coordinates.
+    let mut merged_nodes = HashMap::new();
+    for chain in chains.iter() {
+        let mut sequence = String::new();
+        let mut shifts = vec![];
+        let mut start = 0;
+        for node_id in chain.iter() {
+            let node_sequence = &sequences_by_node_id[node_id];
+            let end = links
+                .get(node_id)
+                .map(|link| link.source_coordinate)
+                .unwrap_or(node_sequence.length);
+            shifts.push((*node_id, sequence.len() as i64 - start));
+            sequence.push_str(&node_sequence.get_sequence(start, end));
+            if let Some(link) = links.get(node_id) {
+                start = link.target_coordinate;
+            }
+        }
+        let seq = Sequence::new()
+            .sequence_type(&sequences_by_node_id[&chain[0]].sequence_type)
+            .sequence(&sequence)
+            .store(Collection::sequence_store(
+                conn,
+                &block_group.collection_name,
+            ))
+            .save(conn);
+        let node_id = Node::create(
+            conn,
+            &seq.hash,
+            calculate_hash(&format!(
+                "{collection}.{sample}.{graph}:{chain}->{sequence_hash}",
+                collection = block_group.collection_name,
+                sample = block_group.sample_name.clone().unwrap_or_default(),
+                graph = block_group.name,
+                chain = chain.iter().map(|node_id| &node_keys[node_id]).join(","),
+                sequence_hash = seq.hash
+            )),
+        );
+        for (chained_node_id, shift) in shifts {
+            merged_nodes.insert(chained_node_id, (node_id, shift));
+        }
+    }
+    let remap = |node_id: i64, coordinate: i64| {
+        merged_nodes
+            .get(&node_id)
+            .map(|(merged_node_id, shift)| (*merged_node_id, coordinate + shift))
+            .unwrap_or((node_id, coordinate))
+    };
+
+    let link_ids = links.values().map(|edge| edge.id).collect::<HashSet<i64>>();
+    let kept_edges = augmented_edges
+        .iter()
+        .filter(|augmented_edge| !link_ids.contains(&augmented_edge.edge.id))
+        .collect::<Vec<_>>();
+    let new_edge_ids = Edge::bulk_create(
+        conn,
+        &kept_edges
+            .iter()
+            .map(|augmented_edge| {
+                let edge = &augmented_edge.edge;
+                let (source_node_id, source_coordinate) =
+                    remap(edge.source_node_id, edge.source_coordinate);
+                let (target_node_id, target_coordinate) =
+                    remap(edge.target_node_id, edge.target_coordinate);
+                EdgeData {
+                    source_node_id,
+                    source_coordinate,
+                    source_strand: edge.source_strand,
+                    target_node_id,
+                    target_coordinate,
+                    target_strand: edge.target_strand,
+                }
+            })
+            .collect::<Vec<EdgeData>>(),
+    );
+    let mut edge_id_map = HashMap::new();
+    let mut new_block_group_edges = vec![];
+    for (augmented_edge, new_edge_id) in kept_edges.iter().zip(new_edge_ids) {
+        edge_id_map.insert(augmented_edge.edge.id, new_edge_id);
+        new_block_group_edges.push(BlockGroupEdgeData {
+            block_group_id: new_block_group_id,
+            edge_id: new_edge_id,
+            chromosome_index: augmented_edge.chromosome_index,
+            phased: augmented_edge.phased,
+        });
+    }
+    BlockGroupEdge::bulk_create(conn, &new_block_group_edges);
+
+    // A path through a link takes it right after entering the link's source node, so leaving the
+    // link out walks the merged node over the same bases.
+    let existing_paths = Path::query(
+        conn,
+        "SELECT * from paths where block_group_id = ?1 ORDER BY id ASC;",
+        rusqlite::params!(SQLValue::from(block_group.id)),
+    );
+    let mut path_map = HashMap::new();
+    for path in existing_paths.iter() {
+        let edge_ids = PathEdge::edges_for_path(conn, path.id)
+            .iter()
+            .filter_map(|edge| edge_id_map.get(&edge.id).copied())
+            .collect::<Vec<i64>>();
+        let new_path = Path::create(conn, &path.name, new_block_group_id, &edge_ids);
+        path_map.insert(path.id, new_path.id);
+    }
+    if let Some(current_path) = BlockGroup::try_get_current_path(conn, block_group.id) {
+        if Some(&current_path) != existing_paths.last() {
+            BlockGroup::set_current_path(conn, new_block_group_id, path_map[&current_path.id]);
+        }
+    }
+
+    let mut accession_map = HashMap::new();
+    for accession in Accession::query(
+        conn,
+        "select * from accessions where path_id IN rarray(?1) order by id;",
+        rusqlite::params!(Rc::new(
+            existing_paths
+                .iter()
+                .map(|path| SQLValue::from(path.id))
+                .collect::<Vec<SQLValue>>()
+        )),
+    ) {
+        let accession_edges = accession
+            .edges(conn)
+            .iter()
+            .map(|edge| {
+                let (source_node_id, source_coordinate) =
+                    remap(edge.source_node_id, edge.source_coordinate);
+                let (target_node_id, target_coordinate) =
+                    remap(edge.target_node_id, edge.target_coordinate);
+                AccessionEdgeData {
+                    source_node_id,
+                    source_coordinate,
+                    source_strand: edge.source_strand,
+                    target_node_id,
+                    target_coordinate,
+                    target_strand: edge.target_strand,
+                    chromosome_index: edge.chromosome_index,
+                }
+            })
+            .collect::<Vec<AccessionEdgeData>>();
+        let new_accession = Accession::create(
+            conn,
+            &accession.name,
+            path_map[&accession.path_id],
+            accession
+                .parent_accession_id
+                .map(|parent_id| *accession_map.get(&parent_id).unwrap_or(&parent_id)),
+        )
+        .expect("Unable to create accession in simplify.");
+        AccessionPath::create(
+            conn,
+            new_accession.id,
+            &AccessionEdge::bulk_create(conn, &accession_edges),
+        );
+        accession_map.insert(accession.id, new_accession.id);
+    }
+
+    links.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::{PathCache, PathChange};
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::path::PathBlock;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, setup_block_group, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_merges_samples() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let aa_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let tt_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/tttttttt.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        for (sample, start, end, fasta) in [
+            ("first", 15, 25, &aa_path),
+            ("second", 2, 4, &tt_path),
+            ("conflicting", 20, 22, &tt_path),
+        ] {
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                sample,
+                "m123",
+                start,
+                end,
+                fasta.to_str().unwrap(),
+            )
+            .unwrap();
+        }
+
+        merge_samples(conn, op_conn, "test", None, "first", "second", "merged").unwrap();
+        let block_groups = Sample::get_block_groups(conn, "test", Some("merged"));
+        assert_eq!(block_groups.len(), 1);
+        assert_eq!(
+            BlockGroup::get_current_path(conn, block_groups[0].id).sequence(conn),
+            "ATTTTTTTTTATCGATCGATCAAACACAGAGA"
+        );
+        // the merged graph still has the paths of both samples
+        let sequences = BlockGroup::get_all_sequences(conn, block_groups[0].id, false);
+        assert!(sequences.contains("ATCGATCGATCGATCAAACACAGAGA"));
+        assert!(sequences.contains("ATTTTTTTTTATCGATCGATCGATCGGGAACACACAGAGA"));
+
+        assert!(matches!(
+            merge_samples(conn, op_conn, "test", None, "first", "second", "merged"),
+            Err(MergeError::SampleExists(_))
+        ));
+        match merge_samples(conn, op_conn, "test", None, "first", "conflicting", "other") {
+            Err(MergeError::Conflict(regions)) => assert_eq!(regions, "m123:15-25"),
+            _ => panic!("Expected a conflict"),
+        }
+        assert!(Sample::get_block_groups(conn, "test", Some("other")).is_empty());
+    }
+
+    #[test]
+    fn test_simplifies_sample() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        // A chain of four nodes with an insertion from the first node into the second one, leaving
+        // the second, third and fourth node as a chain.
+        let (block_group_id, path) = setup_block_group(conn);
+        let insert_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("NNNN")
+            .save(conn);
+        let insert_node_id = Node::create(conn, insert_sequence.hash.as_str(), None);
+        let change = PathChange {
+            block_group_id,
+            path: path.clone(),
+            path_accession: None,
+            start: 7,
+            end: 15,
+            block: PathBlock {
+                id: 0,
+                node_id: insert_node_id,
+                block_sequence: "NNNN".to_string(),
+                sequence_start: 0,
+                sequence_end: 4,
+                path_start: 7,
+                path_end: 15,
+                strand: Strand::Forward,
+            },
+            chromosome_index: 1,
+            phased: 0,
+        };
+        BlockGroup::insert_change(conn, &change, &path.intervaltree(conn));
+        let mut path_cache = PathCache::new(conn);
+        PathCache::lookup(&mut path_cache, block_group_id, path.name.clone());
+        BlockGroup::add_accession(conn, &path, "gene", 12, 35, &mut path_cache);
+
+        simplify_sample(conn, op_conn, "test", None, "simple").unwrap();
+        let block_groups = Sample::get_block_groups(conn, "test", Some("simple"));
+        assert_eq!(block_groups.len(), 1);
+        let edges = BlockGroupEdge::edges_for_block_group(conn, block_groups[0].id);
+        assert_eq!(edges.len(), 5);
+        let new_path = BlockGroup::get_current_path(conn, block_groups[0].id);
+        assert_eq!(new_path.sequence(conn), path.sequence(conn));
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn, block_groups[0].id, false),
+            BlockGroup::get_all_sequences(conn, block_group_id, false)
+        );
+        let accessions = Accession::query(
+            conn,
+            "select * from accessions where path_id = ?1;",
+            rusqlite::params!(SQLValue::from(new_path.id)),
+        );
+        assert_eq!(accessions.len(), 1);
+        assert_eq!(accessions[0].sequence(conn), "TTTTTTTTCCCCCCCCCCGGGGG");
+
+        assert!(matches!(
+            simplify_sample(conn, op_conn, "test", None, "simple"),
+            Err(SimplifyError::SampleExists(_))
+        ));
+        assert!(matches!(
+            simplify_sample(conn, op_conn, "test", Some("missing"), "other"),
+            Err(SimplifyError::NoGraphs(_))
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/*
+   The order graphs are listed and exported in. By default graph names are sorted naturally, so
+   runs of digits compare as numbers and chr2 comes before chr10 and chr19. A custom order, such as
+   a karyotype order, lists graph names one per line; graphs it doesn't list follow in natural order.
+*/
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum GraphOrder {
+    #[default]
+    Natural,
+    Custom(Vec<String>),
+}
+
+impl GraphOrder {
+    // Reads an order from the lines of a file. Only the first column of each line is used, so
+    // files with more columns, like a .fai index, can be used as they are. Blank lines and lines
+    // starting with # are skipped.
+    pub fn from_names(names: &str) -> GraphOrder {
+        GraphOrder::Custom(
+            names
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<GraphOrder> {
+        Ok(GraphOrder::from_names(&fs::read_to_string(path)?))
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            GraphOrder::Natural => natural_cmp(a, b),
+            GraphOrder::Custom(names) => {
+                let position = |name: &str| names.iter().position(|listed| listed == name);
+                match (position(a), position(b)) {
+                    (Some(a_position), Some(b_position)) => a_position.cmp(&b_position),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => natural_cmp(a, b),
+                }
+            }
+        }
+    }
+
+    // Sorts items by the graph name of each, keeping items of the same graph in their order.
+    pub fn sort_by_name<T>(&self, items: &mut [T], name: impl Fn(&T) -> &str) {
+        items.sort_by(|a, b| self.compare(name(a), name(b)));
+    }
+}
+
+// Compares strings with runs of digits compared by their numeric value. Names that only differ by
+// leading zeros fall back to a plain comparison so the order stays total.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+    loop {
+        let ordering = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                let a_is_number = a_chunk.starts_with(|c: char| c.is_ascii_digit());
+                let b_is_number = b_chunk.starts_with(|c: char| c.is_ascii_digit());
+                if a_is_number && b_is_number {
+                    let a_number = a_chunk.trim_start_matches('0');
+                    let b_number = b_chunk.trim_start_matches('0');
+                    a_number
+                        .len()
+                        .cmp(&b_number.len())
+                        .then_with(|| a_number.cmp(b_number))
+                } else {
+                    a_chunk.cmp(b_chunk)
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// Splits a string into alternating runs of digits and other characters.
+fn chunks(value: &str) -> impl Iterator<Item = &str> {
+    let mut rest = value;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let is_digit = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (chunk, remainder) = rest.split_at(end);
+        rest = remainder;
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(order: &GraphOrder, names: &[&str]) -> Vec<String> {
+        let mut names = names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        order.sort_by_name(&mut names, |name| name);
+        names
+    }
+
+    #[test]
+    fn test_natural_order() {
+        assert_eq!(
+            sorted(
+                &GraphOrder::Natural,
+                &["chr20", "chrX", "chr2", "chr19", "chr1", "chr10", "chrM"]
+            ),
+            vec!["chr1", "chr2", "chr10", "chr19", "chr20", "chrM", "chrX"]
+        );
+        assert_eq!(natural_cmp("chr02", "chr2"), Ordering::Less);
+        assert_eq!(natural_cmp("chr2", "chr2a"), Ordering::Less);
+        assert_eq!(natural_cmp("m123", "m123"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_custom_order() {
+        let order = GraphOrder::from_names("# karyotype\nchrX\t156040895\n\nchr2\nchr1\n");
+        assert_eq!(
+            order,
+            GraphOrder::Custom(vec![
+                "chrX".to_string(),
+                "chr2".to_string(),
+                "chr1".to_string()
+            ])
+        );
+        // graphs that aren't listed follow in natural order
+        assert_eq!(
+            sorted(&order, &["chr10", "chr1", "chr3", "chrX", "chr2"]),
+            vec!["chrX", "chr2", "chr1", "chr3", "chr10"]
+        );
+    }
+}
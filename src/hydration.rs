@@ -0,0 +1,221 @@
+use crate::models::node::Node;
+use crate::models::sequence::{
+    cached_sequence, Sequence, OBJECT_STORE_FILE_PATH, PACKED_FILE_PATH,
+};
+use crate::object_store::read_object;
+use noodles::fasta;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum HydrationError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Fasta file {0} does not exist")]
+    MissingFile(String),
+    #[error("{name} not found in fasta file {file_path}")]
+    MissingSequence { name: String, file_path: String },
+    #[error("{0} already exists")]
+    FileExists(String),
+}
+
+fn stored_sequences(conn: &Connection, query: &str, placeholders: Vec<Value>) -> Vec<Sequence> {
+    Sequence::sequences(
+        conn,
+        &format!("select * from sequences where {query} order by hash;"),
+        placeholders,
+    )
+}
+
+/*
+   Shallow imports keep the name of each record and the fasta file it is in instead of its bases.
+   Hydrating reads the bases of these sequences, and of those in the object store or packed, into the
+   sequence column, after which the fasta file is no longer needed. Only sequences of the given fasta file are hydrated if one is given. Sequences keep
+   their hashes, as nodes refer to them.
+*/
+pub fn hydrate_sequences(
+    conn: &Connection,
+    file_path: Option<&str>,
+) -> Result<Vec<String>, HydrationError> {
+    let sequences = match file_path {
+        // shallow imports keep the path they were given, while dehydrating keeps an absolute one
+        Some(file_path) => {
+            let canonical_path = fs::canonicalize(file_path)
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|_| file_path.to_string());
+            stored_sequences(
+                conn,
+                "file_path in (?1, ?2)",
+                vec![
+                    Value::from(file_path.to_string()),
+                    Value::from(canonical_path),
+                ],
+            )
+        }
+        None => stored_sequences(conn, "file_path != ''", vec![]),
+    };
+    let mut hashes = vec![];
+    for sequence in sequences.iter() {
+        if sequence.file_path == OBJECT_STORE_FILE_PATH || sequence.file_path == PACKED_FILE_PATH {
+            let bases = if sequence.file_path == OBJECT_STORE_FILE_PATH {
+                read_object(&sequence.hash)?
+            } else {
+                sequence.get_sequence(None, None)
+            };
+            conn.execute(
+                "update sequences set sequence = ?2, name = '', file_path = '', packed_bases = null, packed_exceptions = null where hash = ?1;",
+                params![sequence.hash, bases],
+            )?;
+            hashes.push(sequence.hash.clone());
+            continue;
+        }
+        if !Path::new(&sequence.file_path).exists() {
+            return Err(HydrationError::MissingFile(sequence.file_path.clone()));
+        }
+        let bases = cached_sequence(
+            &sequence.file_path,
+            &sequence.name,
+            0,
+            sequence.length as usize,
+        )
+        .ok_or_else(|| HydrationError::MissingSequence {
+            name: sequence.name.clone(),
+            file_path: sequence.file_path.clone(),
+        })?;
+        conn.execute(
+            "update sequences set sequence = ?2, file_path = '' where hash = ?1;",
+            params![sequence.hash, bases],
+        )?;
+        hashes.push(sequence.hash.clone());
+    }
+    Ok(hashes)
+}
+
+/*
+   The reverse of hydrating: writes the bases of every sequence stored in the database to a new fasta
+   file, one record named by hash per sequence, and leaves only a reference to the record in the
+   database. An index is written alongside the fasta file so parts of a sequence can be read without
+   reading all of it. Sequences are referred to by the absolute path of the fasta file, so the
+   database can be used from other directories.
+*/
+pub fn dehydrate_sequences(
+    conn: &Connection,
+    fasta_path: &Path,
+) -> Result<Vec<String>, HydrationError> {
+    if fasta_path.exists() {
+        return Err(HydrationError::FileExists(fasta_path.display().to_string()));
+    }
+    // the sequences of the start and end nodes are made with the database and stay in it
+    let sequences = stored_sequences(
+        conn,
+        "file_path = '' and length > 0 and hash not in (?1, ?2)",
+        vec![
+            Value::from(Node::get_start_node().sequence_hash),
+            Value::from(Node::get_end_node().sequence_hash),
+        ],
+    );
+    if sequences.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut writer = fasta::io::Writer::new(File::create(fasta_path)?);
+    for sequence in sequences.iter() {
+        let definition = fasta::record::Definition::new(sequence.hash.clone(), None);
+        let bases = fasta::record::Sequence::from(sequence.get_sequence(None, None).into_bytes());
+        writer.write_record(&fasta::Record::new(definition, bases))?;
+    }
+    drop(writer);
+    let index = fasta::index(fasta_path)?;
+    let mut index_path = fasta_path.as_os_str().to_owned();
+    index_path.push(".fai");
+    fasta::fai::io::Writer::new(File::create(index_path)?).write_index(&index)?;
+
+    let file_path = fs::canonicalize(fasta_path)?.to_string_lossy().to_string();
+    let mut hashes = vec![];
+    for sequence in sequences.iter() {
+        conn.execute(
+            "update sequences set sequence = '', name = ?1, file_path = ?2 where hash = ?1;",
+            params![sequence.hash, file_path],
+        )?;
+        hashes.push(sequence.hash.clone());
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_dehydrate_and_hydrate() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            true,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let bg = get_sample_bg(conn, "test", None);
+        let expected = "ATCGATCGATCGATCGATCGGGAACACACAGAGA";
+        assert_eq!(
+            BlockGroup::get_current_path(conn, bg.id).sequence(conn),
+            expected
+        );
+
+        let hydrated = hydrate_sequences(conn, None).unwrap();
+        assert_eq!(hydrated.len(), 1);
+        let sequence = Sequence::sequence_from_hash(conn, &hydrated[0]).unwrap();
+        assert!(!sequence.external_sequence);
+        assert_eq!(sequence.get_sequence(None, None), expected);
+        assert!(hydrate_sequences(conn, None).unwrap().is_empty());
+
+        let temp_dir = tempdir().unwrap();
+        let dehydrated_path = temp_dir.path().join("sequences.fa");
+        let dehydrated = dehydrate_sequences(conn, &dehydrated_path).unwrap();
+        assert_eq!(dehydrated, hydrated);
+        assert!(temp_dir.path().join("sequences.fa.fai").exists());
+        let sequence = Sequence::sequence_from_hash(conn, &dehydrated[0]).unwrap();
+        assert!(sequence.external_sequence);
+        assert_eq!(sequence.name, sequence.hash);
+        assert_eq!(Sequence::sequence_slice(conn, &sequence.hash, 2, 6), "CGAT");
+        assert_eq!(
+            BlockGroup::get_current_path(conn, bg.id).sequence(conn),
+            expected
+        );
+        assert!(matches!(
+            dehydrate_sequences(conn, &dehydrated_path),
+            Err(HydrationError::FileExists(_))
+        ));
+
+        let hydrated = hydrate_sequences(conn, dehydrated_path.to_str()).unwrap();
+        assert_eq!(hydrated, dehydrated);
+        fs::remove_file(&dehydrated_path).unwrap();
+        assert_eq!(
+            BlockGroup::get_current_path(conn, bg.id).sequence(conn),
+            expected
+        );
+    }
+}
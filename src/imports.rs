@@ -1,3 +1,6 @@
 pub mod fasta;
 pub mod genbank;
 pub mod gfa;
+pub mod library;
+pub mod maf;
+pub mod paf;
@@ -10,11 +10,16 @@ use crate::models::{
     node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID},
     operations::Operation,
     path::Path,
-    sequence::Sequence,
+    sequence::{Molecule, Sequence},
+    sequence_digest::SequenceDigest,
     strand::Strand,
 };
 use crate::operation_management::{end_operation, start_operation, OperationError};
-use crate::progress_bar::{add_saving_operation_bar, get_handler, get_progress_bar};
+use crate::progress_bar::{
+    add_saving_operation_bar, get_handler, get_progress_bar, report_progress, NoProgress,
+    ProgressReporter,
+};
+use crate::tuning::BulkSettings;
 use noodles::fasta;
 use rusqlite;
 use rusqlite::Connection;
@@ -26,6 +31,13 @@ use thiserror::Error;
 pub enum FastaError {
     #[error("Operation Error: {0}")]
     OperationError(#[from] OperationError),
+    #[error("{name} is not a {sequence_type} sequence: it has {character} at position {position}")]
+    InvalidSequence {
+        name: String,
+        sequence_type: String,
+        character: char,
+        position: usize,
+    },
 }
 
 pub fn import_fasta<'a>(
@@ -36,7 +48,46 @@ pub fn import_fasta<'a>(
     conn: &Connection,
     operation_conn: &Connection,
 ) -> Result<Operation, FastaError> {
+    import_fasta_with_type(fasta, name, sample, shallow, "DNA", conn, operation_conn)
+}
+
+// Imports a fasta of DNA, RNA or protein sequences, checking each record against the alphabet of
+// its sequence type.
+pub fn import_fasta_with_type<'a>(
+    fasta: &String,
+    name: &str,
+    sample: impl Into<Option<&'a str>>,
+    shallow: bool,
+    sequence_type: &str,
+    conn: &Connection,
+    operation_conn: &Connection,
+) -> Result<Operation, FastaError> {
+    import_fasta_with_progress(
+        fasta,
+        name,
+        sample,
+        shallow,
+        sequence_type,
+        conn,
+        operation_conn,
+        &NoProgress,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn import_fasta_with_progress<'a>(
+    fasta: &String,
+    name: &str,
+    sample: impl Into<Option<&'a str>>,
+    shallow: bool,
+    sequence_type: &str,
+    conn: &Connection,
+    operation_conn: &Connection,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, FastaError> {
+    let molecule = Molecule::from_sequence_type(sequence_type);
     let progress_bar = get_handler();
+    let _bulk = BulkSettings::apply(conn);
     let mut session = start_operation(conn);
 
     let mut reader = fasta::io::reader::Builder.build_from_path(fasta).unwrap();
@@ -48,6 +99,7 @@ pub fn import_fasta<'a>(
             name: name.to_string(),
         }
     };
+    let store = Collection::sequence_store(conn, &collection.name);
     let sample = sample.into();
     if let Some(sample_name) = sample {
         Sample::get_or_create(conn, sample_name);
@@ -64,19 +116,29 @@ pub fn import_fasta<'a>(
             .to_string();
         let name = String::from_utf8(record.name().to_vec()).unwrap();
         let sequence_length = record.sequence().len() as i64;
+        if let Some((position, character)) = molecule.find_invalid(&sequence) {
+            return Err(FastaError::InvalidSequence {
+                name,
+                sequence_type: sequence_type.to_string(),
+                character,
+                position,
+            });
+        }
         let seq = if shallow {
             Sequence::new()
-                .sequence_type("DNA")
+                .sequence_type(sequence_type)
                 .name(&name)
                 .file_path(fasta)
                 .length(sequence_length)
                 .save(conn)
         } else {
             Sequence::new()
-                .sequence_type("DNA")
+                .sequence_type(sequence_type)
                 .sequence(&sequence)
+                .store(store)
                 .save(conn)
         };
+        SequenceDigest::create(conn, &seq.hash, &sequence);
         let node_id = Node::create(
             conn,
             &seq.hash,
@@ -125,6 +187,7 @@ pub fn import_fasta<'a>(
         let path = Path::create(conn, &name, block_group.id, &[edge_into.id, edge_out_of.id]);
         summary.entry(path.name).or_insert(sequence_length);
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
     let mut summary_str = "".to_string();
@@ -156,7 +219,10 @@ mod tests {
     use super::*;
     use crate::models::metadata;
     use crate::models::operations::setup_db;
+    use crate::models::sequence::{NewSequence, SequenceStore, OBJECT_STORE_FILE_PATH};
     use crate::models::traits::*;
+    use crate::object_store::has_object;
+    use crate::progress_bar::CancellationToken;
     use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
     use std::collections::HashSet;
     use std::path::PathBuf;
@@ -258,6 +324,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_add_fasta_to_object_store() {
+        setup_gen_dir();
+        let mut fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_path.push("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        Collection::set_sequence_store(conn, "test", SequenceStore::Objects).unwrap();
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let path = Path::get(conn, 1);
+        assert_eq!(
+            path.sequence(conn),
+            "ATCGATCGATCGATCGATCGGGAACACACAGAGA".to_string()
+        );
+
+        // the bases are only in the object store, under the hash they would have in the database
+        let hash = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("ATCGATCGATCGATCGATCGGGAACACACAGAGA")
+            .build()
+            .hash;
+        let sequence = Sequence::sequence_from_hash(conn, &hash).unwrap();
+        assert_eq!(sequence.file_path, OBJECT_STORE_FILE_PATH);
+        assert!(has_object(&hash));
+        assert_eq!(Sequence::sequence_slice(conn, &hash, 4, 8), "ATCG");
+        assert_eq!(
+            sequence.inlined().get_sequence(None, None),
+            "ATCGATCGATCGATCGATCGGGAACACACAGAGA"
+        );
+        assert_eq!(NewSequence::from(&sequence.inlined()).hash(), hash);
+    }
+
+    #[test]
+    fn test_add_protein_fasta() {
+        setup_gen_dir();
+        let mut fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_path.push("fixtures/protein.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        let fasta_path = fasta_path.to_str().unwrap().to_string();
+        assert_eq!(
+            import_fasta(&fasta_path, "test", None, false, conn, op_conn).unwrap_err(),
+            FastaError::InvalidSequence {
+                name: "insulin_b".to_string(),
+                sequence_type: "DNA".to_string(),
+                character: 'F',
+                position: 0,
+            }
+        );
+
+        import_fasta_with_type(&fasta_path, "test", None, false, "protein", conn, op_conn).unwrap();
+        let block_groups = Sample::get_block_groups(conn, "test", None);
+        assert_eq!(block_groups.len(), 2);
+        for block_group in block_groups {
+            let path = BlockGroup::get_current_path(conn, block_group.id);
+            let block = path
+                .blocks(conn)
+                .into_iter()
+                .find(|block| !Node::is_terminal(block.node_id))
+                .unwrap();
+            let sequence = &Node::get_sequences_by_node_ids(conn, &[block.node_id])[&block.node_id];
+            assert_eq!(sequence.sequence_type, "protein");
+            assert_eq!(sequence.molecule(), Molecule::Protein);
+        }
+        let path = BlockGroup::get_current_path(
+            conn,
+            Sample::get_block_groups(conn, "test", None)
+                .iter()
+                .find(|block_group| block_group.name == "insulin_a")
+                .unwrap()
+                .id,
+        );
+        assert_eq!(path.sequence(conn), "GIVEQCCTSICSLYQLENYCN*");
+    }
+
     #[test]
     fn test_deduplicates_nodes() {
         setup_gen_dir();
@@ -295,4 +450,64 @@ mod tests {
             Err(FastaError::OperationError(OperationError::NoChanges))
         );
     }
+
+    #[derive(Default)]
+    struct RecordingReporter {
+        reports: std::cell::RefCell<Vec<(String, u64, Option<u64>)>>,
+    }
+
+    impl ProgressReporter for RecordingReporter {
+        fn on_progress(&self, step: &str, completed: u64, total: Option<u64>) {
+            self.reports
+                .borrow_mut()
+                .push((step.to_string(), completed, total));
+        }
+    }
+
+    #[test]
+    fn test_add_fasta_with_progress() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(
+            import_fasta_with_progress(
+                &fasta_path.to_str().unwrap().to_string(),
+                "test",
+                None,
+                false,
+                "DNA",
+                conn,
+                op_conn,
+                &token,
+            ),
+            Err(FastaError::OperationError(OperationError::Cancelled))
+        );
+        // nothing is recorded for a cancelled import
+        assert_eq!(
+            Operation::query(op_conn, "select * from operation;", rusqlite::params!()).len(),
+            0
+        );
+
+        let reporter = RecordingReporter::default();
+        import_fasta_with_progress(
+            &fasta_path.to_str().unwrap().to_string(),
+            "other",
+            None,
+            false,
+            "DNA",
+            conn,
+            op_conn,
+            &reporter,
+        )
+        .unwrap();
+        assert_eq!(
+            reporter.reports.into_inner(),
+            vec![("Entries Processed.".to_string(), 1, None)]
+        );
+    }
 }
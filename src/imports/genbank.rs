@@ -8,12 +8,18 @@ use crate::models::node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID};
 use crate::models::operations::{Operation, OperationInfo};
 use crate::models::path::{Path, PathBlock};
 use crate::models::sample::Sample;
-use crate::models::sequence::Sequence;
+use crate::models::sequence::{Molecule, Sequence};
+use crate::models::sequence_digest::SequenceDigest;
 use crate::models::strand::Strand;
 use crate::operation_management::{end_operation, start_operation};
-use crate::progress_bar::{add_saving_operation_bar, get_handler, get_progress_bar};
+use crate::progress_bar::{
+    add_saving_operation_bar, get_handler, get_progress_bar, report_progress, NoProgress,
+    ProgressReporter,
+};
+use crate::tuning::BulkSettings;
 use gb_io::reader;
 use rusqlite::Connection;
+use std::collections::HashSet;
 use std::io::Read;
 use std::str;
 
@@ -25,10 +31,34 @@ pub fn import_genbank<'a, R>(
     sample: impl Into<Option<&'a str>>,
     operation_info: OperationInfo,
 ) -> Result<Operation, GenBankError>
+where
+    R: Read,
+{
+    import_genbank_with_progress(
+        conn,
+        op_conn,
+        data,
+        collection,
+        sample,
+        operation_info,
+        &NoProgress,
+    )
+}
+
+pub fn import_genbank_with_progress<'a, R>(
+    conn: &Connection,
+    op_conn: &Connection,
+    data: R,
+    collection: impl Into<Option<&'a str>>,
+    sample: impl Into<Option<&'a str>>,
+    operation_info: OperationInfo,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, GenBankError>
 where
     R: Read,
 {
     let progress_bar = get_handler();
+    let _bulk = BulkSettings::apply(conn);
     let mut session = start_operation(conn);
     let reader = reader::SeqReader::new(data);
     let collection = Collection::create(conn, collection.into().unwrap_or_default());
@@ -51,9 +81,23 @@ where
                     seq_model = seq_model.name(&locus.name);
                 }
                 if let Some(ref mol_type) = locus.molecule_type {
+                    // GenBank writes RNA with the DNA alphabet, so only proteins have their own
+                    let molecule = match Molecule::from_sequence_type(mol_type) {
+                        Molecule::Protein => Molecule::Protein,
+                        _ => Molecule::Dna,
+                    };
+                    if let Some((position, character)) = molecule.find_invalid(&original_seq) {
+                        return Err(GenBankError::InvalidSequence {
+                            name: locus.name,
+                            sequence_type: mol_type.clone(),
+                            character,
+                            position,
+                        });
+                    }
                     seq_model = seq_model.sequence_type(mol_type);
                 }
                 let sequence = seq_model.save(conn);
+                SequenceDigest::create(conn, &sequence.hash, &original_seq);
                 let wt_node_id = Node::create(
                     conn,
                     &sequence.hash,
@@ -65,7 +109,13 @@ where
                     )),
                 );
 
-                let block_group = BlockGroup::create(conn, &collection.name, sample, &locus.name);
+                let block_group = BlockGroup::create_with_topology(
+                    conn,
+                    &collection.name,
+                    sample,
+                    &locus.name,
+                    locus.is_circular,
+                );
                 let edge_into = Edge::create(
                     conn,
                     PATH_START_NODE_ID,
@@ -173,10 +223,32 @@ where
                     let tree = path.intervaltree(conn);
                     BlockGroup::insert_change(conn, &change, &tree);
                 }
+
+                // features joining several ranges, such as spliced CDSs, are kept as accessions of
+                // the edited sequence so they can be followed through later changes
+                if !locus.joined_features.is_empty() {
+                    let current_path = BlockGroup::get_current_path(conn, block_group.id);
+                    let mut names = HashSet::new();
+                    for feature in locus.joined_features.iter() {
+                        let mut name = feature.name.clone();
+                        let mut copies = 1;
+                        while !names.insert(name.clone()) {
+                            copies += 1;
+                            name = format!("{}_{copies}", feature.name);
+                        }
+                        BlockGroup::add_joined_accession(
+                            conn,
+                            &current_path,
+                            &name,
+                            &feature.ranges,
+                        )?;
+                    }
+                }
             }
             Err(e) => return Err(GenBankError::ParseError(format!("Failed to parse {}", e))),
         }
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
     let filename = operation_info.file_path.clone();
@@ -197,12 +269,12 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::accession::Accession;
     use crate::models::file_types::FileTypes;
     use crate::models::metadata;
     use crate::models::operations::setup_db;
     use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
     use noodles::fasta;
-    use std::collections::HashSet;
     use std::fs::File;
     use std::io::BufReader;
     use std::path::PathBuf;
@@ -301,6 +373,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_creates_accessions_for_joined_features() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/genbank/joined_features.gb");
+        let file = File::open(&path).unwrap();
+        import_genbank(
+            conn,
+            op_conn,
+            BufReader::new(file),
+            "test",
+            None,
+            OperationInfo {
+                file_path: "".to_string(),
+                file_type: FileTypes::GenBank,
+                description: "test".to_string(),
+            },
+        )
+        .unwrap();
+
+        // features of a single range, like the genes, aren't accessions, and the reverse strand
+        // features are read from their last exon
+        let accessions = Accession::query_for_collection(conn, "test")
+            .iter()
+            .map(|accession| {
+                (
+                    accession.name.clone(),
+                    accession.sequence(conn).to_lowercase(),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(
+            accessions,
+            vec![
+                ("geneA_CDS".to_string(), "atgaaagggtaa".to_string()),
+                ("geneB_mRNA".to_string(), "atgaaagggtaa".to_string()),
+                ("geneB_CDS".to_string(), "atgaaagggtaa".to_string()),
+                ("geneB_CDS_2".to_string(), "atgaaagggtaa".to_string()),
+            ]
+        );
+    }
+
     #[cfg(test)]
     mod geneious_genbanks {
         use super::*;
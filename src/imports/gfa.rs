@@ -3,9 +3,11 @@ use rusqlite::Connection;
 use std::collections::{HashMap, HashSet};
 use std::path::Path as FilePath;
 
-use crate::gfa_reader::Gfa;
+use crate::calculate_hash;
+use crate::gfa_reader::{Gfa, Tags};
 use crate::models::sample::Sample;
 use crate::models::{
+    attribute::AttributeValue,
     block_group::BlockGroup,
     block_group_edge::{BlockGroupEdge, BlockGroupEdgeData},
     collection::Collection,
@@ -15,7 +17,12 @@ use crate::models::{
     sequence::Sequence,
     strand::Strand,
 };
-use crate::progress_bar::{get_handler, get_progress_bar, get_time_elapsed_bar};
+use crate::operation_management::OperationError;
+use crate::progress_bar::{
+    get_handler, get_progress_bar, get_time_elapsed_bar, report_progress, NoProgress,
+    ProgressReporter,
+};
+use crate::tuning::BulkSettings;
 
 fn bool_to_strand(direction: bool) -> Strand {
     if direction {
@@ -25,13 +32,36 @@ fn bool_to_strand(direction: bool) -> Strand {
     }
 }
 
+// Records the optional fields of a segment or link as attributes of what it was imported as.
+fn set_tag_attributes(tags: &Tags, mut set_attribute: impl FnMut(&str, AttributeValue)) {
+    for (key, value) in tags
+        .0
+        .iter()
+        .filter_map(|tag| AttributeValue::from_gfa_tag(tag))
+    {
+        set_attribute(&key, value);
+    }
+}
+
 pub fn import_gfa<'a>(
     gfa_path: &FilePath,
     collection_name: &str,
     sample_name: impl Into<Option<&'a str>>,
     conn: &Connection,
 ) {
+    import_gfa_with_progress(gfa_path, collection_name, sample_name, conn, &NoProgress)
+        .expect("imports without a reporter aren't cancelled");
+}
+
+pub fn import_gfa_with_progress<'a>(
+    gfa_path: &FilePath,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    conn: &Connection,
+    progress: &dyn ProgressReporter,
+) -> Result<(), OperationError> {
     let progress_bar = get_handler();
+    let _bulk = BulkSettings::apply(conn);
     Collection::create(conn, collection_name);
     let sample_name = sample_name.into();
     if let Some(sample_name) = sample_name {
@@ -40,7 +70,7 @@ pub fn import_gfa<'a>(
     let block_group = BlockGroup::create(conn, collection_name, sample_name, "");
     let bar = progress_bar.add(get_time_elapsed_bar());
     bar.set_message("Parsing GFA");
-    let gfa: Gfa<String, (), ()> = Gfa::parse_gfa_file(gfa_path.to_str().unwrap());
+    let gfa: Gfa<String, Tags, ()> = Gfa::parse_gfa_file(gfa_path.to_str().unwrap());
     let mut sequences_by_segment_id: HashMap<&String, Sequence> = HashMap::new();
     let mut node_ids_by_segment_id: HashMap<&String, i64> = HashMap::new();
     bar.finish();
@@ -54,9 +84,22 @@ pub fn import_gfa<'a>(
             .sequence(input_sequence)
             .save(conn);
         sequences_by_segment_id.insert(&segment.id, sequence.clone());
-        let node_id = Node::create(conn, &sequence.hash, None);
+        let node_id = Node::create(
+            conn,
+            &sequence.hash,
+            calculate_hash(&format!(
+                "{collection_name}.{sample}:{segment}:{hash}",
+                sample = sample_name.unwrap_or_default(),
+                segment = segment.id,
+                hash = sequence.hash
+            )),
+        );
+        set_tag_attributes(&segment.opt, |key, value| {
+            Node::set_attribute(conn, node_id, key, value)
+        });
         node_ids_by_segment_id.insert(&segment.id, node_id);
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
 
@@ -75,6 +118,7 @@ pub fn import_gfa<'a>(
             bool_to_strand(link.to_dir),
         ));
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
 
@@ -107,6 +151,7 @@ pub fn import_gfa<'a>(
             Strand::Forward,
         ));
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
 
@@ -139,6 +184,7 @@ pub fn import_gfa<'a>(
             Strand::Forward,
         ));
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
 
@@ -170,6 +216,21 @@ pub fn import_gfa<'a>(
         edge_ids_by_data.insert(key, edge.id);
     }
 
+    // the optional fields of links are attributes of the edges they became
+    for link in &gfa.links {
+        let key = edge_data_from_fields(
+            *node_ids_by_segment_id.get(&link.from).unwrap(),
+            sequences_by_segment_id.get(&link.from).unwrap().length,
+            bool_to_strand(link.from_dir),
+            *node_ids_by_segment_id.get(&link.to).unwrap(),
+            bool_to_strand(link.to_dir),
+        );
+        let edge_id = edge_ids_by_data[&key];
+        set_tag_attributes(&link.opt, |key, value| {
+            Edge::set_attribute(conn, edge_id, key, value)
+        });
+    }
+
     for input_path in &gfa.paths {
         let path_name = &input_path.name;
         let mut source_node_id = PATH_START_NODE_ID;
@@ -240,6 +301,7 @@ pub fn import_gfa<'a>(
         Path::create(conn, path_name, block_group.id, &path_edge_ids);
     }
     bar.finish();
+    Ok(())
 }
 
 fn edge_data_from_fields(
@@ -265,6 +327,7 @@ mod tests {
     use crate::models::traits::*;
     use crate::test_helpers::{get_connection, setup_gen_dir};
     use rusqlite::types::Value as SQLValue;
+    use std::collections::BTreeMap;
     use std::path::PathBuf;
 
     #[test]
@@ -292,6 +355,17 @@ mod tests {
 
         let node_count = Node::query(conn, "select * from nodes", rusqlite::params!()).len() as i64;
         assert_eq!(node_count, 6);
+
+        // segment tags become attributes of their nodes
+        let node_id = path.blocks(conn)[2].node_id;
+        assert_eq!(
+            Node::attributes(conn, node_id),
+            BTreeMap::from([
+                ("SN".to_string(), AttributeValue::from("m123")),
+                ("SO".to_string(), AttributeValue::Integer(3)),
+                ("SR".to_string(), AttributeValue::Integer(0)),
+            ])
+        );
     }
 
     #[test]
@@ -0,0 +1,486 @@
+use crate::calculate_hash;
+use crate::models::file_types::FileTypes;
+use crate::models::operations::OperationInfo;
+use crate::models::sample::Sample;
+use crate::models::{
+    block_group::BlockGroup,
+    block_group_edge::{BlockGroupEdge, BlockGroupEdgeData},
+    collection::Collection,
+    edge::{Edge, EdgeData},
+    node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID},
+    operations::Operation,
+    path::Path,
+    sequence::Sequence,
+    strand::Strand,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use crate::progress_bar::{report_step, NoProgress, ProgressReporter};
+use crate::tuning::BulkSettings;
+use crate::updates::library::{accession_parts, read_library_slots, read_parts};
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LibraryError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("No part named {0} found")]
+    UnknownPart(String),
+    #[error("A graph named {0} already exists")]
+    GraphExists(String),
+}
+
+// Builds a new graph from a library design without an existing backbone. Slots with a single part
+// are fixed segments (such as a vector backbone) and slots with several parts are variable, so the
+// graph has one path for every combination. The current path uses the first option of each slot.
+pub fn import_library<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    path_name: &str,
+    parts_file_path: &str,
+    library_file_path: &str,
+) -> Result<Operation, LibraryError> {
+    import_library_with_progress(
+        conn,
+        operation_conn,
+        collection_name,
+        sample_name,
+        path_name,
+        parts_file_path,
+        library_file_path,
+        &NoProgress,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn import_library_with_progress<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    path_name: &str,
+    parts_file_path: &str,
+    library_file_path: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, LibraryError> {
+    let parts = read_parts(parts_file_path)?;
+    import_library_parts(
+        conn,
+        operation_conn,
+        collection_name,
+        sample_name.into(),
+        path_name,
+        parts,
+        library_file_path,
+        progress,
+    )
+}
+
+// Like import_library, but the parts named in the library file are the accessions already
+// registered in the collection rather than records of a parts fasta.
+pub fn import_library_from_accessions<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    path_name: &str,
+    library_file_path: &str,
+) -> Result<Operation, LibraryError> {
+    import_library_from_accessions_with_progress(
+        conn,
+        operation_conn,
+        collection_name,
+        sample_name,
+        path_name,
+        library_file_path,
+        &NoProgress,
+    )
+}
+
+pub fn import_library_from_accessions_with_progress<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    path_name: &str,
+    library_file_path: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, LibraryError> {
+    let parts = accession_parts(conn, collection_name, library_file_path)?;
+    import_library_parts(
+        conn,
+        operation_conn,
+        collection_name,
+        sample_name.into(),
+        path_name,
+        parts,
+        library_file_path,
+        progress,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn import_library_parts(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    path_name: &str,
+    parts: Vec<(String, String)>,
+    library_file_path: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, LibraryError> {
+    let _bulk = BulkSettings::apply(conn);
+    let mut session = start_operation(conn);
+
+    if !Collection::exists(conn, collection_name) {
+        Collection::create(conn, collection_name);
+    }
+    if let Some(sample_name) = sample_name {
+        Sample::get_or_create(conn, sample_name);
+    }
+    if Sample::get_block_groups(conn, collection_name, sample_name)
+        .iter()
+        .any(|block_group| block_group.name == path_name)
+    {
+        return Err(LibraryError::GraphExists(path_name.to_string()));
+    }
+
+    let sequences_by_name = parts.into_iter().collect::<HashMap<String, String>>();
+    let slots = read_library_slots(library_file_path)?;
+
+    // Each slot gets its own nodes, so a part used in several slots doesn't join them together.
+    let mut slot_nodes: Vec<Vec<(i64, i64)>> = vec![];
+    for (index, slot) in slots.iter().enumerate() {
+        let mut nodes = vec![];
+        for part in slot {
+            let sequence = sequences_by_name
+                .get(part)
+                .ok_or_else(|| LibraryError::UnknownPart(part.clone()))?;
+            let seq = Sequence::new()
+                .sequence_type("DNA")
+                .sequence(sequence)
+                .save(conn);
+            let node_id = Node::create(
+                conn,
+                &seq.hash,
+                calculate_hash(&format!(
+                    "{collection_name}.{path_name}:{index}:{hash}",
+                    hash = seq.hash
+                )),
+            );
+            nodes.push((node_id, seq.length));
+        }
+        slot_nodes.push(nodes);
+        report_step(
+            progress,
+            "Library slots processed.",
+            index as u64 + 1,
+            Some(slots.len() as u64),
+        )?;
+    }
+
+    let mut new_edges = vec![];
+    let mut path_edge_indices = vec![];
+    for (node_id, _) in slot_nodes.first().unwrap() {
+        new_edges.push(EdgeData {
+            source_node_id: PATH_START_NODE_ID,
+            source_coordinate: 0,
+            source_strand: Strand::Forward,
+            target_node_id: *node_id,
+            target_coordinate: 0,
+            target_strand: Strand::Forward,
+        });
+    }
+    path_edge_indices.push(0);
+    for (nodes1, nodes2) in slot_nodes.iter().tuple_windows() {
+        path_edge_indices.push(new_edges.len());
+        for (node1, length1) in nodes1 {
+            for (node2, _) in nodes2 {
+                new_edges.push(EdgeData {
+                    source_node_id: *node1,
+                    source_coordinate: *length1,
+                    source_strand: Strand::Forward,
+                    target_node_id: *node2,
+                    target_coordinate: 0,
+                    target_strand: Strand::Forward,
+                });
+            }
+        }
+    }
+    path_edge_indices.push(new_edges.len());
+    for (node_id, length) in slot_nodes.last().unwrap() {
+        new_edges.push(EdgeData {
+            source_node_id: *node_id,
+            source_coordinate: *length,
+            source_strand: Strand::Forward,
+            target_node_id: PATH_END_NODE_ID,
+            target_coordinate: 0,
+            target_strand: Strand::Forward,
+        });
+    }
+
+    let block_group = BlockGroup::create(conn, collection_name, sample_name, path_name);
+    let edge_ids = Edge::bulk_create(conn, &new_edges);
+    let new_block_group_edges = edge_ids
+        .iter()
+        .map(|edge_id| BlockGroupEdgeData {
+            block_group_id: block_group.id,
+            edge_id: *edge_id,
+            chromosome_index: 0,
+            phased: 0,
+        })
+        .collect::<Vec<_>>();
+    BlockGroupEdge::bulk_create(conn, &new_block_group_edges);
+    let path_edge_ids = path_edge_indices
+        .iter()
+        .map(|index| edge_ids[*index])
+        .collect::<Vec<i64>>();
+    Path::create(conn, path_name, block_group.id, &path_edge_ids);
+
+    let combinations = slot_nodes
+        .iter()
+        .map(|nodes| nodes.len())
+        .product::<usize>();
+    let summary_str = format!("{path_name}: {combinations} changes.\n");
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: library_file_path.to_string(),
+            file_type: FileTypes::CSV,
+            description: "library_csv_import".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::accession::{Accession, AccessionEdge, AccessionEdgeData, AccessionPath};
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::traits::Query;
+    use crate::progress_bar::CancellationToken;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_imports_backbone_library() {
+        setup_gen_dir();
+        let parts_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/plasmid_parts.fa");
+        let library_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/plasmid_design.csv");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_library(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "plasmid",
+            parts_path.to_str().unwrap(),
+            library_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let block_groups = Sample::get_block_groups(conn, "test", None);
+        assert_eq!(block_groups.len(), 1);
+        let block_group = &block_groups[0];
+        assert_eq!(block_group.name, "plasmid");
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn, block_group.id, false),
+            HashSet::from_iter(vec![
+                "GCGGCCAAAAATGATAATTTAAA".to_string(),
+                "GCGGCCAAAAATGTTAATTTAAA".to_string(),
+                "GCGGCCTAATATGATAATTTAAA".to_string(),
+                "GCGGCCTAATATGTTAATTTAAA".to_string(),
+                "GCGGCCCAACATGATAATTTAAA".to_string(),
+                "GCGGCCCAACATGTTAATTTAAA".to_string(),
+            ])
+        );
+        assert_eq!(
+            BlockGroup::get_current_path(conn, block_group.id).sequence(conn),
+            "GCGGCCAAAAATGATAATTTAAA"
+        );
+
+        assert!(matches!(
+            import_library(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "plasmid",
+                parts_path.to_str().unwrap(),
+                library_path.to_str().unwrap(),
+            ),
+            Err(LibraryError::GraphExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_imports_library_from_accessions() {
+        setup_gen_dir();
+        let mut fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_path.push("fixtures/simple.fa");
+        let mut parts_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        parts_path.push("fixtures/parts.fa");
+        let mut library_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        library_path.push("fixtures/combinatorial_design.csv");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        for path in [&fasta_path, &parts_path] {
+            import_fasta(
+                &path.to_str().unwrap().to_string(),
+                "test",
+                None,
+                false,
+                conn,
+                op_conn,
+            )
+            .unwrap();
+        }
+
+        // Register each imported sequence as an accession covering its whole graph, m123 included
+        for block_group in Sample::get_block_groups(conn, "test", None) {
+            let path = BlockGroup::get_current_path(conn, block_group.id);
+            let block = &path.blocks(conn)[1];
+            let edge_ids = AccessionEdge::bulk_create(
+                conn,
+                &vec![
+                    AccessionEdgeData {
+                        source_node_id: PATH_START_NODE_ID,
+                        source_coordinate: -1,
+                        source_strand: Strand::Forward,
+                        target_node_id: block.node_id,
+                        target_coordinate: block.sequence_start,
+                        target_strand: Strand::Forward,
+                        chromosome_index: 0,
+                    },
+                    AccessionEdgeData {
+                        source_node_id: block.node_id,
+                        source_coordinate: block.sequence_end,
+                        source_strand: Strand::Forward,
+                        target_node_id: PATH_END_NODE_ID,
+                        target_coordinate: -1,
+                        target_strand: Strand::Forward,
+                        chromosome_index: 0,
+                    },
+                ],
+            );
+            let accession = Accession::create(conn, &block_group.name, path.id, None).unwrap();
+            AccessionPath::create(conn, accession.id, &edge_ids);
+        }
+
+        import_library_from_accessions(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "pool",
+            library_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let block_group = Sample::get_block_groups(conn, "test", None)
+            .into_iter()
+            .find(|block_group| block_group.name == "pool")
+            .unwrap();
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn, block_group.id, false).len(),
+            9
+        );
+        // only the accessions the design names are made into nodes
+        let node_ids = BlockGroupEdge::edges_for_block_group(conn, block_group.id)
+            .iter()
+            .flat_map(|edge| [edge.edge.source_node_id, edge.edge.target_node_id])
+            .filter(|node_id| !Node::is_terminal(*node_id))
+            .collect::<HashSet<i64>>();
+        assert_eq!(node_ids.len(), 6);
+    }
+
+    #[derive(Default)]
+    struct RecordingReporter {
+        reports: RefCell<Vec<(String, u64, Option<u64>)>>,
+    }
+
+    impl ProgressReporter for RecordingReporter {
+        fn on_progress(&self, step: &str, completed: u64, total: Option<u64>) {
+            self.reports
+                .borrow_mut()
+                .push((step.to_string(), completed, total));
+        }
+    }
+
+    #[test]
+    fn test_imports_library_with_progress() {
+        setup_gen_dir();
+        let parts_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/plasmid_parts.fa");
+        let library_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/plasmid_design.csv");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(matches!(
+            import_library_with_progress(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "plasmid",
+                parts_path.to_str().unwrap(),
+                library_path.to_str().unwrap(),
+                &token,
+            ),
+            Err(LibraryError::OperationError(OperationError::Cancelled))
+        ));
+        // nothing is recorded for a cancelled import
+        assert_eq!(
+            Operation::query(op_conn, "select * from operation;", rusqlite::params!()).len(),
+            0
+        );
+
+        let reporter = RecordingReporter::default();
+        import_library_with_progress(
+            conn,
+            op_conn,
+            "other",
+            None,
+            "plasmid",
+            parts_path.to_str().unwrap(),
+            library_path.to_str().unwrap(),
+            &reporter,
+        )
+        .unwrap();
+        assert_eq!(
+            reporter.reports.into_inner(),
+            (1..=4)
+                .map(|completed| ("Library slots processed.".to_string(), completed, Some(4)))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use crate::calculate_hash;
+use crate::models::file_types::FileTypes;
+use crate::models::operations::OperationInfo;
+use crate::models::sample::Sample;
+use crate::models::{
+    block_group::BlockGroup,
+    block_group_edge::{BlockGroupEdge, BlockGroupEdgeData},
+    collection::Collection,
+    edge::{Edge, EdgeData},
+    node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID},
+    operations::Operation,
+    path::Path,
+    sequence::Sequence,
+    strand::Strand,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use crate::progress_bar::{report_step, NoProgress, ProgressReporter};
+use crate::read_lines;
+use crate::tuning::BulkSettings;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MafError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("Invalid MAF line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("No alignment blocks found")]
+    Empty,
+    #[error("A graph named {0} already exists")]
+    GraphExists(String),
+}
+
+// One aligned sequence of an alignment block: its species and its row of the alignment, with
+// gaps as '-'.
+struct MafRow {
+    species: String,
+    contig: String,
+    text: Vec<u8>,
+}
+
+// The source of a MAF row is species.contig, such as hg38.chr1. Sources without a dot are used as
+// both.
+fn split_source(source: &str) -> (String, String) {
+    match source.split_once('.') {
+        Some((species, contig)) => (species.to_string(), contig.to_string()),
+        None => (source.to_string(), source.to_string()),
+    }
+}
+
+fn read_maf_blocks(maf_path: &str) -> Result<Vec<Vec<MafRow>>, MafError> {
+    let mut blocks = vec![];
+    let mut block: Option<Vec<MafRow>> = None;
+    for (index, line) in read_lines(maf_path)?.enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        match fields.first() {
+            None => {
+                // a blank line ends a block
+                if let Some(finished) = block.take() {
+                    blocks.push(finished);
+                }
+            }
+            Some(&"a") => {
+                if let Some(finished) = block.replace(vec![]) {
+                    blocks.push(finished);
+                }
+            }
+            Some(&"s") => {
+                let rows = block.as_mut().ok_or_else(|| MafError::Parse {
+                    line: line_number,
+                    message: "sequence line outside of an alignment block".to_string(),
+                })?;
+                if fields.len() != 7 {
+                    return Err(MafError::Parse {
+                        line: line_number,
+                        message: format!("expected 7 fields, found {}", fields.len()),
+                    });
+                }
+                let text = fields[6].as_bytes().to_vec();
+                if let Some(first) = rows.first() {
+                    if first.text.len() != text.len() {
+                        return Err(MafError::Parse {
+                            line: line_number,
+                            message: format!(
+                                "alignment row has {} columns, the block has {}",
+                                text.len(),
+                                first.text.len()
+                            ),
+                        });
+                    }
+                }
+                let (species, contig) = split_source(fields[1]);
+                rows.push(MafRow {
+                    species,
+                    contig,
+                    text,
+                });
+            }
+            // comments, and the i, e and q lines of a block, don't change the alignment
+            _ => {}
+        }
+    }
+    if let Some(finished) = block.take() {
+        blocks.push(finished);
+    }
+    Ok(blocks.into_iter().filter(|rows| !rows.is_empty()).collect())
+}
+
+fn is_gap(base: u8) -> bool {
+    base == b'-' || base == b'.'
+}
+
+// Creates a graph from the nodes each path passes through, given as (node id, length), with one
+// edge between each pair of consecutive nodes shared by all paths that take it. Paths without nodes
+// are skipped, and the first path is made the current one. Returns the number of paths created.
+pub(crate) fn create_graph_from_paths(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+    paths: &[(String, Vec<(i64, i64)>)],
+) -> usize {
+    let mut new_edges = vec![];
+    let mut edge_indices: HashMap<EdgeData, usize> = HashMap::new();
+    let mut path_edge_indices = vec![];
+    for (path_name, path_nodes) in paths.iter() {
+        // a path with only gaps has no sequence to make a path of
+        if path_nodes.is_empty() {
+            continue;
+        }
+        let nodes = std::iter::once((PATH_START_NODE_ID, 0))
+            .chain(path_nodes.iter().copied())
+            .chain(std::iter::once((PATH_END_NODE_ID, 0)));
+        let mut indices = vec![];
+        for ((source_node_id, source_length), (target_node_id, _)) in nodes.tuple_windows() {
+            let edge = EdgeData {
+                source_node_id,
+                source_coordinate: source_length,
+                source_strand: Strand::Forward,
+                target_node_id,
+                target_coordinate: 0,
+                target_strand: Strand::Forward,
+            };
+            let index = *edge_indices.entry(edge.clone()).or_insert_with(|| {
+                new_edges.push(edge);
+                new_edges.len() - 1
+            });
+            indices.push(index);
+        }
+        path_edge_indices.push((path_name, indices));
+    }
+
+    let block_group = BlockGroup::create(conn, collection_name, sample_name, graph_name);
+    let edge_ids = Edge::bulk_create(conn, &new_edges);
+    let new_block_group_edges = edge_ids
+        .iter()
+        .map(|edge_id| BlockGroupEdgeData {
+            block_group_id: block_group.id,
+            edge_id: *edge_id,
+            chromosome_index: 0,
+            phased: 0,
+        })
+        .collect::<Vec<_>>();
+    BlockGroupEdge::bulk_create(conn, &new_block_group_edges);
+    // the latest path is the current one, so the first path is added last
+    for (path_name, indices) in path_edge_indices.iter().rev() {
+        let path_edge_ids = indices
+            .iter()
+            .map(|index| edge_ids[*index])
+            .collect::<Vec<i64>>();
+        Path::create(conn, path_name, block_group.id, &path_edge_ids);
+    }
+    path_edge_indices.len()
+}
+
+// Builds a graph from a multiple alignment (MAF) file, with one path for every species in the
+// alignment. Each alignment block is split into runs of columns: columns where every row has the
+// same base become a node shared by all of the species, and in the other columns each distinct
+// sequence of a row gets its own node. A species with more than one row in a block uses its first
+// one. Paths follow the order of the alignment blocks, and the current path is that of the first
+// species of the file. The graph is named after the contig of the first row unless a name is given.
+pub fn import_maf<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    maf_path: &str,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    graph_name: Option<&str>,
+) -> Result<Operation, MafError> {
+    import_maf_with_progress(
+        conn,
+        operation_conn,
+        maf_path,
+        collection_name,
+        sample_name,
+        graph_name,
+        &NoProgress,
+    )
+}
+
+pub fn import_maf_with_progress<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    maf_path: &str,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    graph_name: Option<&str>,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, MafError> {
+    let _bulk = BulkSettings::apply(conn);
+    let mut session = start_operation(conn);
+    let sample_name = sample_name.into();
+
+    let blocks = read_maf_blocks(maf_path)?;
+    let graph_name = match graph_name {
+        Some(graph_name) => graph_name.to_string(),
+        None => blocks
+            .first()
+            .ok_or(MafError::Empty)?
+            .first()
+            .unwrap()
+            .contig
+            .clone(),
+    };
+
+    if !Collection::exists(conn, collection_name) {
+        Collection::create(conn, collection_name);
+    }
+    if let Some(sample_name) = sample_name {
+        Sample::get_or_create(conn, sample_name);
+    }
+    if Sample::get_block_groups(conn, collection_name, sample_name)
+        .iter()
+        .any(|block_group| block_group.name == graph_name)
+    {
+        return Err(MafError::GraphExists(graph_name));
+    }
+
+    let create_node = |sequence: &str, key: &str| -> (i64, i64) {
+        let seq = Sequence::new()
+            .sequence_type("DNA")
+            .sequence(sequence)
+            .save(conn);
+        let node_id = Node::create(
+            conn,
+            &seq.hash,
+            calculate_hash(&format!(
+                "{collection_name}.{graph_name}:{key}:{hash}",
+                hash = seq.hash
+            )),
+        );
+        (node_id, seq.length)
+    };
+
+    // the nodes each species passes through, in alignment order
+    let mut species_order: Vec<String> = vec![];
+    let mut species_nodes: HashMap<String, Vec<(i64, i64)>> = HashMap::new();
+    for (block_index, rows) in blocks.iter().enumerate() {
+        let rows = rows
+            .iter()
+            .unique_by(|row| &row.species)
+            .collect::<Vec<&MafRow>>();
+        for row in rows.iter() {
+            if !species_nodes.contains_key(&row.species) {
+                species_order.push(row.species.clone());
+                species_nodes.insert(row.species.clone(), vec![]);
+            }
+        }
+        let column_count = rows[0].text.len();
+        let is_shared = |column: usize| {
+            let base = rows[0].text[column];
+            !is_gap(base)
+                && rows
+                    .iter()
+                    .all(|row| row.text[column].eq_ignore_ascii_case(&base))
+        };
+        let runs = (0..column_count).chunk_by(|column| is_shared(*column));
+        for (run_index, (shared, columns)) in runs.into_iter().enumerate() {
+            let columns = columns.collect::<Vec<usize>>();
+            let key = format!("{block_index}:{run_index}");
+            let row_sequence = |row: &MafRow| {
+                columns
+                    .iter()
+                    .map(|column| row.text[*column])
+                    .filter(|base| !is_gap(*base))
+                    .map(char::from)
+                    .collect::<String>()
+            };
+            if shared {
+                let node = create_node(&row_sequence(rows[0]), &key);
+                for row in rows.iter() {
+                    species_nodes.get_mut(&row.species).unwrap().push(node);
+                }
+            } else {
+                let mut nodes_by_sequence: HashMap<String, (i64, i64)> = HashMap::new();
+                for row in rows.iter() {
+                    let sequence = row_sequence(row);
+                    // rows with only gaps here skip the run
+                    if sequence.is_empty() {
+                        continue;
+                    }
+                    let node = *nodes_by_sequence
+                        .entry(sequence.clone())
+                        .or_insert_with(|| create_node(&sequence, &key));
+                    species_nodes.get_mut(&row.species).unwrap().push(node);
+                }
+            }
+        }
+        report_step(
+            progress,
+            "Alignment blocks processed.",
+            block_index as u64 + 1,
+            Some(blocks.len() as u64),
+        )?;
+    }
+
+    let paths = species_order
+        .iter()
+        .map(|species| (species.clone(), species_nodes[species].clone()))
+        .collect::<Vec<_>>();
+    let path_count =
+        create_graph_from_paths(conn, collection_name, sample_name, &graph_name, &paths);
+
+    let summary_str = format!(
+        "{graph_name}: {block_count} alignment blocks, {species_count} paths.\n",
+        block_count = blocks.len(),
+        species_count = path_count
+    );
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: maf_path.to_string(),
+            file_type: FileTypes::MAF,
+            description: "maf_import".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::traits::Query;
+    use crate::progress_bar::CancellationToken;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use rusqlite::params;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_imports_maf() {
+        setup_gen_dir();
+        let maf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.maf");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_maf(
+            conn,
+            op_conn,
+            maf_path.to_str().unwrap(),
+            "test",
+            None,
+            None,
+        )
+        .unwrap();
+
+        let block_groups = Sample::get_block_groups(conn, "test", None);
+        assert_eq!(block_groups.len(), 1);
+        let block_group = &block_groups[0];
+        assert_eq!(block_group.name, "chr1");
+
+        let sequences = Path::query(
+            conn,
+            "select * from paths where block_group_id = ?1 order by name;",
+            params!(block_group.id),
+        )
+        .iter()
+        .map(|path| (path.name.clone(), path.sequence(conn)))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            sequences,
+            vec![
+                ("chimp".to_string(), "ATCGATCGGGATGCGGAACACA".to_string()),
+                ("human".to_string(), "ATCGATCGATGCGGAACACA".to_string()),
+                ("mouse".to_string(), "ATCGTTCGGATGC".to_string()),
+            ]
+        );
+        assert_eq!(
+            BlockGroup::get_current_path(conn, block_group.id).name,
+            "human"
+        );
+
+        // ATCG, A, T, TCG, GG, G, ATGC and GGAACACA
+        let node_ids = BlockGroupEdge::edges_for_block_group(conn, block_group.id)
+            .iter()
+            .flat_map(|edge| [edge.edge.source_node_id, edge.edge.target_node_id])
+            .filter(|node_id| !Node::is_terminal(*node_id))
+            .collect::<HashSet<i64>>();
+        assert_eq!(node_ids.len(), 8);
+
+        assert!(matches!(
+            import_maf(
+                conn,
+                op_conn,
+                maf_path.to_str().unwrap(),
+                "test",
+                None,
+                None,
+            ),
+            Err(MafError::GraphExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_cancels_maf_import() {
+        setup_gen_dir();
+        let maf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.maf");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(matches!(
+            import_maf_with_progress(
+                conn,
+                op_conn,
+                maf_path.to_str().unwrap(),
+                "test",
+                None,
+                None,
+                &token,
+            ),
+            Err(MafError::OperationError(OperationError::Cancelled))
+        ));
+        assert!(Sample::get_block_groups(conn, "test", None).is_empty());
+        assert_eq!(
+            Operation::query(op_conn, "select * from operation;", params!()).len(),
+            0
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::calculate_hash;
+use crate::imports::maf::create_graph_from_paths;
+use crate::models::file_types::FileTypes;
+use crate::models::operations::OperationInfo;
+use crate::models::sample::Sample;
+use crate::models::{
+    collection::Collection, node::Node, operations::Operation, sequence::Sequence,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use crate::progress_bar::{report_step, NoProgress, ProgressReporter};
+use crate::read_lines;
+use crate::tuning::BulkSettings;
+use noodles::fasta;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PafError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("Invalid PAF line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("Contig {0} is not in the fasta file")]
+    MissingContig(String),
+    #[error("No alignments found")]
+    Empty,
+    #[error("A graph named {0} already exists")]
+    GraphExists(String),
+}
+
+// The fields of a PAF line used to build a graph. Coordinates are 0-based and end-exclusive, on the
+// forward strand of both contigs.
+struct PafAlignment {
+    line: usize,
+    query_name: String,
+    query_length: usize,
+    query_start: usize,
+    query_end: usize,
+    reverse: bool,
+    target_name: String,
+    target_length: usize,
+    target_start: usize,
+    target_end: usize,
+    cigar: Vec<(usize, char)>,
+}
+
+fn parse_cigar(cigar: &str) -> Option<Vec<(usize, char)>> {
+    let mut operations = vec![];
+    let mut length = String::new();
+    for character in cigar.chars() {
+        if character.is_ascii_digit() {
+            length.push(character);
+        } else {
+            operations.push((length.parse().ok()?, character));
+            length.clear();
+        }
+    }
+    length.is_empty().then_some(operations)
+}
+
+fn read_paf(paf_path: &str) -> Result<Vec<PafAlignment>, PafError> {
+    let mut alignments = vec![];
+    for (index, line) in read_lines(paf_path)?.enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parse_error = |message: String| PafError::Parse {
+            line: line_number,
+            message,
+        };
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 12 {
+            return Err(parse_error(format!(
+                "expected at least 12 fields, found {}",
+                fields.len()
+            )));
+        }
+        let number = |index: usize| {
+            fields[index]
+                .parse::<usize>()
+                .map_err(|_| parse_error(format!("{} is not a number", fields[index])))
+        };
+        let cigar = fields[12..]
+            .iter()
+            .find_map(|tag| tag.strip_prefix("cg:Z:"))
+            .ok_or_else(|| parse_error("alignments need a cg tag".to_string()))?;
+        let cigar =
+            parse_cigar(cigar).ok_or_else(|| parse_error(format!("invalid cg tag {cigar}")))?;
+        alignments.push(PafAlignment {
+            line: line_number,
+            query_name: fields[0].to_string(),
+            query_length: number(1)?,
+            query_start: number(2)?,
+            query_end: number(3)?,
+            reverse: fields[4] == "-",
+            target_name: fields[5].to_string(),
+            target_length: number(6)?,
+            target_start: number(7)?,
+            target_end: number(8)?,
+            cigar,
+        });
+    }
+    Ok(alignments)
+}
+
+// A disjoint set of the bases of every contig, where aligned bases that match are joined.
+struct BaseSets {
+    parents: Vec<usize>,
+}
+
+impl BaseSets {
+    fn new(size: usize) -> BaseSets {
+        BaseSets {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut base: usize) -> usize {
+        while self.parents[base] != base {
+            self.parents[base] = self.parents[self.parents[base]];
+            base = self.parents[base];
+        }
+        base
+    }
+
+    fn join(&mut self, first: usize, second: usize) {
+        let first = self.find(first);
+        let second = self.find(second);
+        if first != second {
+            self.parents[first.max(second)] = first.min(second);
+        }
+    }
+}
+
+// The base next to every occurrence of a set of bases, if it's always the same one.
+#[derive(Clone, Copy, PartialEq)]
+enum Neighbor {
+    Unseen,
+    One(usize),
+    Many,
+}
+
+impl Neighbor {
+    fn add(&mut self, base: Option<usize>) {
+        *self = match (*self, base) {
+            (Neighbor::Unseen, Some(base)) => Neighbor::One(base),
+            (Neighbor::One(current), Some(base)) if current == base => Neighbor::One(base),
+            _ => Neighbor::Many,
+        };
+    }
+}
+
+/*
+   Builds a graph from pairwise alignments (PAF) between contigs, with one path for each contig
+   aligned. PAF files don't have the bases of the contigs, so they are read from a fasta file. The
+   bases of the alignments' cg tags that match are joined, transitively across alignments, and runs
+   of joined bases that always follow each other become nodes shared by the contigs that have them.
+   Mismatches and indels are left as nodes of their own contig. Only forward strand alignments are
+   used; reverse strand ones are skipped and counted in the summary. Paths follow the order contigs
+   are first named in the file, targets before queries, so the current path is the target of the
+   first alignment, which also names the graph unless a name is given.
+*/
+pub fn import_paf<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    paf_path: &str,
+    fasta_path: &str,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    graph_name: Option<&str>,
+) -> Result<Operation, PafError> {
+    import_paf_with_progress(
+        conn,
+        operation_conn,
+        paf_path,
+        fasta_path,
+        collection_name,
+        sample_name,
+        graph_name,
+        &NoProgress,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn import_paf_with_progress<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    paf_path: &str,
+    fasta_path: &str,
+    collection_name: &str,
+    sample_name: impl Into<Option<&'a str>>,
+    graph_name: Option<&str>,
+    progress: &dyn ProgressReporter,
+) -> Result<Operation, PafError> {
+    let _bulk = BulkSettings::apply(conn);
+    let mut session = start_operation(conn);
+    let sample_name = sample_name.into();
+
+    let alignments = read_paf(paf_path)?;
+    let graph_name = match graph_name {
+        Some(graph_name) => graph_name.to_string(),
+        None => alignments
+            .first()
+            .ok_or(PafError::Empty)?
+            .target_name
+            .clone(),
+    };
+
+    if !Collection::exists(conn, collection_name) {
+        Collection::create(conn, collection_name);
+    }
+    if let Some(sample_name) = sample_name {
+        Sample::get_or_create(conn, sample_name);
+    }
+    if Sample::get_block_groups(conn, collection_name, sample_name)
+        .iter()
+        .any(|block_group| block_group.name == graph_name)
+    {
+        return Err(PafError::GraphExists(graph_name));
+    }
+
+    let mut contig_names: Vec<String> = vec![];
+    for alignment in alignments.iter() {
+        for name in [&alignment.target_name, &alignment.query_name] {
+            if !contig_names.contains(name) {
+                contig_names.push(name.clone());
+            }
+        }
+    }
+    let mut contig_sequences: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut reader = fasta::io::reader::Builder.build_from_path(fasta_path)?;
+    for result in reader.records() {
+        let record = result?;
+        let name = String::from_utf8_lossy(record.name()).to_string();
+        if contig_names.contains(&name) {
+            contig_sequences.insert(name, record.sequence().as_ref().to_vec());
+        }
+    }
+    // each base of a contig has an index in the sets, after those of the contigs before it
+    let mut offsets: HashMap<&str, usize> = HashMap::new();
+    let mut size = 0;
+    for name in contig_names.iter() {
+        let sequence = contig_sequences
+            .get(name)
+            .ok_or_else(|| PafError::MissingContig(name.clone()))?;
+        offsets.insert(name, size);
+        size += sequence.len();
+    }
+
+    let mut sets = BaseSets::new(size);
+    let mut reverse_count = 0;
+    for (index, alignment) in alignments.iter().enumerate() {
+        report_step(
+            progress,
+            "Alignments processed.",
+            index as u64,
+            Some(alignments.len() as u64),
+        )?;
+        let parse_error = |message: String| PafError::Parse {
+            line: alignment.line,
+            message,
+        };
+        let query = &contig_sequences[&alignment.query_name];
+        let target = &contig_sequences[&alignment.target_name];
+        if query.len() != alignment.query_length || target.len() != alignment.target_length {
+            return Err(parse_error(
+                "contig lengths differ from the fasta file".to_string(),
+            ));
+        }
+        if alignment.query_start > alignment.query_end
+            || alignment.query_end > alignment.query_length
+            || alignment.target_start > alignment.target_end
+            || alignment.target_end > alignment.target_length
+        {
+            return Err(parse_error("aligned ranges are out of bounds".to_string()));
+        }
+        if alignment.reverse {
+            reverse_count += 1;
+            continue;
+        }
+        let query_offset = offsets[alignment.query_name.as_str()];
+        let target_offset = offsets[alignment.target_name.as_str()];
+        let mut query_position = alignment.query_start;
+        let mut target_position = alignment.target_start;
+        for (length, operation) in alignment.cigar.iter() {
+            match operation {
+                'M' | '=' | 'X' => {
+                    if query_position + length > alignment.query_end
+                        || target_position + length > alignment.target_end
+                    {
+                        return Err(parse_error(
+                            "the cg tag doesn't span the aligned ranges".to_string(),
+                        ));
+                    }
+                    for index in 0..*length {
+                        let query_base = query[query_position + index];
+                        let target_base = target[target_position + index];
+                        if *operation != 'X' && query_base.eq_ignore_ascii_case(&target_base) {
+                            sets.join(
+                                query_offset + query_position + index,
+                                target_offset + target_position + index,
+                            );
+                        }
+                    }
+                    query_position += length;
+                    target_position += length;
+                }
+                'I' => query_position += length,
+                'D' => target_position += length,
+                _ => return Err(parse_error(format!("unsupported cg operation {operation}"))),
+            }
+        }
+        if query_position != alignment.query_end || target_position != alignment.target_end {
+            return Err(parse_error(
+                "the cg tag doesn't span the aligned ranges".to_string(),
+            ));
+        }
+    }
+
+    // the joined bases of each contig, and which come before and after each set of bases
+    let contig_bases = contig_names
+        .iter()
+        .map(|name| {
+            let offset = offsets[name.as_str()];
+            (0..contig_sequences[name].len())
+                .map(|index| sets.find(offset + index))
+                .collect::<Vec<usize>>()
+        })
+        .collect::<Vec<_>>();
+    let mut previous = vec![Neighbor::Unseen; size];
+    let mut next = vec![Neighbor::Unseen; size];
+    for bases in contig_bases.iter() {
+        for (index, base) in bases.iter().enumerate() {
+            previous[*base].add(index.checked_sub(1).map(|index| bases[index]));
+            next[*base].add(bases.get(index + 1).copied());
+        }
+    }
+
+    let mut nodes: HashMap<Vec<usize>, (i64, i64)> = HashMap::new();
+    let mut paths = vec![];
+    for (name, bases) in contig_names.iter().zip(contig_bases.iter()) {
+        let sequence = &contig_sequences[name];
+        let mut path_nodes = vec![];
+        let mut start = 0;
+        while start < bases.len() {
+            // a node goes on while its bases always follow each other, and stops before looping
+            let mut end = start + 1;
+            let mut seen = HashSet::from([bases[start]]);
+            while end < bases.len()
+                && next[bases[end - 1]] == Neighbor::One(bases[end])
+                && previous[bases[end]] == Neighbor::One(bases[end - 1])
+                && seen.insert(bases[end])
+            {
+                end += 1;
+            }
+            let node = *nodes.entry(bases[start..end].to_vec()).or_insert_with(|| {
+                let seq = Sequence::new()
+                    .sequence_type("DNA")
+                    .sequence(&String::from_utf8_lossy(&sequence[start..end]))
+                    .save(conn);
+                let node_id = Node::create(
+                    conn,
+                    &seq.hash,
+                    calculate_hash(&format!(
+                        "{collection_name}.{graph_name}:{base}:{hash}",
+                        base = bases[start],
+                        hash = seq.hash
+                    )),
+                );
+                (node_id, seq.length)
+            });
+            path_nodes.push(node);
+            start = end;
+        }
+        paths.push((name.clone(), path_nodes));
+        report_step(
+            progress,
+            "Contigs processed.",
+            paths.len() as u64,
+            Some(contig_names.len() as u64),
+        )?;
+    }
+    let path_count =
+        create_graph_from_paths(conn, collection_name, sample_name, &graph_name, &paths);
+
+    let mut summary_str = format!(
+        "{graph_name}: {alignment_count} alignments, {path_count} paths, {node_count} nodes.\n",
+        alignment_count = alignments.len(),
+        node_count = nodes.len()
+    );
+    if reverse_count > 0 {
+        summary_str.push_str(&format!(
+            "{reverse_count} reverse strand alignments skipped.\n"
+        ));
+    }
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: paf_path.to_string(),
+            file_type: FileTypes::PAF,
+            description: "paf_import".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::block_group_edge::BlockGroupEdge;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::path::Path;
+    use crate::models::traits::Query;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use rusqlite::params;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_cigar() {
+        assert_eq!(
+            parse_cigar("10M2I24="),
+            Some(vec![(10, 'M'), (2, 'I'), (24, '=')])
+        );
+        assert_eq!(parse_cigar("10M2"), None);
+    }
+
+    #[test]
+    fn test_imports_paf() {
+        setup_gen_dir();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let paf_path = fixtures.join("simple.paf");
+        let fasta_path = fixtures.join("paf_contigs.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+
+        import_paf(
+            conn,
+            op_conn,
+            paf_path.to_str().unwrap(),
+            fasta_path.to_str().unwrap(),
+            "test",
+            None,
+            None,
+        )
+        .unwrap();
+
+        let block_groups = Sample::get_block_groups(conn, "test", None);
+        assert_eq!(block_groups.len(), 1);
+        let block_group = &block_groups[0];
+        assert_eq!(block_group.name, "ref");
+        let sequences = Path::query(
+            conn,
+            "select * from paths where block_group_id = ?1 order by name;",
+            params!(block_group.id),
+        )
+        .iter()
+        .map(|path| (path.name.clone(), path.sequence(conn)))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            sequences,
+            vec![
+                (
+                    "alt".to_string(),
+                    "ATCGATCGATTTCGATCGATCGGTAACACACAGAGA".to_string()
+                ),
+                (
+                    "ref".to_string(),
+                    "ATCGATCGATCGATCGATCGGGAACACACAGAGA".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            BlockGroup::get_current_path(conn, block_group.id).name,
+            "ref"
+        );
+        // ATCGATCGAT, TT, CGATCGATCGG, G, T and AACACACAGAGA
+        let node_ids = BlockGroupEdge::edges_for_block_group(conn, block_group.id)
+            .iter()
+            .flat_map(|edge| [edge.edge.source_node_id, edge.edge.target_node_id])
+            .filter(|node_id| !Node::is_terminal(*node_id))
+            .collect::<HashSet<i64>>();
+        assert_eq!(node_ids.len(), 6);
+
+        assert!(matches!(
+            import_paf(
+                conn,
+                op_conn,
+                paf_path.to_str().unwrap(),
+                fasta_path.to_str().unwrap(),
+                "test",
+                None,
+                None,
+            ),
+            Err(PafError::GraphExists(_))
+        ));
+    }
+}
@@ -4,34 +4,58 @@ use std::path::Path;
 use std::{io, str};
 
 pub mod annotations;
+pub mod clone;
 pub mod config;
+pub mod connection_pool;
 pub mod diffs;
+pub mod errors;
 pub mod exports;
+pub mod fork;
+pub mod fsck;
+pub mod gc;
 pub mod genbank;
 pub mod gfa;
 pub mod gfa_reader;
 pub mod graph;
+pub mod graph_operators;
+pub mod graph_order;
+pub mod hydration;
 pub mod imports;
 pub mod migrations;
 pub mod models;
+pub mod object_store;
 pub mod operation_management;
+pub mod organism;
 pub mod patch;
-mod progress_bar;
+pub mod progress_bar;
 pub mod range;
+pub mod search;
+pub mod server;
 #[cfg(test)]
 pub mod test_helpers;
+pub mod tuning;
 pub mod updates;
+pub mod validate;
 pub mod views;
 
 use crate::migrations::run_migrations;
+use crate::tuning::TuningProfile;
 use noodles::vcf::variant::record::samples::series::value::genotype::Phasing;
 use rusqlite::Connection;
 use sha2::{Digest, Sha256};
 
 pub fn get_connection(db_path: &str) -> Connection {
+    get_connection_with_profile(db_path, TuningProfile::default())
+}
+
+pub fn get_connection_with_profile(db_path: &str, profile: TuningProfile) -> Connection {
     let mut conn =
         Connection::open(db_path).unwrap_or_else(|_| panic!("Error connecting to {}", db_path));
+    conn.busy_timeout(config::BUSY_TIMEOUT).unwrap();
     rusqlite::vtab::array::load_module(&conn).unwrap();
+    profile
+        .apply(&conn)
+        .unwrap_or_else(|err| panic!("Error tuning {db_path}: {err}"));
     run_migrations(&mut conn);
     conn
 }
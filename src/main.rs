@@ -1,39 +1,103 @@
 #![allow(warnings)]
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
+use gb_io::seq::Seq;
 use gen::config;
-use gen::config::{get_gen_dir, get_operation_connection};
+use gen::config::{get_gen_dir, get_operation_connection, RepositoryLock};
 
-use gen::annotations::gff::propagate_gff;
+use gen::annotations::cds::translate_cds;
+use gen::annotations::coverage::{
+    import_coverage, region_coverage, resolve_track, CoverageError, CoverageFormat,
+};
+use gen::annotations::gff::{
+    export_stored_gff, import_gff_annotations, propagate_gff, AnnotationError,
+};
+use gen::clone::{clone_repository, default_destination, remote_path};
+use gen::connection_pool::get_connection_pool;
+use gen::diffs::genbank::{genbank_diff, write_genbank_diff};
 use gen::diffs::gfa::gfa_sample_diff;
-use gen::exports::fasta::export_fasta;
+use gen::diffs::recipe::{create_recipe, write_recipe};
+use gen::diffs::sets::GraphSet;
+use gen::diffs::three_way::{three_way_diff, write_three_way_tsv};
+use gen::diffs::tsv::tsv_sample_diff;
+use gen::diffs::vcf::vcf_sample_diff;
+use gen::errors::GenError;
+use gen::exports::bed::{export_divergence_bed, propagate_bed};
+use gen::exports::fasta::{
+    export_accessions, export_bed_sequences, export_fasta, export_haplotype_fasta,
+    write_region_fasta,
+};
 use gen::exports::genbank::export_genbank;
-use gen::exports::gfa::export_gfa;
-use gen::get_connection;
-use gen::imports::fasta::{import_fasta, FastaError};
+use gen::exports::gfa::{estimate_gfa_export, export_divergent_gfa, export_gfa, export_region_gfa};
+use gen::exports::liftover::{export_chain, export_paf};
+use gen::exports::mapping::export_mapping_tsv;
+use gen::exports::msa::{export_msa, MsaFormat};
+use gen::exports::readme::write_readme;
+use gen::exports::sql::{operations_since, write_sql_delta};
+use gen::fork::fork_repository;
+use gen::fsck::{fsck, FsckError};
+use gen::gc::{collect_garbage, collect_garbage_objects, find_garbage};
+use gen::genbank::GenBankError;
+use gen::get_connection_with_profile;
+use gen::graph::bubbles::variant_sites;
+use gen::graph_operators::{merge_samples, simplify_sample};
+use gen::graph_order::GraphOrder;
+use gen::hydration::{dehydrate_sequences, hydrate_sequences};
+use gen::imports::fasta::{import_fasta_with_type, FastaError};
 use gen::imports::genbank::import_genbank;
 use gen::imports::gfa::import_gfa;
+use gen::imports::library::{import_library, import_library_from_accessions};
+use gen::imports::maf::import_maf;
+use gen::imports::paf::import_paf;
 use gen::models::block_group::BlockGroup;
+use gen::models::block_group_stats::BlockGroupStats;
+use gen::models::collection::Collection;
+use gen::models::edge::EdgeData;
 use gen::models::file_types::FileTypes;
 use gen::models::metadata;
-use gen::models::operations::{setup_db, Branch, Operation, OperationInfo, OperationState};
-use gen::models::sample::Sample;
+use gen::models::node::Node;
+use gen::models::node_usage::NodeUsage;
+use gen::models::operations::{
+    setup_db, Branch, CoordinateFrame, FileAddition, GfaExport, Operation, OperationInfo,
+    OperationState, OperationSummary, Tag,
+};
+use gen::models::sample::{Sample, BASE_SAMPLE_NAME};
+use gen::models::sequence::SequenceStore;
+use gen::models::strand::Strand;
+use gen::models::traits::Query;
 use gen::operation_management;
 use gen::operation_management::{parse_patch_operations, OperationError};
+use gen::organism::Organisms;
 use gen::patch;
-use gen::updates::fasta::update_with_fasta;
+use gen::progress_bar::NoProgress;
+use gen::range::{parse_region, Region as ParsedRegion};
+use gen::search::{find_sequences, read_queries, DEFAULT_KMER_SIZE, DEFAULT_WINDOW_SIZE};
+use gen::server::serve;
+use gen::tuning::TuningProfile;
+use gen::updates::edges::add_edge;
+use gen::updates::fasta::{preview_fasta_update, update_with_fasta};
 use gen::updates::gaf::{transform_csv_to_fasta, update_with_gaf};
 use gen::updates::genbank::update_with_genbank;
 use gen::updates::library::update_with_library;
-use gen::updates::vcf::{update_with_vcf, VcfError};
+use gen::updates::manifest::update_with_manifest;
+use gen::updates::nodes::backfill_node_hashes;
+use gen::updates::paths::{rename_path, set_current_path};
+use gen::updates::recipe::apply_recipe;
+use gen::updates::samples::{dedupe_samples, find_duplicate_samples};
+use gen::updates::vcf::{update_with_vcf_with_progress, VcfError, VcfFilter};
+use gen::validate::{validate, ValidationError};
+use gen::views::operations::render_operation_graph;
 use gen::views::patch::view_patches;
+use indicatif::{HumanBytes, HumanDuration};
 use itertools::Itertools;
-use noodles::core::Region;
 use rusqlite::{types::Value, Connection};
+use std::env;
 use std::fmt::Debug;
-use std::fs::File;
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::time::Duration;
 use std::{io, str};
 
 #[derive(Parser)]
@@ -42,6 +106,9 @@ struct Cli {
     /// The path to the database you wish to utilize
     #[arg(short, long)]
     db: Option<String>,
+    /// Wait for another command changing the repository to finish instead of failing
+    #[arg(long, global = true)]
+    wait: bool,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -54,9 +121,343 @@ fn get_default_collection(conn: &Connection) -> String {
         .unwrap_or("default".to_string())
 }
 
+fn get_graph_order(conn: &Connection) -> GraphOrder {
+    let mut stmt = conn
+        .prepare("select graph_order from defaults where id = 1")
+        .unwrap();
+    match stmt.query_row((), |row| row.get::<_, Option<String>>(0)) {
+        Ok(Some(names)) => GraphOrder::from_names(&names),
+        _ => GraphOrder::Natural,
+    }
+}
+
+fn get_organisms(conn: &Connection) -> Result<Organisms, GenError> {
+    let (profiles, organism): (Option<String>, Option<String>) = conn.query_row(
+        "select organisms, organism from defaults where id = 1",
+        (),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let organisms = match profiles {
+        Some(profiles) => Organisms::from_toml(&profiles)?,
+        None => Organisms::default(),
+    };
+    Ok(organisms.with_default(organism.as_deref())?)
+}
+
+fn get_tuning_profile(conn: &Connection) -> Result<TuningProfile, GenError> {
+    let profile: Option<String> = conn.query_row(
+        "select tuning_profile from defaults where id = 1",
+        (),
+        |row| row.get(0),
+    )?;
+    match profile {
+        Some(profile) => profile.parse().map_err(|_| {
+            GenError::InvalidArgument(format!(
+                "The default database profile {profile} isn't one of safe or bulk, set it with gen defaults --profile."
+            ))
+        }),
+        None => Ok(TuningProfile::default()),
+    }
+}
+
+// Sample names given on the command line, where the base sample can be named as (reference).
+fn sample_arg(sample: &Option<String>) -> Option<&str> {
+    sample.as_deref().and_then(Sample::from_display_name)
+}
+
+fn required_arg<T: Clone>(value: &Option<T>, flag: &str) -> Result<T, GenError> {
+    value
+        .clone()
+        .ok_or_else(|| GenError::InvalidArgument(format!("{flag} must be provided.")))
+}
+
+fn get_frame(
+    operation_conn: &Connection,
+    db_uuid: &str,
+    name: &str,
+) -> Result<CoordinateFrame, GenError> {
+    CoordinateFrame::get_by_name(operation_conn, db_uuid, name)
+        .ok_or_else(|| GenError::NotFound(format!("No coordinate frame named {name}.")))
+}
+
+// The sample of a frame given to a command that reads coordinates on the current paths of a sample,
+// which can't take a frame with a path or offset.
+fn frame_sample(frame: &CoordinateFrame) -> Result<Option<String>, GenError> {
+    if !frame.is_sample_only() {
+        return Err(GenError::InvalidArgument(format!(
+            "Frame {name} has a path or offset, which this command can't use. Only frames of a sample's current paths can be given to it.",
+            name = frame.name
+        )));
+    }
+    Ok(frame.sample_name.clone())
+}
+
+// Runs the given command body inside a transaction on both databases, rolling both back if the
+// body fails so a failed command leaves no partial changes behind.
+fn in_transaction<F>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    body: F,
+) -> Result<(), GenError>
+where
+    F: FnOnce() -> Result<(), GenError>,
+{
+    conn.execute("BEGIN TRANSACTION", [])?;
+    operation_conn.execute("BEGIN TRANSACTION", [])?;
+    match body() {
+        Ok(()) => {
+            conn.execute("END TRANSACTION", [])?;
+            operation_conn.execute("END TRANSACTION", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK TRANSACTION;", [])?;
+            operation_conn.execute("ROLLBACK TRANSACTION;", [])?;
+            Err(e)
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum AccessionCommands {
+    /// Export all accessions as fasta records
+    #[command(arg_required_else_help(true))]
+    Export {
+        /// The name of the collection to export accessions from
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the fasta file to export to
+        #[arg(short, long)]
+        fasta: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CoverageCommands {
+    /// Import read depth from a bedGraph, or the scores of a BED file, as a coverage track
+    #[command(arg_required_else_help(true))]
+    Import {
+        /// The name of the collection the coverage is of
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample the coverage is referenced to (if not provided, the reference)
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the track, the file's name without its extension if not provided
+        #[arg(short, long)]
+        track: Option<String>,
+        /// The bedGraph file to import
+        #[arg(long, conflicts_with = "bed", required_unless_present = "bed")]
+        bedgraph: Option<String>,
+        /// A BED file to import, with the depth as the score of each interval
+        #[arg(long)]
+        bed: Option<String>,
+    },
+    /// Show the depth of a region of a sample, as bedGraph lines split at the graph's nodes
+    #[command(arg_required_else_help(true))]
+    Get {
+        /// The name of the collection the region is in
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample the region is of (if not provided, the reference)
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The track to show, which can be left out if there is only one
+        #[arg(short, long)]
+        track: Option<String>,
+        /// The region, as name, name:start or name:start-end (1-based, inclusive)
+        #[arg(long)]
+        region: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EdgeCommands {
+    /// Add a single edge to a graph, checking that it fits the nodes it joins
+    #[command(arg_required_else_help(true))]
+    Add {
+        /// The name of the collection containing the graph
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample containing the graph
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the graph to add the edge to
+        #[arg(short, long)]
+        graph: String,
+        /// The id of the node the edge leaves
+        #[arg(long)]
+        source_node: i64,
+        /// The coordinate the edge leaves the source node at
+        #[arg(long)]
+        source_coordinate: i64,
+        /// The strand of the source node, + or -
+        #[arg(long, default_value = "+")]
+        source_strand: Strand,
+        /// The id of the node the edge enters
+        #[arg(long)]
+        target_node: i64,
+        /// The coordinate the edge enters the target node at
+        #[arg(long)]
+        target_coordinate: i64,
+        /// The strand of the target node, + or -
+        #[arg(long, default_value = "+")]
+        target_strand: Strand,
+        /// The chromosome index of the edge in the graph
+        #[arg(long, default_value_t = 0)]
+        chromosome_index: i64,
+        /// Whether the edge is phased
+        #[arg(long, default_value_t = 0)]
+        phased: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum PathCommands {
+    /// List the paths of a graph from oldest to newest, marking the current one
+    #[command(arg_required_else_help(true))]
+    List {
+        /// The name of the collection containing the graph
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample containing the graph
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the graph to list the paths of
+        #[arg(short, long)]
+        graph: String,
+    },
+    /// Make a path the current path of its graph until a newer path is added to it
+    #[command(arg_required_else_help(true))]
+    SetCurrent {
+        /// The name of the collection containing the graph
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample containing the graph
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the graph containing the path
+        #[arg(short, long)]
+        graph: String,
+        /// The name of the path to make current
+        #[arg(short, long)]
+        path: String,
+    },
+    /// Rename a path of a graph
+    #[command(arg_required_else_help(true))]
+    Rename {
+        /// The name of the collection containing the graph
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample containing the graph
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the graph containing the path
+        #[arg(short, long)]
+        graph: String,
+        /// The name of the path to rename
+        #[arg(short, long)]
+        path: String,
+        /// The new name of the path
+        #[arg(long)]
+        new_name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum FrameCommands {
+    /// Name a coordinate system: a sample, optionally one of its paths, and the position on the
+    /// path of the frame's first base
+    #[command(arg_required_else_help(true))]
+    Add {
+        /// The name of the frame
+        #[clap(index = 1)]
+        name: String,
+        /// The sample the frame is on (if not provided, the reference)
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the path the frame is on, instead of the current path of each graph
+        #[arg(short, long)]
+        path: Option<String>,
+        /// The 0-based position on the path where the frame starts
+        #[arg(long, default_value_t = 0)]
+        offset: i64,
+    },
+    /// List the coordinate frames
+    List {},
+    /// Remove a coordinate frame
+    #[command(arg_required_else_help(true))]
+    Remove {
+        /// The name of the frame
+        #[clap(index = 1)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SequenceCommands {
+    /// Read the bases of sequences stored in fasta files, such as by shallow imports, into the database
+    Hydrate {
+        /// Only hydrate the sequences stored in this fasta file
+        #[arg(short, long)]
+        fasta: Option<String>,
+    },
+    /// Move the bases of sequences stored in the database to a new, indexed fasta file
+    #[command(arg_required_else_help(true))]
+    Dehydrate {
+        /// The fasta file to write, which must not exist yet
+        #[arg(short, long)]
+        fasta: String,
+    },
+    /// Show or set where new sequences of a collection keep their bases
+    Store {
+        /// The name of the collection
+        #[arg(short, long)]
+        name: Option<String>,
+        /// database, objects for the compressed object store of the .gen directory, or packed for 2 bits a base in the database
+        store: Option<SequenceStore>,
+    },
+}
+
 #[derive(Subcommand)]
 #[allow(clippy::large_enum_variant)]
 enum Commands {
+    /// Work with the accessions registered in a collection
+    #[command(arg_required_else_help(true))]
+    Accessions {
+        #[command(subcommand)]
+        command: AccessionCommands,
+    },
+    /// Import read depth and see how much sequencing evidence supports each part of a graph
+    #[command(arg_required_else_help(true))]
+    Coverage {
+        #[command(subcommand)]
+        command: CoverageCommands,
+    },
+    /// Edit the edges of a graph directly
+    #[command(hide = true, arg_required_else_help(true))]
+    Edge {
+        #[command(subcommand)]
+        command: EdgeCommands,
+    },
+    /// List the paths of a graph, choose its current path and rename paths
+    #[command(arg_required_else_help(true))]
+    Paths {
+        #[command(subcommand)]
+        command: PathCommands,
+    },
+    /// Name coordinate systems that commands accept with --frame in place of a sample and path
+    #[command(arg_required_else_help(true))]
+    Frames {
+        #[command(subcommand)]
+        command: FrameCommands,
+    },
+    /// Move sequence data between the database and fasta files
+    #[command(arg_required_else_help(true))]
+    Sequences {
+        #[command(subcommand)]
+        command: SequenceCommands,
+    },
     /// Commands for transforming file types for input to Gen.
     #[command(arg_required_else_help(true))]
     Transform {
@@ -76,6 +477,26 @@ enum Commands {
         /// GFA file path
         #[arg(short, long)]
         gfa: Option<String>,
+        /// A CSV library design to build a new graph from. Columns with a single part are fixed
+        /// segments and columns with several parts are variable slots.
+        #[arg(long)]
+        library: Option<String>,
+        /// A fasta with the parts named in --library
+        #[arg(long)]
+        parts: Option<String>,
+        /// Use the accessions of the collection named in --library as its parts instead of --parts
+        #[arg(long, action, requires = "library", conflicts_with = "parts")]
+        parts_from_accessions: bool,
+        /// A multiple alignment (MAF) file to build a graph from, with a path for each species
+        #[arg(long)]
+        maf: Option<String>,
+        /// Pairwise alignments (PAF with cg tags) between contigs to build a graph from, with a path
+        /// for each contig. The contigs are read from --fasta
+        #[arg(long, requires = "fasta")]
+        paf: Option<String>,
+        /// The name of the graph to create from --library, --maf or --paf
+        #[arg(long)]
+        path_name: Option<String>,
         /// The name of the collection to store the entry under
         #[arg(short, long)]
         name: Option<String>,
@@ -85,6 +506,9 @@ enum Commands {
         /// Don't store the sequence in the database, instead store the filename
         #[arg(long, action)]
         shallow: bool,
+        /// The type of the sequences in --fasta, such as DNA, RNA or protein
+        #[arg(long, default_value = "DNA")]
+        sequence_type: String,
     },
     /// Update a sequence collection with new data
     #[command(arg_required_else_help(true))]
@@ -110,8 +534,9 @@ enum Commands {
         /// New sample name if we are updating with intentional edits
         #[arg(long)]
         new_sample: Option<String>,
-        /// Use the given sample as the parent sample for changes.
-        #[arg(long, alias = "cf")]
+        /// Use the given coordinate frame, or sample, as the parent sample for changes. A frame's
+        /// path and offset apply to the --path-name, --start and --end of fasta and library updates
+        #[arg(long, alias = "cf", alias = "frame")]
         coordinate_frame: Option<String>,
         /// A CSV with combinatorial library information
         #[arg(short, long)]
@@ -119,6 +544,10 @@ enum Commands {
         /// A fasta with the combinatorial library parts
         #[arg(long)]
         parts: Option<String>,
+        /// A CSV of part pairs a library's combinations must not have (forbid,part,part) or have
+        /// together (require,part,part, where the first part needs the second)
+        #[arg(long, requires = "library")]
+        constraints: Option<String>,
         /// The name of the path to add the library to
         #[arg(short, long)]
         path_name: Option<String>,
@@ -134,6 +563,24 @@ enum Commands {
         /// If a new entity is found, create it as a normal import
         #[arg(long, action, alias = "cm")]
         create_missing: bool,
+        /// Abort a fasta update unless it replaces this many bases
+        #[arg(long, requires = "fasta")]
+        expect_replaced_length: Option<i64>,
+        /// Abort a fasta update unless the bases it replaces are these
+        #[arg(long, requires = "fasta")]
+        expect_removed_seq: Option<String>,
+        /// Only incorporate VCF records with at least this QUAL
+        #[arg(long, requires = "vcf")]
+        min_qual: Option<f32>,
+        /// Only incorporate VCF records whose FILTER is PASS
+        #[arg(long, action, requires = "vcf")]
+        pass_only: bool,
+        /// Only incorporate VCF records whose INFO matches an expression like 'DP>=10 && TYPE=="snp"'
+        #[arg(long, requires = "vcf")]
+        include_expr: Option<String>,
+        /// A YAML manifest of fasta, VCF, library and GAF updates to make in order as one operation
+        #[arg(long, conflicts_with_all = ["fasta", "vcf", "gb", "library"])]
+        manifest: Option<String>,
     },
     /// Update a sequence collecting using GAF results.
     #[command(name = "update-gaf", arg_required_else_help(true))]
@@ -167,6 +614,10 @@ enum Commands {
         /// or discontinuous ranges, use commas. HEAD and HEAD~<number> syntax is supported.
         #[clap(index = 1)]
         operation: String,
+        /// Include the sequences the operations refer to, so the patch applies to repositories
+        /// that don't have them
+        #[arg(long, action)]
+        embed_sequences: bool,
     },
     /// Apply changes from a patch file
     #[command(name = "patch-apply", arg_required_else_help(true))]
@@ -174,6 +625,18 @@ enum Commands {
         /// The patch file
         #[clap(index = 1)]
         patch: String,
+        /// Report what the patch would change and any conflicts without applying it
+        #[arg(long, action)]
+        dry_run: bool,
+        /// Only apply the operations that change graphs of this collection
+        #[arg(long)]
+        collection: Option<String>,
+        /// Only apply the operations that change graphs of this sample
+        #[arg(long)]
+        sample: Option<String>,
+        /// Only apply these operations, given as hashes or hash prefixes separated by commas
+        #[arg(long, value_delimiter = ',')]
+        operation: Vec<String>,
     },
     /// View a patch in dot format
     #[command(name = "patch-view", arg_required_else_help(true))]
@@ -188,6 +651,24 @@ enum Commands {
     },
     /// Initialize a gen repository
     Init {},
+    /// Copy the repository to a new directory. Changesets are hard linked to the originals where the
+    /// filesystem allows it, and databases are copied, which clones them on copy-on-write filesystems.
+    Fork {
+        /// The directory to create the copy in, which must not exist or be empty
+        #[clap(index = 1)]
+        destination: String,
+    },
+    /// Clone a repository into a new directory. The operations and their changesets are copied and
+    /// the database is rebuilt from them, checking out the head of the main branch.
+    Clone {
+        /// The repository to clone, as a path or a file:// URL
+        #[clap(index = 1)]
+        remote: String,
+        /// The directory to create the clone in, which must not exist or be empty. Defaults to
+        /// the name of the remote's directory.
+        #[clap(index = 2)]
+        destination: Option<String>,
+    },
     /// Manage and create branches
     #[command(arg_required_else_help(true))]
     Branch {
@@ -205,6 +686,10 @@ enum Commands {
         list: bool,
         #[arg(short, long, action)]
         merge: bool,
+        /// Report how many operations the current branch is ahead of and behind the given branch,
+        /// or every other branch when none is given
+        #[arg(short, long, action)]
+        status: bool,
         /// The branch name
         #[clap(index = 1)]
         branch_name: Option<String>,
@@ -215,6 +700,12 @@ enum Commands {
         /// Create and checkout a new branch.
         #[arg(short, long)]
         branch: Option<String>,
+        /// Only materialize the given collection, may be repeated
+        #[arg(short, long)]
+        collection: Vec<String>,
+        /// Materialize all collections, ending a sparse checkout
+        #[arg(long, action, conflicts_with = "collection")]
+        all_collections: bool,
         /// The operation hash to move to
         #[clap(index = 1)]
         hash: Option<String>,
@@ -226,12 +717,67 @@ enum Commands {
         #[clap(index = 1)]
         hash: String,
     },
+    /// Undo an operation with a new operation, keeping the operations made since
+    #[command(arg_required_else_help(true))]
+    Revert {
+        /// The operation hash to revert
+        #[clap(index = 1)]
+        hash: String,
+    },
+    /// Combine a range of operations ending at the current one, like HEAD~2..HEAD, into one operation
+    #[command(arg_required_else_help(true))]
+    Squash {
+        /// The range of operations to squash, start..end
+        #[clap(index = 1)]
+        range: String,
+        /// The message of the squashed operation, the messages of the operations if not given
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Replay the operations of the current branch since it split from another branch on top of
+    /// that branch's head
+    #[command(arg_required_else_help(true))]
+    Rebase {
+        /// The branch to rebase onto
+        #[clap(index = 1)]
+        branch: String,
+        /// Edit the list of operations to replay in $VISUAL or $EDITOR first, to reorder or drop them
+        #[arg(short, long, action)]
+        interactive: bool,
+    },
+    /// Check the database and its operations for broken references, printing a JSON report
+    Fsck {},
+    /// Delete sequences, nodes and edges that no graph or reachable operation uses
+    Gc {
+        /// Report what would be deleted without deleting it
+        #[arg(long, action)]
+        dry_run: bool,
+    },
+    /// Give the nodes made without a hash, such as by older versions of gen, the hash they would be
+    /// made with now, so patches match them to the same nodes in other repositories
+    HashNodes {},
     /// View operations carried out against a database
-    #[command()]
+    #[command(alias = "log")]
     Operations {
         /// The branch to list operations for
         #[arg(short, long)]
         branch: Option<String>,
+        /// Draw the operations of all branches as a graph
+        #[arg(long, action, conflicts_with = "branch")]
+        graph: bool,
+        /// The operation hash to set a message for
+        #[arg(long, requires = "message")]
+        edit: Option<String>,
+        /// The message to attach to the operation given by --edit
+        #[arg(short, long, requires = "edit")]
+        message: Option<String>,
+    },
+    /// Show the details of an operation, including the command that created it
+    #[command(arg_required_else_help(true))]
+    Describe {
+        /// The operation hash to describe
+        #[clap(index = 1)]
+        hash: String,
     },
     /// Apply an operation to a branch
     #[command(arg_required_else_help(true))]
@@ -240,6 +786,22 @@ enum Commands {
         #[clap(index = 1)]
         hash: String,
     },
+    /// Name an operation or a sample. An operation's tag can be given in place of its hash, such as
+    /// to checkout, reset, apply or patch-create. Lists the tags without a name
+    Tag {
+        /// The name of the tag
+        #[clap(index = 1)]
+        name: Option<String>,
+        /// The operation hash to tag, the current operation if neither it nor --sample is given
+        #[clap(index = 2, conflicts_with = "sample")]
+        hash: Option<String>,
+        /// Tag a sample instead of an operation
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// Delete the tag
+        #[arg(short, long, action, requires = "name", conflicts_with_all = ["hash", "sample"])]
+        delete: bool,
+    },
     /// Export sequence data
     #[command(arg_required_else_help(true))]
     Export {
@@ -252,15 +814,74 @@ enum Commands {
         /// An optional sample name
         #[arg(short, long)]
         sample: Option<String>,
+        /// Use the base sample, shown as (reference), which is also the default without --sample
+        #[arg(long, action, conflicts_with = "sample")]
+        base_sample: bool,
         /// The name of the fasta file to export to
         #[arg(short, long)]
         fasta: Option<String>,
+        /// Write a record for each phased haplotype of every graph, named like chr1_hap1, instead
+        /// of the current path. GFA exports get a walk for each haplotype instead of paths, which
+        /// `vg gbwt -G` builds a GBWT or GBZ from
+        #[arg(long, action, conflicts_with_all = ["only_divergent", "region"])]
+        haplotypes: bool,
         /// The name of the GenBank file to export to
         #[arg(long)]
         gb: Option<String>,
+        /// Print the predicted size and run time of the GFA export instead of writing it
+        #[arg(
+            long,
+            action,
+            alias = "dry-run",
+            requires = "gfa",
+            conflicts_with_all = ["only_divergent", "region", "haplotypes"]
+        )]
+        estimate: bool,
+        /// Only export the regions of the sample's graphs that differ from the graphs it was made from
+        /// (GFA only)
+        #[arg(long, action, conflicts_with = "region")]
+        only_divergent: bool,
+        /// The number of base pairs of flanking sequence to include around divergent regions
+        #[arg(long, default_value_t = 0)]
+        context: i64,
+        /// Export the block mappings between two samples, given as from_sample,to_sample. Leave a
+        /// side empty to use the reference, eg ",child" (requires --tsv)
+        #[arg(long, requires = "tsv")]
+        mapping: Option<String>,
+        /// The name of the tsv file to export block mappings to
+        #[arg(long, requires = "mapping")]
+        tsv: Option<String>,
+        /// The name of a file to write a multiple sequence alignment of samples through a region
+        /// to. Files ending in .aln or .clustal are written as Clustal, others as aligned FASTA
+        #[arg(long, requires_all = ["region", "samples"])]
+        msa: Option<String>,
+        /// The region to align with --msa or to export with --gfa, as name, name:start or
+        /// name:start-end (1-based, inclusive). GFA exports only hold the part of the graph the
+        /// region spans
+        #[arg(long)]
+        region: Option<String>,
+        /// The path of the graph the region is on when exporting it to GFA, instead of the current
+        /// path
+        #[arg(long, requires_all = ["region", "gfa"])]
+        backbone: Option<String>,
+        /// The samples to align, separated by commas. Use (reference) for the reference
+        #[arg(long, requires = "msa", value_delimiter = ',')]
+        samples: Vec<String>,
+        /// The name of a SQL file to write the changes of the current branch's operations to, as
+        /// statements against a copy of gen's tables (see docs/sql_delta.md)
+        #[arg(long)]
+        sql_delta: Option<String>,
+        /// Only write the operations after this one to --sql-delta
+        #[arg(long, requires = "sql_delta")]
+        since: Option<String>,
+        /// The name of a Markdown file to write a summary of the collection to, with checksums of
+        /// the files exported alongside it
+        #[arg(long)]
+        readme: Option<String>,
     },
     /// Configure default options
     #[command(arg_required_else_help(true))]
+    #[command(alias = "config")]
     Defaults {
         /// The default database to use
         #[arg(short, long)]
@@ -268,9 +889,94 @@ enum Commands {
         /// The default collection to use
         #[arg(short, long)]
         collection: Option<String>,
+        /// The author name recorded on new operations
+        #[arg(long)]
+        author: Option<String>,
+        /// The author email recorded on new operations
+        #[arg(long)]
+        email: Option<String>,
+        /// A file of graph names, one per line, giving the order graphs are listed and exported in,
+        /// or "natural" to sort graph names with their numbers in numeric order (the default)
+        #[arg(long)]
+        graph_order: Option<String>,
+        /// A TOML file of [[organism]] profiles with their codon tables, in addition to the built
+        /// in standard, bacterial and yeast_mitochondrial ones, or "builtin" to only use those.
+        /// Profiles set earlier are replaced
+        #[arg(long)]
+        organisms: Option<String>,
+        /// The organism profile commands use when none is given
+        #[arg(long)]
+        organism: Option<String>,
+        /// The database settings to use, "safe" for interactive use (the default) or "bulk" to
+        /// trade durability for speed when loading a lot of data
+        #[arg(long)]
+        profile: Option<TuningProfile>,
+    },
+    /// Store the annotations of a GFF file in a collection
+    #[command(arg_required_else_help(true))]
+    Annotate {
+        /// The name of the collection to annotate
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample the annotations are referenced to (if not provided, the default)
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The coordinate frame the annotations are referenced to, in place of --sample
+        #[arg(long, conflicts_with = "sample")]
+        frame: Option<String>,
+        /// The GFF file to import
+        #[arg(short, long)]
+        gff: String,
+    },
+    /// Translate the CDS features of a GFF file on a sample and list how its proteins differ from
+    /// the reference's
+    #[command(name = "translate-cds", arg_required_else_help(true))]
+    TranslateCds {
+        /// The name of the collection the sample is in
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample the CDS features are referenced to and compared with (if not
+        /// provided, the default)
+        #[arg(short, long)]
+        from_sample: Option<String>,
+        /// The coordinate frame the CDS features are referenced to, in place of --from-sample
+        #[arg(long, conflicts_with = "from_sample")]
+        frame: Option<String>,
+        /// The name of the sample to translate
+        #[arg(short, long)]
+        sample: String,
+        /// The GFF file with the CDS features. Records sharing an ID are joined as exons.
+        #[arg(short, long)]
+        gff: String,
+        /// The organism profile whose genetic code is used (if not provided, the default)
+        #[arg(long)]
+        organism: Option<String>,
+        /// Exit with an error if any protein changed
+        #[arg(long, action)]
+        require_silent: bool,
+    },
+    /// Write liftover files converting coordinates of one sample to another
+    #[command(arg_required_else_help(true))]
+    Liftover {
+        /// The name of the collection the samples are in
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample to convert coordinates from (if not provided, the default)
+        #[arg(short, long)]
+        from_sample: Option<String>,
+        /// The name of the sample to convert coordinates to
+        #[arg(short, long)]
+        to_sample: String,
+        /// The name of the UCSC chain file to write
+        #[arg(long, required_unless_present = "paf")]
+        chain: Option<String>,
+        /// The name of the PAF file to write
+        #[arg(long)]
+        paf: Option<String>,
     },
     /// Convert annotation coordinates between two samples
     #[command(arg_required_else_help(true))]
+    #[command(group(ArgGroup::new("output").required(true).args(["output_gff", "output_bed"])))]
     PropagateAnnotations {
         /// The name of the collection to annotate
         #[arg(short, long)]
@@ -278,17 +984,47 @@ enum Commands {
         /// The name of the sample the annotations are referenced to (if not provided, the default)
         #[arg(short, long)]
         from_sample: Option<String>,
+        /// The coordinate frame the annotations are referenced to, in place of --from-sample
+        #[arg(long, conflicts_with = "from_sample")]
+        frame: Option<String>,
         /// The name of the sample to annotate
         #[arg(short, long)]
         to_sample: String,
-        /// The name of the annotation file to propagate
+        /// The name of the GFF annotation file to propagate. If omitted with --output-gff, the
+        /// annotations stored with `gen annotate` are used.
+        #[arg(short, long, requires = "output_gff")]
+        gff: Option<String>,
+        /// The name of the GFF output file
         #[arg(short, long)]
-        gff: String,
-        /// The name of the output file
-        #[arg(short, long)]
-        output_gff: String,
+        output_gff: Option<String>,
+        /// The name of the BED annotation file to propagate
+        #[arg(long, requires = "output_bed")]
+        bed: Option<String>,
+        /// The name of the BED output file
+        #[arg(long, requires = "bed", conflicts_with = "output_gff")]
+        output_bed: Option<String>,
+    },
+    ListSamples {
+        /// Also list the base sample, shown as (reference), first
+        #[arg(long, action)]
+        base_sample: bool,
+    },
+    /// Serve a read-only HTTP API of the database's collections, samples and graphs
+    Serve {
+        /// The address to listen on
+        #[arg(long, default_value = "127.0.0.1:3000")]
+        address: String,
+        /// The number of database connections to serve requests with
+        #[arg(long, default_value_t = 4)]
+        connections: u32,
+    },
+    /// Find samples with the same graphs as another sample and record them as its aliases, which
+    /// hides them from list-samples
+    DedupeSamples {
+        /// Only list the duplicate samples, without recording them
+        #[arg(long, action)]
+        dry_run: bool,
     },
-    ListSamples {},
     #[command(arg_required_else_help(true))]
     ListGraphs {
         /// The name of the collection to list graphs for
@@ -297,6 +1033,33 @@ enum Commands {
         /// The name of the sample to list graphs for
         #[arg(short, long)]
         sample: Option<String>,
+        /// Use the base sample, shown as (reference), which is also the default without --sample
+        #[arg(long, action, conflicts_with = "sample")]
+        base_sample: bool,
+    },
+    /// Show node, edge, path, variant site, and sequence length counts for each graph
+    Stats {
+        /// The name of the collection to show stats for
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample to show stats for
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// Show the totals of every sample in the collection instead of each graph of one sample
+        #[arg(long, action, conflicts_with = "sample")]
+        by_sample: bool,
+    },
+    /// List the bubbles of each graph as variant sites, with the allele every sample's path takes
+    Bubbles {
+        /// The name of the collection to list variant sites for
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the sample whose graphs to find bubbles in
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// Only list the variant sites of this graph
+        #[arg(short, long)]
+        graph: Option<String>,
     },
     /// Extract a sequence from a graph
     #[command(arg_required_else_help(true))]
@@ -307,97 +1070,474 @@ enum Commands {
         /// The name of the sample containing the sequence
         #[arg(short, long)]
         sample: Option<String>,
+        /// The coordinate frame coordinates are given in, in place of --sample. The sequence is read
+        /// from the frame's path, with its offset added to the coordinates
+        #[arg(long, conflicts_with_all = ["sample", "bed"])]
+        frame: Option<String>,
         /// The name of the graph to get the sequence for
         #[arg(short, long)]
         graph: Option<String>,
-        /// The start coordinate of the sequence
-        #[arg(long)]
+        /// The start coordinate of the sequence (0-based). Negative values count back from the end
+        #[arg(long, allow_negative_numbers = true)]
         start: Option<i64>,
-        /// The end coordinate of the sequence
-        #[arg(long)]
+        /// The end coordinate of the sequence (0-based, exclusive). Negative values count back from
+        /// the end. On circular graphs it may be before the start, reading across the origin
+        #[arg(long, allow_negative_numbers = true)]
         end: Option<i64>,
-        /// The region (name:start-end format) of the sequence
+        /// The region of the sequence, as name, name:start or name:start-end (1-based, inclusive).
+        /// Coordinates may count back from the last base as $ or $-N
         #[arg(long)]
         region: Option<String>,
+        /// A BED file of regions to extract, instead of a single region
+        #[arg(long, requires = "fasta", conflicts_with_all = ["graph", "region"])]
+        bed: Option<String>,
+        /// The name of the fasta file to write the sequences of the BED regions to
+        #[arg(long, requires = "bed")]
+        fasta: Option<String>,
+        /// Write the sequence as a FASTA record while it's read, for regions too large to hold in
+        /// memory
+        #[arg(long, action, conflicts_with = "bed")]
+        stream: bool,
     },
-    /// Output a file representing the "diff" between two samples
-    Diff {
-        /// The name of the collection to diff
+    /// Show a region with the sequences flanking it on every path through the graph
+    #[command(arg_required_else_help(true))]
+    GetFlanks {
+        /// The name of the collection containing the region
         #[arg(short, long)]
         name: Option<String>,
-        /// The name of the first sample to diff
-        #[arg(long)]
-        sample1: Option<String>,
-        /// The name of the second sample to diff
-        #[arg(long)]
-        sample2: Option<String>,
-        /// The name of the output GFA file
-        #[arg(long)]
-        gfa: String,
+        /// The name of the sample containing the region
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The region of the current path, as name, name:start or name:start-end (1-based, inclusive)
+        #[arg(long, required_unless_present = "node")]
+        region: Option<String>,
+        /// The id of a node to use as the region instead
+        #[arg(long, requires = "graph", conflicts_with = "region")]
+        node: Option<i64>,
+        /// The name of the graph containing the node
+        #[arg(short, long)]
+        graph: Option<String>,
+        /// The length of the flanking sequences
+        #[arg(long, default_value_t = 20)]
+        flank: i64,
     },
-}
-
-fn main() {
-    let cli = Cli::parse();
+    /// Find where the sequences of a fasta file occur in the samples of a collection
+    #[command(arg_required_else_help(true))]
+    Find {
+        /// The name of the collection to search
+        #[arg(short, long)]
+        name: Option<String>,
+        /// A fasta file of the sequences to find
+        #[arg(short, long)]
+        query: String,
+        /// The number of mismatched bases allowed in a match
+        #[arg(long, default_value_t = 0)]
+        max_mismatches: usize,
+        /// The k-mer size of the minimizer index. Matches must share a k-mer with a single node
+        #[arg(long, default_value_t = DEFAULT_KMER_SIZE)]
+        kmer_size: usize,
+        /// The number of consecutive k-mers each minimizer is chosen from
+        #[arg(long, default_value_t = DEFAULT_WINDOW_SIZE)]
+        window_size: usize,
+    },
+    /// Check a collection against the rules of a TOML file, such as accessions a sample must keep or
+    /// the longest a contig may be. Exits with an error if any rule fails
+    #[command(arg_required_else_help(true))]
+    Validate {
+        /// The name of the collection to check
+        #[arg(short, long)]
+        name: Option<String>,
+        /// A TOML file with a [[rule]] table for each rule
+        #[arg(short, long)]
+        rules: String,
+    },
+    /// List the samples and graphs a node is part of, with where their paths pass through it
+    #[command(name = "which-samples", arg_required_else_help(true))]
+    WhichSamples {
+        /// The id of the node to look for
+        #[arg(long, conflicts_with = "sequence_hash")]
+        node: Option<i64>,
+        /// Look for every node with this sequence
+        #[arg(long)]
+        sequence_hash: Option<String>,
+    },
+    /// Compare two revisions of a GenBank file and list the features each change touches
+    #[command(arg_required_else_help(true))]
+    GbDiff {
+        /// The earlier revision
+        old: String,
+        /// The later revision
+        new: String,
+        /// Write the changes to this TSV file instead of printing them
+        #[arg(long)]
+        tsv: Option<String>,
+    },
+    /// Output a file representing the "diff" between two samples
+    Diff {
+        /// The name of the collection to diff
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the first sample to diff, omit or give (reference) for the base sample
+        #[arg(long)]
+        sample1: Option<String>,
+        /// The name of the second sample to diff, omit or give (reference) for the base sample
+        #[arg(long)]
+        sample2: Option<String>,
+        /// The name of the output GFA file
+        #[arg(long, required_unless_present_any = ["vcf", "tsv"])]
+        gfa: Option<String>,
+        /// The name of the output VCF file, with the first sample as the reference
+        #[arg(long)]
+        vcf: Option<String>,
+        /// The name of the output TSV file listing each changed region
+        #[arg(long)]
+        tsv: Option<String>,
+        /// Compare both samples to a common ancestor and write the classified regions to --tsv
+        #[arg(long, action, requires = "tsv", conflicts_with_all = ["gfa", "vcf"])]
+        three_way: bool,
+        /// The name of the common ancestor for --three-way, omit or give (reference) for the base sample
+        #[arg(long, requires = "three_way")]
+        ancestor: Option<String>,
+    },
+    /// Write a BED file of the reference intervals where a sample differs from the reference
+    #[command(arg_required_else_help(true))]
+    DivergenceBed {
+        /// The name of the collection containing the samples
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The sample to find the differences of
+        #[arg(short, long)]
+        sample: String,
+        /// The sample to compare against, omit or give (reference) for the base sample
+        #[arg(long)]
+        reference: Option<String>,
+        /// The name of the output BED file
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Write the sequence edits that turn one sample into another as a recipe that apply-plan can
+    /// replay on other samples
+    #[command(arg_required_else_help(true))]
+    Recipe {
+        /// The name of the collection containing the samples
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The sample the edits start from, omit or give (reference) for the base sample
+        #[arg(long)]
+        from: Option<String>,
+        /// The sample the edits lead to
+        #[arg(long)]
+        to: String,
+        /// The name of the output YAML file
+        #[arg(short, long)]
+        output: String,
+        /// The number of bases kept on each side of an edit to find it by on other samples
+        #[arg(long, default_value_t = 20)]
+        flank: i64,
+    },
+    /// Replay the edits of a recipe from gen recipe on a sample, storing the result as a new sample
+    #[command(arg_required_else_help(true))]
+    ApplyPlan {
+        /// The name of the collection containing the sample
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The recipe file to replay
+        #[arg(long)]
+        plan: String,
+        /// The sample to make the edits on, omit or give (reference) for the base sample
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the sample to create
+        #[arg(long)]
+        new_sample: String,
+    },
+    /// Compare the nodes and edges of two samples without a reference path
+    Compare {
+        /// The name of the collection to compare
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The name of the first sample, omit or give (reference) for the base sample
+        #[arg(long)]
+        sample1: Option<String>,
+        /// The name of the second sample, omit or give (reference) for the base sample
+        #[arg(long)]
+        sample2: Option<String>,
+        /// Write the selected subgraph to this GFA file
+        #[arg(long)]
+        gfa: Option<String>,
+        /// The subgraph to export: shared, union, or only in the first or second sample
+        #[arg(long, value_parser = ["shared", "union", "first", "second"], default_value = "second", requires = "gfa")]
+        subgraph: String,
+    },
+    /// Merge the changes of two samples derived from a common ancestor into a new sample
+    #[command(arg_required_else_help(true))]
+    MergeSamples {
+        /// The name of the collection the samples are in
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The common ancestor of both samples, omit or give (reference) for the base sample
+        #[arg(long)]
+        ancestor: Option<String>,
+        /// The first sample to merge, whose changes are used where both samples agree
+        #[arg(long)]
+        sample1: String,
+        /// The second sample to merge
+        #[arg(long)]
+        sample2: String,
+        /// The name of the merged sample
+        #[arg(long)]
+        new_sample: String,
+    },
+    /// Copy a sample into a new sample with its non-branching chains of nodes merged into single nodes
+    #[command(arg_required_else_help(true))]
+    Simplify {
+        /// The name of the collection the sample is in
+        #[arg(short, long)]
+        name: Option<String>,
+        /// The sample to simplify, omit or give (reference) for the base sample
+        #[arg(short, long)]
+        sample: Option<String>,
+        /// The name of the simplified sample
+        #[arg(long)]
+        new_sample: String,
+    },
+}
+
+impl Commands {
+    // Commands that only read the repository, which can run alongside a command changing it. They
+    // don't fill in the caches kept in the databases either, see config::disable_cache_writes.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Commands::PatchCreate { .. }
+                | Commands::PatchView { .. }
+                | Commands::Branch { status: true, .. }
+                | Commands::Fsck {}
+                | Commands::Operations { edit: None, .. }
+                | Commands::Describe { .. }
+                | Commands::ListSamples { .. }
+                | Commands::Serve { .. }
+                | Commands::ListGraphs { .. }
+                | Commands::Stats { .. }
+                | Commands::Bubbles { .. }
+                | Commands::Paths {
+                    command: PathCommands::List { .. }
+                }
+                | Commands::Frames {
+                    command: FrameCommands::List {}
+                }
+                | Commands::Coverage {
+                    command: CoverageCommands::Get { .. }
+                }
+                | Commands::GetSequence { .. }
+                | Commands::GetFlanks { .. }
+                | Commands::Find { .. }
+                | Commands::Validate { .. }
+                | Commands::WhichSamples { .. }
+                | Commands::Diff { .. }
+                | Commands::DivergenceBed { .. }
+                | Commands::Compare { .. }
+        )
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
+fn run(cli: Cli) -> Result<(), GenError> {
     // commands not requiring a db connection are handled here
     if let Some(Commands::Init {}) = &cli.command {
         config::get_or_create_gen_dir();
         println!("Gen repository initialized.");
-        return;
+        return Ok(());
+    }
+
+    if let Some(Commands::Fork { destination }) = &cli.command {
+        let gen_dir = PathBuf::from(config::get_gen_dir());
+        let summary = fork_repository(gen_dir.parent().unwrap(), Path::new(destination), cli.wait)?;
+        println!(
+            "Forked repository to {destination}: {linked_files} files linked ({linked_bytes}), {copied_files} files copied ({copied_bytes}).",
+            linked_files = summary.linked_files,
+            linked_bytes = HumanBytes(summary.linked_bytes),
+            copied_files = summary.copied_files,
+            copied_bytes = HumanBytes(summary.copied_bytes),
+        );
+        return Ok(());
+    }
+
+    if let Some(Commands::Clone {
+        remote,
+        destination,
+    }) = &cli.command
+    {
+        let destination = match destination {
+            Some(destination) => PathBuf::from(destination),
+            None => default_destination(&remote_path(remote)?),
+        };
+        let summary = clone_repository(remote, &destination, cli.wait)?;
+        println!(
+            "Cloned {remote} into {destination}: {copied_files} files copied ({copied_bytes}).",
+            destination = summary.destination.display(),
+            copied_files = summary.copied_files,
+            copied_bytes = HumanBytes(summary.copied_bytes),
+        );
+        match summary.operation_hash {
+            Some(hash) => println!("Checked out branch {} at {hash}.", summary.branch),
+            None => println!("Branch {} has no operations to check out.", summary.branch),
+        }
+        return Ok(());
     }
 
+    if let Some(Commands::GbDiff { old, new, tsv }) = &cli.command {
+        let read_records = |filename: &str| -> Result<Vec<Seq>, GenError> {
+            gb_io::reader::SeqReader::new(File::open(filename)?)
+                .map(|record| {
+                    record.map_err(|err| {
+                        GenError::from(GenBankError::ParseError(format!("{filename}: {err}")))
+                    })
+                })
+                .collect()
+        };
+        let changes = genbank_diff(&read_records(old)?, &read_records(new)?)?;
+        if let Some(tsv) = tsv {
+            write_genbank_diff(&mut File::create(tsv)?, &changes)?;
+        } else {
+            write_genbank_diff(&mut io::stdout().lock(), &changes)?;
+        }
+        return Ok(());
+    }
+
+    // commands that change the repository hold its lock until they finish
+    let _lock = match &cli.command {
+        Some(command) if command.is_read_only() => {
+            config::disable_cache_writes();
+            None
+        }
+        _ => Some(RepositoryLock::acquire(cli.wait)?),
+    };
+
     let operation_conn = get_operation_connection(None);
     if let Some(Commands::Defaults {
         database,
         collection,
+        author,
+        email,
+        graph_order,
+        organisms,
+        organism,
+        profile,
     }) = &cli.command
     {
         if let Some(name) = database {
-            operation_conn
-                .execute("update defaults set db_name=?1 where id = 1", (name,))
-                .unwrap();
+            operation_conn.execute("update defaults set db_name=?1 where id = 1", (name,))?;
             println!("Default database set to {name}");
         }
         if let Some(name) = collection {
-            operation_conn
-                .execute(
-                    "update defaults set collection_name=?1 where id = 1",
-                    (name,),
-                )
-                .unwrap();
+            operation_conn.execute(
+                "update defaults set collection_name=?1 where id = 1",
+                (name,),
+            )?;
             println!("Default collection set to {name}");
         }
-        return;
+        if let Some(author) = author {
+            operation_conn.execute("update defaults set author=?1 where id = 1", (author,))?;
+            println!("Author set to {author}");
+        }
+        if let Some(email) = email {
+            operation_conn.execute("update defaults set email=?1 where id = 1", (email,))?;
+            println!("Email set to {email}");
+        }
+        if let Some(graph_order) = graph_order {
+            if graph_order == "natural" {
+                operation_conn.execute("update defaults set graph_order=NULL where id = 1", ())?;
+                println!("Graphs will be ordered naturally");
+            } else {
+                // the names are stored rather than the path, so the file can move or change
+                if let GraphOrder::Custom(names) = GraphOrder::from_file(graph_order)? {
+                    operation_conn.execute(
+                        "update defaults set graph_order=?1 where id = 1",
+                        (names.join("\n"),),
+                    )?;
+                }
+                println!("Graph order set from {graph_order}");
+            }
+        }
+        if let Some(organisms) = organisms {
+            // the contents are stored rather than the path, like the graph order
+            let profiles = if organisms == "builtin" {
+                None
+            } else {
+                Some(fs::read_to_string(organisms)?)
+            };
+            let new_organisms = match &profiles {
+                Some(profiles) => Organisms::from_toml(profiles)?,
+                None => Organisms::default(),
+            };
+            // the default organism has to stay defined
+            let default_organism: Option<String> = operation_conn.query_row(
+                "select organism from defaults where id = 1",
+                (),
+                |row| row.get(0),
+            )?;
+            let new_organisms = new_organisms.with_default(default_organism.as_deref())?;
+            operation_conn.execute(
+                "update defaults set organisms=?1 where id = 1",
+                (&profiles,),
+            )?;
+            println!(
+                "Organisms set to {names}",
+                names = new_organisms.names().join(", ")
+            );
+        }
+        if let Some(organism) = organism {
+            get_organisms(&operation_conn)?.get(Some(organism))?;
+            operation_conn.execute("update defaults set organism=?1 where id = 1", (organism,))?;
+            println!("Default organism set to {organism}");
+        }
+        if let Some(profile) = profile {
+            operation_conn.execute(
+                "update defaults set tuning_profile=?1 where id = 1",
+                (profile.to_string(),),
+            )?;
+            println!("Database profile set to {profile}");
+        }
+        return Ok(());
     }
 
     if let Some(Commands::Transform { format_csv_for_gaf }) = &cli.command {
-        let csv = format_csv_for_gaf
-            .clone()
-            .expect("csv for transformation not provided.");
+        let csv = required_arg(format_csv_for_gaf, "--format-csv-for-gaf")?;
         let stdout = io::stdout();
         let mut handle = stdout.lock();
-        let mut csv_file = File::open(csv).unwrap();
+        let mut csv_file = File::open(csv)?;
         transform_csv_to_fasta(&mut csv_file, &mut handle);
-        return;
+        return Ok(());
     }
 
-    let binding = cli.db.unwrap_or_else(|| {
-        let mut stmt = operation_conn
-            .prepare("select db_name from defaults where id = 1;")
-            .unwrap();
-        let row: Option<String> = stmt.query_row((), |row| row.get(0)).unwrap();
-        row.unwrap_or_else(|| {
-            let gen_dir = get_gen_dir();
-            PathBuf::from(gen_dir)
-                .join("default.db")
-                .to_str()
-                .unwrap()
-                .to_string()
-        })
-    });
+    let binding = match cli.db {
+        Some(db) => db,
+        None => {
+            let mut stmt = operation_conn.prepare("select db_name from defaults where id = 1;")?;
+            let row: Option<String> = stmt.query_row((), |row| row.get(0))?;
+            row.unwrap_or_else(|| {
+                let gen_dir = get_gen_dir();
+                PathBuf::from(gen_dir)
+                    .join("default.db")
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+        }
+    };
     let db = binding.as_str();
-    let conn = get_connection(db);
+    let conn = get_connection_with_profile(db, get_tuning_profile(&operation_conn)?);
     let db_uuid = metadata::get_db_uuid(&conn);
 
     // initialize the selected database if needed.
@@ -408,65 +1548,109 @@ fn main() {
             fasta,
             gb,
             gfa,
+            library,
+            parts,
+            parts_from_accessions,
+            maf,
+            paf,
+            path_name,
             name,
             shallow,
             sample,
+            sequence_type,
         }) => {
-            conn.execute("BEGIN TRANSACTION", []).unwrap();
-            operation_conn.execute("BEGIN TRANSACTION", []).unwrap();
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            if fasta.is_some() {
-                match import_fasta(
-                    &fasta.clone().unwrap(),
-                    name,
-                    sample.as_deref(),
-                    *shallow,
-                    &conn,
-                    &operation_conn,
-                ) {
-                    Ok(_) => println!("Fasta imported."),
-                    Err(FastaError::OperationError(OperationError::NoChanges)) => {
-                        println!("Fasta contents already exist.")
+            in_transaction(&conn, &operation_conn, || {
+                if let (Some(paf), Some(fasta)) = (paf, fasta) {
+                    import_paf(
+                        &conn,
+                        &operation_conn,
+                        paf,
+                        fasta,
+                        name,
+                        sample.as_deref(),
+                        path_name.as_deref(),
+                    )?;
+                    println!("PAF imported.");
+                } else if let Some(fasta) = fasta {
+                    match import_fasta_with_type(
+                        fasta,
+                        name,
+                        sample.as_deref(),
+                        *shallow,
+                        sequence_type,
+                        &conn,
+                        &operation_conn,
+                    ) {
+                        Ok(_) => println!("Fasta imported."),
+                        Err(FastaError::OperationError(OperationError::NoChanges)) => {
+                            println!("Fasta contents already exist.")
+                        }
+                        Err(e) => return Err(e.into()),
                     }
-                    Err(_) => {
-                        conn.execute("ROLLBACK TRANSACTION;", []).unwrap();
-                        operation_conn.execute("ROLLBACK TRANSACTION;", []).unwrap();
-                        panic!("Import failed.");
+                } else if let Some(gfa) = gfa {
+                    import_gfa(&PathBuf::from(gfa), name, sample.as_deref(), &conn);
+                } else if let (Some(library), true) = (library, parts_from_accessions) {
+                    import_library_from_accessions(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        sample.as_deref(),
+                        &required_arg(path_name, "--path-name")?,
+                        library,
+                    )?;
+                    println!("Library imported.");
+                } else if let Some(library) = library {
+                    import_library(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        sample.as_deref(),
+                        &required_arg(path_name, "--path-name")?,
+                        &required_arg(parts, "--parts")?,
+                        library,
+                    )?;
+                    println!("Library imported.");
+                } else if let Some(maf) = maf {
+                    import_maf(
+                        &conn,
+                        &operation_conn,
+                        maf,
+                        name,
+                        sample.as_deref(),
+                        path_name.as_deref(),
+                    )?;
+                    println!("MAF imported.");
+                } else if let Some(gb) = gb {
+                    let f = File::open(gb)?;
+                    match import_genbank(
+                        &conn,
+                        &operation_conn,
+                        &f,
+                        name.deref(),
+                        sample.as_deref(),
+                        OperationInfo {
+                            file_path: gb.clone(),
+                            file_type: FileTypes::GenBank,
+                            description: "GenBank Import".to_string(),
+                        },
+                    ) {
+                        Ok(_) => println!("Genbank imported."),
+                        Err(GenBankError::OperationError(OperationError::NoChanges)) => {
+                            println!("Genbank contents already exist.")
+                        }
+                        Err(e) => return Err(e.into()),
                     }
+                } else {
+                    return Err(GenError::InvalidArgument(
+                        "Import command attempted but no recognized file format was specified."
+                            .to_string(),
+                    ));
                 }
-            } else if gfa.is_some() {
-                import_gfa(
-                    &PathBuf::from(gfa.clone().unwrap()),
-                    name,
-                    sample.as_deref(),
-                    &conn,
-                );
-            } else if let Some(gb) = gb {
-                let f = File::open(gb).unwrap();
-                let _ = import_genbank(
-                    &conn,
-                    &operation_conn,
-                    &f,
-                    name.deref(),
-                    sample.as_deref(),
-                    OperationInfo {
-                        file_path: gb.clone(),
-                        file_type: FileTypes::GenBank,
-                        description: "GenBank Import".to_string(),
-                    },
-                );
-                println!("Genbank imported.");
-            } else {
-                conn.execute("ROLLBACK TRANSACTION;", []).unwrap();
-                operation_conn.execute("ROLLBACK TRANSACTION;", []).unwrap();
-                panic!(
-                    "ERROR: Import command attempted but no recognized file format was specified"
-                );
-            }
-            conn.execute("END TRANSACTION", []).unwrap();
-            operation_conn.execute("END TRANSACTION", []).unwrap();
+                Ok(())
+            })?;
         }
         Some(Commands::Update {
             name,
@@ -484,78 +1668,141 @@ fn main() {
             end,
             coordinate_frame,
             create_missing,
+            constraints,
+            expect_replaced_length,
+            expect_removed_seq,
+            min_qual,
+            pass_only,
+            include_expr,
+            manifest,
         }) => {
-            conn.execute("BEGIN TRANSACTION", []).unwrap();
-            operation_conn.execute("BEGIN TRANSACTION", []).unwrap();
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            if let Some(library_path) = library {
-                update_with_library(
-                    &conn,
-                    &operation_conn,
-                    name,
-                    sample.clone().as_deref(),
-                    &new_sample.clone().unwrap(),
-                    &path_name.clone().unwrap(),
-                    start.unwrap(),
-                    end.unwrap(),
-                    &parts.clone().unwrap(),
-                    library_path,
-                )
-                .unwrap();
-            } else if let Some(fasta_path) = fasta {
-                // NOTE: This has to go after library because the library update also uses a fasta
-                // file
-                update_with_fasta(
-                    &conn,
-                    &operation_conn,
-                    name,
-                    sample.clone().as_deref(),
-                    &new_sample.clone().unwrap(),
-                    &region_name.clone().unwrap(),
-                    start.unwrap(),
-                    end.unwrap(),
-                    fasta_path,
-                )
-                .unwrap();
-            } else if let Some(vcf_path) = vcf {
-                match update_with_vcf(
-                    vcf_path,
-                    name,
-                    genotype.clone().unwrap_or("".to_string()),
-                    sample.clone().unwrap_or("".to_string()),
-                    &conn,
-                    &operation_conn,
-                    coordinate_frame.as_deref(),
-                ) {
-                    Ok(_) => {},
-                    Err(VcfError::OperationError(OperationError::NoChanges)) => println!("No changes made. If the VCF lacks a sample or genotype, they need to be provided via --sample and --genotype."),
-                    Err(e) => panic!("Error updating with vcf: {e}"),
-                }
-            } else if let Some(gb_path) = gb {
-                let f = File::open(gb_path).unwrap();
-                match update_with_genbank(
-                    &conn,
-                    &operation_conn,
-                    &f,
-                    name.deref(),
-                    *create_missing,
-                    OperationInfo {
-                        file_path: gb_path.clone(),
-                        file_type: FileTypes::GenBank,
-                        description: "Update from GenBank".to_string(),
-                    },
-                ) {
-                    Ok(_) => {}
-                    Err(e) => panic!("Failed to update. Error is: {e}"),
+            let coordinate_frame = coordinate_frame
+                .as_ref()
+                .map(|frame| CoordinateFrame::resolve(&operation_conn, &db_uuid, frame));
+            let parent_sample = sample_arg(sample).map(str::to_string).or_else(|| {
+                coordinate_frame
+                    .as_ref()
+                    .and_then(|frame| frame.sample_name.clone())
+            });
+            let path_name = path_name.clone().or_else(|| {
+                coordinate_frame
+                    .as_ref()
+                    .and_then(|frame| frame.path_name.clone())
+            });
+            let to_path_coordinate = |coordinate: i64| {
+                coordinate_frame
+                    .as_ref()
+                    .map_or(coordinate, |frame| frame.to_path_coordinate(coordinate))
+            };
+            in_transaction(&conn, &operation_conn, || {
+                if let Some(manifest_path) = manifest {
+                    update_with_manifest(&conn, &operation_conn, name, manifest_path)?;
+                    println!("Updated with manifest: {manifest_path}");
+                } else if let Some(library_path) = library {
+                    update_with_library(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        parent_sample.as_deref(),
+                        &required_arg(new_sample, "--new-sample")?,
+                        &required_arg(&path_name, "--path-name")?,
+                        to_path_coordinate(required_arg(start, "--start")?),
+                        to_path_coordinate(required_arg(end, "--end")?),
+                        &required_arg(parts, "--parts")?,
+                        library_path,
+                        constraints.as_deref(),
+                    )?;
+                } else if let Some(fasta_path) = fasta {
+                    // NOTE: This has to go after library because the library update also uses a fasta
+                    // file
+                    if let Some(frame) = coordinate_frame
+                        .as_ref()
+                        .filter(|frame| frame.path_name.is_some())
+                    {
+                        return Err(GenError::InvalidArgument(format!(
+                            "Frame {name} is on a path, but fasta updates are made on the current path of --region-name.",
+                            name = frame.name
+                        )));
+                    }
+                    let new_sample = required_arg(new_sample, "--new-sample")?;
+                    let region_name = required_arg(region_name, "--region-name")?;
+                    let start = to_path_coordinate(required_arg(start, "--start")?);
+                    let end = to_path_coordinate(required_arg(end, "--end")?);
+                    let preview = preview_fasta_update(
+                        &conn,
+                        name,
+                        parent_sample.as_deref(),
+                        &new_sample,
+                        &region_name,
+                        start,
+                        end,
+                        fasta_path,
+                    )?;
+                    for line in preview.describe() {
+                        println!("{line}");
+                    }
+                    preview.check(*expect_replaced_length, expect_removed_seq.as_deref())?;
+                    update_with_fasta(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        parent_sample.as_deref(),
+                        &new_sample,
+                        &region_name,
+                        start,
+                        end,
+                        fasta_path,
+                    )?;
+                } else if let Some(vcf_path) = vcf {
+                    let parent_sample = coordinate_frame
+                        .as_ref()
+                        .map(frame_sample)
+                        .transpose()?
+                        .flatten();
+                    let filter = VcfFilter {
+                        min_qual: *min_qual,
+                        pass_only: *pass_only,
+                        include_expr: include_expr.as_deref().map(str::parse).transpose()?,
+                    };
+                    match update_with_vcf_with_progress(
+                        vcf_path,
+                        name,
+                        genotype.clone().unwrap_or("".to_string()),
+                        sample.clone().unwrap_or("".to_string()),
+                        &conn,
+                        &operation_conn,
+                        parent_sample.as_deref(),
+                        &filter,
+                        &NoProgress,
+                    ) {
+                        Ok(_) => {},
+                        Err(VcfError::OperationError(OperationError::NoChanges)) => println!("No changes made. If the VCF lacks a sample or genotype, they need to be provided via --sample and --genotype."),
+                        Err(e) => return Err(e.into()),
+                    }
+                } else if let Some(gb_path) = gb {
+                    let f = File::open(gb_path)?;
+                    update_with_genbank(
+                        &conn,
+                        &operation_conn,
+                        &f,
+                        name.deref(),
+                        *create_missing,
+                        OperationInfo {
+                            file_path: gb_path.clone(),
+                            file_type: FileTypes::GenBank,
+                            description: "Update from GenBank".to_string(),
+                        },
+                    )?;
+                } else {
+                    return Err(GenError::InvalidArgument(
+                        "Unknown file type provided for update.".to_string(),
+                    ));
                 }
-            } else {
-                panic!("Unknown file type provided for update.");
-            }
-
-            conn.execute("END TRANSACTION", []).unwrap();
-            operation_conn.execute("END TRANSACTION", []).unwrap();
+                Ok(())
+            })?;
         }
         Some(Commands::UpdateGaf {
             name,
@@ -564,45 +1811,74 @@ fn main() {
             sample,
             parent_sample,
         }) => {
-            conn.execute("BEGIN TRANSACTION", []).unwrap();
-            operation_conn.execute("BEGIN TRANSACTION", []).unwrap();
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            update_with_gaf(
-                &conn,
-                &operation_conn,
-                gaf,
-                csv,
-                name,
-                Some(sample.as_ref()),
-                parent_sample.as_deref(),
-            );
-            conn.execute("END TRANSACTION", []).unwrap();
-            operation_conn.execute("END TRANSACTION", []).unwrap();
+            in_transaction(&conn, &operation_conn, || {
+                update_with_gaf(
+                    &conn,
+                    &operation_conn,
+                    gaf,
+                    csv,
+                    name,
+                    Some(sample.as_ref()),
+                    parent_sample.as_deref(),
+                );
+                Ok(())
+            })?;
         }
-        Some(Commands::Operations { branch }) => {
-            let current_op = OperationState::get_operation(&operation_conn, &db_uuid)
-                .expect("Unable to read operation.");
-            let branch_name = branch.clone().unwrap_or_else(|| {
-                let current_branch_id =
-                    OperationState::get_current_branch(&operation_conn, &db_uuid)
-                        .expect("No current branch is set.");
-                Branch::get_by_id(&operation_conn, current_branch_id)
-                    .unwrap_or_else(|| panic!("No branch with id {current_branch_id}"))
-                    .name
-            });
+        Some(Commands::Operations {
+            branch,
+            graph,
+            edit,
+            message,
+        }) => {
+            if let (Some(op_hash), Some(message)) = (edit, message) {
+                let op_hash = &Tag::resolve_operation(&operation_conn, &db_uuid, op_hash);
+                let operation = Operation::get_by_hash(&operation_conn, op_hash)
+                    .map_err(|_| GenError::NotFound(format!("Hash {op_hash} does not exist.")))?;
+                Operation::set_message(&operation_conn, &operation.hash, message)?;
+                println!("Updated message for operation {}.", operation.hash);
+                return Ok(());
+            }
+            if *graph {
+                for line in render_operation_graph(&operation_conn, &db_uuid) {
+                    println!("{line}");
+                }
+                return Ok(());
+            }
+            let current_op =
+                OperationState::get_operation(&operation_conn, &db_uuid).ok_or_else(|| {
+                    GenError::NotFound("No operations have been recorded.".to_string())
+                })?;
+            let branch_name = match branch {
+                Some(branch_name) => branch_name.clone(),
+                None => {
+                    let current_branch_id =
+                        OperationState::get_current_branch(&operation_conn, &db_uuid).ok_or_else(
+                            || GenError::NotFound("No current branch is set.".to_string()),
+                        )?;
+                    Branch::get_by_id(&operation_conn, current_branch_id)
+                        .ok_or_else(|| {
+                            GenError::NotFound(format!("No branch with id {current_branch_id}."))
+                        })?
+                        .name
+                }
+            };
             let operations = Branch::get_operations(
                 &operation_conn,
                 Branch::get_by_name(&operation_conn, &db_uuid, &branch_name)
-                    .unwrap_or_else(|| panic!("No branch named {branch_name}."))
+                    .ok_or_else(|| GenError::NotFound(format!("No branch named {branch_name}.")))?
                     .id,
             );
             let mut indicator = "";
             println!(
-                "{indicator:<3}{col1:>64}   {col2:<70}",
+                "{indicator:<3}{col1:>64}   {col2:<20}   {col3:<30}   {col4:<30}   {col5}",
                 col1 = "Id",
-                col2 = "Summary"
+                col2 = "Date",
+                col3 = "Author",
+                col4 = "Summary",
+                col5 = "Message"
             );
             for op in operations.iter() {
                 if op.hash == current_op {
@@ -610,48 +1886,104 @@ fn main() {
                 } else {
                     indicator = "";
                 }
+                let author = match (&op.author, &op.email) {
+                    (Some(author), Some(email)) => format!("{author} <{email}>"),
+                    (Some(author), None) => author.clone(),
+                    (None, Some(email)) => format!("<{email}>"),
+                    (None, None) => "".to_string(),
+                };
                 println!(
-                    "{indicator:<3}{col1:>64}   {col2:<70}",
+                    "{indicator:<3}{col1:>64}   {col2:<20}   {col3:<30}   {col4:<30}   {col5}",
                     col1 = op.hash,
-                    col2 = op.change_type
+                    col2 = op.timestamp.clone().unwrap_or_default(),
+                    col3 = author,
+                    col4 = op.change_type,
+                    col5 = op.message.clone().unwrap_or_default()
                 );
             }
         }
+        Some(Commands::Describe { hash }) => {
+            let operation = Operation::get_by_hash(&operation_conn, hash)
+                .map_err(|_| GenError::NotFound(format!("Hash {hash} does not exist.")))?;
+            let file_addition = FileAddition::get(
+                &operation_conn,
+                "select * from file_addition where id = ?1",
+                rusqlite::params![operation.change_id],
+            )?;
+            let summary = OperationSummary::get(
+                &operation_conn,
+                "select * from operation_summary where operation_hash = ?1",
+                rusqlite::params![operation.hash],
+            )?;
+            let branch_name = Branch::get_by_id(&operation_conn, operation.branch_id)
+                .map(|branch| branch.name)
+                .unwrap_or_default();
+            let author = match (&operation.author, &operation.email) {
+                (Some(author), Some(email)) => format!("{author} <{email}>"),
+                (Some(author), None) => author.clone(),
+                (None, Some(email)) => format!("<{email}>"),
+                (None, None) => "".to_string(),
+            };
+            println!("Operation {}", operation.hash);
+            println!(
+                "Parent:     {}",
+                operation.parent_hash.clone().unwrap_or_default()
+            );
+            println!("Branch:     {branch_name}");
+            println!("Author:     {author}");
+            println!(
+                "Date:       {}",
+                operation.timestamp.clone().unwrap_or_default()
+            );
+            println!("Type:       {}", operation.change_type);
+            println!(
+                "File:       {} ({:?})",
+                file_addition.file_path, file_addition.file_type
+            );
+            println!(
+                "Command:    {}",
+                operation.command_line().unwrap_or_default()
+            );
+            println!(
+                "Directory:  {}",
+                operation.working_directory.clone().unwrap_or_default()
+            );
+            println!(
+                "Version:    {}",
+                operation.gen_version.clone().unwrap_or_default()
+            );
+            if let Some(message) = &operation.message {
+                println!("\n    {message}");
+            }
+            println!("\n{}", summary.summary.trim_end());
+        }
         Some(Commands::Branch {
             create,
             delete,
             checkout,
             list,
             merge,
+            status,
             branch_name,
         }) => {
             if *create {
                 Branch::create(
                     &operation_conn,
                     &db_uuid,
-                    &branch_name
-                        .clone()
-                        .expect("Must provide a branch name to create."),
+                    &required_arg(branch_name, "A branch name")?,
                 );
             } else if *delete {
                 Branch::delete(
                     &operation_conn,
                     &db_uuid,
-                    &branch_name
-                        .clone()
-                        .expect("Must provide a branch name to delete."),
+                    &required_arg(branch_name, "A branch name")?,
                 );
             } else if *checkout {
                 operation_management::checkout(
                     &conn,
                     &operation_conn,
                     &db_uuid,
-                    &Some(
-                        branch_name
-                            .clone()
-                            .expect("Must provide a branch name to checkout.")
-                            .to_string(),
-                    ),
+                    &Some(required_arg(branch_name, "A branch name")?),
                     None,
                 );
             } else if *list {
@@ -686,11 +2018,11 @@ fn main() {
                     );
                 }
             } else if *merge {
-                let branch_name = branch_name.clone().expect("Branch name must be provided.");
+                let branch_name = required_arg(branch_name, "A branch name")?;
                 let other_branch = Branch::get_by_name(&operation_conn, &db_uuid, &branch_name)
-                    .unwrap_or_else(|| panic!("Unable to find branch {branch_name}."));
+                    .ok_or_else(|| GenError::NotFound(format!("No branch named {branch_name}.")))?;
                 let current_branch = OperationState::get_current_branch(&operation_conn, &db_uuid)
-                    .expect("Unable to find current branch.");
+                    .ok_or_else(|| GenError::NotFound("No current branch is set.".to_string()))?;
                 operation_management::merge(
                     &conn,
                     &operation_conn,
@@ -699,14 +2031,121 @@ fn main() {
                     other_branch.id,
                     None,
                 );
+            } else if *status {
+                let current_branch_id =
+                    OperationState::get_current_branch(&operation_conn, &db_uuid).ok_or_else(
+                        || GenError::NotFound("No current branch is set.".to_string()),
+                    )?;
+                let current_branch = Branch::get_by_id(&operation_conn, current_branch_id).unwrap();
+                let other_branches = match branch_name {
+                    Some(branch_name) => {
+                        vec![Branch::get_by_name(&operation_conn, &db_uuid, branch_name)
+                            .ok_or_else(|| {
+                                GenError::NotFound(format!("No branch named {branch_name}."))
+                            })?]
+                    }
+                    None => Branch::query(
+                        &operation_conn,
+                        "select * from branch where db_uuid = ?1 and id != ?2 order by name",
+                        vec![
+                            Value::from(db_uuid.to_string()),
+                            Value::from(current_branch_id),
+                        ],
+                    ),
+                };
+                for other_branch in other_branches.iter() {
+                    let divergence =
+                        Branch::divergence(&operation_conn, &current_branch, other_branch);
+                    let common_ancestor = match &divergence.common_ancestor {
+                        Some(hash) => format!("diverged after {hash}"),
+                        None => "no common operation".to_string(),
+                    };
+                    println!(
+                        "{branch} is {ahead} operations ahead of and {behind} behind {other_branch}, {common_ancestor}.",
+                        branch = divergence.branch,
+                        ahead = divergence.ahead,
+                        behind = divergence.behind,
+                        other_branch = divergence.other_branch,
+                    );
+                }
             } else {
                 println!("No options selected.");
             }
         }
         Some(Commands::Apply { hash }) => {
-            operation_management::apply(&conn, &operation_conn, hash, None);
+            let hash = Tag::resolve_operation(&operation_conn, &db_uuid, hash);
+            operation_management::apply(&conn, &operation_conn, &hash, None);
+        }
+        Some(Commands::Tag {
+            name,
+            hash,
+            sample,
+            delete,
+        }) => {
+            if let Some(name) = name {
+                if *delete {
+                    if !Tag::delete(&operation_conn, &db_uuid, name) {
+                        return Err(GenError::NotFound(format!("No tag named {name}.")));
+                    }
+                    println!("Deleted tag {name}.");
+                    return Ok(());
+                }
+                if !Tag::is_valid_name(name) {
+                    return Err(GenError::InvalidArgument(format!(
+                        "{name} can't be used as a tag, tags can't be empty, contain , or .. or start with HEAD."
+                    )));
+                }
+                if Tag::get_by_name(&operation_conn, &db_uuid, name).is_some() {
+                    return Err(GenError::InvalidArgument(format!(
+                        "A tag named {name} already exists."
+                    )));
+                }
+                if let Some(sample) = sample {
+                    Sample::get_by_name(&conn, sample)
+                        .map_err(|_| GenError::NotFound(format!("No sample named {sample}.")))?;
+                    Tag::create(&operation_conn, &db_uuid, name, None, Some(sample))?;
+                    println!("Tagged sample {sample} as {name}.");
+                } else {
+                    let operation_hash = match hash {
+                        Some(hash) => {
+                            Operation::get_by_hash(&operation_conn, hash)
+                                .map_err(|_| {
+                                    GenError::NotFound(format!("Hash {hash} does not exist."))
+                                })?
+                                .hash
+                        }
+                        None => OperationState::get_operation(&operation_conn, &db_uuid)
+                            .ok_or_else(|| {
+                                GenError::NotFound("No operations have been recorded.".to_string())
+                            })?,
+                    };
+                    Tag::create(&operation_conn, &db_uuid, name, Some(&operation_hash), None)?;
+                    println!("Tagged operation {operation_hash} as {name}.");
+                }
+            } else {
+                for tag in Tag::query_for_db(&operation_conn, &db_uuid) {
+                    match (tag.operation_hash, tag.sample_name) {
+                        (Some(operation_hash), _) => {
+                            println!("{name}\toperation\t{operation_hash}", name = tag.name)
+                        }
+                        (_, Some(sample_name)) => {
+                            println!("{name}\tsample\t{sample_name}", name = tag.name)
+                        }
+                        _ => {}
+                    }
+                }
+            }
         }
-        Some(Commands::Checkout { branch, hash }) => {
+        Some(Commands::Checkout {
+            branch,
+            collection,
+            all_collections,
+            hash,
+        }) => {
+            let sparse = !collection.is_empty() || *all_collections;
+            if sparse {
+                operation_management::sparse_checkout(&conn, &operation_conn, &db_uuid, collection);
+            }
             if let Some(name) = branch.clone() {
                 if Branch::get_by_name(&operation_conn, &db_uuid, &name).is_none() {
                     Branch::create(&operation_conn, &db_uuid, &name);
@@ -726,6 +2165,7 @@ fn main() {
                         None,
                     );
                 } else {
+                    let hash_name = Tag::resolve_operation(&operation_conn, &db_uuid, &hash_name);
                     println!("Checking out operation {hash_name}");
                     operation_management::checkout(
                         &conn,
@@ -735,79 +2175,424 @@ fn main() {
                         Some(hash_name),
                     );
                 }
-            } else {
+            } else if !sparse {
                 println!("No branch or hash to checkout provided.");
             }
         }
         Some(Commands::Reset { hash }) => {
-            operation_management::reset(&conn, &operation_conn, &db_uuid, hash);
+            let hash = Tag::resolve_operation(&operation_conn, &db_uuid, hash);
+            operation_management::reset(&conn, &operation_conn, &db_uuid, &hash);
+        }
+        Some(Commands::Revert { hash }) => {
+            let hash = Tag::resolve_operation(&operation_conn, &db_uuid, hash);
+            let operation =
+                operation_management::revert(&conn, &operation_conn, &db_uuid, &hash, None)?;
+            println!("Reverted {hash} with operation {}.", operation.hash);
+        }
+        Some(Commands::Squash { range, message }) => {
+            if !range.contains("..") || range.contains(',') {
+                return Err(GenError::InvalidArgument(format!(
+                    "{range} is not a range of operations like start..end."
+                )));
+            }
+            let current_branch_id = OperationState::get_current_branch(&operation_conn, &db_uuid)
+                .ok_or_else(|| {
+                GenError::NotFound("No current branch is checked out.".to_string())
+            })?;
+            let current_operation_hash =
+                OperationState::get_operation(&operation_conn, &db_uuid)
+                    .ok_or_else(|| GenError::NotFound("There are no operations.".to_string()))?;
+            let branch_ops = Branch::get_operations(&operation_conn, current_branch_id);
+            let range = range
+                .split("..")
+                .map(|reference| Tag::resolve_operation(&operation_conn, &db_uuid, reference))
+                .join("..");
+            let op_hashes = parse_patch_operations(&branch_ops, &current_operation_hash, &range);
+            in_transaction(&conn, &operation_conn, || {
+                let operation = operation_management::squash(
+                    &conn,
+                    &operation_conn,
+                    &db_uuid,
+                    &op_hashes,
+                    message.as_deref(),
+                )?;
+                println!(
+                    "Squashed {count} operations into {hash}.",
+                    count = op_hashes.len(),
+                    hash = operation.hash
+                );
+                Ok(())
+            })?;
+        }
+        Some(Commands::Rebase {
+            branch,
+            interactive,
+        }) => {
+            let onto_branch = Branch::get_by_name(&operation_conn, &db_uuid, branch)
+                .ok_or_else(|| GenError::NotFound(format!("No branch named {branch}.")))?;
+            let (candidates, _) =
+                operation_management::rebase_candidates(&operation_conn, &db_uuid, &onto_branch)?;
+            let operations = if *interactive {
+                let todo_path = PathBuf::from(get_gen_dir()).join("REBASE_TODO");
+                fs::write(
+                    &todo_path,
+                    operation_management::rebase_todo(&operation_conn, &candidates),
+                )?;
+                let editor = env::var("VISUAL")
+                    .or_else(|_| env::var("EDITOR"))
+                    .unwrap_or_else(|_| "vi".to_string());
+                let status = process::Command::new(&editor).arg(&todo_path).status()?;
+                let todo = fs::read_to_string(&todo_path)?;
+                fs::remove_file(&todo_path)?;
+                if !status.success() {
+                    return Err(GenError::InvalidArgument(format!(
+                        "{editor} exited with {status}, the rebase was cancelled."
+                    )));
+                }
+                operation_management::parse_rebase_todo(&todo, &candidates)?
+            } else {
+                candidates
+            };
+            in_transaction(&conn, &operation_conn, || {
+                let new_operations = operation_management::rebase(
+                    &conn,
+                    &operation_conn,
+                    &db_uuid,
+                    &onto_branch,
+                    &operations,
+                )?;
+                match new_operations.last() {
+                    Some(operation) => println!(
+                        "Rebased {count} operations onto {branch}, the branch is now at {hash}.",
+                        count = new_operations.len(),
+                        hash = operation.hash
+                    ),
+                    None => println!("No operations were replayed onto {branch}."),
+                }
+                Ok(())
+            })?;
+        }
+        Some(Commands::Fsck {}) => {
+            let report = fsck(&conn, &operation_conn, &db_uuid)?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).map_err(FsckError::from)?
+            );
+            if !report.is_ok() {
+                return Err(FsckError::Failed(report.issues.len()).into());
+            }
+        }
+        Some(Commands::Gc { dry_run }) => {
+            let garbage = find_garbage(&conn, &operation_conn, &db_uuid)?;
+            println!("{}", garbage.describe());
+            if *dry_run || garbage.is_empty() {
+                return Ok(());
+            }
+            let size_before = fs::metadata(db)?.len();
+            conn.execute("BEGIN TRANSACTION", [])?;
+            collect_garbage(&conn, &garbage)?;
+            conn.execute("END TRANSACTION", [])?;
+            collect_garbage_objects(&garbage)?;
+            conn.execute("VACUUM", [])?;
+            let size_after = fs::metadata(db)?.len();
+            println!(
+                "Reclaimed {} bytes.",
+                size_before.saturating_sub(size_after) + garbage.object_bytes as u64
+            );
+        }
+        Some(Commands::HashNodes {}) => {
+            in_transaction(&conn, &operation_conn, || {
+                match backfill_node_hashes(&conn, &operation_conn) {
+                    Ok((_, hashed_count)) => println!("Hashed {hashed_count} nodes."),
+                    Err(OperationError::NoChanges) => println!("No nodes to hash."),
+                    Err(e) => return Err(e.into()),
+                }
+                Ok(())
+            })?;
         }
         Some(Commands::Export {
             name,
             gb,
             gfa,
             sample,
+            base_sample,
             fasta,
+            haplotypes,
+            only_divergent,
+            estimate,
+            context,
+            mapping,
+            tsv,
+            msa,
+            region,
+            backbone,
+            samples,
+            sql_delta,
+            since,
+            readme,
         }) => {
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            conn.execute("BEGIN TRANSACTION", []).unwrap();
-            operation_conn.execute("BEGIN TRANSACTION", []).unwrap();
-            if let Some(gfa_path) = gfa {
-                export_gfa(&conn, name, &PathBuf::from(gfa_path), sample.clone());
-            } else if let Some(fasta_path) = fasta {
-                export_fasta(
-                    &conn,
-                    name,
-                    sample.clone().as_deref(),
-                    &PathBuf::from(fasta_path),
-                );
-            } else if let Some(gb_path) = gb {
-                export_genbank(
+            in_transaction(&conn, &operation_conn, || {
+                if *estimate {
+                    let estimate = estimate_gfa_export(&conn, name, sample_arg(sample));
+                    println!(
+                        "{segments} segments, {links} links and {paths} paths with {steps} steps",
+                        segments = estimate.segment_count,
+                        links = estimate.link_count,
+                        paths = estimate.path_count,
+                        steps = estimate.path_step_count,
+                    );
+                    println!(
+                        "About {size}, taking about {duration}",
+                        size = HumanBytes(estimate.size as u64),
+                        duration = HumanDuration(Duration::from_secs_f64(estimate.seconds())),
+                    );
+                } else if let Some(gfa_path) = gfa {
+                    let segments = if let Some(region) = region {
+                        let block_groups =
+                            Sample::get_block_groups(&conn, name, sample_arg(sample));
+                        let graph_names = block_groups
+                            .iter()
+                            .map(|block_group| block_group.name.clone())
+                            .collect::<Vec<String>>();
+                        let parsed_region = parse_region(region, &graph_names)?;
+                        let block_group = block_groups
+                            .into_iter()
+                            .find(|block_group| block_group.name == parsed_region.name)
+                            .unwrap();
+                        let path = match backbone {
+                            Some(backbone) => BlockGroup::get_paths(&conn, block_group.id)
+                                .into_iter()
+                                .find(|path| &path.name == backbone)
+                                .ok_or_else(|| {
+                                    GenError::NotFound(format!("No path named {backbone}."))
+                                })?,
+                            None => BlockGroup::try_get_current_path(&conn, block_group.id)
+                                .ok_or_else(|| {
+                                    GenError::NotFound(format!(
+                                        "Graph {} has no paths.",
+                                        block_group.name
+                                    ))
+                                })?,
+                        };
+                        let range = parsed_region.range(path.length(&conn))?;
+                        export_region_gfa(
+                            &conn,
+                            block_group.id,
+                            &path,
+                            &range,
+                            &PathBuf::from(gfa_path),
+                        )
+                    } else if *haplotypes {
+                        export_haplotype_gfa(
+                            &conn,
+                            name,
+                            sample_arg(sample),
+                            &PathBuf::from(gfa_path),
+                            &get_graph_order(&operation_conn),
+                        )
+                    } else if *only_divergent {
+                        export_divergent_gfa(
+                            &conn,
+                            name,
+                            &PathBuf::from(gfa_path),
+                            &required_arg(&sample_arg(sample).map(str::to_string), "--sample")?,
+                            *context,
+                        )?
+                    } else if *base_sample {
+                        // without a sample, every graph in the collection is exported
+                        return Err(GenError::InvalidArgument(
+                            "--base-sample is not supported for GFA exports.".to_string(),
+                        ));
+                    } else {
+                        export_gfa(
+                            &conn,
+                            name,
+                            &PathBuf::from(gfa_path),
+                            sample_arg(sample).map(str::to_string),
+                            &get_graph_order(&operation_conn),
+                        )
+                    };
+                    // kept so update-gaf can resolve the segments of alignments against this export
+                    GfaExport::record(&operation_conn, &db_uuid, name, gfa_path, &segments);
+                } else if let Some(fasta_path) = fasta {
+                    if *haplotypes {
+                        export_haplotype_fasta(
+                            &conn,
+                            name,
+                            sample_arg(sample),
+                            &PathBuf::from(fasta_path),
+                            &get_graph_order(&operation_conn),
+                        )?;
+                    } else {
+                        export_fasta(
+                            &conn,
+                            name,
+                            sample_arg(sample),
+                            &PathBuf::from(fasta_path),
+                            &get_graph_order(&operation_conn),
+                        );
+                    }
+                } else if let (Some(mapping), Some(tsv_path)) = (mapping, tsv) {
+                    let (from_sample, to_sample) = mapping.split_once(',').ok_or_else(|| {
+                        GenError::InvalidArgument(
+                            "--mapping must be given as from_sample,to_sample.".to_string(),
+                        )
+                    })?;
+                    let sample_or_reference = |sample: &str| {
+                        let sample = sample.trim();
+                        (!sample.is_empty())
+                            .then_some(sample)
+                            .and_then(Sample::from_display_name)
+                            .map(str::to_string)
+                    };
+                    export_mapping_tsv(
+                        &conn,
+                        name,
+                        sample_or_reference(from_sample).as_deref(),
+                        sample_or_reference(to_sample).as_deref(),
+                        &PathBuf::from(tsv_path),
+                    )?;
+                } else if let (Some(msa_path), Some(region)) = (msa, region) {
+                    let msa_path = PathBuf::from(msa_path);
+                    export_msa(
+                        &conn,
+                        name,
+                        region,
+                        &samples
+                            .iter()
+                            .map(|sample| Sample::from_display_name(sample).map(str::to_string))
+                            .collect::<Vec<Option<String>>>(),
+                        MsaFormat::from_path(&msa_path),
+                        &msa_path,
+                    )?;
+                } else if let Some(sql_path) = sql_delta {
+                    let branch_id = OperationState::get_current_branch(&operation_conn, &db_uuid)
+                        .ok_or_else(|| {
+                        GenError::NotFound("No current branch is checked out.".to_string())
+                    })?;
+                    let current_hash = OperationState::get_operation(&operation_conn, &db_uuid)
+                        .ok_or_else(|| {
+                            GenError::NotFound("No operations to export.".to_string())
+                        })?;
+                    let branch_operations = Branch::get_operations(&operation_conn, branch_id);
+                    let operations =
+                        operations_since(&branch_operations, &current_hash, since.as_deref())
+                            .ok_or_else(|| {
+                                GenError::NotFound(format!(
+                                    "Operation {} is not on the current branch.",
+                                    since.as_deref().unwrap_or(&current_hash)
+                                ))
+                            })?;
+                    let row_count = write_sql_delta(
+                        &conn,
+                        operations,
+                        &mut BufWriter::new(File::create(sql_path)?),
+                    )?;
+                    println!(
+                        "Exported {operation_count} operations changing {row_count} rows to {sql_path}",
+                        operation_count = operations.len()
+                    );
+                } else if let Some(gb_path) = gb {
+                    export_genbank(&conn, name, sample_arg(sample), &PathBuf::from(gb_path));
+                } else if readme.is_none() {
+                    println!("No file type specified for export.");
+                }
+                Ok(())
+            })?;
+            if let Some(readme_path) = readme {
+                let exported_files = if *estimate {
+                    vec![]
+                } else {
+                    [gfa, fasta, tsv, msa, sql_delta, gb]
+                        .into_iter()
+                        .flatten()
+                        .map(PathBuf::from)
+                        .filter(|path| path.exists())
+                        .collect::<Vec<PathBuf>>()
+                };
+                write_readme(
                     &conn,
+                    &operation_conn,
+                    &db_uuid,
                     name,
-                    sample.clone().as_deref(),
-                    &PathBuf::from(gb_path),
-                );
-            } else {
-                println!("No file type specified for export.");
+                    &exported_files,
+                    &mut BufWriter::new(File::create(readme_path)?),
+                )?;
             }
-            conn.execute("END TRANSACTION", []).unwrap();
-            operation_conn.execute("END TRANSACTION", []).unwrap();
         }
         Some(Commands::PatchCreate {
             name,
             operation,
             branch,
+            embed_sequences,
         }) => {
             let branch = if let Some(branch_name) = branch {
                 Branch::get_by_name(&operation_conn, &db_uuid, branch_name)
-                    .unwrap_or_else(|| panic!("No branch with name {branch_name} found."))
+                    .ok_or_else(|| GenError::NotFound(format!("No branch named {branch_name}.")))?
             } else {
                 let current_branch_id =
-                    OperationState::get_current_branch(&operation_conn, &db_uuid)
-                        .expect("No current branch is checked out.");
-                Branch::get_by_id(&operation_conn, current_branch_id).unwrap()
+                    OperationState::get_current_branch(&operation_conn, &db_uuid).ok_or_else(
+                        || GenError::NotFound("No current branch is checked out.".to_string()),
+                    )?;
+                Branch::get_by_id(&operation_conn, current_branch_id).ok_or_else(|| {
+                    GenError::NotFound(format!("No branch with id {current_branch_id}."))
+                })?
             };
             let branch_ops = Branch::get_operations(&operation_conn, branch.id);
-            let operations = parse_patch_operations(
-                &branch_ops,
-                &branch.current_operation_hash.unwrap(),
-                operation,
-            );
-            let mut f = File::create(format!("{name}.gz")).unwrap();
-            patch::create_patch(&operation_conn, &operations, &mut f);
+            let current_operation_hash = branch.current_operation_hash.ok_or_else(|| {
+                GenError::NotFound(format!("Branch {} has no operations.", branch.name))
+            })?;
+            // tags may stand for either end of a range
+            let operation = operation
+                .split(',')
+                .map(|part| {
+                    part.split("..")
+                        .map(|reference| {
+                            Tag::resolve_operation(&operation_conn, &db_uuid, reference)
+                        })
+                        .join("..")
+                })
+                .join(",");
+            let operations =
+                parse_patch_operations(&branch_ops, &current_operation_hash, &operation);
+            let mut f = File::create(format!("{name}.gz"))?;
+            patch::create_patch(&operation_conn, &operations, *embed_sequences, &mut f);
         }
-        Some(Commands::PatchApply { patch }) => {
-            let mut f = File::open(patch).unwrap();
-            let patches = patch::load_patches(&mut f);
-            patch::apply_patches(&conn, &operation_conn, &patches);
+        Some(Commands::PatchApply {
+            patch,
+            dry_run,
+            collection,
+            sample,
+            operation,
+        }) => {
+            let mut f = File::open(patch)?;
+            let patches = patch::load_patches(&mut f)?;
+            let operation_count = patches.len();
+            let patches =
+                patch::select_patches(patches, collection.as_deref(), sample.as_deref(), operation);
+            if patches.len() < operation_count {
+                println!(
+                    "Selected {selected} of {operation_count} operations.",
+                    selected = patches.len()
+                );
+            }
+            if *dry_run {
+                for result in patch::dry_run_patches(&conn, &operation_conn, &patches)?.iter() {
+                    for line in patch::describe_dry_run(result) {
+                        println!("{line}");
+                    }
+                }
+            } else {
+                patch::apply_patches(&conn, &operation_conn, &patches)?;
+            }
         }
         Some(Commands::PatchView { prefix, patch }) => {
             let patch_path = Path::new(patch);
-            let mut f = File::open(patch_path).unwrap();
-            let patches = patch::load_patches(&mut f);
+            let mut f = File::open(patch_path)?;
+            let patches = patch::load_patches(&mut f)?;
             let diagrams = view_patches(&patches);
             for (patch_hash, patch_diagrams) in diagrams.iter() {
                 for (bg_id, dot) in patch_diagrams.iter() {
@@ -824,9 +2609,8 @@ fn main() {
                                 .unwrap()
                         )
                     };
-                    let mut f = File::create(path).unwrap();
-                    f.write_all(dot.as_bytes())
-                        .expect("Failed to write diagram");
+                    let mut f = File::create(path)?;
+                    f.write_all(dot.as_bytes())?;
                 }
             }
         }
@@ -839,113 +2623,1172 @@ fn main() {
         Some(Commands::Defaults {
             database,
             collection,
+            author,
+            email,
+            graph_order,
+            organisms,
+            organism,
+            profile,
         }) => {}
         Some(Commands::Transform { format_csv_for_gaf }) => {}
-        Some(Commands::PropagateAnnotations {
+        Some(Commands::GbDiff { old, new, tsv }) => {}
+        Some(Commands::Fork { .. }) => {}
+        Some(Commands::Clone { .. }) => {}
+        Some(Commands::Annotate {
+            name,
+            sample,
+            frame,
+            gff,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let sample = match frame {
+                Some(frame) => frame_sample(&get_frame(&operation_conn, &db_uuid, frame)?)?,
+                None => sample_arg(sample).map(str::to_string),
+            };
+            in_transaction(&conn, &operation_conn, || {
+                match import_gff_annotations(&conn, &operation_conn, name, sample.as_deref(), gff) {
+                    Ok(_) => println!("Annotations imported."),
+                    Err(AnnotationError::OperationError(OperationError::NoChanges)) => {
+                        println!("Annotations already exist.")
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+                Ok(())
+            })?;
+        }
+        Some(Commands::Coverage { command }) => match command {
+            CoverageCommands::Import {
+                name,
+                sample,
+                track,
+                bedgraph,
+                bed,
+            } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                let (file_path, format) = match (bedgraph, bed) {
+                    (Some(bedgraph), _) => (bedgraph, CoverageFormat::BedGraph),
+                    (None, Some(bed)) => (bed, CoverageFormat::Bed),
+                    (None, None) => {
+                        return Err(GenError::InvalidArgument(
+                            "--bedgraph or --bed must be provided.".to_string(),
+                        ))
+                    }
+                };
+                let track = track.clone().unwrap_or_else(|| {
+                    Path::new(file_path)
+                        .file_stem()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                });
+                in_transaction(&conn, &operation_conn, || {
+                    match import_coverage(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        sample_arg(sample),
+                        &track,
+                        file_path,
+                        format,
+                    ) {
+                        Ok(_) => println!("Imported coverage track {track}."),
+                        Err(CoverageError::OperationError(OperationError::NoChanges)) => {
+                            println!("No coverage was imported, the file has no intervals on the sample's graphs.")
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                    Ok(())
+                })?;
+            }
+            CoverageCommands::Get {
+                name,
+                sample,
+                track,
+                region,
+            } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                let track = resolve_track(&conn, track.as_deref())?;
+                let graph_names = Sample::get_block_groups(&conn, name, sample_arg(sample))
+                    .into_iter()
+                    .map(|block_group| block_group.name)
+                    .collect::<Vec<String>>();
+                let parsed_region = parse_region(region, &graph_names)?;
+                for run in region_coverage(&conn, name, sample_arg(sample), &track, &parsed_region)?
+                {
+                    println!(
+                        "{graph}\t{start}\t{end}\t{depth}",
+                        graph = parsed_region.name,
+                        start = run.start,
+                        end = run.end,
+                        depth = run.depth,
+                    );
+                }
+            }
+        },
+        Some(Commands::TranslateCds {
             name,
             from_sample,
-            to_sample,
+            frame,
+            sample,
             gff,
-            output_gff,
+            organism,
+            require_silent,
         }) => {
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            let from_sample_name = from_sample.clone();
-
-            conn.execute("BEGIN TRANSACTION", []).unwrap();
-            operation_conn.execute("BEGIN TRANSACTION", []).unwrap();
-
-            propagate_gff(
+            let from_sample = match frame {
+                Some(frame) => frame_sample(&get_frame(&operation_conn, &db_uuid, frame)?)?,
+                None => sample_arg(from_sample).map(str::to_string),
+            };
+            let organisms = get_organisms(&operation_conn)?;
+            let translations = translate_cds(
                 &conn,
                 name,
-                from_sample_name.as_deref(),
-                to_sample,
+                from_sample.as_deref(),
+                sample,
                 gff,
-                output_gff,
-            );
+                organisms.get(organism.as_deref())?,
+            )?;
+            for translation in translations.iter() {
+                println!("{}", translation.describe());
+            }
+            let changed = translations
+                .iter()
+                .filter(|translation| !translation.is_silent())
+                .count();
+            if *require_silent && changed > 0 {
+                return Err(AnnotationError::ProteinsChanged {
+                    changed,
+                    total: translations.len(),
+                }
+                .into());
+            }
+        }
+        Some(Commands::Liftover {
+            name,
+            from_sample,
+            to_sample,
+            chain,
+            paf,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let from_sample = sample_arg(from_sample);
+            let to_sample = Sample::from_display_name(to_sample);
+            if let Some(chain_path) = chain {
+                export_chain(
+                    &conn,
+                    name,
+                    from_sample,
+                    to_sample,
+                    &PathBuf::from(chain_path),
+                )?;
+            }
+            if let Some(paf_path) = paf {
+                export_paf(
+                    &conn,
+                    name,
+                    from_sample,
+                    to_sample,
+                    &PathBuf::from(paf_path),
+                )?;
+            }
+        }
+        Some(Commands::PropagateAnnotations {
+            name,
+            from_sample,
+            frame,
+            to_sample,
+            gff,
+            output_gff,
+            bed,
+            output_bed,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let from_sample_name = match frame {
+                Some(frame) => frame_sample(&get_frame(&operation_conn, &db_uuid, frame)?)?,
+                None => sample_arg(from_sample).map(str::to_string),
+            };
 
-            conn.execute("END TRANSACTION", []).unwrap();
-            operation_conn.execute("END TRANSACTION", []).unwrap();
+            in_transaction(&conn, &operation_conn, || {
+                if let (Some(gff), Some(output_gff)) = (gff, output_gff) {
+                    propagate_gff(
+                        &conn,
+                        name,
+                        from_sample_name.as_deref(),
+                        to_sample,
+                        gff,
+                        output_gff,
+                    )?;
+                } else if let (None, Some(output_gff)) = (gff, output_gff) {
+                    export_stored_gff(&conn, name, to_sample, output_gff)?;
+                } else if let (Some(bed), Some(output_bed)) = (bed, output_bed) {
+                    propagate_bed(
+                        &conn,
+                        name,
+                        from_sample_name.as_deref(),
+                        to_sample,
+                        bed,
+                        output_bed,
+                    )?;
+                } else {
+                    return Err(GenError::InvalidArgument(
+                        "Either --output-gff or --bed and --output-bed must be provided."
+                            .to_string(),
+                    ));
+                }
+                Ok(())
+            })?;
         }
-        Some(Commands::ListSamples {}) => {
+        Some(Commands::ListSamples { base_sample }) => {
+            if *base_sample {
+                println!("{BASE_SAMPLE_NAME}");
+            }
+            let aliases = Sample::get_aliases(&conn);
             let sample_names = Sample::get_all_names(&conn);
             for sample_name in sample_names {
-                println!("{}", sample_name);
+                if !aliases.contains_key(&sample_name) {
+                    println!("{}", sample_name);
+                }
+            }
+        }
+        Some(Commands::Serve {
+            address,
+            connections,
+        }) => {
+            serve(get_connection_pool(db, *connections), address)?;
+        }
+        Some(Commands::DedupeSamples { dry_run }) => {
+            let duplicates = find_duplicate_samples(&conn);
+            if duplicates.is_empty() {
+                println!("No duplicate samples found.");
+                return Ok(());
+            }
+            for (sample_name, aliases) in duplicates.iter() {
+                println!("{sample_name}\t{aliases}", aliases = aliases.join(","));
+            }
+            if !dry_run {
+                in_transaction(&conn, &operation_conn, || {
+                    dedupe_samples(&conn, &operation_conn, &duplicates)?;
+                    Ok(())
+                })?;
             }
         }
-        Some(Commands::ListGraphs { name, sample }) => {
+        Some(Commands::ListGraphs {
+            name,
+            sample,
+            base_sample,
+        }) => {
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            let block_groups = Sample::get_block_groups(&conn, name, sample.as_deref());
+            let sample = if *base_sample {
+                None
+            } else {
+                sample_arg(sample)
+            };
+            let mut block_groups = Sample::get_block_groups(&conn, name, sample);
+            get_graph_order(&operation_conn)
+                .sort_by_name(&mut block_groups, |block_group| &block_group.name);
             for block_group in block_groups {
                 println!("{}", block_group.name);
             }
         }
+        Some(Commands::Accessions { command }) => match command {
+            AccessionCommands::Export { name, fasta } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                export_accessions(&conn, name, &PathBuf::from(fasta));
+            }
+        },
+        Some(Commands::Sequences { command }) => match command {
+            SequenceCommands::Hydrate { fasta } => {
+                conn.execute("BEGIN TRANSACTION", [])?;
+                let hashes = hydrate_sequences(&conn, fasta.as_deref())?;
+                conn.execute("END TRANSACTION", [])?;
+                println!("Hydrated {count} sequences.", count = hashes.len());
+            }
+            SequenceCommands::Dehydrate { fasta } => {
+                conn.execute("BEGIN TRANSACTION", [])?;
+                let hashes = dehydrate_sequences(&conn, &PathBuf::from(fasta))?;
+                conn.execute("END TRANSACTION", [])?;
+                println!("Dehydrated {count} sequences.", count = hashes.len());
+            }
+            SequenceCommands::Store { name, store } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                if let Some(store) = store {
+                    Collection::set_sequence_store(&conn, name, *store)?;
+                }
+                println!(
+                    "Sequence store of {name}: {store}",
+                    store = Collection::sequence_store(&conn, name)
+                );
+            }
+        },
+        Some(Commands::Edge { command }) => match command {
+            EdgeCommands::Add {
+                name,
+                sample,
+                graph,
+                source_node,
+                source_coordinate,
+                source_strand,
+                target_node,
+                target_coordinate,
+                target_strand,
+                chromosome_index,
+                phased,
+            } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                let edge = EdgeData {
+                    source_node_id: *source_node,
+                    source_coordinate: *source_coordinate,
+                    source_strand: *source_strand,
+                    target_node_id: *target_node,
+                    target_coordinate: *target_coordinate,
+                    target_strand: *target_strand,
+                };
+                in_transaction(&conn, &operation_conn, || {
+                    let operation = add_edge(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        sample_arg(sample),
+                        graph,
+                        &edge,
+                        *chromosome_index,
+                        *phased,
+                    )?;
+                    println!("Added edge to {graph} in operation {}.", operation.hash);
+                    Ok(())
+                })?;
+            }
+        },
+        Some(Commands::Paths { command }) => match command {
+            PathCommands::List {
+                name,
+                sample,
+                graph,
+            } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                let block_group = Sample::get_block_groups(&conn, name, sample_arg(sample))
+                    .into_iter()
+                    .find(|block_group| &block_group.name == graph)
+                    .ok_or_else(|| GenError::NotFound(format!("No graph named {graph}.")))?;
+                let current_path = BlockGroup::try_get_current_path(&conn, block_group.id);
+                for path in BlockGroup::get_paths(&conn, block_group.id) {
+                    let marker = if Some(&path) == current_path.as_ref() {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    println!("{marker} {}", path.name);
+                }
+            }
+            PathCommands::SetCurrent {
+                name,
+                sample,
+                graph,
+                path,
+            } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                in_transaction(&conn, &operation_conn, || {
+                    let operation = set_current_path(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        sample_arg(sample),
+                        graph,
+                        path,
+                    )?;
+                    println!(
+                        "Set the current path of {graph} to {path} in operation {}.",
+                        operation.hash
+                    );
+                    Ok(())
+                })?;
+            }
+            PathCommands::Rename {
+                name,
+                sample,
+                graph,
+                path,
+                new_name,
+            } => {
+                let name = &name
+                    .clone()
+                    .unwrap_or_else(|| get_default_collection(&operation_conn));
+                in_transaction(&conn, &operation_conn, || {
+                    let operation = rename_path(
+                        &conn,
+                        &operation_conn,
+                        name,
+                        sample_arg(sample),
+                        graph,
+                        path,
+                        new_name,
+                    )?;
+                    println!(
+                        "Renamed {path} to {new_name} in operation {}.",
+                        operation.hash
+                    );
+                    Ok(())
+                })?;
+            }
+        },
+        Some(Commands::Frames { command }) => match command {
+            FrameCommands::Add {
+                name,
+                sample,
+                path,
+                offset,
+            } => {
+                if CoordinateFrame::get_by_name(&operation_conn, &db_uuid, name).is_some() {
+                    return Err(GenError::InvalidArgument(format!(
+                        "A coordinate frame named {name} already exists."
+                    )));
+                }
+                if *offset < 0 {
+                    return Err(GenError::InvalidArgument(
+                        "--offset can't be negative.".to_string(),
+                    ));
+                }
+                let sample = sample_arg(sample);
+                if let Some(sample) = sample {
+                    Sample::get_by_name(&conn, sample)
+                        .map_err(|_| GenError::NotFound(format!("No sample named {sample}.")))?;
+                }
+                CoordinateFrame::create(
+                    &operation_conn,
+                    &db_uuid,
+                    name,
+                    sample,
+                    path.as_deref(),
+                    *offset,
+                )?;
+                println!("Added coordinate frame {name}.");
+            }
+            FrameCommands::List {} => {
+                println!("name\tsample\tpath\toffset");
+                for frame in CoordinateFrame::query_for_db(&operation_conn, &db_uuid) {
+                    println!(
+                        "{name}\t{sample}\t{path}\t{offset}",
+                        name = frame.name,
+                        sample = Sample::display_name(frame.sample_name.as_deref()),
+                        path = frame.path_name.as_deref().unwrap_or("(current)"),
+                        offset = frame.offset,
+                    );
+                }
+            }
+            FrameCommands::Remove { name } => {
+                if !CoordinateFrame::delete(&operation_conn, &db_uuid, name) {
+                    return Err(GenError::NotFound(format!(
+                        "No coordinate frame named {name}."
+                    )));
+                }
+                println!("Removed coordinate frame {name}.");
+            }
+        },
+        Some(Commands::Stats {
+            name,
+            sample,
+            by_sample,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let print_row = |label: &str, stats: &BlockGroupStats| {
+                println!(
+                    "{label:<30}   {nodes:>10}   {edges:>10}   {paths:>6}   {length:>14}   {variant_sites:>13}   {average:>15.1}",
+                    nodes = stats.node_count,
+                    edges = stats.edge_count,
+                    paths = stats.path_count,
+                    length = stats.total_length,
+                    variant_sites = stats.variant_site_count,
+                    average = stats.average_node_length(),
+                );
+            };
+            println!(
+                "{col1:<30}   {col2:>10}   {col3:>10}   {col4:>6}   {col5:>14}   {col6:>13}   {col7:>15}",
+                col1 = if *by_sample { "Sample" } else { "Graph" },
+                col2 = "Nodes",
+                col3 = "Edges",
+                col4 = "Paths",
+                col5 = "Length",
+                col6 = "Variant sites",
+                col7 = "Avg node length"
+            );
+            let graph_order = get_graph_order(&operation_conn);
+            let sample_stats = |sample_name: Option<&str>| {
+                let mut block_groups = Sample::get_block_groups(&conn, name, sample_name);
+                graph_order.sort_by_name(&mut block_groups, |block_group| &block_group.name);
+                block_groups
+                    .into_iter()
+                    .map(|block_group| {
+                        (
+                            block_group.name,
+                            BlockGroupStats::get_for_block_group(&conn, block_group.id),
+                        )
+                    })
+                    .collect::<Vec<(String, BlockGroupStats)>>()
+            };
+            if *by_sample {
+                let aliases = Sample::get_aliases(&conn);
+                let sample_names = std::iter::once(None).chain(
+                    Sample::get_all_names(&conn)
+                        .into_iter()
+                        .filter(|sample_name| !aliases.contains_key(sample_name))
+                        .map(Some),
+                );
+                for sample_name in sample_names {
+                    let stats = sample_stats(sample_name.as_deref());
+                    // samples without graphs in this collection are left out
+                    if !stats.is_empty() {
+                        print_row(
+                            Sample::display_name(sample_name.as_deref()),
+                            &BlockGroupStats::sum(
+                                &stats
+                                    .into_iter()
+                                    .map(|(_, stats)| stats)
+                                    .collect::<Vec<_>>(),
+                            ),
+                        );
+                    }
+                }
+            } else {
+                let stats = sample_stats(sample_arg(sample));
+                for (graph_name, graph_stats) in stats.iter() {
+                    print_row(graph_name, graph_stats);
+                }
+                if stats.len() > 1 {
+                    print_row(
+                        "Total",
+                        &BlockGroupStats::sum(
+                            &stats
+                                .into_iter()
+                                .map(|(_, stats)| stats)
+                                .collect::<Vec<_>>(),
+                        ),
+                    );
+                }
+            }
+        }
+        Some(Commands::Bubbles {
+            name,
+            sample,
+            graph,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let mut block_groups = Sample::get_block_groups(&conn, name, sample_arg(sample));
+            if let Some(graph) = graph {
+                block_groups.retain(|block_group| &block_group.name == graph);
+                if block_groups.is_empty() {
+                    return Err(GenError::NotFound(format!("No graph named {graph}.")));
+                }
+            }
+            get_graph_order(&operation_conn)
+                .sort_by_name(&mut block_groups, |block_group| &block_group.name);
+            println!("Graph\tStart\tEnd\tSource\tSink\tAlleles\tSamples");
+            for block_group in block_groups.iter() {
+                for site in variant_sites(&conn, block_group) {
+                    let (start, end) = site
+                        .range
+                        .map_or((".".to_string(), ".".to_string()), |range| {
+                            (range.start.to_string(), range.end.to_string())
+                        });
+                    // an empty allele is a deletion, written as - to keep it visible
+                    let alleles = site
+                        .alleles
+                        .iter()
+                        .map(|allele| {
+                            if allele.is_empty() {
+                                "-"
+                            } else {
+                                allele.as_str()
+                            }
+                        })
+                        .join(",");
+                    let samples = site
+                        .sample_alleles
+                        .iter()
+                        .map(|(sample_name, index)| {
+                            format!(
+                                "{}={}",
+                                Sample::display_name(sample_name.as_deref()),
+                                index.map_or(".".to_string(), |index| index.to_string())
+                            )
+                        })
+                        .join(",");
+                    println!(
+                        "{graph}\t{start}\t{end}\t{source}\t{sink}\t{alleles}\t{samples}",
+                        graph = block_group.name,
+                        source = site.bubble.source,
+                        sink = site.bubble.sink,
+                    );
+                }
+            }
+        }
         Some(Commands::GetSequence {
             name,
             sample,
+            frame,
             graph,
             start,
             end,
             region,
+            bed,
+            fasta,
+            stream,
         }) => {
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            let parsed_graph_name = if region.is_some() {
-                let parsed_region = region.as_ref().unwrap().parse::<Region>().unwrap();
-                parsed_region.name().to_string()
-            } else {
-                graph.clone().unwrap()
+            if let (Some(bed), Some(fasta)) = (bed, fasta) {
+                export_bed_sequences(
+                    &conn,
+                    name,
+                    sample_arg(sample),
+                    &PathBuf::from(bed),
+                    &PathBuf::from(fasta),
+                )?;
+                return Ok(());
+            }
+            let frame = frame
+                .as_ref()
+                .map(|frame| get_frame(&operation_conn, &db_uuid, frame))
+                .transpose()?;
+            let sample = match &frame {
+                Some(frame) => frame.sample_name.as_deref(),
+                None => sample_arg(sample),
             };
-            let block_groups = Sample::get_block_groups(&conn, name, sample.as_deref());
-            let formatted_sample_name = if sample.is_some() {
-                format!("sample {}", sample.clone().unwrap())
-            } else {
-                "default sample".to_string()
+            let block_groups = Sample::get_block_groups(&conn, name, sample);
+            let graph_names = block_groups
+                .iter()
+                .map(|block_group| block_group.name.clone())
+                .collect::<Vec<String>>();
+            // --start and --end are already 0-based and end-exclusive, unlike region strings.
+            let parsed_region = match region {
+                Some(region) => parse_region(region, &graph_names)?,
+                None => {
+                    let parsed_region = ParsedRegion {
+                        name: required_arg(graph, "Either --graph or --region")?,
+                        start: *start,
+                        end: *end,
+                    };
+                    parsed_region.validate_name(&graph_names)?;
+                    parsed_region
+                }
             };
             let block_group = block_groups
                 .iter()
-                .find(|bg| bg.name == parsed_graph_name)
-                .unwrap_or_else(|| {
-                    panic!("Graph {parsed_graph_name} not found for {formatted_sample_name}")
-                });
-            let path = BlockGroup::get_current_path(&conn, block_group.id);
-            let sequence = path.sequence(&conn);
-            let start_coordinate;
-            let mut end_coordinate;
-            if region.is_some() {
-                let parsed_region = region.as_ref().unwrap().parse::<Region>().unwrap();
-                let interval = parsed_region.interval();
-                start_coordinate = interval.start().unwrap().get() as i64;
-                end_coordinate = interval.end().unwrap().get() as i64;
+                .find(|bg| bg.name == parsed_region.name)
+                .unwrap();
+            let parsed_region = match &frame {
+                Some(frame) => frame.to_path_region(&parsed_region),
+                None => parsed_region,
+            };
+            let (path, range) = match frame.as_ref().and_then(|frame| frame.path_name.as_ref()) {
+                Some(path_name) => {
+                    let path = BlockGroup::get_path_by_name(&conn, block_group.id, path_name)
+                        .ok_or_else(|| {
+                            GenError::NotFound(format!(
+                                "Graph {graph} has no path named {path_name}.",
+                                graph = block_group.name
+                            ))
+                        })?;
+                    let range =
+                        BlockGroup::path_region_range(&conn, block_group, &path, &parsed_region)?;
+                    (path, range)
+                }
+                None => BlockGroup::region_range(&conn, block_group, &parsed_region)?,
+            };
+            if *stream {
+                write_region_fasta(
+                    &conn,
+                    &block_group.name,
+                    &path,
+                    &range,
+                    &mut BufWriter::new(io::stdout().lock()),
+                )?;
+            } else {
+                println!("{}", path.subsequence(&conn, &range));
+            }
+        }
+        Some(Commands::Find {
+            name,
+            query,
+            max_mismatches,
+            kmer_size,
+            window_size,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let queries = read_queries(query)?;
+            let hits = find_sequences(
+                &conn,
+                name,
+                &queries,
+                *max_mismatches,
+                *kmer_size,
+                *window_size,
+            );
+            println!("query\tsample\tgraph\tstart\tend\tstrand\tmismatches\tnodes");
+            for hit in hits.iter() {
+                println!(
+                    "{query}\t{sample}\t{graph}\t{start}\t{end}\t{strand}\t{mismatches}\t{nodes}",
+                    query = hit.query,
+                    sample = Sample::display_name(hit.sample.as_deref()),
+                    graph = hit.graph,
+                    start = hit.start,
+                    end = hit.end,
+                    strand = hit.strand,
+                    mismatches = hit.mismatches,
+                    nodes = hit.node_ids.iter().join(","),
+                );
+            }
+        }
+        Some(Commands::Validate { name, rules }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let results = validate(&conn, name, rules, &get_organisms(&operation_conn)?)?;
+            for result in results.iter() {
+                let status = if result.passed() { "PASS" } else { "FAIL" };
+                println!("{status}\t{description}", description = result.description);
+                for failure in result.failures.iter() {
+                    println!("\t{failure}");
+                }
+            }
+            let failed = results.iter().filter(|result| !result.passed()).count();
+            if failed > 0 {
+                return Err(ValidationError::Failed {
+                    failed,
+                    total: results.len(),
+                }
+                .into());
+            }
+        }
+        Some(Commands::WhichSamples {
+            node,
+            sequence_hash,
+        }) => {
+            let usages = match (node, sequence_hash) {
+                (Some(node_id), _) => NodeUsage::for_node(&conn, *node_id),
+                (None, Some(sequence_hash)) => NodeUsage::for_sequence(&conn, sequence_hash),
+                (None, None) => {
+                    return Err(GenError::InvalidArgument(
+                        "--node or --sequence-hash must be provided.".to_string(),
+                    ))
+                }
+            };
+            println!("node\tcollection\tsample\tgraph\tpath\tstart\tend\tstrand");
+            for usage in usages.iter() {
+                let graph = format!(
+                    "{node_id}\t{collection}\t{sample}\t{graph}",
+                    node_id = usage.node_id,
+                    collection = usage.collection_name,
+                    sample = Sample::display_name(usage.sample_name.as_deref()),
+                    graph = usage.block_group_name
+                );
+                // graphs with the node off their paths are listed without coordinates
+                if usage.traversals.is_empty() {
+                    println!("{graph}\t\t\t\t");
+                }
+                for traversal in usage.traversals.iter() {
+                    println!(
+                        "{graph}\t{path}\t{start}\t{end}\t{strand}",
+                        path = traversal.path_name,
+                        start = traversal.start,
+                        end = traversal.end,
+                        strand = traversal.strand
+                    );
+                }
+            }
+        }
+        Some(Commands::GetFlanks {
+            name,
+            sample,
+            region,
+            node,
+            graph,
+            flank,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let block_groups = Sample::get_block_groups(&conn, name, sample_arg(sample));
+            let graph_names = block_groups
+                .iter()
+                .map(|block_group| block_group.name.clone())
+                .collect::<Vec<String>>();
+            let (block_group_id, target, upstream_of, downstream_of) = if let Some(node_id) = node {
+                let graph = required_arg(graph, "--graph")?;
+                let block_group = block_groups
+                    .iter()
+                    .find(|bg| bg.name == graph)
+                    .ok_or_else(|| GenError::NotFound(format!("No graph named {graph}.")))?;
+                let sequence = Node::get_sequences_by_node_ids(&conn, &[*node_id])
+                    .remove(node_id)
+                    .ok_or_else(|| GenError::NotFound(format!("No node with id {node_id}.")))?;
+                (
+                    block_group.id,
+                    sequence.get_sequence(None, None),
+                    (*node_id, 0),
+                    (*node_id, sequence.length),
+                )
             } else {
-                start_coordinate = start.unwrap_or(0);
-                end_coordinate = end.unwrap_or(sequence.len() as i64);
+                let parsed_region = parse_region(&required_arg(region, "--region")?, &graph_names)?;
+                let block_group = block_groups
+                    .iter()
+                    .find(|bg| bg.name == parsed_region.name)
+                    .unwrap();
+                let path =
+                    BlockGroup::try_get_current_path(&conn, block_group.id).ok_or_else(|| {
+                        GenError::NotFound(format!("Graph {} has no paths.", block_group.name))
+                    })?;
+                let blocks = path.blocks(&conn);
+                let sequence = path.sequence(&conn);
+                let range = parsed_region.range(sequence.len() as i64)?;
+                // A position between two blocks is anchored on the block the region extends into.
+                let anchor = |position: i64, is_start: bool| {
+                    blocks
+                        .iter()
+                        .filter(|block| {
+                            block.id >= 0
+                                && block.path_start <= position
+                                && position <= block.path_end
+                        })
+                        .min_by_key(|block| {
+                            if is_start {
+                                block.path_end == position
+                            } else {
+                                block.path_start == position
+                            }
+                        })
+                        .map(|block| {
+                            if block.strand == Strand::Reverse {
+                                Err(GenError::InvalidArgument(
+                                    "Regions on the reverse strand are not supported.".to_string(),
+                                ))
+                            } else {
+                                Ok((
+                                    block.node_id,
+                                    block.sequence_start + position - block.path_start,
+                                ))
+                            }
+                        })
+                        .unwrap_or_else(|| {
+                            Err(GenError::NotFound(format!(
+                                "No sequence at position {position}."
+                            )))
+                        })
+                };
+                (
+                    block_group.id,
+                    sequence[range.start as usize..range.end as usize].to_string(),
+                    anchor(range.start, true)?,
+                    anchor(range.end, false)?,
+                )
+            };
+            let (upstream, downstream) = BlockGroup::get_flanking_sequences(
+                &conn,
+                block_group_id,
+                upstream_of,
+                downstream_of,
+                *flank,
+            );
+            println!("upstream\ttarget\tdownstream");
+            for (upstream, downstream) in upstream.iter().cartesian_product(downstream.iter()) {
+                println!("{upstream}\t{target}\t{downstream}");
             }
+        }
+        Some(Commands::Recipe {
+            name,
+            from,
+            to,
+            output,
+            flank,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let recipe = create_recipe(&conn, name, sample_arg(from), Some(to), *flank);
+            write_recipe(&recipe, output)?;
             println!(
-                "{}",
-                &sequence[start_coordinate as usize..end_coordinate as usize]
+                "Wrote {count} edits to {output}.",
+                count = recipe.edits.len()
             );
         }
+        Some(Commands::ApplyPlan {
+            name,
+            plan,
+            sample,
+            new_sample,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            in_transaction(&conn, &operation_conn, || {
+                apply_recipe(
+                    &conn,
+                    &operation_conn,
+                    name,
+                    plan,
+                    sample_arg(sample),
+                    new_sample,
+                )?;
+                println!("Recipe applied to {new_sample}.");
+                Ok(())
+            })?;
+        }
+        Some(Commands::DivergenceBed {
+            name,
+            sample,
+            reference,
+            output,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            export_divergence_bed(
+                &conn,
+                name,
+                Sample::from_display_name(sample),
+                sample_arg(reference),
+                output,
+            )?;
+        }
         Some(Commands::Diff {
             name,
             sample1,
             sample2,
             gfa,
+            vcf,
+            tsv,
+            three_way,
+            ancestor,
         }) => {
             let name = &name
                 .clone()
                 .unwrap_or_else(|| get_default_collection(&operation_conn));
-            gfa_sample_diff(
-                &conn,
-                name,
-                &PathBuf::from(gfa),
-                sample1.as_deref(),
-                sample2.as_deref(),
+            if let Some(gfa) = gfa {
+                gfa_sample_diff(
+                    &conn,
+                    name,
+                    &PathBuf::from(gfa),
+                    sample_arg(sample1),
+                    sample_arg(sample2),
+                );
+            }
+            if let Some(vcf) = vcf {
+                vcf_sample_diff(
+                    &conn,
+                    name,
+                    &PathBuf::from(vcf),
+                    sample_arg(sample1),
+                    sample_arg(sample2),
+                )?;
+            }
+            if *three_way {
+                let regions = three_way_diff(
+                    &conn,
+                    name,
+                    sample_arg(ancestor),
+                    sample_arg(sample1),
+                    sample_arg(sample2),
+                );
+                write_three_way_tsv(&regions, &PathBuf::from(tsv.as_ref().unwrap()))?;
+            } else if let Some(tsv) = tsv {
+                tsv_sample_diff(
+                    &conn,
+                    name,
+                    &PathBuf::from(tsv),
+                    sample_arg(sample1),
+                    sample_arg(sample2),
+                )?;
+            }
+        }
+        Some(Commands::MergeSamples {
+            name,
+            ancestor,
+            sample1,
+            sample2,
+            new_sample,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            in_transaction(&conn, &operation_conn, || {
+                merge_samples(
+                    &conn,
+                    &operation_conn,
+                    name,
+                    sample_arg(ancestor),
+                    sample1,
+                    sample2,
+                    new_sample,
+                )?;
+                println!("Merged {sample1} and {sample2} into {new_sample}.");
+                Ok(())
+            })?;
+        }
+        Some(Commands::Simplify {
+            name,
+            sample,
+            new_sample,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            in_transaction(&conn, &operation_conn, || {
+                simplify_sample(&conn, &operation_conn, name, sample_arg(sample), new_sample)?;
+                println!(
+                    "Simplified {} into {new_sample}.",
+                    Sample::display_name(sample_arg(sample))
+                );
+                Ok(())
+            })?;
+        }
+        Some(Commands::Compare {
+            name,
+            sample1,
+            sample2,
+            gfa,
+            subgraph,
+        }) => {
+            let name = &name
+                .clone()
+                .unwrap_or_else(|| get_default_collection(&operation_conn));
+            let first = GraphSet::for_sample(&conn, name, sample_arg(sample1));
+            let second = GraphSet::for_sample(&conn, name, sample_arg(sample2));
+            let sets = [
+                ("shared", first.intersection(&second)),
+                ("union", first.union(&second)),
+                ("first", first.difference(&second)),
+                ("second", second.difference(&first)),
+            ];
+            println!(
+                "{col1:<30}   {col2:>10}   {col3:>10}   {col4:>14}",
+                col1 = "Set",
+                col2 = "Nodes",
+                col3 = "Edges",
+                col4 = "Length"
             );
+            for (set_name, set) in sets.iter() {
+                let label = match *set_name {
+                    "first" => format!("only in {}", Sample::display_name(sample_arg(sample1))),
+                    "second" => format!("only in {}", Sample::display_name(sample_arg(sample2))),
+                    _ => set_name.to_string(),
+                };
+                println!(
+                    "{col1:<30}   {col2:>10}   {col3:>10}   {col4:>14}",
+                    col1 = label,
+                    col2 = set.node_ids.len(),
+                    col3 = set.edges.len(),
+                    col4 = set.sequence_length(&conn)
+                );
+            }
+            if let Some(gfa) = gfa {
+                let (_, set) = sets
+                    .iter()
+                    .find(|(set_name, _)| set_name == subgraph)
+                    .unwrap();
+                set.write_gfa(&conn, &PathBuf::from(gfa));
+            }
         }
     }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gen::get_connection;
+    use gen::models::annotation::Annotation;
+    use gen::test_helpers::setup_gen_dir;
+
+    fn fixture(name: &str) -> String {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures");
+        path.push(name);
+        path.to_str().unwrap().to_string()
+    }
+
+    fn run_command(args: &[&str]) -> Result<(), GenError> {
+        run(Cli::try_parse_from(["gen"].iter().chain(args)).unwrap())
+    }
+
+    #[test]
+    fn test_base_sample_name_on_update_and_annotate() {
+        setup_gen_dir();
+        let fasta = fixture("simple.fa");
+        let update = fixture("aaaaaaaa.fa");
+        let gff = fixture("simple.gff");
+        run_command(&["import", "--name", "test", "--fasta", &fasta]).unwrap();
+        run_command(&[
+            "update",
+            "--name",
+            "test",
+            "--sample",
+            BASE_SAMPLE_NAME,
+            "--new-sample",
+            "child",
+            "--region-name",
+            "m123",
+            "--start",
+            "2",
+            "--end",
+            "5",
+            "--fasta",
+            &update,
+        ])
+        .unwrap();
+        run_command(&[
+            "annotate",
+            "--name",
+            "test",
+            "--sample",
+            BASE_SAMPLE_NAME,
+            "--gff",
+            &gff,
+        ])
+        .unwrap();
+
+        let conn = get_connection(
+            PathBuf::from(get_gen_dir())
+                .join("default.db")
+                .to_str()
+                .unwrap(),
+        );
+        // (reference) selects the base sample rather than being created as a sample of its own
+        assert_eq!(Sample::get_all_names(&conn), vec!["child".to_string()]);
+        assert_eq!(
+            Sample::get_block_groups(&conn, "test", Some("child")).len(),
+            1
+        );
+        assert_eq!(Annotation::query_for_collection(&conn, "test").len(), 2);
+    }
 }
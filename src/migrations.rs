@@ -12,10 +12,7 @@ pub fn run_migrations(conn: &mut Connection) {
     conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
         .unwrap();
     conn.pragma_update(None, "foreign_keys", "ON").unwrap();
-    conn.execute("PRAGMA cache_size=50000;", []).unwrap();
-    // synchronous = NORMAL should be fine with WAL mode, and helps with performance
-    // https://developer.android.com/topic/performance/sqlite-performance-best-practices
-    conn.execute("PRAGMA synchronous = NORMAL;", []).unwrap();
+    // the cache and sync settings are left to the connection's tuning profile
 
     // 2️⃣ Update the database schema, atomically
     let r = migrations.to_latest(conn);
@@ -35,3 +32,19 @@ pub fn run_operation_migrations(conn: &mut Connection) {
     let r = migrations.to_latest(conn);
     r.unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_revert() {
+        for dir in [&MIGRATION_DIR, &OPERATIONS_MIGRATION_DIR] {
+            let migrations = Migrations::from_directory(dir).unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrations.to_latest(&mut conn).unwrap();
+            migrations.to_version(&mut conn, 1).unwrap();
+            migrations.to_latest(&mut conn).unwrap();
+        }
+    }
+}
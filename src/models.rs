@@ -1,15 +1,23 @@
 pub mod accession;
+pub mod annotation;
+pub mod attribute;
 pub mod block_group;
 pub mod block_group_edge;
+pub mod block_group_lock;
+pub mod block_group_stats;
 pub mod collection;
 pub mod edge;
 pub mod file_types;
 pub mod metadata;
 pub mod node;
+pub mod node_coverage;
+pub mod node_usage;
 pub mod operations;
 pub mod path;
 pub mod path_edge;
+pub mod path_index;
 pub mod sample;
 pub mod sequence;
+pub mod sequence_digest;
 pub mod strand;
 pub mod traits;
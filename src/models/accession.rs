@@ -1,6 +1,8 @@
 use crate::models::block_group_edge::AugmentedEdgeData;
+use crate::models::node::Node;
 use crate::models::strand::Strand;
 use crate::models::traits::*;
+use itertools::Itertools;
 use rusqlite::types::Value;
 use rusqlite::{params_from_iter, Connection, Result as SQLResult, Row};
 use serde::{Deserialize, Serialize};
@@ -125,6 +127,48 @@ impl Accession {
             }
         }
     }
+
+    pub fn query_for_collection(conn: &Connection, collection_name: &str) -> Vec<Accession> {
+        Accession::query(
+            conn,
+            "select a.* from accessions a join paths p on a.path_id = p.id join block_groups bg on p.block_group_id = bg.id where bg.collection_name = ?1 order by a.id",
+            params_from_iter(vec![Value::from(collection_name.to_string())]),
+        )
+    }
+
+    pub fn edges(&self, conn: &Connection) -> Vec<AccessionEdge> {
+        AccessionEdge::query(
+            conn,
+            "select ae.* from accession_edges ae join accession_paths ap on ae.id = ap.edge_id where ap.accession_id = ?1 order by ap.index_in_path",
+            params_from_iter(vec![Value::from(self.id)]),
+        )
+    }
+
+    pub fn sequence(&self, conn: &Connection) -> String {
+        // Accession paths begin and end with edges from/to the terminal nodes, so each consecutive
+        // pair of edges brackets one stretch of a node's sequence.
+        let edges = self.edges(conn);
+        let node_ids = edges
+            .iter()
+            .map(|edge| edge.target_node_id)
+            .collect::<Vec<i64>>();
+        let sequences_by_node_id = Node::get_sequences_by_node_ids(conn, &node_ids);
+        edges
+            .iter()
+            .tuple_windows()
+            .map(|(into, out_of)| {
+                let node_sequence = sequences_by_node_id.get(&into.target_node_id).unwrap();
+                let sequence =
+                    node_sequence.get_sequence(into.target_coordinate, out_of.source_coordinate);
+                if into.target_strand == Strand::Reverse {
+                    node_sequence.molecule().reverse_complement(&sequence)
+                } else {
+                    sequence
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
 }
 
 impl Query for Accession {
@@ -321,6 +365,7 @@ impl Query for AccessionPath {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::block_group::{BlockGroup, PathCache};
     use crate::test_helpers::{get_connection, setup_block_group};
 
     #[test]
@@ -343,4 +388,23 @@ mod tests {
             }]
         )
     }
+
+    #[test]
+    fn test_accession_sequence() {
+        let conn = &get_connection(None);
+        let (block_group_id, path) = setup_block_group(conn);
+        let mut cache = PathCache::new(conn);
+        PathCache::lookup(&mut cache, block_group_id, path.name.clone());
+        let accession = BlockGroup::add_accession(conn, &path, "spanning", 5, 25, &mut cache);
+        assert_eq!(accession.sequence(conn), "AAAAATTTTTTTTTTCCCCC");
+        let accession = BlockGroup::add_accession(conn, &path, "within", 12, 17, &mut cache);
+        assert_eq!(accession.sequence(conn), "TTTTT");
+        assert_eq!(
+            Accession::query_for_collection(conn, "test")
+                .iter()
+                .map(|accession| accession.name.clone())
+                .collect::<Vec<String>>(),
+            vec!["spanning".to_string(), "within".to_string()]
+        );
+    }
 }
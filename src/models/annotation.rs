@@ -0,0 +1,179 @@
+use crate::models::block_group::BlockGroup;
+use crate::models::path::{Annotation as PathAnnotation, Path};
+use crate::models::strand::Strand;
+use crate::models::traits::*;
+use itertools::Itertools;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A feature stored against a path. Coordinates are 0-based and end-exclusive on the path.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Annotation {
+    pub id: i64,
+    pub path_id: i64,
+    pub name: String,
+    pub feature_type: String,
+    pub source: String,
+    pub start: i64,
+    pub end: i64,
+    pub strand: String,
+    pub attributes: String,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct AnnotationData {
+    pub path_id: i64,
+    pub name: String,
+    pub feature_type: String,
+    pub source: String,
+    pub start: i64,
+    pub end: i64,
+    pub strand: String,
+    pub attributes: String,
+}
+
+impl From<&Annotation> for AnnotationData {
+    fn from(item: &Annotation) -> Self {
+        AnnotationData {
+            path_id: item.path_id,
+            name: item.name.clone(),
+            feature_type: item.feature_type.clone(),
+            source: item.source.clone(),
+            start: item.start,
+            end: item.end,
+            strand: item.strand.clone(),
+            attributes: item.attributes.clone(),
+        }
+    }
+}
+
+impl Query for Annotation {
+    type Model = Annotation;
+    fn process_row(row: &Row) -> Self::Model {
+        Annotation {
+            id: row.get(0).unwrap(),
+            path_id: row.get(1).unwrap(),
+            name: row.get(2).unwrap(),
+            feature_type: row.get(3).unwrap(),
+            source: row.get(4).unwrap(),
+            start: row.get(5).unwrap(),
+            end: row.get(6).unwrap(),
+            strand: row.get(7).unwrap(),
+            attributes: row.get(8).unwrap(),
+        }
+    }
+}
+
+impl Annotation {
+    // Annotations that are already stored are skipped, only the ids of new rows are returned.
+    pub fn bulk_create(conn: &Connection, annotations: &[AnnotationData]) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare_cached("INSERT OR IGNORE INTO annotations (path_id, name, feature_type, source, start, end, strand, attributes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);")
+            .unwrap();
+        let mut ids = vec![];
+        for annotation in annotations {
+            let inserted = stmt
+                .execute(params![
+                    annotation.path_id,
+                    annotation.name,
+                    annotation.feature_type,
+                    annotation.source,
+                    annotation.start,
+                    annotation.end,
+                    annotation.strand,
+                    annotation.attributes
+                ])
+                .unwrap();
+            if inserted > 0 {
+                ids.push(conn.last_insert_rowid());
+            }
+        }
+        ids
+    }
+
+    pub fn query_by_paths(conn: &Connection, path_ids: &[i64]) -> Vec<Annotation> {
+        Annotation::query(
+            conn,
+            "select * from annotations where path_id in rarray(?1) order by path_id, start, id",
+            params_from_iter(vec![Rc::new(
+                path_ids
+                    .iter()
+                    .map(|path_id| Value::from(*path_id))
+                    .collect::<Vec<Value>>(),
+            )]),
+        )
+    }
+
+    pub fn query_for_collection(conn: &Connection, collection_name: &str) -> Vec<Annotation> {
+        Annotation::query(
+            conn,
+            "select annotations.* from annotations join paths on paths.id = annotations.path_id join block_groups on block_groups.id = paths.block_group_id where block_groups.collection_name = ?1 order by annotations.id",
+            params![collection_name],
+        )
+    }
+
+    // Projects annotations stored on other paths onto the current path of each of the given block
+    // groups, matching paths by block group name. Annotations whose sequence is absent from the
+    // target path are dropped.
+    pub fn project_onto(
+        conn: &Connection,
+        annotations: &[Annotation],
+        block_groups: &[BlockGroup],
+    ) -> Vec<Annotation> {
+        let mut annotations_by_path_id: HashMap<i64, Vec<&Annotation>> = HashMap::new();
+        for annotation in annotations {
+            annotations_by_path_id
+                .entry(annotation.path_id)
+                .or_default()
+                .push(annotation);
+        }
+        let target_paths_by_name = block_groups
+            .iter()
+            .map(|block_group| {
+                (
+                    block_group.name.clone(),
+                    BlockGroup::get_current_path(conn, block_group.id),
+                )
+            })
+            .collect::<HashMap<String, Path>>();
+
+        let mut projected_annotations = vec![];
+        for (path_id, path_annotations) in annotations_by_path_id
+            .iter()
+            .sorted_by_key(|(path_id, _)| **path_id)
+        {
+            let source_path = Path::get(conn, *path_id);
+            let source_block_group = BlockGroup::get_by_id(conn, source_path.block_group_id);
+            let Some(target_path) = target_paths_by_name.get(&source_block_group.name) else {
+                continue;
+            };
+            let mapping_tree = source_path.get_mapping_tree(conn, target_path);
+            let sequence_length = target_path.sequence(conn).len() as i64;
+            for annotation in path_annotations {
+                if let Some(projected) = Path::propagate_annotation(
+                    PathAnnotation {
+                        name: annotation.name.clone(),
+                        start: annotation.start,
+                        end: annotation.end,
+                        strand: annotation.strand.parse().unwrap_or(Strand::Unknown),
+                    },
+                    &mapping_tree,
+                    sequence_length,
+                    source_block_group.is_circular,
+                ) {
+                    projected_annotations.push(Annotation {
+                        path_id: target_path.id,
+                        start: projected.start,
+                        end: projected.end,
+                        strand: projected.strand.to_string(),
+                        ..(*annotation).clone()
+                    });
+                }
+            }
+        }
+        projected_annotations
+    }
+}
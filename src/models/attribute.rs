@@ -0,0 +1,258 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Value, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+// The value of a node or edge attribute. Values are stored with their SQLite type, so numbers
+// compare as numbers in queries.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum AttributeValue {
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl AttributeValue {
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            AttributeValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            AttributeValue::Integer(value) => Some(*value as f64),
+            AttributeValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttributeValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Parses a GFA optional field such as RC:i:120 into its tag and value. Only integer (i), float
+    // (f) and string (Z) fields are attributes; other types and anything that isn't a field, like
+    // the * gen writes for segments without tags, are left out.
+    pub fn from_gfa_tag(field: &str) -> Option<(String, AttributeValue)> {
+        let mut parts = field.splitn(3, ':');
+        let (tag, tag_type, value) = (parts.next()?, parts.next()?, parts.next()?);
+        if !is_gfa_tag(tag) {
+            return None;
+        }
+        let value = match tag_type {
+            "i" => AttributeValue::Integer(value.parse().ok()?),
+            "f" => AttributeValue::Float(value.parse().ok()?),
+            "Z" => AttributeValue::String(value.to_string()),
+            _ => return None,
+        };
+        Some((tag.to_string(), value))
+    }
+
+    // The GFA optional field of an attribute, if its key can be a GFA tag, which is two letters or
+    // digits starting with a letter.
+    pub fn gfa_tag(&self, key: &str) -> Option<String> {
+        if !is_gfa_tag(key) {
+            return None;
+        }
+        Some(match self {
+            AttributeValue::Integer(value) => format!("{key}:i:{value}"),
+            AttributeValue::Float(value) => format!("{key}:f:{value}"),
+            AttributeValue::String(value) => format!("{key}:Z:{value}"),
+        })
+    }
+}
+
+fn is_gfa_tag(tag: &str) -> bool {
+    let bytes = tag.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1].is_ascii_alphanumeric()
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> AttributeValue {
+        AttributeValue::Integer(value)
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(value: f64) -> AttributeValue {
+        AttributeValue::Float(value)
+    }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> AttributeValue {
+        AttributeValue::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> AttributeValue {
+        AttributeValue::String(value)
+    }
+}
+
+impl ToSql for AttributeValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            AttributeValue::Integer(value) => ToSqlOutput::from(*value),
+            AttributeValue::Float(value) => ToSqlOutput::from(*value),
+            AttributeValue::String(value) => ToSqlOutput::from(value.as_str()),
+        })
+    }
+}
+
+impl FromSql for AttributeValue {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Integer(value) => Ok(AttributeValue::Integer(value)),
+            ValueRef::Real(value) => Ok(AttributeValue::Float(value)),
+            ValueRef::Text(_) => Ok(AttributeValue::String(value.as_str()?.to_string())),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+// The models attributes can be attached to, and the table of each model's attributes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum AttributeTable {
+    Node,
+    Edge,
+}
+
+impl AttributeTable {
+    fn table(self) -> &'static str {
+        match self {
+            AttributeTable::Node => "node_attributes",
+            AttributeTable::Edge => "edge_attributes",
+        }
+    }
+
+    fn id_column(self) -> &'static str {
+        match self {
+            AttributeTable::Node => "node_id",
+            AttributeTable::Edge => "edge_id",
+        }
+    }
+
+    // Sets an attribute, replacing the value it had.
+    pub(crate) fn set(self, conn: &Connection, id: i64, key: &str, value: &AttributeValue) {
+        conn.prepare_cached(&format!(
+            "insert into {table} ({id_column}, key, value) values (?1, ?2, ?3) on conflict do update set value = excluded.value;",
+            table = self.table(),
+            id_column = self.id_column(),
+        ))
+        .unwrap()
+        .execute(params![id, key, value])
+        .unwrap();
+    }
+
+    pub(crate) fn get(self, conn: &Connection, id: i64, key: &str) -> Option<AttributeValue> {
+        conn.prepare_cached(&format!(
+            "select value from {table} where {id_column} = ?1 and key = ?2;",
+            table = self.table(),
+            id_column = self.id_column(),
+        ))
+        .unwrap()
+        .query_row(params![id, key], |row| row.get(0))
+        .optional()
+        .unwrap()
+    }
+
+    pub(crate) fn remove(self, conn: &Connection, id: i64, key: &str) {
+        conn.execute(
+            &format!(
+                "delete from {table} where {id_column} = ?1 and key = ?2;",
+                table = self.table(),
+                id_column = self.id_column(),
+            ),
+            params![id, key],
+        )
+        .unwrap();
+    }
+
+    pub(crate) fn all(self, conn: &Connection, id: i64) -> BTreeMap<String, AttributeValue> {
+        self.for_ids(conn, &[id]).remove(&id).unwrap_or_default()
+    }
+
+    // The attributes of many nodes or edges at once, leaving out those without any.
+    pub(crate) fn for_ids(
+        self,
+        conn: &Connection,
+        ids: &[i64],
+    ) -> HashMap<i64, BTreeMap<String, AttributeValue>> {
+        let mut attributes: HashMap<i64, BTreeMap<String, AttributeValue>> = HashMap::new();
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "select {id_column}, key, value from {table} where {id_column} in rarray(?1);",
+                table = self.table(),
+                id_column = self.id_column(),
+            ))
+            .unwrap();
+        for chunk in ids.chunks(1000) {
+            let chunk_ids = chunk.iter().map(|id| Value::from(*id)).collect::<Vec<_>>();
+            let rows = stmt
+                .query_map(params![Rc::new(chunk_ids)], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })
+                .unwrap();
+            for row in rows {
+                let (id, key, value) = row.unwrap();
+                attributes.entry(id).or_default().insert(key, value);
+            }
+        }
+        attributes
+    }
+
+    // Deletes the attributes of nodes or edges about to be deleted.
+    pub(crate) fn delete_for_ids(
+        self,
+        conn: &Connection,
+        ids: Rc<Vec<Value>>,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            &format!(
+                "delete from {table} where {id_column} in rarray(?1);",
+                table = self.table(),
+                id_column = self.id_column(),
+            ),
+            params![ids],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gfa_tags() {
+        assert_eq!(
+            AttributeValue::from_gfa_tag("RC:i:120"),
+            Some(("RC".to_string(), AttributeValue::Integer(120)))
+        );
+        assert_eq!(
+            AttributeValue::from_gfa_tag("dp:f:0.5"),
+            Some(("dp".to_string(), AttributeValue::Float(0.5)))
+        );
+        assert_eq!(
+            AttributeValue::from_gfa_tag("SN:Z:chr1:extra"),
+            Some(("SN".to_string(), AttributeValue::from("chr1:extra")))
+        );
+        for field in ["*", "0M", "RC:i:many", "XY:B:c,1,2", "ABC:i:1", "1A:i:1"] {
+            assert_eq!(AttributeValue::from_gfa_tag(field), None);
+        }
+
+        assert_eq!(
+            AttributeValue::Float(0.5).gfa_tag("dp"),
+            Some("dp:f:0.5".to_string())
+        );
+        assert_eq!(AttributeValue::from("x").gfa_tag("vcf_file"), None);
+    }
+}
@@ -2,9 +2,12 @@ use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 use intervaltree::IntervalTree;
+use itertools::Itertools;
 use petgraph::graphmap::DiGraphMap;
 use petgraph::Direction;
-use rusqlite::{params, params_from_iter, types::Value as SQLValue, Connection, Row};
+use rusqlite::{
+    params, params_from_iter, types::Value as SQLValue, Connection, Result as SQLResult, Row,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::graph::{
@@ -13,11 +16,14 @@ use crate::graph::{
 use crate::models::accession::{Accession, AccessionEdge, AccessionEdgeData, AccessionPath};
 use crate::models::block_group_edge::{AugmentedEdgeData, BlockGroupEdge, BlockGroupEdgeData};
 use crate::models::edge::{Edge, EdgeData, GroupBlock};
-use crate::models::node::{PATH_END_NODE_ID, PATH_START_NODE_ID};
+use crate::models::node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID};
 use crate::models::path::{Path, PathBlock, PathData};
 use crate::models::path_edge::PathEdge;
+use crate::models::path_index::PathIndex;
+use crate::models::sequence::Molecule;
 use crate::models::strand::Strand;
 use crate::models::traits::*;
+use crate::range::{Range, Region, RegionError};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct BlockGroup {
@@ -25,6 +31,9 @@ pub struct BlockGroup {
     pub collection_name: String,
     pub sample_name: Option<String>,
     pub name: String,
+    // dependency files written before topology was tracked don't have this field
+    #[serde(default)]
+    pub is_circular: bool,
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -53,7 +62,7 @@ pub struct PathCache<'a> {
 }
 
 impl PathCache<'_> {
-    pub fn new(conn: &Connection) -> PathCache {
+    pub fn new(conn: &Connection) -> PathCache<'_> {
         PathCache {
             cache: HashMap::<PathData, Path>::new(),
             intervaltree_cache: HashMap::<Path, IntervalTree<i64, NodeIntervalBlock>>::new(),
@@ -109,33 +118,40 @@ impl BlockGroup {
         sample_name: Option<&str>,
         name: &str,
     ) -> BlockGroup {
-        let query = "INSERT INTO block_groups (collection_name, sample_name, name) VALUES (?1, ?2, ?3) RETURNING *";
+        BlockGroup::create_with_topology(conn, collection_name, sample_name, name, false)
+    }
+
+    // Creates a block group that is either linear or circular. The topology is only set when the
+    // block group is first created, an existing block group is returned as is.
+    pub fn create_with_topology(
+        conn: &Connection,
+        collection_name: &str,
+        sample_name: Option<&str>,
+        name: &str,
+        is_circular: bool,
+    ) -> BlockGroup {
+        let query = "INSERT INTO block_groups (collection_name, sample_name, name, is_circular) VALUES (?1, ?2, ?3, ?4) RETURNING *";
         let mut stmt = conn.prepare(query).unwrap();
-        match stmt.query_row((collection_name, sample_name, name), |row| {
-            Ok(BlockGroup {
-                id: row.get(0)?,
-                collection_name: row.get(1)?,
-                sample_name: row.get(2)?,
-                name: row.get(3)?,
-            })
+        match stmt.query_row((collection_name, sample_name, name, is_circular), |row| {
+            Ok(BlockGroup::process_row(row))
         }) {
             Ok(res) => res,
             Err(rusqlite::Error::SqliteFailure(err, _details)) => {
                 if err.code == rusqlite::ErrorCode::ConstraintViolation {
-                    let bg_id = match sample_name {
+                    let (bg_id, is_circular) = match sample_name {
                         Some(v) => {conn
                             .query_row(
-                                "select id from block_groups where collection_name = ?1 and sample_name = ?2 and name = ?3",
+                                "select id, is_circular from block_groups where collection_name = ?1 and sample_name = ?2 and name = ?3",
                                 (collection_name, v, name),
-                                |row| row.get(0),
+                                |row| Ok((row.get(0)?, row.get(1)?)),
                             )
                             .unwrap()}
                         None => {
                             conn
                             .query_row(
-                                "select id from block_groups where collection_name = ?1 and sample_name is null and name = ?2",
+                                "select id, is_circular from block_groups where collection_name = ?1 and sample_name is null and name = ?2",
                                 (collection_name, name),
-                                |row| row.get(0),
+                                |row| Ok((row.get(0)?, row.get(1)?)),
                             )
                             .unwrap()
                         }
@@ -145,6 +161,7 @@ impl BlockGroup {
                         collection_name: collection_name.to_string(),
                         sample_name: sample_name.map(|s| s.to_string()),
                         name: name.to_string(),
+                        is_circular,
                     }
                 } else {
                     panic!("something bad happened querying the database")
@@ -160,12 +177,7 @@ impl BlockGroup {
         let query = "SELECT * FROM block_groups WHERE id = ?1";
         let mut stmt = conn.prepare(query).unwrap();
         match stmt.query_row(params_from_iter(vec![SQLValue::from(id)]), |row| {
-            Ok(BlockGroup {
-                id: row.get(0)?,
-                collection_name: row.get(1)?,
-                sample_name: row.get(2)?,
-                name: row.get(3)?,
-            })
+            Ok(BlockGroup::process_row(row))
         }) {
             Ok(res) => res,
             Err(rusqlite::Error::QueryReturnedNoRows) => panic!("No block group with id {}", id),
@@ -209,6 +221,15 @@ impl BlockGroup {
             let new_path = Path::create(conn, &path.name, target_block_group_id, &edge_ids);
             path_map.insert(path.id, new_path.id);
         }
+        if let Some(current_path) = BlockGroup::try_get_current_path(conn, source_block_group_id) {
+            if Some(&current_path) != existing_paths.last() {
+                BlockGroup::set_current_path(
+                    conn,
+                    target_block_group_id,
+                    path_map[&current_path.id],
+                );
+            }
+        }
 
         for accession in Accession::query(
             conn,
@@ -296,7 +317,13 @@ impl BlockGroup {
             );
             return Err(Box::leak(error_string.into_boxed_str()));
         }
-        let new_bg_id = BlockGroup::create(conn, collection_name, Some(sample_name), group_name);
+        let new_bg_id = BlockGroup::create_with_topology(
+            conn,
+            collection_name,
+            Some(sample_name),
+            group_name,
+            BlockGroup::get_by_id(conn, bg_id).is_circular,
+        );
 
         // clone parent blocks/edges/path
         BlockGroup::clone(conn, bg_id, new_bg_id.id);
@@ -450,6 +477,250 @@ impl BlockGroup {
         sequences
     }
 
+    /*
+       The sequence of each haplotype of the block group, in order of chromosome index. Haplotype i
+       follows the phased edges of chromosome index i, the newest where several leave a block, and
+       the current path everywhere else, so unphased variants are in none of the haplotypes. Blocks
+       entered on the reverse strand, such as those of inversions, are reverse complemented. Block
+       groups without phased edges have a single haplotype, their current path.
+    */
+    pub fn haplotype_sequences(conn: &Connection, block_group_id: i64) -> Vec<String> {
+        let (walks, blocks) = BlockGroup::haplotype_walks_with_blocks(conn, block_group_id);
+        let blocks_by_id = blocks
+            .into_iter()
+            .map(|block| (block.id, block))
+            .collect::<HashMap<i64, GroupBlock>>();
+        // blocks walked in reverse are read from the other strand of their molecule
+        let reverse_node_ids = walks
+            .iter()
+            .flatten()
+            .filter(|(node, strand)| *strand == Strand::Reverse && !Node::is_terminal(node.node_id))
+            .map(|(node, _)| node.node_id)
+            .unique()
+            .collect::<Vec<i64>>();
+        let molecules_by_node_id = Node::get_sequences_by_node_ids(conn, &reverse_node_ids)
+            .into_iter()
+            .map(|(node_id, sequence)| (node_id, sequence.molecule()))
+            .collect::<HashMap<i64, Molecule>>();
+        walks
+            .into_iter()
+            .map(|walk| {
+                walk.iter()
+                    .filter(|(node, _)| !Node::is_terminal(node.node_id))
+                    .map(|(node, strand)| {
+                        let block_sequence = blocks_by_id[&node.block_id].sequence();
+                        if *strand == Strand::Reverse {
+                            molecules_by_node_id[&node.node_id].reverse_complement(&block_sequence)
+                        } else {
+                            block_sequence
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    // The blocks each haplotype of the block group walks through from the path start node, with the
+    // strand each is entered on, following edges as haplotype_sequences does.
+    pub fn haplotype_walks(
+        conn: &Connection,
+        block_group_id: i64,
+    ) -> Vec<Vec<(GraphNode, Strand)>> {
+        BlockGroup::haplotype_walks_with_blocks(conn, block_group_id).0
+    }
+
+    fn haplotype_walks_with_blocks(
+        conn: &Connection,
+        block_group_id: i64,
+    ) -> (Vec<Vec<(GraphNode, Strand)>>, Vec<GroupBlock>) {
+        let mut edges = BlockGroupEdge::edges_for_block_group(conn, block_group_id);
+        let blocks = Edge::blocks_from_edges(conn, &edges);
+        // read from the table, as loaded edges keep one chromosome index of those an edge has, such
+        // as the edges of homozygous variants
+        let phased_edges = BlockGroupEdge::query(
+            conn,
+            "select * from block_group_edges where block_group_id = ?1 and phased = 1;",
+            params![block_group_id],
+        )
+        .into_iter()
+        .map(|block_group_edge| (block_group_edge.edge_id, block_group_edge.chromosome_index))
+        .collect::<HashSet<(i64, i64)>>();
+        let haplotype_count = phased_edges
+            .iter()
+            .map(|(_, chromosome_index)| chromosome_index + 1)
+            .max()
+            .unwrap_or(1);
+        edges.extend(Edge::boundary_edges_from_sequences(&blocks));
+        let (graph, _) = Edge::build_graph(&edges, &blocks);
+        let path_edge_ids = BlockGroup::try_get_current_path(conn, block_group_id)
+            .map(|path| {
+                PathEdge::edges_for_path(conn, path.id)
+                    .iter()
+                    .map(|edge| edge.id)
+                    .collect::<HashSet<i64>>()
+            })
+            .unwrap_or_default();
+        let Some(start_node) = graph
+            .nodes()
+            .find(|node| node.node_id == PATH_START_NODE_ID)
+        else {
+            return (vec![], blocks);
+        };
+
+        let walks = (0..haplotype_count)
+            .map(|chromosome_index| {
+                let mut walk = vec![];
+                let mut visited = HashSet::new();
+                let mut node = start_node;
+                let mut strand = Strand::Forward;
+                // a walk that comes back to a block has gone around a cycle, and ends there
+                while visited.insert(node) {
+                    walk.push((node, strand));
+                    // boundary edges, which join the blocks of a node, have no id
+                    let next_edge = graph
+                        .edges(node)
+                        .filter(|(_, _, edge)| {
+                            phased_edges.contains(&(edge.edge_id, chromosome_index))
+                        })
+                        .max_by_key(|(_, _, edge)| edge.edge_id)
+                        .or_else(|| {
+                            graph
+                                .edges(node)
+                                .find(|(_, _, edge)| path_edge_ids.contains(&edge.edge_id))
+                        })
+                        .or_else(|| graph.edges(node).find(|(_, _, edge)| edge.edge_id == -1));
+                    match next_edge {
+                        Some((_, target, edge)) => {
+                            if edge.edge_id != -1 {
+                                strand = edge.target_strand;
+                            }
+                            node = target;
+                        }
+                        None => break,
+                    }
+                }
+                walk
+            })
+            .collect();
+        (walks, blocks)
+    }
+
+    // Returns the sequences of the given length that precede one position of the graph and follow
+    // another, over every walk through the graph. Positions are given as a node and a coordinate on
+    // it, and a flank is shorter than the requested length where a walk reaches the end of the
+    // graph first. Like get_all_sequences, nodes are read on the forward strand.
+    pub fn get_flanking_sequences(
+        conn: &Connection,
+        block_group_id: i64,
+        upstream_of: (i64, i64),
+        downstream_of: (i64, i64),
+        length: i64,
+    ) -> (Vec<String>, Vec<String>) {
+        let mut edges = BlockGroupEdge::edges_for_block_group(conn, block_group_id);
+        let blocks = Edge::blocks_from_edges(conn, &edges);
+        edges.extend(Edge::boundary_edges_from_sequences(&blocks));
+        let (graph, _) = Edge::build_graph(&edges, &blocks);
+        let sequences_by_block_id = blocks
+            .iter()
+            .map(|block| (block.id, block.sequence()))
+            .collect::<HashMap<i64, String>>();
+
+        let (node_id, coordinate) = upstream_of;
+        let mut upstream = HashSet::new();
+        // The block that starts at the position, if there is one, has every edge into it.
+        if let Some(node) = graph
+            .nodes()
+            .filter(|node| {
+                node.node_id == node_id
+                    && node.sequence_start <= coordinate
+                    && coordinate <= node.sequence_end
+            })
+            .min_by_key(|node| node.sequence_end == coordinate)
+        {
+            let sequence = &sequences_by_block_id[&node.block_id];
+            let prefix = &sequence[..(coordinate - node.sequence_start) as usize];
+            let flank_start = prefix.len().saturating_sub(length as usize);
+            for flank in Self::walk_flanks(
+                &graph,
+                &sequences_by_block_id,
+                node,
+                length - (prefix.len() - flank_start) as i64,
+                Direction::Incoming,
+            ) {
+                upstream.insert(format!("{flank}{}", &prefix[flank_start..]));
+            }
+        }
+
+        let (node_id, coordinate) = downstream_of;
+        let mut downstream = HashSet::new();
+        // The block that ends at the position, if there is one, has every edge out of it.
+        if let Some(node) = graph
+            .nodes()
+            .filter(|node| {
+                node.node_id == node_id
+                    && node.sequence_start <= coordinate
+                    && coordinate <= node.sequence_end
+            })
+            .min_by_key(|node| node.sequence_start == coordinate)
+        {
+            let sequence = &sequences_by_block_id[&node.block_id];
+            let suffix = &sequence[(coordinate - node.sequence_start) as usize..];
+            let flank_end = suffix.len().min(length as usize);
+            for flank in Self::walk_flanks(
+                &graph,
+                &sequences_by_block_id,
+                node,
+                length - flank_end as i64,
+                Direction::Outgoing,
+            ) {
+                downstream.insert(format!("{}{flank}", &suffix[..flank_end]));
+            }
+        }
+
+        (
+            upstream.into_iter().sorted().collect(),
+            downstream.into_iter().sorted().collect(),
+        )
+    }
+
+    // The sequences of up to the given length that can be read from the neighbors of a block in
+    // one direction, closest to the block.
+    fn walk_flanks(
+        graph: &DiGraphMap<GraphNode, GraphEdge>,
+        sequences_by_block_id: &HashMap<i64, String>,
+        node: GraphNode,
+        length: i64,
+        direction: Direction,
+    ) -> HashSet<String> {
+        let mut flanks = HashSet::new();
+        if length <= 0 {
+            flanks.insert("".to_string());
+            return flanks;
+        }
+        for neighbor in graph.neighbors_directed(node, direction) {
+            if Node::is_terminal(neighbor.node_id) {
+                flanks.insert("".to_string());
+                continue;
+            }
+            let sequence = &sequences_by_block_id[&neighbor.block_id];
+            let taken = sequence.len().min(length as usize);
+            let remaining = length - taken as i64;
+            for flank in
+                Self::walk_flanks(graph, sequences_by_block_id, neighbor, remaining, direction)
+            {
+                if direction == Direction::Incoming {
+                    flanks.insert(format!("{flank}{}", &sequence[sequence.len() - taken..]));
+                } else {
+                    flanks.insert(format!("{}{flank}", &sequence[..taken]));
+                }
+            }
+        }
+        if flanks.is_empty() {
+            flanks.insert("".to_string());
+        }
+        flanks
+    }
+
     pub fn add_accession(
         conn: &Connection,
         path: &Path,
@@ -528,6 +799,91 @@ impl BlockGroup {
         accession
     }
 
+    // Adds an accession reading several ranges of a path one after another, such as the exons of
+    // a spliced feature. Each range is given with the strand it is read on, so ranges on the reverse
+    // strand are read from their end, and the accession jumps from the end of one range to the start
+    // of the next.
+    pub fn add_joined_accession(
+        conn: &Connection,
+        path: &Path,
+        name: &str,
+        ranges: &[(Range, Strand)],
+    ) -> SQLResult<Accession> {
+        // the stretches of node sequence the accession reads, in order
+        let mut pieces = vec![];
+        for (range, strand) in ranges {
+            let mut range_pieces =
+                PathIndex::blocks_in_range(conn, path.id, range.start, range.end)
+                    .into_iter()
+                    .map(|block| {
+                        let start = range.start.max(block.start) - block.start;
+                        let end = range.end.min(block.end) - block.start;
+                        if block.strand == Strand::Reverse {
+                            (
+                                block.node_id,
+                                block.sequence_end - end,
+                                block.sequence_end - start,
+                                strand.flipped(),
+                            )
+                        } else {
+                            (
+                                block.node_id,
+                                block.sequence_start + start,
+                                block.sequence_start + end,
+                                *strand,
+                            )
+                        }
+                    })
+                    .collect::<Vec<_>>();
+            if *strand == Strand::Reverse {
+                range_pieces.reverse();
+            }
+            pieces.extend(range_pieces);
+        }
+        assert!(
+            !pieces.is_empty(),
+            "Accession ranges are outside of the path."
+        );
+        let (first, last) = (pieces[0], pieces[pieces.len() - 1]);
+
+        let mut edges = vec![AccessionEdgeData {
+            source_node_id: PATH_START_NODE_ID,
+            source_coordinate: -1,
+            source_strand: Strand::Forward,
+            target_node_id: first.0,
+            target_coordinate: first.1,
+            target_strand: first.3,
+            chromosome_index: 0,
+        }];
+        for (piece, next_piece) in pieces.iter().tuple_windows() {
+            edges.push(AccessionEdgeData {
+                source_node_id: piece.0,
+                source_coordinate: piece.2,
+                source_strand: piece.3,
+                target_node_id: next_piece.0,
+                target_coordinate: next_piece.1,
+                target_strand: next_piece.3,
+                chromosome_index: 0,
+            });
+        }
+        edges.push(AccessionEdgeData {
+            source_node_id: last.0,
+            source_coordinate: last.2,
+            source_strand: last.3,
+            target_node_id: PATH_END_NODE_ID,
+            target_coordinate: -1,
+            target_strand: Strand::Forward,
+            chromosome_index: 0,
+        });
+        let accession = Accession::create(conn, name, path.id, None)?;
+        AccessionPath::create(
+            conn,
+            accession.id,
+            &AccessionEdge::bulk_create(conn, &edges),
+        );
+        Ok(accession)
+    }
+
     pub fn insert_changes(
         conn: &Connection,
         changes: &Vec<PathChange>,
@@ -711,7 +1067,7 @@ impl BlockGroup {
                 source_strand: Strand::Forward,
                 target_node_id: change.block.node_id,
                 target_coordinate: change.block.sequence_start,
-                target_strand: Strand::Forward,
+                target_strand: change.block.strand,
             };
             let new_augmented_start_edge = AugmentedEdgeData {
                 edge_data: new_start_edge,
@@ -721,7 +1077,7 @@ impl BlockGroup {
             let new_end_edge = EdgeData {
                 source_node_id: change.block.node_id,
                 source_coordinate: change.block.sequence_end,
-                source_strand: Strand::Forward,
+                source_strand: change.block.strand,
                 target_node_id: end_block.node_id,
                 target_coordinate: change.end - end_block.start + end_block.sequence_start,
                 target_strand: Strand::Forward,
@@ -750,12 +1106,159 @@ impl BlockGroup {
     }
 
     pub fn get_current_path(conn: &Connection, block_group_id: i64) -> Path {
-        let paths = Path::query(
+        BlockGroup::try_get_current_path(conn, block_group_id)
+            .unwrap_or_else(|| panic!("Block group {block_group_id} has no path"))
+    }
+
+    // The newest path of the block group with the given name.
+    pub fn get_path_by_name(conn: &Connection, block_group_id: i64, name: &str) -> Option<Path> {
+        Path::query(
+            conn,
+            "SELECT * FROM paths WHERE block_group_id = ?1 AND name = ?2 ORDER BY id DESC LIMIT 1",
+            rusqlite::params!(
+                SQLValue::from(block_group_id),
+                SQLValue::from(name.to_string())
+            ),
+        )
+        .into_iter()
+        .next()
+    }
+
+    // The newest path of the block group, unless another path was chosen as current since it was
+    // added.
+    pub fn try_get_current_path(conn: &Connection, block_group_id: i64) -> Option<Path> {
+        let newest_path = Path::query(
             conn,
-            "SELECT * FROM paths WHERE block_group_id = ?1 ORDER BY id DESC",
+            "SELECT * FROM paths WHERE block_group_id = ?1 ORDER BY id DESC LIMIT 1",
             rusqlite::params!(SQLValue::from(block_group_id)),
+        )
+        .into_iter()
+        .next()?;
+        let chosen_path = Path::query(
+            conn,
+            "SELECT p.* FROM current_path_choices c JOIN paths p ON p.id = c.path_id WHERE c.block_group_id = ?1 AND c.newest_path_id = ?2 ORDER BY c.id DESC LIMIT 1",
+            rusqlite::params!(
+                SQLValue::from(block_group_id),
+                SQLValue::from(newest_path.id)
+            ),
         );
-        paths[0].clone()
+        Some(chosen_path.into_iter().next().unwrap_or(newest_path))
+    }
+
+    // The paths of a block group by name and edges, which is what a clone of it copies.
+    pub fn path_signatures(conn: &Connection, block_group_id: i64) -> HashSet<(String, Vec<i64>)> {
+        Path::query(
+            conn,
+            "select * from paths where block_group_id = ?1",
+            params![block_group_id],
+        )
+        .into_iter()
+        .map(|path| {
+            let edge_ids = PathEdge::edges_for_path(conn, path.id)
+                .into_iter()
+                .map(|edge| edge.id)
+                .collect();
+            (path.name, edge_ids)
+        })
+        .collect()
+    }
+
+    /*
+       Samples don't record which sample they were made from, but a sample's graphs start as copies
+       of its parent's graphs, paths included. The parent of a graph is taken to be the candidate of
+       the same name made before it that shares the most paths with it, preferring the earliest such
+       graph when several tie, as siblings made from the same parent share the parent's paths too.
+       The signatures of the block group and every candidate are looked up in `signatures`.
+    */
+    pub fn infer_parent<'a>(
+        &self,
+        candidates: &'a [BlockGroup],
+        signatures: &HashMap<i64, HashSet<(String, Vec<i64>)>>,
+    ) -> Option<&'a BlockGroup> {
+        candidates
+            .iter()
+            .filter(|candidate| candidate.name == self.name && candidate.id < self.id)
+            .map(|candidate| {
+                let shared = signatures[&candidate.id]
+                    .intersection(&signatures[&self.id])
+                    .count();
+                (shared, candidate)
+            })
+            .filter(|(shared, _)| *shared > 0)
+            .max_by(|(shared1, candidate1), (shared2, candidate2)| {
+                shared1.cmp(shared2).then(candidate2.id.cmp(&candidate1.id))
+            })
+            .map(|(_, candidate)| candidate)
+    }
+
+    pub fn get_paths(conn: &Connection, block_group_id: i64) -> Vec<Path> {
+        Path::query(
+            conn,
+            "SELECT * FROM paths WHERE block_group_id = ?1 ORDER BY id ASC",
+            rusqlite::params!(SQLValue::from(block_group_id)),
+        )
+    }
+
+    // Makes a path of the block group its current path until a newer path is added to it.
+    pub fn set_current_path(conn: &Connection, block_group_id: i64, path_id: i64) {
+        conn.execute(
+            "INSERT INTO current_path_choices (block_group_id, path_id, newest_path_id) SELECT ?1, ?2, max(id) FROM paths WHERE block_group_id = ?1",
+            params![block_group_id, path_id],
+        )
+        .unwrap();
+    }
+
+    // Records a choice of current path made while another path was the newest one, as changesets
+    // carry it.
+    pub fn add_current_path_choice(
+        conn: &Connection,
+        block_group_id: i64,
+        path_id: i64,
+        newest_path_id: i64,
+    ) {
+        conn.execute(
+            "INSERT INTO current_path_choices (block_group_id, path_id, newest_path_id) VALUES (?1, ?2, ?3)",
+            params![block_group_id, path_id, newest_path_id],
+        )
+        .unwrap();
+    }
+
+    // The current path of the block group and the range of it a region covers. On circular graphs
+    // the region may start after its end to read across the origin.
+    pub fn region_range(
+        conn: &Connection,
+        block_group: &BlockGroup,
+        region: &Region,
+    ) -> Result<(Path, Range), RegionError> {
+        let path = BlockGroup::try_get_current_path(conn, block_group.id)
+            .ok_or_else(|| RegionError::NoPath(block_group.name.clone()))?;
+        let range = BlockGroup::path_region_range(conn, block_group, &path, region)?;
+        Ok((path, range))
+    }
+
+    // The range of a region on one of the block group's paths, which may not be the current one.
+    pub fn path_region_range(
+        conn: &Connection,
+        block_group: &BlockGroup,
+        path: &Path,
+        region: &Region,
+    ) -> Result<Range, RegionError> {
+        let length = path.length(conn);
+        if block_group.is_circular {
+            region.circular_range(length)
+        } else {
+            region.range(length)
+        }
+    }
+
+    // The sequence of a region of the block group's current path.
+    pub fn region_sequence(
+        conn: &Connection,
+        block_group: &BlockGroup,
+        region: &Region,
+    ) -> Result<String, RegionError> {
+        let (path, range) = BlockGroup::region_range(conn, block_group, region)?;
+        Ok(path.subsequence(conn, &range))
     }
 }
 
@@ -767,6 +1270,7 @@ impl Query for BlockGroup {
             collection_name: row.get(1).unwrap(),
             sample_name: row.get(2).unwrap(),
             name: row.get(3).unwrap(),
+            is_circular: row.get(4).unwrap(),
         }
     }
 }
@@ -792,6 +1296,41 @@ mod tests {
         assert_ne!(bg1.id, bg2.id);
     }
 
+    #[test]
+    fn test_region_sequence() {
+        let conn = &get_connection(None);
+        let (block_group_id, _path) = setup_block_group(conn);
+        let block_group = BlockGroup::get_by_id(conn, block_group_id);
+        let region = |start, end| Region {
+            name: block_group.name.clone(),
+            start,
+            end,
+        };
+        assert_eq!(
+            BlockGroup::region_sequence(conn, &block_group, &region(Some(8), Some(12))).unwrap(),
+            "AATT"
+        );
+        assert_eq!(
+            BlockGroup::region_sequence(conn, &block_group, &region(Some(-12), Some(-8))).unwrap(),
+            "CCGG"
+        );
+        assert_eq!(
+            BlockGroup::region_sequence(conn, &block_group, &region(Some(-3), None)).unwrap(),
+            "GGG"
+        );
+        assert!(matches!(
+            BlockGroup::region_sequence(conn, &block_group, &region(Some(-50), None)),
+            Err(RegionError::OutOfBounds { .. })
+        ));
+
+        let empty_block_group = BlockGroup::create(conn, "test", None, "empty");
+        assert_eq!(
+            BlockGroup::region_sequence(conn, &empty_block_group, &region(None, None)),
+            Err(RegionError::NoPath("empty".to_string()))
+        );
+        assert!(BlockGroup::try_get_current_path(conn, empty_block_group.id).is_none());
+    }
+
     #[test]
     fn test_blockgroup_clone() {
         let conn = &get_connection(None);
@@ -809,6 +1348,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sample_block_groups_keep_topology() {
+        let conn = &get_connection(None);
+        Collection::create(conn, "test");
+        let plasmid = BlockGroup::create_with_topology(conn, "test", None, "pUC19", true);
+        assert!(plasmid.is_circular);
+        assert!(!BlockGroup::create(conn, "test", None, "chr1").is_circular);
+        // the topology of an existing block group is kept
+        assert!(BlockGroup::create(conn, "test", None, "pUC19").is_circular);
+
+        Sample::get_or_create(conn, "sample");
+        let sample_plasmid_id =
+            BlockGroup::get_or_create_sample_block_group(conn, "test", "sample", "pUC19", None)
+                .unwrap();
+        assert!(BlockGroup::get_by_id(conn, sample_plasmid_id).is_circular);
+        let sample_chromosome_id =
+            BlockGroup::get_or_create_sample_block_group(conn, "test", "sample", "chr1", None)
+                .unwrap();
+        assert!(!BlockGroup::get_by_id(conn, sample_chromosome_id).is_circular);
+    }
+
     #[test]
     fn test_blockgroup_clone_passes_accessions() {
         let conn = &get_connection(None);
@@ -857,6 +1417,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_flanking_sequences() {
+        let conn = get_connection(None);
+        let (block_group_id, path) = setup_block_group(&conn);
+        let insert_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("NNNN")
+            .save(&conn);
+        let insert_node_id = Node::create(&conn, insert_sequence.hash.as_str(), None);
+        let insert = PathBlock {
+            id: 0,
+            node_id: insert_node_id,
+            block_sequence: insert_sequence.get_sequence(0, 4).to_string(),
+            sequence_start: 0,
+            sequence_end: 4,
+            path_start: 7,
+            path_end: 15,
+            strand: Strand::Forward,
+        };
+        let change = PathChange {
+            block_group_id,
+            path: path.clone(),
+            path_accession: None,
+            start: 7,
+            end: 15,
+            block: insert,
+            chromosome_index: 1,
+            phased: 0,
+        };
+        let tree = path.intervaltree(&conn);
+        BlockGroup::insert_change(&conn, &change, &tree);
+
+        // flanks of the C node, which the insertion upstream of it is a variant of
+        let c_node_id = path.blocks(&conn)[3].node_id;
+        let (upstream, downstream) = BlockGroup::get_flanking_sequences(
+            &conn,
+            block_group_id,
+            (c_node_id, 0),
+            (c_node_id, 10),
+            12,
+        );
+        assert_eq!(
+            upstream,
+            vec!["AAANNNNTTTTT".to_string(), "AATTTTTTTTTT".to_string()]
+        );
+        assert_eq!(downstream, vec!["GGGGGGGGGG".to_string()]);
+
+        // flanks starting within a node are trimmed to the requested length
+        let (upstream, downstream) = BlockGroup::get_flanking_sequences(
+            &conn,
+            block_group_id,
+            (c_node_id, 5),
+            (c_node_id, 6),
+            3,
+        );
+        assert_eq!(upstream, vec!["CCC".to_string()]);
+        assert_eq!(downstream, vec!["CCC".to_string()]);
+    }
+
     #[test]
     fn insert_and_deletion_get_all() {
         let conn = get_connection(None);
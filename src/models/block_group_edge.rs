@@ -51,26 +51,21 @@ impl Query for BlockGroupEdge {
 
 impl BlockGroupEdge {
     pub fn bulk_create(conn: &Connection, block_group_edges: &[BlockGroupEdgeData]) {
-        for chunk in block_group_edges.chunks(100000) {
-            let mut rows_to_insert = vec![];
-            for block_group_edge in chunk {
-                let row = format!(
-                    "({0}, {1}, {2}, {3})",
+        for chunk in block_group_edges.chunks(BULK_CHUNK_SIZE) {
+            let insert_statement = format!(
+                "INSERT OR IGNORE INTO block_group_edges (block_group_id, edge_id, chromosome_index, phased) VALUES {0};",
+                placeholder_rows(4, chunk.len())
+            );
+            let values = chunk.iter().flat_map(|block_group_edge| {
+                [
                     block_group_edge.block_group_id,
                     block_group_edge.edge_id,
                     block_group_edge.chromosome_index,
                     block_group_edge.phased,
-                );
-                rows_to_insert.push(row);
-            }
-
-            let formatted_rows_to_insert = rows_to_insert.join(", ");
-
-            let insert_statement = format!(
-                "INSERT OR IGNORE INTO block_group_edges (block_group_id, edge_id, chromosome_index, phased) VALUES {0};",
-                formatted_rows_to_insert
-            );
-            let _ = conn.execute(&insert_statement, ());
+                ]
+            });
+            let mut stmt = conn.prepare_cached(&insert_statement).unwrap();
+            stmt.execute(rusqlite::params_from_iter(values)).unwrap();
         }
     }
 
@@ -0,0 +1,176 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BlockGroupLockError {
+    #[error("Block group {block_group_id} is locked by {owner}")]
+    BlockGroupLocked { block_group_id: i64, owner: String },
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+// An advisory write lock on a block group, held until it is released or dropped. Locks live in the
+// database, so applications sharing a database, or threads with their own connections, can update
+// different block groups at the same time and find out right away when another writer holds the one
+// they want, rather than waiting on SQLite. Nothing stops a writer that doesn't take the lock.
+// The owner describes who holds the lock and should be distinct for every writer.
+#[derive(Debug)]
+pub struct BlockGroupLock<'a> {
+    conn: &'a Connection,
+    pub block_group_id: i64,
+    pub owner: String,
+}
+
+impl<'a> BlockGroupLock<'a> {
+    pub fn acquire(
+        conn: &'a Connection,
+        block_group_id: i64,
+        owner: &str,
+    ) -> Result<BlockGroupLock<'a>, BlockGroupLockError> {
+        let inserted = conn.execute(
+            "insert into block_group_locks (block_group_id, owner) values (?1, ?2) on conflict do nothing;",
+            params![block_group_id, owner],
+        )?;
+        if inserted == 0 {
+            return Err(BlockGroupLockError::BlockGroupLocked {
+                block_group_id,
+                // the lock may have been released since the insert
+                owner: BlockGroupLock::owner(conn, block_group_id)?.unwrap_or_default(),
+            });
+        }
+        Ok(BlockGroupLock {
+            conn,
+            block_group_id,
+            owner: owner.to_string(),
+        })
+    }
+
+    pub fn owner(
+        conn: &Connection,
+        block_group_id: i64,
+    ) -> Result<Option<String>, BlockGroupLockError> {
+        Ok(conn
+            .query_row(
+                "select owner from block_group_locks where block_group_id = ?1;",
+                params![block_group_id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    // Removes a lock whatever its owner, for locks left behind by a writer that exited without
+    // releasing them.
+    pub fn clear(conn: &Connection, block_group_id: i64) -> Result<(), BlockGroupLockError> {
+        conn.execute(
+            "delete from block_group_locks where block_group_id = ?1;",
+            params![block_group_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn release(self) -> Result<(), BlockGroupLockError> {
+        let result = self.delete();
+        std::mem::forget(self);
+        result
+    }
+
+    fn delete(&self) -> Result<(), BlockGroupLockError> {
+        self.conn.execute(
+            "delete from block_group_locks where block_group_id = ?1 and owner = ?2;",
+            params![self.block_group_id, self.owner],
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for BlockGroupLock<'_> {
+    fn drop(&mut self) {
+        let _ = self.delete();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection_pool::get_connection_pool;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::collection::Collection;
+    use std::sync::Barrier;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_locks_block_groups_across_threads() {
+        let db_dir = tempdir().unwrap();
+        let pool = get_connection_pool(db_dir.path().join("locks.db").to_str().unwrap(), 3);
+        let (first_id, second_id) = {
+            let conn = pool.get().unwrap();
+            Collection::create(&conn, "test");
+            (
+                BlockGroup::create(&conn, "test", None, "chr1").id,
+                BlockGroup::create(&conn, "test", None, "chr2").id,
+            )
+        };
+
+        // writers on different block groups both get their locks, and a second writer on the
+        // first block group is turned away while the lock is held
+        let barrier = Barrier::new(3);
+        let results = thread::scope(|scope| {
+            let handles = [
+                (first_id, "writer 1"),
+                (second_id, "writer 2"),
+                (first_id, "writer 3"),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (block_group_id, owner))| {
+                let pool = pool.clone();
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    let conn = pool.get().unwrap();
+                    if index == 2 {
+                        barrier.wait();
+                    }
+                    let lock = BlockGroupLock::acquire(&conn, block_group_id, owner);
+                    if index != 2 {
+                        barrier.wait();
+                    }
+                    barrier.wait();
+                    lock.map(|lock| lock.owner.clone())
+                })
+            })
+            .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(
+            results,
+            vec![
+                Ok("writer 1".to_string()),
+                Ok("writer 2".to_string()),
+                Err(BlockGroupLockError::BlockGroupLocked {
+                    block_group_id: first_id,
+                    owner: "writer 1".to_string()
+                }),
+            ]
+        );
+
+        // dropped locks are released
+        let conn = pool.get().unwrap();
+        assert_eq!(BlockGroupLock::owner(&conn, first_id), Ok(None));
+        let lock = BlockGroupLock::acquire(&conn, first_id, "writer 3").unwrap();
+        assert_eq!(
+            BlockGroupLock::owner(&conn, first_id),
+            Ok(Some("writer 3".to_string()))
+        );
+        lock.release().unwrap();
+        BlockGroupLock::acquire(&conn, first_id, "writer 4").unwrap();
+
+        let stale = BlockGroupLock::acquire(&conn, second_id, "writer 5").unwrap();
+        std::mem::forget(stale);
+        BlockGroupLock::clear(&conn, second_id).unwrap();
+        assert_eq!(BlockGroupLock::owner(&conn, second_id), Ok(None));
+    }
+}
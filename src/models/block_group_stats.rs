@@ -0,0 +1,203 @@
+use crate::config::cache_writes_enabled;
+use crate::models::node::{PATH_END_NODE_ID, PATH_START_NODE_ID};
+use crate::models::traits::*;
+use rusqlite::{params, Connection, Row};
+
+// Node, edge, path, and variant site counts and total sequence length of a block group. These are
+// cached in the block_group_stats table so they don't require aggregating over every edge of a block group each
+// time they're requested. The cache is refreshed for every block group an operation changes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockGroupStats {
+    pub block_group_id: i64,
+    pub node_count: i64,
+    pub edge_count: i64,
+    pub total_length: i64,
+    pub path_count: i64,
+    // The points where the graph branches: an edge leaving a node before its end, where the node
+    // itself continues, or more than one edge leaving the same position.
+    pub variant_site_count: i64,
+}
+
+impl Query for BlockGroupStats {
+    type Model = BlockGroupStats;
+    fn process_row(row: &Row) -> Self::Model {
+        BlockGroupStats {
+            block_group_id: row.get(0).unwrap(),
+            node_count: row.get(1).unwrap(),
+            edge_count: row.get(2).unwrap(),
+            total_length: row.get(3).unwrap(),
+            path_count: row.get(4).unwrap(),
+            variant_site_count: row.get(5).unwrap(),
+        }
+    }
+}
+
+impl BlockGroupStats {
+    pub fn get_for_block_group(conn: &Connection, block_group_id: i64) -> BlockGroupStats {
+        // Block groups created before the cache existed, or outside of an operation, are
+        // calculated on first access, and only cached if caches can be written.
+        match BlockGroupStats::get(
+            conn,
+            "select * from block_group_stats where block_group_id = ?1",
+            params![block_group_id],
+        ) {
+            Ok(stats) => stats,
+            Err(rusqlite::Error::QueryReturnedNoRows) if !cache_writes_enabled() => {
+                BlockGroupStats::calculate(conn, block_group_id)
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                BlockGroupStats::refresh(conn, &[block_group_id]);
+                BlockGroupStats::get(
+                    conn,
+                    "select * from block_group_stats where block_group_id = ?1",
+                    params![block_group_id],
+                )
+                .unwrap()
+            }
+            Err(e) => panic!("something bad happened querying the database {e:?}"),
+        }
+    }
+
+    pub fn calculate(conn: &Connection, block_group_id: i64) -> BlockGroupStats {
+        let edge_count = conn
+            .query_row(
+                "select count(*) from block_group_edges where block_group_id = ?1",
+                params![block_group_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let (node_count, total_length) = conn
+            .query_row(
+                &format!(
+                    "with block_group_nodes as (
+                        select e.source_node_id as node_id from block_group_edges bge
+                        join edges e on e.id = bge.edge_id where bge.block_group_id = ?1
+                        union
+                        select e.target_node_id as node_id from block_group_edges bge
+                        join edges e on e.id = bge.edge_id where bge.block_group_id = ?1
+                    )
+                    select count(*), coalesce(sum(s.length), 0) from block_group_nodes bgn
+                    join nodes n on n.id = bgn.node_id
+                    join sequences s on s.hash = n.sequence_hash
+                    where n.id not in ({PATH_START_NODE_ID}, {PATH_END_NODE_ID})"
+                ),
+                params![block_group_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let path_count = conn
+            .query_row(
+                "select count(*) from paths where block_group_id = ?1",
+                params![block_group_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let variant_site_count = conn
+            .query_row(
+                &format!(
+                    "select count(*) from (
+                        select e.source_node_id, e.source_coordinate, e.source_strand,
+                        count(*) as edge_count, s.length as node_length from block_group_edges bge
+                        join edges e on e.id = bge.edge_id
+                        join nodes n on n.id = e.source_node_id
+                        join sequences s on s.hash = n.sequence_hash
+                        where bge.block_group_id = ?1
+                        group by e.source_node_id, e.source_coordinate, e.source_strand
+                    ) where edge_count > 1
+                    or (source_node_id != {PATH_START_NODE_ID} and source_coordinate < node_length)"
+                ),
+                params![block_group_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        BlockGroupStats {
+            block_group_id,
+            node_count,
+            edge_count,
+            total_length,
+            path_count,
+            variant_site_count,
+        }
+    }
+
+    pub fn average_node_length(&self) -> f64 {
+        if self.node_count == 0 {
+            0.0
+        } else {
+            self.total_length as f64 / self.node_count as f64
+        }
+    }
+
+    // Adds up the stats of several block groups, such as all the graphs of a sample.
+    pub fn sum(block_group_stats: &[BlockGroupStats]) -> BlockGroupStats {
+        block_group_stats.iter().fold(
+            BlockGroupStats {
+                block_group_id: 0,
+                node_count: 0,
+                edge_count: 0,
+                total_length: 0,
+                path_count: 0,
+                variant_site_count: 0,
+            },
+            |total, stats| BlockGroupStats {
+                block_group_id: 0,
+                node_count: total.node_count + stats.node_count,
+                edge_count: total.edge_count + stats.edge_count,
+                total_length: total.total_length + stats.total_length,
+                path_count: total.path_count + stats.path_count,
+                variant_site_count: total.variant_site_count + stats.variant_site_count,
+            },
+        )
+    }
+
+    // Recalculates the cached stats of the given block groups, dropping the stats of any that no
+    // longer exist (such as block groups removed by reverting an operation).
+    pub fn refresh(conn: &Connection, block_group_ids: &[i64]) {
+        let mut delete_stmt = conn
+            .prepare_cached("delete from block_group_stats where block_group_id = ?1")
+            .unwrap();
+        let mut exists_stmt = conn
+            .prepare_cached("select id from block_groups where id = ?1")
+            .unwrap();
+        let mut insert_stmt = conn
+            .prepare_cached("insert into block_group_stats (block_group_id, node_count, edge_count, total_length, path_count, variant_site_count) values (?1, ?2, ?3, ?4, ?5, ?6)")
+            .unwrap();
+        for block_group_id in block_group_ids {
+            delete_stmt.execute(params![block_group_id]).unwrap();
+            if exists_stmt.exists(params![block_group_id]).unwrap() {
+                let stats = BlockGroupStats::calculate(conn, *block_group_id);
+                insert_stmt
+                    .execute(params![
+                        stats.block_group_id,
+                        stats.node_count,
+                        stats.edge_count,
+                        stats.total_length,
+                        stats.path_count,
+                        stats.variant_site_count
+                    ])
+                    .unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::test_helpers::{get_connection, setup_block_group};
+
+    #[test]
+    fn test_calculates_stats() {
+        let conn = get_connection(None);
+        let (block_group_id, _path) = setup_block_group(&conn);
+        let stats = BlockGroupStats::get_for_block_group(&conn, block_group_id);
+        assert_eq!(stats, BlockGroupStats::calculate(&conn, block_group_id));
+        assert_eq!(stats.node_count, 4);
+        assert_eq!(stats.edge_count, 5);
+        assert_eq!(stats.total_length, 40);
+        assert_eq!(stats.path_count, 1);
+        assert_eq!(stats.variant_site_count, 0);
+        assert_eq!(stats.average_node_length(), 10.0);
+    }
+}
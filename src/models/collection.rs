@@ -1,6 +1,7 @@
 use rusqlite::{params_from_iter, Connection, Row};
 
 use crate::models::block_group::BlockGroup;
+use crate::models::sequence::SequenceStore;
 use crate::models::traits::*;
 
 #[derive(Clone, Debug)]
@@ -67,20 +68,39 @@ impl Collection {
         rows.map(|row| row.unwrap()).collect()
     }
 
+    pub fn sequence_store(conn: &Connection, name: &str) -> SequenceStore {
+        conn.query_row(
+            "select sequence_store from collections where name = ?1",
+            [name],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|store| store.parse().ok())
+        .unwrap_or_default()
+    }
+
+    // Sets where new sequences of a collection keep their bases, creating the collection if it
+    // doesn't exist yet so its first import can use the store.
+    pub fn set_sequence_store(
+        conn: &Connection,
+        name: &str,
+        store: SequenceStore,
+    ) -> rusqlite::Result<()> {
+        Collection::create(conn, name);
+        conn.execute(
+            "update collections set sequence_store = ?2 where name = ?1",
+            (name, store.to_string()),
+        )?;
+        Ok(())
+    }
+
     pub fn get_block_groups(conn: &Connection, collection_name: &str) -> Vec<BlockGroup> {
         // Load all block groups that have the given collection_name
         let mut stmt = conn
             .prepare("SELECT * FROM block_groups WHERE collection_name = ?1")
             .unwrap();
         let block_group_iter = stmt
-            .query_map([collection_name], |row| {
-                Ok(BlockGroup {
-                    id: row.get(0)?,
-                    collection_name: row.get(1)?,
-                    sample_name: row.get(2)?,
-                    name: row.get(3)?,
-                })
-            })
+            .query_map([collection_name], |row| Ok(BlockGroup::process_row(row)))
             .unwrap();
         block_group_iter.map(|bg| bg.unwrap()).collect()
     }
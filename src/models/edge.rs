@@ -3,11 +3,12 @@ use petgraph::graphmap::DiGraphMap;
 use rusqlite::types::Value;
 use rusqlite::{params_from_iter, Connection, Result as SQLResult, Row};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, RandomState};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
 use std::rc::Rc;
 
 use crate::graph::{GraphEdge, GraphNode};
+use crate::models::attribute::{AttributeTable, AttributeValue};
 use crate::models::block_group_edge::AugmentedEdge;
 use crate::models::node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID};
 use crate::models::sequence::{cached_sequence, Sequence};
@@ -191,76 +192,96 @@ impl Edge {
         Edge::query(conn, query, rusqlite::params!(Rc::new(query_edge_ids)))
     }
 
-    pub fn bulk_create(conn: &Connection, edges: &Vec<EdgeData>) -> Vec<i64> {
-        let mut edge_rows = vec![];
+    pub fn bulk_create(conn: &Connection, edges: &[EdgeData]) -> Vec<i64> {
+        let unique_edges = edges.iter().unique().collect::<Vec<&EdgeData>>();
         let mut edge_map: HashMap<EdgeData, i64> = HashMap::new();
-        for edge in edges {
-            let source_strand = format!("\"{0}\"", edge.source_strand);
-            let target_strand = format!("\"{0}\"", edge.target_strand);
-            let edge_row = format!(
-                "({0}, {1}, {2}, {3}, {4}, {5})",
-                edge.source_node_id,
-                edge.source_coordinate,
-                source_strand,
-                edge.target_node_id,
-                edge.target_coordinate,
-                target_strand,
-            );
-            edge_rows.push(edge_row);
-        }
-        let formatted_edge_rows = edge_rows.join(", ");
-
-        let select_statement = format!("SELECT * FROM edges WHERE (source_node_id, source_coordinate, source_strand, target_node_id, target_coordinate, target_strand) in ({0});", formatted_edge_rows);
-        let existing_edges = Edge::query(conn, &select_statement, rusqlite::params!());
-        for edge in existing_edges.iter() {
-            edge_map.insert(EdgeData::from(edge), edge.id);
-        }
 
-        let existing_edge_set = HashSet::<EdgeData, RandomState>::from_iter(
-            existing_edges.into_iter().map(Edge::to_data),
-        );
-        let mut edges_to_insert = HashSet::new();
-        for edge in edges {
-            if !existing_edge_set.contains(edge) {
-                edges_to_insert.insert(edge);
+        for chunk in unique_edges.chunks(BULK_CHUNK_SIZE) {
+            let select_statement = format!("SELECT * FROM edges WHERE (source_node_id, source_coordinate, source_strand, target_node_id, target_coordinate, target_strand) IN (VALUES {0});", placeholder_rows(6, chunk.len()));
+            let mut stmt = conn.prepare_cached(&select_statement).unwrap();
+            let rows = stmt
+                .query_map(
+                    params_from_iter(Edge::chunk_values(chunk)),
+                    Edge::edge_from_row,
+                )
+                .unwrap();
+            for row in rows {
+                let edge = row.unwrap();
+                edge_map.insert(EdgeData::from(&edge), edge.id);
             }
         }
 
-        let mut edge_rows_to_insert = vec![];
-        for edge in edges_to_insert {
-            let source_strand = format!("\"{0}\"", edge.source_strand);
-            let target_strand = format!("\"{0}\"", edge.target_strand);
-            let edge_row = format!(
-                "({0}, {1}, {2}, {3}, {4}, {5})",
-                edge.source_node_id,
-                edge.source_coordinate,
-                source_strand,
-                edge.target_node_id,
-                edge.target_coordinate,
-                target_strand,
-            );
-            edge_rows_to_insert.push(edge_row);
-        }
-
-        if !edge_rows_to_insert.is_empty() {
-            for chunk in edge_rows_to_insert.chunks(100000) {
-                let formatted_edge_rows_to_insert = chunk.join(", ");
-
-                let insert_statement = format!("INSERT INTO edges (source_node_id, source_coordinate, source_strand, target_node_id, target_coordinate, target_strand) VALUES {0} RETURNING *;", formatted_edge_rows_to_insert);
-                let mut stmt = conn.prepare(&insert_statement).unwrap();
-                let rows = stmt.query_map([], Edge::edge_from_row).unwrap();
-                for row in rows {
-                    let edge = row.unwrap();
-                    edge_map.insert(EdgeData::from(&edge), edge.id);
-                }
+        let edges_to_insert = unique_edges
+            .into_iter()
+            .filter(|edge| !edge_map.contains_key(*edge))
+            .collect::<Vec<&EdgeData>>();
+        for chunk in edges_to_insert.chunks(BULK_CHUNK_SIZE) {
+            let insert_statement = format!("INSERT INTO edges (source_node_id, source_coordinate, source_strand, target_node_id, target_coordinate, target_strand) VALUES {0} RETURNING *;", placeholder_rows(6, chunk.len()));
+            let mut stmt = conn.prepare_cached(&insert_statement).unwrap();
+            let rows = stmt
+                .query_map(
+                    params_from_iter(Edge::chunk_values(chunk)),
+                    Edge::edge_from_row,
+                )
+                .unwrap();
+            for row in rows {
+                let edge = row.unwrap();
+                edge_map.insert(EdgeData::from(&edge), edge.id);
             }
         }
+
         edges
             .iter()
             .map(|edge| *edge_map.get(edge).unwrap())
             .collect::<Vec<i64>>()
     }
 
+    fn chunk_values(edges: &[&EdgeData]) -> Vec<Value> {
+        edges
+            .iter()
+            .flat_map(|edge| {
+                [
+                    Value::from(edge.source_node_id),
+                    Value::from(edge.source_coordinate),
+                    Value::from(edge.source_strand),
+                    Value::from(edge.target_node_id),
+                    Value::from(edge.target_coordinate),
+                    Value::from(edge.target_strand),
+                ]
+            })
+            .collect()
+    }
+
+    // Like those of nodes, edge attributes belong to the edge rather than a graph, so every graph
+    // with the edge shares them.
+    pub fn set_attribute(
+        conn: &Connection,
+        edge_id: i64,
+        key: &str,
+        value: impl Into<AttributeValue>,
+    ) {
+        AttributeTable::Edge.set(conn, edge_id, key, &value.into());
+    }
+
+    pub fn get_attribute(conn: &Connection, edge_id: i64, key: &str) -> Option<AttributeValue> {
+        AttributeTable::Edge.get(conn, edge_id, key)
+    }
+
+    pub fn remove_attribute(conn: &Connection, edge_id: i64, key: &str) {
+        AttributeTable::Edge.remove(conn, edge_id, key);
+    }
+
+    pub fn attributes(conn: &Connection, edge_id: i64) -> BTreeMap<String, AttributeValue> {
+        AttributeTable::Edge.all(conn, edge_id)
+    }
+
+    pub fn attributes_for_edges(
+        conn: &Connection,
+        edge_ids: &[i64],
+    ) -> HashMap<i64, BTreeMap<String, AttributeValue>> {
+        AttributeTable::Edge.for_ids(conn, edge_ids)
+    }
+
     pub fn to_data(edge: Edge) -> EdgeData {
         EdgeData {
             source_node_id: edge.source_node_id,
@@ -518,6 +539,8 @@ impl Edge {
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    #[cfg(feature = "benchmark")]
+    use crate::models::block_group_edge::BlockGroupEdgeData;
     use crate::models::{
         block_group::{BlockGroup, PathChange},
         block_group_edge::BlockGroupEdge,
@@ -526,6 +549,8 @@ mod tests {
         sequence::Sequence,
     };
     use crate::test_helpers::{get_connection, setup_block_group};
+    #[cfg(feature = "benchmark")]
+    use std::time;
 
     #[test]
     fn test_bulk_create() {
@@ -566,7 +591,7 @@ mod tests {
             target_strand: Strand::Forward,
         };
 
-        let edge_ids = Edge::bulk_create(conn, &vec![edge1, edge2, edge3]);
+        let edge_ids = Edge::bulk_create(conn, &[edge1, edge2, edge3]);
         assert_eq!(edge_ids.len(), 3);
         let edges = Edge::bulk_load(conn, &edge_ids);
         assert_eq!(edges.len(), 3);
@@ -659,6 +684,46 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(feature = "benchmark")]
+    fn test_bulk_create_benchmark() {
+        let conn = &get_connection(None);
+        let (block_group_id, _path) = setup_block_group(conn);
+        let sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("ATCGATCG")
+            .save(conn);
+        let node_ids = (0..1000)
+            .map(|index| Node::create(conn, sequence.hash.as_str(), format!("node {index}")))
+            .collect::<Vec<i64>>();
+        let edges = (0..1_000_000)
+            .map(|index| EdgeData {
+                source_node_id: node_ids[index % 1000],
+                source_coordinate: (index / 1000) as i64,
+                source_strand: Strand::Forward,
+                target_node_id: node_ids[(index + 1) % 1000],
+                target_coordinate: 0,
+                target_strand: Strand::Forward,
+            })
+            .collect::<Vec<EdgeData>>();
+
+        let s = time::Instant::now();
+        let edge_ids = Edge::bulk_create(conn, &edges);
+        // creating them again only looks them up
+        assert_eq!(Edge::bulk_create(conn, &edges), edge_ids);
+        let block_group_edges = edge_ids
+            .iter()
+            .map(|edge_id| BlockGroupEdgeData {
+                block_group_id,
+                edge_id: *edge_id,
+                chromosome_index: 0,
+                phased: 0,
+            })
+            .collect::<Vec<BlockGroupEdgeData>>();
+        BlockGroupEdge::bulk_create(conn, &block_group_edges);
+        assert!(s.elapsed().as_secs() < 20);
+    }
+
     #[test]
     fn test_bulk_create_with_existing_edge() {
         let conn = &mut get_connection(None);
@@ -713,7 +778,7 @@ mod tests {
             target_strand: Strand::Forward,
         };
 
-        let edge_ids = Edge::bulk_create(conn, &vec![edge1, edge2, edge3]);
+        let edge_ids = Edge::bulk_create(conn, &[edge1, edge2, edge3]);
         assert_eq!(edge_ids.len(), 3);
         let edges = Edge::bulk_load(conn, &edge_ids);
         assert_eq!(edges.len(), 3);
@@ -5,24 +5,38 @@ use serde::{Deserialize, Serialize};
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub enum FileTypes {
     GenBank,
+    BedGraph,
     Fasta,
     GFA,
     GAF,
+    GFF,
+    MAF,
+    PAF,
+    Manifest,
+    Recipe,
     VCF,
     Changeset,
     CSV,
+    None,
 }
 
 impl ToSql for FileTypes {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
         let result = match self {
             FileTypes::GenBank => "gb".into(),
+            FileTypes::BedGraph => "bedgraph".into(),
             FileTypes::Fasta => "fasta".into(),
             FileTypes::GFA => "gfa".into(),
             FileTypes::VCF => "vcf".into(),
             FileTypes::Changeset => "changeset".into(),
             FileTypes::CSV => "csv".into(),
             FileTypes::GAF => "gaf".into(),
+            FileTypes::GFF => "gff".into(),
+            FileTypes::MAF => "maf".into(),
+            FileTypes::PAF => "paf".into(),
+            FileTypes::Manifest => "manifest".into(),
+            FileTypes::Recipe => "recipe".into(),
+            FileTypes::None => "none".into(),
         };
         Ok(result)
     }
@@ -32,12 +46,19 @@ impl From<FileTypes> for Value {
     fn from(value: FileTypes) -> Value {
         let result = match value {
             FileTypes::GenBank => "gb",
+            FileTypes::BedGraph => "bedgraph",
             FileTypes::Fasta => "fasta",
             FileTypes::GFA => "gfa",
             FileTypes::VCF => "vcf",
             FileTypes::Changeset => "changeset",
             FileTypes::CSV => "csv",
             FileTypes::GAF => "gaf",
+            FileTypes::GFF => "gff",
+            FileTypes::MAF => "maf",
+            FileTypes::PAF => "paf",
+            FileTypes::Manifest => "manifest",
+            FileTypes::Recipe => "recipe",
+            FileTypes::None => "none",
         };
         Value::Text(result.to_string())
     }
@@ -47,12 +68,19 @@ impl FromSql for FileTypes {
     fn column_result(value: ValueRef) -> FromSqlResult<Self> {
         let result = match value.as_str() {
             Ok("gb") => FileTypes::GenBank,
+            Ok("bedgraph") => FileTypes::BedGraph,
             Ok("fasta") => FileTypes::Fasta,
             Ok("gfa") => FileTypes::GFA,
             Ok("vcf") => FileTypes::VCF,
             Ok("changeset") => FileTypes::Changeset,
             Ok("csv") => FileTypes::CSV,
             Ok("gaf") => FileTypes::GAF,
+            Ok("gff") => FileTypes::GFF,
+            Ok("maf") => FileTypes::MAF,
+            Ok("paf") => FileTypes::PAF,
+            Ok("manifest") => FileTypes::Manifest,
+            Ok("recipe") => FileTypes::Recipe,
+            Ok("none") => FileTypes::None,
             _ => panic!("Invalid entry in database"),
         };
         Ok(result)
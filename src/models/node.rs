@@ -1,8 +1,13 @@
-use rusqlite::{params_from_iter, types::Value as SQLValue, Connection, Row};
+use itertools::Itertools;
+use rusqlite::{params, params_from_iter, types::Value as SQLValue, Connection, Row};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 
+use crate::calculate_hash;
+use crate::models::attribute::{AttributeTable, AttributeValue};
+use crate::models::path::Path;
+use crate::models::path_index::PathIndex;
 use crate::models::sequence::Sequence;
 use crate::models::traits::*;
 
@@ -71,6 +76,13 @@ impl Node {
         }
     }
 
+    // The hash of a node placed between coordinates of a path, given by Path::content_key. It
+    // depends on the node's sequence and where it was placed rather than on row ids, so the same
+    // node made in two repositories gets the same hash and patches find it where it already exists.
+    pub fn path_hash(path_key: &str, start: i64, end: i64, sequence_hash: &str) -> String {
+        calculate_hash(&format!("{path_key}:{start}-{end}->{sequence_hash}"))
+    }
+
     pub fn get_nodes(conn: &Connection, node_ids: &[i64]) -> Vec<Node> {
         let mut nodes: Vec<Node> = vec![];
         for chunk in node_ids.chunks(1000) {
@@ -129,6 +141,77 @@ impl Node {
         }
     }
 
+    // Hashes for the nodes made without one, from where the first path running through a node
+    // places it. Paths are taken in the order of their content keys so every repository picks the
+    // same path for a node. Nodes no path runs through are left out.
+    pub fn missing_hashes(conn: &Connection) -> HashMap<i64, String> {
+        let missing = Node::query(
+            conn,
+            "select * from nodes where hash is null and id not in (?1, ?2);",
+            params![PATH_START_NODE_ID, PATH_END_NODE_ID],
+        )
+        .into_iter()
+        .map(|node| (node.id, node.sequence_hash))
+        .collect::<HashMap<i64, String>>();
+        let mut hashes = HashMap::new();
+        if missing.is_empty() {
+            return hashes;
+        }
+        let paths = Path::query(conn, "select * from paths;", params![])
+            .into_iter()
+            .map(|path| (path.content_key(conn), path.id))
+            .sorted();
+        for (path_key, path_id) in paths {
+            for block in PathIndex::blocks(conn, path_id) {
+                if let Some(sequence_hash) = missing.get(&block.node_id) {
+                    hashes.entry(block.node_id).or_insert_with(|| {
+                        Node::path_hash(&path_key, block.start, block.end, sequence_hash)
+                    });
+                }
+            }
+        }
+        hashes
+    }
+
+    // Hashes are unique, so a node whose hash another node already has keeps none. Returns whether
+    // the node was given the hash.
+    pub fn set_missing_hash(conn: &Connection, node_id: i64, node_hash: &str) -> bool {
+        conn.prepare_cached("UPDATE OR IGNORE nodes SET hash = ?2 WHERE id = ?1 AND hash IS NULL;")
+            .unwrap()
+            .execute(params![node_id, node_hash])
+            .unwrap()
+            == 1
+    }
+
+    // Attributes are typed key/value metadata, such as where the node was imported from.
+    pub fn set_attribute(
+        conn: &Connection,
+        node_id: i64,
+        key: &str,
+        value: impl Into<AttributeValue>,
+    ) {
+        AttributeTable::Node.set(conn, node_id, key, &value.into());
+    }
+
+    pub fn get_attribute(conn: &Connection, node_id: i64, key: &str) -> Option<AttributeValue> {
+        AttributeTable::Node.get(conn, node_id, key)
+    }
+
+    pub fn remove_attribute(conn: &Connection, node_id: i64, key: &str) {
+        AttributeTable::Node.remove(conn, node_id, key);
+    }
+
+    pub fn attributes(conn: &Connection, node_id: i64) -> BTreeMap<String, AttributeValue> {
+        AttributeTable::Node.all(conn, node_id)
+    }
+
+    pub fn attributes_for_nodes(
+        conn: &Connection,
+        node_ids: &[i64],
+    ) -> HashMap<i64, BTreeMap<String, AttributeValue>> {
+        AttributeTable::Node.for_ids(conn, node_ids)
+    }
+
     pub fn is_terminal(node_id: i64) -> bool {
         Node::is_start_node(node_id) || Node::is_end_node(node_id)
     }
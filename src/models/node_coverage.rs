@@ -0,0 +1,76 @@
+use crate::models::traits::*;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, Row};
+use std::rc::Rc;
+
+// A run of bases of a node with the same read depth, in 0-based, end-exclusive coordinates of the
+// node's sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeCoverage {
+    pub track: String,
+    pub node_id: i64,
+    pub start: i64,
+    pub end: i64,
+    pub depth: f64,
+}
+
+impl Query for NodeCoverage {
+    type Model = NodeCoverage;
+    fn process_row(row: &Row) -> Self::Model {
+        NodeCoverage {
+            track: row.get(0).unwrap(),
+            node_id: row.get(1).unwrap(),
+            start: row.get(2).unwrap(),
+            end: row.get(3).unwrap(),
+            depth: row.get(4).unwrap(),
+        }
+    }
+}
+
+impl NodeCoverage {
+    // Runs starting where the track already has one on the node are skipped.
+    pub fn bulk_create(conn: &Connection, coverage: &[NodeCoverage]) -> usize {
+        let mut stmt = conn
+            .prepare_cached("INSERT OR IGNORE INTO node_coverage (track, node_id, start, end, depth) VALUES (?1, ?2, ?3, ?4, ?5);")
+            .unwrap();
+        let mut inserted = 0;
+        for run in coverage {
+            inserted += stmt
+                .execute(params![
+                    run.track,
+                    run.node_id,
+                    run.start,
+                    run.end,
+                    run.depth
+                ])
+                .unwrap();
+        }
+        inserted
+    }
+
+    pub fn query_for_nodes(conn: &Connection, track: &str, node_ids: &[i64]) -> Vec<NodeCoverage> {
+        NodeCoverage::query(
+            conn,
+            "select * from node_coverage where track = ?1 and node_id in rarray(?2) order by node_id, start",
+            params![
+                track,
+                Rc::new(
+                    node_ids
+                        .iter()
+                        .map(|node_id| Value::from(*node_id))
+                        .collect::<Vec<Value>>()
+                )
+            ],
+        )
+    }
+
+    pub fn tracks(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("select distinct track from node_coverage order by track")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|track| track.unwrap())
+            .collect()
+    }
+}
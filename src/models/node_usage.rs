@@ -0,0 +1,191 @@
+use crate::models::path_index::PathIndex;
+use crate::models::strand::Strand;
+use rusqlite::{params, Connection};
+
+// Where a path passes through a node, in path coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeTraversal {
+    pub path_id: i64,
+    pub path_name: String,
+    pub start: i64,
+    pub end: i64,
+    pub strand: Strand,
+}
+
+// A graph a node is part of, with the places its paths traverse the node. A node can be in a graph
+// without any of its paths going through it, such as an alternative allele.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeUsage {
+    pub node_id: i64,
+    pub collection_name: String,
+    pub sample_name: Option<String>,
+    pub block_group_id: i64,
+    pub block_group_name: String,
+    pub traversals: Vec<NodeTraversal>,
+}
+
+impl NodeUsage {
+    pub fn for_node(conn: &Connection, node_id: i64) -> Vec<NodeUsage> {
+        let mut stmt = conn
+            .prepare_cached(
+                "select distinct bg.id, bg.collection_name, bg.sample_name, bg.name from block_groups bg join block_group_edges bge on bge.block_group_id = bg.id join edges e on e.id = bge.edge_id where e.source_node_id = ?1 or e.target_node_id = ?1 order by bg.collection_name, bg.sample_name, bg.name;",
+            )
+            .unwrap();
+        let block_groups = stmt
+            .query_map(params![node_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect::<Vec<(i64, String, Option<String>, String)>>();
+
+        let mut paths_stmt = conn
+            .prepare_cached("select id, name from paths where block_group_id = ?1 order by id;")
+            .unwrap();
+        block_groups
+            .into_iter()
+            .map(
+                |(block_group_id, collection_name, sample_name, block_group_name)| {
+                    let paths = paths_stmt
+                        .query_map(params![block_group_id], |row| {
+                            Ok((row.get(0)?, row.get(1)?))
+                        })
+                        .unwrap()
+                        .map(|row| row.unwrap())
+                        .collect::<Vec<(i64, String)>>();
+                    let traversals = paths
+                        .into_iter()
+                        .flat_map(|(path_id, path_name)| {
+                            PathIndex::node_blocks(conn, path_id, node_id)
+                                .into_iter()
+                                .map(move |block| NodeTraversal {
+                                    path_id,
+                                    path_name: path_name.clone(),
+                                    start: block.start,
+                                    end: block.end,
+                                    strand: block.strand,
+                                })
+                        })
+                        .collect();
+                    NodeUsage {
+                        node_id,
+                        collection_name,
+                        sample_name,
+                        block_group_id,
+                        block_group_name,
+                        traversals,
+                    }
+                },
+            )
+            .collect()
+    }
+
+    // The usages of every node with the given sequence.
+    pub fn for_sequence(conn: &Connection, sequence_hash: &str) -> Vec<NodeUsage> {
+        let mut stmt = conn
+            .prepare_cached("select id from nodes where sequence_hash = ?1 order by id;")
+            .unwrap();
+        let node_ids = stmt
+            .query_map(params![sequence_hash], |row| row.get(0))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect::<Vec<i64>>();
+        node_ids
+            .into_iter()
+            .flat_map(|node_id| NodeUsage::for_node(conn, node_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::path::Path;
+    use crate::models::sequence::Sequence;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_finds_node_usages() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // ATCGA... becomes AT AAAAAAAA TCGA...
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            2,
+            5,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let reference_bg = get_sample_bg(conn, "test", None);
+        let child_bg = get_sample_bg(conn, "test", Some("child"));
+        let reference_path = BlockGroup::get_current_path(conn, reference_bg.id);
+        let child_path = BlockGroup::get_current_path(conn, child_bg.id);
+        let reference_node_id = PathIndex::blocks(conn, reference_path.id)[0].node_id;
+
+        let usages = NodeUsage::for_node(conn, reference_node_id);
+        assert_eq!(
+            usages
+                .iter()
+                .map(|usage| (usage.sample_name.as_deref(), usage.block_group_id))
+                .collect::<Vec<_>>(),
+            vec![(None, reference_bg.id), (Some("child"), child_bg.id)]
+        );
+        let traversal = |path: &Path, start: i64, end: i64| NodeTraversal {
+            path_id: path.id,
+            path_name: path.name.clone(),
+            start,
+            end,
+            strand: Strand::Forward,
+        };
+        assert_eq!(
+            usages[0].traversals,
+            vec![traversal(&reference_path, 0, 34)]
+        );
+        // the child graph keeps the path it was copied from
+        let copied_path = Path::get(conn, usages[1].traversals[0].path_id);
+        assert_eq!(
+            usages[1].traversals,
+            vec![
+                traversal(&copied_path, 0, 34),
+                traversal(&child_path, 0, 2),
+                traversal(&child_path, 10, 39)
+            ]
+        );
+
+        let insert_hash = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("AAAAAAAA")
+            .hash();
+        let usages = NodeUsage::for_sequence(conn, &insert_hash);
+        assert_eq!(usages.len(), 1);
+        assert_eq!(usages[0].sample_name.as_deref(), Some("child"));
+        assert_eq!(usages[0].traversals, vec![traversal(&child_path, 2, 10)]);
+        assert!(NodeUsage::for_sequence(conn, "unknown").is_empty());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::graph::{all_simple_paths, OperationGraph};
 use crate::models::file_types::FileTypes;
 use crate::models::traits::*;
+use crate::range::Region;
+use itertools::Itertools;
 use petgraph::graphmap::UnGraphMap;
 use petgraph::visit::{Dfs, Reversed};
 use petgraph::Direction;
@@ -8,6 +10,7 @@ use rusqlite::types::Value;
 use rusqlite::{params_from_iter, Connection, Result as SQLResult, Row};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::env;
 use std::string::ToString;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -18,6 +21,20 @@ pub struct Operation {
     pub branch_id: i64,
     pub change_type: String,
     pub change_id: i64,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub gen_version: Option<String>,
+    #[serde(default)]
+    pub working_directory: Option<String>,
 }
 
 impl Operation {
@@ -49,7 +66,21 @@ impl Operation {
             }
         }
 
-        let query = "INSERT INTO operation (hash, db_uuid, change_type, change_id, parent_hash, branch_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6);";
+        // the author is whoever is configured with `gen defaults` when the operation is made
+        let (author, email): (Option<String>, Option<String>) = conn.query_row(
+            "select author, email from defaults where id = 1;",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        // record how the operation was made so it can be traced back and re-run
+        let command = env::args().collect::<Vec<String>>();
+        let gen_version = env!("CARGO_PKG_VERSION").to_string();
+        let working_directory = env::current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().to_string());
+
+        let query = "INSERT INTO operation (hash, db_uuid, change_type, change_id, parent_hash, branch_id, author, email, timestamp, command, gen_version, working_directory) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12);";
         let mut stmt = conn.prepare(query).unwrap();
         stmt.execute(params_from_iter(vec![
             Value::from(hash.to_string()),
@@ -58,6 +89,12 @@ impl Operation {
             Value::from(change_id),
             Value::from(current_op.clone()),
             Value::from(current_branch_id),
+            Value::from(author.clone()),
+            Value::from(email.clone()),
+            Value::from(timestamp.clone()),
+            Value::from(serde_json::to_string(&command).unwrap()),
+            Value::from(gen_version.clone()),
+            Value::from(working_directory.clone()),
         ]))?;
         let operation = Operation {
             hash: hash.to_string(),
@@ -66,6 +103,13 @@ impl Operation {
             branch_id: current_branch_id,
             change_type: change_type.to_string(),
             change_id,
+            author,
+            email,
+            timestamp: Some(timestamp),
+            message: None,
+            command: Some(command),
+            gen_version: Some(gen_version),
+            working_directory,
         };
         // TODO: error condition here where we can write to disk but transaction fails
         OperationState::set_operation(conn, &operation.db_uuid, &operation.hash);
@@ -85,6 +129,22 @@ impl Operation {
             .collect::<Vec<String>>()
     }
 
+    // The latest operation two operations both descend from, if they share any.
+    pub fn common_ancestor(
+        conn: &Connection,
+        first_hash: &str,
+        second_hash: &str,
+    ) -> Option<String> {
+        let first_upstream = Operation::get_upstream(conn, first_hash.to_string());
+        let second_upstream = Operation::get_upstream(conn, second_hash.to_string());
+        first_upstream
+            .iter()
+            .zip(second_upstream.iter())
+            .take_while(|(first, second)| first == second)
+            .last()
+            .map(|(ancestor, _)| ancestor.clone())
+    }
+
     pub fn get_operation_graph(conn: &Connection) -> OperationGraph {
         let mut graph = OperationGraph::new();
         let operations = Operation::query(conn, "select * from operation;", rusqlite::params![]);
@@ -160,6 +220,53 @@ impl Operation {
         rows.next().unwrap()
     }
 
+    pub fn set_message(conn: &Connection, op_hash: &str, message: &str) -> SQLResult<usize> {
+        conn.execute(
+            "UPDATE operation SET message = ?2 WHERE hash = ?1",
+            (op_hash, message),
+        )
+    }
+
+    // Carries the authorship and invocation of an operation over to a copy of it, such as one applied
+    // from a patch.
+    pub fn copy_metadata(conn: &Connection, op_hash: &str, source: &Operation) -> SQLResult<usize> {
+        conn.execute(
+            "UPDATE operation SET author = ?2, email = ?3, timestamp = ?4, message = ?5, command = ?6, gen_version = ?7, working_directory = ?8 WHERE hash = ?1",
+            (
+                op_hash,
+                &source.author,
+                &source.email,
+                &source.timestamp,
+                &source.message,
+                source
+                    .command
+                    .as_ref()
+                    .map(|command| serde_json::to_string(command).unwrap()),
+                &source.gen_version,
+                &source.working_directory,
+            ),
+        )
+    }
+
+    // The recorded command quoted so it can be pasted back into a shell.
+    pub fn command_line(&self) -> Option<String> {
+        self.command.as_ref().map(|args| {
+            args.iter()
+                .map(|arg| {
+                    if !arg.is_empty()
+                        && arg
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@%+".contains(c))
+                    {
+                        arg.clone()
+                    } else {
+                        format!("'{}'", arg.replace('\'', "'\\''"))
+                    }
+                })
+                .join(" ")
+        })
+    }
+
     pub fn get_by_hash(conn: &Connection, op_hash: &str) -> SQLResult<Operation> {
         Operation::get(
             conn,
@@ -179,6 +286,16 @@ impl Query for Operation {
             branch_id: row.get(3).unwrap(),
             change_type: row.get(4).unwrap(),
             change_id: row.get(5).unwrap(),
+            author: row.get(6).unwrap(),
+            email: row.get(7).unwrap(),
+            timestamp: row.get(8).unwrap(),
+            message: row.get(9).unwrap(),
+            command: row
+                .get::<_, Option<String>>(10)
+                .unwrap()
+                .map(|command| serde_json::from_str(&command).unwrap()),
+            gen_version: row.get(11).unwrap(),
+            working_directory: row.get(12).unwrap(),
         }
     }
 }
@@ -489,10 +606,51 @@ impl Branch {
         operations
     }
 
+    pub fn divergence(
+        conn: &Connection,
+        branch: &Branch,
+        other_branch: &Branch,
+    ) -> BranchDivergence {
+        let upstream = |branch: &Branch| {
+            branch
+                .current_operation_hash
+                .clone()
+                .map(|hash| Operation::get_upstream(conn, hash))
+                .unwrap_or_default()
+        };
+        let operations = upstream(branch);
+        let other_operations = upstream(other_branch);
+        let common_ancestor = match (
+            &branch.current_operation_hash,
+            &other_branch.current_operation_hash,
+        ) {
+            (Some(hash), Some(other_hash)) => Operation::common_ancestor(conn, hash, other_hash),
+            _ => None,
+        };
+        let shared = common_ancestor.as_ref().map_or(0, |ancestor| {
+            operations.iter().position(|hash| hash == ancestor).unwrap() + 1
+        });
+        BranchDivergence {
+            branch: branch.name.clone(),
+            other_branch: other_branch.name.clone(),
+            common_ancestor,
+            ahead: operations.len() - shared,
+            behind: other_operations.len() - shared,
+        }
+    }
+
     pub fn mask_operation(conn: &Connection, branch_id: i64, operation_hash: &str) {
         conn.execute("INSERT OR IGNORE into branch_masked_operations (branch_id, operation_hash) values (?1, ?2);", (branch_id, operation_hash.to_string())).unwrap();
     }
 
+    pub fn unmask_operation(conn: &Connection, branch_id: i64, operation_hash: &str) {
+        conn.execute(
+            "DELETE FROM branch_masked_operations WHERE branch_id = ?1 AND operation_hash = ?2;",
+            (branch_id, operation_hash.to_string()),
+        )
+        .unwrap();
+    }
+
     pub fn get_masked_operations(conn: &Connection, branch_id: i64) -> Vec<String> {
         let mut stmt = conn
             .prepare("select operation_hash from branch_masked_operations where branch_id = ?1")
@@ -505,6 +663,384 @@ impl Branch {
     }
 }
 
+// Tracks which collections are materialized in a sparse checkout, and which operations still have
+// to be replayed for the collections that were left out.
+// How far the heads of two branches have moved apart, as the number of operations leading to the
+// head of the branch that the other branch lacks (ahead), and the reverse (behind).
+#[derive(Debug, PartialEq)]
+pub struct BranchDivergence {
+    pub branch: String,
+    pub other_branch: String,
+    pub common_ancestor: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+pub struct SparseCheckout {}
+
+impl SparseCheckout {
+    pub fn get_collections(conn: &Connection, db_uuid: &str) -> Option<HashSet<String>> {
+        let mut stmt = conn
+            .prepare("SELECT collection_name from sparse_collections where db_uuid = ?1;")
+            .unwrap();
+        let collections = stmt
+            .query_map((db_uuid,), |row| row.get(0))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect::<HashSet<String>>();
+        if collections.is_empty() {
+            None
+        } else {
+            Some(collections)
+        }
+    }
+
+    // An empty list of collections ends the sparse checkout.
+    pub fn set_collections(conn: &Connection, db_uuid: &str, collections: &[String]) {
+        conn.execute(
+            "DELETE FROM sparse_collections where db_uuid = ?1;",
+            (db_uuid,),
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare(
+                "INSERT OR IGNORE INTO sparse_collections (db_uuid, collection_name) VALUES (?1, ?2);",
+            )
+            .unwrap();
+        for collection in collections {
+            stmt.execute((db_uuid, collection)).unwrap();
+        }
+    }
+
+    pub fn add_pending_operation(
+        conn: &Connection,
+        db_uuid: &str,
+        collection_name: &str,
+        operation_hash: &str,
+    ) {
+        conn.execute(
+            "INSERT OR IGNORE INTO sparse_pending_operations (db_uuid, collection_name, operation_hash) VALUES (?1, ?2, ?3);",
+            (db_uuid, collection_name, operation_hash),
+        )
+        .unwrap();
+    }
+
+    // A reverted operation no longer has to be replayed for any collection.
+    pub fn remove_pending_operation(conn: &Connection, db_uuid: &str, operation_hash: &str) {
+        conn.execute(
+            "DELETE FROM sparse_pending_operations where db_uuid = ?1 and operation_hash = ?2;",
+            (db_uuid, operation_hash),
+        )
+        .unwrap();
+    }
+
+    pub fn get_pending_operations(
+        conn: &Connection,
+        db_uuid: &str,
+        collection_name: &str,
+    ) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT operation_hash from sparse_pending_operations where db_uuid = ?1 and collection_name = ?2 order by id;")
+            .unwrap();
+        stmt.query_map((db_uuid, collection_name), |row| row.get(0))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    pub fn get_pending_collections(conn: &Connection, db_uuid: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT distinct collection_name from sparse_pending_operations where db_uuid = ?1 order by collection_name;")
+            .unwrap();
+        stmt.query_map((db_uuid,), |row| row.get(0))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    pub fn clear_pending_operations(conn: &Connection, db_uuid: &str, collection_name: &str) {
+        conn.execute(
+            "DELETE FROM sparse_pending_operations where db_uuid = ?1 and collection_name = ?2;",
+            (db_uuid, collection_name),
+        )
+        .unwrap();
+    }
+}
+
+// The segments written by GFA exports. Segment IDs of alignments made against an exported GFA are
+// resolved through the export that wrote them, and as nodes don't change once they're created,
+// this still holds after later operations change the graph.
+pub struct GfaExport {}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExportedSegment {
+    pub segment_id: String,
+    pub node_id: i64,
+    pub sequence_start: i64,
+    pub sequence_end: i64,
+}
+
+impl Query for ExportedSegment {
+    type Model = ExportedSegment;
+    fn process_row(row: &Row) -> Self::Model {
+        ExportedSegment {
+            segment_id: row.get(0).unwrap(),
+            node_id: row.get(1).unwrap(),
+            sequence_start: row.get(2).unwrap(),
+            sequence_end: row.get(3).unwrap(),
+        }
+    }
+}
+
+impl GfaExport {
+    // Records an export as made at the current operation of the database.
+    pub fn record(
+        conn: &Connection,
+        db_uuid: &str,
+        collection_name: &str,
+        file_path: &str,
+        segments: &[ExportedSegment],
+    ) -> i64 {
+        let operation_hash = OperationState::get_operation(conn, db_uuid);
+        let export_id: i64 = conn
+            .query_row(
+                "INSERT INTO gfa_export (db_uuid, operation_hash, collection_name, file_path) VALUES (?1, ?2, ?3, ?4) RETURNING (id);",
+                (db_uuid, operation_hash, collection_name, file_path),
+                |row| row.get(0),
+            )
+            .unwrap();
+        let mut stmt = conn
+            .prepare_cached(
+                "INSERT OR IGNORE INTO gfa_export_segment (gfa_export_id, segment_id, node_id, sequence_start, sequence_end) VALUES (?1, ?2, ?3, ?4, ?5);",
+            )
+            .unwrap();
+        for segment in segments {
+            stmt.execute((
+                export_id,
+                &segment.segment_id,
+                segment.node_id,
+                segment.sequence_start,
+                segment.sequence_end,
+            ))
+            .unwrap();
+        }
+        export_id
+    }
+
+    // The segment of the most recent export of the database with the given ID.
+    pub fn get_segment(
+        conn: &Connection,
+        db_uuid: &str,
+        segment_id: &str,
+    ) -> Option<ExportedSegment> {
+        ExportedSegment::get(
+            conn,
+            "SELECT s.segment_id, s.node_id, s.sequence_start, s.sequence_end FROM gfa_export_segment s JOIN gfa_export e ON e.id = s.gfa_export_id WHERE e.db_uuid = ?1 AND s.segment_id = ?2 ORDER BY e.id DESC LIMIT 1;",
+            (db_uuid, segment_id),
+        )
+        .ok()
+    }
+}
+
+// A name given to an operation or a sample of a database. A tag of an operation can be given
+// wherever an operation hash is accepted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tag {
+    pub db_uuid: String,
+    pub name: String,
+    pub operation_hash: Option<String>,
+    pub sample_name: Option<String>,
+}
+
+impl Query for Tag {
+    type Model = Tag;
+    fn process_row(row: &Row) -> Self::Model {
+        Tag {
+            db_uuid: row.get(0).unwrap(),
+            name: row.get(1).unwrap(),
+            operation_hash: row.get(2).unwrap(),
+            sample_name: row.get(3).unwrap(),
+        }
+    }
+}
+
+impl Tag {
+    // Tag names can't be mistaken for the parts of an operation range given to patch-create.
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.contains(',') && !name.contains("..") && !name.starts_with("HEAD")
+    }
+
+    // Tags either an operation or a sample. Fails if the database already has a tag with the name.
+    pub fn create(
+        conn: &Connection,
+        db_uuid: &str,
+        name: &str,
+        operation_hash: Option<&str>,
+        sample_name: Option<&str>,
+    ) -> SQLResult<Tag> {
+        conn.execute(
+            "INSERT INTO tag (db_uuid, name, operation_hash, sample_name) VALUES (?1, ?2, ?3, ?4);",
+            (db_uuid, name, operation_hash, sample_name),
+        )?;
+        Ok(Tag {
+            db_uuid: db_uuid.to_string(),
+            name: name.to_string(),
+            operation_hash: operation_hash.map(str::to_string),
+            sample_name: sample_name.map(str::to_string),
+        })
+    }
+
+    pub fn delete(conn: &Connection, db_uuid: &str, name: &str) -> bool {
+        conn.execute(
+            "DELETE FROM tag WHERE db_uuid = ?1 AND name = ?2;",
+            (db_uuid, name),
+        )
+        .unwrap()
+            > 0
+    }
+
+    pub fn get_by_name(conn: &Connection, db_uuid: &str, name: &str) -> Option<Tag> {
+        Tag::get(
+            conn,
+            "SELECT * FROM tag WHERE db_uuid = ?1 AND name = ?2;",
+            (db_uuid, name),
+        )
+        .ok()
+    }
+
+    pub fn query_for_db(conn: &Connection, db_uuid: &str) -> Vec<Tag> {
+        Tag::query(
+            conn,
+            "SELECT * FROM tag WHERE db_uuid = ?1 ORDER BY name;",
+            (db_uuid,),
+        )
+    }
+
+    // The hash of the operation a tag names, or the reference itself if it isn't the tag of an
+    // operation, so it can be used as a hash.
+    pub fn resolve_operation(conn: &Connection, db_uuid: &str, reference: &str) -> String {
+        Tag::get_by_name(conn, db_uuid, reference)
+            .and_then(|tag| tag.operation_hash)
+            .unwrap_or_else(|| reference.to_string())
+    }
+}
+
+// A named coordinate system of a database: positions along a path of a sample, counted from an
+// offset into the path. Without a path, the frame is on the current path of each graph.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoordinateFrame {
+    pub db_uuid: String,
+    pub name: String,
+    pub sample_name: Option<String>,
+    pub path_name: Option<String>,
+    pub offset: i64,
+}
+
+impl Query for CoordinateFrame {
+    type Model = CoordinateFrame;
+    fn process_row(row: &Row) -> Self::Model {
+        CoordinateFrame {
+            db_uuid: row.get(0).unwrap(),
+            name: row.get(1).unwrap(),
+            sample_name: row.get(2).unwrap(),
+            path_name: row.get(3).unwrap(),
+            offset: row.get(4).unwrap(),
+        }
+    }
+}
+
+impl CoordinateFrame {
+    // Fails if the database already has a frame with the name.
+    pub fn create(
+        conn: &Connection,
+        db_uuid: &str,
+        name: &str,
+        sample_name: Option<&str>,
+        path_name: Option<&str>,
+        offset: i64,
+    ) -> SQLResult<CoordinateFrame> {
+        conn.execute(
+            "INSERT INTO coordinate_frame (db_uuid, name, sample_name, path_name, offset) VALUES (?1, ?2, ?3, ?4, ?5);",
+            (db_uuid, name, sample_name, path_name, offset),
+        )?;
+        Ok(CoordinateFrame {
+            db_uuid: db_uuid.to_string(),
+            name: name.to_string(),
+            sample_name: sample_name.map(str::to_string),
+            path_name: path_name.map(str::to_string),
+            offset,
+        })
+    }
+
+    pub fn delete(conn: &Connection, db_uuid: &str, name: &str) -> bool {
+        conn.execute(
+            "DELETE FROM coordinate_frame WHERE db_uuid = ?1 AND name = ?2;",
+            (db_uuid, name),
+        )
+        .unwrap()
+            > 0
+    }
+
+    pub fn get_by_name(conn: &Connection, db_uuid: &str, name: &str) -> Option<CoordinateFrame> {
+        CoordinateFrame::get(
+            conn,
+            "SELECT * FROM coordinate_frame WHERE db_uuid = ?1 AND name = ?2;",
+            (db_uuid, name),
+        )
+        .ok()
+    }
+
+    pub fn query_for_db(conn: &Connection, db_uuid: &str) -> Vec<CoordinateFrame> {
+        CoordinateFrame::query(
+            conn,
+            "SELECT * FROM coordinate_frame WHERE db_uuid = ?1 ORDER BY name;",
+            (db_uuid,),
+        )
+    }
+
+    // The frame a name given as a coordinate frame stands for. A name that isn't a frame of the
+    // database is a sample, on its current paths, as frames were given before they had names.
+    pub fn resolve(conn: &Connection, db_uuid: &str, name: &str) -> CoordinateFrame {
+        CoordinateFrame::get_by_name(conn, db_uuid, name).unwrap_or_else(|| CoordinateFrame {
+            db_uuid: db_uuid.to_string(),
+            name: name.to_string(),
+            sample_name: Some(name.to_string()),
+            path_name: None,
+            offset: 0,
+        })
+    }
+
+    // Whether coordinates in the frame are those of a sample's current paths, so it can stand in
+    // for the sample where only a sample is accepted.
+    pub fn is_sample_only(&self) -> bool {
+        self.path_name.is_none() && self.offset == 0
+    }
+
+    // The position on the frame's path of a coordinate in the frame. Coordinates counted back from
+    // the end of the path, which are negative, are left as they are.
+    pub fn to_path_coordinate(&self, coordinate: i64) -> i64 {
+        if coordinate < 0 {
+            coordinate
+        } else {
+            coordinate + self.offset
+        }
+    }
+
+    // A region in the frame as a region of the frame's path. A region without a start starts at
+    // the start of the frame.
+    pub fn to_path_region(&self, region: &Region) -> Region {
+        let start = match region.start {
+            None if self.offset == 0 => None,
+            start => Some(self.to_path_coordinate(start.unwrap_or(0))),
+        };
+        Region {
+            name: region.name.clone(),
+            start,
+            end: region.end.map(|end| self.to_path_coordinate(end)),
+        }
+    }
+}
+
 pub struct OperationState {}
 
 impl OperationState {
@@ -953,6 +1489,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_branch_divergence() {
+        // main is 1 -> 2 -> 3 and branch-1 is made at 2 and adds 4 -> 5
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = &metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, db_uuid);
+
+        let empty_branch = Branch::create(op_conn, db_uuid, "empty");
+        create_operation(conn, op_conn, "test.fasta", FileTypes::Fasta, "foo", "op-1");
+        create_operation(conn, op_conn, "test.fasta", FileTypes::Fasta, "foo", "op-2");
+        Branch::create(op_conn, db_uuid, "branch-1");
+        create_operation(conn, op_conn, "test.fasta", FileTypes::Fasta, "foo", "op-3");
+        OperationState::set_operation(op_conn, db_uuid, "op-2");
+        OperationState::set_branch(op_conn, db_uuid, "branch-1");
+        create_operation(conn, op_conn, "test.fasta", FileTypes::Fasta, "foo", "op-4");
+        create_operation(conn, op_conn, "test.fasta", FileTypes::Fasta, "foo", "op-5");
+
+        let main = Branch::get_by_name(op_conn, db_uuid, "main").unwrap();
+        let branch_1 = Branch::get_by_name(op_conn, db_uuid, "branch-1").unwrap();
+        assert_eq!(
+            Operation::common_ancestor(op_conn, "op-3", "op-5"),
+            Some("op-2".to_string())
+        );
+        assert_eq!(
+            Branch::divergence(op_conn, &branch_1, &main),
+            BranchDivergence {
+                branch: "branch-1".to_string(),
+                other_branch: "main".to_string(),
+                common_ancestor: Some("op-2".to_string()),
+                ahead: 2,
+                behind: 1,
+            }
+        );
+        let from_main = Branch::divergence(op_conn, &main, &branch_1);
+        assert_eq!((from_main.ahead, from_main.behind), (1, 2));
+        let from_empty = Branch::divergence(op_conn, &empty_branch, &main);
+        assert_eq!(from_empty.common_ancestor, None);
+        assert_eq!((from_empty.ahead, from_empty.behind), (0, 3));
+    }
+
     #[test]
     fn test_sets_start_operation_hash_on_first_change() {
         setup_gen_dir();
@@ -991,4 +1569,179 @@ mod tests {
             vec![op_2.hash.clone()]
         );
     }
+
+    #[test]
+    fn test_records_operation_metadata() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = &metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, db_uuid);
+        op_conn
+            .execute(
+                "update defaults set author = ?1, email = ?2 where id = 1",
+                ("Ada", "ada@example.com"),
+            )
+            .unwrap();
+
+        let change = FileAddition::create(op_conn, "foo", FileTypes::Fasta);
+        let op_1 =
+            Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-1-hash").unwrap();
+        assert_eq!(op_1.author, Some("Ada".to_string()));
+        assert_eq!(op_1.email, Some("ada@example.com".to_string()));
+        assert!(op_1.timestamp.is_some());
+        assert_eq!(op_1.message, None);
+
+        Operation::set_message(op_conn, &op_1.hash, "initial import").unwrap();
+        let op_1 = Operation::get_by_hash(op_conn, "op-1").unwrap();
+        assert_eq!(op_1.author, Some("Ada".to_string()));
+        assert_eq!(op_1.message, Some("initial import".to_string()));
+    }
+
+    #[test]
+    fn test_records_invocation() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = &metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, db_uuid);
+
+        let change = FileAddition::create(op_conn, "foo", FileTypes::Fasta);
+        Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-1-hash").unwrap();
+        let mut op_1 = Operation::get_by_hash(op_conn, "op-1").unwrap();
+        assert_eq!(op_1.command, Some(env::args().collect::<Vec<String>>()));
+        assert_eq!(
+            op_1.gen_version,
+            Some(env!("CARGO_PKG_VERSION").to_string())
+        );
+        assert!(op_1.working_directory.is_some());
+
+        op_1.command = Some(vec![
+            "gen".to_string(),
+            "update".to_string(),
+            "--vcf".to_string(),
+            "my file's.vcf".to_string(),
+            "--genotype=0/1".to_string(),
+        ]);
+        assert_eq!(
+            op_1.command_line(),
+            Some("gen update --vcf 'my file'\\''s.vcf' --genotype=0/1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tags_operations_and_samples() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = &metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, db_uuid);
+        let operation =
+            create_operation(conn, op_conn, "test.fasta", FileTypes::Fasta, "foo", "op-1");
+
+        let tag = Tag::create(
+            op_conn,
+            db_uuid,
+            "design-v3-frozen",
+            Some(&operation.hash),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            Tag::get_by_name(op_conn, db_uuid, "design-v3-frozen"),
+            Some(tag)
+        );
+        assert_eq!(
+            Tag::resolve_operation(op_conn, db_uuid, "design-v3-frozen"),
+            operation.hash
+        );
+        assert_eq!(Tag::resolve_operation(op_conn, db_uuid, "op-1"), "op-1");
+        // tag names are unique within a database
+        assert!(Tag::create(op_conn, db_uuid, "design-v3-frozen", None, Some("child")).is_err());
+        assert!(Tag::create(
+            op_conn,
+            "other-db",
+            "design-v3-frozen",
+            Some(&operation.hash),
+            None
+        )
+        .is_ok());
+
+        Tag::create(op_conn, db_uuid, "parent-strain", None, Some("child")).unwrap();
+        // a sample tag doesn't stand for an operation
+        assert_eq!(
+            Tag::resolve_operation(op_conn, db_uuid, "parent-strain"),
+            "parent-strain"
+        );
+        assert!(Tag::create(op_conn, db_uuid, "neither", None, None).is_err());
+        assert_eq!(
+            Tag::query_for_db(op_conn, db_uuid)
+                .iter()
+                .map(|tag| tag.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["design-v3-frozen", "parent-strain"]
+        );
+
+        assert!(Tag::delete(op_conn, db_uuid, "parent-strain"));
+        assert!(!Tag::delete(op_conn, db_uuid, "parent-strain"));
+        assert!(Tag::is_valid_name("v1.2"));
+        assert!(!Tag::is_valid_name("a..b"));
+        assert!(!Tag::is_valid_name("HEAD~1"));
+    }
+
+    #[test]
+    fn test_coordinate_frames() {
+        let op_conn = &get_operation_connection(None);
+        let db_uuid = "db";
+        let frame = CoordinateFrame::create(
+            op_conn,
+            db_uuid,
+            "plasmid",
+            Some("child"),
+            Some("m123"),
+            100,
+        )
+        .unwrap();
+        assert_eq!(
+            CoordinateFrame::get_by_name(op_conn, db_uuid, "plasmid"),
+            Some(frame.clone())
+        );
+        assert!(CoordinateFrame::create(op_conn, db_uuid, "plasmid", None, None, 0).is_err());
+        let reference =
+            CoordinateFrame::create(op_conn, db_uuid, "reference", None, None, 0).unwrap();
+        assert!(reference.is_sample_only());
+        assert!(!frame.is_sample_only());
+        assert_eq!(
+            CoordinateFrame::query_for_db(op_conn, db_uuid)
+                .iter()
+                .map(|frame| frame.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["plasmid", "reference"]
+        );
+
+        assert_eq!(frame.to_path_coordinate(5), 105);
+        // positions counted back from the end of the path aren't offset
+        assert_eq!(frame.to_path_coordinate(-5), -5);
+        let region = Region {
+            name: "m123".to_string(),
+            start: None,
+            end: Some(10),
+        };
+        assert_eq!(
+            frame.to_path_region(&region),
+            Region {
+                name: "m123".to_string(),
+                start: Some(100),
+                end: Some(110),
+            }
+        );
+        assert_eq!(reference.to_path_region(&region), region);
+        assert_eq!(CoordinateFrame::resolve(op_conn, db_uuid, "plasmid"), frame);
+        let sample_frame = CoordinateFrame::resolve(op_conn, db_uuid, "child");
+        assert_eq!(sample_frame.sample_name, Some("child".to_string()));
+        assert!(sample_frame.is_sample_only());
+
+        assert!(CoordinateFrame::delete(op_conn, db_uuid, "plasmid"));
+        assert!(!CoordinateFrame::delete(op_conn, db_uuid, "plasmid"));
+    }
 }
@@ -1,5 +1,5 @@
 use core::ops::Range as RustRange;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use intervaltree::IntervalTree;
 use itertools::Itertools;
@@ -7,13 +7,14 @@ use rusqlite::types::Value;
 use rusqlite::{params_from_iter, Connection, Row};
 use serde::{Deserialize, Serialize};
 
-use crate::models::block_group::NodeIntervalBlock;
+use crate::models::block_group::{BlockGroup, NodeIntervalBlock};
 use crate::models::{
     block_group_edge::BlockGroupEdge,
     edge::Edge,
     node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID},
     path_edge::PathEdge,
-    sequence::Sequence,
+    path_index::PathIndex,
+    sequence::{Molecule, Sequence},
     strand::Strand,
     traits::*,
 };
@@ -32,37 +33,59 @@ pub struct PathData {
     pub block_group_id: i64,
 }
 
-// interesting gist here: https://gist.github.com/mbhall88/cd900add6335c96127efea0e0f6a9f48, see if we
-// can expand this to ambiguous bases/keep case
+// The complements of the IUPAC nucleotide codes of DNA. An ambiguity code pairs with the code for
+// the complements of the bases it stands for, so R (A or G) pairs with Y (C or T). U is left out,
+// as A pairs with T here; Molecule::reverse_complement handles RNA.
+const COMPLEMENTS: [(u8, u8); 15] = [
+    (b'A', b'T'),
+    (b'C', b'G'),
+    (b'G', b'C'),
+    (b'T', b'A'),
+    (b'R', b'Y'),
+    (b'Y', b'R'),
+    (b'S', b'S'),
+    (b'W', b'W'),
+    (b'K', b'M'),
+    (b'M', b'K'),
+    (b'B', b'V'),
+    (b'V', b'B'),
+    (b'D', b'H'),
+    (b'H', b'D'),
+    (b'N', b'N'),
+];
+
+// The complement of every ASCII character, keeping case. Gaps and characters that aren't
+// nucleotide codes are left as they are.
+const fn complement_table() -> [u8; 128] {
+    let mut table = [0; 128];
+    let mut character = 0;
+    while character < table.len() {
+        table[character] = character as u8;
+        character += 1;
+    }
+    let mut index = 0;
+    while index < COMPLEMENTS.len() {
+        let (base, complement) = COMPLEMENTS[index];
+        table[base as usize] = complement;
+        table[base.to_ascii_lowercase() as usize] = complement.to_ascii_lowercase();
+        index += 1;
+    }
+    table
+}
+
+static COMPLEMENT_TABLE: [u8; 128] = complement_table();
+
 pub fn revcomp(seq: &str) -> String {
-    String::from_utf8(
-        seq.chars()
-            .rev()
-            .map(|c| -> u8 {
-                let is_upper = c.is_ascii_uppercase();
-                let rc = c as u8;
-                let v = if rc == 78 {
-                    // N
-                    rc
-                } else if rc == 110 {
-                    // n
-                    rc
-                } else if rc & 2 != 0 {
-                    // CG
-                    rc ^ 4
-                } else {
-                    // AT
-                    rc ^ 21
-                };
-                if is_upper {
-                    v
-                } else {
-                    v.to_ascii_lowercase()
-                }
-            })
-            .collect(),
-    )
-    .unwrap()
+    seq.chars()
+        .rev()
+        .map(|c| {
+            if c.is_ascii() {
+                COMPLEMENT_TABLE[c as usize] as char
+            } else {
+                c
+            }
+        })
+        .collect()
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -77,11 +100,30 @@ pub struct PathBlock {
     pub strand: Strand,
 }
 
-#[derive(Clone, Debug)]
+impl PathBlock {
+    // The range of the path covered by a range of the block's node sequence. Blocks on the reverse
+    // strand are read from the end of their sequence, so the range is mirrored within the block.
+    pub fn path_range(&self, sequence_range: &Range) -> Range {
+        if self.strand == Strand::Reverse {
+            Range {
+                start: self.path_start + (self.sequence_end - sequence_range.end),
+                end: self.path_start + (self.sequence_end - sequence_range.start),
+            }
+        } else {
+            Range {
+                start: self.path_start + (sequence_range.start - self.sequence_start),
+                end: self.path_start + (sequence_range.end - self.sequence_start),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Annotation {
     pub name: String,
     pub start: i64,
     pub end: i64,
+    pub strand: Strand,
 }
 
 impl Path {
@@ -200,6 +242,7 @@ impl Path {
         };
 
         PathEdge::bulk_create(conn, path.id, edge_ids);
+        PathIndex::refresh(conn, path.id);
 
         path
     }
@@ -219,6 +262,33 @@ impl Path {
         rows.next().unwrap().unwrap()
     }
 
+    // The path by its collection, sample, graph and name, which unlike its id is the same in every
+    // repository holding it.
+    pub fn content_key(&self, conn: &Connection) -> String {
+        let block_group = BlockGroup::get_by_id(conn, self.block_group_id);
+        format!(
+            "{collection}.{sample}.{graph}.{path}",
+            collection = block_group.collection_name,
+            sample = block_group.sample_name.unwrap_or_default(),
+            graph = block_group.name,
+            path = self.name
+        )
+    }
+
+    // Path names are unique within a block group, so this fails if the block group already has a
+    // path with the new name.
+    pub fn rename(&self, conn: &Connection, name: &str) -> SQLResult<Path> {
+        conn.execute(
+            "UPDATE paths SET name = ?2 WHERE id = ?1",
+            params_from_iter(vec![Value::from(self.id), Value::from(name.to_string())]),
+        )?;
+        Ok(Path {
+            id: self.id,
+            block_group_id: self.block_group_id,
+            name: name.to_string(),
+        })
+    }
+
     pub fn query_for_collection(conn: &Connection, collection_name: &str) -> Vec<Path> {
         let query = "SELECT * FROM paths JOIN block_groups ON paths.block_group_id = block_groups.id WHERE block_groups.collection_name = ?1";
         Path::query(
@@ -237,6 +307,128 @@ impl Path {
             .join("")
     }
 
+    // Paths of circular block groups, such as plasmids, continue from their end back into their
+    // start.
+    pub fn is_circular(&self, conn: &Connection) -> bool {
+        conn.query_row(
+            "SELECT is_circular FROM block_groups WHERE id = ?1",
+            [self.block_group_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    pub fn length(&self, conn: &Connection) -> i64 {
+        PathIndex::path_length(conn, self.id)
+    }
+
+    // The sequence of a range of the path. A range that starts after its end wraps around the
+    // origin, which is only allowed on circular paths.
+    pub fn subsequence(&self, conn: &Connection, range: &Range) -> String {
+        if range.is_wraparound() {
+            assert!(
+                self.is_circular(conn),
+                "Only circular paths have ranges that wrap around the origin"
+            );
+            format!(
+                "{}{}",
+                self.sequence_range(conn, range.start, self.length(conn)),
+                self.sequence_range(conn, 0, range.end)
+            )
+        } else {
+            self.sequence_range(conn, range.start, range.end)
+        }
+    }
+
+    // The sequence from start to end of the path. Only the parts of node sequences in the range are
+    // read, found through the path index, so a region of a chromosome can be fetched without loading the whole chromosome.
+    pub fn sequence_range(&self, conn: &Connection, start: i64, end: i64) -> String {
+        let blocks = PathIndex::blocks_in_range(conn, self.id, start, end);
+        let sequence_hashes_by_node_id = Node::get_nodes(
+            conn,
+            &blocks
+                .iter()
+                .map(|block| block.node_id)
+                .unique()
+                .collect::<Vec<i64>>(),
+        )
+        .into_iter()
+        .map(|node| (node.id, node.sequence_hash))
+        .collect::<HashMap<i64, String>>();
+
+        blocks
+            .iter()
+            .map(|block| {
+                let sequence_hash = &sequence_hashes_by_node_id[&block.node_id];
+                let offset_start = start.max(block.start) - block.start;
+                let offset_end = end.min(block.end) - block.start;
+                if block.strand == Strand::Reverse {
+                    let sequence_type: String = conn
+                        .query_row(
+                            "select sequence_type from sequences where hash = ?1",
+                            [sequence_hash],
+                            |row| row.get(0),
+                        )
+                        .unwrap();
+                    Molecule::from_sequence_type(&sequence_type).reverse_complement(
+                        &Sequence::sequence_slice(
+                            conn,
+                            sequence_hash,
+                            block.sequence_end - offset_end,
+                            block.sequence_end - offset_start,
+                        ),
+                    )
+                } else {
+                    Sequence::sequence_slice(
+                        conn,
+                        sequence_hash,
+                        block.sequence_start + offset_start,
+                        block.sequence_start + offset_end,
+                    )
+                }
+            })
+            .collect()
+    }
+
+    // The sequence of a range of the path in pieces of at most chunk_size bases, read as they're
+    // needed so a region of any size can be written out without holding all of it. Like
+    // subsequence, ranges wrapping around the origin are only allowed on circular paths.
+    pub fn sequence_chunks<'a>(
+        &self,
+        conn: &'a Connection,
+        range: &Range,
+        chunk_size: i64,
+    ) -> SequenceChunks<'a> {
+        assert!(
+            chunk_size > 0,
+            "Sequence chunks must have at least one base"
+        );
+        let ranges = if range.is_wraparound() {
+            assert!(
+                self.is_circular(conn),
+                "Only circular paths have ranges that wrap around the origin"
+            );
+            vec![
+                Range {
+                    start: range.start,
+                    end: self.length(conn),
+                },
+                Range {
+                    start: 0,
+                    end: range.end,
+                },
+            ]
+        } else {
+            vec![range.clone()]
+        };
+        SequenceChunks {
+            conn,
+            path: self.clone(),
+            ranges: ranges.into(),
+            chunk_size,
+        }
+    }
+
     pub fn edge_pairs_to_block(
         &self,
         block_id: i64,
@@ -253,7 +445,9 @@ impl Path {
         let block_sequence_length = end - start;
 
         let block_sequence = if strand == Strand::Reverse {
-            revcomp(&sequence.get_sequence(start, end))
+            sequence
+                .molecule()
+                .reverse_complement(&sequence.get_sequence(start, end))
         } else {
             sequence.get_sequence(start, end)
         };
@@ -334,25 +528,33 @@ impl Path {
     }
 
     pub fn intervaltree(&self, conn: &Connection) -> IntervalTree<i64, NodeIntervalBlock> {
-        let blocks = self.blocks(conn);
-        let tree: IntervalTree<i64, NodeIntervalBlock> = blocks
-            .into_iter()
-            .map(|block| {
-                (
-                    block.path_start..block.path_end,
-                    NodeIntervalBlock {
-                        block_id: block.id,
-                        node_id: block.node_id,
-                        start: block.path_start,
-                        end: block.path_end,
-                        sequence_start: block.sequence_start,
-                        sequence_end: block.sequence_end,
-                        strand: block.strand,
-                    },
-                )
-            })
-            .collect();
-        tree
+        // The blocks come from the path index, so no sequences are loaded. As in blocks(), the
+        // start and end blocks extend the path in both directions.
+        let blocks = PathIndex::blocks(conn, self.id);
+        let path_length = blocks.last().map_or(0, |block| block.end);
+        let start_block = NodeIntervalBlock {
+            block_id: -1,
+            node_id: PATH_START_NODE_ID,
+            start: i64::MIN + 1,
+            end: 0,
+            sequence_start: 0,
+            sequence_end: 0,
+            strand: Strand::Forward,
+        };
+        let end_block = NodeIntervalBlock {
+            block_id: -2,
+            node_id: PATH_END_NODE_ID,
+            start: path_length,
+            end: i64::MAX - 1,
+            sequence_start: 0,
+            sequence_end: 0,
+            strand: Strand::Forward,
+        };
+        std::iter::once(start_block)
+            .chain(blocks)
+            .chain(std::iter::once(end_block))
+            .map(|block| (block.start..block.end, block))
+            .collect()
     }
 
     pub fn find_block_mappings(&self, conn: &Connection, other_path: &Path) -> Vec<RangeMapping> {
@@ -439,23 +641,13 @@ impl Path {
                             }
 
                             let common_range = &common_ranges[0];
-                            let our_start = our_block.path_start
-                                + (common_range.start - our_block.sequence_start);
-                            let our_end = our_block.path_start
-                                + (common_range.end - our_block.sequence_start);
-                            let their_start = their_block.path_start
-                                + (common_range.start - their_block.sequence_start);
-                            let their_end = their_block.path_start
-                                + (common_range.end - their_block.sequence_start);
-
                             let mapping = RangeMapping {
-                                source_range: Range {
-                                    start: our_start,
-                                    end: our_end,
-                                },
-                                target_range: Range {
-                                    start: their_start,
-                                    end: their_end,
+                                source_range: our_block.path_range(common_range),
+                                target_range: their_block.path_range(common_range),
+                                strand: if our_block.strand == their_block.strand {
+                                    Strand::Forward
+                                } else {
+                                    Strand::Reverse
                                 },
                             };
                             mappings.push(mapping);
@@ -478,10 +670,58 @@ impl Path {
             .collect::<Vec<RangeMapping>>()
     }
 
+    pub fn find_differences(&self, conn: &Connection, other_path: &Path) -> Vec<RangeMapping> {
+        // The complement of find_block_mappings: a list of ranges of this path that are replaced
+        // by a range of the other path.  One of the two ranges is empty for insertions and
+        // deletions.  Like the GFA diff, this assumes shared blocks are in the same order on both
+        // paths.
+        let mut differences = vec![];
+        let mut last_source_position = 0;
+        let mut last_target_position = 0;
+        let mut ends = self
+            .find_block_mappings(conn, other_path)
+            .into_iter()
+            .map(|mapping| (mapping.source_range, mapping.target_range))
+            .collect::<Vec<(Range, Range)>>();
+        let source_len = self.sequence(conn).len() as i64;
+        let target_len = other_path.sequence(conn).len() as i64;
+        ends.push((
+            Range {
+                start: source_len,
+                end: source_len,
+            },
+            Range {
+                start: target_len,
+                end: target_len,
+            },
+        ));
+        for (source_range, target_range) in ends {
+            let source_start = source_range.start.max(last_source_position);
+            let target_start = target_range.start.max(last_target_position);
+            if source_start > last_source_position || target_start > last_target_position {
+                differences.push(RangeMapping {
+                    source_range: Range {
+                        start: last_source_position,
+                        end: source_start,
+                    },
+                    target_range: Range {
+                        start: last_target_position,
+                        end: target_start,
+                    },
+                    strand: Strand::Forward,
+                });
+            }
+            last_source_position = last_source_position.max(source_range.end);
+            last_target_position = last_target_position.max(target_range.end);
+        }
+        differences
+    }
+
     pub fn propagate_annotation(
         annotation: Annotation,
         mapping_tree: &IntervalTree<i64, RangeMapping>,
         sequence_length: i64,
+        is_circular: bool,
     ) -> Option<Annotation> {
         /*
         This method contains the core logic for propagating an annotation from one path to another.
@@ -496,56 +736,91 @@ impl Path {
         3. If the first and last parts of the annotation can be propagated to subregions of the
             other path (but not one or more parts of the middle of the annotation), we propagate the
             entire annotation, including across the parts that don't match those of this path
+
+        On circular paths an annotation may start after it ends, in which case it runs from its
+        start to the end of the path and continues from the origin.
          */
 
         // TODO: Add support for different propagation strategies
-        // TODO: Handle circular contigs
         let start = annotation.start;
         let end = annotation.end;
-        let mappings: Vec<RangeMapping> = mapping_tree
-            .query(RustRange { start, end })
-            .map(|x| x.value.clone())
-            .collect();
-        if mappings.is_empty() {
+        let wraps_origin = is_circular && start > end;
+        let sorted_mappings_in = |start: i64, end: i64| {
+            mapping_tree
+                .query(RustRange { start, end })
+                .map(|x| x.value.clone())
+                .sorted_by(|a, b| a.source_range.start.cmp(&b.source_range.start))
+                .collect::<Vec<RangeMapping>>()
+        };
+        let sorted_mappings = if wraps_origin {
+            let mut mappings = sorted_mappings_in(start, i64::MAX);
+            mappings.extend(sorted_mappings_in(0, end));
+            mappings
+        } else {
+            sorted_mappings_in(start, end)
+        };
+        if sorted_mappings.is_empty() {
             return None;
         }
 
-        let sorted_mappings: Vec<RangeMapping> = mappings
-            .into_iter()
-            .sorted_by(|a, b| a.source_range.start.cmp(&b.source_range.start))
-            .collect();
         let first_mapping = sorted_mappings.first().unwrap();
         let last_mapping = sorted_mappings.last().unwrap();
+        // Positions in a reversed mapping are counted back from the end of its target range, so
+        // the start of the annotation becomes its end on the other path.
+        let translate = |mapping: &RangeMapping, index: i64| {
+            if mapping.strand == Strand::Reverse {
+                Ok(mapping.target_range.end - (index - mapping.source_range.start))
+            } else {
+                mapping.source_range.translate_index(
+                    index,
+                    &mapping.target_range,
+                    sequence_length,
+                    false,
+                )
+            }
+        };
         let translated_start = if first_mapping.source_range.contains(start) {
-            first_mapping.source_range.translate_index(
-                start,
-                &first_mapping.target_range,
-                sequence_length,
-                false,
-            )
+            translate(first_mapping, start)
         } else {
-            Ok(first_mapping.target_range.start)
+            translate(first_mapping, first_mapping.source_range.start)
         };
 
         let translated_end = if last_mapping.source_range.contains(end) {
-            last_mapping.source_range.translate_index(
-                end,
-                &last_mapping.target_range,
-                sequence_length,
-                false,
-            )
+            translate(last_mapping, end)
         } else {
-            Ok(last_mapping.target_range.end)
+            translate(last_mapping, last_mapping.source_range.end)
         };
 
-        if translated_start.is_err() || translated_end.is_err() {
+        let (Ok(translated_start), Ok(translated_end)) = (translated_start, translated_end) else {
             return None;
-        }
+        };
 
+        // An annotation is only flipped if it lies entirely in inverted sequence. One that spans
+        // into it is placed across both of its translated ends and keeps its strand.
+        let inverted =
+            first_mapping.strand == Strand::Reverse && last_mapping.strand == Strand::Reverse;
+        let strand = if inverted {
+            annotation.strand.flipped()
+        } else {
+            annotation.strand
+        };
+        // The ends of an annotation across the origin can't be ordered by position, they are only
+        // swapped if the annotation was inverted.
+        let (start, end) = if !wraps_origin {
+            (
+                translated_start.min(translated_end),
+                translated_start.max(translated_end),
+            )
+        } else if inverted {
+            (translated_end, translated_start)
+        } else {
+            (translated_start, translated_end)
+        };
         Some(Annotation {
             name: annotation.name,
-            start: translated_start.expect("Failed to translate start"),
-            end: translated_end.expect("Failed to translate end"),
+            start,
+            end,
+            strand,
         })
     }
 
@@ -574,10 +849,16 @@ impl Path {
     ) -> Vec<Annotation> {
         let mapping_tree = self.get_mapping_tree(conn, path);
         let sequence_length = path.sequence(conn).len();
+        let is_circular = path.is_circular(conn);
         annotations
             .into_iter()
             .filter_map(|annotation| {
-                Path::propagate_annotation(annotation, &mapping_tree, sequence_length as i64)
+                Path::propagate_annotation(
+                    annotation,
+                    &mapping_tree,
+                    sequence_length as i64,
+                    is_circular,
+                )
             })
             .clone()
             .collect()
@@ -729,6 +1010,34 @@ impl Path {
     }
 }
 
+pub struct SequenceChunks<'a> {
+    conn: &'a Connection,
+    path: Path,
+    ranges: VecDeque<Range>,
+    chunk_size: i64,
+}
+
+impl Iterator for SequenceChunks<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while let Some(range) = self.ranges.pop_front() {
+            if range.start >= range.end {
+                continue;
+            }
+            let chunk_end = range.end.min(range.start + self.chunk_size);
+            if chunk_end < range.end {
+                self.ranges.push_front(Range {
+                    start: chunk_end,
+                    end: range.end,
+                });
+            }
+            return Some(self.path.sequence_range(self.conn, range.start, chunk_end));
+        }
+        None
+    }
+}
+
 impl Query for Path {
     type Model = Path;
     fn process_row(row: &Row) -> Self::Model {
@@ -922,6 +1231,37 @@ mod tests {
 
         let path = Path::create(conn, "chr1", block_group.id, &edge_ids);
         assert_eq!(path.sequence(conn), "CCCCCCCGGGGGGGTTTTTTTCGATCGAT");
+
+        // every range read from the node sequences matches the same slice of the whole path
+        let sequence = path.sequence(conn);
+        assert_eq!(path.length(conn), sequence.len() as i64);
+        for start in 0..sequence.len() {
+            for end in start..=sequence.len() {
+                assert_eq!(
+                    path.sequence_range(conn, start as i64, end as i64),
+                    sequence[start..end]
+                );
+            }
+        }
+
+        // chunks of the range join back into its sequence
+        let range = Range { start: 3, end: 26 };
+        let chunks = path
+            .sequence_chunks(conn, &range, 5)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.len())
+                .collect::<Vec<usize>>(),
+            vec![5, 5, 5, 5, 3]
+        );
+        assert_eq!(chunks.concat(), sequence[3..26]);
+        assert_eq!(
+            path.sequence_chunks(conn, &Range { start: 4, end: 4 }, 5)
+                .count(),
+            0
+        );
     }
 
     #[test]
@@ -929,6 +1269,68 @@ mod tests {
         assert_eq!(revcomp("ATCCGG"), "CCGGAT");
         assert_eq!(revcomp("CNNNNA"), "TNNNNG");
         assert_eq!(revcomp("cNNgnAt"), "aTncNNg");
+        assert_eq!(revcomp("RYSWKMBDHV"), "BDHVKMWSRY");
+        assert_eq!(revcomp("ryswkmbdhv"), "bdhvkmwsry");
+        assert_eq!(revcomp("AC-GT.t"), "a.AC-GT");
+        // U isn't a DNA base, so it is left as it is
+        assert_eq!(revcomp("AU"), "UT");
+    }
+
+    #[test]
+    fn test_reverse_complement_properties() {
+        // the bases each IUPAC code stands for
+        let bases = HashMap::from([
+            ('A', "A"),
+            ('C', "C"),
+            ('G', "G"),
+            ('T', "T"),
+            ('R', "AG"),
+            ('Y', "CT"),
+            ('S', "CG"),
+            ('W', "AT"),
+            ('K', "GT"),
+            ('M', "AC"),
+            ('B', "CGT"),
+            ('D', "AGT"),
+            ('H', "ACT"),
+            ('V', "ACG"),
+            ('N', "ACGT"),
+        ]);
+        let complement_bases = |code: char| {
+            bases[&code]
+                .chars()
+                .map(|base| revcomp(&base.to_string()).chars().next().unwrap())
+                .sorted()
+                .collect::<String>()
+        };
+        for (code, _) in bases.iter() {
+            let complement = revcomp(&code.to_string()).chars().next().unwrap();
+            assert_eq!(bases[&complement], complement_bases(*code), "{code}");
+        }
+
+        let alphabet = bases
+            .keys()
+            .flat_map(|code| [*code, code.to_ascii_lowercase()])
+            .chain(['U', 'u', '-', '.'])
+            .collect::<Vec<char>>();
+        for length in 0..=3 {
+            for sequence in (0..length)
+                .map(|_| alphabet.iter())
+                .multi_cartesian_product()
+                .map(|characters| characters.into_iter().collect::<String>())
+            {
+                let reverse_complement = revcomp(&sequence);
+                assert_eq!(revcomp(&reverse_complement), sequence);
+                assert_eq!(reverse_complement.len(), sequence.len());
+                for (original, complement) in sequence.chars().rev().zip(reverse_complement.chars())
+                {
+                    assert_eq!(
+                        original.is_ascii_lowercase(),
+                        complement.is_ascii_lowercase()
+                    );
+                }
+            }
+        }
     }
 
     #[test]
@@ -1547,6 +1949,16 @@ mod tests {
         assert_eq!(mapping2.source_range.end, 8);
         assert_eq!(mapping2.target_range.start, 10);
         assert_eq!(mapping2.target_range.end, 12);
+
+        assert_eq!(
+            path1.find_differences(conn, &path2),
+            vec![RangeMapping {
+                source_range: Range { start: 2, end: 6 },
+                target_range: Range { start: 2, end: 10 },
+                strand: Strand::Forward,
+            }]
+        );
+        assert!(path1.find_differences(conn, &path1).is_empty());
     }
 
     #[test]
@@ -2086,6 +2498,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 8,
+            strand: Strand::Forward,
         };
         let annotations = path.propagate_annotations(conn, &path, vec![annotation]);
         assert_eq!(annotations.len(), 1);
@@ -2189,6 +2602,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 8,
+            strand: Strand::Forward,
         };
         let annotations = path1.propagate_annotations(conn, &path2, vec![annotation]);
         assert_eq!(annotations.len(), 0);
@@ -2303,6 +2717,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 8,
+            strand: Strand::Forward,
         };
         let annotations = path1.propagate_annotations(conn, &path2, vec![annotation]);
         assert_eq!(annotations.len(), 1);
@@ -2417,6 +2832,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 8,
+            strand: Strand::Forward,
         };
 
         let annotations = path1.propagate_annotations(conn, &path2, vec![annotation]);
@@ -2535,6 +2951,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 4,
+            strand: Strand::Forward,
         };
 
         let annotations = path1.propagate_annotations(conn, &path2, vec![annotation]);
@@ -2667,6 +3084,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 16,
+            strand: Strand::Forward,
         };
 
         let annotations = path1.propagate_annotations(conn, &path2, vec![annotation]);
@@ -2785,6 +3203,7 @@ mod tests {
             name: "foo".to_string(),
             start: 0,
             end: 12,
+            strand: Strand::Forward,
         };
 
         let annotations = path1.propagate_annotations(conn, &path2, vec![annotation]);
@@ -2797,6 +3216,254 @@ mod tests {
         assert_eq!(result_annotation.end, 4);
     }
 
+    #[test]
+    fn test_propagate_annotations_with_inversion() {
+        /*
+            path 1: node 1 (0, 8) -> node 2 (0, 8)
+            |ATCGATCG|AAAACCCC|
+
+            path 2: node 1 (0, 8) -> node 2 (0, 8) on the reverse strand
+            |ATCGATCG|GGGGTTTT|
+
+            Mappings: (0, 8) -> (0, 8), (8, 16) -> (8, 16) inverted
+        */
+        let conn = &mut get_connection(None);
+        Collection::create(conn, "test collection");
+        let block_group = BlockGroup::create(conn, "test collection", None, "test block group");
+        let sequence1 = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("ATCGATCG")
+            .save(conn);
+        let node1_id = Node::create(conn, sequence1.hash.as_str(), None);
+        let sequence2 = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("AAAACCCC")
+            .save(conn);
+        let node2_id = Node::create(conn, sequence2.hash.as_str(), None);
+        let edge1 = Edge::create(
+            conn,
+            PATH_START_NODE_ID,
+            -1,
+            Strand::Forward,
+            node1_id,
+            0,
+            Strand::Forward,
+        );
+        let edge2 = Edge::create(
+            conn,
+            node1_id,
+            8,
+            Strand::Forward,
+            node2_id,
+            0,
+            Strand::Forward,
+        );
+        let edge3 = Edge::create(
+            conn,
+            node2_id,
+            8,
+            Strand::Forward,
+            PATH_END_NODE_ID,
+            -1,
+            Strand::Forward,
+        );
+        let edge4 = Edge::create(
+            conn,
+            node1_id,
+            8,
+            Strand::Forward,
+            node2_id,
+            0,
+            Strand::Reverse,
+        );
+        let edge5 = Edge::create(
+            conn,
+            node2_id,
+            8,
+            Strand::Reverse,
+            PATH_END_NODE_ID,
+            -1,
+            Strand::Forward,
+        );
+
+        let edge_ids = [edge1.id, edge2.id, edge3.id, edge4.id, edge5.id];
+        let block_group_edges = edge_ids
+            .iter()
+            .map(|edge_id| BlockGroupEdgeData {
+                block_group_id: block_group.id,
+                edge_id: *edge_id,
+                chromosome_index: 0,
+                phased: 0,
+            })
+            .collect::<Vec<BlockGroupEdgeData>>();
+        BlockGroupEdge::bulk_create(conn, &block_group_edges);
+
+        let path1 = Path::create(
+            conn,
+            "chr1",
+            block_group.id,
+            &[edge1.id, edge2.id, edge3.id],
+        );
+        let path2 = Path::create(
+            conn,
+            "chr2",
+            block_group.id,
+            &[edge1.id, edge4.id, edge5.id],
+        );
+        assert_eq!(path1.sequence(conn), "ATCGATCGAAAACCCC");
+        assert_eq!(path2.sequence(conn), "ATCGATCGGGGGTTTT");
+
+        let mappings = path1.find_block_mappings(conn, &path2);
+        assert_eq!(
+            mappings,
+            vec![
+                RangeMapping {
+                    source_range: Range { start: 0, end: 8 },
+                    target_range: Range { start: 0, end: 8 },
+                    strand: Strand::Forward,
+                },
+                RangeMapping {
+                    source_range: Range { start: 8, end: 16 },
+                    target_range: Range { start: 8, end: 16 },
+                    strand: Strand::Reverse,
+                },
+            ]
+        );
+
+        // The AAAA annotation ends up over TTTT on the other strand
+        let inverted = Annotation {
+            name: "inverted".to_string(),
+            start: 8,
+            end: 12,
+            strand: Strand::Forward,
+        };
+        // An annotation spanning into the inversion keeps its strand
+        let spanning = Annotation {
+            name: "spanning".to_string(),
+            start: 4,
+            end: 12,
+            strand: Strand::Forward,
+        };
+        let annotations = path1.propagate_annotations(conn, &path2, vec![inverted, spanning]);
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[0].name, "inverted");
+        assert_eq!(annotations[0].start, 12);
+        assert_eq!(annotations[0].end, 16);
+        assert_eq!(annotations[0].strand, Strand::Reverse);
+        assert_eq!(annotations[1].name, "spanning");
+        assert_eq!(annotations[1].start, 4);
+        assert_eq!(annotations[1].end, 12);
+        assert_eq!(annotations[1].strand, Strand::Forward);
+    }
+
+    #[test]
+    fn test_propagate_annotations_across_origin() {
+        /*
+            path 1 (one node/sequence) on a circular block group:
+            |ATCGATCG| -> sequence (0, 8)
+
+            path 2: an insertion in the middle of path 1
+            |ATCG|TTTTTTTT|ATCG|
+
+            An annotation of (6, 2) on path 1 covers CG at its end and AT at its start.
+        */
+        let conn = &mut get_connection(None);
+        Collection::create(conn, "test collection");
+        let block_group = BlockGroup::create_with_topology(
+            conn,
+            "test collection",
+            None,
+            "test block group",
+            true,
+        );
+        let sequence1 = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("ATCGATCG")
+            .save(conn);
+        let node1_id = Node::create(conn, sequence1.hash.as_str(), None);
+        let sequence2 = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("TTTTTTTT")
+            .save(conn);
+        let node2_id = Node::create(conn, sequence2.hash.as_str(), None);
+        let edges = [
+            (PATH_START_NODE_ID, -1, node1_id, 0),
+            (node1_id, 8, PATH_END_NODE_ID, -1),
+            (node1_id, 4, node2_id, 0),
+            (node2_id, 8, node1_id, 4),
+        ]
+        .map(|(source, source_coordinate, target, target_coordinate)| {
+            Edge::create(
+                conn,
+                source,
+                source_coordinate,
+                Strand::Forward,
+                target,
+                target_coordinate,
+                Strand::Forward,
+            )
+            .id
+        });
+        let block_group_edges = edges
+            .iter()
+            .map(|edge_id| BlockGroupEdgeData {
+                block_group_id: block_group.id,
+                edge_id: *edge_id,
+                chromosome_index: 0,
+                phased: 0,
+            })
+            .collect::<Vec<BlockGroupEdgeData>>();
+        BlockGroupEdge::bulk_create(conn, &block_group_edges);
+
+        let path1 = Path::create(conn, "chr1", block_group.id, &[edges[0], edges[1]]);
+        let path2 = Path::create(
+            conn,
+            "chr2",
+            block_group.id,
+            &[edges[0], edges[2], edges[3], edges[1]],
+        );
+        assert!(path1.is_circular(conn));
+        assert_eq!(path2.sequence(conn), "ATCGTTTTTTTTATCG");
+
+        let annotation = Annotation {
+            name: "foo".to_string(),
+            start: 6,
+            end: 2,
+            strand: Strand::Forward,
+        };
+        let annotations = path1.propagate_annotations(conn, &path2, vec![annotation.clone()]);
+        assert_eq!(
+            annotations,
+            vec![Annotation {
+                name: "foo".to_string(),
+                start: 14,
+                end: 2,
+                strand: Strand::Forward,
+            }]
+        );
+        assert_eq!(
+            path2.subsequence(conn, &Range { start: 14, end: 2 }),
+            "CGAT"
+        );
+        assert_eq!(
+            path2.subsequence(conn, &Range { start: 2, end: 14 }),
+            "CGTTTTTTTTAT"
+        );
+        assert_eq!(
+            path2
+                .sequence_chunks(conn, &Range { start: 14, end: 2 }, 3)
+                .collect::<Vec<String>>(),
+            vec!["CG", "AT"]
+        );
+
+        // the same annotation has no position on a linear path
+        let mapping_tree = path1.get_mapping_tree(conn, &path2);
+        assert_eq!(
+            Path::propagate_annotation(annotation, &mapping_tree, 16, false),
+            None
+        );
+    }
+
     #[test]
     fn test_new_path_with() {
         let conn = &mut get_connection(None);
@@ -0,0 +1,205 @@
+use crate::config::cache_writes_enabled;
+use crate::models::block_group::NodeIntervalBlock;
+use crate::models::path_edge::PathEdge;
+use crate::models::traits::*;
+use itertools::Itertools;
+use rusqlite::{params, Connection, Row};
+
+// The blocks of a path, cached in the path_index table so coordinate lookups are indexed queries
+// rather than a walk over every edge of the path. Paths are created with the index, and paths
+// created before it existed are indexed on first access, or laid out in memory if caches can't be
+// written.
+pub struct PathIndex;
+
+impl Query for PathIndex {
+    type Model = NodeIntervalBlock;
+    fn process_row(row: &Row) -> Self::Model {
+        NodeIntervalBlock {
+            block_id: row.get(0).unwrap(),
+            node_id: row.get(1).unwrap(),
+            start: row.get(2).unwrap(),
+            end: row.get(3).unwrap(),
+            sequence_start: row.get(4).unwrap(),
+            sequence_end: row.get(5).unwrap(),
+            strand: row.get(6).unwrap(),
+        }
+    }
+}
+
+impl PathIndex {
+    // Lays out the blocks of a path from the coordinates of its edges.
+    pub fn calculate(conn: &Connection, path_id: i64) -> Vec<NodeIntervalBlock> {
+        let mut blocks = vec![];
+        let mut path_length = 0;
+        for (index, (into, out_of)) in PathEdge::edges_for_path(conn, path_id)
+            .into_iter()
+            .tuple_windows()
+            .enumerate()
+        {
+            let block_length = out_of.source_coordinate - into.target_coordinate;
+            blocks.push(NodeIntervalBlock {
+                block_id: index as i64,
+                node_id: into.target_node_id,
+                start: path_length,
+                end: path_length + block_length,
+                sequence_start: into.target_coordinate,
+                sequence_end: out_of.source_coordinate,
+                strand: into.target_strand,
+            });
+            path_length += block_length;
+        }
+        blocks
+    }
+
+    pub fn refresh(conn: &Connection, path_id: i64) {
+        conn.prepare_cached("delete from path_index where path_id = ?1")
+            .unwrap()
+            .execute(params![path_id])
+            .unwrap();
+        let mut insert_stmt = conn
+            .prepare_cached("insert into path_index (path_id, block_id, node_id, path_start, path_end, sequence_start, sequence_end, strand) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")
+            .unwrap();
+        let blocks = PathIndex::calculate(conn, path_id);
+        for block in &blocks {
+            insert_stmt
+                .execute(params![
+                    path_id,
+                    block.block_id,
+                    block.node_id,
+                    block.start,
+                    block.end,
+                    block.sequence_start,
+                    block.sequence_end,
+                    block.strand
+                ])
+                .unwrap();
+        }
+    }
+
+    // Whether the path is indexed, indexing it first if caches can be written.
+    fn ensure(conn: &Connection, path_id: i64) -> bool {
+        let indexed = conn
+            .prepare_cached("select 1 from path_index where path_id = ?1 limit 1")
+            .unwrap()
+            .exists(params![path_id])
+            .unwrap();
+        if !indexed && cache_writes_enabled() {
+            PathIndex::refresh(conn, path_id);
+            return true;
+        }
+        indexed
+    }
+
+    pub fn blocks(conn: &Connection, path_id: i64) -> Vec<NodeIntervalBlock> {
+        if !PathIndex::ensure(conn, path_id) {
+            return PathIndex::calculate(conn, path_id);
+        }
+        PathIndex::query(
+            conn,
+            "select block_id, node_id, path_start, path_end, sequence_start, sequence_end, strand from path_index where path_id = ?1 order by block_id",
+            params![path_id],
+        )
+    }
+
+    pub fn block_count(conn: &Connection, path_id: i64) -> i64 {
+        if !PathIndex::ensure(conn, path_id) {
+            return PathIndex::calculate(conn, path_id).len() as i64;
+        }
+        conn.prepare_cached("select count(*) from path_index where path_id = ?1")
+            .unwrap()
+            .query_row(params![path_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    // The blocks overlapping the range from start to end of the path, in path order.
+    pub fn blocks_in_range(
+        conn: &Connection,
+        path_id: i64,
+        start: i64,
+        end: i64,
+    ) -> Vec<NodeIntervalBlock> {
+        if !PathIndex::ensure(conn, path_id) {
+            return PathIndex::calculate(conn, path_id)
+                .into_iter()
+                .filter(|block| block.start < end && block.end > start)
+                .collect();
+        }
+        PathIndex::query(
+            conn,
+            "select block_id, node_id, path_start, path_end, sequence_start, sequence_end, strand from path_index where path_id = ?1 and path_start < ?3 and path_end > ?2 order by path_start",
+            params![path_id, start, end],
+        )
+    }
+
+    // The blocks of the path that are part of a node, in path order.
+    pub fn node_blocks(conn: &Connection, path_id: i64, node_id: i64) -> Vec<NodeIntervalBlock> {
+        if !PathIndex::ensure(conn, path_id) {
+            return PathIndex::calculate(conn, path_id)
+                .into_iter()
+                .filter(|block| block.node_id == node_id)
+                .collect();
+        }
+        PathIndex::query(
+            conn,
+            "select block_id, node_id, path_start, path_end, sequence_start, sequence_end, strand from path_index where path_id = ?1 and node_id = ?2 order by path_start",
+            params![path_id, node_id],
+        )
+    }
+
+    pub fn path_length(conn: &Connection, path_id: i64) -> i64 {
+        if !PathIndex::ensure(conn, path_id) {
+            return PathIndex::calculate(conn, path_id)
+                .last()
+                .map_or(0, |block| block.end);
+        }
+        conn.prepare_cached("select coalesce(max(path_end), 0) from path_index where path_id = ?1")
+            .unwrap()
+            .query_row(params![path_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    // Drops the index of paths that no longer exist, such as paths removed by reverting an
+    // operation.
+    pub fn remove_stale(conn: &Connection) {
+        conn.execute(
+            "delete from path_index where path_id not in (select id from paths)",
+            [],
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::path::Path;
+    use crate::test_helpers::{get_connection, setup_block_group};
+
+    #[test]
+    fn test_indexes_path_blocks() {
+        let conn = get_connection(None);
+        let (_block_group_id, path) = setup_block_group(&conn);
+        let blocks = PathIndex::blocks(&conn, path.id);
+        assert_eq!(blocks, PathIndex::calculate(&conn, path.id));
+        assert_eq!(
+            PathIndex::path_length(&conn, path.id),
+            path.sequence(&conn).len() as i64
+        );
+        assert_eq!(
+            PathIndex::blocks_in_range(&conn, path.id, 5, 15),
+            blocks
+                .iter()
+                .filter(|block| block.start < 15 && block.end > 5)
+                .copied()
+                .collect::<Vec<_>>()
+        );
+
+        // paths without an index, such as those made before it existed, are indexed on first use
+        conn.execute("delete from path_index", []).unwrap();
+        assert_eq!(PathIndex::blocks(&conn, path.id), blocks);
+        assert_eq!(
+            Path::get(&conn, path.id).sequence_range(&conn, 0, 40),
+            path.sequence(&conn)
+        );
+    }
+}
@@ -3,8 +3,13 @@ use crate::models::block_group::BlockGroup;
 use crate::models::traits::*;
 use petgraph::prelude::DiGraphMap;
 use rusqlite::{params, types::Value as SQLValue, Connection, Result as SQLResult, Row};
+use std::collections::HashMap;
 use std::fmt::*;
 
+// Graphs that aren't part of a sample belong to the base sample, which has no name in the database.
+// This is the name it's shown with, and commands accept it wherever a sample name is expected.
+pub const BASE_SAMPLE_NAME: &str = "(reference)";
+
 #[derive(Debug)]
 pub struct Sample {
     pub name: String,
@@ -127,6 +132,15 @@ impl Sample {
         samples.iter().map(|s| s.name.clone()).collect()
     }
 
+    pub fn display_name(sample_name: Option<&str>) -> &str {
+        sample_name.unwrap_or(BASE_SAMPLE_NAME)
+    }
+
+    // The inverse of display_name, for sample names given by a user.
+    pub fn from_display_name(name: &str) -> Option<&str> {
+        (name != BASE_SAMPLE_NAME).then_some(name)
+    }
+
     pub fn get_by_name(conn: &Connection, name: &str) -> SQLResult<Sample> {
         Sample::get(
             conn,
@@ -134,4 +148,24 @@ impl Sample {
             rusqlite::params!(name),
         )
     }
+
+    // Records that a sample duplicates another one. A sample keeps the first alias it is given.
+    pub fn add_alias(conn: &Connection, alias: &str, sample_name: &str) {
+        conn.execute(
+            "INSERT INTO sample_aliases (alias, sample_name) VALUES (?1, ?2) ON CONFLICT DO NOTHING;",
+            (alias, sample_name),
+        )
+        .unwrap();
+    }
+
+    // Samples that were found to duplicate another sample, by the name of the sample they duplicate.
+    pub fn get_aliases(conn: &Connection) -> HashMap<String, String> {
+        let mut stmt = conn
+            .prepare("SELECT alias, sample_name FROM sample_aliases;")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
 }
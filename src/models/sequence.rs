@@ -7,8 +7,281 @@ use rusqlite::{params_from_iter, Connection};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::{fs, str, sync};
 
+use crate::models::path::revcomp;
+use crate::object_store::{read_object, write_object};
+
+// The file path of sequences whose bases are in the object store, which are named by their hash.
+pub const OBJECT_STORE_FILE_PATH: &str = "gen:objects";
+// The file path of sequences whose bases are packed in the database, which are also named by their
+// hash.
+pub const PACKED_FILE_PATH: &str = "gen:packed";
+
+// Sequences packed in the database are read from there rather than a file.
+fn is_external(file_path: &str) -> bool {
+    !file_path.is_empty() && file_path != PACKED_FILE_PATH
+}
+
+const PACKED_BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+type PackedExceptions = (Vec<(i64, i64, char)>, Vec<(i64, i64)>);
+
+/*
+   DNA packed 2 bits a base, 4 bases a byte with the first base in the highest bits, which takes a
+   quarter of the space of the bases as text. Other characters, such as N and ambiguity codes, are
+   packed as A and listed as exceptions, runs of the same character, and soft masked bases as runs of
+   lowercase bases, so a sequence unpacks to exactly what was packed. Sequences are taken to be ASCII.
+*/
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct PackedSequence {
+    pub length: i64,
+    pub bases: Vec<u8>,
+    // the start, length and character of runs of characters other than ACGT
+    pub exceptions: Vec<(i64, i64, char)>,
+    // the start and length of runs of lowercase characters
+    pub lowercase: Vec<(i64, i64)>,
+}
+
+impl PackedSequence {
+    pub fn pack(sequence: &str) -> PackedSequence {
+        let mut packed = PackedSequence {
+            length: sequence.len() as i64,
+            bases: vec![0; sequence.len().div_ceil(4)],
+            ..PackedSequence::default()
+        };
+        for (index, base) in sequence.bytes().enumerate() {
+            let position = index as i64;
+            if base.is_ascii_lowercase() {
+                match packed.lowercase.last_mut() {
+                    Some((start, length)) if *start + *length == position => *length += 1,
+                    _ => packed.lowercase.push((position, 1)),
+                }
+            }
+            let base = base.to_ascii_uppercase();
+            match PACKED_BASES
+                .iter()
+                .position(|packed_base| *packed_base == base)
+            {
+                Some(code) => packed.bases[index / 4] |= (code as u8) << (6 - 2 * (index % 4)),
+                None => match packed.exceptions.last_mut() {
+                    Some((start, length, character))
+                        if *start + *length == position && *character == base as char =>
+                    {
+                        *length += 1
+                    }
+                    _ => packed.exceptions.push((position, 1, base as char)),
+                },
+            }
+        }
+        packed
+    }
+
+    pub fn from_columns(length: i64, bases: Vec<u8>, exceptions: &[u8]) -> PackedSequence {
+        let (exceptions, lowercase): PackedExceptions =
+            serde_json::from_slice(exceptions).expect("Packed sequence exceptions are corrupt");
+        PackedSequence {
+            length,
+            bases,
+            exceptions,
+            lowercase,
+        }
+    }
+
+    // The exceptions and lowercase runs as kept in the packed_exceptions column.
+    pub fn exception_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&(&self.exceptions, &self.lowercase)).unwrap()
+    }
+
+    pub fn unpack(&self) -> String {
+        self.unpack_range(0, self.length)
+    }
+
+    // Unpacks bases start to end, leaving the rest packed.
+    pub fn unpack_range(&self, start: i64, end: i64) -> String {
+        let first_byte = (start / 4) as usize;
+        let end_byte = ((end + 3) / 4) as usize;
+        unpack_window(
+            &self.bases[first_byte..end_byte],
+            start,
+            end,
+            &self.exceptions,
+            &self.lowercase,
+        )
+    }
+
+    // Reads bases start to end of a sequence packed in the database, only fetching the bytes
+    // holding them.
+    pub fn read_range(conn: &Connection, hash: &str, start: i64, end: i64) -> String {
+        let (bases, exceptions): (Vec<u8>, Vec<u8>) = conn
+            .query_row(
+                "select substr(packed_bases, ?2, ?3), packed_exceptions from sequences where hash = ?1",
+                (hash, start / 4 + 1, (end + 3) / 4 - start / 4),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap_or_else(|err| panic!("Unable to read packed sequence {hash}: {err}"));
+        let (exceptions, lowercase): PackedExceptions =
+            serde_json::from_slice(&exceptions).expect("Packed sequence exceptions are corrupt");
+        unpack_window(&bases, start, end, &exceptions, &lowercase)
+    }
+}
+
+// Unpacks bases start to end from the bytes of a packed sequence holding them, the first of which
+// is the byte holding start.
+fn unpack_window(
+    bytes: &[u8],
+    start: i64,
+    end: i64,
+    exceptions: &[(i64, i64, char)],
+    lowercase: &[(i64, i64)],
+) -> String {
+    let first = start - start % 4;
+    let mut unpacked = (start..end)
+        .map(|position| {
+            let offset = (position - first) as usize;
+            PACKED_BASES[((bytes[offset / 4] >> (6 - 2 * (offset % 4))) & 3) as usize]
+        })
+        .collect::<Vec<u8>>();
+    let first_exception =
+        exceptions.partition_point(|(run_start, length, _)| run_start + length <= start);
+    for (run_start, length, character) in exceptions[first_exception..]
+        .iter()
+        .take_while(|(run_start, _, _)| *run_start < end)
+    {
+        for position in start.max(*run_start)..end.min(run_start + length) {
+            unpacked[(position - start) as usize] = *character as u8;
+        }
+    }
+    let first_lowercase =
+        lowercase.partition_point(|(run_start, length)| run_start + length <= start);
+    for (run_start, length) in lowercase[first_lowercase..]
+        .iter()
+        .take_while(|(run_start, _)| *run_start < end)
+    {
+        for position in start.max(*run_start)..end.min(run_start + length) {
+            unpacked[(position - start) as usize].make_ascii_lowercase();
+        }
+    }
+    String::from_utf8(unpacked).unwrap()
+}
+
+// Where a collection keeps the bases of its new sequences.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SequenceStore {
+    #[default]
+    Database,
+    Objects,
+    Packed,
+}
+
+impl fmt::Display for SequenceStore {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(match self {
+            SequenceStore::Database => "database",
+            SequenceStore::Objects => "objects",
+            SequenceStore::Packed => "packed",
+        })
+    }
+}
+
+impl FromStr for SequenceStore {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "database" => Ok(SequenceStore::Database),
+            "objects" => Ok(SequenceStore::Objects),
+            "packed" => Ok(SequenceStore::Packed),
+            _ => Err(format!("Invalid sequence store {value}")),
+        }
+    }
+}
+
+// What a sequence is made of, going by its sequence type. GenBank molecule types such as mRNA and
+// ss-DNA are RNA and DNA, and sequences of other types are taken to be DNA, as every sequence was
+// before RNA and proteins were told apart.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Molecule {
+    Dna,
+    Rna,
+    Protein,
+}
+
+const NUCLEOTIDE_CODES: &str = "RYSWKMBDHVN-.";
+const AMINO_ACIDS: &str = "ACDEFGHIKLMNPQRSTVWYBZJXUO*-.";
+
+impl Molecule {
+    pub fn from_sequence_type(sequence_type: &str) -> Molecule {
+        let sequence_type = sequence_type.to_lowercase();
+        if ["protein", "aa", "peptide"].contains(&sequence_type.as_str()) {
+            Molecule::Protein
+        } else if sequence_type.contains("rna") {
+            Molecule::Rna
+        } else {
+            Molecule::Dna
+        }
+    }
+
+    // The molecule type of the LOCUS line of a GenBank record. Types GenBank knows, such as mRNA,
+    // are kept as they are.
+    pub fn genbank_molecule_type(sequence_type: &str) -> String {
+        let molecule = Molecule::from_sequence_type(sequence_type);
+        let kept = match molecule {
+            Molecule::Dna => ["DNA", "ss-DNA", "ds-DNA", "ms-DNA"].contains(&sequence_type),
+            Molecule::Rna => sequence_type.ends_with("RNA") && sequence_type.len() <= 7,
+            Molecule::Protein => false,
+        };
+        if kept {
+            return sequence_type.to_string();
+        }
+        match molecule {
+            Molecule::Dna => "DNA",
+            Molecule::Rna => "RNA",
+            Molecule::Protein => "protein",
+        }
+        .to_string()
+    }
+
+    // Whether a character is in the molecule's alphabet: IUPAC nucleotide codes with T for DNA and
+    // U for RNA, and IUPAC amino acid codes with * for stops for proteins. Gaps are allowed in
+    // either, and case doesn't matter.
+    pub fn is_valid(&self, character: char) -> bool {
+        let character = character.to_ascii_uppercase();
+        match self {
+            Molecule::Dna => "ACGT".contains(character) || NUCLEOTIDE_CODES.contains(character),
+            Molecule::Rna => "ACGU".contains(character) || NUCLEOTIDE_CODES.contains(character),
+            Molecule::Protein => AMINO_ACIDS.contains(character),
+        }
+    }
+
+    // The position and character of the first character of a sequence outside of the alphabet.
+    pub fn find_invalid(&self, sequence: &str) -> Option<(usize, char)> {
+        sequence
+            .chars()
+            .enumerate()
+            .find(|(_, character)| !self.is_valid(*character))
+    }
+
+    // The sequence of the other strand, read in its own direction. RNA is complemented as DNA with U
+    // in place of T. Proteins have no other strand, so they are left as they are.
+    pub fn reverse_complement(&self, sequence: &str) -> String {
+        match self {
+            Molecule::Dna => revcomp(sequence),
+            Molecule::Rna => revcomp(&sequence.replace('U', "T").replace('u', "t"))
+                .chars()
+                .map(|base| match base {
+                    'T' => 'U',
+                    't' => 'u',
+                    _ => base,
+                })
+                .collect(),
+            Molecule::Protein => sequence.to_string(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub struct Sequence {
     pub hash: String,
@@ -21,6 +294,9 @@ pub struct Sequence {
     // indicates whether the sequence is stored externally, a quick flag instead of having to
     // check sequence or file_path and do the logic in function calls.
     pub external_sequence: bool,
+    // the bases of a sequence packed in the database, which are left out of sequence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    packed: Option<PackedSequence>,
 }
 
 #[derive(Default, Debug)]
@@ -31,6 +307,8 @@ pub struct NewSequence<'a> {
     file_path: Option<&'a str>,
     length: Option<i64>,
     shallow: bool,
+    store: SequenceStore,
+    packed: Option<PackedSequence>,
 }
 
 impl<'a> From<&'a Sequence> for NewSequence<'a> {
@@ -41,6 +319,7 @@ impl<'a> From<&'a Sequence> for NewSequence<'a> {
             .name(&value.name)
             .file_path(&value.file_path)
             .length(value.length)
+            .packed(value.packed.clone())
     }
 }
 
@@ -57,6 +336,17 @@ impl<'a> NewSequence<'a> {
         self
     }
 
+    pub fn store(mut self, store: SequenceStore) -> Self {
+        self.store = store;
+        self
+    }
+
+    // The bases of a sequence packed in the database, as copied from another database.
+    pub fn packed(mut self, packed: Option<PackedSequence>) -> Self {
+        self.packed = packed;
+        self
+    }
+
     pub fn sequence_type(mut self, seq_type: &'a str) -> Self {
         self.sequence_type = Some(seq_type);
         self
@@ -87,6 +377,15 @@ impl<'a> NewSequence<'a> {
     }
 
     pub fn hash(&self) -> String {
+        // sequences in the object store or packed keep the hash of their bases as their name
+        if self.file_path == Some(OBJECT_STORE_FILE_PATH)
+            || self.file_path == Some(PACKED_FILE_PATH)
+        {
+            return self
+                .name
+                .expect("A filepath must have an accompanying sequence name")
+                .to_string();
+        }
         let mut hasher = Sha256::new();
         hasher.update(self.sequence_type.expect("Sequence type must be defined."));
         hasher.update(";");
@@ -114,7 +413,7 @@ impl<'a> NewSequence<'a> {
 
     pub fn build(self) -> Sequence {
         let file_path = self.file_path.unwrap_or("").to_string();
-        let external_sequence = !file_path.is_empty();
+        let external_sequence = is_external(&file_path);
         Sequence {
             hash: self.hash(),
             sequence_type: self.sequence_type.unwrap().to_string(),
@@ -123,6 +422,7 @@ impl<'a> NewSequence<'a> {
             file_path,
             length: self.length.unwrap(),
             external_sequence,
+            packed: self.packed.clone(),
         }
     }
 
@@ -143,6 +443,32 @@ impl<'a> NewSequence<'a> {
             }
         }
         let hash = self.hash();
+        let length = self.length.unwrap_or(length);
+        // the bases of sequences kept in the object store or packed are left out of the sequence column
+        let (sequence, name, file_path, packed) = if self.shallow || self.file_path.is_some() {
+            (
+                "",
+                self.name.unwrap_or(""),
+                self.file_path.unwrap_or(""),
+                self.packed.clone(),
+            )
+        } else {
+            match self.store {
+                SequenceStore::Objects => ("", hash.as_str(), OBJECT_STORE_FILE_PATH, None),
+                SequenceStore::Packed if self.sequence_type == Some("DNA") => (
+                    "",
+                    hash.as_str(),
+                    PACKED_FILE_PATH,
+                    Some(PackedSequence::pack(self.sequence.unwrap())),
+                ),
+                _ => (self.sequence.unwrap(), self.name.unwrap_or(""), "", None),
+            }
+        };
+        // stored even if the sequence is already in the database, so the returned sequence can be read
+        if file_path == OBJECT_STORE_FILE_PATH {
+            write_object(&hash, self.sequence.unwrap())
+                .unwrap_or_else(|err| panic!("Unable to store sequence {hash}: {err}"));
+        }
         let mut obj_hash: String = match conn.query_row(
             "SELECT hash from sequences where hash = ?1;",
             [hash.clone()],
@@ -155,23 +481,18 @@ impl<'a> NewSequence<'a> {
             }
         };
         if obj_hash.is_empty() {
-            let mut stmt = conn.prepare("INSERT INTO sequences (hash, sequence_type, sequence, name, file_path, length) VALUES (?1, ?2, ?3, ?4, ?5, ?6) RETURNING (hash);").unwrap();
+            let mut stmt = conn.prepare("INSERT INTO sequences (hash, sequence_type, sequence, name, file_path, length, packed_bases, packed_exceptions) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) RETURNING (hash);").unwrap();
             let mut rows = stmt
                 .query_map(
                     (
                         Value::from(hash.to_string()),
                         Value::from(self.sequence_type.unwrap().to_string()),
-                        Value::from(
-                            (if self.shallow {
-                                ""
-                            } else {
-                                self.sequence.unwrap()
-                            })
-                            .to_string(),
-                        ),
-                        Value::from(self.name.unwrap_or("").to_string()),
-                        Value::from(self.file_path.unwrap_or("").to_string()),
-                        Value::from(self.length.unwrap_or(length)),
+                        Value::from(sequence.to_string()),
+                        Value::from(name.to_string()),
+                        Value::from(file_path.to_string()),
+                        Value::from(length),
+                        Value::from(packed.as_ref().map(|packed| packed.bases.clone())),
+                        Value::from(packed.as_ref().map(PackedSequence::exception_bytes)),
                     ),
                     |row| row.get(0),
                 )
@@ -181,11 +502,16 @@ impl<'a> NewSequence<'a> {
         Sequence {
             hash: obj_hash,
             sequence_type: self.sequence_type.unwrap().to_string(),
-            sequence: self.sequence.unwrap_or("").to_string(),
-            name: self.name.unwrap_or("").to_string(),
-            file_path: self.file_path.unwrap_or("").to_string(),
-            length: self.length.unwrap_or(length),
-            external_sequence: !self.file_path.unwrap_or("").is_empty(),
+            sequence: if file_path.is_empty() {
+                self.sequence.unwrap_or("").to_string()
+            } else {
+                "".to_string()
+            },
+            name: name.to_string(),
+            file_path: file_path.to_string(),
+            length,
+            external_sequence: is_external(file_path),
+            packed,
         }
     }
 }
@@ -226,8 +552,10 @@ pub fn cached_sequence(file_path: &str, name: &str, start: usize, end: usize) ->
     let mut cache = SEQUENCE_CACHE.write().unwrap();
 
     let mut sequence: Option<String> = None;
-    let region = name.parse::<Region>().unwrap();
-    if let Some(index) = fasta_index(file_path) {
+    if file_path == OBJECT_STORE_FILE_PATH {
+        sequence = read_object(name).ok();
+    } else if let Some(index) = fasta_index(file_path) {
+        let region = name.parse::<Region>().unwrap();
         let builder = IndexBuilder::default().set_index(index);
         if let Some(gzi_index) = fasta_gzi_index(file_path) {
             let bgzf_reader = bgzf::indexed_reader::Builder::default()
@@ -281,6 +609,10 @@ impl Sequence {
         NewSequence::new()
     }
 
+    pub fn molecule(&self) -> Molecule {
+        Molecule::from_sequence_type(&self.sequence_type)
+    }
+
     pub fn get_sequence(
         &self,
         start: impl Into<Option<i64>>,
@@ -292,6 +624,9 @@ impl Sequence {
         let end: Option<i64> = end.into();
         let start = start.unwrap_or(0) as usize;
         let end = end.unwrap_or(self.length) as usize;
+        if let Some(packed) = &self.packed {
+            return packed.unpack_range(start as i64, end as i64);
+        }
         if self.external_sequence {
             if let Some(sequence) = cached_sequence(&self.file_path, &self.name, start, end) {
                 return sequence;
@@ -314,20 +649,33 @@ impl Sequence {
         let rows = stmt
             .query_map(params_from_iter(placeholders), |row| {
                 let file_path: String = row.get(4).unwrap();
-                let mut external_sequence = false;
-                if !file_path.is_empty() {
-                    external_sequence = true;
-                }
+                let external_sequence = is_external(&file_path);
                 let hash: String = row.get(0).unwrap();
                 let sequence = row.get(2).unwrap();
+                let length = row.get(5).unwrap();
+                // the packed columns are only read by queries selecting every column
+                let packed = if row.as_ref().column_count() > 7 {
+                    match (
+                        row.get::<_, Option<Vec<u8>>>(6).unwrap(),
+                        row.get::<_, Option<Vec<u8>>>(7).unwrap(),
+                    ) {
+                        (Some(bases), Some(exceptions)) => {
+                            Some(PackedSequence::from_columns(length, bases, &exceptions))
+                        }
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
                 Ok(Sequence {
                     hash,
                     sequence_type: row.get(1).unwrap(),
                     sequence,
                     name: row.get(3).unwrap(),
                     file_path,
-                    length: row.get(5).unwrap(),
+                    length,
                     external_sequence,
+                    packed,
                 })
             })
             .unwrap();
@@ -355,6 +703,44 @@ impl Sequence {
             .collect::<HashMap<String, Sequence>>()
     }
 
+    // Reads part of a sequence, leaving the rest of a stored sequence in the database.
+    pub fn sequence_slice(conn: &Connection, hash: &str, start: i64, end: i64) -> String {
+        let sequence = Sequence::sequences(
+            conn,
+            "select hash, sequence_type, substr(sequence, ?2, ?3), name, file_path, length from sequences where hash = ?1",
+            vec![
+                Value::from(hash.to_string()),
+                Value::from(start + 1),
+                Value::from(end - start),
+            ],
+        )
+        .pop()
+        .unwrap();
+        if sequence.file_path == PACKED_FILE_PATH {
+            PackedSequence::read_range(conn, hash, start, end)
+        } else if sequence.external_sequence {
+            sequence.get_sequence(start, end)
+        } else {
+            sequence.sequence
+        }
+    }
+
+    // The sequence with its bases in the sequence column, as a sequence kept in the object store or
+    // packed would be stored by a collection without either. Its hash stays the same.
+    pub fn inlined(&self) -> Sequence {
+        if self.file_path != OBJECT_STORE_FILE_PATH && self.file_path != PACKED_FILE_PATH {
+            return self.clone();
+        }
+        Sequence {
+            sequence: self.get_sequence(None, None),
+            name: "".to_string(),
+            file_path: "".to_string(),
+            external_sequence: false,
+            packed: None,
+            ..self.clone()
+        }
+    }
+
     pub fn sequence_from_hash(conn: &Connection, hash: &str) -> Option<Sequence> {
         let sequences_by_hash = Sequence::sequences_by_hash(conn, vec![hash]);
         sequences_by_hash.get(hash).cloned()
@@ -464,6 +850,94 @@ mod tests {
         assert_eq!(seq.get_sequence(None, 5), "ATCGA");
     }
 
+    #[test]
+    fn test_packed_sequence() {
+        let bases = "ACGTNNNNacgtRYacgTTGCAn";
+        let packed = PackedSequence::pack(bases);
+        assert_eq!(packed.bases.len(), 6);
+        assert_eq!(packed.bases[0], 0b00011011);
+        assert_eq!(
+            packed.exceptions,
+            vec![(4, 4, 'N'), (12, 1, 'R'), (13, 1, 'Y'), (22, 1, 'N')]
+        );
+        assert_eq!(packed.lowercase, vec![(8, 4), (14, 3), (22, 1)]);
+        assert_eq!(packed.unpack(), bases);
+        for start in 0..bases.len() {
+            for end in start..=bases.len() {
+                assert_eq!(
+                    packed.unpack_range(start as i64, end as i64),
+                    bases[start..end]
+                );
+            }
+        }
+        let copy = PackedSequence::from_columns(
+            packed.length,
+            packed.bases.clone(),
+            &packed.exception_bytes(),
+        );
+        assert_eq!(copy, packed);
+    }
+
+    #[test]
+    fn test_create_packed_sequence_in_db() {
+        let conn = &get_connection(None);
+        let bases = "ATCGATCGATCGATCGATCGGGAACACACAGAGANNNNatcg";
+        let in_database = Sequence::new().sequence_type("DNA").sequence(bases).build();
+        let sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence(bases)
+            .store(SequenceStore::Packed)
+            .save(conn);
+        assert_eq!(sequence.hash, in_database.hash);
+        assert_eq!(sequence.file_path, PACKED_FILE_PATH);
+        assert!(!sequence.external_sequence);
+        assert_eq!(sequence.get_sequence(None, None), bases);
+
+        let stored = Sequence::sequence_from_hash(conn, &sequence.hash).unwrap();
+        assert_eq!(stored, sequence);
+        assert_eq!(stored.sequence, "");
+        assert_eq!(stored.get_sequence(30, 40), "GAGANNNNat");
+        assert_eq!(
+            Sequence::sequence_slice(conn, &sequence.hash, 33, 41),
+            "ANNNNatc"
+        );
+        assert_eq!(stored.inlined(), in_database);
+
+        // proteins aren't packed
+        let protein = Sequence::new()
+            .sequence_type("Protein")
+            .sequence("MAW")
+            .store(SequenceStore::Packed)
+            .save(conn);
+        assert_eq!(protein.file_path, "");
+        assert_eq!(protein.get_sequence(None, None), "MAW");
+    }
+
+    #[test]
+    fn test_molecule() {
+        assert_eq!(Molecule::from_sequence_type("DNA"), Molecule::Dna);
+        assert_eq!(Molecule::from_sequence_type("mRNA"), Molecule::Rna);
+        assert_eq!(Molecule::from_sequence_type("Protein"), Molecule::Protein);
+        assert_eq!(Molecule::from_sequence_type("aa"), Molecule::Protein);
+
+        assert_eq!(Molecule::Dna.find_invalid("ACGTNacgt-"), None);
+        assert_eq!(Molecule::Dna.find_invalid("ACGU"), Some((3, 'U')));
+        assert_eq!(Molecule::Rna.find_invalid("ACGU"), None);
+        assert_eq!(Molecule::Rna.find_invalid("ACGT"), Some((3, 'T')));
+        assert_eq!(Molecule::Protein.find_invalid("MAWQ*"), None);
+        assert_eq!(Molecule::Protein.find_invalid("MAW1"), Some((3, '1')));
+
+        assert_eq!(Molecule::Dna.reverse_complement("AACGt"), "aCGTT");
+        assert_eq!(Molecule::Rna.reverse_complement("AACGu"), "aCGUU");
+        assert_eq!(Molecule::Rna.reverse_complement("AC-GU.u"), "a.AC-GU");
+        assert_eq!(Molecule::Protein.reverse_complement("MAW"), "MAW");
+
+        assert_eq!(Molecule::genbank_molecule_type("DNA"), "DNA");
+        assert_eq!(Molecule::genbank_molecule_type("mRNA"), "mRNA");
+        assert_eq!(Molecule::genbank_molecule_type("dna"), "DNA");
+        assert_eq!(Molecule::genbank_molecule_type("aa"), "protein");
+    }
+
     #[test]
     #[cfg(feature = "benchmark")]
     fn test_cached_sequence_performance() {
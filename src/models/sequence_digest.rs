@@ -0,0 +1,133 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use md5::Md5;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use sha2::{Digest, Sha512};
+
+// GA4GH identifiers are the TRUNC512 digest in base64url, after this prefix.
+const GA4GH_PREFIX: &str = "SQ.";
+
+/*
+   The checksums the GA4GH refget protocol identifies a sequence by: the MD5 and the TRUNC512 (the
+   first 24 bytes of the SHA-512) of its bases in upper case, as hex. The GA4GH identifier of a
+   sequence is its TRUNC512 in base64url. These are computed when sequences are imported, so
+   sequences can be served to genome browsers by checksum.
+*/
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SequenceDigest {
+    pub sequence_hash: String,
+    pub md5: String,
+    pub trunc512: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+impl SequenceDigest {
+    pub fn compute(sequence_hash: &str, bases: &str) -> SequenceDigest {
+        let bases = bases.to_ascii_uppercase();
+        SequenceDigest {
+            sequence_hash: sequence_hash.to_string(),
+            md5: to_hex(&Md5::digest(bases.as_bytes())),
+            trunc512: to_hex(&Sha512::digest(bases.as_bytes())[..24]),
+        }
+    }
+
+    pub fn ga4gh(&self) -> String {
+        let bytes = (0..self.trunc512.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&self.trunc512[index..index + 2], 16).unwrap())
+            .collect::<Vec<u8>>();
+        format!("{GA4GH_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn process_row(row: &Row) -> rusqlite::Result<SequenceDigest> {
+        Ok(SequenceDigest {
+            sequence_hash: row.get(0)?,
+            md5: row.get(1)?,
+            trunc512: row.get(2)?,
+        })
+    }
+
+    // Records the digests of a sequence, unless they already are.
+    pub fn create(conn: &Connection, sequence_hash: &str, bases: &str) -> SequenceDigest {
+        let digest = SequenceDigest::compute(sequence_hash, bases);
+        conn.execute(
+            "insert into sequence_digests (sequence_hash, md5, trunc512) values (?1, ?2, ?3) on conflict do nothing;",
+            params![digest.sequence_hash, digest.md5, digest.trunc512],
+        )
+        .unwrap();
+        digest
+    }
+
+    // Finds the sequence a refget checksum identifies: an MD5, a TRUNC512 or a GA4GH identifier,
+    // optionally with its namespace as in md5:... or ga4gh:SQ....
+    pub fn find(conn: &Connection, checksum: &str) -> Option<SequenceDigest> {
+        let checksum = checksum
+            .strip_prefix("md5:")
+            .or_else(|| checksum.strip_prefix("ga4gh:"))
+            .unwrap_or(checksum);
+        let (column, value) = if let Some(identifier) = checksum.strip_prefix(GA4GH_PREFIX) {
+            let bytes = URL_SAFE_NO_PAD.decode(identifier).ok()?;
+            ("trunc512", to_hex(&bytes))
+        } else {
+            match checksum.len() {
+                32 => ("md5", checksum.to_ascii_lowercase()),
+                48 => ("trunc512", checksum.to_ascii_lowercase()),
+                _ => return None,
+            }
+        };
+        conn.query_row(
+            &format!(
+                "select sequence_hash, md5, trunc512 from sequence_digests where {column} = ?1;"
+            ),
+            params![value],
+            SequenceDigest::process_row,
+        )
+        .optional()
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sequence::Sequence;
+    use crate::test_helpers::get_connection;
+
+    #[test]
+    fn test_digests() {
+        // the refget compliance sequence digests of ACGT
+        let digest = SequenceDigest::compute("hash", "acgt");
+        assert_eq!(digest.md5, "f1f8f4bf413b16ad135722aa4591043e");
+        assert_eq!(
+            digest.trunc512,
+            "68a178f7c740c5c240aa67ba41843b119d3bf9f8b0f0ac36"
+        );
+        assert_eq!(digest.ga4gh(), "SQ.aKF498dAxcJAqme6QYQ7EZ07-fiw8Kw2");
+    }
+
+    #[test]
+    fn test_find() {
+        let conn = &get_connection(None);
+        let sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("ACGT")
+            .save(conn);
+        let digest = SequenceDigest::create(conn, &sequence.hash, "ACGT");
+        assert_eq!(SequenceDigest::create(conn, &sequence.hash, "ACGT"), digest);
+
+        for checksum in [
+            digest.md5.clone(),
+            format!("md5:{}", digest.md5.to_ascii_uppercase()),
+            digest.trunc512.clone(),
+            digest.ga4gh(),
+            format!("ga4gh:{}", digest.ga4gh()),
+        ] {
+            assert_eq!(SequenceDigest::find(conn, &checksum), Some(digest.clone()));
+        }
+        assert_eq!(SequenceDigest::find(conn, "SQ.unknown"), None);
+        assert_eq!(SequenceDigest::find(conn, "acgt"), None);
+    }
+}
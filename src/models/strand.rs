@@ -2,6 +2,7 @@ use rusqlite::types::{FromSql, FromSqlResult, ToSqlOutput, Value, ValueRef};
 use rusqlite::ToSql;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Ord, PartialOrd)]
 pub enum Strand {
@@ -11,6 +12,17 @@ pub enum Strand {
     ImportantButUnknown,
 }
 
+impl Strand {
+    // The strand a feature is on once the sequence it's on is read in the opposite direction.
+    pub fn flipped(self) -> Strand {
+        match self {
+            Strand::Forward => Strand::Reverse,
+            Strand::Reverse => Strand::Forward,
+            strand => strand,
+        }
+    }
+}
+
 // example https://docs.rs/rusqlite/latest/rusqlite/types/index.html
 impl ToSql for Strand {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
@@ -61,6 +73,20 @@ impl fmt::Display for Strand {
     }
 }
 
+impl FromStr for Strand {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "+" => Ok(Strand::Forward),
+            "-" => Ok(Strand::Reverse),
+            "." => Ok(Strand::Unknown),
+            "?" => Ok(Strand::ImportantButUnknown),
+            _ => Err(format!("Invalid strand {value}")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,4 +118,13 @@ mod tests {
             Strand::ImportantButUnknown
         );
     }
+
+    #[test]
+    fn test_parses_and_flips() {
+        assert_eq!("-".parse::<Strand>().unwrap(), Strand::Reverse);
+        assert!("x".parse::<Strand>().is_err());
+        assert_eq!(Strand::Forward.flipped(), Strand::Reverse);
+        assert_eq!(Strand::Reverse.flipped(), Strand::Forward);
+        assert_eq!(Strand::Unknown.flipped(), Strand::Unknown);
+    }
 }
@@ -21,3 +21,14 @@ pub trait Query {
 
     fn process_row(row: &Row) -> Self::Model;
 }
+
+// Rows per statement when writing models in bulk. The statements have a placeholder for each value
+// and are cached, so inserting any number of rows only parses two of them, one for full chunks
+// and one for the remainder.
+pub const BULK_CHUNK_SIZE: usize = 1000;
+
+// The placeholders of a multi-row VALUES list, like "(?, ?), (?, ?)" for two rows of two columns.
+pub fn placeholder_rows(columns: usize, rows: usize) -> String {
+    let row = format!("({})", vec!["?"; columns].join(", "));
+    vec![row; rows].join(", ")
+}
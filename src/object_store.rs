@@ -0,0 +1,104 @@
+use crate::config::get_gen_dir;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/*
+   A content addressed store of sequence bases in the objects directory of .gen, for collections
+   whose genomes would bloat the database. Each sequence is a zstd compressed file named by its hash,
+   split on the first two characters of the hash like git's objects so directories stay small.
+   Objects are never changed once written, as their contents are given by their names.
+*/
+pub fn object_path(hash: &str) -> PathBuf {
+    let (prefix, rest) = hash.split_at(hash.len().min(2));
+    Path::new(&get_gen_dir())
+        .join("objects")
+        .join(prefix)
+        .join(rest)
+}
+
+pub fn has_object(hash: &str) -> bool {
+    object_path(hash).is_file()
+}
+
+// Writes the bases of a sequence under its hash, unless they are already stored. Objects are
+// written to a temporary file first so a reader never sees a partial one.
+pub fn write_object(hash: &str, bases: &str) -> io::Result<()> {
+    let path = object_path(hash);
+    if path.is_file() {
+        return Ok(());
+    }
+    let directory = path.parent().unwrap();
+    fs::create_dir_all(directory)?;
+    let mut file = NamedTempFile::new_in(directory)?;
+    file.write_all(&zstd::encode_all(bases.as_bytes(), COMPRESSION_LEVEL)?)?;
+    file.persist(&path).map_err(|err| err.error)?;
+    Ok(())
+}
+
+pub fn read_object(hash: &str) -> io::Result<String> {
+    let bases = zstd::decode_all(File::open(object_path(hash))?)?;
+    String::from_utf8(bases).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// The hashes of the stored objects, with the size of their files.
+pub fn list_objects() -> io::Result<Vec<(String, u64)>> {
+    let mut objects = vec![];
+    let prefixes = match fs::read_dir(Path::new(&get_gen_dir()).join("objects")) {
+        Ok(prefixes) => prefixes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(objects),
+        Err(err) => return Err(err),
+    };
+    for prefix in prefixes {
+        let prefix = prefix?;
+        if !prefix.file_type()?.is_dir() {
+            continue;
+        }
+        let prefix_name = prefix.file_name().to_string_lossy().to_string();
+        for entry in fs::read_dir(prefix.path())? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // temporary files of objects being written aren't objects yet
+            if name.starts_with('.') || !entry.file_type()?.is_file() {
+                continue;
+            }
+            objects.push((format!("{prefix_name}{name}"), entry.metadata()?.len()));
+        }
+    }
+    objects.sort();
+    Ok(objects)
+}
+
+pub fn remove_object(hash: &str) -> io::Result<()> {
+    match fs::remove_file(object_path(hash)) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::setup_gen_dir;
+
+    #[test]
+    fn test_round_trip() {
+        setup_gen_dir();
+        let hash = "a25fd98acde399ab74235e3a8fa6d0cc163743e6df1846894fb0b118400887cc";
+        let bases = "ATCG".repeat(1000);
+        assert!(!has_object(hash));
+        write_object(hash, &bases).unwrap();
+        assert!(has_object(hash));
+        assert!(object_path(hash).ends_with(
+            "objects/a2/5fd98acde399ab74235e3a8fa6d0cc163743e6df1846894fb0b118400887cc"
+        ));
+        assert!(fs::metadata(object_path(hash)).unwrap().len() < 100);
+        assert_eq!(read_object(hash).unwrap(), bases);
+        // objects are immutable, so writing one again keeps the first
+        write_object(hash, "GATTACA").unwrap();
+        assert_eq!(read_object(hash).unwrap(), bases);
+    }
+}
@@ -1,32 +1,43 @@
 use crate::config::get_changeset_path;
 use crate::models::accession::{Accession, AccessionEdge, AccessionEdgeData, AccessionPath};
+use crate::models::annotation::{Annotation, AnnotationData};
+use crate::models::attribute::AttributeValue;
 use crate::models::block_group::BlockGroup;
 use crate::models::block_group_edge::{BlockGroupEdge, BlockGroupEdgeData};
+use crate::models::block_group_stats::BlockGroupStats;
 use crate::models::collection::Collection;
 use crate::models::edge::{Edge, EdgeData};
 use crate::models::file_types::FileTypes;
 use crate::models::metadata;
 use crate::models::node::Node;
+use crate::models::node_coverage::NodeCoverage;
 use crate::models::operations::{
     Branch, FileAddition, Operation, OperationInfo, OperationState, OperationSummary,
+    SparseCheckout,
 };
 use crate::models::path::Path;
+use crate::models::path_index::PathIndex;
 use crate::models::sample::Sample;
-use crate::models::sequence::Sequence;
+use crate::models::sequence::{PackedSequence, Sequence};
 use crate::models::strand::Strand;
 use crate::models::traits::*;
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use itertools::Itertools;
 use petgraph::Direction;
 use rusqlite;
+use rusqlite::hooks::Action;
 use rusqlite::session::{ChangesetItem, ChangesetIter};
-use rusqlite::types::{FromSql, Value};
+use rusqlite::types::{FromSql, Value, ValueRef};
 use rusqlite::{session, Connection};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
-use std::io::{Read, Write};
-use std::{fs, path::PathBuf, str};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str,
+};
 use thiserror::Error;
 /* General information
 
@@ -42,6 +53,16 @@ pub enum OperationError {
     NoChanges,
     #[error("Operation Already Exists")]
     OperationExists,
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Operation {0} depends on changes of the operation being reverted, revert it first")]
+    RevertDependency(String),
+    #[error("Invalid range of operations: {0}")]
+    InvalidRange(String),
+    #[error("Can't rebase: {0}")]
+    InvalidRebase(String),
+    #[error("Operation {0} and operation {1} of the branch being rebased onto both change {2}")]
+    RebaseConflict(String, String, String),
 }
 
 pub enum FileMode {
@@ -101,6 +122,7 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
     let mut previous_accession_edges = HashSet::new();
     let mut created_block_groups = HashSet::new();
     let mut created_paths = HashSet::new();
+    let mut renamed_paths: HashMap<i64, String> = HashMap::new();
     let mut created_accessions = HashSet::new();
     let mut created_edges = HashSet::new();
     let mut created_accession_edges = HashSet::new();
@@ -112,6 +134,13 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
         // info on indirect changes: https://www.sqlite.org/draft/session/sqlite3session_indirect.html
         if !op.indirect() {
             let table = op.table_name();
+            // rows a revert deletes refer to nothing beyond themselves, only the attributes of
+            // existing nodes and edges do
+            if op.code() == Action::SQLITE_DELETE
+                && !matches!(table, "node_attributes" | "edge_attributes")
+            {
+                continue;
+            }
             let pk_column = item
                 .pk()
                 .unwrap()
@@ -130,6 +159,19 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
                     let bg_pk = item.new_value(pk_column).unwrap().as_i64().unwrap();
                     created_block_groups.insert(bg_pk);
                 }
+                "paths" if op.code() == Action::SQLITE_UPDATE => {
+                    // a renamed path is found by its old name where the change is applied
+                    let path_id = item.old_value(pk_column).unwrap().as_i64().unwrap();
+                    if !created_paths.contains(&path_id) {
+                        previous_paths.insert(path_id);
+                        renamed_paths.insert(
+                            path_id,
+                            str::from_utf8(item.old_value(2).unwrap().as_bytes().unwrap())
+                                .unwrap()
+                                .to_string(),
+                        );
+                    }
+                }
                 "paths" => {
                     created_paths.insert(item.new_value(pk_column).unwrap().as_i64().unwrap());
                     let bg_id = item.new_value(1).unwrap().as_i64().unwrap();
@@ -137,6 +179,8 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
                         previous_block_groups.insert(bg_id);
                     }
                 }
+                // hashes given to existing nodes are matched by the hash where applied
+                "nodes" if op.code() == Action::SQLITE_UPDATE => {}
                 "nodes" => {
                     created_nodes.insert(item.new_value(pk_column).unwrap().as_i64().unwrap());
                     let sequence_hash =
@@ -217,6 +261,48 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
                         previous_accession_edges.insert(edge_id);
                     }
                 }
+                "annotations" => {
+                    let path_id = item.new_value(1).unwrap().as_i64().unwrap();
+                    if !created_paths.contains(&path_id) {
+                        previous_paths.insert(path_id);
+                    }
+                }
+                "current_path_choices" => {
+                    let bg_id = item.new_value(1).unwrap().as_i64().unwrap();
+                    if !created_block_groups.contains(&bg_id) {
+                        previous_block_groups.insert(bg_id);
+                    }
+                    for column in [2, 3] {
+                        let path_id = item.new_value(column).unwrap().as_i64().unwrap();
+                        if !created_paths.contains(&path_id) {
+                            previous_paths.insert(path_id);
+                        }
+                    }
+                }
+                "node_attributes" => {
+                    let (node_id, _, _) = parse_attribute(item, op.code());
+                    if !created_nodes.contains(&node_id) && !Node::is_terminal(node_id) {
+                        for node in Node::get_nodes(conn, &[node_id]) {
+                            previous_sequences.insert(node.sequence_hash);
+                            previous_nodes.insert(node.id);
+                        }
+                    }
+                }
+                "edge_attributes" => {
+                    let (edge_id, _, _) = parse_attribute(item, op.code());
+                    if !created_edges.contains(&edge_id) {
+                        previous_edges.insert(edge_id);
+                    }
+                }
+                "node_coverage" => {
+                    let node_id = item.new_value(1).unwrap().as_i64().unwrap();
+                    if !created_nodes.contains(&node_id) && !Node::is_terminal(node_id) {
+                        for node in Node::get_nodes(conn, &[node_id]) {
+                            previous_sequences.insert(node.sequence_hash);
+                            previous_nodes.insert(node.id);
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -257,7 +343,13 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
                 ids = previous_paths.iter().join(",")
             ),
             rusqlite::params!(),
-        ),
+        )
+        .into_iter()
+        .map(|path| Path {
+            name: renamed_paths.remove(&path.id).unwrap_or(path.name),
+            ..path
+        })
+        .collect(),
         accessions: Accession::query(
             conn,
             &format!(
@@ -278,6 +370,39 @@ pub fn get_changeset_dependencies(conn: &Connection, mut changes: &[u8]) -> Vec<
     serde_json::to_vec(&s).unwrap()
 }
 
+// Returns the ids of all block groups that a changeset creates, removes, or changes the edges of.
+pub fn get_changeset_block_group_ids(mut changes: &[u8]) -> Vec<i64> {
+    let input: &mut dyn Read = &mut changes;
+    let mut iter = ChangesetIter::start_strm(&input).unwrap();
+    let mut block_group_ids = HashSet::new();
+    while let Some(item) = iter.next().unwrap() {
+        let op = item.op().unwrap();
+        let column = match op.table_name() {
+            "block_groups" => 0,
+            "block_group_edges" => 1,
+            _ => continue,
+        };
+        let value = match op.code() {
+            Action::SQLITE_DELETE => item.old_value(column),
+            _ => item.new_value(column),
+        };
+        if let Ok(block_group_id) = value.unwrap().as_i64() {
+            block_group_ids.insert(block_group_id);
+        }
+    }
+    block_group_ids.into_iter().sorted().collect()
+}
+
+// Changesets larger than this, such as of whole genome imports, are written compressed with zstd.
+// Smaller ones are written as they are.
+const CHANGESET_COMPRESSION_THRESHOLD: usize = 1 << 20;
+const CHANGESET_COMPRESSION_LEVEL: i32 = 3;
+// the frame magic number every zstd file starts with, which a changeset can't start with
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Writes the changeset and dependencies of an operation. The changeset is already in memory, as the
+// operation's hash, dependencies and block group stats are all derived from it before it is written,
+// so only its compression is streamed.
 pub fn write_changeset(operation: &Operation, changes: &[u8], dependencies: &[u8]) {
     let change_path =
         get_changeset_path(operation).join(format!("{op_id}.cs", op_id = operation.hash));
@@ -291,7 +416,28 @@ pub fn write_changeset(operation: &Operation, changes: &[u8], dependencies: &[u8
     let mut file = fs::File::create_new(&change_path)
         .unwrap_or_else(|_| panic!("Unable to open {change_path:?}"));
 
-    file.write_all(changes).unwrap()
+    if changes.len() > CHANGESET_COMPRESSION_THRESHOLD {
+        zstd::stream::copy_encode(changes, file, CHANGESET_COMPRESSION_LEVEL).unwrap()
+    } else {
+        file.write_all(changes).unwrap()
+    }
+}
+
+// Reads a changeset file, decompressing it as it is read if it was written compressed, so large
+// changesets can be streamed rather than held in memory.
+pub fn changeset_reader(change_path: &Path) -> io::Result<Box<dyn Read>> {
+    let mut file = BufReader::new(fs::File::open(change_path)?);
+    if file.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(file)?))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+pub fn open_changeset(operation: &Operation) -> Box<dyn Read> {
+    let change_path =
+        get_changeset_path(operation).join(format!("{op_id}.cs", op_id = operation.hash));
+    changeset_reader(&change_path).unwrap_or_else(|_| panic!("Unable to open {change_path:?}"))
 }
 
 pub fn load_changeset_dependencies(operation: &Operation) -> DependencyModels {
@@ -301,11 +447,10 @@ pub fn load_changeset_dependencies(operation: &Operation) -> DependencyModels {
 }
 
 pub fn load_changeset(operation: &Operation) -> Vec<u8> {
-    let change_path =
-        get_changeset_path(operation).join(format!("{op_id}.cs", op_id = operation.hash));
-    let mut file = fs::File::open(change_path).unwrap();
     let mut contents = vec![];
-    file.read_to_end(&mut contents).unwrap();
+    open_changeset(operation)
+        .read_to_end(&mut contents)
+        .unwrap();
     contents
 }
 
@@ -331,6 +476,56 @@ fn parse_maybe_number(item: &ChangesetItem, col: usize) -> Option<i64> {
     item.new_value(col).unwrap().as_i64_or_null().unwrap()
 }
 
+// The id, key and value of a node or edge attribute a changeset sets, or no value for attributes it
+// removes. Updated rows only have the new value, with the key in the old values.
+fn parse_attribute(item: &ChangesetItem, code: Action) -> (i64, String, Option<AttributeValue>) {
+    let old_key = || {
+        (
+            item.old_value(0).unwrap().as_i64().unwrap(),
+            str::from_utf8(item.old_value(1).unwrap().as_bytes().unwrap())
+                .unwrap()
+                .to_string(),
+        )
+    };
+    let new_value = || Some(AttributeValue::column_result(item.new_value(2).unwrap()).unwrap());
+    match code {
+        Action::SQLITE_INSERT => (parse_number(item, 0), parse_string(item, 1), new_value()),
+        Action::SQLITE_UPDATE => {
+            let (id, key) = old_key();
+            (id, key, new_value())
+        }
+        _ => {
+            let (id, key) = old_key();
+            (id, key, None)
+        }
+    }
+}
+
+// Changesets recorded before block groups had a topology only hold the first four columns.
+fn parse_is_circular(item: &ChangesetItem) -> bool {
+    item.new_value(4)
+        .ok()
+        .and_then(|value| value.as_i64().ok())
+        .is_some_and(|value| value != 0)
+}
+
+// Changesets recorded before sequences could be packed only hold the first six columns.
+fn parse_packed(item: &ChangesetItem) -> Option<PackedSequence> {
+    let column = |col| {
+        item.new_value(col)
+            .ok()
+            .and_then(|value| value.as_bytes_or_null().ok().flatten())
+    };
+    match (column(6), column(7)) {
+        (Some(bases), Some(exceptions)) => Some(PackedSequence::from_columns(
+            parse_number(item, 5),
+            bases.to_vec(),
+            exceptions,
+        )),
+        _ => None,
+    }
+}
+
 pub fn load_changeset_models(changeset: &mut ChangesetIter) -> ChangesetModels {
     let mut created_block_groups = vec![];
     let mut created_edges = vec![];
@@ -341,7 +536,8 @@ pub fn load_changeset_models(changeset: &mut ChangesetIter) -> ChangesetModels {
     while let Some(item) = changeset.next().unwrap() {
         let op = item.op().unwrap();
         // info on indirect changes: https://www.sqlite.org/draft/session/sqlite3session_indirect.html
-        if !op.indirect() {
+        // rows a revert deletes aren't among the models a changeset creates
+        if !op.indirect() && op.code() != Action::SQLITE_DELETE {
             let table = op.table_name();
             let pk_column = item
                 .pk()
@@ -359,6 +555,7 @@ pub fn load_changeset_models(changeset: &mut ChangesetIter) -> ChangesetModels {
                         .name(&parse_string(item, 3))
                         .file_path(&parse_string(item, 4))
                         .length(parse_number(item, 5))
+                        .packed(parse_packed(item))
                         .build();
                     assert_eq!(hash, sequence.hash);
                     created_sequences.push(sequence);
@@ -368,8 +565,10 @@ pub fn load_changeset_models(changeset: &mut ChangesetIter) -> ChangesetModels {
                     collection_name: parse_string(item, 1),
                     sample_name: parse_maybe_string(item, 2),
                     name: parse_string(item, 3),
+                    is_circular: parse_is_circular(item),
                 }),
 
+                "nodes" if op.code() == Action::SQLITE_UPDATE => {}
                 "nodes" => created_nodes.push(Node {
                     id: parse_number(item, pk_column),
                     sequence_hash: parse_string(item, 1),
@@ -404,21 +603,118 @@ pub fn load_changeset_models(changeset: &mut ChangesetIter) -> ChangesetModels {
     }
 }
 
+// Decides which rows of a changeset belong to the collections of a sparse checkout. Block groups
+// and paths are resolved through the changeset and its dependencies first, falling back to the
+// database for rows made by earlier operations. Everything is included if no collections are set.
+struct CollectionFilter<'a> {
+    collections: Option<&'a HashSet<String>>,
+    block_group_collections: HashMap<i64, String>,
+    path_block_groups: HashMap<i64, i64>,
+    skipped: HashSet<String>,
+}
+
+impl CollectionFilter<'_> {
+    fn includes_collection(&mut self, collection_name: &str) -> bool {
+        match self.collections {
+            Some(collections) if !collections.contains(collection_name) => {
+                self.skipped.insert(collection_name.to_string());
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn includes_block_group(&mut self, conn: &Connection, block_group_id: i64) -> bool {
+        if self.collections.is_none() {
+            return true;
+        }
+        let collection_name = match self.block_group_collections.get(&block_group_id) {
+            Some(collection_name) => collection_name.clone(),
+            None => match BlockGroup::query(
+                conn,
+                "select * from block_groups where id = ?1",
+                rusqlite::params![block_group_id],
+            )
+            .first()
+            {
+                Some(block_group) => block_group.collection_name.clone(),
+                None => return true,
+            },
+        };
+        self.includes_collection(&collection_name)
+    }
+
+    fn includes_path(&mut self, conn: &Connection, path_id: i64) -> bool {
+        if self.collections.is_none() {
+            return true;
+        }
+        let block_group_id = match self.path_block_groups.get(&path_id) {
+            Some(block_group_id) => *block_group_id,
+            None => match Path::query(
+                conn,
+                "select * from paths where id = ?1",
+                rusqlite::params![path_id],
+            )
+            .first()
+            {
+                Some(path) => path.block_group_id,
+                None => return true,
+            },
+        };
+        self.includes_block_group(conn, block_group_id)
+    }
+}
+
 pub fn apply_changeset(
     conn: &Connection,
     changeset: &mut ChangesetIter,
     dependencies: &DependencyModels,
 ) {
+    apply_changeset_to_collections(conn, changeset, dependencies, None);
+}
+
+// Applies a changeset, only materializing the rows of the given collections. Shared rows such as
+// sequences, nodes and edges are always applied. Returns the collections whose rows were skipped.
+pub fn apply_changeset_to_collections(
+    conn: &Connection,
+    changeset: &mut ChangesetIter,
+    dependencies: &DependencyModels,
+    collections: Option<&HashSet<String>>,
+) -> HashSet<String> {
     for node in dependencies.nodes.iter() {
         if !Node::is_terminal(node.id) {
             assert!(Sequence::sequence_from_hash(conn, &node.sequence_hash).is_some());
         }
     }
 
+    let mut filter = CollectionFilter {
+        collections,
+        block_group_collections: dependencies
+            .block_group
+            .iter()
+            .map(|bg| (bg.id, bg.collection_name.clone()))
+            .collect(),
+        path_block_groups: dependencies
+            .paths
+            .iter()
+            .map(|path| (path.id, path.block_group_id))
+            .collect(),
+        skipped: HashSet::new(),
+    };
+
     let mut dep_bg_map = HashMap::new();
     for bg in dependencies.block_group.iter() {
+        if !filter.includes_collection(&bg.collection_name) {
+            continue;
+        }
         let sample_name = bg.sample_name.as_ref().map(|v| v as &str);
-        let new_bg = BlockGroup::create(conn, &bg.collection_name, sample_name, &bg.name);
+        let new_bg = BlockGroup::create_with_topology(
+            conn,
+            &bg.collection_name,
+            sample_name,
+            &bg.name,
+            bg.is_circular,
+        );
         dep_bg_map.insert(&bg.id, new_bg.id);
     }
 
@@ -431,7 +727,11 @@ pub fn apply_changeset(
     let mut dep_edge_map = HashMap::new();
     let new_edges = Edge::bulk_create(
         conn,
-        &dependencies.edges.iter().map(EdgeData::from).collect(),
+        &dependencies
+            .edges
+            .iter()
+            .map(EdgeData::from)
+            .collect::<Vec<_>>(),
     );
     for (index, edge_id) in new_edges.iter().enumerate() {
         dep_edge_map.insert(&dependencies.edges[index].id, *edge_id);
@@ -439,6 +739,9 @@ pub fn apply_changeset(
 
     let mut dep_path_map = HashMap::new();
     for path in dependencies.paths.iter() {
+        if !filter.includes_block_group(conn, path.block_group_id) {
+            continue;
+        }
         let new_path = Path::create(
             conn,
             &path.name,
@@ -465,6 +768,9 @@ pub fn apply_changeset(
 
     let mut dep_accession_map: HashMap<i64, i64> = HashMap::new();
     for accession in dependencies.accessions.iter() {
+        if !filter.includes_path(conn, accession.path_id) {
+            continue;
+        }
         let new_accession = if let Some(acc_id) = accession.parent_accession_id {
             Accession::get_or_create(
                 conn,
@@ -495,7 +801,16 @@ pub fn apply_changeset(
     let mut path_edges: HashMap<i64, Vec<(i64, i64)>> = HashMap::new();
     let mut insert_paths = vec![];
     let mut insert_accessions = vec![];
+    let mut insert_annotations = vec![];
+    let mut rename_paths = vec![];
+    let mut node_hashes = HashSet::new();
+    let mut insert_path_choices = vec![];
+    let mut node_attributes = vec![];
+    let mut edge_attributes = vec![];
+    let mut insert_node_coverage = vec![];
     let mut insert_block_group_edges = vec![];
+    let mut insert_block_groups = vec![];
+    let mut insert_collections = vec![];
 
     let mut accession_edge_map: HashMap<i64, AccessionEdgeData> = HashMap::new();
     let mut accession_path_edges: HashMap<i64, Vec<(i64, i64)>> = HashMap::new();
@@ -516,6 +831,9 @@ pub fn apply_changeset(
                 "samples" => {
                     Sample::get_or_create(conn, &parse_string(item, pk_column));
                 }
+                "sample_aliases" => {
+                    Sample::add_alias(conn, &parse_string(item, pk_column), &parse_string(item, 1));
+                }
                 "sequences" => {
                     Sequence::new()
                         .sequence_type(&parse_string(item, 1))
@@ -523,30 +841,46 @@ pub fn apply_changeset(
                         .name(&parse_string(item, 3))
                         .file_path(&parse_string(item, 4))
                         .length(parse_number(item, 5))
+                        .packed(parse_packed(item))
                         .save(conn);
                 }
                 "block_groups" => {
+                    // defer block group creation until the collection of every row is known
                     let bg_pk = parse_number(item, pk_column);
-                    if let Some(v) = dep_bg_map.get(&bg_pk) {
-                        blockgroup_map.insert(bg_pk, *v);
-                    } else {
-                        let sample_name = parse_maybe_string(item, 2);
-                        let new_bg = BlockGroup::create(
-                            conn,
-                            &parse_string(item, 1),
-                            sample_name.as_deref(),
-                            &parse_string(item, 3),
-                        );
-                        blockgroup_map.insert(bg_pk, new_bg.id);
-                    };
+                    let collection_name = parse_string(item, 1);
+                    filter
+                        .block_group_collections
+                        .insert(bg_pk, collection_name.clone());
+                    insert_block_groups.push((
+                        bg_pk,
+                        collection_name,
+                        parse_maybe_string(item, 2),
+                        parse_string(item, 3),
+                        parse_is_circular(item),
+                    ));
+                }
+                "paths" if op.code() == Action::SQLITE_UPDATE => {
+                    // renames are deferred like the paths, only the name of a path can change
+                    rename_paths.push((
+                        item.old_value(pk_column).unwrap().as_i64().unwrap(),
+                        parse_string(item, 2),
+                    ));
                 }
                 "paths" => {
                     // defer path creation until edges are made
-                    insert_paths.push(Path {
+                    let path = Path {
                         id: parse_number(item, pk_column),
                         block_group_id: parse_number(item, 1),
                         name: parse_string(item, 2),
-                    });
+                    };
+                    filter
+                        .path_block_groups
+                        .insert(path.id, path.block_group_id);
+                    insert_paths.push(path);
+                }
+                "nodes" if op.code() == Action::SQLITE_UPDATE => {
+                    // only the hash of a node made without one changes
+                    node_hashes.insert(parse_string(item, 2));
                 }
                 "nodes" => {
                     let node_pk = parse_number(item, pk_column);
@@ -593,11 +927,7 @@ pub fn apply_changeset(
                     insert_block_group_edges.push((bg_id, edge_id, chromosome_index, phased));
                 }
                 "collections" => {
-                    Collection::create(
-                        conn,
-                        str::from_utf8(item.new_value(pk_column).unwrap().as_bytes().unwrap())
-                            .unwrap(),
-                    );
+                    insert_collections.push(parse_string(item, pk_column));
                 }
                 "accessions" => {
                     // we defer accession creation until edges and paths are made
@@ -635,6 +965,44 @@ pub fn apply_changeset(
                         .or_default()
                         .push((index, accession_edge_id));
                 }
+                "annotations" => {
+                    // we defer annotation creation until paths are made
+                    insert_annotations.push(AnnotationData {
+                        path_id: parse_number(item, 1),
+                        name: parse_string(item, 2),
+                        feature_type: parse_string(item, 3),
+                        source: parse_string(item, 4),
+                        start: parse_number(item, 5),
+                        end: parse_number(item, 6),
+                        strand: parse_string(item, 7),
+                        attributes: parse_string(item, 8),
+                    });
+                }
+                "current_path_choices" => {
+                    // deferred until paths are made
+                    insert_path_choices.push((
+                        parse_number(item, 1),
+                        parse_number(item, 2),
+                        parse_number(item, 3),
+                    ));
+                }
+                "node_attributes" => {
+                    // deferred until nodes are made, like the edges
+                    node_attributes.push(parse_attribute(item, op.code()));
+                }
+                "edge_attributes" => {
+                    edge_attributes.push(parse_attribute(item, op.code()));
+                }
+                "node_coverage" => {
+                    // deferred until nodes are made, like the node attributes
+                    insert_node_coverage.push(NodeCoverage {
+                        track: parse_string(item, 0),
+                        node_id: parse_number(item, 1),
+                        start: parse_number(item, 2),
+                        end: parse_number(item, 3),
+                        depth: item.new_value(4).unwrap().as_f64().unwrap(),
+                    });
+                }
                 _ => {
                     panic!("unhandled table is {v}", v = op.table_name());
                 }
@@ -642,12 +1010,53 @@ pub fn apply_changeset(
         }
     }
 
+    for collection_name in insert_collections {
+        if filter.includes_collection(&collection_name) {
+            Collection::create(conn, &collection_name);
+        }
+    }
+
+    for (bg_pk, collection_name, sample_name, name, is_circular) in insert_block_groups {
+        if !filter.includes_collection(&collection_name) {
+            continue;
+        }
+        if let Some(v) = dep_bg_map.get(&bg_pk) {
+            blockgroup_map.insert(bg_pk, *v);
+        } else {
+            let new_bg = BlockGroup::create_with_topology(
+                conn,
+                &collection_name,
+                sample_name.as_deref(),
+                &name,
+                is_circular,
+            );
+            blockgroup_map.insert(bg_pk, new_bg.id);
+        };
+    }
+
+    insert_block_group_edges.retain(|(bg_id, _, _, _)| filter.includes_block_group(conn, *bg_id));
+    insert_paths.retain(|path| filter.includes_block_group(conn, path.block_group_id));
+    insert_accessions.retain(|accession| filter.includes_path(conn, accession.path_id));
+    insert_annotations.retain(|annotation| filter.includes_path(conn, annotation.path_id));
+    rename_paths.retain(|(path_id, _)| filter.includes_path(conn, *path_id));
+    insert_path_choices.retain(|(bg_id, _, _)| filter.includes_block_group(conn, *bg_id));
+
     let mut node_id_map: HashMap<i64, i64> = HashMap::new();
     for (node_id, (sequence_hash, node_hash)) in node_map {
         let new_node_id = Node::create(conn, &sequence_hash, node_hash);
         node_id_map.insert(node_id, new_node_id);
     }
 
+    // A node without a hash can't be told apart from others of its sequence by id, so hashes given
+    // to nodes are taken by the nodes this database would give the same hash.
+    if !node_hashes.is_empty() {
+        for (node_id, node_hash) in Node::missing_hashes(conn) {
+            if node_hashes.contains(&node_hash) {
+                Node::set_missing_hash(conn, node_id, &node_hash);
+            }
+        }
+    }
+
     let mut updated_edge_map = HashMap::new();
     for (edge_id, edge) in edge_map {
         let updated_source_node_id = dep_node_map.get(&edge.source_node_id).unwrap_or(
@@ -720,6 +1129,7 @@ pub fn apply_changeset(
         BlockGroupEdge::bulk_create(conn, &new_block_group_edges);
     }
 
+    let mut path_id_map: HashMap<i64, i64> = HashMap::new();
     for path in insert_paths {
         let mut sorted_edges = vec![];
         for (_, edge_id) in path_edges
@@ -739,7 +1149,68 @@ pub fn apply_changeset(
                 .get(&path.block_group_id)
                 .or(Some(&path.block_group_id)))
             .unwrap();
-        Path::create(conn, &path.name, new_bg_id, &sorted_edges);
+        let new_path = Path::create(conn, &path.name, new_bg_id, &sorted_edges);
+        path_id_map.insert(path.id, new_path.id);
+    }
+
+    let new_path_id = |path_id: &i64| {
+        *dep_path_map
+            .get(path_id)
+            .or(path_id_map.get(path_id))
+            .unwrap_or(path_id)
+    };
+    for (path_id, name) in rename_paths {
+        Path::get(conn, new_path_id(&path_id))
+            .rename(conn, &name)
+            .unwrap();
+    }
+    for (bg_id, path_id, newest_path_id) in insert_path_choices {
+        let bg_id = *dep_bg_map
+            .get(&bg_id)
+            .or(blockgroup_map.get(&bg_id))
+            .unwrap_or(&bg_id);
+        BlockGroup::add_current_path_choice(
+            conn,
+            bg_id,
+            new_path_id(&path_id),
+            new_path_id(&newest_path_id),
+        );
+    }
+
+    for annotation in insert_annotations.iter_mut() {
+        annotation.path_id = *dep_path_map
+            .get(&annotation.path_id)
+            .or(path_id_map.get(&annotation.path_id))
+            .unwrap_or(&annotation.path_id);
+    }
+    Annotation::bulk_create(conn, &insert_annotations);
+
+    for (node_id, key, value) in node_attributes {
+        let node_id = *dep_node_map
+            .get(&node_id)
+            .or(node_id_map.get(&node_id))
+            .unwrap_or(&node_id);
+        match value {
+            Some(value) => Node::set_attribute(conn, node_id, &key, value),
+            None => Node::remove_attribute(conn, node_id, &key),
+        }
+    }
+    for run in insert_node_coverage.iter_mut() {
+        run.node_id = *dep_node_map
+            .get(&run.node_id)
+            .or(node_id_map.get(&run.node_id))
+            .unwrap_or(&run.node_id);
+    }
+    NodeCoverage::bulk_create(conn, &insert_node_coverage);
+    for (edge_id, key, value) in edge_attributes {
+        let edge_id = *dep_edge_map
+            .get(&edge_id)
+            .or(edge_id_map.get(&edge_id))
+            .unwrap_or(&edge_id);
+        match value {
+            Some(value) => Edge::set_attribute(conn, edge_id, &key, value),
+            None => Edge::remove_attribute(conn, edge_id, &key),
+        }
     }
 
     let mut updated_accession_edge_map = HashMap::new();
@@ -808,83 +1279,164 @@ pub fn apply_changeset(
     }
 
     conn.pragma_update(None, "foreign_keys", "1").unwrap();
-}
 
-pub fn revert_changeset(conn: &Connection, operation: &Operation) {
-    let change_path =
-        get_changeset_path(operation).join(format!("{op_id}.cs", op_id = operation.hash));
-    let mut file = fs::File::open(change_path).unwrap();
-    let mut contents = vec![];
-    file.read_to_end(&mut contents).unwrap();
-    let mut inverted_contents: Vec<u8> = vec![];
-    session::invert_strm(&mut &contents[..], &mut inverted_contents).unwrap();
+    let changed_block_group_ids = blockgroup_map
+        .values()
+        .chain(block_group_edges.keys())
+        .copied()
+        .unique()
+        .collect::<Vec<i64>>();
+    BlockGroupStats::refresh(conn, &changed_block_group_ids);
 
+    filter.skipped
+}
+
+// Applies a changeset as it was recorded, with the ids of this database, omitting changes that
+// conflict with the rows there, such as rows a sparse checkout never materialized.
+fn apply_recorded_changeset(conn: &Connection, changes: &[u8]) {
     conn.pragma_update(None, "foreign_keys", "0").unwrap();
     conn.apply_strm(
-        &mut &inverted_contents[..],
+        &mut &changes[..],
         None::<fn(&str) -> bool>,
         |_conflict_type, _item| session::ConflictAction::SQLITE_CHANGESET_OMIT,
     )
     .unwrap();
     conn.pragma_update(None, "foreign_keys", "1").unwrap();
+
+    BlockGroupStats::refresh(conn, &get_changeset_block_group_ids(changes));
+    PathIndex::remove_stale(conn);
 }
 
-pub fn reset(conn: &Connection, operation_conn: &Connection, db_uuid: &str, op_hash: &str) {
-    let current_op = OperationState::get_operation(operation_conn, db_uuid).unwrap();
-    let current_branch_id = OperationState::get_current_branch(operation_conn, db_uuid).unwrap();
-    let current_branch = Branch::get_by_id(operation_conn, current_branch_id).unwrap();
-    let branch_operations: Vec<String> = Branch::get_operations(operation_conn, current_branch_id)
-        .iter()
-        .map(|b| b.hash.clone())
-        .collect();
-    if !branch_operations.contains(&current_op) {
-        panic!("{op_hash} is not contained in this branch's operations.");
-    }
-    let operation = Operation::get_by_hash(operation_conn, op_hash)
-        .unwrap_or_else(|_| panic!("Hash {op_hash} does not exist."));
-    let full_op_hash = operation.hash.clone();
-    move_to(conn, operation_conn, &operation);
+fn inverted_changeset(operation: &Operation) -> Vec<u8> {
+    let contents = load_changeset(operation);
+    let mut inverted_contents: Vec<u8> = vec![];
+    session::invert_strm(&mut &contents[..], &mut inverted_contents).unwrap();
+    inverted_contents
+}
 
-    if current_branch.name != "main" {
-        match operation_conn.execute(
-            "UPDATE branch SET start_operation_hash = ?2 WHERE id = ?1",
-            (current_branch_id, full_op_hash.to_string()),
-        ) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("Unable to reset branch: {e}");
-            }
+pub fn revert_changeset(conn: &Connection, operation: &Operation) {
+    apply_recorded_changeset(conn, &inverted_changeset(operation));
+}
+
+// The keys of the rows of a table a changeset inserts, taken from the table's first column.
+pub(crate) fn inserted_keys(mut changeset: &[u8], table: &str) -> Vec<String> {
+    let input: &mut dyn Read = &mut changeset;
+    let mut iter = ChangesetIter::start_strm(&input).unwrap();
+    let mut keys = vec![];
+    while let Some(item) = iter.next().unwrap() {
+        let op = item.op().unwrap();
+        if op.table_name() == table && op.code() == Action::SQLITE_INSERT {
+            keys.push(match item.new_value(0).unwrap() {
+                ValueRef::Integer(value) => value.to_string(),
+                value => value.as_str().unwrap().to_string(),
+            });
         }
     }
+    keys
+}
 
-    // hide all child operations from this point
-    for op in Operation::query(
-        operation_conn,
-        "select * from operation where parent_hash = ?1",
-        rusqlite::params!(Value::from(full_op_hash.to_string())),
-    )
-    .iter()
-    {
-        Branch::mask_operation(operation_conn, current_branch_id, &op.hash);
-    }
-    OperationState::set_operation(operation_conn, db_uuid, &full_op_hash);
+// Operations that revert another hold deletions, which only apply to the database the rows were
+// made in, so they are replayed as recorded rather than through apply_changeset.
+pub const REVERT_CHANGE_TYPE: &str = "changeset_revert";
+
+// The first operation after the given one that refers to rows it created, which reverting it would
+// leave dangling.
+fn find_revert_dependency(operation: &Operation, later_operations: &[Operation]) -> Option<String> {
+    let changeset = load_changeset(operation);
+    let created =
+        |table: &str| -> HashSet<String> { inserted_keys(&changeset, table).into_iter().collect() };
+    let (sequences, block_groups, nodes, edges, paths, accessions, accession_edges) = (
+        created("sequences"),
+        created("block_groups"),
+        created("nodes"),
+        created("edges"),
+        created("paths"),
+        created("accessions"),
+        created("accession_edges"),
+    );
+    later_operations
+        .iter()
+        .find(|later| {
+            let dependencies = load_changeset_dependencies(later);
+            dependencies
+                .sequences
+                .iter()
+                .any(|sequence| sequences.contains(&sequence.hash))
+                || dependencies
+                    .block_group
+                    .iter()
+                    .any(|block_group| block_groups.contains(&block_group.id.to_string()))
+                || dependencies
+                    .nodes
+                    .iter()
+                    .any(|node| nodes.contains(&node.id.to_string()))
+                || dependencies
+                    .edges
+                    .iter()
+                    .any(|edge| edges.contains(&edge.id.to_string()))
+                || dependencies
+                    .paths
+                    .iter()
+                    .any(|path| paths.contains(&path.id.to_string()))
+                || dependencies
+                    .accessions
+                    .iter()
+                    .any(|accession| accessions.contains(&accession.id.to_string()))
+                || dependencies
+                    .accession_edges
+                    .iter()
+                    .any(|edge| accession_edges.contains(&edge.id.to_string()))
+        })
+        .map(|later| later.hash.clone())
 }
 
-pub fn apply<'a>(
+/* Undoes an operation of the current operation's history with a new operation, like git revert,
+   so a bad change can be dropped without resetting the operations made since. The inverse of the
+   operation's changeset is applied and recorded as the new operation's changes. Operations made
+   since that refer to rows the reverted operation created, such as an update of the sample it
+   made, have to be reverted first.
+*/
+pub fn revert<'a>(
     conn: &Connection,
     operation_conn: &Connection,
+    db_uuid: &str,
     op_hash: &str,
     force_hash: impl Into<Option<&'a str>>,
-) -> Operation {
-    let mut session = start_operation(conn);
+) -> Result<Operation, OperationError> {
     let operation = Operation::get_by_hash(operation_conn, op_hash)
         .unwrap_or_else(|_| panic!("Hash {op_hash} does not exist."));
-    let changeset = load_changeset(&operation);
-    let input: &mut dyn Read = &mut changeset.as_slice();
-    let mut iter = ChangesetIter::start_strm(&input).unwrap();
-    let dependencies = load_changeset_dependencies(&operation);
-    apply_changeset(conn, &mut iter, &dependencies);
+    let current_op = OperationState::get_operation(operation_conn, db_uuid).unwrap();
+    let history = Operation::get_upstream(operation_conn, current_op.clone());
+    let position = history
+        .iter()
+        .position(|hash| *hash == operation.hash)
+        .unwrap_or_else(|| panic!("{op_hash} is not in the history of the current operation."));
+    let later_operations = history[position + 1..]
+        .iter()
+        .map(|hash| Operation::get_by_hash(operation_conn, hash).unwrap())
+        .collect::<Vec<_>>();
+    if let Some(dependent) = find_revert_dependency(&operation, &later_operations) {
+        return Err(OperationError::RevertDependency(dependent));
+    }
+
+    let mut session = start_operation(conn);
+    apply_recorded_changeset(conn, &inverted_changeset(&operation));
+    if session.is_empty() {
+        return Err(OperationError::NoChanges);
+    }
     let full_op_hash = operation.hash.clone();
+    // reverting a revert makes the changes of the operation it reverted again, so the hash comes
+    // from what is reverted and where rather than from the changes
+    let hash = force_hash.into().map_or_else(
+        || {
+            let mut hasher = Sha256::new();
+            hasher.update(REVERT_CHANGE_TYPE);
+            hasher.update(&full_op_hash);
+            hasher.update(&current_op);
+            format!("{:x}", hasher.finalize())
+        },
+        str::to_string,
+    );
     end_operation(
         conn,
         operation_conn,
@@ -892,25 +1444,206 @@ pub fn apply<'a>(
         OperationInfo {
             file_path: format!("{full_op_hash}.cs"),
             file_type: FileTypes::Changeset,
-            description: "changeset_application".to_string(),
+            description: REVERT_CHANGE_TYPE.to_string(),
         },
-        &format!("Applied changeset {full_op_hash}."),
-        force_hash,
+        &format!("Reverted changeset {full_op_hash}."),
+        hash.as_str(),
     )
-    .unwrap()
 }
 
-pub fn merge<'a>(
+/* Replaces consecutive operations ending at the current one with a single operation holding their
+   combined changes, such as the steps of an exploratory session. The database moves back to before
+   the first of them, the concatenated changeset is applied again, and the squashed operations are
+   hidden from the branch like a reset hides them. The summaries of the operations are kept in order,
+   and their messages are joined unless a message is given.
+*/
+pub fn squash(
     conn: &Connection,
     operation_conn: &Connection,
     db_uuid: &str,
-    source_branch: i64,
-    other_branch: i64,
-    force_hash: impl Into<Option<&'a str>>,
-) -> Vec<Operation> {
-    let mut new_operations: Vec<Operation> = vec![];
-    let hash_prefix = force_hash.into();
-    let current_branch =
+    op_hashes: &[String],
+    message: Option<&str>,
+) -> Result<Operation, OperationError> {
+    let invalid = |reason: String| Err(OperationError::InvalidRange(reason));
+    if op_hashes.len() < 2 {
+        return invalid("squashing needs at least two operations".to_string());
+    }
+    let current_op = OperationState::get_operation(operation_conn, db_uuid).unwrap();
+    let current_branch_id = OperationState::get_current_branch(operation_conn, db_uuid).unwrap();
+    let history = Operation::get_upstream(operation_conn, current_op);
+    if !history.ends_with(op_hashes) {
+        return invalid(
+            "the operations have to be consecutive and end at the current operation".to_string(),
+        );
+    }
+    if history.len() == op_hashes.len() {
+        return invalid("the first operation of the database can't be squashed".to_string());
+    }
+    let operations = op_hashes
+        .iter()
+        .map(|hash| Operation::get_by_hash(operation_conn, hash).unwrap())
+        .collect::<Vec<_>>();
+    for operation in operations.iter() {
+        if operation.branch_id != current_branch_id {
+            return invalid(format!("{} was made on another branch", operation.hash));
+        }
+        // a revert's deletions would make the squashed operation one that can only be replayed as
+        // recorded
+        if operation.change_type == REVERT_CHANGE_TYPE {
+            return invalid(format!("{} is a revert", operation.hash));
+        }
+    }
+
+    let mut combined = load_changeset(&operations[0]);
+    for operation in operations[1..].iter() {
+        let mut concatenated = vec![];
+        session::concat_strm(
+            &mut combined.as_slice(),
+            &mut load_changeset(operation).as_slice(),
+            &mut concatenated,
+        )
+        .unwrap();
+        combined = concatenated;
+    }
+    let summary = operations
+        .iter()
+        .flat_map(|operation| {
+            OperationSummary::query(
+                operation_conn,
+                "select * from operation_summary where operation_hash = ?1",
+                vec![Value::from(operation.hash.clone())],
+            )
+        })
+        .map(|summary| summary.summary)
+        .join("\n");
+    let message = message.map(str::to_string).unwrap_or_else(|| {
+        operations
+            .iter()
+            .filter_map(|operation| operation.message.clone())
+            .join("\n")
+    });
+
+    let parent_hash = &history[history.len() - op_hashes.len() - 1];
+    move_to(
+        conn,
+        operation_conn,
+        &Operation::get_by_hash(operation_conn, parent_hash).unwrap(),
+    );
+    Branch::mask_operation(operation_conn, current_branch_id, &operations[0].hash);
+
+    let mut session = start_operation(conn);
+    apply_recorded_changeset(conn, &combined);
+    let first = &operations[0].hash;
+    let last = &operations[operations.len() - 1].hash;
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: format!("{first}..{last}"),
+            file_type: FileTypes::Changeset,
+            description: "squash".to_string(),
+        },
+        &summary,
+        None,
+    )?;
+    if !message.is_empty() {
+        Operation::set_message(operation_conn, &operation.hash, &message).unwrap();
+    }
+    Ok(Operation::get_by_hash(operation_conn, &operation.hash).unwrap())
+}
+
+pub fn reset(conn: &Connection, operation_conn: &Connection, db_uuid: &str, op_hash: &str) {
+    let current_op = OperationState::get_operation(operation_conn, db_uuid).unwrap();
+    let current_branch_id = OperationState::get_current_branch(operation_conn, db_uuid).unwrap();
+    let current_branch = Branch::get_by_id(operation_conn, current_branch_id).unwrap();
+    let branch_operations: Vec<String> = Branch::get_operations(operation_conn, current_branch_id)
+        .iter()
+        .map(|b| b.hash.clone())
+        .collect();
+    if !branch_operations.contains(&current_op) {
+        panic!("{op_hash} is not contained in this branch's operations.");
+    }
+    let operation = Operation::get_by_hash(operation_conn, op_hash)
+        .unwrap_or_else(|_| panic!("Hash {op_hash} does not exist."));
+    let full_op_hash = operation.hash.clone();
+    move_to(conn, operation_conn, &operation);
+
+    if current_branch.name != "main" {
+        match operation_conn.execute(
+            "UPDATE branch SET start_operation_hash = ?2 WHERE id = ?1",
+            (current_branch_id, full_op_hash.to_string()),
+        ) {
+            Ok(_) => {}
+            Err(e) => {
+                panic!("Unable to reset branch: {e}");
+            }
+        }
+    }
+
+    // hide all child operations from this point
+    for op in Operation::query(
+        operation_conn,
+        "select * from operation where parent_hash = ?1",
+        rusqlite::params!(Value::from(full_op_hash.to_string())),
+    )
+    .iter()
+    {
+        Branch::mask_operation(operation_conn, current_branch_id, &op.hash);
+    }
+    OperationState::set_operation(operation_conn, db_uuid, &full_op_hash);
+}
+
+// Makes the changes an operation recorded to a database, without recording a new operation.
+pub fn replay_operation(conn: &Connection, operation: &Operation) {
+    if operation.change_type == REVERT_CHANGE_TYPE {
+        apply_recorded_changeset(conn, &load_changeset(operation));
+    } else {
+        let mut changeset = open_changeset(operation);
+        let input: &mut dyn Read = &mut *changeset;
+        let mut iter = ChangesetIter::start_strm(&input).unwrap();
+        let dependencies = load_changeset_dependencies(operation);
+        apply_changeset(conn, &mut iter, &dependencies);
+    }
+}
+
+pub fn apply<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    op_hash: &str,
+    force_hash: impl Into<Option<&'a str>>,
+) -> Operation {
+    let mut session = start_operation(conn);
+    let operation = Operation::get_by_hash(operation_conn, op_hash)
+        .unwrap_or_else(|_| panic!("Hash {op_hash} does not exist."));
+    replay_operation(conn, &operation);
+    let full_op_hash = operation.hash.clone();
+    end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: format!("{full_op_hash}.cs"),
+            file_type: FileTypes::Changeset,
+            description: "changeset_application".to_string(),
+        },
+        &format!("Applied changeset {full_op_hash}."),
+        force_hash,
+    )
+    .unwrap()
+}
+
+pub fn merge<'a>(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+    source_branch: i64,
+    other_branch: i64,
+    force_hash: impl Into<Option<&'a str>>,
+) -> Vec<Operation> {
+    let mut new_operations: Vec<Operation> = vec![];
+    let hash_prefix = force_hash.into();
+    let current_branch =
         OperationState::get_current_branch(operation_conn, db_uuid).expect("No current branch.");
     if source_branch != current_branch {
         panic!("Unable to merge branch. Source branch and current branch must match. Checkout the branch you wish to merge into.");
@@ -940,6 +1673,314 @@ pub fn merge<'a>(
     new_operations
 }
 
+// The regions of graphs an operation changes, as the span of coordinates on each node that the edges
+// it adds to or removes from a graph attach at. Graphs and nodes are identified by their content,
+// so the regions of operations made on different branches can be compared.
+fn changed_regions(
+    conn: &Connection,
+    operation: &Operation,
+) -> HashMap<(String, String), (i64, i64)> {
+    let items = changeset_rows(&load_changeset(operation));
+    let dependencies = load_changeset_dependencies(operation);
+    let mut content = ChangesetContent::new(conn, &items, &dependencies);
+    let mut regions: HashMap<(String, String), (i64, i64)> = HashMap::new();
+    for (table, code, values) in items.iter() {
+        if table != "block_group_edges" || *code == Action::SQLITE_UPDATE {
+            continue;
+        }
+        let value = |column: usize| {
+            let (old, new) = &values[column];
+            new.clone().or(old.clone())
+        };
+        let (Some(Value::Integer(block_group_id)), Some(Value::Integer(edge_id))) =
+            (value(1), value(2))
+        else {
+            continue;
+        };
+        let (Some(block_group), Some(edge)) = (
+            content.row("block_groups", block_group_id),
+            content.row("edges", edge_id),
+        ) else {
+            continue;
+        };
+        let graph = match (&block_group[1], &block_group[2], &block_group[3]) {
+            (Value::Text(collection), Value::Text(sample), Value::Text(name)) => {
+                format!("graph {name} of sample {sample} in {collection}")
+            }
+            (Value::Text(collection), _, Value::Text(name)) => {
+                format!("graph {name} in {collection}")
+            }
+            _ => content.key("block_groups", block_group_id).to_string(),
+        };
+        for (node_column, coordinate_column) in [(1, 2), (4, 5)] {
+            let (Value::Integer(node_id), Value::Integer(coordinate)) =
+                (&edge[node_column], &edge[coordinate_column])
+            else {
+                continue;
+            };
+            // every path starts and ends at the same terminal nodes
+            if Node::is_terminal(*node_id) {
+                continue;
+            }
+            let node = content.key("nodes", *node_id).to_string();
+            regions
+                .entry((graph.clone(), node))
+                .and_modify(|(start, end)| {
+                    *start = (*start).min(*coordinate);
+                    *end = (*end).max(*coordinate);
+                })
+                .or_insert((*coordinate, *coordinate));
+        }
+    }
+    regions
+}
+
+// Finds an operation of the series being rebased that changes a region of a graph an operation of
+// the other series also changes, returning the two operations and the graph.
+fn find_rebase_conflict(
+    conn: &Connection,
+    operations: &[Operation],
+    other_operations: &[Operation],
+) -> Option<(String, String, String)> {
+    let other_regions = other_operations
+        .iter()
+        .map(|operation| (operation, changed_regions(conn, operation)))
+        .collect::<Vec<_>>();
+    for operation in operations.iter() {
+        for ((graph, node), (start, end)) in changed_regions(conn, operation).iter() {
+            for (other_operation, regions) in other_regions.iter() {
+                if let Some((other_start, other_end)) = regions.get(&(graph.clone(), node.clone()))
+                {
+                    if start <= other_end && other_start <= end {
+                        return Some((
+                            operation.hash.clone(),
+                            other_operation.hash.clone(),
+                            graph.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// The operations of the current branch since it split from another branch, in the order they were
+// made, and the last operation the two branches share.
+pub fn rebase_candidates(
+    operation_conn: &Connection,
+    db_uuid: &str,
+    onto_branch: &Branch,
+) -> Result<(Vec<Operation>, String), OperationError> {
+    let invalid = |reason: String| Err(OperationError::InvalidRebase(reason));
+    let current_branch_id = OperationState::get_current_branch(operation_conn, db_uuid).unwrap();
+    if onto_branch.id == current_branch_id {
+        return invalid(format!("{} is the current branch", onto_branch.name));
+    }
+    let (Some(current_op), Some(onto_head)) = (
+        OperationState::get_operation(operation_conn, db_uuid),
+        onto_branch.current_operation_hash.clone(),
+    ) else {
+        return invalid("both branches need operations".to_string());
+    };
+    let Some(ancestor) = Operation::common_ancestor(operation_conn, &current_op, &onto_head) else {
+        return invalid(format!(
+            "the branches have no common operation with {}",
+            onto_branch.name
+        ));
+    };
+    let history = Operation::get_upstream(operation_conn, current_op);
+    let split = history.iter().position(|hash| *hash == ancestor).unwrap();
+    let operations = history[split + 1..]
+        .iter()
+        .map(|hash| Operation::get_by_hash(operation_conn, hash).unwrap())
+        .collect();
+    Ok((operations, ancestor))
+}
+
+// Replays operations of the current branch since it split from another branch on top of the other
+// branch's head, like git rebase, so the current branch continues from it. The operations are given
+// in the order to replay them, and those of the split left out are dropped. The replayed operations
+// keep the authorship and messages of the originals, which are hidden from the branch as with a
+// reset. Nothing is changed if a replayed operation changes a region of a graph that an operation of
+// the other branch since the split also changes.
+pub fn rebase(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+    onto_branch: &Branch,
+    operations: &[Operation],
+) -> Result<Vec<Operation>, OperationError> {
+    let (candidates, ancestor) = rebase_candidates(operation_conn, db_uuid, onto_branch)?;
+    for operation in operations.iter() {
+        if !candidates.contains(operation) {
+            return Err(OperationError::InvalidRebase(format!(
+                "{} isn't an operation of the current branch since it split from {}",
+                operation.hash, onto_branch.name
+            )));
+        }
+        if operations
+            .iter()
+            .filter(|other| *other == operation)
+            .count()
+            > 1
+        {
+            return Err(OperationError::InvalidRebase(format!(
+                "{} is given more than once",
+                operation.hash
+            )));
+        }
+    }
+    let onto_head = onto_branch.current_operation_hash.clone().unwrap();
+    let onto_history = Operation::get_upstream(operation_conn, onto_head.clone());
+    let onto_split = onto_history
+        .iter()
+        .position(|hash| *hash == ancestor)
+        .unwrap();
+    let onto_operations = onto_history[onto_split + 1..]
+        .iter()
+        .map(|hash| Operation::get_by_hash(operation_conn, hash).unwrap())
+        .collect::<Vec<_>>();
+    if let Some((operation, other_operation, graph)) =
+        find_rebase_conflict(conn, operations, &onto_operations)
+    {
+        return Err(OperationError::RebaseConflict(
+            operation,
+            other_operation,
+            graph,
+        ));
+    }
+
+    // when the current branch already continues from the other branch's head, operations kept as
+    // they are at the start of the series aren't replayed
+    let kept = if onto_head == ancestor {
+        operations
+            .iter()
+            .zip(candidates.iter())
+            .take_while(|(operation, candidate)| operation == candidate)
+            .count()
+    } else {
+        0
+    };
+    if kept == candidates.len() {
+        return Ok(vec![]);
+    }
+    let base = if kept > 0 {
+        candidates[kept - 1].hash.clone()
+    } else {
+        onto_head.clone()
+    };
+    let current_branch_id = OperationState::get_current_branch(operation_conn, db_uuid).unwrap();
+    move_to(
+        conn,
+        operation_conn,
+        &Operation::get_by_hash(operation_conn, &base).unwrap(),
+    );
+    Branch::mask_operation(operation_conn, current_branch_id, &candidates[kept].hash);
+    if onto_head != ancestor {
+        operation_conn
+            .execute(
+                "UPDATE branch SET start_operation_hash = ?2 WHERE id = ?1",
+                (current_branch_id, &onto_head),
+            )
+            .unwrap();
+    }
+    OperationState::set_operation(operation_conn, db_uuid, &base);
+
+    let mut new_operations = vec![];
+    for operation in operations[kept..].iter() {
+        println!("Replaying operation {op_id}", op_id = operation.hash);
+        let mut session = start_operation(conn);
+        replay_operation(conn, operation);
+        let summary = OperationSummary::query(
+            operation_conn,
+            "select * from operation_summary where operation_hash = ?1",
+            vec![Value::from(operation.hash.clone())],
+        )
+        .iter()
+        .map(|summary| summary.summary.clone())
+        .join("\n");
+        let new_operation = end_operation(
+            conn,
+            operation_conn,
+            &mut session,
+            OperationInfo {
+                file_path: format!("{}.cs", operation.hash),
+                file_type: FileTypes::Changeset,
+                description: "rebase".to_string(),
+            },
+            &summary,
+            None,
+        )?;
+        Operation::copy_metadata(operation_conn, &new_operation.hash, operation).unwrap();
+        new_operations.push(Operation::get_by_hash(operation_conn, &new_operation.hash).unwrap());
+    }
+    Ok(new_operations)
+}
+
+// The list of operations an interactive rebase starts from, one per line as `pick <hash>` followed
+// by the operation's message or summary, for the user to reorder or drop.
+pub fn rebase_todo(operation_conn: &Connection, operations: &[Operation]) -> String {
+    let mut todo = String::new();
+    for operation in operations.iter() {
+        let description = operation.message.clone().unwrap_or_else(|| {
+            OperationSummary::query(
+                operation_conn,
+                "select * from operation_summary where operation_hash = ?1",
+                vec![Value::from(operation.hash.clone())],
+            )
+            .iter()
+            .map(|summary| summary.summary.clone())
+            .join(" ")
+        });
+        let description = description.lines().next().unwrap_or_default();
+        todo.push_str(&format!(
+            "pick {hash} {description}\n",
+            hash = operation.hash
+        ));
+    }
+    todo.push_str(
+        "\n# Operations are replayed from the top. Reorder the lines to replay them in another\n\
+         # order, and change pick to drop or remove a line to leave an operation out.\n",
+    );
+    todo
+}
+
+// The operations an edited rebase todo list picks, in order. Hashes can be shortened to a prefix of
+// a single candidate.
+pub fn parse_rebase_todo(
+    todo: &str,
+    candidates: &[Operation],
+) -> Result<Vec<Operation>, OperationError> {
+    let mut operations = vec![];
+    for line in todo.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (command, hash) = (fields.next().unwrap(), fields.next().unwrap_or_default());
+        let matches = candidates
+            .iter()
+            .filter(|operation| !hash.is_empty() && operation.hash.starts_with(hash))
+            .collect::<Vec<_>>();
+        let [operation] = matches[..] else {
+            return Err(OperationError::InvalidRebase(format!(
+                "{line} doesn't name one operation being rebased"
+            )));
+        };
+        match command {
+            "pick" | "p" => operations.push(operation.clone()),
+            "drop" | "d" => {}
+            _ => {
+                return Err(OperationError::InvalidRebase(format!(
+                    "{command} isn't pick or drop"
+                )))
+            }
+        }
+    }
+    Ok(operations)
+}
+
 pub fn move_to(conn: &Connection, operation_conn: &Connection, operation: &Operation) {
     let current_op_hash =
         OperationState::get_operation(operation_conn, &operation.db_uuid).unwrap();
@@ -956,10 +1997,14 @@ pub fn move_to(conn: &Connection, operation_conn: &Connection, operation: &Opera
         match direction {
             Direction::Incoming => {
                 println!("Reverting operation {operation_hash}");
-                revert_changeset(
-                    conn,
-                    &Operation::get_by_hash(operation_conn, operation_hash)
-                        .unwrap_or_else(|_| panic!("Hash {operation_hash} does not exist.")),
+                let op_to_revert = Operation::get_by_hash(operation_conn, operation_hash)
+                    .unwrap_or_else(|_| panic!("Hash {operation_hash} does not exist."));
+                // rows that a sparse checkout never materialized are skipped by the revert
+                revert_changeset(conn, &op_to_revert);
+                SparseCheckout::remove_pending_operation(
+                    operation_conn,
+                    &operation.db_uuid,
+                    &op_to_revert.hash,
                 );
                 OperationState::set_operation(operation_conn, &operation.db_uuid, next_op);
             }
@@ -967,18 +2012,407 @@ pub fn move_to(conn: &Connection, operation_conn: &Connection, operation: &Opera
                 println!("Applying operation {next_op}");
                 let op_to_apply = Operation::get_by_hash(operation_conn, next_op)
                     .unwrap_or_else(|_| panic!("Hash {next_op} does not exist."));
-                let changeset = load_changeset(&op_to_apply);
-                let input: &mut dyn Read = &mut changeset.as_slice();
-                let mut iter = ChangesetIter::start_strm(&input).unwrap();
-                let dependencies = load_changeset_dependencies(&op_to_apply);
-                apply_changeset(conn, &mut iter, &dependencies);
+                if op_to_apply.change_type == REVERT_CHANGE_TYPE {
+                    apply_recorded_changeset(conn, &load_changeset(&op_to_apply));
+                } else {
+                    let mut changeset = open_changeset(&op_to_apply);
+                    let input: &mut dyn Read = &mut *changeset;
+                    let mut iter = ChangesetIter::start_strm(&input).unwrap();
+                    let dependencies = load_changeset_dependencies(&op_to_apply);
+                    let collections =
+                        SparseCheckout::get_collections(operation_conn, &operation.db_uuid);
+                    let skipped_collections = apply_changeset_to_collections(
+                        conn,
+                        &mut iter,
+                        &dependencies,
+                        collections.as_ref(),
+                    );
+                    for collection_name in skipped_collections {
+                        SparseCheckout::add_pending_operation(
+                            operation_conn,
+                            &operation.db_uuid,
+                            &collection_name,
+                            &op_to_apply.hash,
+                        );
+                    }
+                }
                 OperationState::set_operation(operation_conn, &operation.db_uuid, next_op);
             }
         }
     }
 }
 
-pub fn start_operation(conn: &Connection) -> session::Session {
+// Restricts checkouts to the given collections. Rows of other collections are left as they are and
+// operations applied from now on only materialize the selected collections. Operations that were
+// skipped for a collection are replayed when it is selected again, and an empty list of collections
+// materializes everything and ends the sparse checkout.
+pub fn sparse_checkout(
+    conn: &Connection,
+    operation_conn: &Connection,
+    db_uuid: &str,
+    collections: &[String],
+) {
+    let to_materialize = if collections.is_empty() {
+        SparseCheckout::get_pending_collections(operation_conn, db_uuid)
+    } else {
+        collections.to_vec()
+    };
+    for collection_name in to_materialize.iter() {
+        let selected = HashSet::from([collection_name.clone()]);
+        for op_hash in
+            SparseCheckout::get_pending_operations(operation_conn, db_uuid, collection_name)
+        {
+            println!("Materializing {collection_name} for operation {op_hash}");
+            let operation = Operation::get_by_hash(operation_conn, &op_hash)
+                .unwrap_or_else(|_| panic!("Hash {op_hash} does not exist."));
+            let mut changeset = open_changeset(&operation);
+            let input: &mut dyn Read = &mut *changeset;
+            let mut iter = ChangesetIter::start_strm(&input).unwrap();
+            let dependencies = load_changeset_dependencies(&operation);
+            apply_changeset_to_collections(conn, &mut iter, &dependencies, Some(&selected));
+        }
+        SparseCheckout::clear_pending_operations(operation_conn, db_uuid, collection_name);
+    }
+    SparseCheckout::set_collections(operation_conn, db_uuid, collections);
+}
+
+// Tables with an integer surrogate id, which differs between databases holding the same data. Rows
+// of these tables are identified by the rest of their values instead when hashing a changeset.
+const SURROGATE_KEY_TABLES: [&str; 11] = [
+    "block_groups",
+    "paths",
+    "nodes",
+    "edges",
+    "path_edges",
+    "block_group_edges",
+    "accessions",
+    "accession_edges",
+    "accession_paths",
+    "annotations",
+    "current_path_choices",
+];
+
+// The table a column of a changeset row refers to by its surrogate id.
+fn referenced_table(table: &str, column: usize) -> Option<&'static str> {
+    match (table, column) {
+        ("paths", 1) | ("block_group_edges", 1) | ("current_path_choices", 1) => {
+            Some("block_groups")
+        }
+        ("edges", 1 | 4)
+        | ("accession_edges", 1 | 4)
+        | ("node_attributes", 0)
+        | ("node_coverage", 1) => Some("nodes"),
+        ("path_edges", 3) | ("block_group_edges", 2) | ("edge_attributes", 0) => Some("edges"),
+        ("path_edges", 1)
+        | ("accessions", 2)
+        | ("annotations", 1)
+        | ("current_path_choices", 2 | 3) => Some("paths"),
+        ("accessions", 3) | ("accession_paths", 1) => Some("accessions"),
+        ("accession_paths", 3) => Some("accession_edges"),
+        _ => None,
+    }
+}
+
+// Describes the rows of a changeset by their content, replacing surrogate ids with the content of
+// the rows they identify, so the same change made in two databases is described the same way.
+struct ChangesetContent<'a> {
+    conn: &'a Connection,
+    // rows the changeset creates or removes and the rows it depends on, as they were when the
+    // changeset was made, before the database
+    rows: HashMap<(String, i64), Vec<Value>>,
+    keys: HashMap<(String, i64), serde_json::Value>,
+}
+
+// A row of a changeset as its table, what was done to it, and the old and new value of each of its
+// columns, which are None where the changeset doesn't hold them.
+type ChangesetRow = (String, Action, Vec<(Option<Value>, Option<Value>)>);
+
+fn changeset_rows(mut changes: &[u8]) -> Vec<ChangesetRow> {
+    let input: &mut dyn Read = &mut changes;
+    let mut iter = ChangesetIter::start_strm(&input).unwrap();
+    let mut rows = vec![];
+    while let Some(item) = iter.next().unwrap() {
+        let op = item.op().unwrap();
+        let values = (0..op.number_of_columns() as usize)
+            .map(|column| {
+                (
+                    item.old_value(column).ok().map(Value::from),
+                    item.new_value(column).ok().map(Value::from),
+                )
+            })
+            .collect::<Vec<_>>();
+        rows.push((op.table_name().to_string(), op.code(), values));
+    }
+    rows
+}
+
+// The hashes of the sequences a changeset inserts.
+pub fn changeset_sequence_hashes(changes: &[u8]) -> Vec<String> {
+    changeset_rows(changes)
+        .into_iter()
+        .filter(|(table, code, _)| table == "sequences" && *code == Action::SQLITE_INSERT)
+        .filter_map(|(_, _, values)| match &values[0].1 {
+            Some(Value::Text(hash)) => Some(hash.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+impl<'a> ChangesetContent<'a> {
+    fn new(
+        conn: &'a Connection,
+        items: &[ChangesetRow],
+        dependencies: &DependencyModels,
+    ) -> ChangesetContent<'a> {
+        let mut content = ChangesetContent {
+            conn,
+            rows: dependency_rows(dependencies)
+                .into_iter()
+                .filter_map(|(table, row)| match row[0] {
+                    Value::Integer(id) => Some(((table.to_string(), id), row)),
+                    _ => None,
+                })
+                .collect(),
+            keys: HashMap::new(),
+        };
+        for (table, code, values) in items.iter() {
+            if !SURROGATE_KEY_TABLES.contains(&table.as_str()) || *code == Action::SQLITE_UPDATE {
+                continue;
+            }
+            let row = values
+                .iter()
+                .map(|(old, new)| new.clone().or(old.clone()).unwrap_or(Value::Null))
+                .collect::<Vec<Value>>();
+            if let Value::Integer(id) = row[0] {
+                content.rows.insert((table.clone(), id), row);
+            }
+        }
+        content
+    }
+
+    fn row(&self, table: &str, id: i64) -> Option<Vec<Value>> {
+        if let Some(row) = self.rows.get(&(table.to_string(), id)) {
+            return Some(row.clone());
+        }
+        let mut stmt = self
+            .conn
+            .prepare(&format!("select * from {table} where id = ?1;"))
+            .unwrap();
+        let column_count = stmt.column_count();
+        stmt.query_row((id,), |row| {
+            (0..column_count)
+                .map(|column| row.get::<_, Value>(column))
+                .collect::<rusqlite::Result<Vec<Value>>>()
+        })
+        .ok()
+    }
+
+    fn key(&mut self, table: &str, id: i64) -> serde_json::Value {
+        if let Some(key) = self.keys.get(&(table.to_string(), id)) {
+            return key.clone();
+        }
+        let key = match self.row(table, id) {
+            Some(row) => serde_json::Value::Array(
+                row.iter()
+                    .enumerate()
+                    .skip(1)
+                    .map(|(column, value)| self.value(table, column, value))
+                    .collect(),
+            ),
+            // a row found nowhere can only be told apart by its id
+            None => serde_json::json!({ "id": id }),
+        };
+        self.keys.insert((table.to_string(), id), key.clone());
+        key
+    }
+
+    fn value(&mut self, table: &str, column: usize, value: &Value) -> serde_json::Value {
+        match (value, referenced_table(table, column)) {
+            (Value::Integer(id), Some(referenced)) => self.key(referenced, *id),
+            (Value::Integer(_), None) if column == 0 && SURROGATE_KEY_TABLES.contains(&table) => {
+                serde_json::Value::Null
+            }
+            (Value::Null, _) => serde_json::Value::Null,
+            (Value::Integer(value), _) => serde_json::json!(value),
+            (Value::Real(value), _) => serde_json::json!(value),
+            (Value::Text(value), _) => serde_json::json!(value),
+            (Value::Blob(value), _) => serde_json::json!({
+                "blob": value.iter().map(|byte| format!("{byte:02x}")).collect::<String>()
+            }),
+        }
+    }
+}
+
+// The rows of a changeset's dependencies, with their columns in table order.
+fn dependency_rows(dependencies: &DependencyModels) -> Vec<(&'static str, Vec<Value>)> {
+    let block_groups = dependencies.block_group.iter().map(|block_group| {
+        (
+            "block_groups",
+            vec![
+                Value::from(block_group.id),
+                Value::from(block_group.collection_name.clone()),
+                Value::from(block_group.sample_name.clone()),
+                Value::from(block_group.name.clone()),
+                Value::from(block_group.is_circular),
+            ],
+        )
+    });
+    let nodes = dependencies.nodes.iter().map(|node| {
+        (
+            "nodes",
+            vec![
+                Value::from(node.id),
+                Value::from(node.sequence_hash.clone()),
+                Value::from(node.hash.clone()),
+            ],
+        )
+    });
+    let edges = dependencies.edges.iter().map(|edge| {
+        (
+            "edges",
+            vec![
+                Value::from(edge.id),
+                Value::from(edge.source_node_id),
+                Value::from(edge.source_coordinate),
+                Value::from(edge.source_strand),
+                Value::from(edge.target_node_id),
+                Value::from(edge.target_coordinate),
+                Value::from(edge.target_strand),
+            ],
+        )
+    });
+    let paths = dependencies.paths.iter().map(|path| {
+        (
+            "paths",
+            vec![
+                Value::from(path.id),
+                Value::from(path.block_group_id),
+                Value::from(path.name.clone()),
+            ],
+        )
+    });
+    let accessions = dependencies.accessions.iter().map(|accession| {
+        (
+            "accessions",
+            vec![
+                Value::from(accession.id),
+                Value::from(accession.name.clone()),
+                Value::from(accession.path_id),
+                Value::from(accession.parent_accession_id),
+            ],
+        )
+    });
+    let accession_edges = dependencies.accession_edges.iter().map(|edge| {
+        (
+            "accession_edges",
+            vec![
+                Value::from(edge.id),
+                Value::from(edge.source_node_id),
+                Value::from(edge.source_coordinate),
+                Value::from(edge.source_strand),
+                Value::from(edge.target_node_id),
+                Value::from(edge.target_coordinate),
+                Value::from(edge.target_strand),
+                Value::from(edge.chromosome_index),
+            ],
+        )
+    });
+    block_groups
+        .chain(nodes)
+        .chain(edges)
+        .chain(paths)
+        .chain(accessions)
+        .chain(accession_edges)
+        .collect()
+}
+
+// A hash of the content of a changeset on top of its parent operation and branch. Row ids are
+// assigned by each database, so rather than hashing the changeset as recorded, rows are described by
+// their content with surrogate ids replaced by the content of the rows they identify (edges by their
+// nodes, nodes by their sequence hash and so on). Rows are hashed separately and sorted, so the order
+// changes were made in doesn't matter either, and the same change gets the same hash in every
+// repository.
+// Rows are looked up in the changeset and its dependencies before the database, which may have
+// changed since, so the hash of an operation can be checked again later.
+pub fn changeset_content_hash(
+    conn: &Connection,
+    changes: &[u8],
+    dependencies: &DependencyModels,
+    parent_hash: Option<&str>,
+    branch_name: &str,
+) -> String {
+    let items = changeset_rows(changes);
+    let mut content = ChangesetContent::new(conn, &items, dependencies);
+
+    let mut row_hashes = items
+        .iter()
+        .map(|(table, code, values)| {
+            let description = match code {
+                Action::SQLITE_INSERT => serde_json::json!({
+                    "insert": table,
+                    "new": values
+                        .iter()
+                        .enumerate()
+                        .map(|(column, (_, new))| {
+                            content.value(table, column, new.as_ref().unwrap_or(&Value::Null))
+                        })
+                        .collect::<Vec<_>>(),
+                }),
+                Action::SQLITE_DELETE => serde_json::json!({
+                    "delete": table,
+                    "old": values
+                        .iter()
+                        .enumerate()
+                        .map(|(column, (old, _))| {
+                            content.value(table, column, old.as_ref().unwrap_or(&Value::Null))
+                        })
+                        .collect::<Vec<_>>(),
+                }),
+                _ => {
+                    // updates only hold the primary key and the columns they change, the row
+                    // itself is identified by its content in the database
+                    let row = match &values[0].0 {
+                        Some(Value::Integer(id))
+                            if SURROGATE_KEY_TABLES.contains(&table.as_str())
+                                && content.row(table, *id).is_some() =>
+                        {
+                            content.key(table, *id)
+                        }
+                        _ => serde_json::Value::Null,
+                    };
+                    serde_json::json!({
+                        "update": table,
+                        "row": row,
+                        "changes": values
+                            .iter()
+                            .enumerate()
+                            .map(|(column, (old, new))| {
+                                [old, new].map(|value| {
+                                    value.as_ref().map(|value| content.value(table, column, value))
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    })
+                }
+            };
+            Sha256::digest(serde_json::to_vec(&description).unwrap()).to_vec()
+        })
+        .collect::<Vec<Vec<u8>>>();
+    row_hashes.sort();
+
+    let mut hasher = Sha256::new();
+    if let Some(parent_hash) = parent_hash {
+        hasher.update(parent_hash.as_bytes());
+    }
+    // the same change made on two branches from the same operation is two operations
+    hasher.update(Sha256::digest(branch_name.as_bytes()));
+    for row_hash in row_hashes.iter() {
+        hasher.update(row_hash);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn start_operation(conn: &Connection) -> session::Session<'_> {
     let mut session = session::Session::new(conn).unwrap();
     attach_session(&mut session);
     session
@@ -999,6 +2433,9 @@ pub fn end_operation<'a>(
     session.changeset_strm(&mut output).unwrap();
 
     let dependencies = get_changeset_dependencies(conn, &output);
+    // The stats table is not attached to the session, so this must happen after the changeset is
+    // captured.
+    BlockGroupStats::refresh(conn, &get_changeset_block_group_ids(&output));
 
     let hash = if let Some(hash) = force_hash.into() {
         hash.to_string()
@@ -1006,10 +2443,27 @@ pub fn end_operation<'a>(
         if output.is_empty() {
             return Err(OperationError::NoChanges);
         }
-        let mut hasher = Sha256::new();
-        hasher.update(&output[..]);
-        hasher.update(&dependencies[..]);
-        format!("{:x}", hasher.finalize())
+        let parent_hash = OperationState::get_operation(operation_conn, &db_uuid);
+        let branch = OperationState::get_current_branch(operation_conn, &db_uuid)
+            .and_then(|branch_id| Branch::get_by_id(operation_conn, branch_id))
+            .expect("No branch is checked out.");
+        let hash = changeset_content_hash(
+            conn,
+            &output,
+            &serde_json::from_slice(&dependencies).unwrap(),
+            parent_hash.as_deref(),
+            &branch.name,
+        );
+        // Redoing a change that was reset away gives the operation the branch already has, so the
+        // branch goes back to it rather than recording the change again.
+        if let Ok(existing) = Operation::get_by_hash(operation_conn, &hash) {
+            if existing.hash == hash && existing.branch_id == branch.id {
+                Branch::unmask_operation(operation_conn, branch.id, &hash);
+                OperationState::set_operation(operation_conn, &db_uuid, &hash);
+                return Ok(existing);
+            }
+        }
+        hash
     };
 
     operation_conn
@@ -1056,6 +2510,9 @@ pub fn end_operation<'a>(
     }
 }
 
+// The tables changesets record. The other core tables aren't part of the history: block_group_stats,
+// path_index and sequence_digests are derived from tracked rows and rebuilt from them, and
+// block_group_locks are held by the applications running against a database.
 pub fn attach_session(session: &mut session::Session) {
     for table in [
         "collections",
@@ -1070,6 +2527,12 @@ pub fn attach_session(session: &mut session::Session) {
         "accessions",
         "accession_edges",
         "accession_paths",
+        "annotations",
+        "current_path_choices",
+        "sample_aliases",
+        "node_attributes",
+        "edge_attributes",
+        "node_coverage",
     ] {
         session.attach(Some(table)).unwrap();
     }
@@ -1200,12 +2663,14 @@ mod tests {
     use super::*;
     use crate::imports::fasta::import_fasta;
     use crate::models::file_types::FileTypes;
+    use crate::models::node::PATH_START_NODE_ID;
     use crate::models::operations::{setup_db, Branch, FileAddition, Operation, OperationState};
     use crate::models::{edge::Edge, metadata, node::Node, sample::Sample};
     use crate::test_helpers::{
-        create_operation, get_connection, get_operation_connection, setup_block_group,
-        setup_gen_dir,
+        create_operation, get_connection, get_operation_connection, get_sample_bg,
+        setup_block_group, setup_gen_dir,
     };
+    use crate::updates::fasta::update_with_fasta;
     use crate::updates::vcf::update_with_vcf;
     use rusqlite::types::Value;
     use std::path::{Path, PathBuf};
@@ -1472,6 +2937,236 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_block_group_stats_follow_operations() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &db_uuid);
+        let mut fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_path.push("fixtures/simple.fa");
+        let mut fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_update_path.push("fixtures/aaaaaaaa.fa");
+
+        let import_op = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            operation_conn,
+            "test",
+            None,
+            "child sample",
+            "m123",
+            2,
+            5,
+            fasta_update_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let child_block_group = get_sample_bg(conn, "test", "child sample");
+        let cached_stats = BlockGroupStats::query(
+            conn,
+            "select * from block_group_stats where block_group_id = ?1",
+            rusqlite::params!(child_block_group.id),
+        );
+        assert_eq!(
+            cached_stats,
+            vec![BlockGroupStats::calculate(conn, child_block_group.id)]
+        );
+        // the 34 bp reference sequence plus the 8 bp insertion
+        assert_eq!(cached_stats[0].node_count, 2);
+        assert_eq!(cached_stats[0].total_length, 42);
+        // the insertion branches off the reference node, and the sample gets a path through it
+        assert_eq!(cached_stats[0].variant_site_count, 1);
+        assert_eq!(cached_stats[0].path_count, 2);
+
+        checkout(conn, operation_conn, &db_uuid, &None, Some(import_op.hash));
+        assert!(BlockGroupStats::query(
+            conn,
+            "select * from block_group_stats where block_group_id = ?1",
+            rusqlite::params!(child_block_group.id),
+        )
+        .is_empty());
+        // the index of the child sample's path goes with it
+        let stale_index_rows: i64 = conn
+            .query_row(
+                "select count(*) from path_index where path_id not in (select id from paths)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stale_index_rows, 0);
+    }
+
+    #[test]
+    fn test_same_change_gets_same_hash_in_different_databases() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let mut hashes = vec![];
+        for unrelated_rows in [0, 3] {
+            let conn = &get_connection(None);
+            let db_uuid = metadata::get_db_uuid(conn);
+            let op_conn = &get_operation_connection(None);
+            setup_db(op_conn, &db_uuid);
+            // rows outside of any operation shift the ids the operations below are given
+            for index in 0..unrelated_rows {
+                let sequence = Sequence::new()
+                    .sequence_type("DNA")
+                    .sequence(&"G".repeat(index + 1))
+                    .save(conn);
+                let node_id = Node::create(conn, &sequence.hash, None);
+                Edge::create(
+                    conn,
+                    PATH_START_NODE_ID,
+                    0,
+                    Strand::Forward,
+                    node_id,
+                    0,
+                    Strand::Forward,
+                );
+            }
+            let import = import_fasta(
+                &fasta_path.to_str().unwrap().to_string(),
+                "test",
+                None,
+                false,
+                conn,
+                op_conn,
+            )
+            .unwrap();
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "child",
+                "m123",
+                2,
+                5,
+                insert_path.to_str().unwrap(),
+            )
+            .unwrap();
+            let update_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+            hashes.push((import.hash, update_hash));
+        }
+        assert_eq!(hashes[0], hashes[1]);
+        assert_ne!(hashes[0].0, hashes[0].1);
+    }
+
+    #[test]
+    fn test_redoing_a_reset_change_returns_to_its_operation() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let import = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let update = || {
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "child",
+                "m123",
+                2,
+                5,
+                insert_path.to_str().unwrap(),
+            )
+            .unwrap()
+        };
+
+        update();
+        let update_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+        reset(conn, op_conn, &db_uuid, &import.hash);
+        update();
+
+        assert_eq!(
+            OperationState::get_operation(op_conn, &db_uuid).unwrap(),
+            update_hash
+        );
+        let branch_id = OperationState::get_current_branch(op_conn, &db_uuid).unwrap();
+        assert!(Branch::get_masked_operations(op_conn, branch_id).is_empty());
+        assert_eq!(
+            Branch::get_operations(op_conn, branch_id)
+                .iter()
+                .map(|op| op.hash.clone())
+                .collect::<Vec<String>>(),
+            vec![import.hash, update_hash]
+        );
+    }
+
+    #[test]
+    fn test_same_change_on_two_branches() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let import = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let other_branch = Branch::create(op_conn, &db_uuid, "other");
+        let update = || {
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "child",
+                "m123",
+                2,
+                5,
+                insert_path.to_str().unwrap(),
+            )
+            .unwrap()
+        };
+
+        update();
+        let main_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+        checkout(conn, op_conn, &db_uuid, &Some("other".to_string()), None);
+        update();
+        let other_hash = OperationState::get_operation(op_conn, &db_uuid).unwrap();
+
+        assert_ne!(main_hash, other_hash);
+        let other_operation = Operation::get_by_hash(op_conn, &other_hash).unwrap();
+        assert_eq!(other_operation.parent_hash, Some(import.hash));
+        assert_eq!(other_operation.branch_id, other_branch.id);
+        assert_eq!(
+            Branch::get_by_name(op_conn, &db_uuid, "main")
+                .unwrap()
+                .current_operation_hash,
+            Some(main_hash)
+        );
+    }
+
     #[test]
     fn test_writes_operation_hash() {
         setup_gen_dir();
@@ -1489,6 +3184,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compresses_large_changesets() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let change = FileAddition::create(op_conn, "test", FileTypes::Fasta);
+        let small_operation =
+            Operation::create(op_conn, &db_uuid, "test", change.id, "small-hash").unwrap();
+        let large_operation =
+            Operation::create(op_conn, &db_uuid, "test", change.id, "large-hash").unwrap();
+        let small_changes = b"ATCG".repeat(16);
+        let large_changes = b"ATCG".repeat(CHANGESET_COMPRESSION_THRESHOLD);
+        write_changeset(&small_operation, &small_changes, b"{}");
+        write_changeset(&large_operation, &large_changes, b"{}");
+
+        let changeset_size = |operation: &Operation| {
+            fs::metadata(
+                get_changeset_path(operation).join(format!("{hash}.cs", hash = operation.hash)),
+            )
+            .unwrap()
+            .len() as usize
+        };
+        assert_eq!(changeset_size(&small_operation), small_changes.len());
+        assert!(changeset_size(&large_operation) < large_changes.len());
+        assert_eq!(load_changeset(&small_operation), small_changes);
+        assert_eq!(load_changeset(&large_operation), large_changes);
+    }
+
     #[test]
     fn test_records_patch_dependencies() {
         setup_gen_dir();
@@ -1818,9 +3543,32 @@ mod tests {
                 .collect::<Vec<String>>(),
             vec!["".to_string(), "foo".to_string()]
         );
+        let vcf_files = |conn: &Connection| {
+            conn.prepare(
+                "select distinct value from node_attributes where key = 'vcf_file' order by value;",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap()
+        };
+        assert_eq!(vcf_files(conn), vec!["simple2.vcf".to_string()]);
 
         // apply changes from branch-1, it will be operation id 2
         apply(conn, operation_conn, &op_2.hash, None);
+        assert_eq!(
+            vcf_files(conn),
+            vec!["simple.vcf".to_string(), "simple2.vcf".to_string()]
+        );
+        let orphaned_attributes: i64 = conn
+            .query_row(
+                "select count(*) from node_attributes where node_id not in (select id from nodes);",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphaned_attributes, 0);
 
         let foo_bg_id = BlockGroup::get_id(conn, &collection, Some("foo"), "m123");
         let patch_2_seqs = HashSet::from_iter(vec![
@@ -2110,6 +3858,205 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_reverts_operations() {
+        setup_gen_dir();
+        let fasta_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let vcf_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.vcf");
+        let conn = &mut get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &db_uuid);
+        let collection = "test".to_string();
+        let import_op = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            &collection,
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let counts = |conn: &Connection| {
+            (
+                Node::query(conn, "select * from nodes", rusqlite::params!()).len(),
+                Edge::query(conn, "select * from edges", rusqlite::params!()).len(),
+                BlockGroup::query(conn, "select * from block_groups", rusqlite::params!()).len(),
+                Sample::query(conn, "select * from samples", rusqlite::params!()).len(),
+            )
+        };
+        let imported = counts(conn);
+
+        let vcf_op = update_with_vcf(
+            &vcf_path.to_str().unwrap().to_string(),
+            &collection,
+            "".to_string(),
+            "".to_string(),
+            conn,
+            operation_conn,
+            None,
+        )
+        .unwrap();
+        let updated = counts(conn);
+        assert_ne!(updated, imported);
+        let foo_sequences =
+            BlockGroup::get_all_sequences(conn, get_sample_bg(conn, &collection, "foo").id, false);
+
+        // the import made the sequence the update changes, so it can't go first
+        assert_eq!(
+            revert(conn, operation_conn, &db_uuid, &import_op.hash, None),
+            Err(OperationError::RevertDependency(vcf_op.hash.clone()))
+        );
+
+        let revert_op = revert(conn, operation_conn, &db_uuid, &vcf_op.hash, None).unwrap();
+        assert_eq!(revert_op.parent_hash, Some(vcf_op.hash.clone()));
+        assert_eq!(revert_op.change_type, REVERT_CHANGE_TYPE);
+        assert_eq!(counts(conn), imported);
+        assert_eq!(
+            revert(conn, operation_conn, &db_uuid, &vcf_op.hash, None),
+            Err(OperationError::NoChanges)
+        );
+
+        // moving through the revert replays it
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(vcf_op.hash.clone()),
+        );
+        assert_eq!(counts(conn), updated);
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(revert_op.hash.clone()),
+        );
+        assert_eq!(counts(conn), imported);
+
+        // and a revert can itself be reverted
+        revert(conn, operation_conn, &db_uuid, &revert_op.hash, None).unwrap();
+        assert_eq!(counts(conn), updated);
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn, get_sample_bg(conn, &collection, "foo").id, false),
+            foo_sequences
+        );
+    }
+
+    #[test]
+    fn test_squashes_operations() {
+        setup_gen_dir();
+        let fasta_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let vcf_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.vcf");
+        let vcf2_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple2.vcf");
+        let conn = &mut get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &db_uuid);
+        let collection = "test".to_string();
+        let import_op = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            &collection,
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let counts = |conn: &Connection| {
+            (
+                Node::query(conn, "select * from nodes", rusqlite::params!()).len(),
+                Edge::query(conn, "select * from edges", rusqlite::params!()).len(),
+                BlockGroupEdge::query(conn, "select * from block_group_edges", rusqlite::params!())
+                    .len(),
+                Sample::query(conn, "select * from samples", rusqlite::params!()).len(),
+            )
+        };
+        let imported = counts(conn);
+        let mut op_hashes = vec![];
+        for (path, message) in [(&vcf_path, "first round"), (&vcf2_path, "second round")] {
+            let operation = update_with_vcf(
+                &path.to_str().unwrap().to_string(),
+                &collection,
+                "".to_string(),
+                "".to_string(),
+                conn,
+                operation_conn,
+                None,
+            )
+            .unwrap();
+            Operation::set_message(operation_conn, &operation.hash, message).unwrap();
+            op_hashes.push(operation.hash);
+        }
+        let updated = counts(conn);
+        let summary = |hash: &str| {
+            OperationSummary::query(
+                operation_conn,
+                "select * from operation_summary where operation_hash = ?1",
+                vec![Value::from(hash.to_string())],
+            )[0]
+            .summary
+            .clone()
+        };
+        let expected_summary = op_hashes.iter().map(|hash| summary(hash)).join("\n");
+
+        assert_eq!(
+            squash(conn, operation_conn, &db_uuid, &op_hashes[..1], None),
+            Err(OperationError::InvalidRange(
+                "squashing needs at least two operations".to_string()
+            ))
+        );
+        assert_eq!(
+            squash(
+                conn,
+                operation_conn,
+                &db_uuid,
+                &[import_op.hash.clone(), op_hashes[0].clone()],
+                None
+            ),
+            Err(OperationError::InvalidRange(
+                "the operations have to be consecutive and end at the current operation"
+                    .to_string()
+            ))
+        );
+
+        let squashed = squash(conn, operation_conn, &db_uuid, &op_hashes, None).unwrap();
+        assert_eq!(squashed.parent_hash, Some(import_op.hash.clone()));
+        assert_eq!(
+            squashed.message,
+            Some("first round\nsecond round".to_string())
+        );
+        assert_eq!(counts(conn), updated);
+        let branch_id = OperationState::get_current_branch(operation_conn, &db_uuid).unwrap();
+        assert_eq!(
+            Branch::get_operations(operation_conn, branch_id)
+                .iter()
+                .map(|op| op.hash.clone())
+                .collect::<Vec<String>>(),
+            vec![import_op.hash.clone(), squashed.hash.clone()]
+        );
+        assert_eq!(summary(&squashed.hash), expected_summary);
+
+        // the squashed operation is replayed like any other
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(import_op.hash.clone()),
+        );
+        assert_eq!(counts(conn), imported);
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(squashed.hash.clone()),
+        );
+        assert_eq!(counts(conn), updated);
+    }
+
     #[test]
     fn test_reset_with_branches() {
         // Our setup is like this:
@@ -2299,4 +4246,263 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_sparse_checkout() {
+        setup_gen_dir();
+        let fasta_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &db_uuid);
+        let op_1 = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "a",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let op_2 = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "b",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let op_3 = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "a2",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(op_1.hash.clone()),
+        );
+        assert!(!Collection::exists(conn, "b"));
+        sparse_checkout(conn, operation_conn, &db_uuid, &["a2".to_string()]);
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(op_3.hash.clone()),
+        );
+
+        // only the selected collection is materialized, b is left pending
+        assert!(Collection::exists(conn, "a2"));
+        assert_eq!(Sample::get_block_groups(conn, "a2", None).len(), 1);
+        assert!(!Collection::exists(conn, "b"));
+        assert!(Sample::get_block_groups(conn, "b", None).is_empty());
+        assert_eq!(
+            SparseCheckout::get_pending_operations(operation_conn, &db_uuid, "b"),
+            vec![op_2.hash.clone()]
+        );
+
+        // selecting b replays its skipped operation
+        sparse_checkout(conn, operation_conn, &db_uuid, &["b".to_string()]);
+        assert!(Collection::exists(conn, "b"));
+        assert_eq!(Sample::get_block_groups(conn, "b", None).len(), 1);
+        assert!(SparseCheckout::get_pending_operations(operation_conn, &db_uuid, "b").is_empty());
+        assert_eq!(
+            SparseCheckout::get_collections(operation_conn, &db_uuid),
+            Some(HashSet::from(["b".to_string()]))
+        );
+
+        // moving back drops pending operations for the reverted operations
+        sparse_checkout(conn, operation_conn, &db_uuid, &["a".to_string()]);
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(op_1.hash.clone()),
+        );
+        assert!(!Collection::exists(conn, "b"));
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(op_3.hash.clone()),
+        );
+        assert_eq!(
+            SparseCheckout::get_pending_collections(operation_conn, &db_uuid),
+            vec!["a2".to_string(), "b".to_string()]
+        );
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(op_1.hash.clone()),
+        );
+        assert!(SparseCheckout::get_pending_collections(operation_conn, &db_uuid).is_empty());
+
+        // an empty selection ends the sparse checkout
+        sparse_checkout(conn, operation_conn, &db_uuid, &[]);
+        assert_eq!(
+            SparseCheckout::get_collections(operation_conn, &db_uuid),
+            None
+        );
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &None,
+            Some(op_3.hash.clone()),
+        );
+        assert_eq!(Sample::get_block_groups(conn, "b", None).len(), 1);
+        assert_eq!(Sample::get_block_groups(conn, "a2", None).len(), 1);
+    }
+
+    // Imports simple.fa on main, then updates it on main and on a feature branch made at the import,
+    // each with sample, start and end, leaving feature checked out.
+    fn setup_diverged_branches(
+        conn: &Connection,
+        operation_conn: &Connection,
+        main_update: (&str, i64, i64),
+        feature_update: (&str, i64, i64),
+    ) -> (String, String, String) {
+        let fasta_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let db_uuid = metadata::get_db_uuid(conn);
+        setup_db(operation_conn, &db_uuid);
+        let import_op = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        Branch::create(operation_conn, &db_uuid, "feature");
+        let update = |(sample, start, end): (&str, i64, i64)| {
+            update_with_fasta(
+                conn,
+                operation_conn,
+                "test",
+                None,
+                sample,
+                "m123",
+                start,
+                end,
+                insert_path.to_str().unwrap(),
+            )
+            .unwrap();
+            OperationState::get_operation(operation_conn, &db_uuid).unwrap()
+        };
+        let main_op = update(main_update);
+        checkout(
+            conn,
+            operation_conn,
+            &db_uuid,
+            &Some("feature".to_string()),
+            None,
+        );
+        let feature_op = update(feature_update);
+        (import_op.hash, main_op, feature_op)
+    }
+
+    #[test]
+    fn test_rebases_operations_onto_branch() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let operation_conn = &get_operation_connection(None);
+        let (import_op, main_op, feature_op) =
+            setup_diverged_branches(conn, operation_conn, ("child-a", 2, 5), ("child-b", 20, 24));
+        let main = Branch::get_by_name(operation_conn, &db_uuid, "main").unwrap();
+        let (candidates, ancestor) = rebase_candidates(operation_conn, &db_uuid, &main).unwrap();
+        assert_eq!(ancestor, import_op);
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|operation| operation.hash.clone())
+                .collect::<Vec<_>>(),
+            vec![feature_op.clone()]
+        );
+
+        let todo = rebase_todo(operation_conn, &candidates);
+        assert!(todo.starts_with(&format!("pick {feature_op} ")));
+        assert_eq!(parse_rebase_todo(&todo, &candidates).unwrap(), candidates);
+        assert_eq!(
+            parse_rebase_todo(&format!("drop {}", &feature_op[..8]), &candidates).unwrap(),
+            vec![]
+        );
+        assert!(matches!(
+            parse_rebase_todo(&format!("squash {feature_op}"), &candidates),
+            Err(OperationError::InvalidRebase(_))
+        ));
+
+        let rebased = rebase(conn, operation_conn, &db_uuid, &main, &candidates).unwrap();
+        assert_eq!(rebased.len(), 1);
+        assert_eq!(rebased[0].parent_hash, Some(main_op.clone()));
+        assert_eq!(
+            OperationState::get_operation(operation_conn, &db_uuid),
+            Some(rebased[0].hash.clone())
+        );
+        let feature = Branch::get_by_name(operation_conn, &db_uuid, "feature").unwrap();
+        assert_eq!(
+            Branch::get_operations(operation_conn, feature.id)
+                .iter()
+                .map(|operation| operation.hash.clone())
+                .collect::<Vec<_>>(),
+            vec![import_op, main_op, rebased[0].hash.clone()]
+        );
+        // the database has the changes of both branches
+        for sample in ["child-a", "child-b"] {
+            assert_eq!(
+                Sample::get_block_groups(conn, "test", Some(sample)).len(),
+                1
+            );
+        }
+        let divergence = Branch::divergence(operation_conn, &feature, &main);
+        assert_eq!((divergence.ahead, divergence.behind), (1, 0));
+    }
+
+    #[test]
+    fn test_rebase_refuses_conflicting_changes() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let operation_conn = &get_operation_connection(None);
+        let (_, main_op, feature_op) =
+            setup_diverged_branches(conn, operation_conn, ("child", 2, 5), ("child", 4, 8));
+        let main = Branch::get_by_name(operation_conn, &db_uuid, "main").unwrap();
+        let (candidates, _) = rebase_candidates(operation_conn, &db_uuid, &main).unwrap();
+        let result = rebase(conn, operation_conn, &db_uuid, &main, &candidates);
+        assert_eq!(
+            result.unwrap_err(),
+            OperationError::RebaseConflict(
+                feature_op.clone(),
+                main_op,
+                "graph m123 of sample child in test".to_string()
+            )
+        );
+        assert_eq!(
+            OperationState::get_operation(operation_conn, &db_uuid),
+            Some(feature_op)
+        );
+        assert!(matches!(
+            rebase_candidates(
+                operation_conn,
+                &db_uuid,
+                &Branch::get_by_name(operation_conn, &db_uuid, "feature").unwrap()
+            ),
+            Err(OperationError::InvalidRebase(_))
+        ));
+    }
 }
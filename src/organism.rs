@@ -0,0 +1,317 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum OrganismError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid organism profiles: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Unknown organism: {0}")]
+    UnknownOrganism(String),
+    #[error("Invalid codon table for {name}: {reason}")]
+    InvalidCodonTable { name: String, reason: String },
+}
+
+const BASES: [char; 4] = ['T', 'C', 'A', 'G'];
+
+// Genetic codes as listed by NCBI, one amino acid per codon with codons ordered by their first,
+// second and third base in TCAG order. Stops are *.
+const STANDARD_CODE: &str = "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+const YEAST_MITOCHONDRIAL_CODE: &str =
+    "FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+fn codon_table(amino_acids: &str) -> HashMap<String, char> {
+    let mut codons = vec![];
+    for first in BASES {
+        for second in BASES {
+            for third in BASES {
+                codons.push(format!("{first}{second}{third}"));
+            }
+        }
+    }
+    codons.into_iter().zip(amino_acids.chars()).collect()
+}
+
+fn codons(names: &[&str]) -> Vec<String> {
+    names.iter().map(|codon| codon.to_string()).collect()
+}
+
+// How an organism reads its sequences: the genetic code used for translation and finding open
+// reading frames, and how its chromosomes are usually named.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrganismProfile {
+    pub name: String,
+    // the NCBI translation table id
+    pub genetic_code_id: u32,
+    pub codon_table: HashMap<String, char>,
+    pub start_codons: Vec<String>,
+    // graph names of the organism's chromosomes start with this, as in chr1
+    pub chromosome_prefix: String,
+}
+
+impl OrganismProfile {
+    pub fn standard() -> OrganismProfile {
+        OrganismProfile {
+            name: "standard".to_string(),
+            genetic_code_id: 1,
+            codon_table: codon_table(STANDARD_CODE),
+            start_codons: codons(&["ATG", "CTG", "TTG"]),
+            chromosome_prefix: "chr".to_string(),
+        }
+    }
+
+    // Bacteria, archaea and plastids share the standard code but start with more codons.
+    pub fn bacterial() -> OrganismProfile {
+        OrganismProfile {
+            name: "bacterial".to_string(),
+            genetic_code_id: 11,
+            codon_table: codon_table(STANDARD_CODE),
+            start_codons: codons(&["ATA", "ATC", "ATG", "ATT", "CTG", "GTG", "TTG"]),
+            chromosome_prefix: "chromosome".to_string(),
+        }
+    }
+
+    pub fn yeast_mitochondrial() -> OrganismProfile {
+        OrganismProfile {
+            name: "yeast_mitochondrial".to_string(),
+            genetic_code_id: 3,
+            codon_table: codon_table(YEAST_MITOCHONDRIAL_CODE),
+            start_codons: codons(&["ATA", "ATG", "GTG"]),
+            chromosome_prefix: "chrM".to_string(),
+        }
+    }
+
+    pub fn builtin() -> Vec<OrganismProfile> {
+        vec![
+            OrganismProfile::standard(),
+            OrganismProfile::bacterial(),
+            OrganismProfile::yeast_mitochondrial(),
+        ]
+    }
+
+    pub fn is_stop(&self, codon: &str) -> bool {
+        self.codon_table.get(&codon.to_uppercase()) == Some(&'*')
+    }
+
+    pub fn stop_codons(&self) -> Vec<String> {
+        let mut stop_codons = self
+            .codon_table
+            .iter()
+            .filter(|(_, amino_acid)| **amino_acid == '*')
+            .map(|(codon, _)| codon.clone())
+            .collect::<Vec<String>>();
+        stop_codons.sort();
+        stop_codons
+    }
+
+    // Translates a sequence from its first base, leaving off any partial codon at the end. Codons
+    // with bases other than ACGT translate to X.
+    pub fn translate(&self, sequence: &str) -> String {
+        sequence
+            .to_uppercase()
+            .as_bytes()
+            .chunks_exact(3)
+            .map(|codon| {
+                std::str::from_utf8(codon)
+                    .ok()
+                    .and_then(|codon| self.codon_table.get(codon))
+                    .copied()
+                    .unwrap_or('X')
+            })
+            .collect()
+    }
+}
+
+// A user defined organism, as a [[organism]] table of a TOML file. Profiles start from the standard
+// code, or the built in profile given as base, and change the codons listed.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrganismDefinition {
+    name: String,
+    base: Option<String>,
+    genetic_code_id: Option<u32>,
+    #[serde(default)]
+    codons: HashMap<String, char>,
+    start_codons: Option<Vec<String>>,
+    chromosome_prefix: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrganismDefinitions {
+    #[serde(default, rename = "organism")]
+    organisms: Vec<OrganismDefinition>,
+}
+
+// The built in and user defined organism profiles, along with the one to use when none is named.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Organisms {
+    profiles: Vec<OrganismProfile>,
+    default_name: Option<String>,
+}
+
+impl Default for Organisms {
+    fn default() -> Organisms {
+        Organisms {
+            profiles: OrganismProfile::builtin(),
+            default_name: None,
+        }
+    }
+}
+
+impl Organisms {
+    // Adds the profiles of a TOML file's contents to the built in ones. A user defined profile
+    // with the name of a built in one replaces it.
+    pub fn from_toml(contents: &str) -> Result<Organisms, OrganismError> {
+        let mut organisms = Organisms::default();
+        let definitions: OrganismDefinitions = toml::from_str(contents)?;
+        for definition in definitions.organisms {
+            let mut profile = organisms
+                .get(Some(definition.base.as_deref().unwrap_or("standard")))?
+                .clone();
+            profile.name = definition.name;
+            for (codon, amino_acid) in definition.codons {
+                let codon = codon.to_uppercase();
+                if !profile.codon_table.contains_key(&codon) {
+                    return Err(OrganismError::InvalidCodonTable {
+                        name: profile.name,
+                        reason: format!("{codon} is not a codon"),
+                    });
+                }
+                profile
+                    .codon_table
+                    .insert(codon, amino_acid.to_ascii_uppercase());
+            }
+            if let Some(genetic_code_id) = definition.genetic_code_id {
+                profile.genetic_code_id = genetic_code_id;
+            }
+            if let Some(start_codons) = definition.start_codons {
+                profile.start_codons = start_codons
+                    .iter()
+                    .map(|codon| codon.to_uppercase())
+                    .collect();
+            }
+            if let Some(chromosome_prefix) = definition.chromosome_prefix {
+                profile.chromosome_prefix = chromosome_prefix;
+            }
+            organisms
+                .profiles
+                .retain(|existing| existing.name != profile.name);
+            organisms.profiles.push(profile);
+        }
+        Ok(organisms)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Organisms, OrganismError> {
+        Organisms::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn with_default(mut self, name: Option<&str>) -> Result<Organisms, OrganismError> {
+        if let Some(name) = name {
+            self.get(Some(name))?;
+        }
+        self.default_name = name.map(str::to_string);
+        Ok(self)
+    }
+
+    // The named profile, or the default one without a name. The standard code is the default
+    // unless another is set.
+    pub fn get(&self, name: Option<&str>) -> Result<&OrganismProfile, OrganismError> {
+        let name = name.or(self.default_name.as_deref()).unwrap_or("standard");
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| OrganismError::UnknownOrganism(name.to_string()))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles
+            .iter()
+            .map(|profile| profile.name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_codes() {
+        let standard = OrganismProfile::standard();
+        assert_eq!(standard.codon_table.len(), 64);
+        assert_eq!(standard.stop_codons(), vec!["TAA", "TAG", "TGA"]);
+        assert_eq!(standard.translate("atgGCCtggTAAc"), "MAW*");
+        assert_eq!(standard.translate("ATGNNN"), "MX");
+
+        let bacterial = OrganismProfile::bacterial();
+        assert_eq!(bacterial.codon_table, standard.codon_table);
+        assert!(bacterial.start_codons.contains(&"GTG".to_string()));
+
+        // TGA is tryptophan, ATA methionine and CTN threonine in yeast mitochondria
+        let yeast = OrganismProfile::yeast_mitochondrial();
+        assert_eq!(yeast.stop_codons(), vec!["TAA", "TAG"]);
+        assert_eq!(yeast.translate("ATATGACTG"), "MWT");
+        assert!(!yeast.is_stop("tga"));
+    }
+
+    #[test]
+    fn test_user_defined_organisms() {
+        let organisms = Organisms::from_toml(
+            r#"
+[[organism]]
+name = "ciliate"
+genetic_code_id = 6
+chromosome_prefix = "mac"
+codons = { TAA = "Q", tag = "q" }
+
+[[organism]]
+name = "mito"
+base = "yeast_mitochondrial"
+start_codons = ["atg"]
+"#,
+        )
+        .unwrap();
+        let ciliate = organisms.get(Some("ciliate")).unwrap();
+        assert_eq!(ciliate.genetic_code_id, 6);
+        assert_eq!(ciliate.chromosome_prefix, "mac");
+        assert_eq!(ciliate.stop_codons(), vec!["TGA"]);
+        assert_eq!(
+            ciliate.start_codons,
+            OrganismProfile::standard().start_codons
+        );
+        let mito = organisms.get(Some("mito")).unwrap();
+        assert_eq!(mito.genetic_code_id, 3);
+        assert_eq!(mito.start_codons, vec!["ATG"]);
+        assert_eq!(
+            organisms.names(),
+            vec![
+                "standard",
+                "bacterial",
+                "yeast_mitochondrial",
+                "ciliate",
+                "mito"
+            ]
+        );
+
+        assert_eq!(organisms.get(None).unwrap().name, "standard");
+        let organisms = organisms.with_default(Some("ciliate")).unwrap();
+        assert_eq!(organisms.get(None).unwrap().name, "ciliate");
+        assert!(matches!(
+            organisms.clone().with_default(Some("unknown")),
+            Err(OrganismError::UnknownOrganism(_))
+        ));
+        assert!(matches!(
+            Organisms::from_toml("[[organism]]\nname = \"bad\"\ncodons = { TAAA = \"Q\" }\n"),
+            Err(OrganismError::InvalidCodonTable { .. })
+        ));
+        assert!(matches!(
+            Organisms::from_toml("[[organism]]\nname = \"bad\"\nbase = \"unknown\"\n"),
+            Err(OrganismError::UnknownOrganism(_))
+        ));
+    }
+}
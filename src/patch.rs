@@ -1,20 +1,60 @@
 use crate::config::get_changeset_path;
+use crate::models::block_group::BlockGroup;
+use crate::models::metadata::get_db_uuid;
+use crate::models::node::Node;
 use crate::models::operations::{FileAddition, Operation, OperationInfo, OperationSummary};
+use crate::models::sample::Sample;
+use crate::models::sequence::{NewSequence, Sequence};
 use crate::models::traits::Query;
 use crate::operation_management;
 use crate::operation_management::{
-    apply_changeset, end_operation, load_changeset, load_changeset_dependencies, start_operation,
-    OperationError,
+    apply_changeset, end_operation, get_changeset_block_group_ids, inserted_keys, start_operation,
+    DependencyModels, OperationError, REVERT_CHANGE_TYPE,
 };
+use fallible_streaming_iterator::FallibleStreamingIterator;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use itertools::Itertools;
+use rusqlite::backup::Backup;
+use rusqlite::hooks::Action;
 use rusqlite::session::ChangesetIter;
 use rusqlite::types::Value;
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const PATCH_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum PatchError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Invalid patch: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("Unsupported patch version {0}, this version of gen reads patches up to version {PATCH_FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Patch is corrupt: operation {0} does not match its checksum")]
+    ChecksumMismatch(String),
+    #[error("Patch is corrupt: operation {0} has no checksum")]
+    MissingChecksum(String),
+    #[error("Patch is corrupt: embedded sequence {0} does not match its hash")]
+    InvalidSequence(String),
+    #[error("Operation {operation} needs sequences that are neither in this database nor embedded in the patch: {}. Recreate the patch with --embed-sequences.", hashes.join(", "))]
+    MissingSequences {
+        operation: String,
+        hashes: Vec<String>,
+    },
+    #[error("Operation {0} reverts another operation, which can only be replayed in the database it was made in")]
+    Revert(String),
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct OperationPatch {
@@ -23,10 +63,163 @@ pub struct OperationPatch {
     summary: OperationSummary,
     dependencies: Vec<u8>,
     changeset: Vec<u8>,
+    // the sequences the operation's changes refer to but don't create, so the patch can be
+    // applied to a repository that lacks them. Only set on request since sequences can be large.
+    #[serde(default)]
+    sequences: Vec<Sequence>,
 }
 
-pub fn create_patch<W>(op_conn: &Connection, operations: &[String], write_stream: &mut W)
-where
+impl OperationPatch {
+    // A SHA-256 digest of everything applying the operation relies on, as a hex string.
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            &self.changeset,
+            &self.dependencies,
+            &serde_json::to_vec(&self.sequences).unwrap(),
+        ] {
+            // lengths keep the parts from running together
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    // The collection and sample of each graph the operation changes, including graphs whose paths
+    // or annotations it changes.
+    pub fn affected_graphs(&self) -> HashSet<(String, Option<String>)> {
+        let dependencies: DependencyModels = serde_json::from_slice(&self.dependencies).unwrap();
+        let mut graphs_by_id = dependencies
+            .block_group
+            .iter()
+            .map(|block_group| {
+                (
+                    block_group.id,
+                    (
+                        block_group.collection_name.clone(),
+                        block_group.sample_name.clone(),
+                    ),
+                )
+            })
+            .collect::<HashMap<i64, (String, Option<String>)>>();
+        let mut path_block_groups = dependencies
+            .paths
+            .iter()
+            .map(|path| (path.id, path.block_group_id))
+            .collect::<HashMap<i64, i64>>();
+        let mut block_group_ids = HashSet::new();
+        let mut path_ids = HashSet::new();
+
+        let input: &mut dyn Read = &mut self.changeset.as_slice();
+        let mut iter = ChangesetIter::start_strm(&input).unwrap();
+        while let Some(item) = iter.next().unwrap() {
+            let op = item.op().unwrap();
+            // deleted rows only have old values, and updated rows only have new values for the
+            // columns that changed
+            let value = |column: usize| match op.code() {
+                Action::SQLITE_DELETE => item.old_value(column).ok(),
+                _ => item
+                    .new_value(column)
+                    .or_else(|_| item.old_value(column))
+                    .ok(),
+            };
+            let integer = |column: usize| value(column).and_then(|value| value.as_i64().ok());
+            match op.table_name() {
+                "block_groups" => {
+                    let Some(id) = integer(0) else { continue };
+                    if let Some(collection_name) =
+                        value(1).and_then(|value| value.as_str().ok().map(str::to_string))
+                    {
+                        let sample_name = value(2)
+                            .and_then(|value| value.as_str_or_null().ok().flatten())
+                            .map(str::to_string);
+                        graphs_by_id.insert(id, (collection_name, sample_name));
+                    }
+                    block_group_ids.insert(id);
+                }
+                "paths" => match (integer(0), integer(1)) {
+                    (Some(id), Some(block_group_id)) => {
+                        path_block_groups.insert(id, block_group_id);
+                        block_group_ids.insert(block_group_id);
+                    }
+                    // renamed paths are found through the dependencies
+                    (Some(id), None) => {
+                        path_ids.insert(id);
+                    }
+                    _ => {}
+                },
+                "block_group_edges" | "current_path_choices" => block_group_ids.extend(integer(1)),
+                "path_edges" | "annotations" => path_ids.extend(integer(1)),
+                "accessions" => path_ids.extend(integer(2)),
+                _ => {}
+            }
+        }
+        block_group_ids.extend(
+            path_ids
+                .iter()
+                .filter_map(|path_id| path_block_groups.get(path_id)),
+        );
+        block_group_ids
+            .iter()
+            .filter_map(|block_group_id| graphs_by_id.get(block_group_id).cloned())
+            .collect()
+    }
+}
+
+// The operations of a patch that match every filter given: operations whose hash starts with one
+// of the given hashes, and operations changing a graph of the collection or sample.
+pub fn select_patches(
+    patches: Vec<OperationPatch>,
+    collection_name: Option<&str>,
+    sample_name: Option<&str>,
+    operation_hashes: &[String],
+) -> Vec<OperationPatch> {
+    patches
+        .into_iter()
+        .filter(|patch| {
+            operation_hashes.is_empty()
+                || operation_hashes
+                    .iter()
+                    .any(|hash| patch.operation.hash.starts_with(hash.as_str()))
+        })
+        .filter(|patch| {
+            (collection_name.is_none() && sample_name.is_none())
+                || patch.affected_graphs().iter().any(|(collection, sample)| {
+                    collection_name.is_none_or(|name| name == collection)
+                        && sample_name.is_none_or(|name| Some(name) == sample.as_deref())
+                })
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ManifestEntry {
+    pub operation_hash: String,
+    pub sha256: String,
+}
+
+// The contents of a patch file from version 2 on. Version 1 patches are a bare list of operation
+// patches without a manifest.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PatchFile {
+    pub version: u32,
+    pub operations: Vec<OperationPatch>,
+    pub manifest: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VersionedPatch {
+    V1(Vec<OperationPatch>),
+    Versioned(PatchFile),
+}
+
+pub fn create_patch<W>(
+    op_conn: &Connection,
+    operations: &[String],
+    embed_sequences: bool,
+    write_stream: &mut W,
+) where
     W: Write,
 {
     let mut patches = vec![];
@@ -38,11 +231,8 @@ where
             get_changeset_path(&operation).join(format!("{op_id}.dep", op_id = operation.hash));
         let dependencies: operation_management::DependencyModels =
             serde_json::from_reader(File::open(dependency_path).unwrap()).unwrap();
-        let change_path =
-            get_changeset_path(&operation).join(format!("{op_id}.cs", op_id = operation.hash));
-        let mut file = File::open(change_path).unwrap();
-        let mut contents = vec![];
-        file.read_to_end(&mut contents).unwrap();
+        // patches carry changesets uncompressed, as the whole patch is compressed
+        let contents = operation_management::load_changeset(&operation);
         patches.push(OperationPatch {
             operation: operation.clone(),
             files: FileAddition::get(
@@ -59,34 +249,133 @@ where
             .unwrap(),
             dependencies: serde_json::to_vec(&dependencies).unwrap(),
             changeset: contents,
+            sequences: if embed_sequences {
+                dependencies
+                    .sequences
+                    .iter()
+                    .map(Sequence::inlined)
+                    .collect()
+            } else {
+                vec![]
+            },
         })
     }
-    let to_compress = serde_json::to_vec(&patches).unwrap();
-    let mut e = GzEncoder::new(Vec::new(), Compression::default());
-    e.write_all(&to_compress).unwrap();
-    let compressed = e.finish().unwrap();
-    write_stream.write_all(&compressed).unwrap();
+    let patch_file = PatchFile {
+        version: PATCH_FORMAT_VERSION,
+        manifest: patches
+            .iter()
+            .map(|patch| ManifestEntry {
+                operation_hash: patch.operation.hash.clone(),
+                sha256: patch.checksum(),
+            })
+            .collect(),
+        operations: patches,
+    };
+    let mut e = GzEncoder::new(write_stream, Compression::default());
+    serde_json::to_writer(&mut e, &patch_file).unwrap();
+    e.finish().unwrap();
 }
 
-pub fn load_patches<R>(reader: R) -> Vec<OperationPatch>
+// Reads a patch file of any version, checking each operation of a version 2 patch against the
+// manifest.
+pub fn load_patches<R>(reader: R) -> Result<Vec<OperationPatch>, PatchError>
 where
     R: Read,
 {
-    let mut d = GzDecoder::new(reader);
-    let mut s = Vec::new();
-    d.read_to_end(&mut s).unwrap();
-    let patches: Vec<OperationPatch> = serde_json::from_slice(&s[..]).unwrap();
-    patches
+    let d = io::BufReader::new(GzDecoder::new(reader));
+    let patch_file = match serde_json::from_reader(d)? {
+        VersionedPatch::V1(patches) => return Ok(patches),
+        VersionedPatch::Versioned(patch_file) => patch_file,
+    };
+    if patch_file.version > PATCH_FORMAT_VERSION {
+        return Err(PatchError::UnsupportedVersion(patch_file.version));
+    }
+    for patch in patch_file.operations.iter() {
+        let entry = patch_file
+            .manifest
+            .iter()
+            .find(|entry| entry.operation_hash == patch.operation.hash)
+            .ok_or_else(|| PatchError::MissingChecksum(patch.operation.hash.clone()))?;
+        if entry.sha256 != patch.checksum() {
+            return Err(PatchError::ChecksumMismatch(patch.operation.hash.clone()));
+        }
+        for sequence in patch.sequences.iter() {
+            if NewSequence::from(sequence).hash() != sequence.hash {
+                return Err(PatchError::InvalidSequence(sequence.hash.clone()));
+            }
+        }
+    }
+    Ok(patch_file.operations)
+}
+
+// The sequences an operation refers to that are neither in the database, embedded in its patch nor
+// among the given available ones.
+fn missing_sequences(
+    conn: &Connection,
+    patch: &OperationPatch,
+    available: &HashSet<String>,
+) -> Result<Vec<String>, PatchError> {
+    let dependencies: DependencyModels = serde_json::from_slice(&patch.dependencies)?;
+    let embedded = patch
+        .sequences
+        .iter()
+        .map(|sequence| &sequence.hash)
+        .collect::<HashSet<&String>>();
+    Ok(dependencies
+        .nodes
+        .iter()
+        .filter(|node| !Node::is_terminal(node.id))
+        .map(|node| node.sequence_hash.clone())
+        .filter(|hash| {
+            !available.contains(hash)
+                && !embedded.contains(hash)
+                && Sequence::sequence_from_hash(conn, hash).is_none()
+        })
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .sorted()
+        .collect())
+}
+
+// Checks that every sequence the patches refer to is in the database, embedded in a patch or
+// created by an earlier operation of the patch, so nothing is applied unless all of it can be.
+fn check_sequences(conn: &Connection, patches: &[OperationPatch]) -> Result<(), PatchError> {
+    let mut available = HashSet::new();
+    for patch in patches.iter() {
+        let missing = missing_sequences(conn, patch, &available)?;
+        if !missing.is_empty() {
+            return Err(PatchError::MissingSequences {
+                operation: patch.operation.hash.clone(),
+                hashes: missing,
+            });
+        }
+        available.extend(patch.sequences.iter().map(|sequence| sequence.hash.clone()));
+        available.extend(inserted_keys(&patch.changeset, "sequences"));
+    }
+    Ok(())
 }
 
-pub fn apply_patches(conn: &Connection, op_conn: &Connection, patches: &[OperationPatch]) {
+pub fn apply_patches(
+    conn: &Connection,
+    op_conn: &Connection,
+    patches: &[OperationPatch],
+) -> Result<(), PatchError> {
+    check_sequences(conn, patches)?;
+    if let Some(patch) = patches
+        .iter()
+        .find(|patch| patch.operation.change_type == REVERT_CHANGE_TYPE)
+    {
+        return Err(PatchError::Revert(patch.operation.hash.clone()));
+    }
     for patch in patches.iter() {
         let op_info = &patch.operation;
-        let changeset = load_changeset(op_info);
-        let input: &mut dyn Read = &mut changeset.as_slice();
+        let input: &mut dyn Read = &mut patch.changeset.as_slice();
         let mut iter = ChangesetIter::start_strm(&input).unwrap();
-        let dependencies = load_changeset_dependencies(op_info);
+        let dependencies: DependencyModels = serde_json::from_slice(&patch.dependencies)?;
         let mut session = start_operation(conn);
+        for sequence in patch.sequences.iter() {
+            NewSequence::from(sequence).save(conn);
+        }
         apply_changeset(conn, &mut iter, &dependencies);
         match end_operation(
             conn,
@@ -100,7 +389,8 @@ pub fn apply_patches(conn: &Connection, op_conn: &Connection, patches: &[Operati
             &patch.summary.summary,
             None,
         ) {
-            Ok(_new_op) => {
+            Ok(new_op) => {
+                Operation::copy_metadata(op_conn, &new_op.hash, op_info).unwrap();
                 println!("Successfully applied operation.");
             }
             Err(e) => match e {
@@ -108,9 +398,153 @@ pub fn apply_patches(conn: &Connection, op_conn: &Connection, patches: &[Operati
                 OperationError::NoChanges => {
                     println!("No new changes present in operation. Skipping.")
                 }
+                // saving an operation can't be cancelled, depend on a revert or take a range
+                OperationError::Cancelled
+                | OperationError::RevertDependency(_)
+                | OperationError::InvalidRange(_) => unreachable!(),
             },
         }
     }
+    Ok(())
+}
+
+// What applying an operation of a patch would do.
+#[derive(Debug, Default, PartialEq)]
+pub struct DryRunResult {
+    pub operation_hash: String,
+    pub change_type: String,
+    // the graphs the operation changes, as collection, sample and name
+    pub block_groups: Vec<(String, Option<String>, String)>,
+    // the paths the operation adds, as the sample and name of their graph and their own name
+    pub paths: Vec<(Option<String>, String, String)>,
+    // edges of the operation that are already in the database
+    pub existing_edges: usize,
+    // sequences the operation needs that the database doesn't have, in which case the operation
+    // can't be applied
+    pub missing_sequences: Vec<String>,
+    // whether the repository already has the operation, in which case it is skipped
+    pub already_applied: bool,
+    // whether applying the operation would change anything
+    pub changes: bool,
+}
+
+impl DryRunResult {
+    pub fn has_conflicts(&self) -> bool {
+        self.existing_edges > 0 || !self.missing_sequences.is_empty()
+    }
+}
+
+// Applies patches to an in-memory copy of a database and reports what each operation would change,
+// leaving the database and the repository's operations untouched.
+pub fn dry_run_patches(
+    conn: &Connection,
+    op_conn: &Connection,
+    patches: &[OperationPatch],
+) -> Result<Vec<DryRunResult>, PatchError> {
+    let db_uuid = get_db_uuid(conn);
+    let mut copy = Connection::open_in_memory()?;
+    rusqlite::vtab::array::load_module(&copy)?;
+    Backup::new(conn, &mut copy)?.run_to_completion(1000, Duration::ZERO, None)?;
+
+    let mut results = vec![];
+    for patch in patches.iter() {
+        let mut result = DryRunResult {
+            operation_hash: patch.operation.hash.clone(),
+            change_type: patch.operation.change_type.clone(),
+            missing_sequences: missing_sequences(&copy, patch, &HashSet::new())?,
+            already_applied: op_conn.query_row(
+                "select count(*) from operation where hash = ?1 and db_uuid = ?2;",
+                params![patch.operation.hash, db_uuid],
+                |row| row.get::<_, i64>(0),
+            )? > 0,
+            ..DryRunResult::default()
+        };
+        if result.already_applied || !result.missing_sequences.is_empty() {
+            results.push(result);
+            continue;
+        }
+        let input: &mut dyn Read = &mut patch.changeset.as_slice();
+        let mut iter = ChangesetIter::start_strm(&input)?;
+        let dependencies: DependencyModels = serde_json::from_slice(&patch.dependencies)?;
+        let mut session = start_operation(&copy);
+        for sequence in patch.sequences.iter() {
+            NewSequence::from(sequence).save(&copy);
+        }
+        apply_changeset(&copy, &mut iter, &dependencies);
+        let mut applied = vec![];
+        session.changeset_strm(&mut applied)?;
+        drop(session);
+
+        result.changes = !applied.is_empty();
+        result.existing_edges = inserted_keys(&patch.changeset, "edges")
+            .len()
+            .saturating_sub(inserted_keys(&applied, "edges").len());
+        result.block_groups = get_changeset_block_group_ids(&applied)
+            .into_iter()
+            .map(|block_group_id| {
+                let block_group = BlockGroup::get_by_id(&copy, block_group_id);
+                (
+                    block_group.collection_name,
+                    block_group.sample_name,
+                    block_group.name,
+                )
+            })
+            .collect();
+        result.paths = inserted_keys(&applied, "paths")
+            .iter()
+            .map(|path_id| {
+                copy.query_row(
+                    "select bg.sample_name, bg.name, p.name from paths p join block_groups bg on bg.id = p.block_group_id where p.id = ?1;",
+                    params![path_id.parse::<i64>().unwrap()],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+// Describes a dry run result for the command line.
+pub fn describe_dry_run(result: &DryRunResult) -> Vec<String> {
+    let mut lines = vec![format!(
+        "Operation {hash:.7} ({change_type}):",
+        hash = result.operation_hash,
+        change_type = result.change_type
+    )];
+    if result.already_applied {
+        lines.push("  already applied".to_string());
+        return lines;
+    }
+    if !result.missing_sequences.is_empty() {
+        lines.push(format!(
+            "  CONFLICT: missing sequences {}",
+            result.missing_sequences.join(", ")
+        ));
+        return lines;
+    }
+    if result.existing_edges > 0 {
+        lines.push(format!(
+            "  CONFLICT: {} edges already exist",
+            result.existing_edges
+        ));
+    }
+    if !result.changes {
+        lines.push("  no new changes".to_string());
+    }
+    for (collection_name, sample_name, name) in result.block_groups.iter() {
+        lines.push(format!(
+            "  changes {collection_name} {sample} {name}",
+            sample = Sample::display_name(sample_name.as_deref())
+        ));
+    }
+    for (sample_name, block_group_name, path_name) in result.paths.iter() {
+        lines.push(format!(
+            "  adds path {path_name} to {sample} {block_group_name}",
+            sample = Sample::display_name(sample_name.as_deref())
+        ));
+    }
+    lines
 }
 
 #[cfg(test)]
@@ -154,8 +588,13 @@ mod tests {
         )
         .unwrap();
         let mut write_stream: Vec<u8> = Vec::new();
-        create_patch(operation_conn, &[op_1.hash, op_2.hash], &mut write_stream);
-        load_patches(&write_stream[..]);
+        create_patch(
+            operation_conn,
+            &[op_1.hash, op_2.hash],
+            false,
+            &mut write_stream,
+        );
+        load_patches(&write_stream[..]).unwrap();
     }
 
     #[test]
@@ -192,10 +631,15 @@ mod tests {
         )
         .unwrap();
         let mut write_stream: Vec<u8> = Vec::new();
-        create_patch(operation_conn, &[op_1.hash, op_2.hash], &mut write_stream);
-        let patches = load_patches(&write_stream[..]);
-        apply_patches(conn2, operation_conn2, &patches);
-        apply_patches(conn, operation_conn, &patches);
+        create_patch(
+            operation_conn,
+            &[op_1.hash, op_2.hash],
+            false,
+            &mut write_stream,
+        );
+        let patches = load_patches(&write_stream[..]).unwrap();
+        apply_patches(conn2, operation_conn2, &patches).unwrap();
+        apply_patches(conn, operation_conn, &patches).unwrap();
         for bg in BlockGroup::query(conn, "select * from block_groups;", params![]).iter() {
             let seqs = BlockGroup::get_all_sequences(conn, bg.id, false);
             assert!(!seqs.is_empty());
@@ -236,7 +680,7 @@ mod tests {
         )
         .unwrap();
         let mut write_stream: Vec<u8> = Vec::new();
-        create_patch(operation_conn, &[op_2.hash], &mut write_stream);
+        create_patch(operation_conn, &[op_2.hash], false, &mut write_stream);
 
         operation_management::checkout(
             conn,
@@ -245,13 +689,280 @@ mod tests {
             &Some("main".to_string()),
             None,
         );
-        let patches = load_patches(&write_stream[..]);
-        apply_patches(conn, operation_conn, &patches);
+        let patches = load_patches(&write_stream[..]).unwrap();
+        apply_patches(conn, operation_conn, &patches).unwrap();
         let branch_ops = Branch::get_operations(operation_conn, main_branch.id);
         assert_eq!(branch_ops.len(), 2);
         // ensure if we apply the operation again it'll be a no-op
-        apply_patches(conn, operation_conn, &patches);
+        apply_patches(conn, operation_conn, &patches).unwrap();
         let branch_ops = Branch::get_operations(operation_conn, main_branch.id);
         assert_eq!(branch_ops.len(), 2);
     }
+
+    #[test]
+    fn test_patch_integrity_and_embedded_sequences() {
+        setup_gen_dir();
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.vcf");
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let op_2 = update_with_vcf(
+            &vcf_path.to_str().unwrap().to_string(),
+            "test",
+            "".to_string(),
+            "".to_string(),
+            conn,
+            operation_conn,
+            None,
+        )
+        .unwrap();
+
+        // the vcf update refers to the imported sequence, which a new repository lacks
+        let conn2 = &get_connection(None);
+        let operation_conn2 = &get_operation_connection(None);
+        setup_db(operation_conn2, &get_db_uuid(conn2));
+        let mut bare = vec![];
+        create_patch(
+            operation_conn,
+            std::slice::from_ref(&op_2.hash),
+            false,
+            &mut bare,
+        );
+        let result = apply_patches(conn2, operation_conn2, &load_patches(&bare[..]).unwrap());
+        assert!(matches!(result, Err(PatchError::MissingSequences { .. })));
+        assert!(BlockGroup::query(conn2, "select * from block_groups;", params![]).is_empty());
+
+        let mut embedded = vec![];
+        create_patch(
+            operation_conn,
+            std::slice::from_ref(&op_2.hash),
+            true,
+            &mut embedded,
+        );
+        let patches = load_patches(&embedded[..]).unwrap();
+        assert!(!patches[0].sequences.is_empty());
+        apply_patches(conn2, operation_conn2, &patches).unwrap();
+        let sample_bg = |conn: &Connection| {
+            BlockGroup::query(
+                conn,
+                "select * from block_groups where sample_name = 'foo';",
+                params![],
+            )[0]
+            .id
+        };
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn2, sample_bg(conn2), false),
+            BlockGroup::get_all_sequences(conn, sample_bg(conn), false)
+        );
+
+        // changes to a patch are caught before anything is applied
+        let rewrite = |patch_file: &PatchFile| {
+            let mut e = GzEncoder::new(Vec::new(), Compression::default());
+            e.write_all(&serde_json::to_vec(patch_file).unwrap())
+                .unwrap();
+            e.finish().unwrap()
+        };
+        let read = |bytes: &[u8]| {
+            let mut decoded = vec![];
+            GzDecoder::new(bytes).read_to_end(&mut decoded).unwrap();
+            serde_json::from_slice::<PatchFile>(&decoded).unwrap()
+        };
+        let mut patch_file = read(&embedded);
+        assert_eq!(patch_file.version, PATCH_FORMAT_VERSION);
+        let last = patch_file.operations[0].changeset.len() - 1;
+        patch_file.operations[0].changeset[last] ^= 1;
+        assert!(matches!(
+            load_patches(&rewrite(&patch_file)[..]),
+            Err(PatchError::ChecksumMismatch(hash)) if hash == op_2.hash
+        ));
+        let mut patch_file = read(&embedded);
+        patch_file.manifest.clear();
+        assert!(matches!(
+            load_patches(&rewrite(&patch_file)[..]),
+            Err(PatchError::MissingChecksum(_))
+        ));
+        let mut patch_file = read(&embedded);
+        patch_file.version = PATCH_FORMAT_VERSION + 1;
+        assert!(matches!(
+            load_patches(&rewrite(&patch_file)[..]),
+            Err(PatchError::UnsupportedVersion(_))
+        ));
+
+        // version 1 patches are a bare list of operations
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&serde_json::to_vec(&read(&bare).operations).unwrap())
+            .unwrap();
+        let v1_patches = load_patches(&e.finish().unwrap()[..]).unwrap();
+        assert_eq!(v1_patches[0].operation, op_2);
+    }
+
+    #[test]
+    fn test_dry_run_patches() {
+        setup_gen_dir();
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.vcf");
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &get_db_uuid(conn));
+        let op_1 = import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            operation_conn,
+        )
+        .unwrap();
+        let op_2 = update_with_vcf(
+            &vcf_path.to_str().unwrap().to_string(),
+            "test",
+            "".to_string(),
+            "".to_string(),
+            conn,
+            operation_conn,
+            None,
+        )
+        .unwrap();
+        let mut write_stream = vec![];
+        create_patch(
+            operation_conn,
+            &[op_1.hash.clone(), op_2.hash.clone()],
+            false,
+            &mut write_stream,
+        );
+        let patches = load_patches(&write_stream[..]).unwrap();
+
+        let conn2 = &get_connection(None);
+        let operation_conn2 = &get_operation_connection(None);
+        setup_db(operation_conn2, &get_db_uuid(conn2));
+        let results = dry_run_patches(conn2, operation_conn2, &patches).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.changes));
+        assert!(!results.iter().any(|result| result.has_conflicts()));
+        assert_eq!(
+            results[0].block_groups,
+            vec![("test".to_string(), None, "m123".to_string())]
+        );
+        assert_eq!(
+            results[0].paths,
+            vec![(None, "m123".to_string(), "m123".to_string())]
+        );
+        assert!(results[1].block_groups.contains(&(
+            "test".to_string(),
+            Some("foo".to_string()),
+            "m123".to_string()
+        )));
+        // nothing was applied
+        assert!(BlockGroup::query(conn2, "select * from block_groups;", params![]).is_empty());
+
+        // without the first operation, the second lacks the imported sequence
+        let results = dry_run_patches(conn2, operation_conn2, &patches[1..]).unwrap();
+        assert_eq!(results[0].missing_sequences.len(), 1);
+        assert!(results[0].has_conflicts());
+        assert!(describe_dry_run(&results[0])[1].starts_with("  CONFLICT: missing sequences"));
+
+        // the database the patch came from already has everything
+        let results = dry_run_patches(conn, operation_conn, &patches).unwrap();
+        assert!(results.iter().all(|result| result.already_applied));
+        assert_eq!(describe_dry_run(&results[0])[1], "  already applied");
+    }
+
+    #[test]
+    fn test_selects_patches() {
+        setup_gen_dir();
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.vcf");
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let operation_conn = &get_operation_connection(None);
+        setup_db(operation_conn, &get_db_uuid(conn));
+        let mut operations = vec![];
+        for collection in ["test", "other"] {
+            operations.push(
+                import_fasta(
+                    &fasta_path.to_str().unwrap().to_string(),
+                    collection,
+                    None,
+                    false,
+                    conn,
+                    operation_conn,
+                )
+                .unwrap()
+                .hash,
+            );
+        }
+        operations.push(
+            update_with_vcf(
+                &vcf_path.to_str().unwrap().to_string(),
+                "test",
+                "".to_string(),
+                "".to_string(),
+                conn,
+                operation_conn,
+                None,
+            )
+            .unwrap()
+            .hash,
+        );
+        let mut write_stream = vec![];
+        create_patch(operation_conn, &operations, false, &mut write_stream);
+        let selected = |collection_name: Option<&str>,
+                        sample_name: Option<&str>,
+                        operation_hashes: &[String]| {
+            select_patches(
+                load_patches(&write_stream[..]).unwrap(),
+                collection_name,
+                sample_name,
+                operation_hashes,
+            )
+            .into_iter()
+            .map(|patch| patch.operation.hash)
+            .collect::<Vec<String>>()
+        };
+
+        assert_eq!(selected(None, None, &[]), operations);
+        assert_eq!(
+            selected(Some("test"), None, &[]),
+            vec![operations[0].clone(), operations[2].clone()]
+        );
+        assert_eq!(
+            selected(Some("other"), None, &[]),
+            vec![operations[1].clone()]
+        );
+        assert_eq!(
+            selected(None, Some("foo"), &[]),
+            vec![operations[2].clone()]
+        );
+        assert!(selected(Some("other"), Some("foo"), &[]).is_empty());
+        assert_eq!(
+            selected(None, None, &[operations[1][..8].to_string()]),
+            vec![operations[1].clone()]
+        );
+        assert!(selected(Some("test"), None, &[operations[1].clone()]).is_empty());
+
+        let conn2 = &get_connection(None);
+        let operation_conn2 = &get_operation_connection(None);
+        setup_db(operation_conn2, &get_db_uuid(conn2));
+        let patches = select_patches(
+            load_patches(&write_stream[..]).unwrap(),
+            Some("other"),
+            None,
+            &[],
+        );
+        apply_patches(conn2, operation_conn2, &patches).unwrap();
+        let collections = BlockGroup::query(conn2, "select * from block_groups;", params![])
+            .into_iter()
+            .map(|block_group| block_group.collection_name)
+            .collect::<Vec<String>>();
+        assert_eq!(collections, vec!["other".to_string()]);
+    }
 }
@@ -1,6 +1,80 @@
+use crate::operation_management::OperationError;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+/*
+   Lets callers driving gen as a library follow long imports and updates and stop them. Reporters are
+   told how far each step of the work is, under the message of its progress bar, and are asked
+   whether to go on between items. A cancelled import or update returns OperationError::Cancelled
+   without recording an operation, and like other errors leaves its changes for the caller to roll
+   back.
+*/
+pub trait ProgressReporter {
+    // Called as a step advances, with the number of items done and the number of items in the step
+    // if it's known ahead of time.
+    fn on_progress(&self, _step: &str, _completed: u64, _total: Option<u64>) {}
+
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {}
+
+// A flag shared between the caller and an operation, which can be cancelled from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl ProgressReporter for CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub fn percent_complete(completed: u64, total: Option<u64>) -> Option<f64> {
+    match total {
+        Some(0) => Some(100.0),
+        Some(total) => Some(100.0 * completed as f64 / total as f64),
+        None => None,
+    }
+}
+
+// Tells a reporter where a bar is, and stops the operation if the reporter has been cancelled.
+pub fn report_progress(
+    reporter: &dyn ProgressReporter,
+    bar: &ProgressBar,
+) -> Result<(), OperationError> {
+    report_step(reporter, &bar.message(), bar.position(), bar.length())
+}
+
+// Like report_progress, for steps that don't show a progress bar.
+pub fn report_step(
+    reporter: &dyn ProgressReporter,
+    step: &str,
+    completed: u64,
+    total: Option<u64>,
+) -> Result<(), OperationError> {
+    reporter.on_progress(step, completed, total);
+    if reporter.is_cancelled() {
+        return Err(OperationError::Cancelled);
+    }
+    Ok(())
+}
+
 pub fn get_handler() -> MultiProgress {
     let p = MultiProgress::new();
     #[cfg(test)]
@@ -55,6 +129,18 @@ pub fn get_progress_bar(length: impl Into<Option<u64>>) -> ProgressBar {
     bar
 }
 
+// A bar for long steps of known length, which also shows the time left.
+pub fn get_eta_progress_bar(length: u64) -> ProgressBar {
+    let bar = ProgressBar::new(length).with_style(
+        ProgressStyle::with_template(
+            "[{elapsed_precise}] {bar:41.cyan/blue}  {human_pos:>7}/{human_len:7} {msg} (eta {eta})",
+        )
+        .unwrap(),
+    );
+    bar.enable_steady_tick(Duration::from_millis(250));
+    bar
+}
+
 pub fn get_time_elapsed_bar() -> ProgressBar {
     let bar = ProgressBar::no_length().with_style(
         ProgressStyle::with_template(
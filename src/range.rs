@@ -1,5 +1,7 @@
+use crate::models::strand::Strand;
 use itertools::Itertools;
 use std::cmp::{max, min};
+use thiserror::Error;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Range {
@@ -200,6 +202,9 @@ impl Range {
 pub struct RangeMapping {
     pub source_range: Range,
     pub target_range: Range,
+    // Reverse if the target range is read in the opposite direction of the source range, such as
+    // across an inversion, so the start of the source range maps to the end of the target range.
+    pub strand: Strand,
 }
 
 impl RangeMapping {
@@ -212,12 +217,20 @@ impl RangeMapping {
                 current_group.push(mapping);
             } else {
                 let last_mapping = current_group.last().unwrap();
-                if last_mapping
-                    .source_range
-                    .left_adjoins(&mapping.source_range, None)
-                    && last_mapping
+                let targets_adjoin = if mapping.strand == Strand::Reverse {
+                    mapping
+                        .target_range
+                        .left_adjoins(&last_mapping.target_range, None)
+                } else {
+                    last_mapping
                         .target_range
                         .left_adjoins(&mapping.target_range, None)
+                };
+                if last_mapping.strand == mapping.strand
+                    && last_mapping
+                        .source_range
+                        .left_adjoins(&mapping.source_range, None)
+                    && targets_adjoin
                 {
                     current_group.push(mapping);
                 } else {
@@ -235,9 +248,15 @@ impl RangeMapping {
         for group in grouped_mappings {
             let first = group.first().unwrap();
             let last = group.last().unwrap();
+            let target_range = if first.strand == Strand::Reverse {
+                last.target_range.extend_to(&first.target_range)
+            } else {
+                first.target_range.extend_to(&last.target_range)
+            };
             merged_mappings.push(RangeMapping {
                 source_range: first.source_range.extend_to(&last.source_range),
-                target_range: first.target_range.extend_to(&last.target_range),
+                target_range,
+                strand: first.strand,
             });
         }
 
@@ -245,6 +264,281 @@ impl RangeMapping {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum RegionError {
+    #[error("Invalid region \"{0}\": {1}")]
+    Malformed(String, &'static str),
+    #[error("Unknown contig {name}{}", format_suggestions(.suggestions))]
+    UnknownContig {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    #[error("Region {start}-{end} is out of bounds for {name} (length {length})")]
+    OutOfBounds {
+        name: String,
+        start: i64,
+        end: i64,
+        length: i64,
+    },
+    #[error("Graph {0} has no path")]
+    NoPath(String),
+    #[error("The path of {0} is empty")]
+    EmptyPath(String),
+}
+
+fn format_suggestions(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        "".to_string()
+    } else {
+        format!(", did you mean {}?", suggestions.join(" or "))
+    }
+}
+
+/*
+   A region as given on the command line. Region strings follow the samtools convention: `name`,
+   `name:start` or `name:start-end`, with 1-based coordinates and an inclusive end, and commas are
+   allowed as thousands separators. They are stored here in the 0-based, end-exclusive coordinates
+   used everywhere else, so chr1:1-10 has a start of 0 and an end of 10. A missing start or end
+   means the start or end of the contig.
+
+   Coordinates can also count back from the end of the contig, as `$` for the last base and `$-N`
+   for N bases before it, so chr1:$-9-$ is the last 10 bases. These are stored as negative values
+   that are added to the contig's length when the region is resolved, so the start of that region
+   is -10. A negative end is likewise relative to the end of the contig, which is a missing end.
+*/
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+// Parses a region and checks that its contig is one of `contig_names`.
+pub fn parse_region(region: &str, contig_names: &[String]) -> Result<Region, RegionError> {
+    let trimmed_region = region.trim();
+    // Contig names may themselves contain colons (e.g. HLA alleles), so a region that exactly names
+    // a contig always refers to that whole contig, as with samtools.
+    if contig_names.iter().any(|name| name == trimmed_region) {
+        return Ok(Region {
+            name: trimmed_region.to_string(),
+            start: None,
+            end: None,
+        });
+    }
+    let parsed_region = parse_region_string(region)?;
+    parsed_region.validate_name(contig_names)?;
+    Ok(parsed_region)
+}
+
+// Parses the region syntax alone, without checking that the contig exists.
+pub fn parse_region_string(region: &str) -> Result<Region, RegionError> {
+    let malformed = |reason| RegionError::Malformed(region.to_string(), reason);
+    let region = region.trim();
+    if region.is_empty() {
+        return Err(malformed("a contig name is required"));
+    }
+
+    // Only treat the text after the last colon as coordinates if it looks like them.
+    let (name, coordinates) = match region.rsplit_once(':') {
+        Some((name, coordinates))
+            if !coordinates.is_empty()
+                && coordinates
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || c == ',' || c == '-' || c == '$') =>
+        {
+            (name, Some(coordinates.replace(',', "")))
+        }
+        _ => (region, None),
+    };
+    if name.is_empty() {
+        return Err(malformed("a contig name is required"));
+    }
+
+    let Some(coordinates) = coordinates else {
+        return Ok(Region {
+            name: name.to_string(),
+            start: None,
+            end: None,
+        });
+    };
+    let parse = |value: &str| parse_coordinate(value).ok_or_else(|| malformed(COORDINATE_FORMAT));
+    let (start, end) = split_coordinates(&coordinates);
+    let start = parse(start)?;
+    let end = end.map(parse).transpose()?;
+    for coordinate in [Some(start), end].into_iter().flatten() {
+        if let Coordinate::Position(position) = coordinate {
+            if position < 1 {
+                return Err(malformed(
+                    "coordinates are 1-based, so they must be at least 1",
+                ));
+            }
+        }
+    }
+    // Positions counted from the end can only be compared once the contig's length is known.
+    if let Some(end) = end {
+        if start.same_origin(&end) && end.value() < start.value() {
+            return Err(malformed("the end must not be before the start"));
+        }
+    }
+
+    // An end of $ or $-0 is the end of the contig, which a missing end already means. Positions are
+    // at least 1, so only those give an end of 0.
+    Ok(Region {
+        name: name.to_string(),
+        start: Some(start.value() - 1),
+        end: end.map(|end| end.value()).filter(|end| *end != 0),
+    })
+}
+
+const COORDINATE_FORMAT: &str = "coordinates must be positive integers, $ or $-N";
+
+// A 1-based coordinate of a region string, either a position or a number of bases before the last
+// base, written $-N.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Coordinate {
+    Position(i64),
+    FromEnd(i64),
+}
+
+impl Coordinate {
+    fn same_origin(&self, other: &Coordinate) -> bool {
+        matches!(
+            (self, other),
+            (Coordinate::Position(_), Coordinate::Position(_))
+                | (Coordinate::FromEnd(_), Coordinate::FromEnd(_))
+        )
+    }
+
+    // The position, with positions from the end as offsets past the contig's length, where $ is 0.
+    fn value(&self) -> i64 {
+        match self {
+            Coordinate::Position(position) => *position,
+            Coordinate::FromEnd(offset) => -offset,
+        }
+    }
+}
+
+fn parse_coordinate(value: &str) -> Option<Coordinate> {
+    let parse_number = |value: &str| {
+        if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+            value.parse::<i64>().ok()
+        } else {
+            None
+        }
+    };
+    match value.strip_prefix('$') {
+        Some("") => Some(Coordinate::FromEnd(0)),
+        Some(offset) => parse_number(offset.strip_prefix('-')?).map(Coordinate::FromEnd),
+        None => parse_number(value).map(Coordinate::Position),
+    }
+}
+
+// Splits coordinates into a start and an optional end. A dash after $ belongs to the coordinate,
+// so $-5 is a single coordinate while $-5-$ is a start and an end.
+fn split_coordinates(coordinates: &str) -> (&str, Option<&str>) {
+    let separator = match coordinates.strip_prefix("$-") {
+        Some(rest) => rest.find('-').map(|index| index + 2),
+        None => coordinates.find('-'),
+    };
+    match separator {
+        Some(index) => (&coordinates[..index], Some(&coordinates[index + 1..])),
+        None => (coordinates, None),
+    }
+}
+
+impl Region {
+    // Checks the region's contig against the contigs that exist, suggesting close matches for
+    // likely typos.
+    pub fn validate_name(&self, contig_names: &[String]) -> Result<(), RegionError> {
+        if contig_names.contains(&self.name) {
+            return Ok(());
+        }
+        let max_distance = (self.name.len() / 4).max(2);
+        let suggestions = contig_names
+            .iter()
+            .map(|contig_name| {
+                (
+                    edit_distance(&self.name.to_lowercase(), &contig_name.to_lowercase()),
+                    contig_name,
+                )
+            })
+            .filter(|(distance, _)| *distance <= max_distance)
+            .sorted()
+            .take(3)
+            .map(|(_, contig_name)| contig_name.clone())
+            .collect();
+        Err(RegionError::UnknownContig {
+            name: self.name.clone(),
+            suggestions,
+        })
+    }
+
+    // Resolves the region against a contig of the given length, filling in a missing start or end
+    // and counting negative coordinates back from the end.
+    pub fn range(&self, length: i64) -> Result<Range, RegionError> {
+        let (start, end) = self.resolve(length)?;
+        if start < 0 || start > end || end > length {
+            return Err(RegionError::OutOfBounds {
+                name: self.name.clone(),
+                start,
+                end,
+                length,
+            });
+        }
+        Ok(Range { start, end })
+    }
+
+    // Resolves the region against a circular contig, where a start after the end wraps around the
+    // origin.
+    pub fn circular_range(&self, length: i64) -> Result<Range, RegionError> {
+        let (start, end) = self.resolve(length)?;
+        if start < 0 || start > length || end < 0 || end > length {
+            return Err(RegionError::OutOfBounds {
+                name: self.name.clone(),
+                start,
+                end,
+                length,
+            });
+        }
+        Ok(Range { start, end })
+    }
+
+    fn resolve(&self, length: i64) -> Result<(i64, i64), RegionError> {
+        if length == 0 {
+            return Err(RegionError::EmptyPath(self.name.clone()));
+        }
+        let from_end = |coordinate: i64| {
+            if coordinate < 0 {
+                length + coordinate
+            } else {
+                coordinate
+            }
+        };
+        Ok((
+            from_end(self.start.unwrap_or(0)),
+            self.end.map(from_end).unwrap_or(length),
+        ))
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars = b.chars().collect::<Vec<char>>();
+    let mut previous_row = (0..=b_chars.len()).collect::<Vec<usize>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current_row = vec![i + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            current_row.push(
+                (previous_row[j] + substitution_cost)
+                    .min(previous_row[j + 1] + 1)
+                    .min(current_row[j] + 1),
+            );
+        }
+        previous_row = current_row;
+    }
+    previous_row[b_chars.len()]
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -288,14 +582,17 @@ mod tests {
             RangeMapping {
                 source_range: Range { start: 0, end: 2 },
                 target_range: Range { start: 2, end: 4 },
+                strand: Strand::Forward,
             },
             RangeMapping {
                 source_range: Range { start: 2, end: 5 },
                 target_range: Range { start: 4, end: 7 },
+                strand: Strand::Forward,
             },
             RangeMapping {
                 source_range: Range { start: 7, end: 8 },
                 target_range: Range { start: 9, end: 10 },
+                strand: Strand::Forward,
             },
         ];
 
@@ -307,12 +604,253 @@ mod tests {
                 RangeMapping {
                     source_range: Range { start: 0, end: 5 },
                     target_range: Range { start: 2, end: 7 },
+                    strand: Strand::Forward,
                 },
                 RangeMapping {
                     source_range: Range { start: 7, end: 8 },
                     target_range: Range { start: 9, end: 10 },
+                    strand: Strand::Forward,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_contiguous_reverse_ranges() {
+        let mappings = vec![
+            RangeMapping {
+                source_range: Range { start: 0, end: 2 },
+                target_range: Range { start: 8, end: 10 },
+                strand: Strand::Reverse,
+            },
+            RangeMapping {
+                source_range: Range { start: 2, end: 5 },
+                target_range: Range { start: 5, end: 8 },
+                strand: Strand::Reverse,
+            },
+            RangeMapping {
+                source_range: Range { start: 5, end: 7 },
+                target_range: Range { start: 10, end: 12 },
+                strand: Strand::Forward,
+            },
+        ];
+
+        assert_eq!(
+            RangeMapping::merge_contiguous_mappings(mappings),
+            vec![
+                RangeMapping {
+                    source_range: Range { start: 0, end: 5 },
+                    target_range: Range { start: 5, end: 10 },
+                    strand: Strand::Reverse,
+                },
+                RangeMapping {
+                    source_range: Range { start: 5, end: 7 },
+                    target_range: Range { start: 10, end: 12 },
+                    strand: Strand::Forward,
                 },
             ]
         );
     }
+
+    #[test]
+    fn test_parse_region_string() {
+        assert_eq!(
+            parse_region_string("chr1:1-10").unwrap(),
+            Region {
+                name: "chr1".to_string(),
+                start: Some(0),
+                end: Some(10)
+            }
+        );
+        assert_eq!(
+            parse_region_string(" chr1:1,001-2,000 ").unwrap(),
+            Region {
+                name: "chr1".to_string(),
+                start: Some(1000),
+                end: Some(2000)
+            }
+        );
+        assert_eq!(
+            parse_region_string("chr1:5").unwrap(),
+            Region {
+                name: "chr1".to_string(),
+                start: Some(4),
+                end: None
+            }
+        );
+        assert_eq!(
+            parse_region_string("HLA-A*01:01:1-5").unwrap().name,
+            "HLA-A*01:01".to_string()
+        );
+        assert_eq!(
+            parse_region_string("chr1").unwrap(),
+            Region {
+                name: "chr1".to_string(),
+                start: None,
+                end: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_region_errors() {
+        assert!(matches!(
+            parse_region_string(""),
+            Err(RegionError::Malformed(_, _))
+        ));
+        assert!(matches!(
+            parse_region_string(":1-5"),
+            Err(RegionError::Malformed(_, _))
+        ));
+        assert!(matches!(
+            parse_region_string("chr1:0-5"),
+            Err(RegionError::Malformed(_, _))
+        ));
+        assert!(matches!(
+            parse_region_string("chr1:$-5-0"),
+            Err(RegionError::Malformed(_, _))
+        ));
+        assert!(matches!(
+            parse_region_string("chr1:10-5"),
+            Err(RegionError::Malformed(_, _))
+        ));
+        assert!(matches!(
+            parse_region_string("chr1:1-2-3"),
+            Err(RegionError::Malformed(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_region_validation() {
+        let contigs = vec![
+            "chr1".to_string(),
+            "chr2".to_string(),
+            "m123".to_string(),
+            "HLA-A*01:01".to_string(),
+        ];
+        assert_eq!(
+            parse_region("Chr1:1-5", &contigs).unwrap_err().to_string(),
+            "Unknown contig Chr1, did you mean chr1 or chr2?"
+        );
+        assert_eq!(
+            parse_region("plasmid", &contigs).unwrap_err().to_string(),
+            "Unknown contig plasmid"
+        );
+        assert_eq!(
+            parse_region("HLA-A*01:01", &contigs).unwrap(),
+            Region {
+                name: "HLA-A*01:01".to_string(),
+                start: None,
+                end: None
+            }
+        );
+
+        assert_eq!(
+            parse_region("m123:2-4", &contigs)
+                .unwrap()
+                .range(34)
+                .unwrap(),
+            Range { start: 1, end: 4 }
+        );
+        assert_eq!(
+            parse_region("m123", &contigs).unwrap().range(34).unwrap(),
+            Range { start: 0, end: 34 }
+        );
+        assert_eq!(
+            parse_region("m123:30-40", &contigs).unwrap().range(34),
+            Err(RegionError::OutOfBounds {
+                name: "m123".to_string(),
+                start: 29,
+                end: 40,
+                length: 34
+            })
+        );
+
+        let wrapping_region = Region {
+            name: "m123".to_string(),
+            start: Some(30),
+            end: Some(4),
+        };
+        assert!(wrapping_region.range(34).is_err());
+        assert_eq!(
+            wrapping_region.circular_range(34).unwrap(),
+            Range { start: 30, end: 4 }
+        );
+        assert!(parse_region("m123:30-40", &contigs)
+            .unwrap()
+            .circular_range(34)
+            .is_err());
+    }
+
+    #[test]
+    fn test_end_relative_coordinates() {
+        let region = |coordinates: &str| parse_region_string(&format!("chr1:{coordinates}"));
+        assert_eq!(region("$-9-$").unwrap().start, Some(-10));
+        assert_eq!(region("$-9-$").unwrap().end, None);
+        assert_eq!(
+            region("$-9-$").unwrap().range(34).unwrap(),
+            Range { start: 24, end: 34 }
+        );
+        assert_eq!(
+            region("$").unwrap().range(34).unwrap(),
+            Range { start: 33, end: 34 }
+        );
+        assert_eq!(
+            region("$-4").unwrap().range(34).unwrap(),
+            Range { start: 29, end: 34 }
+        );
+        assert_eq!(region("$-0").unwrap(), region("$").unwrap());
+        assert_eq!(
+            region("$-0").unwrap().range(34).unwrap(),
+            Range { start: 33, end: 34 }
+        );
+        assert_eq!(region("1-$-0").unwrap().end, None);
+        assert_eq!(
+            region("1-$-0").unwrap().range(34).unwrap(),
+            Range { start: 0, end: 34 }
+        );
+        assert_eq!(
+            region("5-$-4").unwrap().range(34).unwrap(),
+            Range { start: 4, end: 30 }
+        );
+        assert_eq!(
+            region("$-4-5").unwrap().range(34),
+            Err(RegionError::OutOfBounds {
+                name: "chr1".to_string(),
+                start: 29,
+                end: 5,
+                length: 34,
+            })
+        );
+        assert_eq!(
+            region("$-40").unwrap().range(34),
+            Err(RegionError::OutOfBounds {
+                name: "chr1".to_string(),
+                start: -7,
+                end: 34,
+                length: 34,
+            })
+        );
+        for malformed in ["$-2-$-5", "$5", "$--1", "1-$-", "$$"] {
+            assert!(
+                matches!(region(malformed), Err(RegionError::Malformed(_, _))),
+                "{malformed}"
+            );
+        }
+
+        let region = Region {
+            name: "chr1".to_string(),
+            start: Some(-4),
+            end: Some(-1),
+        };
+        assert_eq!(region.range(34).unwrap(), Range { start: 30, end: 33 });
+        assert_eq!(
+            region.range(0),
+            Err(RegionError::EmptyPath("chr1".to_string()))
+        );
+        assert_eq!(
+            region.circular_range(0),
+            Err(RegionError::EmptyPath("chr1".to_string()))
+        );
+    }
 }
@@ -0,0 +1,394 @@
+use crate::models::block_group::{BlockGroup, NodeIntervalBlock};
+use crate::models::node::Node;
+use crate::models::path::{revcomp, Path};
+use crate::models::path_index::PathIndex;
+use crate::models::sample::Sample;
+use crate::models::sequence::Sequence;
+use crate::models::strand::Strand;
+use itertools::Itertools;
+use noodles::fasta;
+use rusqlite::Connection;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::{io, str};
+
+pub const DEFAULT_KMER_SIZE: usize = 15;
+pub const DEFAULT_WINDOW_SIZE: usize = 10;
+
+// A k-mer of a node sequence chosen as the minimizer of one of its windows, given as the node and
+// the offset of the k-mer in the node's sequence.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Seed {
+    pub node_id: i64,
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchHit {
+    pub query: String,
+    pub sample: Option<String>,
+    pub graph: String,
+    pub start: i64,
+    pub end: i64,
+    pub strand: Strand,
+    pub mismatches: usize,
+    pub node_ids: Vec<i64>,
+}
+
+// Reads the records of a fasta file as pairs of name and sequence.
+pub fn read_queries(fasta_path: &str) -> io::Result<Vec<(String, String)>> {
+    let mut reader = fasta::io::reader::Builder.build_from_path(fasta_path)?;
+    reader
+        .records()
+        .map(|result| {
+            let record = result?;
+            Ok((
+                String::from_utf8_lossy(record.name()).to_string(),
+                str::from_utf8(record.sequence().as_ref())
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+                    .to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn kmer_hash(kmer: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    kmer.to_ascii_uppercase().hash(&mut hasher);
+    hasher.finish()
+}
+
+// Returns the (w, k)-minimizers of a sequence as pairs of k-mer hash and offset. Every window of w
+// consecutive k-mers contributes its smallest k-mer, and sequences too short for a full window
+// contribute their smallest k-mer.
+pub fn minimizers(sequence: &[u8], k: usize, w: usize) -> Vec<(u64, usize)> {
+    if k == 0 || sequence.len() < k {
+        return vec![];
+    }
+    let hashes = sequence.windows(k).map(kmer_hash).collect::<Vec<u64>>();
+    let window_size = w.clamp(1, hashes.len());
+    hashes
+        .windows(window_size)
+        .enumerate()
+        .map(|(window_start, window)| {
+            let (offset, hash) = window
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, hash)| **hash)
+                .unwrap();
+            (*hash, window_start + offset)
+        })
+        .dedup()
+        .collect()
+}
+
+// An index of the minimizers of node sequences. Nodes are indexed on their forward strand, so
+// queries are looked up on both strands.
+pub struct MinimizerIndex {
+    pub k: usize,
+    pub w: usize,
+    seeds: HashMap<u64, Vec<Seed>>,
+}
+
+impl MinimizerIndex {
+    pub fn build(node_sequences: &HashMap<i64, String>, k: usize, w: usize) -> MinimizerIndex {
+        let mut seeds: HashMap<u64, Vec<Seed>> = HashMap::new();
+        for (node_id, sequence) in node_sequences.iter() {
+            for (hash, offset) in minimizers(sequence.as_bytes(), k, w) {
+                seeds.entry(hash).or_default().push(Seed {
+                    node_id: *node_id,
+                    offset,
+                });
+            }
+        }
+        MinimizerIndex { k, w, seeds }
+    }
+
+    // Returns the seeds sharing a minimizer with the query, with the offset of that minimizer in
+    // the query.
+    pub fn lookup(&self, query: &[u8]) -> Vec<(usize, Seed)> {
+        minimizers(query, self.k, self.w)
+            .into_iter()
+            .flat_map(|(hash, query_offset)| {
+                self.seeds
+                    .get(&hash)
+                    .into_iter()
+                    .flatten()
+                    .map(move |seed| (query_offset, *seed))
+            })
+            .collect()
+    }
+}
+
+fn mismatches(a: &str, b: &str) -> usize {
+    a.bytes()
+        .zip(b.bytes())
+        .filter(|(a, b)| !a.eq_ignore_ascii_case(b))
+        .count()
+}
+
+// The current path of every graph of every sample in a collection, with the sample it belongs to.
+// Samples recorded as aliases of another are left out, as their graphs are the same.
+fn collection_paths(
+    conn: &Connection,
+    collection_name: &str,
+) -> Vec<(Option<String>, String, Path)> {
+    let aliases = Sample::get_aliases(conn);
+    std::iter::once(None)
+        .chain(
+            Sample::get_all_names(conn)
+                .into_iter()
+                .filter(|sample_name| !aliases.contains_key(sample_name))
+                .map(Some),
+        )
+        .flat_map(|sample_name| {
+            Sample::get_block_groups(conn, collection_name, sample_name.as_deref())
+                .into_iter()
+                .map(move |block_group| {
+                    (
+                        sample_name.clone(),
+                        block_group.name.clone(),
+                        BlockGroup::get_current_path(conn, block_group.id),
+                    )
+                })
+        })
+        .collect()
+}
+
+// Finds where query sequences occur in the current paths of the samples of a collection, on
+// either strand, with up to max_mismatches substitutions. Candidate positions come from the
+// minimizers a query shares with the node sequences, and are then compared against the path
+// sequence, so a query has to share at least one k-mer with a single node to be found. The k-mer
+// size is shrunk to the shortest query if needed.
+pub fn find_sequences(
+    conn: &Connection,
+    collection_name: &str,
+    queries: &[(String, String)],
+    max_mismatches: usize,
+    k: usize,
+    w: usize,
+) -> Vec<SearchHit> {
+    let paths = collection_paths(conn, collection_name);
+    let path_blocks = paths
+        .iter()
+        .map(|(_, _, path)| PathIndex::blocks(conn, path.id))
+        .collect::<Vec<Vec<NodeIntervalBlock>>>();
+    let path_lengths = paths
+        .iter()
+        .map(|(_, _, path)| path.length(conn))
+        .collect::<Vec<i64>>();
+
+    // where each node appears on the paths, as path index and block
+    let mut node_blocks: HashMap<i64, Vec<(usize, NodeIntervalBlock)>> = HashMap::new();
+    for (path_index, blocks) in path_blocks.iter().enumerate() {
+        for block in blocks.iter() {
+            node_blocks
+                .entry(block.node_id)
+                .or_default()
+                .push((path_index, *block));
+        }
+    }
+    let nodes = Node::get_nodes(conn, &node_blocks.keys().copied().collect::<Vec<i64>>());
+    let sequences = Sequence::sequences_by_hash(
+        conn,
+        nodes
+            .iter()
+            .map(|node| node.sequence_hash.as_str())
+            .unique()
+            .collect(),
+    );
+    let node_sequences = nodes
+        .iter()
+        .map(|node| {
+            (
+                node.id,
+                sequences[&node.sequence_hash].get_sequence(None, None),
+            )
+        })
+        .collect::<HashMap<i64, String>>();
+
+    let k = queries
+        .iter()
+        .map(|(_, sequence)| sequence.len())
+        .filter(|length| *length > 0)
+        .min()
+        .unwrap_or(k)
+        .min(k);
+    let index = MinimizerIndex::build(&node_sequences, k, w);
+
+    let mut hits = vec![];
+    for (query_name, query_sequence) in queries.iter() {
+        if query_sequence.is_empty() {
+            continue;
+        }
+        let query_length = query_sequence.len() as i64;
+        let reverse_query = revcomp(query_sequence);
+        let mut candidates: HashSet<(usize, i64, Strand)> = HashSet::new();
+        for (strand, oriented_query) in [
+            (Strand::Forward, query_sequence),
+            (Strand::Reverse, &reverse_query),
+        ] {
+            for (query_offset, seed) in index.lookup(oriented_query.as_bytes()) {
+                let query_offset = query_offset as i64;
+                let seed_offset = seed.offset as i64;
+                for (path_index, block) in node_blocks[&seed.node_id].iter() {
+                    if seed_offset < block.sequence_start
+                        || seed_offset + k as i64 > block.sequence_end
+                    {
+                        continue;
+                    }
+                    // on a reverse block the path reads the reverse complement of the seed, which
+                    // is where the reverse complement of the query lines up
+                    let candidate = match block.strand {
+                        Strand::Reverse => (
+                            block.start + block.sequence_end
+                                - seed_offset
+                                - k as i64
+                                - (query_length - query_offset - k as i64),
+                            strand.flipped(),
+                        ),
+                        _ => (
+                            block.start + seed_offset - block.sequence_start - query_offset,
+                            strand,
+                        ),
+                    };
+                    candidates.insert((*path_index, candidate.0, candidate.1));
+                }
+            }
+        }
+
+        for (path_index, start, strand) in candidates
+            .into_iter()
+            .sorted_by_key(|(path_index, start, strand)| (*path_index, *start, *strand))
+        {
+            let end = start + query_length;
+            if start < 0 || end > path_lengths[path_index] {
+                continue;
+            }
+            let (sample, graph, path) = &paths[path_index];
+            let expected = match strand {
+                Strand::Reverse => &reverse_query,
+                _ => query_sequence,
+            };
+            let found = mismatches(&path.sequence_range(conn, start, end), expected);
+            if found <= max_mismatches {
+                hits.push(SearchHit {
+                    query: query_name.clone(),
+                    sample: sample.clone(),
+                    graph: graph.clone(),
+                    start,
+                    end,
+                    strand,
+                    mismatches: found,
+                    node_ids: PathIndex::blocks_in_range(conn, path.id, start, end)
+                        .iter()
+                        .map(|block| block.node_id)
+                        .collect(),
+                });
+            }
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_minimizers_cover_every_window() {
+        let sequence = b"ATCGATCGATCGATCGATCGGGAACACACAGAGA";
+        let found = minimizers(sequence, 5, 4);
+        // every window of 4 consecutive 5-mers holds one of the minimizers
+        for window_start in 0..=(sequence.len() - 5 - 3) {
+            assert!(found
+                .iter()
+                .any(|(_, offset)| (window_start..window_start + 4).contains(offset)));
+        }
+        // and a sequence shorter than a window still has one
+        assert_eq!(minimizers(b"ATCGAT", 5, 4).len(), 1);
+        assert!(minimizers(b"ATCG", 5, 4).is_empty());
+    }
+
+    #[test]
+    fn test_finds_sequences_across_samples() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // child has AAAAAAAA in place of bases 15-25 of ATCGATCGATCGATCGATCGGGAACACACAGAGA
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let child_node_ids = PathIndex::blocks(
+            conn,
+            BlockGroup::get_current_path(conn, get_sample_bg(conn, "test", "child").id).id,
+        )
+        .iter()
+        .map(|block| block.node_id)
+        .collect::<Vec<i64>>();
+
+        let queries = vec![
+            // only the reference has this
+            ("reference".to_string(), "GATCGGGAACACACAG".to_string()),
+            // the reverse complement of the junction into the child's insert, with one mismatch
+            ("insert".to_string(), "TTTTTTTTGATCGTT".to_string()),
+        ];
+        let hits = find_sequences(conn, "test", &queries, 1, 5, 3);
+        assert_eq!(
+            hits,
+            vec![
+                SearchHit {
+                    query: "reference".to_string(),
+                    sample: None,
+                    graph: "m123".to_string(),
+                    start: 15,
+                    end: 31,
+                    strand: Strand::Forward,
+                    mismatches: 0,
+                    node_ids: vec![child_node_ids[0]],
+                },
+                SearchHit {
+                    query: "insert".to_string(),
+                    sample: Some("child".to_string()),
+                    graph: "m123".to_string(),
+                    start: 8,
+                    end: 23,
+                    strand: Strand::Reverse,
+                    mismatches: 1,
+                    node_ids: child_node_ids[0..2].to_vec(),
+                },
+            ]
+        );
+    }
+}
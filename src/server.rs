@@ -0,0 +1,805 @@
+use crate::connection_pool::{ConnectionPool, PooledSqliteConnection};
+use crate::exports::fasta::write_region_fasta;
+use crate::exports::gfa::export_block_group_gfa;
+use crate::models::block_group::BlockGroup;
+use crate::models::collection::Collection;
+use crate::models::path::Path as GraphPath;
+use crate::models::sample::{Sample, BASE_SAMPLE_NAME};
+use crate::models::sequence::Sequence;
+use crate::models::sequence_digest::SequenceDigest;
+use crate::models::traits::Query as ModelQuery;
+use crate::range::{parse_region, Range, RegionError};
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures_util::stream;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use tempfile::tempdir;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    RangeNotSatisfiable(String),
+    #[error("{0}")]
+    NotImplemented(String),
+    #[error("{0}")]
+    Region(#[from] RegionError),
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Database Error: {0}")]
+    Pool(#[from] r2d2::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ServerError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServerError::BadRequest(_) | ServerError::Region(_) => StatusCode::BAD_REQUEST,
+            ServerError::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            ServerError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            ServerError::Io(_) | ServerError::Pool(_) | ServerError::Database(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, Json(serde_json::json!({"error": self.to_string()}))).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SampleParams {
+    sample: Option<String>,
+    format: Option<String>,
+    path: Option<String>,
+}
+
+impl SampleParams {
+    // The sample the request is for, which is the reference if none or its display name is given.
+    fn sample(&self) -> Option<&str> {
+        self.sample.as_deref().and_then(Sample::from_display_name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefgetParams {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+// Streamed responses are sent in buffers of this size, with this many waiting at most.
+const STREAM_BUFFER_BYTES: usize = 1 << 16;
+const STREAM_BUFFERS: usize = 16;
+
+// The content type refget clients expect sequences in.
+const REFGET_CONTENT_TYPE: &str = "text/vnd.ga4gh.refget.v2.0.0+plain";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RefgetMetadata {
+    pub id: String,
+    pub md5: String,
+    pub trunc512: String,
+    pub ga4gh: String,
+    pub length: i64,
+    pub aliases: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PathSummary {
+    pub name: String,
+    pub length: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GraphSummary {
+    pub name: String,
+    pub sample: String,
+    pub is_circular: bool,
+    pub current_path: Option<String>,
+    pub paths: Vec<PathSummary>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SequenceResponse {
+    pub name: String,
+    pub start: i64,
+    pub end: i64,
+    pub sequence: String,
+}
+
+pub fn collection_names(conn: &Connection) -> Vec<String> {
+    Collection::query(conn, "select * from collections order by name;", params![])
+        .into_iter()
+        .map(|collection| collection.name)
+        .collect()
+}
+
+// The samples of the repository, as list-samples shows them: the reference first and aliases left
+// out.
+pub fn sample_names(conn: &Connection) -> Vec<String> {
+    let aliases = Sample::get_aliases(conn);
+    std::iter::once(BASE_SAMPLE_NAME.to_string())
+        .chain(
+            Sample::get_all_names(conn)
+                .into_iter()
+                .filter(|name| !aliases.contains_key(name)),
+        )
+        .collect()
+}
+
+fn get_graph(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+) -> Result<BlockGroup, ServerError> {
+    Sample::get_block_groups(conn, collection_name, sample_name)
+        .into_iter()
+        .find(|block_group| block_group.name == graph_name)
+        .ok_or_else(|| {
+            ServerError::NotFound(format!(
+                "No graph {graph_name} in collection {collection_name} for sample {sample}",
+                sample = Sample::display_name(sample_name)
+            ))
+        })
+}
+
+fn graph_summary(conn: &Connection, block_group: &BlockGroup) -> GraphSummary {
+    let paths = GraphPath::query(
+        conn,
+        "select * from paths where block_group_id = ?1 order by id;",
+        params![block_group.id],
+    );
+    GraphSummary {
+        name: block_group.name.clone(),
+        sample: Sample::display_name(block_group.sample_name.as_deref()).to_string(),
+        is_circular: block_group.is_circular,
+        current_path: BlockGroup::try_get_current_path(conn, block_group.id).map(|path| path.name),
+        paths: paths
+            .iter()
+            .map(|path| PathSummary {
+                name: path.name.clone(),
+                length: path.length(conn),
+            })
+            .collect(),
+    }
+}
+
+pub fn graph_summaries(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+) -> Vec<GraphSummary> {
+    let mut block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    block_groups.sort_by(|a, b| a.name.cmp(&b.name));
+    block_groups
+        .iter()
+        .map(|block_group| graph_summary(conn, block_group))
+        .collect()
+}
+
+// The sequence of a path of a graph, the current one unless a path is named.
+pub fn path_sequence(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+    path_name: Option<&str>,
+) -> Result<SequenceResponse, ServerError> {
+    let block_group = get_graph(conn, collection_name, sample_name, graph_name)?;
+    let path = match path_name {
+        Some(path_name) => GraphPath::query(
+            conn,
+            "select * from paths where block_group_id = ?1 and name = ?2 order by id desc;",
+            params![block_group.id, path_name],
+        )
+        .into_iter()
+        .next(),
+        None => BlockGroup::try_get_current_path(conn, block_group.id),
+    }
+    .ok_or_else(|| ServerError::NotFound(format!("No path found in graph {graph_name}")))?;
+    let sequence = path.sequence(conn);
+    Ok(SequenceResponse {
+        name: path.name,
+        start: 0,
+        end: sequence.len() as i64,
+        sequence,
+    })
+}
+
+// The graph a region of a sample is on, with the range of its current path the region covers.
+// Regions are given the way they are everywhere else, such as chr1:101-200.
+fn find_region(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    region: &str,
+) -> Result<(BlockGroup, GraphPath, Range), ServerError> {
+    let block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    let graph_names = block_groups
+        .iter()
+        .map(|block_group| block_group.name.clone())
+        .collect::<Vec<String>>();
+    let region = parse_region(region, &graph_names)?;
+    let block_group = block_groups
+        .into_iter()
+        .find(|block_group| block_group.name == region.name)
+        .unwrap();
+    let (path, range) = BlockGroup::region_range(conn, &block_group, &region)?;
+    Ok((block_group, path, range))
+}
+
+pub fn region_sequence(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    region: &str,
+) -> Result<SequenceResponse, ServerError> {
+    let (block_group, path, range) = find_region(conn, collection_name, sample_name, region)?;
+    Ok(SequenceResponse {
+        name: block_group.name,
+        start: range.start,
+        end: range.end,
+        sequence: path.subsequence(conn, &range),
+    })
+}
+
+fn find_digest(conn: &Connection, checksum: &str) -> Result<SequenceDigest, ServerError> {
+    SequenceDigest::find(conn, checksum)
+        .ok_or_else(|| ServerError::NotFound(format!("No sequence with checksum {checksum}")))
+}
+
+fn sequence_length(conn: &Connection, sequence_hash: &str) -> Result<i64, ServerError> {
+    Ok(conn.query_row(
+        "select length from sequences where hash = ?1;",
+        params![sequence_hash],
+        |row| row.get(0),
+    )?)
+}
+
+pub fn refget_metadata(conn: &Connection, checksum: &str) -> Result<RefgetMetadata, ServerError> {
+    let digest = find_digest(conn, checksum)?;
+    Ok(RefgetMetadata {
+        id: digest.md5.clone(),
+        ga4gh: digest.ga4gh(),
+        length: sequence_length(conn, &digest.sequence_hash)?,
+        md5: digest.md5,
+        trunc512: digest.trunc512,
+        aliases: vec![],
+    })
+}
+
+/*
+   The bases of a sequence found by checksum, from start to end, 0-based and end exclusive, as the
+   refget protocol gives them. Ranges wrapping around the end of a sequence are for circular
+   sequences, which this server doesn't support.
+*/
+pub fn refget_sequence(
+    conn: &Connection,
+    checksum: &str,
+    start: Option<i64>,
+    end: Option<i64>,
+) -> Result<String, ServerError> {
+    let digest = find_digest(conn, checksum)?;
+    let length = sequence_length(conn, &digest.sequence_hash)?;
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(length);
+    if start < 0 || start >= length.max(1) || end > length {
+        return Err(ServerError::RangeNotSatisfiable(format!(
+            "Range {start}-{end} is outside of the sequence of length {length}"
+        )));
+    }
+    if start > end {
+        return Err(ServerError::NotImplemented(
+            "Circular ranges are not supported".to_string(),
+        ));
+    }
+    Ok(Sequence::sequence_slice(
+        conn,
+        &digest.sequence_hash,
+        start,
+        end,
+    ))
+}
+
+// Parses a Range header of one byte range, as bytes=first-last with both inclusive, into a start
+// and end like the start and end parameters of refget.
+fn parse_byte_range(range: &str) -> Result<(i64, i64), ServerError> {
+    let invalid = || ServerError::BadRequest(format!("Invalid range {range}"));
+    let (first, last) = range
+        .strip_prefix("bytes=")
+        .and_then(|range| range.split_once('-'))
+        .ok_or_else(invalid)?;
+    let first = first.trim().parse::<i64>().map_err(|_| invalid())?;
+    let last = last.trim().parse::<i64>().map_err(|_| invalid())?;
+    if first > last {
+        return Err(invalid());
+    }
+    Ok((first, last + 1))
+}
+
+fn graph_gfa(conn: &Connection, block_group: &BlockGroup) -> Result<String, ServerError> {
+    let directory = tempdir()?;
+    let gfa_path = directory.path().join("graph.gfa");
+    export_block_group_gfa(conn, block_group.id, &gfa_path);
+    Ok(fs::read_to_string(gfa_path)?)
+}
+
+// Runs a query on a connection of the pool, off of the async runtime as SQLite blocks.
+async fn with_connection<T, F>(pool: ConnectionPool, query: F) -> Result<T, ServerError>
+where
+    T: Send + 'static,
+    F: FnOnce(&PooledSqliteConnection) -> Result<T, ServerError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || query(&pool.get()?))
+        .await
+        .map_err(|err| ServerError::Io(io::Error::other(err)))?
+}
+
+async fn list_collections(
+    State(pool): State<ConnectionPool>,
+) -> Result<Json<Vec<String>>, ServerError> {
+    with_connection(pool, |conn| Ok(Json(collection_names(conn)))).await
+}
+
+async fn list_samples(
+    State(pool): State<ConnectionPool>,
+) -> Result<Json<Vec<String>>, ServerError> {
+    with_connection(pool, |conn| Ok(Json(sample_names(conn)))).await
+}
+
+async fn list_graphs(
+    State(pool): State<ConnectionPool>,
+    Path(collection_name): Path<String>,
+    Query(params): Query<SampleParams>,
+) -> Result<Json<Vec<GraphSummary>>, ServerError> {
+    with_connection(pool, move |conn| {
+        Ok(Json(graph_summaries(
+            conn,
+            &collection_name,
+            params.sample(),
+        )))
+    })
+    .await
+}
+
+async fn show_graph(
+    State(pool): State<ConnectionPool>,
+    Path((collection_name, graph_name)): Path<(String, String)>,
+    Query(params): Query<SampleParams>,
+) -> Result<Response, ServerError> {
+    with_connection(pool, move |conn| {
+        let block_group = get_graph(conn, &collection_name, params.sample(), &graph_name)?;
+        match params.format.as_deref() {
+            None | Some("json") => Ok(Json(graph_summary(conn, &block_group)).into_response()),
+            Some("gfa") => Ok((
+                [(header::CONTENT_TYPE, "text/plain")],
+                graph_gfa(conn, &block_group)?,
+            )
+                .into_response()),
+            Some(format) => Err(ServerError::BadRequest(format!(
+                "Unknown format {format}, expected json or gfa"
+            ))),
+        }
+    })
+    .await
+}
+
+async fn show_sequence(
+    State(pool): State<ConnectionPool>,
+    Path((collection_name, graph_name)): Path<(String, String)>,
+    Query(params): Query<SampleParams>,
+) -> Result<Json<SequenceResponse>, ServerError> {
+    with_connection(pool, move |conn| {
+        Ok(Json(path_sequence(
+            conn,
+            &collection_name,
+            params.sample(),
+            &graph_name,
+            params.path.as_deref(),
+        )?))
+    })
+    .await
+}
+
+async fn show_region(
+    State(pool): State<ConnectionPool>,
+    Path((collection_name, region)): Path<(String, String)>,
+    Query(params): Query<SampleParams>,
+) -> Result<Json<SequenceResponse>, ServerError> {
+    with_connection(pool, move |conn| {
+        Ok(Json(region_sequence(
+            conn,
+            &collection_name,
+            params.sample(),
+            &region,
+        )?))
+    })
+    .await
+}
+
+async fn refget_service_info() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "id": "gen.refget",
+        "name": "gen refget",
+        "type": {"group": "org.ga4gh", "artifact": "refget", "version": "2.0.0"},
+        "refget": {
+            "circular_supported": false,
+            "algorithms": ["md5", "ga4gh", "trunc512"],
+            "identifier_types": [],
+            "subsequence_limit": null,
+        },
+    }))
+}
+
+async fn show_refget_sequence(
+    State(pool): State<ConnectionPool>,
+    Path(checksum): Path<String>,
+    Query(params): Query<RefgetParams>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let range = headers
+        .get(header::RANGE)
+        .map(|range| {
+            range
+                .to_str()
+                .map_err(|_| ServerError::BadRequest("Invalid range".to_string()))
+                .and_then(parse_byte_range)
+        })
+        .transpose()?;
+    if range.is_some() && (params.start.is_some() || params.end.is_some()) {
+        return Err(ServerError::BadRequest(
+            "A range can't be given with start or end".to_string(),
+        ));
+    }
+    with_connection(pool, move |conn| match range {
+        Some((start, end)) => {
+            // the end of a byte range may go past the end of the sequence
+            let length = sequence_length(conn, &find_digest(conn, &checksum)?.sequence_hash)?;
+            let sequence = refget_sequence(conn, &checksum, Some(start), Some(end.min(length)))?;
+            Ok((
+                StatusCode::PARTIAL_CONTENT,
+                [(header::CONTENT_TYPE, REFGET_CONTENT_TYPE)],
+                sequence,
+            )
+                .into_response())
+        }
+        None => Ok((
+            [(header::CONTENT_TYPE, REFGET_CONTENT_TYPE)],
+            refget_sequence(conn, &checksum, params.start, params.end)?,
+        )
+            .into_response()),
+    })
+    .await
+}
+
+async fn show_refget_metadata(
+    State(pool): State<ConnectionPool>,
+    Path(checksum): Path<String>,
+) -> Result<Json<serde_json::Value>, ServerError> {
+    with_connection(pool, move |conn| {
+        Ok(Json(
+            serde_json::json!({"metadata": refget_metadata(conn, &checksum)?}),
+        ))
+    })
+    .await
+}
+
+// Sends what is written to it on to a streamed response, failing once the client has gone.
+struct ChannelWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/*
+   Streams a region as FASTA while it's read from the database, so regions of any size can be
+   fetched without the server holding their whole sequence. The region is checked before the
+   response starts, so invalid regions still get an error status.
+*/
+async fn stream_region(
+    State(pool): State<ConnectionPool>,
+    Path((collection_name, region)): Path<(String, String)>,
+    Query(params): Query<SampleParams>,
+) -> Result<Response, ServerError> {
+    let region_pool = pool.clone();
+    let (block_group, path, range) = with_connection(region_pool, move |conn| {
+        find_region(conn, &collection_name, params.sample(), &region)
+    })
+    .await?;
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFERS);
+    tokio::task::spawn_blocking(move || -> Result<(), ServerError> {
+        let conn = pool.get()?;
+        let mut writer = BufWriter::with_capacity(STREAM_BUFFER_BYTES, ChannelWriter(sender));
+        // writing stops early if the client disconnects, which isn't an error of the server
+        let _ = write_region_fasta(&conn, &block_group.name, &path, &range, &mut writer);
+        Ok(())
+    });
+    let body = Body::from_stream(stream::unfold(receiver, |mut receiver| async move {
+        receiver
+            .recv()
+            .await
+            .map(|bytes| (Ok::<_, io::Error>(bytes), receiver))
+    }));
+    Ok(([(header::CONTENT_TYPE, "text/x-fasta")], body).into_response())
+}
+
+/*
+   The routes of `gen serve`, a read-only HTTP API for browsing a repository without linking the
+   crate. Responses are JSON, except for graphs requested with ?format=gfa. Samples are given with
+   ?sample=, and the reference is used without one.
+
+     GET /collections
+     GET /samples
+     GET /collections/{collection}/graphs
+     GET /collections/{collection}/graphs/{graph}
+     GET /collections/{collection}/graphs/{graph}/sequence (?path= for a path other than the current)
+     GET /collections/{collection}/regions/{region}
+     GET /collections/{collection}/regions/{region}/fasta (streamed)
+
+   Sequences are also served by checksum following the GA4GH refget protocol, for genome browsers
+   and other refget clients. Checksums are MD5, TRUNC512 or GA4GH identifiers, and sequences can be
+   sliced with ?start=&end= or a Range header.
+
+     GET /sequence/service-info
+     GET /sequence/{checksum}
+     GET /sequence/{checksum}/metadata
+
+   Nothing is changed through the API. Like the other read-only commands, gen serve runs with cache
+   writes disabled, so reading a path doesn't store its index either.
+*/
+pub fn router(pool: ConnectionPool) -> Router {
+    Router::new()
+        .route("/collections", get(list_collections))
+        .route("/samples", get(list_samples))
+        .route("/collections/:collection/graphs", get(list_graphs))
+        .route("/collections/:collection/graphs/:graph", get(show_graph))
+        .route(
+            "/collections/:collection/graphs/:graph/sequence",
+            get(show_sequence),
+        )
+        .route("/collections/:collection/regions/:region", get(show_region))
+        .route(
+            "/collections/:collection/regions/:region/fasta",
+            get(stream_region),
+        )
+        .route("/sequence/service-info", get(refget_service_info))
+        .route("/sequence/:checksum", get(show_refget_sequence))
+        .route("/sequence/:checksum/metadata", get(show_refget_metadata))
+        .with_state(pool)
+}
+
+pub fn serve(pool: ConnectionPool, address: &str) -> io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(address).await?;
+        println!("Serving on http://{}", listener.local_addr()?);
+        axum::serve(listener, router(pool)).await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection_pool::get_connection_pool;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+
+    fn setup_repository(conn: &Connection) {
+        setup_gen_dir();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fixtures.join("simple.fa").to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            fixtures.join("aa.fa").to_str().unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_queries() {
+        let conn = &get_connection(None);
+        setup_repository(conn);
+
+        assert_eq!(collection_names(conn), vec!["test"]);
+        assert_eq!(sample_names(conn), vec!["(reference)", "child"]);
+        let graphs = graph_summaries(conn, "test", Some("child"));
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[0].name, "m123");
+        assert_eq!(graphs[0].sample, "child");
+        let current_path = graphs[0].paths.last().unwrap();
+        assert_eq!(current_path.length, 26);
+        assert_eq!(graphs[0].current_path, Some(current_path.name.clone()));
+
+        // a chosen current path is reported instead of the latest one
+        let block_group = get_graph(conn, "test", Some("child"), "m123").unwrap();
+        let first_path = BlockGroup::get_paths(conn, block_group.id)
+            .into_iter()
+            .next()
+            .unwrap();
+        BlockGroup::set_current_path(conn, block_group.id, first_path.id);
+        let graphs = graph_summaries(conn, "test", Some("child"));
+        assert_eq!(graphs[0].paths.len(), 2);
+        assert_eq!(graphs[0].current_path, Some(first_path.name.clone()));
+        assert_ne!(graphs[0].current_path, Some(current_path.name.clone()));
+
+        assert_eq!(
+            path_sequence(conn, "test", None, "m123", None)
+                .unwrap()
+                .sequence,
+            "ATCGATCGATCGATCGATCGGGAACACACAGAGA"
+        );
+        assert_eq!(
+            region_sequence(conn, "test", Some("child"), "m123:11-20").unwrap(),
+            SequenceResponse {
+                name: "m123".to_string(),
+                start: 10,
+                end: 20,
+                sequence: "CGATCAAACA".to_string(),
+            }
+        );
+        assert!(matches!(
+            region_sequence(conn, "test", None, "m12:1-5"),
+            Err(ServerError::Region(RegionError::UnknownContig { .. }))
+        ));
+        assert!(matches!(
+            path_sequence(conn, "test", None, "chr1", None),
+            Err(ServerError::NotFound(_))
+        ));
+
+        // digests are stored for imported sequences, though not for ones added by updates
+        let md5 = SequenceDigest::compute("", "ATCGATCGATCGATCGATCGGGAACACACAGAGA").md5;
+        let metadata = refget_metadata(conn, &md5).unwrap();
+        assert_eq!(metadata.length, 34);
+        assert_eq!(refget_metadata(conn, &metadata.ga4gh).unwrap(), metadata);
+        assert_eq!(
+            refget_sequence(conn, &md5, Some(20), Some(24)).unwrap(),
+            "GGAA"
+        );
+        assert_eq!(
+            refget_sequence(conn, &metadata.trunc512, Some(29), None).unwrap(),
+            "AGAGA"
+        );
+        assert!(matches!(
+            refget_sequence(conn, &md5, Some(10), Some(5)),
+            Err(ServerError::NotImplemented(_))
+        ));
+        assert!(matches!(
+            refget_sequence(conn, &md5, Some(30), Some(35)),
+            Err(ServerError::RangeNotSatisfiable(_))
+        ));
+        assert!(matches!(
+            refget_sequence(conn, "00000000000000000000000000000000", None, None),
+            Err(ServerError::NotFound(_))
+        ));
+        assert_eq!(parse_byte_range("bytes=0-3").unwrap(), (0, 4));
+        assert!(parse_byte_range("bytes=3-0").is_err());
+        assert!(parse_byte_range("0-3").is_err());
+
+        let block_group = get_graph(conn, "test", Some("child"), "m123").unwrap();
+        let gfa = graph_gfa(conn, &block_group).unwrap();
+        assert!(gfa.lines().any(|line| line.starts_with("S\t")));
+        assert!(gfa
+            .lines()
+            .any(|line| line.starts_with("P\t") && line.contains(".child\t")));
+    }
+
+    #[test]
+    fn test_serves_requests() {
+        let directory = tempdir().unwrap();
+        let db_path = directory.path().join("test.db");
+        let conn = get_connection(db_path.to_str().unwrap());
+        setup_repository(&conn);
+        let pool = get_connection_pool(db_path.to_str().unwrap(), 2);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        runtime.spawn(async move { axum::serve(listener, router(pool)).await });
+
+        let request_with_headers = |path: &str, headers: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "GET {path} HTTP/1.1\r\nHost: localhost\r\n{headers}Connection: close\r\n\r\n"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let request = |path: &str| request_with_headers(path, "");
+
+        let response = request("/collections/test/regions/m123:1-4?sample=child");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with(r#"{"name":"m123","start":0,"end":4,"sequence":"ATCG"}"#));
+
+        let response = request("/collections/test/regions/m123:11-20/fasta?sample=child");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("text/x-fasta"));
+        assert!(response.contains(">m123:11-20\nCGATCAAACA\n"));
+        let response = request("/collections/test/regions/m12:1-4/fasta");
+        assert!(response.starts_with("HTTP/1.1 400"));
+
+        let response = request("/collections/test/graphs/m123?format=gfa");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("\r\n\r\nS\t"));
+
+        let response = request("/collections/test/graphs/chr1");
+        assert!(response.starts_with("HTTP/1.1 404"));
+        assert!(response.contains(r#""error":"No graph chr1 in collection test"#));
+
+        let md5 = SequenceDigest::compute("", "ATCGATCGATCGATCGATCGGGAACACACAGAGA").md5;
+        let response = request(&format!("/sequence/{md5}?start=0&end=4"));
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("text/vnd.ga4gh.refget.v2.0.0+plain"));
+        assert!(response.ends_with("\r\n\r\nATCG"));
+
+        // byte ranges are inclusive and cut off at the end of the sequence
+        let response = request_with_headers(&format!("/sequence/{md5}"), "Range: bytes=29-40\r\n");
+        assert!(response.starts_with("HTTP/1.1 206"));
+        assert!(response.ends_with("\r\n\r\nAGAGA"));
+
+        let response =
+            request_with_headers(&format!("/sequence/{md5}?start=0"), "Range: bytes=0-3\r\n");
+        assert!(response.starts_with("HTTP/1.1 400"));
+        let response = request(&format!("/sequence/{md5}?start=40"));
+        assert!(response.starts_with("HTTP/1.1 416"));
+
+        let response = request(&format!("/sequence/{md5}/metadata"));
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(r#""length":34"#));
+
+        let response = request("/sequence/service-info");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(r#""circular_supported":false"#));
+    }
+}
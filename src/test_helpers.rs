@@ -24,6 +24,7 @@ use crate::models::sample::Sample;
 use crate::models::sequence::Sequence;
 use crate::models::strand::Strand;
 use crate::operation_management::{end_operation, start_operation};
+use crate::tuning::TuningProfile;
 
 pub fn get_connection<'a>(db_path: impl Into<Option<&'a str>>) -> Connection {
     let path: Option<&str> = db_path.into();
@@ -38,6 +39,7 @@ pub fn get_connection<'a>(db_path: impl Into<Option<&'a str>>) -> Connection {
             .unwrap_or_else(|_| panic!("Error opening in memory test db"));
     }
     rusqlite::vtab::array::load_module(&conn).unwrap();
+    TuningProfile::Safe.apply(&conn).unwrap();
     run_migrations(&mut conn);
     conn
 }
@@ -0,0 +1,197 @@
+use rusqlite::{Connection, OptionalExtension};
+use std::fmt;
+use std::str::FromStr;
+
+/*
+Connection settings for the two ways a database is used. Interactive commands read and write a
+little at a time and should not lose a committed operation if the machine goes down, while imports
+write millions of rows at once and are simply run again if they are interrupted.
+
+With WAL, synchronous = NORMAL only risks the last commits on a power loss (never corruption), and
+OFF leaves syncing to the OS entirely. A large page cache keeps the indexes being filled in memory
+instead of spilling them to the WAL mid-transaction, and memory mapping avoids copying pages on
+reads. The page size only applies to a database with nothing in it yet.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TuningProfile {
+    Bulk,
+    #[default]
+    Safe,
+}
+
+struct Settings {
+    page_size: i64,
+    synchronous: &'static str,
+    // negative sizes are in KiB rather than pages
+    cache_size: i64,
+    mmap_size: i64,
+    temp_store: &'static str,
+}
+
+impl TuningProfile {
+    fn settings(&self) -> Settings {
+        match self {
+            TuningProfile::Bulk => Settings {
+                page_size: 8192,
+                synchronous: "OFF",
+                cache_size: -512 * 1024,
+                mmap_size: 1 << 30,
+                temp_store: "MEMORY",
+            },
+            TuningProfile::Safe => Settings {
+                page_size: 4096,
+                synchronous: "NORMAL",
+                cache_size: 50000,
+                mmap_size: 256 << 20,
+                temp_store: "DEFAULT",
+            },
+        }
+    }
+
+    // Applies the profile to a connection. This has to happen outside of a transaction, and before
+    // anything is written to a new database for its page size to be used.
+    pub fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        let settings = self.settings();
+        conn.pragma_update(None, "page_size", settings.page_size)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.pragma_update(None, "synchronous", settings.synchronous)?;
+        conn.pragma_update(None, "cache_size", settings.cache_size)?;
+        conn.pragma_update(None, "mmap_size", settings.mmap_size)?;
+        conn.pragma_update(None, "temp_store", settings.temp_store)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for TuningProfile {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(match self {
+            TuningProfile::Bulk => "bulk",
+            TuningProfile::Safe => "safe",
+        })
+    }
+}
+
+impl FromStr for TuningProfile {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bulk" => Ok(TuningProfile::Bulk),
+            "safe" => Ok(TuningProfile::Safe),
+            _ => Err(format!("Invalid tuning profile {value}")),
+        }
+    }
+}
+
+/*
+Bulk settings for the duration of an import, whatever profile the connection was opened with.
+Importers usually run inside the command's transaction, where only the cache and memory map can
+change, and where syncing only happens once at the end anyway. Outside of one, every statement is
+its own commit and syncing is switched off as well. In memory databases have no memory map to size.
+The previous settings are put back when this is dropped.
+*/
+pub struct BulkSettings<'a> {
+    conn: &'a Connection,
+    synchronous: Option<i64>,
+    cache_size: i64,
+    mmap_size: Option<i64>,
+}
+
+impl<'a> BulkSettings<'a> {
+    pub fn apply(conn: &'a Connection) -> BulkSettings<'a> {
+        let get = |pragma: &str| -> Option<i64> {
+            conn.pragma_query_value(None, pragma, |row| row.get(0))
+                .optional()
+                .unwrap()
+        };
+        let previous = BulkSettings {
+            conn,
+            synchronous: get("synchronous").filter(|_| conn.is_autocommit()),
+            cache_size: get("cache_size").unwrap(),
+            mmap_size: get("mmap_size"),
+        };
+        let settings = TuningProfile::Bulk.settings();
+        if previous.synchronous.is_some() {
+            conn.pragma_update(None, "synchronous", settings.synchronous)
+                .unwrap();
+        }
+        conn.pragma_update(None, "cache_size", settings.cache_size)
+            .unwrap();
+        conn.pragma_update(None, "mmap_size", settings.mmap_size)
+            .unwrap();
+        previous
+    }
+}
+
+impl Drop for BulkSettings<'_> {
+    fn drop(&mut self) {
+        if let Some(synchronous) = self.synchronous {
+            let _ = self.conn.pragma_update(None, "synchronous", synchronous);
+        }
+        let _ = self.conn.pragma_update(None, "cache_size", self.cache_size);
+        if let Some(mmap_size) = self.mmap_size {
+            let _ = self.conn.pragma_update(None, "mmap_size", mmap_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_connection_with_profile;
+    use tempfile::tempdir;
+
+    fn pragma(conn: &Connection, name: &str) -> i64 {
+        conn.pragma_query_value(None, name, |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_applies_profiles() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bulk.db");
+        let conn = get_connection_with_profile(path.to_str().unwrap(), TuningProfile::Bulk);
+        assert_eq!(pragma(&conn, "page_size"), 8192);
+        assert_eq!(pragma(&conn, "synchronous"), 0);
+        let journal_mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        // the page size of an existing database stays the same
+        drop(conn);
+        let conn = get_connection_with_profile(path.to_str().unwrap(), TuningProfile::Safe);
+        assert_eq!(pragma(&conn, "page_size"), 8192);
+        assert_eq!(pragma(&conn, "synchronous"), 1);
+        assert_eq!(pragma(&conn, "cache_size"), 50000);
+
+        assert_eq!("bulk".parse(), Ok(TuningProfile::Bulk));
+        assert!("fast".parse::<TuningProfile>().is_err());
+    }
+
+    #[test]
+    fn test_restores_settings_after_bulk_writes() {
+        let dir = tempdir().unwrap();
+        let conn = get_connection_with_profile(
+            dir.path().join("safe.db").to_str().unwrap(),
+            TuningProfile::Safe,
+        );
+        {
+            let _bulk = BulkSettings::apply(&conn);
+            assert_eq!(pragma(&conn, "synchronous"), 0);
+            assert_eq!(pragma(&conn, "cache_size"), -512 * 1024);
+        }
+        assert_eq!(pragma(&conn, "synchronous"), 1);
+        assert_eq!(pragma(&conn, "cache_size"), 50000);
+
+        // the sync setting can't change within a transaction
+        conn.execute("BEGIN TRANSACTION", []).unwrap();
+        {
+            let _bulk = BulkSettings::apply(&conn);
+            assert_eq!(pragma(&conn, "synchronous"), 1);
+            assert_eq!(pragma(&conn, "cache_size"), -512 * 1024);
+        }
+        conn.execute("END TRANSACTION", []).unwrap();
+        assert_eq!(pragma(&conn, "cache_size"), 50000);
+    }
+}
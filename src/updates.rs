@@ -1,5 +1,11 @@
+pub mod edges;
 pub mod fasta;
 pub mod gaf;
 pub mod genbank;
 pub mod library;
+pub mod manifest;
+pub mod nodes;
+pub mod paths;
+pub mod recipe;
+pub mod samples;
 pub mod vcf;
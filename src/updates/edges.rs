@@ -0,0 +1,268 @@
+use crate::models::{
+    block_group_edge::{BlockGroupEdge, BlockGroupEdgeData},
+    edge::{Edge, EdgeData},
+    file_types::FileTypes,
+    node::Node,
+    operations::{Operation, OperationInfo},
+    sample::Sample,
+    strand::Strand,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use rusqlite::Connection;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EdgeError {
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("No graph named {0}")]
+    MissingGraph(String),
+    #[error("No node with id {0}")]
+    MissingNode(i64),
+    #[error("Coordinate {coordinate} is outside of node {node_id} (length {length})")]
+    OutOfBounds {
+        node_id: i64,
+        coordinate: i64,
+        length: i64,
+    },
+    #[error("Invalid edge: {0}")]
+    InvalidEdge(String),
+    #[error("Edge {0} is already in the graph")]
+    Duplicate(i64),
+}
+
+// Checks that an edge can join two nodes: the path start node can only be a source and the path end
+// node only a target, coordinates fall within the sequence of their node, strands are known and the
+// edge doesn't go into and out of a node at the same position.
+pub fn validate_edge(conn: &Connection, edge: &EdgeData) -> Result<(), EdgeError> {
+    if Node::is_end_node(edge.source_node_id) {
+        return Err(EdgeError::InvalidEdge(
+            "The path end node can't be the source of an edge".to_string(),
+        ));
+    }
+    if Node::is_start_node(edge.target_node_id) {
+        return Err(EdgeError::InvalidEdge(
+            "The path start node can't be the target of an edge".to_string(),
+        ));
+    }
+    for strand in [edge.source_strand, edge.target_strand] {
+        if !matches!(strand, Strand::Forward | Strand::Reverse) {
+            return Err(EdgeError::InvalidEdge(format!(
+                "Edges must be on the forward or reverse strand, not {strand}"
+            )));
+        }
+    }
+    if edge.source_node_id == edge.target_node_id
+        && edge.source_coordinate == edge.target_coordinate
+    {
+        return Err(EdgeError::InvalidEdge(
+            "An edge can't go into and out of a node at the same coordinate".to_string(),
+        ));
+    }
+
+    let sequences_by_node_id =
+        Node::get_sequences_by_node_ids(conn, &[edge.source_node_id, edge.target_node_id]);
+    for (node_id, coordinate) in [
+        (edge.source_node_id, edge.source_coordinate),
+        (edge.target_node_id, edge.target_coordinate),
+    ] {
+        if Node::is_terminal(node_id) {
+            continue;
+        }
+        let sequence = sequences_by_node_id
+            .get(&node_id)
+            .ok_or(EdgeError::MissingNode(node_id))?;
+        if coordinate < 0 || coordinate > sequence.length {
+            return Err(EdgeError::OutOfBounds {
+                node_id,
+                coordinate,
+                length: sequence.length,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Adds a single validated edge to a graph as its own operation. This is meant for building graph
+// structures by hand that no importer creates yet; the current path of the graph is unchanged.
+#[allow(clippy::too_many_arguments)]
+pub fn add_edge(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+    edge: &EdgeData,
+    chromosome_index: i64,
+    phased: i64,
+) -> Result<Operation, EdgeError> {
+    let block_group = Sample::get_block_groups(conn, collection_name, sample_name)
+        .into_iter()
+        .find(|block_group| block_group.name == graph_name)
+        .ok_or_else(|| EdgeError::MissingGraph(graph_name.to_string()))?;
+    validate_edge(conn, edge)?;
+    if let Some(existing) = BlockGroupEdge::edges_for_block_group(conn, block_group.id)
+        .into_iter()
+        .find(|augmented_edge| EdgeData::from(&augmented_edge.edge) == *edge)
+    {
+        return Err(EdgeError::Duplicate(existing.edge.id));
+    }
+
+    let mut session = start_operation(conn);
+    let edge_ids = Edge::bulk_create(conn, std::slice::from_ref(edge));
+    BlockGroupEdge::bulk_create(
+        conn,
+        &[BlockGroupEdgeData {
+            block_group_id: block_group.id,
+            edge_id: edge_ids[0],
+            chromosome_index,
+            phased,
+        }],
+    );
+    let summary_str = format!(
+        "{graph}: added edge {source}:{source_coordinate}{source_strand} -> {target}:{target_coordinate}{target_strand}",
+        graph = block_group.name,
+        source = edge.source_node_id,
+        source_coordinate = edge.source_coordinate,
+        source_strand = edge.source_strand,
+        target = edge.target_node_id,
+        target_coordinate = edge.target_coordinate,
+        target_strand = edge.target_strand,
+    );
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "add_edge".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::metadata;
+    use crate::models::node::{PATH_END_NODE_ID, PATH_START_NODE_ID};
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_adds_validated_edges() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let block_group = &Sample::get_block_groups(conn, "test", None)[0];
+        let node_id = BlockGroup::get_current_path(conn, block_group.id).blocks(conn)[1].node_id;
+
+        // a deletion of [10, 20)
+        let deletion = EdgeData {
+            source_node_id: node_id,
+            source_coordinate: 10,
+            source_strand: Strand::Forward,
+            target_node_id: node_id,
+            target_coordinate: 20,
+            target_strand: Strand::Forward,
+        };
+        add_edge(conn, op_conn, "test", None, "m123", &deletion, 0, 0).unwrap();
+        assert!(BlockGroup::get_all_sequences(conn, block_group.id, false)
+            .contains("ATCGATCGATGGAACACACAGAGA"));
+        assert!(matches!(
+            add_edge(conn, op_conn, "test", None, "m123", &deletion, 0, 0),
+            Err(EdgeError::Duplicate(_))
+        ));
+
+        assert_eq!(
+            add_edge(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "m123",
+                &EdgeData {
+                    target_coordinate: 35,
+                    ..deletion.clone()
+                },
+                0,
+                0
+            )
+            .unwrap_err(),
+            EdgeError::OutOfBounds {
+                node_id,
+                coordinate: 35,
+                length: 34
+            }
+        );
+        assert!(matches!(
+            add_edge(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "m123",
+                &EdgeData {
+                    source_node_id: PATH_END_NODE_ID,
+                    ..deletion.clone()
+                },
+                0,
+                0
+            ),
+            Err(EdgeError::InvalidEdge(_))
+        ));
+        assert!(matches!(
+            add_edge(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "m123",
+                &EdgeData {
+                    target_node_id: PATH_START_NODE_ID,
+                    ..deletion.clone()
+                },
+                0,
+                0
+            ),
+            Err(EdgeError::InvalidEdge(_))
+        ));
+        assert!(matches!(
+            add_edge(
+                conn,
+                op_conn,
+                "test",
+                None,
+                "m123",
+                &EdgeData {
+                    target_strand: Strand::Unknown,
+                    ..deletion.clone()
+                },
+                0,
+                0
+            ),
+            Err(EdgeError::InvalidEdge(_))
+        ));
+        assert_eq!(
+            add_edge(conn, op_conn, "test", None, "other", &deletion, 0, 0).unwrap_err(),
+            EdgeError::MissingGraph("other".to_string())
+        );
+    }
+}
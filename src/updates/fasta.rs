@@ -2,20 +2,211 @@ use noodles::fasta;
 use rusqlite;
 use rusqlite::{types::Value as SQLValue, Connection};
 use std::{io, str};
+use thiserror::Error;
 
 use crate::models::operations::OperationInfo;
 use crate::models::{
     block_group::{BlockGroup, PathChange},
+    collection::Collection,
     edge::Edge,
     file_types::FileTypes,
     node::Node,
-    path::PathBlock,
+    path::revcomp,
+    path::{Path, PathBlock},
     sample::Sample,
-    sequence::Sequence,
+    sequence::{Molecule, Sequence},
     strand::Strand,
     traits::*,
 };
-use crate::{calculate_hash, operation_management};
+use crate::operation_management;
+
+#[derive(Debug, Error)]
+pub enum FastaUpdateError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("No region found with name: {0}")]
+    RegionNotFound(String),
+    #[error("{start}-{end} is not a range of {region_name}, which is {length} bp long")]
+    InvalidRange {
+        region_name: String,
+        start: i64,
+        end: i64,
+        length: i64,
+    },
+    #[error("Expected to replace {expected} bp, but {start}-{end} is {actual} bp")]
+    ReplacedLengthMismatch {
+        start: i64,
+        end: i64,
+        expected: i64,
+        actual: i64,
+    },
+    #[error("Expected to remove {expected}, but {start}-{end} is {actual}{strand_note}",
+        strand_note = if *reverse_complement { " (the reverse complement of the expected sequence, check the strand)" } else { "" })]
+    RemovedSequenceMismatch {
+        start: i64,
+        end: i64,
+        expected: String,
+        actual: String,
+        reverse_complement: bool,
+    },
+}
+
+// What a fasta update will do to a region, worked out before anything is changed. Coordinates are
+// 0-based and end-exclusive like the update's, and the junctions are where the inserted sequence
+// starts and ends on the updated region.
+#[derive(Debug, PartialEq)]
+pub struct FastaUpdatePreview {
+    pub region_name: String,
+    pub start: i64,
+    pub end: i64,
+    pub removed_sequence: String,
+    pub inserted_sequence: String,
+    pub upstream_context: String,
+    pub downstream_context: String,
+    pub junctions: (i64, i64),
+    pub old_length: i64,
+    pub new_length: i64,
+}
+
+// The number of bases shown on either side of an edit
+const CONTEXT_LENGTH: i64 = 10;
+
+impl FastaUpdatePreview {
+    pub fn length_change(&self) -> i64 {
+        self.new_length - self.old_length
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        vec![
+            format!(
+                "{region}: replacing {start}-{end} ({removed} bp) with {inserted} bp",
+                region = self.region_name,
+                start = self.start,
+                end = self.end,
+                removed = self.removed_sequence.len(),
+                inserted = self.inserted_sequence.len()
+            ),
+            format!("  removed:   {}", self.removed_sequence),
+            format!("  inserted:  {}", self.inserted_sequence),
+            format!(
+                "  junctions: {upstream}..[{start}-{end}]..{downstream}",
+                upstream = self.upstream_context,
+                start = self.junctions.0,
+                end = self.junctions.1,
+                downstream = self.downstream_context
+            ),
+            format!(
+                "  length:    {old} -> {new} bp ({change:+})",
+                old = self.old_length,
+                new = self.new_length,
+                change = self.length_change()
+            ),
+        ]
+    }
+
+    // Checks the edit against what the caller expects it to replace. Sequences are compared
+    // ignoring case, and a removed sequence that is the reverse complement of the expected one is
+    // called out since it usually means coordinates were taken from the other strand.
+    pub fn check(
+        &self,
+        expected_replaced_length: Option<i64>,
+        expected_removed_sequence: Option<&str>,
+    ) -> Result<(), FastaUpdateError> {
+        let removed_length = self.removed_sequence.len() as i64;
+        if let Some(expected) = expected_replaced_length {
+            if expected != removed_length {
+                return Err(FastaUpdateError::ReplacedLengthMismatch {
+                    start: self.start,
+                    end: self.end,
+                    expected,
+                    actual: removed_length,
+                });
+            }
+        }
+        if let Some(expected) = expected_removed_sequence {
+            let expected = expected.to_uppercase();
+            let actual = self.removed_sequence.to_uppercase();
+            if expected != actual {
+                return Err(FastaUpdateError::RemovedSequenceMismatch {
+                    start: self.start,
+                    end: self.end,
+                    reverse_complement: revcomp(&expected) == actual,
+                    expected,
+                    actual: self.removed_sequence.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_first_sequence(fasta_file_path: &str) -> io::Result<String> {
+    let mut fasta_reader = fasta::io::reader::Builder.build_from_path(fasta_file_path)?;
+    // Assuming just one entry in the fasta file
+    let record = fasta_reader.records().next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "No records found in fasta file")
+    })??;
+    Ok(str::from_utf8(record.sequence().as_ref())
+        .unwrap()
+        .to_string())
+}
+
+// Works out what update_with_fasta would do to the current path of a region, without changing
+// anything. Like the update, this is the new sample's path if it already has the region and the
+// parent sample's otherwise.
+#[allow(clippy::too_many_arguments)]
+pub fn preview_fasta_update(
+    conn: &Connection,
+    collection_name: &str,
+    parent_sample_name: Option<&str>,
+    new_sample_name: &str,
+    region_name: &str,
+    start_coordinate: i64,
+    end_coordinate: i64,
+    fasta_file_path: &str,
+) -> Result<FastaUpdatePreview, FastaUpdateError> {
+    let find_region = |sample_name: Option<&str>| {
+        Sample::get_block_groups(conn, collection_name, sample_name)
+            .into_iter()
+            .find(|block_group| block_group.name == region_name)
+    };
+    let block_group = find_region(Some(new_sample_name))
+        .or_else(|| find_region(parent_sample_name))
+        .ok_or_else(|| FastaUpdateError::RegionNotFound(region_name.to_string()))?;
+    let path = BlockGroup::get_current_path(conn, block_group.id);
+    let old_length = path.length(conn);
+    if start_coordinate < 0 || start_coordinate > end_coordinate || end_coordinate > old_length {
+        return Err(FastaUpdateError::InvalidRange {
+            region_name: region_name.to_string(),
+            start: start_coordinate,
+            end: end_coordinate,
+            length: old_length,
+        });
+    }
+    let inserted_sequence = read_first_sequence(fasta_file_path)?;
+    let inserted_length = inserted_sequence.len() as i64;
+    let removed_length = end_coordinate - start_coordinate;
+    Ok(FastaUpdatePreview {
+        region_name: region_name.to_string(),
+        start: start_coordinate,
+        end: end_coordinate,
+        removed_sequence: path.sequence_range(conn, start_coordinate, end_coordinate),
+        upstream_context: path.sequence_range(
+            conn,
+            (start_coordinate - CONTEXT_LENGTH).max(0),
+            start_coordinate,
+        ),
+        downstream_context: path.sequence_range(
+            conn,
+            end_coordinate,
+            (end_coordinate + CONTEXT_LENGTH).min(old_length),
+        ),
+        junctions: (start_coordinate, start_coordinate + inserted_length),
+        old_length,
+        new_length: old_length - removed_length + inserted_length,
+        inserted_sequence,
+    })
+}
 
 #[allow(clippy::too_many_arguments)]
 pub fn update_with_fasta(
@@ -31,7 +222,49 @@ pub fn update_with_fasta(
 ) -> io::Result<()> {
     let mut session = operation_management::start_operation(conn);
 
-    let mut fasta_reader = fasta::io::reader::Builder.build_from_path(fasta_file_path)?;
+    let summary_str = apply_fasta_update(
+        conn,
+        collection_name,
+        parent_sample_name,
+        new_sample_name,
+        region_name,
+        start_coordinate,
+        end_coordinate,
+        fasta_file_path,
+    )?;
+    operation_management::end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: fasta_file_path.to_string(),
+            file_type: FileTypes::Fasta,
+            description: "fasta_update".to_string(),
+        },
+        &summary_str,
+        None,
+    )
+    .unwrap();
+
+    println!("Updated with fasta file: {}", fasta_file_path);
+
+    Ok(())
+}
+
+// Makes the changes of a fasta update without recording them as an operation, so several updates
+// can be recorded as one. Returns the summary of the changes.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_fasta_update(
+    conn: &Connection,
+    collection_name: &str,
+    parent_sample_name: Option<&str>,
+    new_sample_name: &str,
+    region_name: &str,
+    start_coordinate: i64,
+    end_coordinate: i64,
+    fasta_file_path: &str,
+) -> io::Result<String> {
+    let sequence = read_first_sequence(fasta_file_path)?;
 
     let _new_sample = Sample::get_or_create(conn, new_sample_name);
     let block_groups = Sample::get_block_groups(conn, collection_name, parent_sample_name);
@@ -56,35 +289,68 @@ pub fn update_with_fasta(
     }
 
     let path = BlockGroup::get_current_path(conn, new_block_group_id);
+    let sequence_type = path_sequence_type(conn, &path);
+    if let Some((position, character)) =
+        Molecule::from_sequence_type(&sequence_type).find_invalid(&sequence)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{region_name} is {sequence_type}, but the new sequence has {character} at position {position}"),
+        ));
+    }
 
-    // Assuming just one entry in the fasta file
-    let record = fasta_reader.records().next().ok_or_else(|| {
-        io::Error::new(io::ErrorKind::InvalidData, "No records found in fasta file")
-    })??;
+    let new_path = replace_path_range(
+        conn,
+        new_block_group_id,
+        &path,
+        start_coordinate,
+        end_coordinate,
+        &sequence,
+    );
 
-    let sequence = str::from_utf8(record.sequence().as_ref())
-        .unwrap()
-        .to_string();
+    Ok(format!(" {}: 1 change", new_path.name))
+}
+
+// The sequence type of the nodes a path goes through, so sequences put into it are of the same type.
+// Paths made by importing have one type, so the first node's is used.
+fn path_sequence_type(conn: &Connection, path: &Path) -> String {
+    path.blocks(conn)
+        .into_iter()
+        .find(|block| !Node::is_terminal(block.node_id))
+        .map(|block| {
+            Node::get_sequences_by_node_ids(conn, &[block.node_id])[&block.node_id]
+                .sequence_type
+                .clone()
+        })
+        .unwrap_or_else(|| "DNA".to_string())
+}
+
+// Replaces the bases from start to end of a path with a new sequence, adding a node for the sequence
+// and the edges around it to the block group, and returns the new path that goes through it.
+pub fn replace_path_range(
+    conn: &Connection,
+    block_group_id: i64,
+    path: &Path,
+    start_coordinate: i64,
+    end_coordinate: i64,
+    sequence: &str,
+) -> Path {
+    let collection_name = BlockGroup::get_by_id(conn, block_group_id).collection_name;
     let seq = Sequence::new()
-        .sequence_type("DNA")
-        .sequence(&sequence)
+        .sequence_type(&path_sequence_type(conn, path))
+        .sequence(sequence)
+        .store(Collection::sequence_store(conn, &collection_name))
         .save(conn);
     let node_id = Node::create(
         conn,
         &seq.hash,
-        calculate_hash(&format!(
-            "{path_id}:{ref_start}-{ref_end}->{sequence_hash}",
-            path_id = path.id,
-            ref_start = 0,
-            ref_end = seq.length,
-            sequence_hash = seq.hash
-        )),
+        Node::path_hash(&path.content_key(conn), 0, seq.length, &seq.hash),
     );
 
     let path_block = PathBlock {
         id: -1,
         node_id,
-        block_sequence: sequence,
+        block_sequence: sequence.to_string(),
         sequence_start: 0,
         sequence_end: seq.length,
         path_start: start_coordinate,
@@ -93,7 +359,7 @@ pub fn update_with_fasta(
     };
 
     let path_change = PathChange {
-        block_group_id: new_block_group_id,
+        block_group_id,
         path: path.clone(),
         path_accession: None,
         start: start_coordinate,
@@ -118,32 +384,13 @@ pub fn update_with_fasta(
         rusqlite::params!(SQLValue::from(node_id)),
     )[0]
     .clone();
-    let new_path = path.new_path_with(
+    path.new_path_with(
         conn,
         start_coordinate,
         end_coordinate,
         &edge_to_new_node,
         &edge_from_new_node,
-    );
-
-    let summary_str = format!(" {}: 1 change", new_path.name);
-    operation_management::end_operation(
-        conn,
-        operation_conn,
-        &mut session,
-        OperationInfo {
-            file_path: fasta_file_path.to_string(),
-            file_type: FileTypes::Fasta,
-            description: "fasta_update".to_string(),
-        },
-        &summary_str,
-        None,
     )
-    .unwrap();
-
-    println!("Updated with fasta file: {}", fasta_file_path);
-
-    Ok(())
 }
 
 #[cfg(test)]
@@ -588,4 +835,133 @@ mod tests {
             HashSet::from_iter(expected_sequences),
         );
     }
+
+    #[test]
+    fn test_previews_fasta_update() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        let preview = preview_fasta_update(
+            conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            preview,
+            FastaUpdatePreview {
+                region_name: "m123".to_string(),
+                start: 15,
+                end: 25,
+                removed_sequence: "GATCGGGAAC".to_string(),
+                inserted_sequence: "AAAAAAAA".to_string(),
+                upstream_context: "TCGATCGATC".to_string(),
+                downstream_context: "ACACAGAGA".to_string(),
+                junctions: (15, 23),
+                old_length: 34,
+                new_length: 32,
+            }
+        );
+        assert_eq!(preview.length_change(), -2);
+        assert_eq!(
+            preview.describe()[3],
+            "  junctions: TCGATCGATC..[15-23]..ACACAGAGA"
+        );
+
+        assert!(preview.check(Some(10), Some("gatcgggaac")).is_ok());
+        assert!(matches!(
+            preview.check(Some(9), None),
+            Err(FastaUpdateError::ReplacedLengthMismatch {
+                expected: 9,
+                actual: 10,
+                ..
+            })
+        ));
+        assert!(matches!(
+            preview.check(None, Some("ATCGGGAACA")),
+            Err(FastaUpdateError::RemovedSequenceMismatch {
+                reverse_complement: false,
+                ..
+            })
+        ));
+        assert!(matches!(
+            preview.check(None, Some("GTTCCCGATC")),
+            Err(FastaUpdateError::RemovedSequenceMismatch {
+                reverse_complement: true,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            preview_fasta_update(
+                conn,
+                "test",
+                None,
+                "child",
+                "m123",
+                30,
+                40,
+                insert_path.to_str().unwrap(),
+            ),
+            Err(FastaUpdateError::InvalidRange { length: 34, .. })
+        ));
+        assert!(matches!(
+            preview_fasta_update(
+                conn,
+                "test",
+                None,
+                "child",
+                "chr1",
+                0,
+                1,
+                insert_path.to_str().unwrap(),
+            ),
+            Err(FastaUpdateError::RegionNotFound(_))
+        ));
+
+        // once the new sample has the region, further edits are previewed against it
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        let preview = preview_fasta_update(
+            conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            23,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(preview.removed_sequence, "AAAAAAAA");
+        assert_eq!(preview.old_length, 32);
+    }
 }
@@ -4,12 +4,15 @@ use crate::models::block_group::BlockGroup;
 use crate::models::block_group_edge::{BlockGroupEdge, BlockGroupEdgeData};
 use crate::models::edge::{Edge, EdgeData};
 use crate::models::file_types::FileTypes;
+use crate::models::metadata;
 use crate::models::node::{Node, PATH_END_NODE_ID, PATH_START_NODE_ID};
-use crate::models::operations::OperationInfo;
+use crate::models::operations::{GfaExport, OperationInfo};
 use crate::models::sample::Sample;
 use crate::models::sequence::Sequence;
 use crate::models::strand::Strand;
 use crate::models::traits::*;
+use crate::operation_management::OperationError;
+use crate::progress_bar::{report_step, NoProgress, ProgressReporter};
 use crate::{operation_management, read_lines};
 use regex::Regex;
 use rusqlite::types::Value;
@@ -72,30 +75,113 @@ pub fn update_with_gaf<'a, P>(
     parent_sample: impl Into<Option<&'a str>>,
 ) where
     P: AsRef<Path> + Clone,
+{
+    update_with_gaf_with_progress(
+        conn,
+        op_conn,
+        gaf_path,
+        csv_path,
+        collection_name,
+        sample_name,
+        parent_sample,
+        &NoProgress,
+    )
+    .expect("updates without a reporter aren't cancelled");
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_with_gaf_with_progress<'a, P>(
+    conn: &Connection,
+    op_conn: &Connection,
+    gaf_path: P,
+    csv_path: P,
+    collection_name: &'a str,
+    sample_name: impl Into<Option<&'a str>>,
+    parent_sample: impl Into<Option<&'a str>>,
+    progress: &dyn ProgressReporter,
+) -> Result<(), OperationError>
+where
+    P: AsRef<Path> + Clone,
 {
     // Given a gaf, this will incorporate the alignment into the specified graph, creating new nodes.
 
     let mut session = operation_management::start_operation(conn);
 
+    let change_count = apply_gaf_changes(
+        conn,
+        op_conn,
+        gaf_path.clone(),
+        csv_path,
+        collection_name,
+        sample_name,
+        parent_sample,
+        progress,
+    )?;
+
+    operation_management::end_operation(
+        conn,
+        op_conn,
+        &mut session,
+        OperationInfo {
+            file_path: gaf_path.as_ref().to_str().unwrap().to_string(),
+            file_type: FileTypes::GAF,
+            description: "insert_via_gaf".to_string(),
+        },
+        &format!("{change_count} updates."),
+        None,
+    )
+    .unwrap();
+    Ok(())
+}
+
+// Makes the changes of a GAF update without recording them as an operation, so several updates can
+// be recorded as one. Returns the number of changes.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_gaf_changes<'a, P>(
+    conn: &Connection,
+    op_conn: &Connection,
+    gaf_path: P,
+    csv_path: P,
+    collection_name: &'a str,
+    sample_name: impl Into<Option<&'a str>>,
+    parent_sample: impl Into<Option<&'a str>>,
+    progress: &dyn ProgressReporter,
+) -> Result<usize, OperationError>
+where
+    P: AsRef<Path> + Clone,
+{
     let parent_sample = parent_sample.into();
     let sample_name = sample_name
         .into()
         .map(|name| Sample::get_or_create_child(conn, collection_name, name, parent_sample).name);
 
-    let mut node_lengths: HashMap<String, (i64, i64)> = HashMap::new();
-
-    let mut get_node_info = |node_id: &str| -> (i64, i64) {
-        *node_lengths.entry(node_id.to_string()).or_insert_with(|| {
-            let node_info : Vec<&str> = node_id.rsplitn(2, '.').collect();
-            let node_id = *node_info.last().unwrap();
-            let id = node_id.parse::<i64>().unwrap();
+    let db_uuid = metadata::get_db_uuid(conn);
+    let mut segment_info: HashMap<String, (i64, i64, i64)> = HashMap::new();
+
+    // Resolves a segment ID to its node, the start of the segment on the node, and the segment's
+    // length. Segments of GFA exports are resolved through the recorded export. Other segment IDs
+    // are read like our GFA export encodes them, as node_id.sequence_start, with the segment
+    // running to the end of the node.
+    let mut get_node_info = |segment_id: &str| -> (i64, i64, i64) {
+        *segment_info.entry(segment_id.to_string()).or_insert_with(|| {
+            if let Some(segment) = GfaExport::get_segment(op_conn, &db_uuid, segment_id) {
+                return (
+                    segment.node_id,
+                    segment.sequence_start,
+                    segment.sequence_end - segment.sequence_start,
+                );
+            }
+            let (id, start) = match segment_id.rsplit_once('.') {
+                Some((id, start)) => (id, start.parse::<i64>().unwrap_or(0)),
+                None => (segment_id, 0),
+            };
+            let id = id.parse::<i64>().unwrap();
             let mut stmt = conn.prepare_cached("select s.length from nodes n left join sequences s on (s.hash = n.sequence_hash) where n.id = ?1;").unwrap();
-            let res = stmt.query_row([id], |row| row.get(0)).unwrap();
-            (id, res)
+            let node_length: i64 = stmt.query_row([id], |row| row.get(0)).unwrap();
+            (id, start, node_length - start)
         })
     };
 
-    // our GFA export encodes segments like node_id.sequence_start
     let re = Regex::new(
         r"(?x)
         ^
@@ -162,7 +248,8 @@ pub fn update_with_gaf<'a, P>(
     let mut gaf_changes: HashMap<String, HashMap<String, (i64, Strand, i64)>> = HashMap::new();
 
     if let Ok(lines) = read_lines(&gaf_path) {
-        for line in lines.map_while(Result::ok) {
+        for (index, line) in lines.map_while(Result::ok).enumerate() {
+            report_step(progress, "Alignments read.", index as u64, None)?;
             let entry = re.captures(&line).unwrap();
             let aln_path = &entry["path"];
             let mut node_start: i64 = entry["path_start"].parse::<i64>().unwrap();
@@ -193,21 +280,24 @@ pub fn update_with_gaf<'a, P>(
                         query_key = "left";
                         let mut matches = entry["residue_match"].parse::<i64>().unwrap();
                         for (segment_strand, segment_id) in segments.iter() {
-                            let (segment_node_id, node_length) = get_node_info(segment_id);
-                            if node_length >= matches {
+                            let (segment_node_id, segment_start, segment_length) =
+                                get_node_info(segment_id);
+                            if segment_length >= matches {
                                 strand = Some(*segment_strand);
                                 node_id = Some(segment_node_id);
-                                node_start = matches;
+                                node_start = segment_start + matches;
                                 break;
                             }
-                            matches -= node_length;
+                            matches -= segment_length;
                         }
                     } else if query.ends_with("right") {
                         query_key = "right";
                         let (segment_strand, segment_id) = segments.first().unwrap();
-                        let (segment_node_id, _node_length) = get_node_info(segment_id);
+                        let (segment_node_id, segment_start, _segment_length) =
+                            get_node_info(segment_id);
                         strand = Some(*segment_strand);
                         node_id = Some(segment_node_id);
+                        node_start += segment_start;
                     } else {
                         continue;
                     };
@@ -237,7 +327,13 @@ pub fn update_with_gaf<'a, P>(
     }
 
     let mut change_count = 0;
-    for (path_id, path_changes) in gaf_changes.iter() {
+    for (index, (path_id, path_changes)) in gaf_changes.iter().enumerate() {
+        report_step(
+            progress,
+            "Changes applied.",
+            index as u64,
+            Some(gaf_changes.len() as u64),
+        )?;
         if let Some(change) = change_spec.get(path_id) {
             change_count += 1;
             let sequence = Sequence::new()
@@ -352,32 +448,26 @@ pub fn update_with_gaf<'a, P>(
         }
     }
 
-    operation_management::end_operation(
-        conn,
-        op_conn,
-        &mut session,
-        OperationInfo {
-            file_path: gaf_path.as_ref().to_str().unwrap().to_string(),
-            file_type: FileTypes::GAF,
-            description: "insert_via_gaf".to_string(),
-        },
-        &format!("{change_count} updates."),
-        None,
-    )
-    .unwrap();
+    Ok(change_count)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exports::gfa::export_gfa;
     use crate::graph::{GraphEdge, GraphNode};
+    use crate::graph_order::GraphOrder;
+    use crate::imports::fasta::import_fasta;
     use crate::imports::gfa::import_gfa;
     use crate::models::metadata;
     use crate::models::operations::setup_db;
     use crate::models::traits::Query;
     use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
     use petgraph::Direction;
+    use std::fs;
     use std::path::PathBuf;
+    use tempfile::tempdir;
 
     mod test_transform {
         use super::*;
@@ -550,4 +640,127 @@ mod tests {
         // This checks that we have an outgoing edge from the end of the old graph to our insert
         assert_eq!(edges[1].1.node_id, insert_node_id);
     }
+
+    #[test]
+    fn test_resolves_segments_through_export() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = &metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, db_uuid);
+        let fixture = |name: &str| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(name)
+        };
+        import_fasta(
+            &fixture("simple.fa").to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // inserts AA at 15 of the 34 bp reference node
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "child",
+            "m123",
+            15,
+            15,
+            fixture("aa.fa").to_str().unwrap(),
+        )
+        .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let gfa_path = temp_dir.path().join("child.gfa");
+        let segments = export_gfa(
+            conn,
+            "test",
+            &gfa_path,
+            Some("child".to_string()),
+            &GraphOrder::Natural,
+        );
+        GfaExport::record(
+            op_conn,
+            db_uuid,
+            "test",
+            gfa_path.to_str().unwrap(),
+            &segments,
+        );
+        let reference_segment = segments
+            .iter()
+            .find(|segment| segment.sequence_start == 15)
+            .unwrap();
+        let reference_node_id = reference_segment.node_id;
+        let insert_segment = segments
+            .iter()
+            .find(|segment| segment.node_id != reference_node_id)
+            .unwrap();
+
+        // the left flank runs through the first reference segment and the insertion and ends 3 bp
+        // into the second reference segment, at 18 of the reference node
+        let gaf_path = temp_dir.path().join("alignments.gaf");
+        fs::write(
+            &gaf_path,
+            format!(
+                "ins_left\t20\t0\t20\t+\t>{reference_node_id}.0>{insert}>{reference}\t36\t0\t20\t20\t20\t60\tNM:i:0\tcg:Z:20M\n\
+                 ins_right\t5\t0\t5\t+\t>{reference}\t19\t10\t15\t5\t5\t60\tNM:i:0\tcg:Z:5M\n",
+                insert = insert_segment.segment_id,
+                reference = reference_segment.segment_id,
+            ),
+        )
+        .unwrap();
+        let csv_path = temp_dir.path().join("changes.csv");
+        fs::write(
+            &csv_path,
+            "id,left,sequence,right\nins,ATCGATCGATCGATCAATCG,TTTT,TCGGG\n",
+        )
+        .unwrap();
+
+        update_with_gaf(
+            conn,
+            op_conn,
+            &gaf_path,
+            &csv_path,
+            "test",
+            "gaf child",
+            "child",
+        );
+
+        let insert_node_id = Node::query(
+            conn,
+            "select n.* from nodes n join sequences s on (n.sequence_hash = s.hash) where s.sequence = 'TTTT'",
+            params![],
+        )[0]
+        .id;
+        let mut edges = Edge::query(
+            conn,
+            "select * from edges where source_node_id = ?1 or target_node_id = ?1",
+            params![insert_node_id],
+        )
+        .iter()
+        .map(|edge| {
+            (
+                edge.source_node_id,
+                edge.source_coordinate,
+                edge.target_node_id,
+                edge.target_coordinate,
+            )
+        })
+        .collect::<Vec<_>>();
+        // the edge into the insertion, then the edge out of it
+        edges.sort_by_key(|edge| edge.0 == insert_node_id);
+        assert_eq!(
+            edges,
+            vec![
+                (reference_node_id, 18, insert_node_id, 0),
+                (insert_node_id, 4, reference_node_id, 25),
+            ]
+        );
+    }
 }
@@ -61,7 +61,7 @@ where
                         if !create_missing {
                             return Err(GenBankError::LookupError(format!("No block group named {contig} exists. Try importing first or pass --create-missing.", contig=&locus.name)));
                         }
-                        BlockGroup::create(conn, &collection.name, None, &locus.name)
+                        BlockGroup::create_with_topology(conn, &collection.name, None, &locus.name, locus.is_circular)
                     };
                 let paths = Path::query(
                     conn,
@@ -2,11 +2,12 @@ use csv;
 use itertools::Itertools;
 use noodles::fasta;
 use rusqlite::Connection;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::BufReader;
 use std::str;
 
+use crate::models::accession::Accession;
 use crate::models::block_group::BlockGroup;
 use crate::models::block_group_edge::{BlockGroupEdge, BlockGroupEdgeData};
 use crate::models::edge::{Edge, EdgeData};
@@ -18,6 +19,272 @@ use crate::models::sequence::Sequence;
 use crate::models::strand::Strand;
 use crate::{calculate_hash, operation_management};
 
+pub(crate) fn read_parts(parts_file_path: &str) -> std::io::Result<Vec<(String, String)>> {
+    let mut parts_reader = fasta::io::reader::Builder.build_from_path(parts_file_path)?;
+    let mut parts = vec![];
+    for result in parts_reader.records() {
+        let record = result?;
+        let sequence = str::from_utf8(record.sequence().as_ref())
+            .unwrap()
+            .to_string();
+        let name = String::from_utf8(record.name().to_vec()).unwrap();
+        parts.push((name, sequence));
+    }
+    Ok(parts)
+}
+
+// Each column of a library file is a slot in the design and each row lists an option for the
+// slots, so a column with a single part is a fixed segment. Returns the part names of each slot.
+pub(crate) fn read_library_slots(library_file_path: &str) -> std::io::Result<Vec<Vec<String>>> {
+    let library_file = File::open(library_file_path)?;
+    let library_reader = BufReader::new(library_file);
+
+    let mut parts_by_index: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut library_csv_reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(library_reader);
+    let mut max_index = 0;
+    for result in library_csv_reader.records() {
+        let record = result?;
+        for (index, part) in record.iter().enumerate() {
+            if !part.is_empty() {
+                parts_by_index
+                    .entry(index)
+                    .or_default()
+                    .push(part.to_string());
+                if index >= max_index {
+                    max_index = index + 1;
+                }
+            }
+        }
+    }
+
+    let mut slots = vec![];
+    for index in 0..max_index {
+        slots.push(parts_by_index.remove(&index).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Column {} of {library_file_path} has no parts", index + 1),
+            )
+        })?);
+    }
+    Ok(slots)
+}
+
+// A constraint on the combinations of a library: no combination has both parts of a forbidden
+// pair, and every combination with the first part of a required pair has the second as well.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PartConstraint {
+    Forbidden(String, String),
+    Required(String, String),
+}
+
+impl PartConstraint {
+    pub fn parts(&self) -> (&str, &str) {
+        match self {
+            PartConstraint::Forbidden(first, second) | PartConstraint::Required(first, second) => {
+                (first, second)
+            }
+        }
+    }
+
+    fn involves(&self, part: &str) -> bool {
+        let (first, second) = self.parts();
+        first == part || second == part
+    }
+
+    fn allows(&self, chosen: &BTreeSet<String>) -> bool {
+        match self {
+            PartConstraint::Forbidden(first, second) => {
+                !(chosen.contains(first) && chosen.contains(second))
+            }
+            PartConstraint::Required(first, second) => {
+                !chosen.contains(first) || chosen.contains(second)
+            }
+        }
+    }
+}
+
+// Each row of a constraints file is forbid or require followed by two part names, e.g.
+// "forbid,p1,cds2" or "require,p2,cds2".
+pub(crate) fn read_constraints(
+    constraints_file_path: &str,
+) -> std::io::Result<Vec<PartConstraint>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(BufReader::new(File::open(constraints_file_path)?));
+    let mut constraints = vec![];
+    for result in reader.records() {
+        let record = result?;
+        let constraint = match (record.get(0), record.get(1), record.get(2)) {
+            (Some("forbid"), Some(first), Some(second)) => {
+                PartConstraint::Forbidden(first.to_string(), second.to_string())
+            }
+            (Some("require"), Some(first), Some(second)) => {
+                PartConstraint::Required(first.to_string(), second.to_string())
+            }
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid constraint {row} in {constraints_file_path}, expected forbid or require and two parts",
+                        row = record.iter().join(",")
+                    ),
+                ))
+            }
+        };
+        constraints.push(constraint);
+    }
+    Ok(constraints)
+}
+
+fn read_optional_constraints(
+    constraints_file_path: Option<&str>,
+) -> std::io::Result<Vec<PartConstraint>> {
+    constraints_file_path.map_or(Ok(vec![]), read_constraints)
+}
+
+// A part of a library in one of its compatibility classes, given by the parts chosen before it that
+// constraints still to be decided depend on. Without constraints each part has a single class.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PartClass {
+    part: String,
+    context: BTreeSet<String>,
+}
+
+/*
+   The combinations of a library that satisfy its constraints, as a graph with a layer of part
+   classes per slot and the links between classes of consecutive slots. A constraint is decided at
+   the last slot either of its parts is in, so classes carry the parts chosen earlier until every
+   constraint on them is decided, and a part chosen after different parts is copied into a class for
+   each. Every path from the first layer to the last is then a valid combination, and classes that
+   can't reach the last layer are left out.
+*/
+#[derive(Debug)]
+struct LibraryGraph {
+    layers: Vec<Vec<PartClass>>,
+    links: Vec<Vec<(usize, usize)>>,
+}
+
+impl LibraryGraph {
+    fn new(slots: &[Vec<String>], constraints: &[PartConstraint]) -> LibraryGraph {
+        let last_slot = |part: &str| {
+            slots
+                .iter()
+                .rposition(|slot| slot.iter().any(|p| p == part))
+        };
+        let decided_at = constraints
+            .iter()
+            .map(|constraint| {
+                let (first, second) = constraint.parts();
+                last_slot(first).max(last_slot(second))
+            })
+            .collect::<Vec<Option<usize>>>();
+
+        let mut layers: Vec<Vec<PartClass>> = vec![];
+        let mut links = vec![];
+        for (index, slot) in slots.iter().enumerate() {
+            let contexts = match layers.last() {
+                Some(previous_layer) => previous_layer
+                    .iter()
+                    .map(|class| class.context.clone())
+                    .collect(),
+                None => vec![BTreeSet::new()],
+            };
+            let mut layer: Vec<PartClass> = vec![];
+            let mut layer_links = vec![];
+            for (previous_index, context) in contexts.iter().enumerate() {
+                for part in slot {
+                    let mut chosen = context.clone();
+                    chosen.insert(part.clone());
+                    let allowed = constraints
+                        .iter()
+                        .zip(decided_at.iter())
+                        .filter(|(_, decided_at)| **decided_at == Some(index))
+                        .all(|(constraint, _)| constraint.allows(&chosen));
+                    if !allowed {
+                        continue;
+                    }
+                    let class = PartClass {
+                        part: part.clone(),
+                        context: chosen
+                            .into_iter()
+                            .filter(|chosen_part| {
+                                constraints.iter().zip(decided_at.iter()).any(
+                                    |(constraint, decided_at)| {
+                                        decided_at.is_some_and(|decided_at| decided_at > index)
+                                            && constraint.involves(chosen_part)
+                                    },
+                                )
+                            })
+                            .collect(),
+                    };
+                    let class_index = match layer.iter().position(|existing| *existing == class) {
+                        Some(class_index) => class_index,
+                        None => {
+                            layer.push(class);
+                            layer.len() - 1
+                        }
+                    };
+                    if index > 0 && !layer_links.contains(&(previous_index, class_index)) {
+                        layer_links.push((previous_index, class_index));
+                    }
+                }
+            }
+            if index > 0 {
+                links.push(layer_links);
+            }
+            layers.push(layer);
+        }
+
+        // Leave out the classes with no way to the last slot, last layer first
+        for index in (0..links.len()).rev() {
+            let linked = links[index]
+                .iter()
+                .map(|(class, _)| *class)
+                .collect::<HashSet<usize>>();
+            let kept = (0..layers[index].len())
+                .filter(|class| linked.contains(class))
+                .collect::<Vec<usize>>();
+            layers[index] = kept
+                .iter()
+                .map(|class| layers[index][*class].clone())
+                .collect();
+            links[index] = links[index]
+                .iter()
+                .map(|(class, next_class)| {
+                    (
+                        kept.iter().position(|kept| kept == class).unwrap(),
+                        *next_class,
+                    )
+                })
+                .collect();
+            if index > 0 {
+                links[index - 1].retain(|(_, class)| kept.contains(class));
+                for (_, class) in links[index - 1].iter_mut() {
+                    *class = kept.iter().position(|kept| kept == class).unwrap();
+                }
+            }
+        }
+        LibraryGraph { layers, links }
+    }
+
+    fn combination_count(&self) -> usize {
+        let Some(first_layer) = self.layers.first() else {
+            return 0;
+        };
+        let mut counts = vec![1; first_layer.len()];
+        for (index, links) in self.links.iter().enumerate() {
+            let mut next_counts = vec![0; self.layers[index + 1].len()];
+            for (class, next_class) in links {
+                next_counts[*next_class] += counts[*class];
+            }
+            counts = next_counts;
+        }
+        counts.iter().sum()
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn update_with_library(
     conn: &Connection,
@@ -30,11 +297,98 @@ pub fn update_with_library(
     end_coordinate: i64,
     parts_file_path: &str,
     library_file_path: &str,
+    constraints_file_path: Option<&str>,
 ) -> std::io::Result<()> {
+    let parts = read_parts(parts_file_path)?;
+    let constraints = read_optional_constraints(constraints_file_path)?;
     let mut session = operation_management::start_operation(conn);
 
-    let mut parts_reader = fasta::io::reader::Builder.build_from_path(parts_file_path)?;
+    let summary_str = apply_library_update(
+        conn,
+        collection_name,
+        parent_sample_name,
+        new_sample_name,
+        region_name,
+        start_coordinate,
+        end_coordinate,
+        &parts,
+        library_file_path,
+        &constraints,
+    )?;
+    operation_management::end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: library_file_path.to_string(),
+            file_type: FileTypes::CSV,
+            description: "library_csv_update".to_string(),
+        },
+        &summary_str,
+        None,
+    )
+    .unwrap();
+
+    println!("Updated with library file: {}", library_file_path);
+
+    Ok(())
+}
+
+// The accessions of a collection that a library design names as parts, by name. Accessions the
+// design doesn't use are left out, so no nodes are made for them.
+pub(crate) fn accession_parts(
+    conn: &Connection,
+    collection_name: &str,
+    library_file_path: &str,
+) -> std::io::Result<Vec<(String, String)>> {
+    let part_names = read_library_slots(library_file_path)?
+        .into_iter()
+        .flatten()
+        .collect::<HashSet<String>>();
+    // Sample block groups inherit the accessions of their parents, so the same accession usually
+    // appears several times. That is only a problem if the copies differ in sequence.
+    let mut sequences_by_name: HashMap<String, String> = HashMap::new();
+    for accession in Accession::query_for_collection(conn, collection_name) {
+        if !part_names.contains(&accession.name) {
+            continue;
+        }
+        let sequence = accession.sequence(conn);
+        match sequences_by_name.get(&accession.name) {
+            Some(existing_sequence) if *existing_sequence != sequence => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Accession {} has different sequences in collection {}",
+                        accession.name, collection_name
+                    ),
+                ));
+            }
+            _ => {
+                sequences_by_name.insert(accession.name, sequence);
+            }
+        }
+    }
+    Ok(sequences_by_name
+        .into_iter()
+        .sorted()
+        .collect::<Vec<(String, String)>>())
+}
 
+// Adds the parts of a library between two coordinates of a region without recording an operation,
+// so several updates can be recorded as one. Returns the summary of the changes.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_library_update(
+    conn: &Connection,
+    collection_name: &str,
+    parent_sample_name: Option<&str>,
+    new_sample_name: &str,
+    region_name: &str,
+    start_coordinate: i64,
+    end_coordinate: i64,
+    parts: &[(String, String)],
+    library_file_path: &str,
+    constraints: &[PartConstraint],
+) -> std::io::Result<String> {
     let _new_sample = Sample::create(conn, new_sample_name);
     let block_groups = Sample::get_block_groups(conn, collection_name, parent_sample_name);
 
@@ -57,59 +411,85 @@ pub fn update_with_library(
         panic!("No region found with name: {}", region_name);
     }
     let path = BlockGroup::get_current_path(conn, new_block_group_id);
+    let path_key = path.content_key(conn);
 
     let mut node_ids_by_name = HashMap::new();
     let mut sequence_lengths_by_node_id = HashMap::new();
-    for result in parts_reader.records() {
-        let record = result?;
-        let sequence = str::from_utf8(record.sequence().as_ref())
-            .unwrap()
-            .to_string();
-        let name = String::from_utf8(record.name().to_vec()).unwrap();
+    let mut sequence_hashes_by_node_id = HashMap::new();
+    for (name, sequence) in parts {
         let seq = Sequence::new()
             .sequence_type("DNA")
-            .sequence(&sequence)
+            .sequence(sequence)
             .save(conn);
         let node_id = Node::create(
             conn,
             &seq.hash,
-            calculate_hash(&format!(
-                "{path_id}:{ref_start}-{ref_end}->{sequence_hash}",
-                path_id = path.id,
-                ref_start = 0,
-                ref_end = seq.length,
-                sequence_hash = seq.hash
-            )),
+            Node::path_hash(&path_key, 0, seq.length, &seq.hash),
         );
 
-        node_ids_by_name.insert(name, node_id);
+        node_ids_by_name.insert(name.clone(), node_id);
         sequence_lengths_by_node_id.insert(node_id, seq.length);
+        sequence_hashes_by_node_id.insert(node_id, seq.hash);
     }
 
-    let library_file = File::open(library_file_path)?;
-    let library_reader = BufReader::new(library_file);
-
-    let mut parts_by_index = HashMap::new();
-    let mut library_csv_reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(library_reader);
-    let mut max_index = 0;
-    for result in library_csv_reader.records() {
-        let record = result?;
-        for (index, part) in record.iter().enumerate() {
-            if !part.is_empty() {
-                let part_id = node_ids_by_name.get(part).unwrap();
-                parts_by_index.entry(index).or_insert(vec![]).push(part_id);
-                if index >= max_index {
-                    max_index = index + 1;
-                }
-            }
+    let slots = read_library_slots(library_file_path)?;
+    for part in slots.iter().flatten() {
+        if !node_ids_by_name.contains_key(part) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("No part named {part} found"),
+            ));
         }
     }
+    for part in constraints.iter().flat_map(|constraint| {
+        let (first, second) = constraint.parts();
+        [first, second]
+    }) {
+        if !node_ids_by_name.contains_key(part) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("No part named {part} found for a constraint"),
+            ));
+        }
+    }
+    let library_graph = LibraryGraph::new(&slots, constraints);
+    if library_graph.combination_count() == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("No combination of {library_file_path} satisfies the constraints"),
+        ));
+    }
 
-    let mut parts_list = vec![];
-    for index in 0..max_index {
-        parts_list.push(parts_by_index.get(&index).unwrap());
+    // Parts in a compatibility class of their own are copies of the part's node
+    let mut class_node_ids = vec![];
+    for (index, layer) in library_graph.layers.iter().enumerate() {
+        let mut layer_node_ids = vec![];
+        for class in layer {
+            let node_id = node_ids_by_name[&class.part];
+            if class.context.is_empty() {
+                layer_node_ids.push(node_id);
+                continue;
+            }
+            let sequence_hash = &sequence_hashes_by_node_id[&node_id];
+            let class_node_id = Node::create(
+                conn,
+                sequence_hash,
+                calculate_hash(&format!(
+                    "{node_hash}:{index}:{context}",
+                    node_hash = Node::path_hash(
+                        &path_key,
+                        0,
+                        sequence_lengths_by_node_id[&node_id],
+                        sequence_hash
+                    ),
+                    context = class.context.iter().join(",")
+                )),
+            );
+            sequence_lengths_by_node_id
+                .insert(class_node_id, sequence_lengths_by_node_id[&node_id]);
+            layer_node_ids.push(class_node_id);
+        }
+        class_node_ids.push(layer_node_ids);
     }
 
     let path_intervaltree = path.intervaltree(conn);
@@ -130,24 +510,22 @@ pub fn update_with_library(
     let node_end_coordinate = end_coordinate - end_block.start + end_block.sequence_start;
 
     let mut new_edges = HashSet::new();
-    let start_parts = parts_list.first().unwrap();
-    for start_part in *start_parts {
+    for start_part in class_node_ids.first().unwrap() {
         let edge = EdgeData {
             source_node_id: start_block.node_id,
             source_coordinate: node_start_coordinate,
             source_strand: Strand::Forward,
-            target_node_id: **start_part,
+            target_node_id: *start_part,
             target_coordinate: 0,
             target_strand: Strand::Forward,
         };
         new_edges.insert(edge);
     }
 
-    let end_parts = parts_list.last().unwrap();
-    for end_part in *end_parts {
+    for end_part in class_node_ids.last().unwrap() {
         let end_part_source_coordinate = sequence_lengths_by_node_id.get(end_part).unwrap();
         let edge = EdgeData {
-            source_node_id: **end_part,
+            source_node_id: *end_part,
             source_coordinate: *end_part_source_coordinate,
             source_strand: Strand::Forward,
             target_node_id: end_block.node_id,
@@ -157,28 +535,24 @@ pub fn update_with_library(
         new_edges.insert(edge);
     }
 
-    let mut path_changes_count = 1;
-    for (parts1, parts2) in parts_list.iter().tuple_windows() {
-        path_changes_count *= parts1.len();
-        for part1 in *parts1 {
-            for part2 in *parts2 {
-                let part1_source_coordinate = sequence_lengths_by_node_id.get(part1).unwrap();
-                let edge = EdgeData {
-                    source_node_id: **part1,
-                    source_coordinate: *part1_source_coordinate,
-                    source_strand: Strand::Forward,
-                    target_node_id: **part2,
-                    target_coordinate: 0,
-                    target_strand: Strand::Forward,
-                };
-                new_edges.insert(edge);
-            }
+    for (index, links) in library_graph.links.iter().enumerate() {
+        for (class1, class2) in links {
+            let part1 = class_node_ids[index][*class1];
+            let part2 = class_node_ids[index + 1][*class2];
+            let edge = EdgeData {
+                source_node_id: part1,
+                source_coordinate: sequence_lengths_by_node_id[&part1],
+                source_strand: Strand::Forward,
+                target_node_id: part2,
+                target_coordinate: 0,
+                target_strand: Strand::Forward,
+            };
+            new_edges.insert(edge);
         }
     }
+    let path_changes_count = library_graph.combination_count();
 
-    path_changes_count *= end_parts.len();
-
-    let new_edge_ids = Edge::bulk_create(conn, &new_edges.iter().cloned().collect());
+    let new_edge_ids = Edge::bulk_create(conn, &new_edges.iter().cloned().collect::<Vec<_>>());
     let new_block_group_edges = new_edge_ids
         .iter()
         .map(|edge_id| BlockGroupEdgeData {
@@ -190,24 +564,7 @@ pub fn update_with_library(
         .collect::<Vec<_>>();
     BlockGroupEdge::bulk_create(conn, &new_block_group_edges);
 
-    let summary_str = format!("{region_name}: {path_changes_count} changes.\n");
-    operation_management::end_operation(
-        conn,
-        operation_conn,
-        &mut session,
-        OperationInfo {
-            file_path: library_file_path.to_string(),
-            file_type: FileTypes::CSV,
-            description: "library_csv_update".to_string(),
-        },
-        &summary_str,
-        None,
-    )
-    .unwrap();
-
-    println!("Updated with library file: {}", library_file_path);
-
-    Ok(())
+    Ok(format!("{region_name}: {path_changes_count} changes.\n"))
 }
 
 #[cfg(test)]
@@ -255,6 +612,7 @@ mod tests {
             20,
             parts_path.to_str().unwrap(),
             library_path.to_str().unwrap(),
+            None,
         );
 
         let block_groups = Sample::get_block_groups(conn, "test", Some("new sample"));
@@ -277,4 +635,121 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn makes_a_pool_with_constraints() {
+        setup_gen_dir();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fixtures.join("simple.fa").to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        // p1 can't be used with cds2, and p2 only with cds2
+        update_with_library(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "new sample",
+            "m123",
+            7,
+            20,
+            fixtures.join("parts.fa").to_str().unwrap(),
+            fixtures.join("combinatorial_design.csv").to_str().unwrap(),
+            fixtures.join("library_constraints.csv").to_str(),
+        )
+        .unwrap();
+
+        let block_group = &Sample::get_block_groups(conn, "test", Some("new sample"))[0];
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn, block_group.id, false),
+            HashSet::from_iter(vec![
+                "ATCGATCGATCGATCGATCGGGAACACACAGAGA".to_string(),
+                "ATCGATCAAAAATGATAAGGAACACACAGAGA".to_string(),
+                "ATCGATCAAAAATGCTAAGGAACACACAGAGA".to_string(),
+                "ATCGATCTAATATGTTAAGGAACACACAGAGA".to_string(),
+                "ATCGATCCAACATGATAAGGAACACACAGAGA".to_string(),
+                "ATCGATCCAACATGTTAAGGAACACACAGAGA".to_string(),
+                "ATCGATCCAACATGCTAAGGAACACACAGAGA".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_library_graph() {
+        let slots = vec![
+            vec!["a1".to_string(), "a2".to_string()],
+            vec!["b1".to_string(), "b2".to_string()],
+            vec!["c1".to_string(), "c2".to_string()],
+        ];
+        let combinations = |graph: &LibraryGraph| {
+            let mut combinations = vec![];
+            let mut stack = (0..graph.layers[0].len())
+                .map(|class| vec![class])
+                .collect::<Vec<_>>();
+            while let Some(classes) = stack.pop() {
+                let index = classes.len() - 1;
+                if index == graph.links.len() {
+                    combinations.push(
+                        classes
+                            .iter()
+                            .enumerate()
+                            .map(|(index, class)| graph.layers[index][*class].part.as_str())
+                            .join(""),
+                    );
+                    continue;
+                }
+                for (class, next_class) in graph.links[index].iter() {
+                    if *class == classes[index] {
+                        let mut next = classes.clone();
+                        next.push(*next_class);
+                        stack.push(next);
+                    }
+                }
+            }
+            combinations.sort();
+            combinations
+        };
+
+        let unconstrained = LibraryGraph::new(&slots, &[]);
+        assert_eq!(unconstrained.combination_count(), 8);
+        assert!(unconstrained
+            .layers
+            .iter()
+            .all(|layer| layer.len() == 2 && layer.iter().all(|class| class.context.is_empty())));
+
+        // a1 needs c2, two slots later, and b2 can't be used with c1
+        let constraints = vec![
+            PartConstraint::Required("a1".to_string(), "c2".to_string()),
+            PartConstraint::Forbidden("b2".to_string(), "c1".to_string()),
+        ];
+        let graph = LibraryGraph::new(&slots, &constraints);
+        assert_eq!(
+            combinations(&graph),
+            vec!["a1b1c2", "a1b2c2", "a2b1c1", "a2b1c2", "a2b2c2"]
+        );
+        assert_eq!(graph.combination_count(), 5);
+        // b1 and b2 are each copied for the choice of a1 or a2
+        assert_eq!(graph.layers[1].len(), 4);
+
+        let impossible = vec![
+            PartConstraint::Required("a1".to_string(), "b1".to_string()),
+            PartConstraint::Required("a2".to_string(), "b1".to_string()),
+            PartConstraint::Forbidden("b1".to_string(), "c1".to_string()),
+            PartConstraint::Forbidden("b1".to_string(), "c2".to_string()),
+        ];
+        assert_eq!(
+            LibraryGraph::new(&slots, &impossible).combination_count(),
+            0
+        );
+    }
 }
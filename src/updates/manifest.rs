@@ -0,0 +1,405 @@
+use crate::models::{
+    file_types::FileTypes,
+    metadata::get_db_uuid,
+    operations::{CoordinateFrame, Operation, OperationInfo},
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use crate::progress_bar::{get_handler, NoProgress};
+use crate::updates::fasta::{apply_fasta_update, preview_fasta_update, FastaUpdateError};
+use crate::updates::gaf::apply_gaf_changes;
+use crate::updates::library::{
+    accession_parts, apply_library_update, read_constraints, read_parts,
+};
+use crate::updates::vcf::{apply_vcf_changes, VcfError, VcfFilter};
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid manifest: {0}")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("Update {index} of the manifest ({path}) failed: {message}")]
+    UpdateFailed {
+        index: usize,
+        path: String,
+        message: String,
+    },
+}
+
+// An update of a manifest, given by its `type` key. Paths are relative to the manifest. Samples
+// are the parent sample of the changes, the reference if not given, as for `gen update`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ManifestUpdate {
+    // Replaces start-end of a region with the first sequence of a fasta file, optionally checking
+    // what is replaced like --expect-replaced-length and --expect-removed-seq.
+    Fasta {
+        path: String,
+        region: String,
+        start: i64,
+        end: i64,
+        sample: Option<String>,
+        new_sample: String,
+        expect_replaced_length: Option<i64>,
+        expect_removed_seq: Option<String>,
+    },
+    Vcf {
+        path: String,
+        genotype: Option<String>,
+        sample: Option<String>,
+        coordinate_frame: Option<String>,
+        min_qual: Option<f32>,
+        #[serde(default)]
+        pass_only: bool,
+        include_expr: Option<String>,
+    },
+    // A combinatorial library between start and end of a path, with its parts from a fasta file or,
+    // without one, the accessions of the collection, and optionally a file of part constraints.
+    Library {
+        path: String,
+        parts: Option<String>,
+        constraints: Option<String>,
+        path_name: String,
+        start: i64,
+        end: i64,
+        sample: Option<String>,
+        new_sample: String,
+    },
+    Gaf {
+        path: String,
+        csv: String,
+        sample: String,
+        parent_sample: Option<String>,
+    },
+}
+
+impl ManifestUpdate {
+    pub fn path(&self) -> &str {
+        match self {
+            ManifestUpdate::Fasta { path, .. }
+            | ManifestUpdate::Vcf { path, .. }
+            | ManifestUpdate::Library { path, .. }
+            | ManifestUpdate::Gaf { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub updates: Vec<ManifestUpdate>,
+}
+
+pub fn read_manifest(manifest_path: &str) -> Result<Manifest, ManifestError> {
+    Ok(serde_yaml::from_reader(BufReader::new(File::open(
+        manifest_path,
+    )?))?)
+}
+
+fn apply_update(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    update: &ManifestUpdate,
+    directory: &Path,
+) -> Result<String, String> {
+    let resolve = |path: &str| directory.join(path).to_string_lossy().to_string();
+    match update {
+        ManifestUpdate::Fasta {
+            path,
+            region,
+            start,
+            end,
+            sample,
+            new_sample,
+            expect_replaced_length,
+            expect_removed_seq,
+        } => {
+            let path = resolve(path);
+            if expect_replaced_length.is_some() || expect_removed_seq.is_some() {
+                preview_fasta_update(
+                    conn,
+                    collection_name,
+                    sample.as_deref(),
+                    new_sample,
+                    region,
+                    *start,
+                    *end,
+                    &path,
+                )
+                .and_then(|preview| {
+                    preview.check(*expect_replaced_length, expect_removed_seq.as_deref())
+                })
+                .map_err(|err: FastaUpdateError| err.to_string())?;
+            }
+            apply_fasta_update(
+                conn,
+                collection_name,
+                sample.as_deref(),
+                new_sample,
+                region,
+                *start,
+                *end,
+                &path,
+            )
+            .map_err(|err| err.to_string())
+        }
+        ManifestUpdate::Vcf {
+            path,
+            genotype,
+            sample,
+            coordinate_frame,
+            min_qual,
+            pass_only,
+            include_expr,
+        } => {
+            let filter = VcfFilter {
+                min_qual: *min_qual,
+                pass_only: *pass_only,
+                include_expr: include_expr
+                    .as_deref()
+                    .map(str::parse)
+                    .transpose()
+                    .map_err(|err: VcfError| err.to_string())?,
+            };
+            let parent_sample = match coordinate_frame {
+                Some(name) => {
+                    let frame = CoordinateFrame::resolve(operation_conn, &get_db_uuid(conn), name);
+                    if !frame.is_sample_only() {
+                        return Err(format!(
+                            "frame {name} has a path or offset, which VCF updates can't use"
+                        ));
+                    }
+                    frame.sample_name
+                }
+                None => None,
+            };
+            apply_vcf_changes(
+                &resolve(path),
+                collection_name,
+                genotype.clone().unwrap_or_default(),
+                sample.clone().unwrap_or_default(),
+                conn,
+                parent_sample.as_deref(),
+                &filter,
+                &get_handler(),
+                &NoProgress,
+            )
+            .map_err(|err| err.to_string())
+        }
+        ManifestUpdate::Library {
+            path,
+            parts,
+            constraints,
+            path_name,
+            start,
+            end,
+            sample,
+            new_sample,
+        } => {
+            let parts = match parts {
+                Some(parts) => read_parts(&resolve(parts)),
+                None => accession_parts(conn, collection_name, &resolve(path)),
+            }
+            .map_err(|err| err.to_string())?;
+            let constraints = match constraints {
+                Some(constraints) => {
+                    read_constraints(&resolve(constraints)).map_err(|err| err.to_string())?
+                }
+                None => vec![],
+            };
+            apply_library_update(
+                conn,
+                collection_name,
+                sample.as_deref(),
+                new_sample,
+                path_name,
+                *start,
+                *end,
+                &parts,
+                &resolve(path),
+                &constraints,
+            )
+            .map_err(|err| err.to_string())
+        }
+        ManifestUpdate::Gaf {
+            path,
+            csv,
+            sample,
+            parent_sample,
+        } => {
+            let change_count = apply_gaf_changes(
+                conn,
+                operation_conn,
+                resolve(path),
+                resolve(csv),
+                collection_name,
+                Some(sample.as_str()),
+                parent_sample.as_deref(),
+                &NoProgress,
+            )
+            .map_err(|err| err.to_string())?;
+            Ok(format!(" {sample}: {change_count} updates.\n"))
+        }
+    }
+}
+
+/*
+   Makes the updates of a manifest in order and records them as one operation, so a campaign of
+   edits made from different kinds of files is applied entirely or not at all and can be replayed
+   from the manifest. Each update sees the changes of the ones before it, so later updates can
+   build on samples made earlier in the manifest. Like other updates, changes made before an update
+   fails are left for the caller to roll back.
+*/
+pub fn update_with_manifest(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    manifest_path: &str,
+) -> Result<Operation, ManifestError> {
+    let manifest = read_manifest(manifest_path)?;
+    let directory = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let mut session = start_operation(conn);
+
+    let mut summary_str = "".to_string();
+    for (index, update) in manifest.updates.iter().enumerate() {
+        let summary = apply_update(conn, operation_conn, collection_name, update, directory)
+            .map_err(|message| ManifestError::UpdateFailed {
+                index: index + 1,
+                path: update.path().to_string(),
+                message,
+            })?;
+        summary_str.push_str(&summary);
+        if !summary_str.ends_with('\n') {
+            summary_str.push('\n');
+        }
+    }
+
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: manifest_path.to_string(),
+            file_type: FileTypes::Manifest,
+            description: "manifest_update".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::BlockGroup;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::sample::Sample;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn operation_count(operation_conn: &Connection) -> i64 {
+        operation_conn
+            .query_row("select count(*) from operation;", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_update_with_manifest() {
+        setup_gen_dir();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        let import = import_fasta(
+            &fixtures.join("simple.fa").to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        let operation = update_with_manifest(
+            conn,
+            op_conn,
+            "test",
+            fixtures.join("manifest.yaml").to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(operation.parent_hash, Some(import.hash));
+        assert_eq!(operation_count(op_conn), 2);
+
+        let edited = get_sample_bg(conn, "test", "edited");
+        assert_eq!(
+            BlockGroup::get_current_path(conn, edited.id).sequence(conn),
+            "ATCGATCGATCGATCAAACACAGAGA"
+        );
+        let pool = get_sample_bg(conn, "test", "pool");
+        assert_eq!(
+            BlockGroup::get_all_sequences(conn, pool.id, false).len(),
+            10
+        );
+        assert_eq!(Sample::get_block_groups(conn, "test", Some("G1")).len(), 1);
+    }
+
+    #[test]
+    fn test_failed_manifest_update() {
+        setup_gen_dir();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fixtures.join("simple.fa").to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        let dir = tempdir().unwrap();
+        let manifest_path = dir.path().join("manifest.yaml");
+        fs::write(
+            &manifest_path,
+            format!(
+                "updates:\n  - type: fasta\n    path: {fasta}\n    region: m123\n    start: 5\n    end: 7\n    new_sample: first\n  - type: fasta\n    path: {fasta}\n    region: m123\n    start: 5\n    end: 7\n    sample: first\n    new_sample: second\n    expect_removed_seq: TC\n",
+                fasta = fixtures.join("aa.fa").display()
+            ),
+        )
+        .unwrap();
+        // the second update sees the first's change, so it doesn't remove TC
+        let result = update_with_manifest(conn, op_conn, "test", manifest_path.to_str().unwrap());
+        assert!(matches!(
+            result,
+            Err(ManifestError::UpdateFailed { index: 2, .. })
+        ));
+        assert_eq!(operation_count(op_conn), 1);
+
+        fs::write(&manifest_path, "updates:\n  - type: bed\n    path: a.bed\n").unwrap();
+        assert!(matches!(
+            read_manifest(manifest_path.to_str().unwrap()),
+            Err(ManifestError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::models::{
+    file_types::FileTypes,
+    node::Node,
+    operations::{Operation, OperationInfo},
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use rusqlite::Connection;
+
+// Gives the nodes made without a hash, such as by older versions, the hash they're made with now,
+// as one operation. Returns the operation and the number of nodes given a hash.
+pub fn backfill_node_hashes(
+    conn: &Connection,
+    operation_conn: &Connection,
+) -> Result<(Operation, usize), OperationError> {
+    let mut session = start_operation(conn);
+    let hashed_count = Node::missing_hashes(conn)
+        .into_iter()
+        .filter(|(node_id, node_hash)| Node::set_missing_hash(conn, *node_id, node_hash))
+        .count();
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "backfill_node_hashes".to_string(),
+        },
+        &format!("hashed {hashed_count} nodes"),
+        None,
+    )?;
+    Ok((operation, hashed_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::{path_edge::PathEdge, sequence::Sequence};
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, setup_block_group, setup_gen_dir,
+    };
+
+    #[test]
+    fn test_backfills_node_hashes() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        let (_, path) = setup_block_group(conn);
+        let unplaced_sequence = Sequence::new()
+            .sequence_type("DNA")
+            .sequence("GATTACA")
+            .save(conn);
+        let unplaced_node_id = Node::create(conn, &unplaced_sequence.hash, None);
+
+        let (_, hashed_count) = backfill_node_hashes(conn, op_conn).unwrap();
+        let path_node_ids = PathEdge::edges_for_path(conn, path.id)
+            .iter()
+            .map(|edge| edge.target_node_id)
+            .filter(|node_id| !Node::is_terminal(*node_id))
+            .collect::<Vec<i64>>();
+        assert_eq!(hashed_count, path_node_ids.len());
+        let nodes = Node::get_nodes(conn, &path_node_ids);
+        assert!(nodes.iter().all(|node| node.hash.is_some()));
+        // the first node of the path is hashed by where it starts the path
+        assert_eq!(
+            nodes[0].hash,
+            Some(Node::path_hash(
+                &path.content_key(conn),
+                0,
+                10,
+                &nodes[0].sequence_hash
+            ))
+        );
+        assert_eq!(Node::get_nodes(conn, &[unplaced_node_id])[0].hash, None);
+
+        assert_eq!(
+            backfill_node_hashes(conn, op_conn).unwrap_err(),
+            OperationError::NoChanges
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::models::{
+    block_group::BlockGroup,
+    file_types::FileTypes,
+    operations::{Operation, OperationInfo},
+    path::Path,
+    sample::Sample,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use rusqlite::Connection;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PathError {
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("No graph named {0}")]
+    MissingGraph(String),
+    #[error("No path named {0}")]
+    MissingPath(String),
+    #[error("A path named {0} already exists")]
+    Duplicate(String),
+}
+
+// The graph of a sample with the given name and the path of it with the given name.
+fn find_path(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+    path_name: &str,
+) -> Result<(BlockGroup, Path), PathError> {
+    let block_group = Sample::get_block_groups(conn, collection_name, sample_name)
+        .into_iter()
+        .find(|block_group| block_group.name == graph_name)
+        .ok_or_else(|| PathError::MissingGraph(graph_name.to_string()))?;
+    let path = BlockGroup::get_paths(conn, block_group.id)
+        .into_iter()
+        .find(|path| path.name == path_name)
+        .ok_or_else(|| PathError::MissingPath(path_name.to_string()))?;
+    Ok((block_group, path))
+}
+
+// Makes a path of a graph its current path as its own operation. The choice holds until a newer
+// path is added to the graph, which then becomes the current path as usual.
+pub fn set_current_path(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+    path_name: &str,
+) -> Result<Operation, PathError> {
+    let (block_group, path) = find_path(conn, collection_name, sample_name, graph_name, path_name)?;
+
+    let mut session = start_operation(conn);
+    BlockGroup::set_current_path(conn, block_group.id, path.id);
+    let summary_str = format!(
+        "{graph}: set current path to {path}",
+        graph = block_group.name,
+        path = path.name,
+    );
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "set_current_path".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+pub fn rename_path(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    graph_name: &str,
+    path_name: &str,
+    new_name: &str,
+) -> Result<Operation, PathError> {
+    let (block_group, path) = find_path(conn, collection_name, sample_name, graph_name, path_name)?;
+    if BlockGroup::get_paths(conn, block_group.id)
+        .iter()
+        .any(|existing| existing.name == new_name)
+    {
+        return Err(PathError::Duplicate(new_name.to_string()));
+    }
+
+    let mut session = start_operation(conn);
+    path.rename(conn, new_name).unwrap();
+    let summary_str = format!(
+        "{graph}: renamed path {path_name} to {new_name}",
+        graph = block_group.name,
+    );
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "rename_path".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::models::path_edge::PathEdge;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_sets_current_path_and_renames_paths() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let block_group = &Sample::get_block_groups(conn, "test", None)[0];
+        let original = BlockGroup::get_current_path(conn, block_group.id);
+        let edge_ids = PathEdge::edges_for_path(conn, original.id)
+            .iter()
+            .map(|edge| edge.id)
+            .collect::<Vec<i64>>();
+        let copy = Path::create(conn, "copy", block_group.id, &edge_ids);
+        assert_eq!(BlockGroup::get_current_path(conn, block_group.id), copy);
+
+        set_current_path(conn, op_conn, "test", None, "m123", "m123").unwrap();
+        assert_eq!(BlockGroup::get_current_path(conn, block_group.id), original);
+        assert_eq!(
+            BlockGroup::get_paths(conn, block_group.id),
+            vec![original.clone(), copy.clone()]
+        );
+
+        rename_path(conn, op_conn, "test", None, "m123", "m123", "reference").unwrap();
+        assert_eq!(
+            BlockGroup::get_current_path(conn, block_group.id).name,
+            "reference"
+        );
+        assert_eq!(
+            rename_path(conn, op_conn, "test", None, "m123", "copy", "reference").unwrap_err(),
+            PathError::Duplicate("reference".to_string())
+        );
+        assert_eq!(
+            set_current_path(conn, op_conn, "test", None, "m123", "m123").unwrap_err(),
+            PathError::MissingPath("m123".to_string())
+        );
+
+        // a newer path replaces the chosen one
+        let newest = Path::create(conn, "newest", block_group.id, &edge_ids);
+        assert_eq!(BlockGroup::get_current_path(conn, block_group.id), newest);
+    }
+}
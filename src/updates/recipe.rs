@@ -0,0 +1,285 @@
+use crate::diffs::recipe::{Recipe, RecipeEdit};
+use crate::models::{
+    block_group::BlockGroup,
+    file_types::FileTypes,
+    operations::{Operation, OperationInfo},
+    sample::Sample,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use crate::updates::fasta::replace_path_range;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::fs::File;
+use std::io::{self, BufReader};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RecipeError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid recipe: {0}")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("Operation Error: {0}")]
+    OperationError(#[from] OperationError),
+    #[error("No graph named {0}")]
+    MissingGraph(String),
+    #[error("Edit {index} of the recipe ({graph}:{start}-{end}) was not found")]
+    SiteNotFound {
+        index: usize,
+        graph: String,
+        start: i64,
+        end: i64,
+    },
+    #[error("Edit {index} of the recipe ({graph}:{start}-{end}) matches {count} sites")]
+    AmbiguousSite {
+        index: usize,
+        graph: String,
+        start: i64,
+        end: i64,
+        count: usize,
+    },
+    #[error("Edits {0} and {1} of the recipe overlap")]
+    Overlap(usize, usize),
+}
+
+pub fn read_recipe(recipe_path: &str) -> Result<Recipe, RecipeError> {
+    Ok(serde_yaml::from_reader(BufReader::new(File::open(
+        recipe_path,
+    )?))?)
+}
+
+// Finds where an edit goes in a sequence, as the start of its reference bases between its flanks.
+// Sites with the same flanks are told apart by the coordinates the recipe was made with.
+fn locate_edit(sequence: &str, index: usize, edit: &RecipeEdit) -> Result<i64, RecipeError> {
+    let site = format!("{}{}{}", edit.upstream, edit.reference, edit.downstream);
+    let starts = (0..sequence.len())
+        .filter(|position| sequence[*position..].starts_with(&site))
+        .map(|position| (position + edit.upstream.len()) as i64)
+        .collect::<Vec<i64>>();
+    match starts[..] {
+        [] => Err(RecipeError::SiteNotFound {
+            index,
+            graph: edit.graph.clone(),
+            start: edit.start,
+            end: edit.end,
+        }),
+        [start] => Ok(start),
+        _ if starts.contains(&edit.start) => Ok(edit.start),
+        _ => Err(RecipeError::AmbiguousSite {
+            index,
+            graph: edit.graph.clone(),
+            start: edit.start,
+            end: edit.end,
+            count: starts.len(),
+        }),
+    }
+}
+
+// Replays the edits of a recipe on a sample, storing the result as a new sample. Every edit is
+// found by its flanks on the parent sample before any is made, so edits close to each other don't
+// disturb each other's flanks, and they are then made from the end of each graph backwards so the
+// positions of the remaining ones hold.
+pub fn apply_recipe(
+    conn: &Connection,
+    operation_conn: &Connection,
+    collection_name: &str,
+    recipe_path: &str,
+    parent_sample_name: Option<&str>,
+    new_sample_name: &str,
+) -> Result<Operation, RecipeError> {
+    let recipe = read_recipe(recipe_path)?;
+    let mut session = start_operation(conn);
+
+    let parent_block_groups = Sample::get_block_groups(conn, collection_name, parent_sample_name);
+    for edit in recipe.edits.iter() {
+        if !parent_block_groups.iter().any(|bg| bg.name == edit.graph) {
+            return Err(RecipeError::MissingGraph(edit.graph.clone()));
+        }
+    }
+
+    Sample::get_or_create(conn, new_sample_name);
+    let mut summary_str = "".to_string();
+    for (graph, edits) in recipe
+        .edits
+        .iter()
+        .enumerate()
+        .into_group_map_by(|(_, edit)| edit.graph.clone())
+        .into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+    {
+        let block_group_id = BlockGroup::get_or_create_sample_block_group(
+            conn,
+            collection_name,
+            new_sample_name,
+            &graph,
+            parent_sample_name,
+        )
+        .unwrap();
+        let mut path = BlockGroup::get_current_path(conn, block_group_id);
+        let sequence = path.sequence(conn);
+        let mut sites = edits
+            .iter()
+            .map(|(index, edit)| {
+                let start = locate_edit(&sequence, *index, edit)?;
+                Ok((*index, start, start + edit.reference.len() as i64, *edit))
+            })
+            .collect::<Result<Vec<_>, RecipeError>>()?;
+        sites.sort_by_key(|(_, start, end, _)| (*start, *end));
+        for ((first_index, _, first_end, _), (second_index, second_start, _, _)) in
+            sites.iter().tuple_windows()
+        {
+            if first_end > second_start {
+                return Err(RecipeError::Overlap(*first_index, *second_index));
+            }
+        }
+        for (_, start, end, edit) in sites.iter().rev() {
+            path = replace_path_range(conn, block_group_id, &path, *start, *end, &edit.replacement);
+        }
+        summary_str.push_str(&format!(" {graph}: {count} changes\n", count = sites.len()));
+    }
+
+    let operation = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: recipe_path.to_string(),
+            file_type: FileTypes::Recipe,
+            description: "recipe_apply".to_string(),
+        },
+        &summary_str,
+        None,
+    )?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diffs::recipe::{create_recipe, write_recipe};
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn sample_sequence(conn: &Connection, sample_name: &str) -> String {
+        BlockGroup::get_current_path(conn, get_sample_bg(conn, "test", sample_name).id)
+            .sequence(conn)
+    }
+
+    #[test]
+    fn test_replays_recipe_on_another_sample() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let aa_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // two edits on the way to the child: GATCGGGAAC at 15 becomes AAAAAAAA, and then the
+        // reference's GA at 30 becomes AA
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "edited",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            Some("edited"),
+            "child",
+            "m123",
+            28,
+            30,
+            aa_path.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            sample_sequence(conn, "child"),
+            "ATCGATCGATCGATCAAAAAAAAACACAAAGA"
+        );
+        // a strain where the edit sites have moved, with CGAT at 2 replaced by AA
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "strain",
+            "m123",
+            2,
+            6,
+            aa_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let recipe = create_recipe(conn, "test", None, Some("child"), 5);
+        assert_eq!(recipe.edits.len(), 2);
+        let recipe_dir = tempdir().unwrap();
+        let recipe_path = recipe_dir.path().join("recipe.yaml");
+        write_recipe(&recipe, recipe_path.to_str().unwrap()).unwrap();
+        assert_eq!(read_recipe(recipe_path.to_str().unwrap()).unwrap(), recipe);
+
+        apply_recipe(
+            conn,
+            op_conn,
+            "test",
+            recipe_path.to_str().unwrap(),
+            None,
+            "replayed",
+        )
+        .unwrap();
+        assert_eq!(
+            sample_sequence(conn, "replayed"),
+            sample_sequence(conn, "child")
+        );
+
+        apply_recipe(
+            conn,
+            op_conn,
+            "test",
+            recipe_path.to_str().unwrap(),
+            Some("strain"),
+            "ported",
+        )
+        .unwrap();
+        assert_eq!(
+            sample_sequence(conn, "ported"),
+            "ATAACGATCGATCAAAAAAAAACACAAAGA"
+        );
+
+        // the edit sites are gone from the child itself
+        assert!(matches!(
+            apply_recipe(
+                conn,
+                op_conn,
+                "test",
+                recipe_path.to_str().unwrap(),
+                Some("child"),
+                "twice",
+            ),
+            Err(RecipeError::SiteNotFound { index: 0, .. })
+        ));
+    }
+}
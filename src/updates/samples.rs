@@ -0,0 +1,213 @@
+use crate::models::{
+    block_group::BlockGroup,
+    block_group_edge::BlockGroupEdge,
+    edge::Edge,
+    file_types::FileTypes,
+    node::Node,
+    operations::{Operation, OperationInfo},
+    path::Path,
+    path_edge::PathEdge,
+    sample::Sample,
+    traits::*,
+};
+use crate::operation_management::{end_operation, start_operation, OperationError};
+use itertools::Itertools;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+
+// An edge by the sequences of the nodes it joins rather than their ids, as repeating an update
+// makes new nodes for the same sequence.
+type EdgeContent = (String, i64, String, String, i64, String);
+
+// A graph as a sample holds it: its collection, name and topology, its edges with their
+// chromosome index and phasing, and the edges of each of its paths. Path names are left out, as
+// updates name paths after the nodes they make.
+type GraphContent = (
+    String,
+    String,
+    bool,
+    Vec<(EdgeContent, i64, i64)>,
+    Vec<Vec<EdgeContent>>,
+);
+
+fn sample_content(conn: &Connection, sample_name: &str) -> Vec<GraphContent> {
+    let block_groups = BlockGroup::query(
+        conn,
+        "select * from block_groups where sample_name = ?1;",
+        params!(sample_name),
+    );
+    block_groups
+        .into_iter()
+        .map(|block_group| {
+            let block_group_edges = BlockGroupEdge::edges_for_block_group(conn, block_group.id);
+            let node_ids = block_group_edges
+                .iter()
+                .flat_map(|edge| [edge.edge.source_node_id, edge.edge.target_node_id])
+                .unique()
+                .collect::<Vec<i64>>();
+            let sequences_by_node_id = Node::get_sequences_by_node_ids(conn, &node_ids);
+            let edge_content = |edge: &Edge| {
+                (
+                    sequences_by_node_id[&edge.source_node_id].hash.clone(),
+                    edge.source_coordinate,
+                    edge.source_strand.to_string(),
+                    sequences_by_node_id[&edge.target_node_id].hash.clone(),
+                    edge.target_coordinate,
+                    edge.target_strand.to_string(),
+                )
+            };
+            let edges = block_group_edges
+                .iter()
+                .map(|edge| (edge_content(&edge.edge), edge.chromosome_index, edge.phased))
+                .sorted()
+                .collect();
+            let paths = Path::query(
+                conn,
+                "select * from paths where block_group_id = ?1;",
+                params!(block_group.id),
+            )
+            .into_iter()
+            .map(|path| {
+                PathEdge::edges_for_path(conn, path.id)
+                    .iter()
+                    .map(edge_content)
+                    .collect()
+            })
+            .sorted()
+            .collect();
+            (
+                block_group.collection_name,
+                block_group.name,
+                block_group.is_circular,
+                edges,
+                paths,
+            )
+        })
+        .sorted()
+        .collect()
+}
+
+// Finds samples with the same graphs as another sample, down to their edges and paths, such as
+// those left behind by retried pipeline runs. Each group of duplicates is returned as the sample
+// that sorts first by name and the samples that duplicate it. Samples without any graphs and
+// samples that are already aliases are not considered.
+pub fn find_duplicate_samples(conn: &Connection) -> Vec<(String, Vec<String>)> {
+    let aliases = Sample::get_aliases(conn);
+    let mut samples_by_content: HashMap<Vec<GraphContent>, Vec<String>> = HashMap::new();
+    for sample_name in Sample::get_all_names(conn) {
+        if aliases.contains_key(&sample_name) {
+            continue;
+        }
+        let content = sample_content(conn, &sample_name);
+        if !content.is_empty() {
+            samples_by_content
+                .entry(content)
+                .or_default()
+                .push(sample_name);
+        }
+    }
+    samples_by_content
+        .into_values()
+        .filter(|sample_names| sample_names.len() > 1)
+        .map(|sample_names| {
+            let mut sample_names = sample_names.into_iter().sorted();
+            let sample_name = sample_names.next().unwrap();
+            (sample_name, sample_names.collect())
+        })
+        .sorted()
+        .collect()
+}
+
+// Records every duplicate sample as an alias of the sample it duplicates, as one operation. The
+// duplicates aren't removed, but are hidden from sample listings.
+pub fn dedupe_samples(
+    conn: &Connection,
+    operation_conn: &Connection,
+    duplicates: &[(String, Vec<String>)],
+) -> Result<Operation, OperationError> {
+    let mut session = start_operation(conn);
+    for (sample_name, aliases) in duplicates {
+        for alias in aliases {
+            Sample::add_alias(conn, alias, sample_name);
+        }
+    }
+    let summary_str = duplicates
+        .iter()
+        .map(|(sample_name, aliases)| {
+            format!(
+                "{sample_name}: aliased {aliases}",
+                aliases = aliases.join(", ")
+            )
+        })
+        .join("\n");
+    end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: "".to_string(),
+            file_type: FileTypes::None,
+            description: "dedupe_samples".to_string(),
+        },
+        &summary_str,
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_dedupe_samples() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let fasta_update_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        // the same update made three times, once at a different position
+        for (sample_name, start) in [("run-1", 2), ("run-1-retry", 2), ("run-2", 4)] {
+            update_with_fasta(
+                conn,
+                op_conn,
+                "test",
+                None,
+                sample_name,
+                "m123",
+                start,
+                start + 3,
+                fasta_update_path.to_str().unwrap(),
+            )
+            .unwrap();
+        }
+
+        let duplicates = find_duplicate_samples(conn);
+        assert_eq!(
+            duplicates,
+            vec![("run-1".to_string(), vec!["run-1-retry".to_string()])]
+        );
+
+        dedupe_samples(conn, op_conn, &duplicates).unwrap();
+        assert_eq!(
+            Sample::get_aliases(conn),
+            HashMap::from([("run-1-retry".to_string(), "run-1".to_string())])
+        );
+        assert!(find_duplicate_samples(conn).is_empty());
+    }
+}
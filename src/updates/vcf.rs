@@ -1,6 +1,9 @@
 use crate::models::operations::OperationInfo;
 use crate::models::{
-    block_group::{BlockGroup, BlockGroupData, PathCache, PathChange},
+    attribute::AttributeValue,
+    block_group::{BlockGroup, BlockGroupData, NodeIntervalBlock, PathCache, PathChange},
+    block_group_edge::{AugmentedEdgeData, BlockGroupEdge, BlockGroupEdgeData},
+    edge::{Edge, EdgeData},
     file_types::FileTypes,
     node::Node,
     operations::Operation,
@@ -11,14 +14,20 @@ use crate::models::{
     traits::*,
 };
 use crate::operation_management::{end_operation, start_operation, OperationError};
-use crate::progress_bar::{add_saving_operation_bar, get_handler, get_progress_bar};
-use crate::{calculate_hash, parse_genotype};
+use crate::parse_genotype;
+use crate::progress_bar::{
+    add_saving_operation_bar, get_handler, get_progress_bar, report_progress, NoProgress,
+    ProgressReporter,
+};
+use indicatif::MultiProgress;
+use intervaltree::IntervalTree;
 use noodles::vcf;
+use noodles::vcf::variant::record::info::field::value::Array;
 use noodles::vcf::variant::record::info::field::Value as InfoValue;
 use noodles::vcf::variant::record::samples::series::value::genotype::Phasing;
 use noodles::vcf::variant::record::samples::series::Value;
 use noodles::vcf::variant::record::samples::Sample as NoodlesSample;
-use noodles::vcf::variant::record::AlternateBases;
+use noodles::vcf::variant::record::{AlternateBases, Ids};
 use noodles::vcf::variant::Record;
 use regex;
 use regex::Regex;
@@ -36,7 +45,7 @@ struct BlockGroupCache<'a> {
 }
 
 impl<'a> BlockGroupCache<'_> {
-    pub fn new(conn: &Connection) -> BlockGroupCache {
+    pub fn new(conn: &Connection) -> BlockGroupCache<'_> {
         BlockGroupCache {
             cache: HashMap::<BlockGroupData, i64>::new(),
             conn,
@@ -88,7 +97,7 @@ pub struct SequenceCache<'a> {
 }
 
 impl<'a> SequenceCache<'_> {
-    pub fn new(conn: &Connection) -> SequenceCache {
+    pub fn new(conn: &Connection) -> SequenceCache<'_> {
         SequenceCache {
             cache: HashMap::<SequenceKey, Sequence>::new(),
             conn,
@@ -133,8 +142,8 @@ fn prepare_change(
     block_sequence: String,
     sequence_length: i64,
     node_id: i64,
+    strand: Strand,
 ) -> PathChange {
-    // TODO: new sequence may not be real and be <DEL> or some sort. Handle these.
     let new_block = PathBlock {
         id: 0,
         node_id,
@@ -143,7 +152,7 @@ fn prepare_change(
         sequence_end: sequence_length,
         path_start: ref_start,
         path_end: ref_end,
-        strand: Strand::Forward,
+        strand,
     };
     PathChange {
         block_group_id: sample_bg_id,
@@ -157,14 +166,352 @@ fn prepare_change(
     }
 }
 
+// What an alternate allele does to the reference, in 0-based, end-exclusive coordinates.
+#[derive(Clone, Debug, PartialEq)]
+enum AltAllele {
+    // Replaces the bases from start to end, with * for an allele another deletion removes.
+    Sequence {
+        start: i64,
+        end: i64,
+        sequence: String,
+    },
+    // Replaces the bases from start to end with a number of copies of them, as <DUP> and <CN>
+    // alleles do.
+    Copies {
+        start: i64,
+        end: i64,
+        count: usize,
+    },
+    // Reads the bases from start to end on the reverse strand.
+    Inversion {
+        start: i64,
+        end: i64,
+    },
+    Breakend(Breakend),
+}
+
+/*
+   A breakend joins the reference next to the base at POS to a mate position, on the same or
+   another sequence, as in t[p[, t]p], ]p]t and [p[t. Positions are where the join meets each
+   sequence: t[p[ continues forward from the start of base p, and t]p] continues from the end of
+   base p on the reverse strand.
+*/
+#[derive(Clone, Debug, PartialEq)]
+struct Breakend {
+    position: i64,
+    // whether the join leaves the reference after t for the mate, or comes from the mate before t
+    leaves: bool,
+    mate_name: String,
+    mate_position: i64,
+    // whether the mate position is at the end of base p rather than its start
+    mate_after_base: bool,
+    mate_strand: Strand,
+}
+
+impl AltAllele {
+    // Reads an alternate allele of a record starting at ref_start, giving None for alleles that
+    // can't be applied, such as symbolic alleles without an end.
+    fn parse(
+        alt: &str,
+        ref_seq: &str,
+        ref_start: i64,
+        ref_end: i64,
+        sv_end: Option<i64>,
+        cnv_re: &Regex,
+        breakend_re: &Regex,
+    ) -> Option<AltAllele> {
+        // the base at POS comes before symbolic structural variants
+        let sv_start = ref_start + 1;
+        match alt {
+            "<DEL>" => Some(AltAllele::Sequence {
+                start: sv_start,
+                end: sv_end?,
+                sequence: "".to_string(),
+            }),
+            "<DUP>" | "<DUP:TANDEM>" => Some(AltAllele::Copies {
+                start: sv_start,
+                end: sv_end?,
+                count: 2,
+            }),
+            "<INV>" => Some(AltAllele::Inversion {
+                start: sv_start,
+                end: sv_end?,
+            }),
+            _ if alt.starts_with('<') => {
+                // our ref sequence will be something like "ATC" and our new alt sequence will be
+                // (ATC)*count. The position provided will be the left most base, so the A here.
+                let count = cnv_re.captures(alt)?["count"]
+                    .parse()
+                    .expect("Invalid CN specification");
+                Some(AltAllele::Copies {
+                    start: ref_start,
+                    end: ref_end,
+                    count,
+                })
+            }
+            _ if alt.contains(['[', ']']) => {
+                let captures = breakend_re.captures(alt)?;
+                let (before, after) = (&captures["before"], &captures["after"]);
+                // only single base breakends are supported, not those inserting bases at the join
+                if captures["open"] != captures["close"] || before.len() + after.len() != 1 {
+                    return None;
+                }
+                let leaves = !before.is_empty();
+                let mate_after_base = &captures["open"] == "]";
+                let mate_position: i64 = captures["mate_position"].parse().ok()?;
+                Some(AltAllele::Breakend(Breakend {
+                    position: if leaves { ref_start + 1 } else { ref_start },
+                    leaves,
+                    mate_name: captures["mate_name"].to_string(),
+                    mate_position: if mate_after_base {
+                        mate_position
+                    } else {
+                        mate_position - 1
+                    },
+                    mate_after_base,
+                    // t]p] and [p[t read the mate on the reverse strand
+                    mate_strand: if leaves == mate_after_base {
+                        Strand::Reverse
+                    } else {
+                        Strand::Forward
+                    },
+                }))
+            }
+            _ => {
+                let mut start = ref_start;
+                let mut sequence = alt.to_string();
+                // If the alt sequence is a deletion, we want to remove the base in common in the
+                // VCF spec. So if VCF says ATC -> A, we don't want to include the `A` in the alt_seq.
+                if !sequence.is_empty() && sequence != "*" && sequence.len() < ref_seq.len() {
+                    start += 1;
+                    sequence = sequence[1..].to_string();
+                }
+                Some(AltAllele::Sequence {
+                    start,
+                    end: ref_end,
+                    sequence,
+                })
+            }
+        }
+    }
+
+    fn mate_name(&self) -> Option<&str> {
+        match self {
+            AltAllele::Breakend(breakend) => Some(&breakend.mate_name),
+            _ => None,
+        }
+    }
+}
+
+// The end of a symbolic structural variant, 0-based and end-exclusive: its END, or otherwise the
+// length of its SVLEN past POS.
+fn structural_variant_end(record: &vcf::Record, header: &vcf::Header) -> Option<i64> {
+    if let Some(Ok(Some(InfoValue::Integer(end)))) = record.info().get(header, "END") {
+        return Some(end as i64);
+    }
+    let length = match record.info().get(header, "SVLEN")?.ok()?? {
+        InfoValue::Integer(length) => length,
+        InfoValue::Array(Array::Integer(lengths)) => lengths.iter().next()?.ok()??,
+        _ => return None,
+    };
+    // VCF 4.2 gives deletions a negative length
+    Some(record.variant_start()?.ok()?.get() as i64 + length.abs() as i64)
+}
+
+// The node and coordinate a join meets a path at, from the block holding the base before the
+// position or the one holding the base at it.
+fn node_coordinate(
+    tree: &IntervalTree<i64, NodeIntervalBlock>,
+    position: i64,
+    after_base: bool,
+) -> Option<(i64, i64)> {
+    let base = if after_base { position - 1 } else { position };
+    let block = tree
+        .query_point(base)
+        .map(|interval| &interval.value)
+        .find(|block| block.strand == Strand::Forward)?;
+    Some((block.node_id, position - block.start + block.sequence_start))
+}
+
+// Which records of a VCF to incorporate. Records without a QUAL fail a minimum QUAL, and records
+// without a FILTER fail pass_only.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VcfFilter {
+    pub min_qual: Option<f32>,
+    pub pass_only: bool,
+    pub include_expr: Option<InfoExpression>,
+}
+
+impl VcfFilter {
+    fn includes(&self, record: &vcf::Record, header: &vcf::Header) -> bool {
+        if let Some(min_qual) = self.min_qual {
+            match record.quality_score() {
+                Some(Ok(quality_score)) if quality_score >= min_qual => {}
+                _ => return false,
+            }
+        }
+        if self.pass_only && record.filters().as_ref() != "PASS" {
+            return false;
+        }
+        self.include_expr
+            .as_ref()
+            .is_none_or(|expression| expression.matches(record, header))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct InfoCondition {
+    key: String,
+    // None checks that the key is present, such as for flags
+    comparison: Option<(Comparison, String)>,
+}
+
+impl InfoCondition {
+    fn matches(&self, record: &vcf::Record, header: &vcf::Header) -> bool {
+        let info = record.info();
+        let Some(Ok(value)) = info.get(header, &self.key) else {
+            return false;
+        };
+        let Some((comparison, expected)) = &self.comparison else {
+            return true;
+        };
+        // a field with several values matches if any of them do
+        info_values(value).iter().any(|actual| {
+            let ordering = match (actual.parse::<f64>(), expected.parse::<f64>()) {
+                (Ok(actual), Ok(expected)) => actual.partial_cmp(&expected),
+                _ => Some(actual.as_str().cmp(expected.as_str())),
+            };
+            let Some(ordering) = ordering else {
+                return false;
+            };
+            match comparison {
+                Comparison::Equal => ordering.is_eq(),
+                Comparison::NotEqual => ordering.is_ne(),
+                Comparison::Greater => ordering.is_gt(),
+                Comparison::GreaterOrEqual => ordering.is_ge(),
+                Comparison::Less => ordering.is_lt(),
+                Comparison::LessOrEqual => ordering.is_le(),
+            }
+        })
+    }
+}
+
+// The values of an INFO field as written, with none for flags and missing values.
+fn info_values(value: Option<InfoValue>) -> Vec<String> {
+    fn present<T: ToString>(
+        values: Box<dyn Iterator<Item = io::Result<Option<T>>> + '_>,
+    ) -> Vec<String> {
+        values
+            .filter_map(|value| value.ok().flatten())
+            .map(|value| value.to_string())
+            .collect()
+    }
+    match value {
+        None | Some(InfoValue::Flag) => vec![],
+        Some(InfoValue::Integer(value)) => vec![value.to_string()],
+        Some(InfoValue::Float(value)) => vec![value.to_string()],
+        Some(InfoValue::Character(value)) => vec![value.to_string()],
+        Some(InfoValue::String(value)) => vec![value.to_string()],
+        Some(InfoValue::Array(Array::Integer(values))) => present(values.iter()),
+        Some(InfoValue::Array(Array::Float(values))) => present(values.iter()),
+        Some(InfoValue::Array(Array::Character(values))) => present(values.iter()),
+        Some(InfoValue::Array(Array::String(values))) => present(values.iter()),
+    }
+}
+
+// A simple expression over the INFO fields of a record, conditions like DP>=10, TYPE=="snp" or
+// a bare key for a flag, joined by &&.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InfoExpression {
+    conditions: Vec<InfoCondition>,
+}
+
+impl InfoExpression {
+    fn matches(&self, record: &vcf::Record, header: &vcf::Header) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(record, header))
+    }
+}
+
+impl str::FromStr for InfoExpression {
+    type Err = VcfError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        // two character operators come first so >= isn't read as >
+        const OPERATORS: [(&str, Comparison); 7] = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+        ];
+        let invalid = || VcfError::InvalidExpression(expression.to_string());
+        let is_key = |key: &str| {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        };
+        let mut conditions = vec![];
+        for condition in expression.split("&&").map(str::trim) {
+            let operator = OPERATORS
+                .iter()
+                .filter_map(|(operator, comparison)| {
+                    condition
+                        .find(operator)
+                        .map(|index| (index, *operator, *comparison))
+                })
+                .min_by_key(|(index, operator, _)| (*index, std::cmp::Reverse(operator.len())));
+            let condition = match operator {
+                None if is_key(condition) => InfoCondition {
+                    key: condition.to_string(),
+                    comparison: None,
+                },
+                Some((index, operator, comparison)) => {
+                    let key = condition[..index].trim();
+                    let value = condition[index + operator.len()..].trim();
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .unwrap_or(value);
+                    if !is_key(key) || value.is_empty() || value.contains(['<', '>', '=', '!']) {
+                        return Err(invalid());
+                    }
+                    InfoCondition {
+                        key: key.to_string(),
+                        comparison: Some((comparison, value.to_string())),
+                    }
+                }
+                None => return Err(invalid()),
+            };
+            conditions.push(condition);
+        }
+        Ok(InfoExpression { conditions })
+    }
+}
+
 #[derive(Debug)]
 struct VcfEntry {
     block_group_id: i64,
     sample_name: String,
     path: Path,
+    // the path of the sequence a breakend joins
+    mate_path: Option<Path>,
     ids: Option<String>,
-    ref_start: i64,
-    alt_seq: String,
+    allele: AltAllele,
     chromosome_index: i64,
     phased: i64,
 }
@@ -173,6 +520,8 @@ struct VcfEntry {
 pub enum VcfError {
     #[error("Operation Error: {0}")]
     OperationError(#[from] OperationError),
+    #[error("Invalid INFO expression: {0}")]
+    InvalidExpression(String),
 }
 
 pub fn update_with_vcf<'a>(
@@ -183,17 +532,95 @@ pub fn update_with_vcf<'a>(
     conn: &Connection,
     operation_conn: &Connection,
     coordinate_frame: impl Into<Option<&'a str>>,
+) -> Result<Operation, VcfError> {
+    update_with_vcf_with_progress(
+        vcf_path,
+        collection_name,
+        fixed_genotype,
+        fixed_sample,
+        conn,
+        operation_conn,
+        coordinate_frame,
+        &VcfFilter::default(),
+        &NoProgress,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_with_vcf_with_progress<'a>(
+    vcf_path: &String,
+    collection_name: &'a str,
+    fixed_genotype: String,
+    fixed_sample: String,
+    conn: &Connection,
+    operation_conn: &Connection,
+    coordinate_frame: impl Into<Option<&'a str>>,
+    filter: &VcfFilter,
+    progress: &dyn ProgressReporter,
 ) -> Result<Operation, VcfError> {
     let progress_bar = get_handler();
-    let coordinate_frame = coordinate_frame.into();
-    let cnv_re = Regex::new(r"(?x)<CN(?P<count>\d+)>").unwrap();
 
     let mut session = start_operation(conn);
 
+    let summary_str = apply_vcf_changes(
+        vcf_path,
+        collection_name,
+        fixed_genotype,
+        fixed_sample,
+        conn,
+        coordinate_frame,
+        filter,
+        &progress_bar,
+        progress,
+    )?;
+
+    let bar = add_saving_operation_bar(&progress_bar);
+    bar.set_message("Saving operation");
+    let op = end_operation(
+        conn,
+        operation_conn,
+        &mut session,
+        OperationInfo {
+            file_path: vcf_path.to_string(),
+            file_type: FileTypes::VCF,
+            description: "vcf_addition".to_string(),
+        },
+        &summary_str,
+        None,
+    )
+    .map_err(VcfError::OperationError);
+    bar.finish();
+    op
+}
+
+// Makes the changes of a VCF without recording them as an operation, so several updates can be
+// recorded as one. Returns the summary of the changes.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_vcf_changes<'a>(
+    vcf_path: &String,
+    collection_name: &'a str,
+    fixed_genotype: String,
+    fixed_sample: String,
+    conn: &Connection,
+    coordinate_frame: impl Into<Option<&'a str>>,
+    filter: &VcfFilter,
+    progress_bar: &MultiProgress,
+    progress: &dyn ProgressReporter,
+) -> Result<String, VcfError> {
+    let coordinate_frame = coordinate_frame.into();
+    let cnv_re = Regex::new(r"(?x)<CN(?P<count>\d+)>").unwrap();
+    let breakend_re = Regex::new(
+        r"^(?P<before>[A-Za-z]*)(?P<open>[\[\]])(?P<mate_name>[^:\[\]]+):(?P<mate_position>\d+)(?P<close>[\[\]])(?P<after>[A-Za-z]*)$",
+    )
+    .unwrap();
+
     let mut reader = vcf::io::reader::Builder::default()
         .build_from_path(vcf_path)
         .expect("Unable to parse");
     let header = reader.read_header().unwrap();
+    let vcf_file_name = std::path::Path::new(vcf_path)
+        .file_name()
+        .map_or(vcf_path.clone(), |name| name.to_string_lossy().to_string());
     let sample_names = header.sample_names();
     for name in sample_names {
         Sample::get_or_create(conn, name);
@@ -213,8 +640,9 @@ pub fn update_with_vcf<'a>(
     let mut accession_cache = HashMap::new();
 
     let mut changes: HashMap<(Path, String), Vec<PathChange>> = HashMap::new();
+    let mut breakend_edges: HashMap<(Path, String), Vec<AugmentedEdgeData>> = HashMap::new();
 
-    let mut parent_block_groups: HashMap<(&str, i64), i64> = HashMap::new();
+    let mut parent_path_keys: HashMap<(&str, i64), String> = HashMap::new();
     let mut created_samples = HashSet::new();
 
     let _ = progress_bar.println("Parsing VCF for changes.");
@@ -224,10 +652,15 @@ pub fn update_with_vcf<'a>(
     bar.set_message("Records Parsed");
     for result in reader.records() {
         let record = result.unwrap();
+        if !filter.includes(&record, &header) {
+            bar.inc(1);
+            continue;
+        }
         let seq_name: String = record.reference_sequence_name().to_string();
         let ref_seq = record.reference_bases();
         // this converts the coordinates to be zero based, start inclusive, end exclusive
         let ref_end = record.variant_end(&header).unwrap().get() as i64;
+        let sv_end = structural_variant_end(&record, &header);
         let alt_bases = record.alternate_bases();
         let alt_alleles: Vec<_> = alt_bases.iter().collect::<io::Result<_>>().unwrap();
         let mut vcf_entries = vec![];
@@ -265,37 +698,43 @@ pub fn update_with_vcf<'a>(
                     let allele_accession = accession_name
                         .clone()
                         .filter(|_| gt.allele as i32 == accession_allele);
-                    let mut ref_start = (record.variant_start().unwrap().unwrap().get() - 1) as i64;
+                    let ref_start = (record.variant_start().unwrap().unwrap().get() - 1) as i64;
                     if gt.allele != 0 {
-                        let mut alt_seq = alt_alleles[chromosome_index - 1].to_string();
-                        if alt_seq.starts_with("<") {
-                            if let Some(cap) = cnv_re.captures(&alt_seq) {
-                                let count: usize =
-                                    cap["count"].parse().expect("Invalid CN specification");
-                                alt_seq = ref_seq.to_string().repeat(count);
-                            } else {
-                                continue;
-                            };
-                        }
-                        // If the alt sequence is a deletion, we want to remove the base in common in the VCF spec.
-                        // So if VCF says ATC -> A, we don't want to include the `A` in the alt_seq.
-                        if !alt_seq.is_empty() && alt_seq != "*" && alt_seq.len() < ref_seq.len() {
-                            ref_start += 1;
-                            alt_seq = alt_seq[1..].to_string();
-                        }
+                        let Some(allele) = AltAllele::parse(
+                            alt_alleles[gt.allele as usize - 1],
+                            ref_seq,
+                            ref_start,
+                            ref_end,
+                            sv_end,
+                            &cnv_re,
+                            &breakend_re,
+                        ) else {
+                            continue;
+                        };
                         let phased = match gt.phasing {
                             Phasing::Phased => 1,
                             Phasing::Unphased => 0,
                         };
                         let sample_path =
                             PathCache::lookup(&mut path_cache, sample_bg_id, seq_name.clone());
+                        let mate_path = allele.mate_name().map(|mate_name| {
+                            let mate_bg_id = BlockGroupCache::lookup(
+                                &mut block_group_cache,
+                                collection_name,
+                                &fixed_sample,
+                                mate_name.to_string(),
+                                coordinate_frame,
+                            )
+                            .expect("can't find the block group of a breakend's mate");
+                            PathCache::lookup(&mut path_cache, mate_bg_id, mate_name.to_string())
+                        });
                         vcf_entries.push(VcfEntry {
                             ids: allele_accession,
-                            ref_start,
                             block_group_id: sample_bg_id,
                             path: sample_path.clone(),
+                            mate_path,
                             sample_name: fixed_sample.clone(),
-                            alt_seq,
+                            allele,
                             chromosome_index: chromosome_index as i64,
                             phased,
                         });
@@ -334,56 +773,62 @@ pub fn update_with_vcf<'a>(
                 let sample_bg_id =
                     sample_bg_id.expect("can't find sample bg....check this out more");
                 let genotype = sample.get(&header, "GT");
-                if genotype.is_some() {
-                    if let Value::Genotype(genotypes) = genotype.unwrap().unwrap().unwrap() {
+                if let Some(genotype) = genotype {
+                    if let Value::Genotype(genotypes) = genotype.unwrap().unwrap() {
                         for (chromosome_index, gt) in genotypes.iter().enumerate() {
-                            if gt.is_ok() {
-                                let (allele, phasing) = gt.unwrap();
+                            if let Ok((allele, phasing)) = gt {
                                 let phased = match phasing {
                                     Phasing::Phased => 1,
                                     Phasing::Unphased => 0,
                                 };
-                                let mut ref_start =
+                                let ref_start =
                                     (record.variant_start().unwrap().unwrap().get() - 1) as i64;
                                 if let Some(allele) = allele {
                                     let allele_accession = accession_name
                                         .clone()
                                         .filter(|_| allele as i32 == accession_allele);
                                     if allele != 0 {
-                                        let mut alt_seq = alt_alleles[allele - 1].to_string();
-                                        if alt_seq.starts_with("<") {
-                                            if let Some(cap) = cnv_re.captures(&alt_seq) {
-                                                let count: usize = cap["count"]
-                                                    .parse()
-                                                    .expect("Invalid CN specification");
-                                                // our ref sequence will be something like "ATC" and our new alt
-                                                // sequence will be (ATC)*count. The position provided will be
-                                                // the left most base, so the A here.
-                                                alt_seq = ref_seq.to_string().repeat(count);
-                                            } else {
-                                                continue;
-                                            }
-                                        }
-                                        if !alt_seq.is_empty()
-                                            && alt_seq != "*"
-                                            && alt_seq.len() < ref_seq.len()
-                                        {
-                                            ref_start += 1;
-                                            alt_seq = alt_seq[1..].to_string();
-                                        }
+                                        let Some(alt_allele) = AltAllele::parse(
+                                            alt_alleles[allele - 1],
+                                            ref_seq,
+                                            ref_start,
+                                            ref_end,
+                                            sv_end,
+                                            &cnv_re,
+                                            &breakend_re,
+                                        ) else {
+                                            continue;
+                                        };
                                         let sample_path = PathCache::lookup(
                                             &mut path_cache,
                                             sample_bg_id,
                                             seq_name.clone(),
                                         );
+                                        let mate_path = alt_allele.mate_name().map(|mate_name| {
+                                            let mate_bg_id = BlockGroupCache::lookup(
+                                                &mut block_group_cache,
+                                                collection_name,
+                                                sample_name,
+                                                mate_name.to_string(),
+                                                coordinate_frame,
+                                            )
+                                            .expect(
+                                                "can't find the block group of a breakend's mate",
+                                            );
+                                            PathCache::lookup(
+                                                &mut path_cache,
+                                                mate_bg_id,
+                                                mate_name.to_string(),
+                                            )
+                                        });
 
                                         vcf_entries.push(VcfEntry {
                                             ids: allele_accession,
                                             block_group_id: sample_bg_id,
-                                            ref_start,
                                             path: sample_path.clone(),
+                                            mate_path,
                                             sample_name: sample_name.clone(),
-                                            alt_seq,
+                                            allele: alt_allele,
                                             chromosome_index: chromosome_index as i64,
                                             phased,
                                         });
@@ -411,36 +856,124 @@ pub fn update_with_vcf<'a>(
             }
         }
 
+        // the alt allele nodes record where they were imported from
+        let mut record_attributes = vec![
+            ("vcf_file", AttributeValue::from(vcf_file_name.as_str())),
+            (
+                "vcf_position",
+                AttributeValue::from(record.variant_start().unwrap().unwrap().get() as i64),
+            ),
+        ];
+        let record_ids = record.ids();
+        if !record_ids.is_empty() {
+            record_attributes.push((
+                "vcf_id",
+                AttributeValue::from(record_ids.iter().collect::<Vec<&str>>().join(";")),
+            ));
+        }
+        if let Some(quality_score) = record.quality_score() {
+            // going through the shortest decimal keeps the score as written, as widening the f32
+            // would give 2216.60009765625 for 2216.6
+            let quality_score: f64 = quality_score.unwrap().to_string().parse().unwrap();
+            record_attributes.push(("vcf_qual", AttributeValue::from(quality_score)));
+        }
+
         for vcf_entry in vcf_entries {
-            // * indicates this allele is removed by another deletion in the sample
-            if vcf_entry.alt_seq == "*" {
-                continue;
-            }
-            let ref_start = vcf_entry.ref_start;
-            let sequence =
-                SequenceCache::lookup(&mut sequence_cache, "DNA", vcf_entry.alt_seq.to_string());
+            let (ref_start, ref_end, alt_seq, strand) = match &vcf_entry.allele {
+                // * indicates this allele is removed by another deletion in the sample
+                AltAllele::Sequence { sequence, .. } if sequence == "*" => continue,
+                AltAllele::Sequence {
+                    start,
+                    end,
+                    sequence,
+                } => (*start, *end, sequence.clone(), Strand::Forward),
+                AltAllele::Copies { start, end, count } => (
+                    *start,
+                    *end,
+                    vcf_entry
+                        .path
+                        .sequence_range(conn, *start, *end)
+                        .repeat(*count),
+                    Strand::Forward,
+                ),
+                // the new node holds the bases as they are, and is joined on the reverse strand
+                AltAllele::Inversion { start, end } => (
+                    *start,
+                    *end,
+                    vcf_entry.path.sequence_range(conn, *start, *end),
+                    Strand::Reverse,
+                ),
+                AltAllele::Breakend(breakend) => {
+                    let mate_path = vcf_entry.mate_path.as_ref().unwrap();
+                    let here = node_coordinate(
+                        PathCache::get_intervaltree(&path_cache, &vcf_entry.path).unwrap(),
+                        breakend.position,
+                        breakend.leaves,
+                    );
+                    let mate = node_coordinate(
+                        PathCache::get_intervaltree(&path_cache, mate_path).unwrap(),
+                        breakend.mate_position,
+                        breakend.mate_after_base,
+                    );
+                    // joins past either end of a sequence have nothing to attach to
+                    let (Some((node_id, coordinate)), Some((mate_node_id, mate_coordinate))) =
+                        (here, mate)
+                    else {
+                        continue;
+                    };
+                    let edge_data = if breakend.leaves {
+                        EdgeData {
+                            source_node_id: node_id,
+                            source_coordinate: coordinate,
+                            source_strand: Strand::Forward,
+                            target_node_id: mate_node_id,
+                            target_coordinate: mate_coordinate,
+                            target_strand: breakend.mate_strand,
+                        }
+                    } else {
+                        EdgeData {
+                            source_node_id: mate_node_id,
+                            source_coordinate: mate_coordinate,
+                            source_strand: breakend.mate_strand,
+                            target_node_id: node_id,
+                            target_coordinate: coordinate,
+                            target_strand: Strand::Forward,
+                        }
+                    };
+                    breakend_edges
+                        .entry((vcf_entry.path, vcf_entry.sample_name))
+                        .or_default()
+                        .push(AugmentedEdgeData {
+                            edge_data,
+                            chromosome_index: vcf_entry.chromosome_index,
+                            phased: vcf_entry.phased,
+                        });
+                    continue;
+                }
+            };
+            let sequence = SequenceCache::lookup(&mut sequence_cache, "DNA", alt_seq);
             let sequence_string = sequence.get_sequence(None, None);
 
-            let parent_path_id : i64 = *parent_block_groups.entry((collection_name, vcf_entry.path.id)).or_insert_with(|| {
+            // nodes are hashed on the reference sample's path where there is one
+            let parent_path_key = parent_path_keys.entry((collection_name, vcf_entry.path.id)).or_insert_with(|| {
                 let parent_bg = BlockGroup::query(conn, "select * from block_groups where collection_name = ?1 AND sample_name is null and name = ?2", rusqlite::params!(SQLValue::from(collection_name.to_string()), SQLValue::from(vcf_entry.path.name.clone())));
                 if parent_bg.is_empty() {
-                    vcf_entry.path.id
+                    vcf_entry.path.content_key(conn)
                 } else {
                     let parent_path =
                         PathCache::lookup(&mut path_cache, parent_bg.first().unwrap().id, vcf_entry.path.name.clone());
-                    parent_path.id
+                    parent_path.content_key(conn)
                 }
             });
 
             let node_id = Node::create(
                 conn,
                 sequence.hash.as_str(),
-                calculate_hash(&format!(
-                    "{path_id}:{ref_start}-{ref_end}->{sequence_hash}",
-                    path_id = parent_path_id,
-                    sequence_hash = sequence.hash
-                )),
+                Node::path_hash(parent_path_key, ref_start, ref_end, &sequence.hash),
             );
+            for (key, value) in record_attributes.iter() {
+                Node::set_attribute(conn, node_id, key, value.clone());
+            }
             let change = prepare_change(
                 vcf_entry.block_group_id,
                 &vcf_entry.path,
@@ -452,6 +985,7 @@ pub fn update_with_vcf<'a>(
                 sequence_string.clone(),
                 sequence_string.len() as i64,
                 node_id,
+                strand,
             );
             changes
                 .entry((vcf_entry.path, vcf_entry.sample_name))
@@ -459,6 +993,7 @@ pub fn update_with_vcf<'a>(
                 .push(change);
         }
         bar.inc(1);
+        report_progress(progress, &bar)?;
     }
     bar.finish();
 
@@ -475,6 +1010,7 @@ pub fn update_with_vcf<'a>(
             coordinate_frame.is_some(),
         );
         bar.inc(path_changes.len() as u64);
+        report_progress(progress, &bar)?;
         summary
             .entry(sample_name)
             .or_default()
@@ -482,6 +1018,34 @@ pub fn update_with_vcf<'a>(
             .or_insert(path_changes.len() as i64);
     }
     bar.finish();
+    // breakends join existing nodes, so they are edges without a path change
+    for ((path, sample_name), edges) in breakend_edges {
+        let edge_ids = Edge::bulk_create(
+            conn,
+            &edges
+                .iter()
+                .map(|edge| edge.edge_data.clone())
+                .collect::<Vec<EdgeData>>(),
+        );
+        BlockGroupEdge::bulk_create(
+            conn,
+            &edge_ids
+                .iter()
+                .zip(edges.iter())
+                .map(|(edge_id, edge)| BlockGroupEdgeData {
+                    block_group_id: path.block_group_id,
+                    edge_id: *edge_id,
+                    chromosome_index: edge.chromosome_index,
+                    phased: edge.phased,
+                })
+                .collect::<Vec<BlockGroupEdgeData>>(),
+        );
+        *summary
+            .entry(sample_name)
+            .or_default()
+            .entry(path.name)
+            .or_default() += edges.len() as i64;
+    }
     for ((path, accession_name), (acc_start, acc_end)) in accession_cache.iter() {
         BlockGroup::add_accession(
             conn,
@@ -499,24 +1063,7 @@ pub fn update_with_vcf<'a>(
             summary_str.push_str(&format!(" {path_name}: {change_count} changes.\n"));
         }
     }
-
-    let bar = add_saving_operation_bar(&progress_bar);
-    bar.set_message("Saving operation");
-    let op = end_operation(
-        conn,
-        operation_conn,
-        &mut session,
-        OperationInfo {
-            file_path: vcf_path.to_string(),
-            file_type: FileTypes::VCF,
-            description: "vcf_addition".to_string(),
-        },
-        &summary_str,
-        None,
-    )
-    .map_err(VcfError::OperationError);
-    bar.finish();
-    op
+    Ok(summary_str)
 }
 
 #[cfg(test)]
@@ -597,6 +1144,25 @@ mod tests {
                 "ATCATCGATCGATCGATCGGGAACACACAGAGA".to_string(),
             ])
         );
+
+        // the nodes of alt alleles record the records they came from
+        let node_id: i64 = conn
+            .query_row(
+                "select node_id from node_attributes where key = 'vcf_position' and value = 10;",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let attributes = Node::attributes(conn, node_id);
+        assert_eq!(
+            attributes.get("vcf_file"),
+            Some(&AttributeValue::from("simple.vcf"))
+        );
+        assert_eq!(
+            attributes.get("vcf_qual"),
+            Some(&AttributeValue::Float(2216.6))
+        );
+        assert_eq!(attributes.get("vcf_id"), None);
     }
 
     #[test]
@@ -795,6 +1361,233 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parses_structural_variants() {
+        setup_gen_dir();
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/sv.vcf");
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/sv.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        let collection = "test".to_string();
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            &collection,
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        update_with_vcf(
+            &vcf_path.to_str().unwrap().to_string(),
+            &collection,
+            "".to_string(),
+            "".to_string(),
+            conn,
+            op_conn,
+            None,
+        )
+        .unwrap();
+
+        // foo is 1|0 for <DEL>, 0|1 for <INV>, 1|1 for a <DUP> given by SVLEN and 0|1 for a
+        // breakend into chr2
+        let block_group_id = BlockGroup::get_id(conn, &collection, Some("foo"), "chr1");
+        assert_eq!(
+            BlockGroup::haplotype_sequences(conn, block_group_id),
+            vec![
+                "ATCGATCGATCGATCGGGAAGGAACACACAGAGA".to_string(),
+                "ATCGATCGATATCGCGATCGGGAAGGAACACACACCCCAAAATTTT".to_string(),
+            ]
+        );
+        let chr2_block_group_id = BlockGroup::get_id(conn, &collection, Some("foo"), "chr2");
+        assert_eq!(
+            BlockGroup::haplotype_sequences(conn, chr2_block_group_id),
+            vec!["GGGGCCCCAAAATTTT".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parses_breakends() {
+        let cnv_re = Regex::new(r"(?x)<CN(?P<count>\d+)>").unwrap();
+        let breakend_re = Regex::new(
+            r"^(?P<before>[A-Za-z]*)(?P<open>[\[\]])(?P<mate_name>[^:\[\]]+):(?P<mate_position>\d+)(?P<close>[\[\]])(?P<after>[A-Za-z]*)$",
+        )
+        .unwrap();
+        // a breakend after or before the base at POS 10
+        let parse = |alt: &str| AltAllele::parse(alt, "G", 9, 10, None, &cnv_re, &breakend_re);
+        let breakend = |position, leaves, mate_position, mate_after_base, mate_strand| {
+            Some(AltAllele::Breakend(Breakend {
+                position,
+                leaves,
+                mate_name: "chr2".to_string(),
+                mate_position,
+                mate_after_base,
+                mate_strand,
+            }))
+        };
+
+        assert_eq!(
+            parse("G[chr2:5["),
+            breakend(10, true, 4, false, Strand::Forward)
+        );
+        assert_eq!(
+            parse("G]chr2:5]"),
+            breakend(10, true, 5, true, Strand::Reverse)
+        );
+        assert_eq!(
+            parse("]chr2:5]G"),
+            breakend(9, false, 5, true, Strand::Forward)
+        );
+        assert_eq!(
+            parse("[chr2:5[G"),
+            breakend(9, false, 4, false, Strand::Reverse)
+        );
+        // breakends inserting bases and symbolic alleles without an end can't be applied
+        assert_eq!(parse("GAC[chr2:5["), None);
+        assert_eq!(parse("G[chr2:5]"), None);
+        assert_eq!(parse("<DEL>"), None);
+        assert_eq!(parse("<INS>"), None);
+    }
+
+    #[test]
+    fn test_filters_records() {
+        setup_gen_dir();
+        let vcf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/filtered.vcf");
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let conn = &get_connection(None);
+        let db_uuid = metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &db_uuid);
+
+        let collection = "test".to_string();
+
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            &collection,
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+
+        // the reference with the alt alleles of the records at the given positions
+        let with_variants = |positions: &[usize]| {
+            let mut sequence = "ATCGATCGATCGATCGATCGGGAACACACAGAGA".to_string();
+            for (position, alt) in [
+                (2, "A"),
+                (6, "G"),
+                (10, "C"),
+                (14, "G"),
+                (18, "A"),
+                (22, "T"),
+            ] {
+                if positions.contains(&position) {
+                    sequence.replace_range(position - 1..position, alt);
+                }
+            }
+            vec![sequence]
+        };
+        let cases = [
+            (
+                "qual",
+                VcfFilter {
+                    min_qual: Some(30.0),
+                    ..Default::default()
+                },
+                vec![2, 10, 14, 18],
+            ),
+            (
+                "pass",
+                VcfFilter {
+                    pass_only: true,
+                    ..Default::default()
+                },
+                vec![2, 6, 14, 18],
+            ),
+            (
+                "expr",
+                VcfFilter {
+                    include_expr: Some("DP>=10 && TYPE==\"snp\"".parse().unwrap()),
+                    ..Default::default()
+                },
+                vec![2, 6, 10, 22],
+            ),
+            (
+                "flag",
+                VcfFilter {
+                    min_qual: Some(30.0),
+                    pass_only: true,
+                    include_expr: Some("SOMATIC".parse().unwrap()),
+                },
+                vec![18],
+            ),
+        ];
+        for (sample, filter, positions) in cases {
+            update_with_vcf_with_progress(
+                &vcf_path.to_str().unwrap().to_string(),
+                &collection,
+                "1".to_string(),
+                sample.to_string(),
+                conn,
+                op_conn,
+                None,
+                &filter,
+                &NoProgress,
+            )
+            .unwrap();
+            let block_group_id = BlockGroup::get_id(conn, &collection, Some(sample), "m123");
+            assert_eq!(
+                BlockGroup::haplotype_sequences(conn, block_group_id),
+                with_variants(&positions),
+                "{sample}"
+            );
+        }
+
+        // no graph is made for a sample whose records all fail the filter
+        update_with_vcf_with_progress(
+            &vcf_path.to_str().unwrap().to_string(),
+            &collection,
+            "1".to_string(),
+            "none".to_string(),
+            conn,
+            op_conn,
+            None,
+            &VcfFilter {
+                include_expr: Some("DP<4".parse().unwrap()),
+                ..Default::default()
+            },
+            &NoProgress,
+        )
+        .unwrap();
+        assert!(BlockGroup::query(
+            conn,
+            "select * from block_groups where sample_name = 'none';",
+            rusqlite::params!(),
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_parses_info_expressions() {
+        assert!("DP>=10 && TYPE==\"snp\" && SOMATIC"
+            .parse::<InfoExpression>()
+            .is_ok());
+        assert!("AF != 0.5".parse::<InfoExpression>().is_ok());
+        for expression in ["", "DP>=", ">=10", "DP>>10", "DP>=10 &&", "not a key"] {
+            assert_eq!(
+                expression.parse::<InfoExpression>(),
+                Err(VcfError::InvalidExpression(expression.to_string())),
+                "{expression}"
+            );
+        }
+    }
+
     #[test]
     fn test_deduplicates_nodes() {
         setup_gen_dir();
@@ -0,0 +1,467 @@
+use crate::models::annotation::Annotation;
+use crate::models::block_group::BlockGroup;
+use crate::models::path::{revcomp, Path};
+use crate::models::sample::Sample;
+use crate::organism::Organisms;
+use crate::range::Range;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid rules file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("{failed} of {total} rules failed")]
+    Failed { failed: usize, total: usize },
+}
+
+// What a rule checks, given as the `check` key of the rule.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum Check {
+    // The sample has an accession with this name.
+    HasAccession {
+        accession: String,
+    },
+    // The current path of every graph of the sample is within these lengths.
+    GraphLength {
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    // Each of the named annotations is still found on the sample, with a length that is a whole
+    // number of codons and no stop codon before its last codon. Stops are those of the organism
+    // given, or of the default organism.
+    IntactOrfs {
+        features: Vec<String>,
+        organism: Option<String>,
+    },
+}
+
+// An invariant of a repository, checked against the current state of a sample. Rules without a
+// sample check the reference.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Rule {
+    pub name: Option<String>,
+    pub sample: Option<String>,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Rules {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RuleResult {
+    pub description: String,
+    pub failures: Vec<String>,
+}
+
+impl RuleResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Rule {
+    pub fn description(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            let sample = Sample::display_name(self.sample.as_deref());
+            match &self.check {
+                Check::HasAccession { accession } => format!("{sample} has accession {accession}"),
+                Check::GraphLength { min, max } => format!(
+                    "{sample} graph lengths within {min}-{max}",
+                    min = min.map(|min| min.to_string()).unwrap_or_default(),
+                    max = max.map(|max| max.to_string()).unwrap_or_default()
+                ),
+                Check::IntactOrfs { features, organism } => {
+                    format!(
+                        "{sample} has intact {features}{organism}",
+                        features = features.join(", "),
+                        organism = organism
+                            .as_ref()
+                            .map(|organism| format!(" ({organism})"))
+                            .unwrap_or_default()
+                    )
+                }
+            }
+        })
+    }
+}
+
+pub fn read_rules(rules_path: &str) -> Result<Rules, ValidationError> {
+    Ok(toml::from_str(&fs::read_to_string(rules_path)?)?)
+}
+
+fn check_accession(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    accession: &str,
+) -> Vec<String> {
+    let count: i64 = conn
+        .query_row(
+            "select count(*) from accessions a join paths p on p.id = a.path_id join block_groups bg on bg.id = p.block_group_id where bg.collection_name = ?1 and bg.sample_name is ?2 and a.name = ?3;",
+            params![collection_name, sample_name, accession],
+            |row| row.get(0),
+        )
+        .unwrap();
+    if count == 0 {
+        vec![format!("accession {accession} was not found")]
+    } else {
+        vec![]
+    }
+}
+
+fn check_graph_lengths(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    min: Option<i64>,
+    max: Option<i64>,
+) -> Vec<String> {
+    let mut failures = vec![];
+    for block_group in Sample::get_block_groups(conn, collection_name, sample_name) {
+        let length = BlockGroup::get_current_path(conn, block_group.id).length(conn);
+        if min.is_some_and(|min| length < min) || max.is_some_and(|max| length > max) {
+            failures.push(format!("{} is {length} bp long", block_group.name));
+        }
+    }
+    failures
+}
+
+fn check_orfs(
+    conn: &Connection,
+    collection_name: &str,
+    sample_name: Option<&str>,
+    features: &[String],
+    organisms: &Organisms,
+    organism: Option<&str>,
+) -> Vec<String> {
+    let profile = match organisms.get(organism) {
+        Ok(profile) => profile,
+        Err(err) => return vec![err.to_string()],
+    };
+    let block_groups = Sample::get_block_groups(conn, collection_name, sample_name);
+    let annotations = Annotation::query_for_collection(conn, collection_name)
+        .into_iter()
+        .filter(|annotation| features.contains(&annotation.name))
+        .collect::<Vec<_>>();
+    let projected = Annotation::project_onto(conn, &annotations, &block_groups);
+    let paths_by_id = block_groups
+        .iter()
+        .map(|block_group| {
+            let path = BlockGroup::get_current_path(conn, block_group.id);
+            (path.id, path)
+        })
+        .collect::<HashMap<i64, Path>>();
+
+    let mut failures = vec![];
+    for feature in features {
+        let feature_annotations = projected
+            .iter()
+            .filter(|annotation| &annotation.name == feature)
+            .collect::<Vec<_>>();
+        if feature_annotations.is_empty() {
+            failures.push(format!("{feature} was not found"));
+        }
+        for annotation in feature_annotations {
+            let mut sequence = paths_by_id[&annotation.path_id]
+                .subsequence(
+                    conn,
+                    &Range {
+                        start: annotation.start,
+                        end: annotation.end,
+                    },
+                )
+                .to_uppercase();
+            if annotation.strand == "-" {
+                sequence = revcomp(&sequence);
+            }
+            if sequence.len() % 3 != 0 {
+                failures.push(format!(
+                    "{feature} is {length} bp long, not a whole number of codons",
+                    length = sequence.len()
+                ));
+                continue;
+            }
+            let protein = profile.translate(&sequence);
+            if let Some(index) = protein
+                .char_indices()
+                .take(protein.len().saturating_sub(1))
+                .find_map(|(index, amino_acid)| (amino_acid == '*').then_some(index))
+            {
+                failures.push(format!(
+                    "{feature} has a stop codon at codon {codon}",
+                    codon = index + 1
+                ));
+            }
+        }
+    }
+    failures
+}
+
+pub fn check_rule(
+    conn: &Connection,
+    collection_name: &str,
+    rule: &Rule,
+    organisms: &Organisms,
+) -> RuleResult {
+    let sample_name = rule.sample.as_deref();
+    let failures = match &rule.check {
+        Check::HasAccession { accession } => {
+            check_accession(conn, collection_name, sample_name, accession)
+        }
+        Check::GraphLength { min, max } => {
+            check_graph_lengths(conn, collection_name, sample_name, *min, *max)
+        }
+        Check::IntactOrfs { features, organism } => check_orfs(
+            conn,
+            collection_name,
+            sample_name,
+            features,
+            organisms,
+            organism.as_deref(),
+        ),
+    };
+    RuleResult {
+        description: rule.description(),
+        failures,
+    }
+}
+
+// Checks every rule of a rules file against the current state of a collection, in the order of the
+// file.
+pub fn validate(
+    conn: &Connection,
+    collection_name: &str,
+    rules_path: &str,
+    organisms: &Organisms,
+) -> Result<Vec<RuleResult>, ValidationError> {
+    Ok(read_rules(rules_path)?
+        .rules
+        .iter()
+        .map(|rule| check_rule(conn, collection_name, rule, organisms))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::gff::import_gff_annotations;
+    use crate::imports::fasta::import_fasta;
+    use crate::models::block_group::PathCache;
+    use crate::models::metadata;
+    use crate::models::operations::setup_db;
+    use crate::test_helpers::{
+        get_connection, get_operation_connection, get_sample_bg, setup_gen_dir,
+    };
+    use crate::updates::fasta::update_with_fasta;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_validates_rules() {
+        setup_gen_dir();
+        let fasta_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/simple.fa");
+        let insert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/aaaaaaaa.fa");
+        let conn = &get_connection(None);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, &metadata::get_db_uuid(conn));
+        import_fasta(
+            &fasta_path.to_str().unwrap().to_string(),
+            "test",
+            None,
+            false,
+            conn,
+            op_conn,
+        )
+        .unwrap();
+        let path = BlockGroup::get_current_path(conn, get_sample_bg(conn, "test", None).id);
+        let mut cache = PathCache::new(conn);
+        PathCache::lookup(&mut cache, path.block_group_id, path.name.clone());
+        BlockGroup::add_accession(conn, &path, "promoter", 2, 8, &mut cache);
+
+        // ATC GAT CGA TCG ATC GAT CGG GAA CAC ACA: 10 codons without a stop
+        let dir = tempdir().unwrap();
+        let gff_path = dir.path().join("orf.gff");
+        fs::write(
+            &gff_path,
+            "##gff-version 3\nm123\ttest\tCDS\t1\t30\t.\t+\t0\tID=orf1\n",
+        )
+        .unwrap();
+        import_gff_annotations(conn, op_conn, "test", None, gff_path.to_str().unwrap()).unwrap();
+        // a frameshift: GATCGGGAAC at 15 becomes AAAAAAAA
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "shifted",
+            "m123",
+            15,
+            25,
+            insert_path.to_str().unwrap(),
+        )
+        .unwrap();
+        // the second codon becomes a stop
+        let stop_path = dir.path().join("stop.fa");
+        fs::write(&stop_path, ">stop\nTAA\n").unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "stopped",
+            "m123",
+            3,
+            6,
+            stop_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        // TGA only stops translation in the standard code
+        let tga_path = dir.path().join("tga.fa");
+        fs::write(&tga_path, ">tga\nTGA\n").unwrap();
+        update_with_fasta(
+            conn,
+            op_conn,
+            "test",
+            None,
+            "opal",
+            "m123",
+            3,
+            6,
+            tga_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let rules_path = dir.path().join("rules.toml");
+        fs::write(
+            &rules_path,
+            r#"
+[[rule]]
+check = "has_accession"
+accession = "promoter"
+
+[[rule]]
+name = "shifted keeps the promoter"
+sample = "shifted"
+check = "has_accession"
+accession = "promoter"
+
+[[rule]]
+check = "has_accession"
+accession = "terminator"
+
+[[rule]]
+check = "graph_length"
+max = 33
+
+[[rule]]
+sample = "shifted"
+check = "graph_length"
+min = 10
+max = 33
+
+[[rule]]
+check = "intact_orfs"
+features = ["orf1"]
+
+[[rule]]
+sample = "shifted"
+check = "intact_orfs"
+features = ["orf1", "orf2"]
+
+[[rule]]
+sample = "stopped"
+check = "intact_orfs"
+features = ["orf1"]
+
+[[rule]]
+sample = "opal"
+check = "intact_orfs"
+features = ["orf1"]
+
+[[rule]]
+sample = "opal"
+check = "intact_orfs"
+features = ["orf1"]
+organism = "yeast_mitochondrial"
+
+[[rule]]
+check = "intact_orfs"
+features = ["orf1"]
+organism = "unknown"
+"#,
+        )
+        .unwrap();
+
+        let results = validate(
+            conn,
+            "test",
+            rules_path.to_str().unwrap(),
+            &Organisms::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.description.as_str(), result.failures.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("(reference) has accession promoter", vec![]),
+                ("shifted keeps the promoter", vec![]),
+                (
+                    "(reference) has accession terminator",
+                    vec!["accession terminator was not found".to_string()]
+                ),
+                (
+                    "(reference) graph lengths within -33",
+                    vec!["m123 is 34 bp long".to_string()]
+                ),
+                ("shifted graph lengths within 10-33", vec![]),
+                ("(reference) has intact orf1", vec![]),
+                (
+                    "shifted has intact orf1, orf2",
+                    vec![
+                        "orf1 is 28 bp long, not a whole number of codons".to_string(),
+                        "orf2 was not found".to_string()
+                    ]
+                ),
+                (
+                    "stopped has intact orf1",
+                    vec!["orf1 has a stop codon at codon 2".to_string()]
+                ),
+                (
+                    "opal has intact orf1",
+                    vec!["orf1 has a stop codon at codon 2".to_string()]
+                ),
+                ("opal has intact orf1 (yeast_mitochondrial)", vec![]),
+                (
+                    "(reference) has intact orf1 (unknown)",
+                    vec!["Unknown organism: unknown".to_string()]
+                ),
+            ]
+        );
+
+        fs::write(&rules_path, "[[rule]]\ncheck = \"unknown\"\n").unwrap();
+        assert!(matches!(
+            validate(
+                conn,
+                "test",
+                rules_path.to_str().unwrap(),
+                &Organisms::default()
+            ),
+            Err(ValidationError::Parse(_))
+        ));
+    }
+}
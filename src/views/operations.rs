@@ -0,0 +1,156 @@
+use crate::models::operations::{Branch, Operation, OperationState};
+use crate::models::traits::*;
+use itertools::Itertools;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+
+// Renders the operations of a database as a graph in the style of `git log --graph`, newest first.
+// Each operation has a single parent, so branches show up as forks in the graph. Branch names are
+// shown on the newest operation of each branch, and HEAD marks the checked out operation.
+pub fn render_operation_graph(conn: &Connection, db_uuid: &str) -> Vec<String> {
+    let operations = Operation::query(
+        conn,
+        "select * from operation where db_uuid = ?1 order by rowid",
+        params![Value::from(db_uuid.to_string())],
+    );
+    let current_operation = OperationState::get_operation(conn, db_uuid);
+    let current_branch_id = OperationState::get_current_branch(conn, db_uuid);
+
+    let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+    for branch in Branch::query(
+        conn,
+        "select * from branch where db_uuid = ?1 order by id",
+        vec![Value::from(db_uuid.to_string())],
+    ) {
+        if let Some(tip) = Branch::get_operations(conn, branch.id).last() {
+            let label = if Some(branch.id) == current_branch_id
+                && current_operation.as_ref() == Some(&tip.hash)
+            {
+                format!("HEAD -> {}", branch.name)
+            } else {
+                branch.name.clone()
+            };
+            labels.entry(tip.hash.clone()).or_default().push(label);
+        }
+    }
+    if let Some(hash) = &current_operation {
+        let entry = labels.entry(hash.clone()).or_default();
+        if !entry.iter().any(|label| label.starts_with("HEAD")) {
+            entry.insert(0, "HEAD".to_string());
+        }
+    }
+
+    // Emitting a reversed pre-order keeps every fork contiguous, so the operations of a branch are
+    // drawn together and forks join back up next to the column they split from.
+    let mut children: HashMap<Option<String>, Vec<&Operation>> = HashMap::new();
+    for operation in operations.iter() {
+        children
+            .entry(operation.parent_hash.clone())
+            .or_default()
+            .push(operation);
+    }
+    let mut ordered = vec![];
+    let mut stack = children
+        .get(&None)
+        .map(|roots| roots.iter().rev().copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+    while let Some(operation) = stack.pop() {
+        ordered.push(operation);
+        if let Some(operation_children) = children.get(&Some(operation.hash.clone())) {
+            stack.extend(operation_children.iter().rev());
+        }
+    }
+    ordered.reverse();
+
+    let mut lines = vec![];
+    // each column holds the hash of the operation its line is waiting to reach
+    let mut columns: Vec<String> = vec![];
+    for operation in ordered {
+        let column = match columns.iter().position(|hash| *hash == operation.hash) {
+            Some(column) => column,
+            None => {
+                columns.push(operation.hash.clone());
+                columns.len() - 1
+            }
+        };
+        while columns.len() > column + 1 && columns[columns.len() - 1] == operation.hash {
+            lines.push(format!("{}|/", "| ".repeat(columns.len() - 2)));
+            columns.pop();
+        }
+
+        let graph = (0..columns.len())
+            .map(|index| if index == column { "*" } else { "|" })
+            .join(" ");
+        let decoration = labels
+            .get(&operation.hash)
+            .map(|labels| format!(" ({})", labels.join(", ")))
+            .unwrap_or_default();
+        let message = operation
+            .message
+            .as_ref()
+            .map(|message| format!(": {message}"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{graph} {hash}{decoration} {change_type}{message}",
+            hash = &operation.hash[..operation.hash.len().min(12)],
+            change_type = operation.change_type,
+        ));
+
+        match &operation.parent_hash {
+            Some(parent_hash) => columns[column] = parent_hash.clone(),
+            None => {
+                columns.remove(column);
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::file_types::FileTypes;
+    use crate::models::metadata;
+    use crate::models::operations::{setup_db, FileAddition};
+    use crate::test_helpers::{get_connection, get_operation_connection, setup_gen_dir};
+
+    #[test]
+    fn test_renders_branches() {
+        setup_gen_dir();
+        let conn = &get_connection(None);
+        let db_uuid = &metadata::get_db_uuid(conn);
+        let op_conn = &get_operation_connection(None);
+        setup_db(op_conn, db_uuid);
+        let change = FileAddition::create(op_conn, "foo", FileTypes::Fasta);
+
+        // main: 1 -> 2 -> 3, branch-1 forks from 2 with 4 -> 5, branch-2 forks from 4 with 6
+        Operation::create(op_conn, db_uuid, "fasta_addition", change.id, "op-1").unwrap();
+        Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-2").unwrap();
+        Branch::create(op_conn, db_uuid, "branch-1");
+        Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-3").unwrap();
+        OperationState::set_branch(op_conn, db_uuid, "branch-1");
+        OperationState::set_operation(op_conn, db_uuid, "op-2");
+        Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-4").unwrap();
+        Branch::create(op_conn, db_uuid, "branch-2");
+        Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-5").unwrap();
+        OperationState::set_branch(op_conn, db_uuid, "branch-2");
+        OperationState::set_operation(op_conn, db_uuid, "op-4");
+        Operation::create(op_conn, db_uuid, "vcf_addition", change.id, "op-6").unwrap();
+        Operation::set_message(op_conn, "op-6", "latest").unwrap();
+
+        assert_eq!(
+            render_operation_graph(op_conn, db_uuid),
+            vec![
+                "* op-6 (HEAD -> branch-2) vcf_addition: latest",
+                "| * op-5 (branch-1) vcf_addition",
+                "|/",
+                "* op-4 vcf_addition",
+                "| * op-3 (main) vcf_addition",
+                "|/",
+                "* op-2 vcf_addition",
+                "* op-1 fasta_addition",
+            ]
+        );
+    }
+}
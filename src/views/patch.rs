@@ -3,7 +3,7 @@ use crate::models::edge::Edge;
 use crate::models::node::Node;
 use crate::models::sequence::Sequence;
 use crate::operation_management::{
-    load_changeset, load_changeset_dependencies, load_changeset_models,
+    load_changeset_dependencies, load_changeset_models, open_changeset,
 };
 use crate::patch::OperationPatch;
 use html_escape;
@@ -27,10 +27,10 @@ pub fn view_patches(patches: &[OperationPatch]) -> HashMap<String, HashMap<i64,
         let mut bg_dots: HashMap<i64, String> = HashMap::new();
 
         let op_info = &patch.operation;
-        let changeset = load_changeset(op_info);
+        let mut changeset = open_changeset(op_info);
         let dependencies = load_changeset_dependencies(op_info);
 
-        let input: &mut dyn Read = &mut changeset.as_slice();
+        let input: &mut dyn Read = &mut *changeset;
         let mut iter = ChangesetIter::start_strm(&input).unwrap();
 
         let new_models = load_changeset_models(&mut iter);